@@ -0,0 +1,485 @@
+//! Audit events for SIEM ingestion
+//!
+//! Every rotation attempt (and flagging a secret) produces an [`AuditEvent`]
+//! that is sent to the sinks configured under `[[audit.syslog]]`, either as an
+//! RFC 5424 syslog message with structured data or as a CEF record carried in
+//! a syslog message. Delivery failures are logged and never fail the rotation.
+//!
+//! Events are delivered from a dedicated thread so that slow or unreachable
+//! receivers never block the async runtime; dropping the [`AuditLog`] waits
+//! for queued events to be sent.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::warn;
+
+/// Structured data ID for RFC 5424 messages (uses the documentation enterprise number)
+const SD_ID: &str = "asr@32473";
+
+/// How long to wait when connecting to a TCP syslog receiver
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+        }
+    }
+}
+
+/// Something that happened to a secret
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    /// What was done (e.g., "rotate", "flag")
+    pub action: String,
+    pub path: String,
+    pub backend: String,
+    pub outcome: Outcome,
+    /// Target user whose password was updated, if any
+    pub target_username: Option<String>,
+    /// Error message for failures
+    pub detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(action: &str, path: &str, backend: &str, outcome: Outcome) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            action: action.to_string(),
+            path: path.to_string(),
+            backend: backend.to_string(),
+            outcome,
+            target_username: None,
+            detail: None,
+        }
+    }
+
+    pub fn with_target_username(mut self, username: Option<&str>) -> Self {
+        self.target_username = username.map(str::to_string);
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Human-readable summary used as the syslog message
+    fn message(&self) -> String {
+        let mut message = format!("{} {} {}", self.action, self.path, self.outcome.as_str());
+        if let Some(ref detail) = self.detail {
+            message.push_str(": ");
+            message.push_str(detail);
+        }
+        message
+    }
+
+    /// Syslog severity: informational for successes, warning for failures
+    fn severity(&self) -> u8 {
+        match self.outcome {
+            Outcome::Success => 6,
+            Outcome::Failure => 4,
+        }
+    }
+}
+
+/// Audit sink configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Syslog receivers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub syslog: Vec<SyslogSinkConfig>,
+}
+
+impl AuditConfig {
+    pub fn is_empty(&self) -> bool {
+        self.syslog.is_empty()
+    }
+}
+
+/// Message format for a syslog sink
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditFormat {
+    /// RFC 5424 with the event fields as structured data
+    #[default]
+    Rfc5424,
+    /// ArcSight Common Event Format inside an RFC 5424 message
+    Cef,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyslogSinkConfig {
+    /// Receiver address: udp://host:port, tcp://host:port, or unix:///dev/log
+    pub address: String,
+
+    /// rfc5424 (default) or cef
+    #[serde(default)]
+    pub format: AuditFormat,
+
+    /// Syslog facility name (default: authpriv)
+    #[serde(default = "default_facility")]
+    pub facility: String,
+
+    /// APP-NAME field of the syslog header
+    #[serde(default = "default_app_name")]
+    pub app_name: String,
+}
+
+fn default_facility() -> String {
+    "authpriv".to_string()
+}
+
+fn default_app_name() -> String {
+    "asr".to_string()
+}
+
+/// Numeric code of a syslog facility name
+fn facility_code(name: &str) -> Result<u8> {
+    let code = match name.to_lowercase().as_str() {
+        "kern" => 0,
+        "user" => 1,
+        "daemon" => 3,
+        "auth" => 4,
+        "syslog" => 5,
+        "authpriv" => 10,
+        "local0" => 16,
+        "local1" => 17,
+        "local2" => 18,
+        "local3" => 19,
+        "local4" => 20,
+        "local5" => 21,
+        "local6" => 22,
+        "local7" => 23,
+        other => anyhow::bail!("Unknown syslog facility: {}", other),
+    };
+    Ok(code)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Transport {
+    Udp(String),
+    Tcp(String),
+    Unix(String),
+}
+
+impl Transport {
+    fn parse(address: &str) -> Result<Self> {
+        if let Some(addr) = address.strip_prefix("udp://") {
+            Ok(Transport::Udp(addr.to_string()))
+        } else if let Some(addr) = address.strip_prefix("tcp://") {
+            Ok(Transport::Tcp(addr.to_string()))
+        } else if let Some(path) = address.strip_prefix("unix://") {
+            Ok(Transport::Unix(path.to_string()))
+        } else {
+            anyhow::bail!(
+                "Invalid syslog address '{}' (expected udp://, tcp://, or unix://)",
+                address
+            )
+        }
+    }
+
+    fn send(&self, message: &str) -> Result<()> {
+        match self {
+            Transport::Udp(addr) => {
+                let socket =
+                    UdpSocket::bind("0.0.0.0:0").context("Failed to open UDP socket for syslog")?;
+                socket
+                    .send_to(message.as_bytes(), addr.as_str())
+                    .with_context(|| format!("Failed to send syslog message to {}", addr))?;
+            }
+            Transport::Tcp(addr) => {
+                let socket_addr = addr
+                    .to_socket_addrs()
+                    .with_context(|| format!("Failed to resolve syslog receiver {}", addr))?
+                    .next()
+                    .with_context(|| format!("No address found for syslog receiver {}", addr))?;
+                let mut stream = TcpStream::connect_timeout(&socket_addr, CONNECT_TIMEOUT)
+                    .with_context(|| format!("Failed to connect to syslog receiver {}", addr))?;
+                // Octet-counting framing (RFC 6587)
+                write!(stream, "{} {}", message.len(), message)
+                    .with_context(|| format!("Failed to send syslog message to {}", addr))?;
+            }
+            #[cfg(unix)]
+            Transport::Unix(path) => {
+                let socket = std::os::unix::net::UnixDatagram::unbound()
+                    .context("Failed to open Unix socket for syslog")?;
+                socket
+                    .send_to(message.as_bytes(), path)
+                    .with_context(|| format!("Failed to send syslog message to {}", path))?;
+            }
+            #[cfg(not(unix))]
+            Transport::Unix(_) => {
+                anyhow::bail!("Unix syslog sockets are not supported on this platform")
+            }
+        }
+        Ok(())
+    }
+}
+
+struct SyslogSink {
+    transport: Transport,
+    format: AuditFormat,
+    facility: u8,
+    app_name: String,
+    hostname: String,
+}
+
+impl SyslogSink {
+    fn from_config(config: &SyslogSinkConfig, hostname: &str) -> Result<Self> {
+        Ok(Self {
+            transport: Transport::parse(&config.address)?,
+            format: config.format,
+            facility: facility_code(&config.facility)?,
+            app_name: config.app_name.clone(),
+            hostname: hostname.to_string(),
+        })
+    }
+
+    fn format(&self, event: &AuditEvent) -> String {
+        let (structured_data, message) = match self.format {
+            AuditFormat::Rfc5424 => (structured_data(event), event.message()),
+            AuditFormat::Cef => ("-".to_string(), format_cef(event)),
+        };
+        format!(
+            "<{}>1 {} {} {} {} {} {} {}",
+            self.facility * 8 + event.severity(),
+            event.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.hostname,
+            self.app_name,
+            std::process::id(),
+            event.action,
+            structured_data,
+            message
+        )
+    }
+}
+
+/// RFC 5424 structured data element holding the event fields
+fn structured_data(event: &AuditEvent) -> String {
+    let mut params = vec![
+        ("action", event.action.as_str()),
+        ("path", event.path.as_str()),
+        ("backend", event.backend.as_str()),
+        ("outcome", event.outcome.as_str()),
+    ];
+    if let Some(ref username) = event.target_username {
+        params.push(("target_username", username));
+    }
+
+    let mut element = format!("[{}", SD_ID);
+    for (name, value) in params {
+        let escaped = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace(']', "\\]");
+        element.push_str(&format!(" {}=\"{}\"", name, escaped));
+    }
+    element.push(']');
+    element
+}
+
+/// Format an event as a CEF record
+fn format_cef(event: &AuditEvent) -> String {
+    fn header(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('|', "\\|")
+    }
+    fn extension(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace('=', "\\=")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")
+    }
+
+    let (severity, name) = match event.outcome {
+        Outcome::Success => (3, format!("Secret {} succeeded", event.action)),
+        Outcome::Failure => (7, format!("Secret {} failed", event.action)),
+    };
+
+    let mut fields = vec![
+        ("rt", event.timestamp.timestamp_millis().to_string()),
+        ("act", event.action.clone()),
+        ("outcome", event.outcome.as_str().to_string()),
+        ("filePath", event.path.clone()),
+        ("cs1Label", "backend".to_string()),
+        ("cs1", event.backend.clone()),
+    ];
+    if let Some(ref username) = event.target_username {
+        fields.push(("duser", username.clone()));
+    }
+    if let Some(ref detail) = event.detail {
+        fields.push(("msg", detail.clone()));
+    }
+
+    format!(
+        "CEF:0|Automatic-Secret-Rotation|asr|{}|{}|{}|{}|{}",
+        header(env!("CARGO_PKG_VERSION")),
+        header(&format!("{}-{}", event.action, event.outcome.as_str())),
+        header(&name),
+        severity,
+        fields
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, extension(value)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    )
+}
+
+/// Name of this host for the syslog header
+pub(crate) fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty() && !name.contains(char::is_whitespace))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Sends audit events to the configured sinks
+#[derive(Default)]
+pub struct AuditLog {
+    /// Queue of the delivery thread, when any sink is configured
+    worker: Option<(mpsc::Sender<AuditEvent>, JoinHandle<()>)>,
+}
+
+impl AuditLog {
+    pub fn from_config(config: &AuditConfig) -> Result<Self> {
+        if config.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let hostname = hostname();
+        let sinks: Vec<SyslogSink> = config
+            .syslog
+            .iter()
+            .map(|sink| {
+                SyslogSink::from_config(sink, &hostname)
+                    .with_context(|| format!("Invalid audit sink {}", sink.address))
+            })
+            .collect::<Result<_>>()?;
+
+        let (sender, events) = mpsc::channel::<AuditEvent>();
+        let thread = std::thread::Builder::new()
+            .name("audit".to_string())
+            .spawn(move || {
+                for event in events {
+                    for sink in &sinks {
+                        if let Err(e) = sink.transport.send(&sink.format(&event)) {
+                            warn!("Failed to deliver audit event: {:#}", e);
+                        }
+                    }
+                }
+            })
+            .context("Failed to start audit delivery thread")?;
+        Ok(Self {
+            worker: Some((sender, thread)),
+        })
+    }
+
+    /// Queue an event for every sink; delivery failures are logged
+    pub fn record(&self, event: &AuditEvent) {
+        if let Some((sender, _)) = &self.worker {
+            // Only fails once the delivery thread is gone
+            let _ = sender.send(event.clone());
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        if let Some((sender, thread)) = self.worker.take() {
+            drop(sender);
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> AuditEvent {
+        let mut event = AuditEvent::new("rotate", "app/d\"b]", "vault", Outcome::Failure)
+            .with_target_username(Some("app"))
+            .with_detail("permission=denied | retry");
+        event.timestamp = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        event
+    }
+
+    #[test]
+    fn test_rfc5424_format() {
+        let sink = SyslogSink::from_config(
+            &SyslogSinkConfig {
+                address: "udp://127.0.0.1:514".to_string(),
+                format: AuditFormat::Rfc5424,
+                facility: "local4".to_string(),
+                app_name: "asr".to_string(),
+            },
+            "host1",
+        )
+        .unwrap();
+
+        let message = sink.format(&event());
+        // local4 (20) * 8 + warning (4)
+        assert!(message.starts_with("<164>1 2024-05-01T12:00:00.000Z host1 asr "));
+        assert!(message.contains(
+            "rotate [asr@32473 action=\"rotate\" path=\"app/d\\\"b\\]\" backend=\"vault\" outcome=\"failure\" target_username=\"app\"] rotate app/d\"b] failure: permission=denied | retry"
+        ));
+    }
+
+    #[test]
+    fn test_cef_format() {
+        assert_eq!(
+            format_cef(&event()),
+            format!(
+                "CEF:0|Automatic-Secret-Rotation|asr|{}|rotate-failure|Secret rotate failed|7|rt=1714564800000 act=rotate outcome=failure filePath=app/d\"b] cs1Label=backend cs1=vault duser=app msg=permission\\=denied | retry",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn test_udp_delivery() -> Result<()> {
+        let receiver = UdpSocket::bind("127.0.0.1:0")?;
+        receiver.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let log = AuditLog::from_config(&AuditConfig {
+            syslog: vec![SyslogSinkConfig {
+                address: format!("udp://{}", receiver.local_addr()?),
+                format: AuditFormat::Cef,
+                facility: default_facility(),
+                app_name: default_app_name(),
+            }],
+        })?;
+
+        log.record(&AuditEvent::new("flag", "app/db", "file", Outcome::Success));
+        let mut buffer = [0; 2048];
+        let len = receiver.recv(&mut buffer)?;
+        let message = String::from_utf8_lossy(&buffer[..len]);
+        assert!(message.starts_with("<86>1 "));
+        assert!(message.contains("CEF:0|Automatic-Secret-Rotation|asr|"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_sink() {
+        assert!(Transport::parse("http://example.com").is_err());
+        assert!(facility_code("bogus").is_err());
+    }
+}
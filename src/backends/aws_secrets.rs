@@ -1,17 +1,63 @@
 use anyhow::{Context, Result};
 use aws_config::Region;
-use aws_sdk_secretsmanager::types::Tag;
+use aws_sdk_secretsmanager::primitives::Blob;
+use aws_sdk_secretsmanager::types::{
+    Filter, FilterNameStringType, ReplicationStatusType, StatusType, Tag,
+};
 use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use std::collections::HashMap;
-use tracing::{debug, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
 use super::secret_backend::{SecretBackend, SecretData};
+use super::SecretPage;
+
+/// Staging label of the value currently served to readers
+const STAGE_CURRENT: &str = "AWSCURRENT";
+/// Staging label of a rotated value that has not been verified yet
+const STAGE_PENDING: &str = "AWSPENDING";
+/// Staging label AWS moves to the replaced value, kept for rollback
+const STAGE_PREVIOUS: &str = "AWSPREVIOUS";
+
+/// Most values AWS accepts in one `ListSecrets` filter, and so the most
+/// secrets whose metadata is read per call
+const METADATA_BATCH_SIZE: usize = 10;
+
+/// Default key for secrets that are not stored as a JSON object
+const DEFAULT_VALUE_KEY: &str = "value";
+
+/// How often replication status is polled while waiting for replicas
+const REPLICATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How a secret's value is stored in AWS Secrets Manager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SecretFormat {
+    /// `SecretString` holding a JSON object of key/value pairs
+    Json,
+    /// `SecretString` holding a single plain value
+    Plain,
+    /// `SecretBinary` holding a single UTF-8 value
+    Binary,
+    /// `SecretBinary` holding raw bytes, exposed base64-encoded
+    Base64Binary,
+}
+
+/// A secret value ready to be sent to AWS Secrets Manager
+enum SecretPayload {
+    String(String),
+    Binary(Blob),
+}
 
 /// AWS Secrets Manager client
 pub struct AwsSecretsClient {
     client: SecretsManagerClient,
     #[allow(dead_code)] // Kept for potential future use (logging, debugging)
     region: String,
+    value_key: String,
+    /// How long writes wait for replica regions to be in sync
+    replication_wait: Option<Duration>,
 }
 
 impl AwsSecretsClient {
@@ -23,18 +69,37 @@ impl AwsSecretsClient {
 
         // Load AWS config from environment and explicitly set the region
         // Using defaults() with region override to ensure the provided region is used
-        let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
-            .region(Region::new(region_str.clone()))
-            .load()
-            .await;
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(Region::new(region_str.clone()));
+        if let Some(http_client) = crate::http::aws_http_client()? {
+            loader = loader.http_client(http_client);
+        }
+        let config = loader.load().await;
         let client = SecretsManagerClient::new(&config);
 
         Ok(Self {
             client,
             region: region_str.clone(),
+            value_key: DEFAULT_VALUE_KEY.to_string(),
+            replication_wait: None,
         })
     }
 
+    /// Set the key under which plain-string and binary secrets are exposed
+    pub fn with_value_key(mut self, value_key: impl Into<String>) -> Self {
+        self.value_key = value_key.into();
+        self
+    }
+
+    /// Wait up to `timeout` after each write for replica regions to be in sync
+    ///
+    /// Without a wait, replica state is only reported; a failed replica is an
+    /// error either way.
+    pub fn with_replication_wait(mut self, timeout: Duration) -> Self {
+        self.replication_wait = Some(timeout).filter(|t| !t.is_zero());
+        self
+    }
+
     /// Convert AWS tags to metadata HashMap
     fn tags_to_metadata(&self, tags: &[Tag]) -> HashMap<String, String> {
         tags.iter()
@@ -52,13 +117,104 @@ impl AwsSecretsClient {
             .map(|(k, v)| Tag::builder().key(k).value(v).build())
             .collect()
     }
-}
 
-#[async_trait::async_trait]
-impl SecretBackend for AwsSecretsClient {
-    async fn read_secret(&self, path: &str) -> Result<SecretData> {
-        debug!("Reading secret from AWS Secrets Manager: {}", path);
+    /// Tags of the secrets in `names`, from `ListSecrets` filtered by name
+    ///
+    /// The name filter matches prefixes, so other secrets may be listed as
+    /// well; only exact matches are kept. Secrets that are not listed (such as
+    /// ones scheduled for deletion) are missing from the result.
+    async fn list_tags(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, HashMap<String, String>>> {
+        let filter = Filter::builder()
+            .key(FilterNameStringType::Name)
+            .set_values(Some(names.to_vec()))
+            .build();
+
+        let mut found = HashMap::new();
+        let mut next_token: Option<String> = None;
+        loop {
+            let response = self
+                .client
+                .list_secrets()
+                .filters(filter.clone())
+                .set_next_token(next_token)
+                .send()
+                .await
+                .context("Failed to list secrets from AWS Secrets Manager")?;
+
+            for secret in response.secret_list() {
+                if let Some(name) = secret.name().filter(|name| names.iter().any(|n| n == name)) {
+                    found.insert(name.to_string(), self.tags_to_metadata(secret.tags()));
+                }
+            }
+
+            next_token = response.next_token().map(|s| s.to_string());
+            if next_token.is_none() || found.len() == names.len() {
+                break;
+            }
+        }
+        Ok(found)
+    }
 
+    /// Check whether a secret exists
+    async fn secret_exists(&self, path: &str) -> bool {
+        self.client
+            .describe_secret()
+            .secret_id(path)
+            .send()
+            .await
+            .is_ok()
+    }
+
+    /// Map of version IDs to their staging labels
+    async fn version_stages(&self, path: &str) -> Result<HashMap<String, Vec<String>>> {
+        let response = self
+            .client
+            .describe_secret()
+            .secret_id(path)
+            .send()
+            .await
+            .with_context(|| format!("Failed to describe secret '{}'", path))?;
+
+        Ok(response
+            .version_ids_to_stages()
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// Store a new secret value as a new version with the given staging label
+    async fn put_secret_value(
+        &self,
+        path: &str,
+        payload: SecretPayload,
+        stage: &str,
+    ) -> Result<()> {
+        let request = self
+            .client
+            .put_secret_value()
+            .secret_id(path)
+            .version_stages(stage);
+        let request = match payload {
+            SecretPayload::String(value) => request.secret_string(value),
+            SecretPayload::Binary(value) => request.secret_binary(value),
+        };
+
+        request.send().await.with_context(|| {
+            format!(
+                "Failed to store {} value of secret '{}' in AWS Secrets Manager",
+                stage, path
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Fetch the current value of a secret and decode it into key/value pairs
+    async fn get_secret_value(
+        &self,
+        path: &str,
+    ) -> Result<(HashMap<String, String>, SecretFormat)> {
         let response = self
             .client
             .get_secret_value()
@@ -69,13 +225,231 @@ impl SecretBackend for AwsSecretsClient {
                 format!("Failed to read secret '{}' from AWS Secrets Manager", path)
             })?;
 
-        // Parse the secret string as JSON
-        let secret_string = response
-            .secret_string()
-            .ok_or_else(|| anyhow::anyhow!("Secret '{}' has no string value", path))?;
+        decode_secret(
+            path,
+            response.secret_string(),
+            response.secret_binary().map(|b| b.as_ref()),
+            &self.value_key,
+        )
+    }
+
+    /// Encode secret data the way the existing secret is stored
+    ///
+    /// Data consisting of only the value key is written back as a plain string
+    /// or binary if that is what the secret currently holds; everything else is
+    /// stored as a JSON object.
+    async fn encode_secret(
+        &self,
+        path: &str,
+        data: &HashMap<String, String>,
+    ) -> Result<SecretPayload> {
+        let format = match data.get(&self.value_key) {
+            Some(_) if data.len() == 1 => self
+                .get_secret_value(path)
+                .await
+                .map(|(_, format)| format)
+                .unwrap_or(SecretFormat::Json),
+            _ => SecretFormat::Json,
+        };
+
+        encode_secret(data, format, &self.value_key)
+    }
 
-        let data: HashMap<String, String> = serde_json::from_str(secret_string)
-            .with_context(|| format!("Failed to parse secret '{}' as JSON", path))?;
+    /// Report the state of the secret's replica regions after a write
+    ///
+    /// Fails if a replica failed, or if a wait is configured and a replica is
+    /// still not in sync when it runs out, so a rotation never finishes with
+    /// a disaster-recovery copy silently holding the old value.
+    async fn check_replication(&self, path: &str) -> Result<()> {
+        let deadline = self.replication_wait.map(|wait| Instant::now() + wait);
+        loop {
+            let response = self
+                .client
+                .describe_secret()
+                .secret_id(path)
+                .send()
+                .await
+                .with_context(|| format!("Failed to read replication status of '{}'", path))?;
+            let replicas = response.replication_status();
+            if replicas.is_empty() {
+                return Ok(());
+            }
+
+            let in_sync = replicas_in_sync(path, replicas)?;
+            let waiting = deadline.is_some_and(|deadline| Instant::now() < deadline);
+            if in_sync || !waiting {
+                for replica in replicas {
+                    info!(
+                        "Replica of secret '{}' in {}: {}",
+                        path,
+                        replica.region().unwrap_or("unknown region"),
+                        replica_status(replica)
+                    );
+                }
+                if in_sync {
+                    return Ok(());
+                }
+                if let Some(wait) = self.replication_wait {
+                    anyhow::bail!(
+                        "Replicas of secret '{}' not in sync after {}s: {}",
+                        path,
+                        wait.as_secs(),
+                        lagging_replicas(replicas)
+                    );
+                }
+                warn!(
+                    "Replicas of secret '{}' are still being updated: {}",
+                    path,
+                    lagging_replicas(replicas)
+                );
+                return Ok(());
+            }
+
+            debug!("Waiting for replicas of secret '{}'", path);
+            tokio::time::sleep(REPLICATION_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Remove the AWSPENDING label from a version
+    async fn clear_pending(&self, path: &str, version_id: &str) -> Result<()> {
+        self.client
+            .update_secret_version_stage()
+            .secret_id(path)
+            .version_stage(STAGE_PENDING)
+            .remove_from_version_id(version_id)
+            .send()
+            .await
+            .with_context(|| format!("Failed to clear {} on secret '{}'", STAGE_PENDING, path))?;
+        Ok(())
+    }
+}
+
+/// Turn a stored secret into key/value pairs
+///
+/// JSON objects are used as-is; plain strings and binary values are exposed
+/// under `value_key`.
+fn decode_secret(
+    path: &str,
+    secret_string: Option<&str>,
+    secret_binary: Option<&[u8]>,
+    value_key: &str,
+) -> Result<(HashMap<String, String>, SecretFormat)> {
+    if let Some(secret_string) = secret_string {
+        if let Ok(data) = serde_json::from_str::<HashMap<String, String>>(secret_string) {
+            return Ok((data, SecretFormat::Json));
+        }
+        // Anything that looks like JSON but does not parse is most likely a
+        // nested object, which would be destroyed by rotating it as plain text
+        if secret_string.trim_start().starts_with('{') {
+            anyhow::bail!(
+                "Failed to parse secret '{}' as JSON: only flat string values are supported",
+                path
+            );
+        }
+        let data = HashMap::from([(value_key.to_string(), secret_string.to_string())]);
+        return Ok((data, SecretFormat::Plain));
+    }
+
+    if let Some(secret_binary) = secret_binary {
+        let (value, format) = match std::str::from_utf8(secret_binary) {
+            Ok(value) => (value.to_string(), SecretFormat::Binary),
+            Err(_) => (STANDARD.encode(secret_binary), SecretFormat::Base64Binary),
+        };
+        let data = HashMap::from([(value_key.to_string(), value)]);
+        return Ok((data, format));
+    }
+
+    anyhow::bail!("Secret '{}' has no value", path)
+}
+
+/// Turn key/value pairs into a payload in the given storage format
+fn encode_secret(
+    data: &HashMap<String, String>,
+    format: SecretFormat,
+    value_key: &str,
+) -> Result<SecretPayload> {
+    let value = data.get(value_key).filter(|_| data.len() == 1);
+    match (format, value) {
+        (SecretFormat::Plain, Some(value)) => Ok(SecretPayload::String(value.clone())),
+        (SecretFormat::Binary, Some(value)) => {
+            Ok(SecretPayload::Binary(Blob::new(value.as_bytes())))
+        }
+        (SecretFormat::Base64Binary, Some(value)) => STANDARD
+            .decode(value)
+            .map(|bytes| SecretPayload::Binary(Blob::new(bytes)))
+            .context("Binary secret holds raw bytes, so its new value must be base64"),
+        _ => serde_json::to_string(data)
+            .map(SecretPayload::String)
+            .context("Failed to serialize secret data to JSON"),
+    }
+}
+
+/// Whether every replica is in sync, failing if any replica failed
+fn replicas_in_sync(path: &str, replicas: &[ReplicationStatusType]) -> Result<bool> {
+    let failed: Vec<String> = replicas
+        .iter()
+        .filter(|r| r.status() == Some(&StatusType::Failed))
+        .map(|r| {
+            format!(
+                "{} ({})",
+                r.region().unwrap_or("unknown region"),
+                r.status_message().unwrap_or("no details")
+            )
+        })
+        .collect();
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "Replication of secret '{}' failed in {}",
+            path,
+            failed.join(", ")
+        );
+    }
+
+    Ok(replicas
+        .iter()
+        .all(|r| r.status() == Some(&StatusType::InSync)))
+}
+
+/// Replicas that are not in sync, with their status
+fn lagging_replicas(replicas: &[ReplicationStatusType]) -> String {
+    replicas
+        .iter()
+        .filter(|r| r.status() != Some(&StatusType::InSync))
+        .map(|r| {
+            format!(
+                "{} ({})",
+                r.region().unwrap_or("unknown region"),
+                replica_status(r)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn replica_status(replica: &ReplicationStatusType) -> &str {
+    replica
+        .status()
+        .map(StatusType::as_str)
+        .unwrap_or("Unknown")
+}
+
+/// Find the version ID carrying a staging label
+fn version_with_stage<'a>(
+    stages: &'a HashMap<String, Vec<String>>,
+    stage: &str,
+) -> Option<&'a str> {
+    stages
+        .iter()
+        .find(|(_, labels)| labels.iter().any(|label| label == stage))
+        .map(|(version_id, _)| version_id.as_str())
+}
+
+#[async_trait::async_trait]
+impl SecretBackend for AwsSecretsClient {
+    async fn read_secret(&self, path: &str) -> Result<SecretData> {
+        debug!("Reading secret from AWS Secrets Manager: {}", path);
+
+        let (data, _) = self.get_secret_value(path).await?;
 
         // Get tags for metadata
         let tags_response = self
@@ -93,42 +467,25 @@ impl SecretBackend for AwsSecretsClient {
         Ok(SecretData {
             data,
             metadata: Some(metadata),
+            version: None,
         })
     }
 
     async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
         debug!("Writing secret to AWS Secrets Manager: {}", path);
 
-        // Convert HashMap to JSON string
-        let secret_string =
-            serde_json::to_string(&data).context("Failed to serialize secret data to JSON")?;
-
-        // Check if secret exists
-        let exists = self
-            .client
-            .describe_secret()
-            .secret_id(path)
-            .send()
-            .await
-            .is_ok();
-
-        if exists {
-            // Update existing secret
-            self.client
-                .update_secret()
-                .secret_id(path)
-                .secret_string(&secret_string)
-                .send()
-                .await
-                .with_context(|| {
-                    format!("Failed to update secret '{}' in AWS Secrets Manager", path)
-                })?;
+        if self.secret_exists(path).await {
+            // Add a new AWSCURRENT version; AWS moves AWSPREVIOUS to the old one
+            let payload = self.encode_secret(path, &data).await?;
+            self.put_secret_value(path, payload, STAGE_CURRENT).await?;
             info!(
                 "Successfully updated secret '{}' in AWS Secrets Manager",
                 path
             );
         } else {
             // Create new secret
+            let secret_string =
+                serde_json::to_string(&data).context("Failed to serialize secret data to JSON")?;
             self.client
                 .create_secret()
                 .name(path)
@@ -144,6 +501,73 @@ impl SecretBackend for AwsSecretsClient {
             );
         }
 
+        self.check_replication(path).await
+    }
+
+    fn stages_secrets(&self) -> bool {
+        true
+    }
+
+    /// Store the rotated value as AWSPENDING, leaving AWSCURRENT untouched
+    async fn stage_secret(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        _version: Option<u64>,
+    ) -> Result<()> {
+        if !self.secret_exists(path).await {
+            // Nothing is being served yet, so there is nothing to stage against
+            return self.write_secret(path, data).await;
+        }
+
+        debug!("Staging secret in AWS Secrets Manager: {}", path);
+        let payload = self.encode_secret(path, &data).await?;
+        self.put_secret_value(path, payload, STAGE_PENDING).await?;
+        info!("Staged new value of secret '{}' as {}", path, STAGE_PENDING);
+        Ok(())
+    }
+
+    /// Move AWSCURRENT to the AWSPENDING version; the old value becomes AWSPREVIOUS
+    async fn promote_staged_secret(&self, path: &str) -> Result<()> {
+        let stages = self.version_stages(path).await?;
+        let Some(pending) = version_with_stage(&stages, STAGE_PENDING) else {
+            debug!("No {} version of '{}' to promote", STAGE_PENDING, path);
+            return Ok(());
+        };
+
+        let current = version_with_stage(&stages, STAGE_CURRENT);
+        if current != Some(pending) {
+            let mut request = self
+                .client
+                .update_secret_version_stage()
+                .secret_id(path)
+                .version_stage(STAGE_CURRENT)
+                .move_to_version_id(pending);
+            if let Some(current) = current {
+                request = request.remove_from_version_id(current);
+            }
+            request.send().await.with_context(|| {
+                format!(
+                    "Failed to promote {} version of secret '{}'",
+                    STAGE_PENDING, path
+                )
+            })?;
+        }
+
+        self.clear_pending(path, pending).await?;
+        info!(
+            "Promoted new value of secret '{}' to {} (previous value kept as {})",
+            path, STAGE_CURRENT, STAGE_PREVIOUS
+        );
+        self.check_replication(path).await
+    }
+
+    async fn discard_staged_secret(&self, path: &str) -> Result<()> {
+        let stages = self.version_stages(path).await?;
+        if let Some(pending) = version_with_stage(&stages, STAGE_PENDING) {
+            self.clear_pending(path, pending).await?;
+            info!("Discarded {} value of secret '{}'", STAGE_PENDING, path);
+        }
         Ok(())
     }
 
@@ -180,6 +604,20 @@ impl SecretBackend for AwsSecretsClient {
         Ok(())
     }
 
+    async fn remove_metadata(&self, path: &str, keys: &[String]) -> Result<()> {
+        debug!("Removing metadata keys from secret: {}", path);
+
+        // Tags merge on update, so removed keys have to be untagged explicitly
+        self.client
+            .untag_resource()
+            .secret_id(path)
+            .set_tag_keys(Some(keys.to_vec()))
+            .send()
+            .await
+            .with_context(|| format!("Failed to remove metadata from secret '{}'", path))?;
+        Ok(())
+    }
+
     async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>> {
         debug!("Reading metadata for secret: {}", path);
 
@@ -196,52 +634,79 @@ impl SecretBackend for AwsSecretsClient {
         Ok(metadata)
     }
 
-    async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
-        debug!(
-            "Listing secrets in AWS Secrets Manager with prefix: {}",
-            path
-        );
+    /// Reads tags for up to [`METADATA_BATCH_SIZE`] secrets per `ListSecrets`
+    /// call instead of one `DescribeSecret` call each
+    async fn read_metadata_batch(&self, paths: &[String]) -> Vec<Result<HashMap<String, String>>> {
+        let mut results = Vec::with_capacity(paths.len());
+        for chunk in paths.chunks(METADATA_BATCH_SIZE) {
+            let mut found = self.list_tags(chunk).await.unwrap_or_else(|e| {
+                debug!("Batch metadata read failed, reading one by one: {:#}", e);
+                HashMap::new()
+            });
+            for path in chunk {
+                // Anything not listed is described on its own, which also
+                // gives a proper error for secrets that do not exist
+                results.push(match found.remove(path) {
+                    Some(metadata) => Ok(metadata),
+                    None => self.read_metadata(path).await,
+                });
+            }
+        }
+        results
+    }
 
+    async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
         let mut secrets = Vec::new();
-        let mut next_token: Option<String> = None;
-
+        let mut cursor: Option<String> = None;
         loop {
-            let mut request = self.client.list_secrets();
-
-            if let Some(ref token) = next_token {
-                request = request.set_next_token(Some(token.clone()));
+            let page = self.list_secrets_page(path, cursor.as_deref()).await?;
+            secrets.extend(page.names);
+            cursor = page.next;
+            if cursor.is_none() {
+                break;
             }
+        }
+        Ok(secrets)
+    }
 
-            let response = request
-                .send()
-                .await
-                .context("Failed to list secrets from AWS Secrets Manager")?;
+    /// One `ListSecrets` call; the cursor is AWS's `NextToken`
+    async fn list_secrets_page(&self, path: &str, cursor: Option<&str>) -> Result<SecretPage> {
+        debug!(
+            "Listing secrets in AWS Secrets Manager with prefix: {}",
+            path
+        );
 
-            for secret in response.secret_list() {
-                if let Some(name) = secret.name() {
-                    // Filter by prefix if path is specified
-                    if path.is_empty() || name.starts_with(path) {
-                        // If path is not empty, remove the prefix to match Vault behavior
-                        let secret_name = if path.is_empty() {
-                            name.to_string()
-                        } else {
-                            name.strip_prefix(path)
-                                .and_then(|s| s.strip_prefix("/"))
-                                .map(|s| s.to_string())
-                                .unwrap_or_else(|| name.to_string())
-                        };
-                        secrets.push(secret_name);
-                    }
+        let response = self
+            .client
+            .list_secrets()
+            .set_next_token(cursor.map(str::to_string))
+            .send()
+            .await
+            .context("Failed to list secrets from AWS Secrets Manager")?;
+
+        let mut names = Vec::new();
+        for secret in response.secret_list() {
+            if let Some(name) = secret.name() {
+                // Filter by prefix if path is specified
+                if path.is_empty() || name.starts_with(path) {
+                    // If path is not empty, remove the prefix to match Vault behavior
+                    let secret_name = if path.is_empty() {
+                        name.to_string()
+                    } else {
+                        name.strip_prefix(path)
+                            .and_then(|s| s.strip_prefix("/"))
+                            .map(|s| s.to_string())
+                            .unwrap_or_else(|| name.to_string())
+                    };
+                    names.push(secret_name);
                 }
             }
-
-            next_token = response.next_token().map(|s| s.to_string());
-            if next_token.is_none() {
-                break;
-            }
         }
 
-        Ok(secrets)
+        Ok(SecretPage {
+            names,
+            next: response.next_token().map(|s| s.to_string()),
+        })
     }
 
     fn backend_type(&self) -> &'static str {
@@ -258,6 +723,8 @@ mod tests {
         let client = AwsSecretsClient {
             client: create_test_client(),
             region: "us-east-1".to_string(),
+            value_key: DEFAULT_VALUE_KEY.to_string(),
+            replication_wait: None,
         };
 
         let tags = vec![
@@ -289,6 +756,8 @@ mod tests {
         let client = AwsSecretsClient {
             client: create_test_client(),
             region: "us-east-1".to_string(),
+            value_key: DEFAULT_VALUE_KEY.to_string(),
+            replication_wait: None,
         };
 
         let tags = vec![];
@@ -301,6 +770,8 @@ mod tests {
         let client = AwsSecretsClient {
             client: create_test_client(),
             region: "us-east-1".to_string(),
+            value_key: DEFAULT_VALUE_KEY.to_string(),
+            replication_wait: None,
         };
 
         let mut metadata = HashMap::new();
@@ -339,6 +810,8 @@ mod tests {
         let client = AwsSecretsClient {
             client: create_test_client(),
             region: "us-east-1".to_string(),
+            value_key: DEFAULT_VALUE_KEY.to_string(),
+            replication_wait: None,
         };
 
         let metadata = HashMap::new();
@@ -346,6 +819,167 @@ mod tests {
         assert!(tags.is_empty());
     }
 
+    #[test]
+    fn test_version_with_stage() {
+        let stages = HashMap::from([
+            ("v2".to_string(), vec![STAGE_PENDING.to_string()]),
+            ("v1".to_string(), vec![STAGE_CURRENT.to_string()]),
+            ("v0".to_string(), vec![STAGE_PREVIOUS.to_string()]),
+        ]);
+
+        assert_eq!(version_with_stage(&stages, STAGE_PENDING), Some("v2"));
+        assert_eq!(version_with_stage(&stages, STAGE_CURRENT), Some("v1"));
+        assert_eq!(version_with_stage(&stages, STAGE_PREVIOUS), Some("v0"));
+        assert_eq!(version_with_stage(&HashMap::new(), STAGE_CURRENT), None);
+    }
+
+    #[test]
+    fn test_decode_secret_formats() -> Result<()> {
+        let (data, format) = decode_secret("s", Some(r#"{"password":"p"}"#), None, "value")?;
+        assert_eq!(format, SecretFormat::Json);
+        assert_eq!(data.get("password"), Some(&"p".to_string()));
+
+        let (data, format) = decode_secret("s", Some("plain-api-key"), None, "value")?;
+        assert_eq!(format, SecretFormat::Plain);
+        assert_eq!(data.get("value"), Some(&"plain-api-key".to_string()));
+
+        let (data, format) = decode_secret("s", None, Some(b"binary-token"), "token")?;
+        assert_eq!(format, SecretFormat::Binary);
+        assert_eq!(data.get("token"), Some(&"binary-token".to_string()));
+
+        assert!(decode_secret("s", Some(r#"{"nested":{"a":"b"}}"#), None, "value").is_err());
+        let (data, format) = decode_secret("s", None, Some(&[0xff, 0xfe]), "value")?;
+        assert_eq!(format, SecretFormat::Base64Binary);
+        assert_eq!(data.get("value"), Some(&"//4=".to_string()));
+        match encode_secret(&data, format, "value")? {
+            SecretPayload::Binary(blob) => assert_eq!(blob.as_ref(), [0xff, 0xfe]),
+            SecretPayload::String(_) => panic!("expected a binary payload"),
+        }
+        assert!(decode_secret("s", None, None, "value").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_secret_keeps_format() -> Result<()> {
+        let single = HashMap::from([("value".to_string(), "new".to_string())]);
+
+        match encode_secret(&single, SecretFormat::Plain, "value")? {
+            SecretPayload::String(s) => assert_eq!(s, "new"),
+            SecretPayload::Binary(_) => panic!("expected string payload"),
+        }
+        match encode_secret(&single, SecretFormat::Binary, "value")? {
+            SecretPayload::Binary(b) => assert_eq!(b.as_ref(), b"new"),
+            SecretPayload::String(_) => panic!("expected binary payload"),
+        }
+
+        // Extra keys cannot be represented as a single value
+        let mut multiple = single.clone();
+        multiple.insert("username".to_string(), "admin".to_string());
+        match encode_secret(&multiple, SecretFormat::Plain, "value")? {
+            SecretPayload::String(s) => {
+                let parsed: HashMap<String, String> = serde_json::from_str(&s)?;
+                assert_eq!(parsed, multiple);
+            }
+            SecretPayload::Binary(_) => panic!("expected JSON payload"),
+        }
+        Ok(())
+    }
+
+    fn replica(region: &str, status: StatusType) -> ReplicationStatusType {
+        ReplicationStatusType::builder()
+            .region(region)
+            .status(status)
+            .build()
+    }
+
+    #[test]
+    fn test_replicas_in_sync() -> Result<()> {
+        let synced = vec![
+            replica("us-west-2", StatusType::InSync),
+            replica("eu-west-1", StatusType::InSync),
+        ];
+        assert!(replicas_in_sync("app/db", &synced)?);
+
+        let lagging = vec![
+            replica("us-west-2", StatusType::InSync),
+            replica("eu-west-1", StatusType::InProgress),
+        ];
+        assert!(!replicas_in_sync("app/db", &lagging)?);
+        assert_eq!(lagging_replicas(&lagging), "eu-west-1 (InProgress)");
+
+        let failed = vec![
+            replica("us-west-2", StatusType::InSync),
+            ReplicationStatusType::builder()
+                .region("eu-west-1")
+                .status(StatusType::Failed)
+                .status_message("KMS key not found")
+                .build(),
+        ];
+        let err = replicas_in_sync("app/db", &failed).unwrap_err().to_string();
+        assert!(err.contains("eu-west-1 (KMS key not found)"), "{}", err);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_metadata_batch() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        // One ListSecrets call covers the batch; prefix matches are ignored
+        let list = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "secretsmanager.ListSecrets")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "Filters": [{"Key": "name", "Values": ["app/db", "app/api", "app/gone"]}]
+            })))
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body(
+                serde_json::json!({ "SecretList": [
+                    {"Name": "app/db", "Tags": [{"Key": "rotation_enabled", "Value": "true"}]},
+                    {"Name": "app/db-old", "Tags": [{"Key": "owner", "Value": "nobody"}]},
+                    {"Name": "app/api", "Tags": []},
+                ]})
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        // Secrets that are not listed are described one by one
+        let describe = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "secretsmanager.DescribeSecret")
+            .with_status(400)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_header("x-amzn-errortype", "ResourceNotFoundException")
+            .with_body(r#"{"__type": "ResourceNotFoundException", "message": "not found"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let config = aws_sdk_secretsmanager::Config::builder()
+            .behavior_version(aws_config::BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .endpoint_url(server.url())
+            .credentials_provider(aws_sdk_secretsmanager::config::Credentials::new(
+                "AKID", "secret", None, None, "test",
+            ))
+            .build();
+        let client = AwsSecretsClient {
+            client: SecretsManagerClient::from_conf(config),
+            region: "us-east-1".to_string(),
+            value_key: DEFAULT_VALUE_KEY.to_string(),
+            replication_wait: None,
+        };
+
+        let paths = ["app/db", "app/api", "app/gone"].map(String::from);
+        let results = client.read_metadata_batch(&paths).await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap()["rotation_enabled"], "true");
+        assert!(results[1].as_ref().unwrap().is_empty());
+        assert!(results[2].is_err());
+        list.assert_async().await;
+        describe.assert_async().await;
+        Ok(())
+    }
+
     // Helper function to create a test client
     // Note: This creates a real client but tests don't actually call AWS APIs
     // In a real scenario, you'd use a mock client
@@ -0,0 +1,253 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use super::secret_backend::{SecretBackend, SecretData};
+use super::{SecretPage, SecretVersion};
+
+/// Backend that remembers secret metadata for a while
+///
+/// A long-running process such as `asr serve` scans the same tree over and
+/// over, and without a cache every scan reads the metadata of every secret
+/// again. Metadata read through this backend is reused until it is `ttl` old.
+/// Writes made through it drop the cached entry, so a rotation or a flag is
+/// seen by the next scan; changes made by other processes are seen once the
+/// entry expires. Secret values are never cached.
+pub struct CachedBackend<B: ?Sized = dyn SecretBackend> {
+    inner: Box<B>,
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+struct Entry {
+    stored: Instant,
+    metadata: HashMap<String, String>,
+}
+
+impl<B: SecretBackend + ?Sized> CachedBackend<B> {
+    pub fn new(inner: Box<B>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The wrapped backend
+    #[cfg_attr(not(test), allow(dead_code))] // Used in tests
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Forget the cached metadata of a secret
+    pub fn invalidate(&self, path: &str) {
+        self.lock().remove(path);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn cached(&self, path: &str) -> Option<HashMap<String, String>> {
+        let mut entries = self.lock();
+        match entries.get(path) {
+            Some(entry) if entry.stored.elapsed() < self.ttl => Some(entry.metadata.clone()),
+            Some(_) => {
+                entries.remove(path);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn store(&self, path: &str, metadata: &HashMap<String, String>) {
+        let entry = Entry {
+            stored: Instant::now(),
+            metadata: metadata.clone(),
+        };
+        self.lock().insert(path.to_string(), entry);
+    }
+}
+
+#[async_trait::async_trait]
+impl<B: SecretBackend + ?Sized> SecretBackend for CachedBackend<B> {
+    async fn read_secret(&self, path: &str) -> Result<SecretData> {
+        self.inner.read_secret(path).await
+    }
+
+    async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
+        self.invalidate(path);
+        self.inner.write_secret(path, data).await
+    }
+
+    async fn write_secret_cas(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.invalidate(path);
+        self.inner.write_secret_cas(path, data, version).await
+    }
+
+    async fn stage_secret(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.invalidate(path);
+        self.inner.stage_secret(path, data, version).await
+    }
+
+    async fn promote_staged_secret(&self, path: &str) -> Result<()> {
+        self.invalidate(path);
+        self.inner.promote_staged_secret(path).await
+    }
+
+    async fn discard_staged_secret(&self, path: &str) -> Result<()> {
+        self.invalidate(path);
+        self.inner.discard_staged_secret(path).await
+    }
+
+    async fn update_metadata(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        // Dropped rather than updated, since backends differ in whether an
+        // update replaces or merges
+        self.invalidate(path);
+        self.inner.update_metadata(path, metadata).await
+    }
+
+    async fn remove_metadata(&self, path: &str, keys: &[String]) -> Result<()> {
+        self.invalidate(path);
+        self.inner.remove_metadata(path, keys).await
+    }
+
+    async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>> {
+        if let Some(metadata) = self.cached(path) {
+            return Ok(metadata);
+        }
+        let metadata = self.inner.read_metadata(path).await?;
+        self.store(path, &metadata);
+        Ok(metadata)
+    }
+
+    /// Only the secrets missing from the cache are read, in one batch
+    async fn read_metadata_batch(&self, paths: &[String]) -> Vec<Result<HashMap<String, String>>> {
+        let mut results: Vec<Option<Result<HashMap<String, String>>>> =
+            paths.iter().map(|path| self.cached(path).map(Ok)).collect();
+        let missing: Vec<usize> = (0..paths.len())
+            .filter(|&index| results[index].is_none())
+            .collect();
+        debug!(
+            "Metadata cache: {} of {} secrets cached",
+            paths.len() - missing.len(),
+            paths.len()
+        );
+
+        if !missing.is_empty() {
+            let batch: Vec<String> = missing.iter().map(|&i| paths[i].clone()).collect();
+            let read = self.inner.read_metadata_batch(&batch).await;
+            for (index, result) in missing.into_iter().zip(read) {
+                if let Ok(ref metadata) = result {
+                    self.store(&paths[index], metadata);
+                }
+                results[index] = Some(result);
+            }
+        }
+
+        results
+            .into_iter()
+            .zip(paths)
+            .map(|(result, path)| {
+                result.unwrap_or_else(|| {
+                    Err(anyhow::anyhow!("Backend returned no metadata for {}", path))
+                })
+            })
+            .collect()
+    }
+
+    async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
+        self.inner.list_secrets(path).await
+    }
+
+    async fn list_secrets_page(&self, path: &str, cursor: Option<&str>) -> Result<SecretPage> {
+        self.inner.list_secrets_page(path, cursor).await
+    }
+
+    async fn list_versions(&self, path: &str) -> Result<Vec<SecretVersion>> {
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(&self, path: &str, version: u64) -> Result<SecretData> {
+        self.inner.read_version(path, version).await
+    }
+
+    fn supports_cas(&self) -> bool {
+        self.inner.supports_cas()
+    }
+
+    fn stages_secrets(&self) -> bool {
+        self.inner.stages_secrets()
+    }
+
+    fn backend_type(&self) -> &'static str {
+        self.inner.backend_type()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::{MemoryBackend, Operation};
+
+    fn enabled() -> HashMap<String, String> {
+        HashMap::from([("rotation_enabled".to_string(), "true".to_string())])
+    }
+
+    #[tokio::test]
+    async fn test_cached_metadata() -> Result<()> {
+        let memory = MemoryBackend::new()
+            .with_secret("app/a", [("password", "x")])
+            .with_secret("app/b", [("password", "x")]);
+        memory.update_metadata("app/a", enabled()).await?;
+        let cached = CachedBackend::new(Box::new(memory), Duration::from_secs(3600));
+        let memory = cached.inner();
+
+        assert_eq!(cached.read_metadata("app/a").await?, enabled());
+        assert_eq!(cached.read_metadata("app/a").await?, enabled());
+        assert_eq!(memory.calls(Operation::ReadMetadata), 1);
+
+        // Only app/b is read
+        let paths = vec!["app/a".to_string(), "app/b".to_string()];
+        let batch = cached.read_metadata_batch(&paths).await;
+        assert_eq!(batch[0].as_ref().unwrap(), &enabled());
+        assert!(batch[1].as_ref().unwrap().is_empty());
+        assert_eq!(memory.calls(Operation::ReadMetadata), 2);
+
+        // A write through the cache is seen by the next read
+        cached.update_metadata("app/b", enabled()).await?;
+        assert_eq!(cached.read_metadata("app/b").await?, enabled());
+        assert_eq!(memory.calls(Operation::ReadMetadata), 3);
+
+        // Failures are not cached
+        memory.fail_next(Operation::ReadMetadata, "down");
+        cached.invalidate("app/a");
+        assert!(cached.read_metadata("app/a").await.is_err());
+        assert_eq!(cached.read_metadata("app/a").await?, enabled());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cached_metadata_expires() -> Result<()> {
+        let memory = MemoryBackend::new().with_secret("app/a", [("password", "x")]);
+        let cached = CachedBackend::new(Box::new(memory), Duration::ZERO);
+        let memory = cached.inner();
+
+        cached.read_metadata("app/a").await?;
+        cached.read_metadata("app/a").await?;
+        assert_eq!(memory.calls(Operation::ReadMetadata), 2);
+        Ok(())
+    }
+}
@@ -1,10 +1,14 @@
 use anyhow::{Context, Result};
+use chrono::{NaiveDateTime, Utc};
+use fs2::FileExt;
 use std::collections::HashMap;
-use std::fs;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-use super::secret_backend::{SecretBackend, SecretData};
+use super::secret_backend::{SecretBackend, SecretData, SecretVersion};
+use crate::transit::{self, Transit};
 
 /// File-based backend for storing secrets in local flat files
 ///
@@ -13,12 +17,37 @@ use super::secret_backend::{SecretBackend, SecretData};
 ///   password:mysecret123
 ///   username:admin
 ///
-/// Metadata is stored in a separate .meta file alongside the secret file
+/// Metadata is stored in a separate `<secret>.meta` sidecar file.
+///
+/// Secret and metadata files are created with `0600` permissions and
+/// directories with `0700` (on Unix). Every read and write holds an advisory
+/// lock on a hidden `.<secret>.lock` file, and files are replaced atomically,
+/// so several processes can share the same directory.
+///
+/// The last few values of each secret are kept under a hidden `.versions/`
+/// directory, one file per version named `<version>-<timestamp>`, so a
+/// secret can be rolled back (see [`FileBackend::with_versions`]).
+///
+/// With [`FileBackend::with_transit`], secret and version files hold a Vault
+/// transit envelope instead of the key:value lines (see [`crate::transit`]).
+/// Plaintext files written before are still read, and sealed on their next
+/// write. Metadata files are not encrypted.
 pub struct FileBackend {
     base_dir: PathBuf,
+    versions: usize,
+    transit: Option<Transit>,
 }
 
+/// Directory under the base directory holding kept versions
+const VERSIONS_DIR: &str = ".versions";
+
+/// Timestamp format of version file names
+const VERSION_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
 impl FileBackend {
+    /// Versions kept per secret unless configured otherwise
+    pub const DEFAULT_VERSIONS: usize = 5;
+
     /// Create a new file backend
     pub fn new(base_dir: impl AsRef<Path>) -> Result<Self> {
         let mut base_dir = base_dir.as_ref().to_path_buf();
@@ -33,12 +62,28 @@ impl FileBackend {
 
         // Create base directory if it doesn't exist
         if !base_dir.exists() {
-            fs::create_dir_all(&base_dir)
+            create_private_dir(&base_dir)
                 .with_context(|| format!("Failed to create base directory: {:?}", base_dir))?;
             info!("Created base directory: {:?}", base_dir);
         }
 
-        Ok(Self { base_dir })
+        Ok(Self {
+            base_dir,
+            versions: Self::DEFAULT_VERSIONS,
+            transit: None,
+        })
+    }
+
+    /// Keep the last `versions` values of each secret (0 keeps none)
+    pub fn with_versions(mut self, versions: usize) -> Self {
+        self.versions = versions;
+        self
+    }
+
+    /// Envelope-encrypt secret files with a Vault transit key
+    pub fn with_transit(mut self, transit: Transit) -> Self {
+        self.transit = Some(transit);
+        self
     }
 
     /// Convert a secret path to a file path
@@ -48,8 +93,151 @@ impl FileBackend {
     }
 
     /// Get metadata file path for a secret
+    ///
+    /// The suffix is appended rather than replacing an extension, so secrets
+    /// such as `app/tls.key` and `app/tls.crt` get separate metadata files.
     fn metadata_path(&self, path: &str) -> PathBuf {
-        self.path_to_file(path).with_extension("meta")
+        let mut meta_path = self.path_to_file(path).into_os_string();
+        meta_path.push(".meta");
+        meta_path.into()
+    }
+
+    /// Metadata file used before the suffix was appended, for secrets whose
+    /// name has an extension (`app/tls.key` -> `app/tls.meta`)
+    ///
+    /// It is not used when it is the current metadata file of another secret
+    /// (`app/tls`).
+    fn legacy_metadata_path(&self, path: &str) -> Option<PathBuf> {
+        let file_path = self.path_to_file(path);
+        file_path.extension()?;
+        let stem = file_path.with_extension("");
+        (!stem.is_file()).then(|| file_path.with_extension("meta"))
+    }
+
+    /// Read a secret's metadata file, falling back to its legacy name
+    ///
+    /// Metadata found under the legacy name moves to the current one on the
+    /// secret's next metadata update.
+    fn read_metadata_file(&self, path: &str) -> Result<Option<HashMap<String, String>>> {
+        let meta_path = self.metadata_path(path);
+        if let Some(metadata) = Self::read_entries(&meta_path)
+            .with_context(|| format!("Failed to read metadata file: {:?}", meta_path))?
+        {
+            return Ok(Some(metadata));
+        }
+        let Some(legacy_path) = self.legacy_metadata_path(path) else {
+            return Ok(None);
+        };
+        let metadata = Self::read_entries(&legacy_path)
+            .with_context(|| format!("Failed to read metadata file: {:?}", legacy_path))?;
+        if metadata.is_some() {
+            debug!("Using legacy metadata file {:?} for {}", legacy_path, path);
+        }
+        Ok(metadata)
+    }
+
+    /// Directory holding the kept versions of a secret
+    fn versions_dir(&self, path: &str) -> PathBuf {
+        self.base_dir.join(VERSIONS_DIR).join(path)
+    }
+
+    /// Kept versions of a secret and their files, oldest first
+    fn kept_versions(&self, path: &str) -> Result<Vec<(SecretVersion, PathBuf)>> {
+        let dir = self.versions_dir(path);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read directory: {:?}", dir))
+            }
+        };
+
+        let mut versions = Vec::new();
+        for entry in entries {
+            let entry = entry.context("Failed to read directory entry")?;
+            // Temporary files are hidden and don't parse
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some((version, created)) = name.split_once('-') else {
+                continue;
+            };
+            let (Ok(version), Ok(created)) = (
+                version.parse(),
+                NaiveDateTime::parse_from_str(created, VERSION_TIME_FORMAT),
+            ) else {
+                continue;
+            };
+            let created = created.and_utc();
+            versions.push((SecretVersion { version, created }, entry.path()));
+        }
+        versions.sort_by_key(|(version, _)| version.version);
+        Ok(versions)
+    }
+
+    /// Keep `content` as the newest version of a secret, dropping the oldest
+    /// ones beyond the configured number (the caller holds the secret's lock)
+    fn record_version(&self, path: &str, content: &str) -> Result<()> {
+        if self.versions == 0 {
+            return Ok(());
+        }
+        let kept = self.kept_versions(path)?;
+        let next = kept.last().map_or(1, |(last, _)| last.version + 1);
+        let dir = self.versions_dir(path);
+        create_private_dir(&dir)
+            .with_context(|| format!("Failed to create directory: {:?}", dir))?;
+        let name = format!("{}-{}", next, Utc::now().format(VERSION_TIME_FORMAT));
+        write_private_file(&dir.join(name), content)?;
+
+        let excess = (kept.len() + 1).saturating_sub(self.versions);
+        for (_, file) in kept.iter().take(excess) {
+            fs::remove_file(file)
+                .with_context(|| format!("Failed to remove old version: {:?}", file))?;
+        }
+        Ok(())
+    }
+
+    /// Take an advisory lock guarding a secret and its metadata
+    ///
+    /// The lock lives in a separate file because secret files are replaced on
+    /// write. It is released when the returned file is dropped. Shared locks
+    /// are skipped when the secret's directory does not exist, so reading a
+    /// missing secret does not create anything on disk.
+    ///
+    /// Waiting for the lock happens on a blocking thread, so a lock held by
+    /// another process does not stall the async runtime.
+    async fn lock(&self, path: &str, exclusive: bool) -> Result<Option<File>> {
+        let file_path = self.path_to_file(path);
+        if exclusive {
+            self.ensure_parent_dir(&file_path)?;
+        } else if !file_path.parent().is_some_and(Path::is_dir) {
+            return Ok(None);
+        }
+
+        let file_name = file_path
+            .file_name()
+            .with_context(|| format!("Invalid secret path: {}", path))?;
+        let mut lock_name = std::ffi::OsString::from(".");
+        lock_name.push(file_name);
+        lock_name.push(".lock");
+        let lock_path = file_path.with_file_name(lock_name);
+
+        tokio::task::spawn_blocking(move || {
+            let lock_file = private_open_options()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&lock_path)
+                .with_context(|| format!("Failed to open lock file: {:?}", lock_path))?;
+
+            if exclusive {
+                lock_file.lock_exclusive()
+            } else {
+                lock_file.lock_shared()
+            }
+            .with_context(|| format!("Failed to lock {:?}", lock_path))?;
+            Ok(Some(lock_file))
+        })
+        .await
+        .context("Lock task failed")?
     }
 
     /// Parse a key:value line from the secret file
@@ -68,52 +256,140 @@ impl FileBackend {
     }
 
     /// Format a key:value pair for writing
-    fn format_line(key: &str, value: &str) -> String {
+    pub(super) fn format_line(key: &str, value: &str) -> String {
         format!("{}:{}\n", key, value)
     }
 
+    /// Read a file, returning `None` if it does not exist
+    fn read_content(file_path: &Path) -> Result<Option<String>> {
+        match fs::read_to_string(file_path) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read file: {:?}", file_path)),
+        }
+    }
+
+    fn parse_entries(content: &str) -> HashMap<String, String> {
+        content.lines().filter_map(Self::parse_line).collect()
+    }
+
+    /// Read a key:value file, returning `None` if it does not exist
+    pub(super) fn read_entries(file_path: &Path) -> Result<Option<HashMap<String, String>>> {
+        Ok(Self::read_content(file_path)?.map(|content| Self::parse_entries(&content)))
+    }
+
+    /// Read a secret or version file, opening it if it is a transit envelope
+    async fn read_data(&self, file_path: &Path) -> Result<Option<HashMap<String, String>>> {
+        let Some(content) = Self::read_content(file_path)? else {
+            return Ok(None);
+        };
+        if !transit::is_envelope(&content) {
+            return Ok(Some(Self::parse_entries(&content)));
+        }
+
+        let transit = self.transit.as_ref().with_context(|| {
+            format!(
+                "{:?} is encrypted with Vault transit; set transit = true in [file]",
+                file_path
+            )
+        })?;
+        let content = String::from_utf8(transit.open(&content).await?)
+            .with_context(|| format!("Decrypted {:?} is not valid UTF-8", file_path))?;
+        Ok(Some(Self::parse_entries(&content)))
+    }
+
     /// Ensure parent directories exist for a path
     fn ensure_parent_dir(&self, file_path: &Path) -> Result<()> {
         if let Some(parent) = file_path.parent() {
-            fs::create_dir_all(parent)
+            create_private_dir(parent)
                 .with_context(|| format!("Failed to create parent directory: {:?}", parent))?;
         }
         Ok(())
     }
 }
 
+/// Open options that create files readable by the owner only
+fn private_open_options() -> OpenOptions {
+    #[allow(unused_mut)]
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+/// Create a directory (and its parents) accessible by the owner only
+pub(super) fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(path)
+}
+
+/// Replace a file's contents atomically with a new `0600` file
+///
+/// The content is written to a hidden temporary file next to the target and
+/// renamed over it, so readers never see a partially written file.
+pub(super) fn write_private_file(file_path: &Path, content: impl AsRef<[u8]>) -> Result<()> {
+    let file_name = file_path
+        .file_name()
+        .with_context(|| format!("Invalid file path: {:?}", file_path))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".tmp");
+    let tmp_path = file_path.with_file_name(tmp_name);
+
+    let mut file = private_open_options()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&tmp_path)
+        .with_context(|| format!("Failed to create temporary file: {:?}", tmp_path))?;
+    file.write_all(content.as_ref())
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("Failed to write temporary file: {:?}", tmp_path))?;
+
+    fs::rename(&tmp_path, file_path)
+        .with_context(|| format!("Failed to replace file: {:?}", file_path))
+}
+
 #[async_trait::async_trait]
 impl SecretBackend for FileBackend {
     async fn read_secret(&self, path: &str) -> Result<SecretData> {
         debug!("Reading secret from file: {}", path);
 
         let file_path = self.path_to_file(path);
+        let _lock = self.lock(path, false).await?;
 
-        if !file_path.exists() {
-            anyhow::bail!("Secret file not found: {:?}", file_path);
-        }
-
-        let content = fs::read_to_string(&file_path)
-            .with_context(|| format!("Failed to read secret file: {:?}", file_path))?;
-
-        let mut data = HashMap::new();
-        for line in content.lines() {
-            if let Some((key, value)) = Self::parse_line(line) {
-                data.insert(key, value);
-            }
-        }
+        let data = self
+            .read_data(&file_path)
+            .await
+            .with_context(|| format!("Failed to read secret file: {:?}", file_path))?
+            .with_context(|| format!("Secret file not found: {:?}", file_path))?;
 
         // Read metadata if it exists
-        let metadata = self.read_metadata(path).await.ok();
-
-        Ok(SecretData { data, metadata })
+        let metadata = self
+            .read_metadata_file(path)
+            .ok()
+            .map(Option::unwrap_or_default);
+
+        Ok(SecretData {
+            data,
+            metadata,
+            version: None,
+        })
     }
 
     async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
         info!("Writing secret to file: {}", path);
 
         let file_path = self.path_to_file(path);
-        self.ensure_parent_dir(&file_path)?;
 
         let mut content = String::new();
         content.push_str("# Secret file - Auto-generated by asr\n");
@@ -122,9 +398,21 @@ impl SecretBackend for FileBackend {
         for (key, value) in &data {
             content.push_str(&Self::format_line(key, value));
         }
+        if let Some(ref transit) = self.transit {
+            content = transit
+                .seal(content.as_bytes())
+                .await
+                .with_context(|| format!("Failed to encrypt {}", path))?;
+        }
 
-        fs::write(&file_path, content)
+        let _lock = self.lock(path, true).await?;
+        write_private_file(&file_path, &content)
             .with_context(|| format!("Failed to write secret file: {:?}", file_path))?;
+        // The secret is already replaced, so failing now would report a write
+        // that happened; a missing version only shortens the history
+        if let Err(e) = self.record_version(path, &content) {
+            warn!("Failed to keep a version of {}: {:#}", path, e);
+        }
 
         debug!("Successfully wrote secret to: {:?}", file_path);
         Ok(())
@@ -134,7 +422,7 @@ impl SecretBackend for FileBackend {
         debug!("Updating metadata for: {}", path);
 
         let meta_path = self.metadata_path(path);
-        self.ensure_parent_dir(&meta_path)?;
+        let _lock = self.lock(path, true).await?;
 
         let mut content = String::new();
         content.push_str("# Metadata file - Auto-generated by asr\n");
@@ -144,30 +432,15 @@ impl SecretBackend for FileBackend {
             content.push_str(&Self::format_line(key, value));
         }
 
-        fs::write(&meta_path, content)
+        write_private_file(&meta_path, &content)
             .with_context(|| format!("Failed to write metadata file: {:?}", meta_path))?;
 
         Ok(())
     }
 
     async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>> {
-        let meta_path = self.metadata_path(path);
-
-        if !meta_path.exists() {
-            return Ok(HashMap::new());
-        }
-
-        let content = fs::read_to_string(&meta_path)
-            .with_context(|| format!("Failed to read metadata file: {:?}", meta_path))?;
-
-        let mut metadata = HashMap::new();
-        for line in content.lines() {
-            if let Some((key, value)) = Self::parse_line(line) {
-                metadata.insert(key, value);
-            }
-        }
-
-        Ok(metadata)
+        let _lock = self.lock(path, false).await?;
+        Ok(self.read_metadata_file(path)?.unwrap_or_default())
     }
 
     async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
@@ -194,8 +467,10 @@ impl SecretBackend for FileBackend {
                 let entry = entry.context("Failed to read directory entry")?;
                 let file_path = entry.path();
 
-                // Skip metadata files and hidden files
-                if file_path.extension().map(|e| e == "meta").unwrap_or(false) {
+                // Skip metadata files and hidden files (locks, temporary files)
+                if file_path.extension().map(|e| e == "meta").unwrap_or(false)
+                    || entry.file_name().to_string_lossy().starts_with('.')
+                {
                     continue;
                 }
 
@@ -231,6 +506,34 @@ impl SecretBackend for FileBackend {
         Ok(secrets)
     }
 
+    async fn list_versions(&self, path: &str) -> Result<Vec<SecretVersion>> {
+        let _lock = self.lock(path, false).await?;
+        Ok(self
+            .kept_versions(path)?
+            .into_iter()
+            .map(|(version, _)| version)
+            .collect())
+    }
+
+    async fn read_version(&self, path: &str, version: u64) -> Result<SecretData> {
+        let _lock = self.lock(path, false).await?;
+        let file = self
+            .kept_versions(path)?
+            .into_iter()
+            .find(|(kept, _)| kept.version == version)
+            .map(|(_, file)| file)
+            .with_context(|| format!("Version {} of {} is not kept", version, path))?;
+        let data = self
+            .read_data(&file)
+            .await?
+            .with_context(|| format!("Version {} of {} is not kept", version, path))?;
+        Ok(SecretData {
+            data,
+            metadata: None,
+            version: Some(version),
+        })
+    }
+
     fn backend_type(&self) -> &'static str {
         "file"
     }
@@ -274,6 +577,23 @@ mod tests {
         let read_meta = backend.read_metadata("test/secret").await?;
         assert_eq!(read_meta, metadata);
 
+        backend
+            .remove_metadata("test/secret", &["last_rotated".to_string()])
+            .await?;
+        metadata.remove("last_rotated");
+        assert_eq!(backend.read_metadata("test/secret").await?, metadata);
+
+        // Metadata written under the old `<stem>.meta` name is still found,
+        // and moves to `<name>.meta` on the next update
+        fs::write(
+            temp_dir.path().join("test/tls.meta"),
+            "rotation_enabled:true\n",
+        )?;
+        let legacy = backend.read_metadata("test/tls.key").await?;
+        assert_eq!(legacy["rotation_enabled"], "true");
+        backend.update_metadata("test/tls.key", legacy).await?;
+        assert!(temp_dir.path().join("test/tls.key.meta").is_file());
+
         Ok(())
     }
 
@@ -297,6 +617,142 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_metadata_sidecar_per_secret() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?;
+
+        let data = HashMap::from([("value".to_string(), "pem".to_string())]);
+        backend.write_secret("tls/server.key", data.clone()).await?;
+        backend.write_secret("tls/server.crt", data).await?;
+
+        let key_meta = HashMap::from([("owner".to_string(), "key".to_string())]);
+        let crt_meta = HashMap::from([("owner".to_string(), "crt".to_string())]);
+        backend
+            .update_metadata("tls/server.key", key_meta.clone())
+            .await?;
+        backend
+            .update_metadata("tls/server.crt", crt_meta.clone())
+            .await?;
+
+        assert!(temp_dir.path().join("tls/server.key.meta").exists());
+        assert_eq!(backend.read_metadata("tls/server.key").await?, key_meta);
+        assert_eq!(backend.read_metadata("tls/server.crt").await?, crt_meta);
+
+        // Lock and metadata files are not secrets
+        let mut secrets = backend.list_secrets("tls").await?;
+        secrets.sort();
+        assert_eq!(secrets, vec!["tls/server.crt", "tls/server.key"]);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_private_permissions() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path().join("secrets"))?;
+
+        let data = HashMap::from([("password".to_string(), "p".to_string())]);
+        backend.write_secret("app/db", data).await?;
+        backend
+            .update_metadata(
+                "app/db",
+                HashMap::from([("a".to_string(), "b".to_string())]),
+            )
+            .await?;
+
+        let mode = |path: &str| -> Result<u32> {
+            Ok(fs::metadata(temp_dir.path().join(path))?
+                .permissions()
+                .mode()
+                & 0o777)
+        };
+        assert_eq!(mode("secrets")?, 0o700);
+        assert_eq!(mode("secrets/app")?, 0o700);
+        assert_eq!(mode("secrets/app/db")?, 0o600);
+        assert_eq!(mode("secrets/app/db.meta")?, 0o600);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_versions() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?.with_versions(3);
+
+        for value in ["v1", "v2", "v3", "v4"] {
+            let data = HashMap::from([("password".to_string(), value.to_string())]);
+            backend.write_secret("app/db", data).await?;
+        }
+
+        let versions: Vec<u64> = backend
+            .list_versions("app/db")
+            .await?
+            .iter()
+            .map(|v| v.version)
+            .collect();
+        assert_eq!(versions, [2, 3, 4]);
+        let old = backend.read_version("app/db", 2).await?;
+        assert_eq!(old.data["password"], "v2");
+        assert_eq!(old.version, Some(2));
+        assert!(backend.read_version("app/db", 1).await.is_err());
+
+        // Kept versions are not secrets
+        assert_eq!(backend.list_secrets("").await?, vec!["app/db"]);
+
+        let unversioned = FileBackend::new(temp_dir.path())?.with_versions(0);
+        let data = HashMap::from([("password".to_string(), "x".to_string())]);
+        unversioned.write_secret("app/other", data).await?;
+        assert!(unversioned.list_versions("app/other").await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transit_envelopes() -> Result<()> {
+        const DATA_KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/transit/datakey/plaintext/asr")
+            .with_body(format!(
+                r#"{{"data": {{"plaintext": "{}", "ciphertext": "vault:v1:wrapped"}}}}"#,
+                DATA_KEY
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/v1/transit/decrypt/asr")
+            .with_body(format!(r#"{{"data": {{"plaintext": "{}"}}}}"#, DATA_KEY))
+            .create_async()
+            .await;
+        let client = crate::backends::VaultClient::new(server.url(), "token".into())?;
+        let transit = Transit::new(client, &toml::from_str(r#"key = "asr""#)?);
+
+        let temp_dir = TempDir::new()?;
+        fs::write(temp_dir.path().join("legacy"), "password:plain\n")?;
+        let backend = FileBackend::new(temp_dir.path())?.with_transit(transit);
+        let data = HashMap::from([("password".to_string(), "sealed-pass".to_string())]);
+        backend.write_secret("app/db", data.clone()).await?;
+
+        let on_disk = fs::read_to_string(temp_dir.path().join("app/db"))?;
+        assert!(transit::is_envelope(&on_disk));
+        assert!(!on_disk.contains("sealed-pass"));
+        assert_eq!(backend.read_secret("app/db").await?.data, data);
+        assert_eq!(backend.read_version("app/db", 1).await?.data, data);
+        assert_eq!(
+            backend.read_secret("legacy").await?.data["password"],
+            "plain"
+        );
+
+        // Without the transit key, sealed files can't be read
+        let plain = FileBackend::new(temp_dir.path())?;
+        assert!(plain.read_secret("app/db").await.is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_parse_line() {
         assert_eq!(
@@ -0,0 +1,438 @@
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use keepass::db::{Entry, Group, Node, Value};
+use keepass::{Database, DatabaseKey};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, info};
+
+use super::file::write_private_file;
+use super::secret_backend::{SecretBackend, SecretData};
+use crate::redact::SecretString;
+
+/// Standard KeePass fields and the keys they are exposed under
+const STANDARD_FIELDS: [(&str, &str); 4] = [
+    ("Password", "password"),
+    ("UserName", "username"),
+    ("URL", "url"),
+    ("Notes", "notes"),
+];
+
+/// Backend for a KeePass (KDBX) database file
+///
+/// A secret path names a group path and an entry title, so `Team/db/prod`
+/// is the entry titled `prod` in group `db` of group `Team`. The standard
+/// fields are exposed as `password`, `username`, `url` and `notes`, and
+/// protected custom fields ("protect in memory") under their own names.
+/// Unprotected custom fields hold the rotation metadata, which keeps it
+/// visible and editable in any KeePass client.
+///
+/// The whole database is read for every operation and written back
+/// atomically after a change, holding an advisory lock on a hidden
+/// `.<database>.lock` file so several `asr` processes can share it. Other
+/// KeePass clients do not take that lock; close them before rotating.
+pub struct KeePassBackend {
+    path: PathBuf,
+    password: Option<SecretString>,
+    keyfile: Option<PathBuf>,
+    /// Serializes read-modify-write cycles within this process
+    write_lock: Mutex<()>,
+}
+
+impl KeePassBackend {
+    /// Create a backend for the database at `path`
+    ///
+    /// At least one of `password` and `keyfile` must be given. The database
+    /// is opened once here, so a wrong key is reported straight away.
+    pub fn new(
+        path: impl AsRef<Path>,
+        password: Option<SecretString>,
+        keyfile: Option<PathBuf>,
+    ) -> Result<Self> {
+        if password.is_none() && keyfile.is_none() {
+            anyhow::bail!("A KeePass database needs a password, a key file, or both");
+        }
+
+        let backend = Self {
+            path: expand_home(path.as_ref())?,
+            password,
+            keyfile: keyfile.as_deref().map(expand_home).transpose()?,
+            write_lock: Mutex::new(()),
+        };
+        backend.open()?;
+        Ok(backend)
+    }
+
+    /// Composite key for the database
+    fn key(&self) -> Result<DatabaseKey> {
+        let mut key = DatabaseKey::new();
+        if let Some(password) = &self.password {
+            key = key.with_password(password.expose_secret());
+        }
+        if let Some(keyfile) = &self.keyfile {
+            let mut file = File::open(keyfile)
+                .with_context(|| format!("Failed to open key file: {:?}", keyfile))?;
+            key = key
+                .with_keyfile(&mut file)
+                .with_context(|| format!("Failed to read key file: {:?}", keyfile))?;
+        }
+        Ok(key)
+    }
+
+    /// Take an advisory lock on the database, released when the file is dropped
+    fn lock(&self, exclusive: bool) -> Result<File> {
+        let file_name = self
+            .path
+            .file_name()
+            .with_context(|| format!("Invalid database path: {:?}", self.path))?;
+        let mut lock_name = std::ffi::OsString::from(".");
+        lock_name.push(file_name);
+        lock_name.push(".lock");
+        let lock_path = self.path.with_file_name(lock_name);
+
+        let lock_file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock file: {:?}", lock_path))?;
+        if exclusive {
+            lock_file.lock_exclusive()
+        } else {
+            lock_file.lock_shared()
+        }
+        .with_context(|| format!("Failed to lock {:?}", lock_path))?;
+
+        Ok(lock_file)
+    }
+
+    /// Read and decrypt the database
+    fn open(&self) -> Result<Database> {
+        let mut file = File::open(&self.path)
+            .with_context(|| format!("Failed to open KeePass database: {:?}", self.path))?;
+        Database::open(&mut file, self.key()?)
+            .with_context(|| format!("Failed to unlock KeePass database: {:?}", self.path))
+    }
+
+    /// Encrypt and write the database back, replacing the file atomically
+    fn save(&self, db: &Database) -> Result<()> {
+        let mut content = Vec::new();
+        db.save(&mut content, self.key()?)
+            .context("Failed to encrypt KeePass database")?;
+        write_private_file(&self.path, content)
+            .with_context(|| format!("Failed to write KeePass database: {:?}", self.path))
+    }
+
+    /// Read the database under a shared lock
+    fn read<T>(&self, f: impl FnOnce(&Database) -> Result<T>) -> Result<T> {
+        let _lock = self.lock(false)?;
+        f(&self.open()?)
+    }
+
+    /// Change the database under an exclusive lock and save it
+    fn modify(&self, f: impl FnOnce(&mut Database) -> Result<()>) -> Result<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let _lock = self.lock(true)?;
+        let mut db = self.open()?;
+        f(&mut db)?;
+        self.save(&db)
+    }
+}
+
+/// Expand a leading `~` to the home directory
+fn expand_home(path: &Path) -> Result<PathBuf> {
+    match path.to_str().and_then(|p| p.strip_prefix('~')) {
+        Some(rest) => {
+            let home = std::env::var("HOME").context("HOME environment variable not set")?;
+            Ok(format!("{}{}", home, rest).into())
+        }
+        None => Ok(path.to_path_buf()),
+    }
+}
+
+/// Split a secret path into its group names and entry title
+fn split_path(path: &str) -> Result<(Vec<&str>, &str)> {
+    let mut parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    let title = parts
+        .pop()
+        .with_context(|| format!("Invalid secret path: {}", path))?;
+    Ok((parts, title))
+}
+
+fn find_group<'a>(group: &'a Group, name: &str) -> Option<&'a Group> {
+    group.children.iter().find_map(|node| match node {
+        Node::Group(child) if child.name == name => Some(child),
+        _ => None,
+    })
+}
+
+fn find_entry<'a>(group: &'a Group, title: &str) -> Option<&'a Entry> {
+    group.children.iter().find_map(|node| match node {
+        Node::Entry(entry) if entry.get_title() == Some(title) => Some(entry),
+        _ => None,
+    })
+}
+
+fn find_entry_mut<'a>(group: &'a mut Group, title: &str) -> Option<&'a mut Entry> {
+    group.children.iter_mut().find_map(|node| match node {
+        Node::Entry(entry) if entry.get_title() == Some(title) => Some(entry),
+        _ => None,
+    })
+}
+
+/// Entry at a secret path, if it exists
+fn entry<'a>(db: &'a Database, path: &str) -> Result<Option<&'a Entry>> {
+    let (groups, title) = split_path(path)?;
+    let mut group = &db.root;
+    for name in groups {
+        match find_group(group, name) {
+            Some(child) => group = child,
+            None => return Ok(None),
+        }
+    }
+    Ok(find_entry(group, title))
+}
+
+/// Entry at a secret path, creating it and its groups if needed
+fn entry_mut<'a>(db: &'a mut Database, path: &str) -> Result<&'a mut Entry> {
+    let (groups, title) = split_path(path)?;
+    let mut group = &mut db.root;
+    for name in groups {
+        let index = match group
+            .children
+            .iter()
+            .position(|node| matches!(node, Node::Group(child) if child.name == name))
+        {
+            Some(index) => index,
+            None => {
+                group.children.push(Node::Group(Group::new(name)));
+                group.children.len() - 1
+            }
+        };
+        group = match &mut group.children[index] {
+            Node::Group(child) => child,
+            Node::Entry(_) => unreachable!("index points at a group"),
+        };
+    }
+
+    if find_entry(group, title).is_none() {
+        let mut entry = Entry::new();
+        entry
+            .fields
+            .insert("Title".to_string(), Value::Unprotected(title.to_string()));
+        group.children.push(Node::Entry(entry));
+    }
+    Ok(find_entry_mut(group, title).expect("entry was just created"))
+}
+
+fn is_standard(field: &str) -> bool {
+    field == "Title" || STANDARD_FIELDS.iter().any(|(name, _)| *name == field)
+}
+
+/// Secret values of an entry: standard fields and protected custom fields
+fn entry_data(entry: &Entry) -> HashMap<String, String> {
+    let mut data = HashMap::new();
+    for (field, key) in STANDARD_FIELDS {
+        if let Some(value) = entry.get(field) {
+            data.insert(key.to_string(), value.to_string());
+        }
+    }
+    for (field, value) in &entry.fields {
+        if !is_standard(field) && matches!(value, Value::Protected(_)) {
+            if let Some(value) = entry.get(field) {
+                data.insert(field.clone(), value.to_string());
+            }
+        }
+    }
+    data
+}
+
+/// Rotation metadata of an entry: unprotected custom fields
+fn entry_metadata(entry: &Entry) -> HashMap<String, String> {
+    entry
+        .fields
+        .iter()
+        .filter_map(|(field, value)| match value {
+            Value::Unprotected(value) if !is_standard(field) => {
+                Some((field.clone(), value.clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Collect the paths of all entries under `group`
+fn collect_entries(group: &Group, prefix: &str, secrets: &mut Vec<String>) {
+    for node in &group.children {
+        match node {
+            Node::Group(child) => {
+                collect_entries(child, &format!("{}{}/", prefix, child.name), secrets)
+            }
+            Node::Entry(entry) => {
+                if let Some(title) = entry.get_title() {
+                    secrets.push(format!("{}{}", prefix, title));
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretBackend for KeePassBackend {
+    async fn read_secret(&self, path: &str) -> Result<SecretData> {
+        debug!("Reading secret from KeePass database: {}", path);
+
+        self.read(|db| {
+            let entry =
+                entry(db, path)?.with_context(|| format!("KeePass entry not found: {}", path))?;
+            Ok(SecretData {
+                data: entry_data(entry),
+                metadata: Some(entry_metadata(entry)),
+                version: None,
+            })
+        })
+    }
+
+    async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
+        info!("Writing secret to KeePass database: {}", path);
+
+        self.modify(|db| {
+            let entry = entry_mut(db, path)?;
+            entry.fields.retain(|field, value| {
+                field == "Title" || !(is_standard(field) || matches!(value, Value::Protected(_)))
+            });
+
+            for (key, value) in data {
+                match STANDARD_FIELDS.iter().find(|(_, k)| *k == key) {
+                    Some((field, "password")) => {
+                        entry.fields.insert(
+                            field.to_string(),
+                            Value::Protected(value.into_bytes().into()),
+                        );
+                    }
+                    Some((field, _)) => {
+                        entry
+                            .fields
+                            .insert(field.to_string(), Value::Unprotected(value));
+                    }
+                    None => {
+                        entry
+                            .fields
+                            .insert(key, Value::Protected(value.into_bytes().into()));
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    async fn update_metadata(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        debug!("Updating metadata for: {}", path);
+
+        self.modify(|db| {
+            let entry = entry_mut(db, path)?;
+            entry
+                .fields
+                .retain(|field, value| is_standard(field) || matches!(value, Value::Protected(_)));
+            for (key, value) in metadata {
+                if is_standard(&key) {
+                    anyhow::bail!("Metadata key '{}' is a standard KeePass field", key);
+                }
+                entry.fields.insert(key, Value::Unprotected(value));
+            }
+            Ok(())
+        })
+    }
+
+    async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>> {
+        self.read(|db| Ok(entry(db, path)?.map(entry_metadata).unwrap_or_default()))
+    }
+
+    async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
+        debug!("Listing KeePass entries at path: {}", path);
+
+        self.read(|db| {
+            let mut group = &db.root;
+            let mut prefix = String::new();
+            for name in path.split('/').filter(|p| !p.is_empty()) {
+                match find_group(group, name) {
+                    Some(child) => group = child,
+                    None => {
+                        // The path may name an entry rather than a group
+                        return Ok(entry(db, path)?
+                            .map(|_| vec![path.trim_matches('/').to_string()])
+                            .unwrap_or_default());
+                    }
+                }
+                prefix.push_str(name);
+                prefix.push('/');
+            }
+
+            let mut secrets = Vec::new();
+            collect_entries(group, &prefix, &mut secrets);
+            Ok(secrets)
+        })
+    }
+
+    fn backend_type(&self) -> &'static str {
+        "keepass"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepass::config::DatabaseConfig;
+    use tempfile::TempDir;
+
+    fn new_database(dir: &TempDir) -> Result<KeePassBackend> {
+        let path = dir.path().join("team.kdbx");
+        let mut file = File::create(&path)?;
+        Database::new(DatabaseConfig::default())
+            .save(&mut file, DatabaseKey::new().with_password("hunter2"))?;
+        KeePassBackend::new(&path, Some("hunter2".into()), None)
+    }
+
+    #[tokio::test]
+    async fn test_write_read_and_metadata() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = new_database(&temp_dir)?;
+
+        let data = HashMap::from([
+            ("password".to_string(), "s3cret".to_string()),
+            ("username".to_string(), "admin".to_string()),
+            ("api_token".to_string(), "t0ken".to_string()),
+        ]);
+        backend.write_secret("Team/db/prod", data.clone()).await?;
+
+        let metadata = HashMap::from([("rotation_enabled".to_string(), "true".to_string())]);
+        backend
+            .update_metadata("Team/db/prod", metadata.clone())
+            .await?;
+
+        let secret = backend.read_secret("Team/db/prod").await?;
+        assert_eq!(secret.data, data);
+        assert_eq!(secret.metadata, Some(metadata.clone()));
+
+        // Rewriting the secret keeps its metadata
+        backend.write_secret("Team/db/prod", data).await?;
+        assert_eq!(backend.read_metadata("Team/db/prod").await?, metadata);
+
+        assert_eq!(backend.list_secrets("").await?, vec!["Team/db/prod"]);
+        assert_eq!(backend.list_secrets("Team/db").await?, vec!["Team/db/prod"]);
+        assert!(backend.list_secrets("Other").await?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_password() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = new_database(&temp_dir)?.path;
+        assert!(KeePassBackend::new(&path, Some("wrong".into()), None).is_err());
+        assert!(KeePassBackend::new(&path, None, None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+use anyhow::Result;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::secret_backend::{SecretBackend, SecretData, SecretPage, VersionConflict};
+
+/// Backend operations that can be made to fail in a [`MemoryBackend`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Read,
+    Write,
+    ReadMetadata,
+    UpdateMetadata,
+    List,
+}
+
+#[derive(Debug, Clone, Default)]
+struct StoredSecret {
+    data: HashMap<String, String>,
+    metadata: HashMap<String, String>,
+    version: u64,
+}
+
+#[derive(Default)]
+struct State {
+    secrets: HashMap<String, StoredSecret>,
+    /// One-shot failures, consumed in order
+    fail_next: HashMap<Operation, VecDeque<String>>,
+    /// Failures returned on every call until cleared
+    fail_always: HashMap<Operation, String>,
+    calls: HashMap<Operation, usize>,
+    /// Values written by `stage_secret` when staging is enabled
+    staged: HashMap<String, HashMap<String, String>>,
+}
+
+/// In-memory backend for testing rotation flows without Vault or AWS
+///
+/// Secrets are versioned like Vault KV v2, so check-and-set writes behave as
+/// they would against a real server. Failures and latency can be injected per
+/// operation to exercise error handling (see [`MemoryBackend::fail_next`]).
+#[derive(Default)]
+pub struct MemoryBackend {
+    state: Mutex<State>,
+    latency: Option<Duration>,
+    page_size: Option<usize>,
+    staging: bool,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seed a secret
+    pub fn with_secret<K, V>(self, path: &str, data: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.insert_secret(path, data);
+        self
+    }
+
+    /// Delay every operation by `latency`
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Return listings `page_size` names at a time from `list_secrets_page`
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = Some(page_size.max(1));
+        self
+    }
+
+    /// Keep rotated values staged until promoted, like AWS's AWSPENDING
+    pub fn with_staging(mut self) -> Self {
+        self.staging = true;
+        self
+    }
+
+    /// Value staged for a secret and not yet promoted or discarded
+    pub fn staged(&self, path: &str) -> Option<HashMap<String, String>> {
+        self.lock().staged.get(path).cloned()
+    }
+
+    /// Store a secret, bumping its version
+    pub fn insert_secret<K, V>(&self, path: &str, data: impl IntoIterator<Item = (K, V)>)
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let mut state = self.lock();
+        let secret = state.secrets.entry(path.to_string()).or_default();
+        secret.data = data
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        secret.version += 1;
+    }
+
+    /// Current data of a secret, bypassing injected failures
+    pub fn secret(&self, path: &str) -> Option<HashMap<String, String>> {
+        self.lock().secrets.get(path).map(|s| s.data.clone())
+    }
+
+    /// Current metadata of a secret, bypassing injected failures
+    pub fn metadata(&self, path: &str) -> Option<HashMap<String, String>> {
+        self.lock().secrets.get(path).map(|s| s.metadata.clone())
+    }
+
+    /// Make the next call of `operation` fail with `message`
+    pub fn fail_next(&self, operation: Operation, message: impl Into<String>) {
+        self.lock()
+            .fail_next
+            .entry(operation)
+            .or_default()
+            .push_back(message.into());
+    }
+
+    /// Make every call of `operation` fail with `message` until cleared
+    pub fn fail_always(&self, operation: Operation, message: impl Into<String>) {
+        self.lock().fail_always.insert(operation, message.into());
+    }
+
+    /// Remove all injected failures
+    pub fn clear_failures(&self) {
+        let mut state = self.lock();
+        state.fail_next.clear();
+        state.fail_always.clear();
+    }
+
+    /// Number of times `operation` was called (including failed calls)
+    pub fn calls(&self, operation: Operation) -> usize {
+        self.lock().calls.get(&operation).copied().unwrap_or(0)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Apply latency, count the call, and return any injected failure
+    async fn enter(&self, operation: Operation) -> Result<()> {
+        if let Some(latency) = self.latency {
+            tokio::time::sleep(latency).await;
+        }
+
+        let mut state = self.lock();
+        *state.calls.entry(operation).or_default() += 1;
+
+        let injected = match state
+            .fail_next
+            .get_mut(&operation)
+            .and_then(VecDeque::pop_front)
+        {
+            Some(message) => Some(message),
+            None => state.fail_always.get(&operation).cloned(),
+        };
+        match injected {
+            Some(message) => anyhow::bail!("{} (injected {:?} failure)", message, operation),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretBackend for MemoryBackend {
+    async fn read_secret(&self, path: &str) -> Result<SecretData> {
+        self.enter(Operation::Read).await?;
+        let state = self.lock();
+        let secret = state
+            .secrets
+            .get(path)
+            .ok_or_else(|| anyhow::anyhow!("Secret not found: {}", path))?;
+
+        Ok(SecretData {
+            data: secret.data.clone(),
+            metadata: Some(secret.metadata.clone()),
+            version: Some(secret.version),
+        })
+    }
+
+    async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
+        self.enter(Operation::Write).await?;
+        self.insert_secret(path, data);
+        Ok(())
+    }
+
+    async fn write_secret_cas(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.enter(Operation::Write).await?;
+        let mut state = self.lock();
+        let secret = state.secrets.entry(path.to_string()).or_default();
+        if let Some(expected) = version {
+            if secret.version != expected {
+                return Err(VersionConflict {
+                    path: path.to_string(),
+                    expected,
+                }
+                .into());
+            }
+        }
+        secret.data = data;
+        secret.version += 1;
+        Ok(())
+    }
+
+    fn supports_cas(&self) -> bool {
+        true
+    }
+
+    fn stages_secrets(&self) -> bool {
+        self.staging
+    }
+
+    async fn stage_secret(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        if !self.staging {
+            return self.write_secret_cas(path, data, version).await;
+        }
+        self.enter(Operation::Write).await?;
+        let mut state = self.lock();
+        let current = state.secrets.get(path).map_or(0, |secret| secret.version);
+        if let Some(expected) = version.filter(|expected| *expected != current) {
+            return Err(VersionConflict {
+                path: path.to_string(),
+                expected,
+            }
+            .into());
+        }
+        state.staged.insert(path.to_string(), data);
+        Ok(())
+    }
+
+    async fn promote_staged_secret(&self, path: &str) -> Result<()> {
+        let staged = self.lock().staged.remove(path);
+        if let Some(data) = staged {
+            self.write_secret(path, data).await?;
+        }
+        Ok(())
+    }
+
+    async fn discard_staged_secret(&self, path: &str) -> Result<()> {
+        self.lock().staged.remove(path);
+        Ok(())
+    }
+
+    async fn update_metadata(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        self.enter(Operation::UpdateMetadata).await?;
+        self.lock()
+            .secrets
+            .entry(path.to_string())
+            .or_default()
+            .metadata = metadata;
+        Ok(())
+    }
+
+    async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>> {
+        self.enter(Operation::ReadMetadata).await?;
+        Ok(self.metadata(path).unwrap_or_default())
+    }
+
+    async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
+        self.enter(Operation::List).await?;
+        let prefix = path.trim_matches('/');
+        let mut secrets: Vec<String> = self
+            .lock()
+            .secrets
+            .keys()
+            .filter_map(|key| {
+                if prefix.is_empty() {
+                    Some(key.clone())
+                } else {
+                    key.strip_prefix(prefix)
+                        .and_then(|rest| rest.strip_prefix('/'))
+                        .map(str::to_string)
+                }
+            })
+            .collect();
+        secrets.sort();
+        Ok(secrets)
+    }
+
+    /// Pages of the sorted listing; the cursor is the index of the next name
+    async fn list_secrets_page(&self, path: &str, cursor: Option<&str>) -> Result<SecretPage> {
+        let mut names = self.list_secrets(path).await?;
+        let Some(page_size) = self.page_size else {
+            return Ok(SecretPage { names, next: None });
+        };
+
+        let start = match cursor {
+            Some(cursor) => cursor
+                .parse::<usize>()
+                .map_err(|_| anyhow::anyhow!("Invalid list cursor '{}'", cursor))?,
+            None => 0,
+        }
+        .min(names.len());
+        let end = (start + page_size).min(names.len());
+        let next = (end < names.len()).then(|| end.to_string());
+        Ok(SecretPage {
+            names: names.drain(start..end).collect(),
+            next,
+        })
+    }
+
+    fn backend_type(&self) -> &'static str {
+        "memory"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_backend_roundtrip_and_list() -> Result<()> {
+        let backend = MemoryBackend::new()
+            .with_secret("app/db", [("password", "p")])
+            .with_secret("app/api", [("token", "t")])
+            .with_secret("other", [("value", "v")]);
+
+        let secret = backend.read_secret("app/db").await?;
+        assert_eq!(secret.data.get("password"), Some(&"p".to_string()));
+        assert_eq!(secret.version, Some(1));
+
+        assert_eq!(backend.list_secrets("app").await?, vec!["api", "db"]);
+        assert_eq!(backend.list_secrets("").await?.len(), 3);
+        assert!(backend.read_secret("missing").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_injected_failures() -> Result<()> {
+        let backend = MemoryBackend::new().with_latency(Duration::from_millis(1));
+        backend.fail_next(Operation::Write, "boom");
+
+        let data = HashMap::from([("k".to_string(), "v".to_string())]);
+        assert!(backend.write_secret("a", data.clone()).await.is_err());
+        backend.write_secret("a", data.clone()).await?;
+        assert_eq!(backend.calls(Operation::Write), 2);
+
+        backend.fail_always(Operation::ReadMetadata, "down");
+        assert!(backend.read_metadata("a").await.is_err());
+        assert!(backend.read_metadata("a").await.is_err());
+        backend.clear_failures();
+        assert!(backend.read_metadata("a").await.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cas_conflict() -> Result<()> {
+        let backend = MemoryBackend::new().with_secret("a", [("k", "v1")]);
+        let data = HashMap::from([("k".to_string(), "v2".to_string())]);
+
+        let err = backend
+            .write_secret_cas("a", data.clone(), Some(7))
+            .await
+            .unwrap_err();
+        assert!(err.is::<VersionConflict>());
+
+        backend.write_secret_cas("a", data, Some(1)).await?;
+        assert_eq!(backend.secret("a").unwrap()["k"], "v2");
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use tracing::{debug, warn};
+
+use super::secret_backend::{SecretBackend, SecretData};
+use super::{Backend, SecretPage, SecretVersion};
+
+/// Backend that keeps the same secrets in several backends
+///
+/// Used for secrets copied to several AWS regions for disaster recovery:
+/// reads and listings come from the first (primary) backend, and every write
+/// goes to all of them, so a rotated value reaches every copy. Once the
+/// primary is written, every other copy is attempted even if one fails, and
+/// the error names each copy that was not updated.
+pub struct MirroredBackend {
+    /// Backends with the name used for them in errors (e.g., their region)
+    backends: Vec<(String, Backend)>,
+}
+
+impl MirroredBackend {
+    /// Create a mirrored backend; the first backend is the primary
+    pub fn new(backends: Vec<(String, Backend)>) -> Result<Self> {
+        if backends.is_empty() {
+            anyhow::bail!("A mirrored backend needs at least one backend");
+        }
+        Ok(Self { backends })
+    }
+
+    fn primary(&self) -> &Backend {
+        &self.backends[0].1
+    }
+
+    /// Run a write on every copy, reporting the copies it failed on
+    ///
+    /// `write` gets whether it is writing the primary, which is written
+    /// first. If the primary fails, the copies are left alone so they keep
+    /// matching it.
+    async fn write_all<'a, F>(&'a self, path: &'a str, write: F) -> Result<()>
+    where
+        F: Fn(bool, &'a Backend) -> WriteFuture<'a>,
+    {
+        let (primary_name, primary) = &self.backends[0];
+        write(true, primary)
+            .await
+            .map_err(|e| e.context(format!("Failed to update {} in {}", path, primary_name)))?;
+
+        let mut failed = Vec::new();
+        for (name, backend) in &self.backends[1..] {
+            match write(false, backend).await {
+                Ok(()) => debug!("Updated {} in {}", path, name),
+                Err(e) => {
+                    warn!("Failed to update {} in {}: {:#}", path, name, e);
+                    failed.push(format!("{}: {:#}", name, e));
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            anyhow::bail!(
+                "Updated {} in {} but not in {}",
+                path,
+                primary_name,
+                failed.join("; ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Future returned by the backend write methods
+type WriteFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+#[async_trait::async_trait]
+impl SecretBackend for MirroredBackend {
+    async fn read_secret(&self, path: &str) -> Result<SecretData> {
+        self.primary().read_secret(path).await
+    }
+
+    async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
+        self.write_all(path, |_, b| b.write_secret(path, data.clone()))
+            .await
+    }
+
+    /// The primary decides check-and-set writes
+    fn supports_cas(&self) -> bool {
+        self.backends[0].1.supports_cas()
+    }
+
+    fn stages_secrets(&self) -> bool {
+        self.backends
+            .iter()
+            .any(|(_, backend)| backend.stages_secrets())
+    }
+
+    /// Check-and-set on the primary; the copies follow it unconditionally
+    async fn write_secret_cas(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.write_all(path, |primary, b| {
+            if primary {
+                b.write_secret_cas(path, data.clone(), version)
+            } else {
+                b.write_secret(path, data.clone())
+            }
+        })
+        .await
+    }
+
+    async fn stage_secret(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.write_all(path, |primary, b| {
+            let version = if primary { version } else { None };
+            b.stage_secret(path, data.clone(), version)
+        })
+        .await
+    }
+
+    async fn promote_staged_secret(&self, path: &str) -> Result<()> {
+        self.write_all(path, |_, b| b.promote_staged_secret(path))
+            .await
+    }
+
+    /// Discard everywhere, even where staging failed, and report the first error
+    async fn discard_staged_secret(&self, path: &str) -> Result<()> {
+        let mut result = Ok(());
+        for (name, backend) in &self.backends {
+            if let Err(e) = backend.discard_staged_secret(path).await {
+                warn!("Failed to discard staged {} in {}: {:#}", path, name, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    async fn update_metadata(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        self.write_all(path, |_, b| b.update_metadata(path, metadata.clone()))
+            .await
+    }
+
+    async fn remove_metadata(&self, path: &str, keys: &[String]) -> Result<()> {
+        self.write_all(path, |_, b| b.remove_metadata(path, keys))
+            .await
+    }
+
+    async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>> {
+        self.primary().read_metadata(path).await
+    }
+
+    async fn read_metadata_batch(&self, paths: &[String]) -> Vec<Result<HashMap<String, String>>> {
+        self.primary().read_metadata_batch(paths).await
+    }
+
+    async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
+        self.primary().list_secrets(path).await
+    }
+
+    async fn list_secrets_page(&self, path: &str, cursor: Option<&str>) -> Result<SecretPage> {
+        self.primary().list_secrets_page(path, cursor).await
+    }
+
+    async fn list_versions(&self, path: &str) -> Result<Vec<SecretVersion>> {
+        self.primary().list_versions(path).await
+    }
+
+    async fn read_version(&self, path: &str, version: u64) -> Result<SecretData> {
+        self.primary().read_version(path, version).await
+    }
+
+    fn backend_type(&self) -> &'static str {
+        self.primary().backend_type()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::{FileBackend, MemoryBackend, Operation};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_writes_reach_every_copy() -> Result<()> {
+        let east = TempDir::new()?;
+        let west = TempDir::new()?;
+        let mirrored = MirroredBackend::new(vec![
+            (
+                "us-east-1".to_string(),
+                Box::new(FileBackend::new(east.path())?),
+            ),
+            (
+                "us-west-2".to_string(),
+                Box::new(FileBackend::new(west.path())?),
+            ),
+        ])?;
+
+        let data = HashMap::from([("password".to_string(), "p".to_string())]);
+        mirrored.write_secret("app/db", data.clone()).await?;
+        mirrored
+            .update_metadata(
+                "app/db",
+                HashMap::from([("rotation_enabled".to_string(), "true".to_string())]),
+            )
+            .await?;
+
+        let west_backend = FileBackend::new(west.path())?;
+        assert_eq!(west_backend.read_secret("app/db").await?.data, data);
+        assert_eq!(
+            west_backend.read_metadata("app/db").await?["rotation_enabled"],
+            "true"
+        );
+        assert_eq!(mirrored.list_secrets("").await?, vec!["app/db"]);
+
+        assert!(MirroredBackend::new(Vec::new()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_copy_is_reported() -> Result<()> {
+        let west = MemoryBackend::new();
+        west.fail_next(Operation::Write, "region unavailable");
+        let mirrored = MirroredBackend::new(vec![
+            ("us-east-1".to_string(), Box::new(MemoryBackend::new())),
+            ("us-west-2".to_string(), Box::new(west)),
+        ])?;
+
+        let data = HashMap::from([("password".to_string(), "p".to_string())]);
+        let err = mirrored.write_secret("app/db", data).await.unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("us-west-2"), "{}", message);
+        assert!(message.contains("region unavailable"), "{}", message);
+
+        // The primary was still updated
+        assert!(mirrored.read_secret("app/db").await.is_ok());
+        Ok(())
+    }
+}
@@ -3,22 +3,41 @@
 //! This module provides abstractions and implementations for different secret backends.
 
 mod aws_secrets;
+mod cached;
 mod file;
+#[cfg(feature = "keepass")]
+mod kdbx;
+#[cfg(any(test, feature = "test-util"))]
+mod memory;
+mod mirrored;
+mod pass;
+mod routed;
 mod secret_backend;
 mod vault;
 
 pub use aws_secrets::AwsSecretsClient;
+pub use cached::CachedBackend;
 pub use file::FileBackend;
-pub use secret_backend::SecretBackend;
-pub use vault::{VaultBackend, VaultClient};
+#[cfg(feature = "keepass")]
+pub use kdbx::KeePassBackend;
+#[cfg(any(test, feature = "test-util"))]
+pub use memory::{MemoryBackend, Operation};
+pub use mirrored::MirroredBackend;
+pub use pass::PassBackend;
+pub use routed::{path_matches, BackendRoute, RoutedBackend};
+pub use secret_backend::{
+    is_listed, SecretBackend, SecretData, SecretPage, SecretVersion, VersionConflict,
+};
+pub use vault::{LeaseInfo, VaultBackend, VaultClient, VaultFlavor};
 
 /// Backend type enumeration
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[allow(dead_code)] // Reserved for future use (e.g., type-safe backend selection)
 pub enum BackendType {
     Vault,
     Aws,
     File,
+    Pass,
+    KeePass,
 }
 
 impl std::str::FromStr for BackendType {
@@ -29,8 +48,10 @@ impl std::str::FromStr for BackendType {
             "vault" => Ok(BackendType::Vault),
             "aws" => Ok(BackendType::Aws),
             "file" => Ok(BackendType::File),
+            "pass" => Ok(BackendType::Pass),
+            "keepass" => Ok(BackendType::KeePass),
             _ => Err(format!(
-                "Unknown backend type: {}. Supported: vault, aws, file",
+                "Unknown backend type: {}. Supported: vault, aws, file, pass, keepass",
                 s
             )),
         }
@@ -0,0 +1,454 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info};
+
+use super::file::{create_private_dir, write_private_file, FileBackend};
+use super::secret_backend::{SecretBackend, SecretData};
+
+/// Key the first line of an entry is exposed under by default
+const DEFAULT_VALUE_KEY: &str = "password";
+
+/// Backend for a `pass`/gopass password store
+///
+/// Each secret is a GPG-encrypted `<secret>.gpg` file, encrypted to the keys
+/// listed in the nearest `.gpg-id` file, as `pass insert` would write it. The
+/// first line of an entry is the password and is exposed under `value_key`;
+/// following `key: value` lines whose key is a plain identifier become the
+/// other keys. Other lines (notes, URLs, `otpauth://` URIs) are kept verbatim
+/// and in place when the entry is rewritten.
+///
+/// Rotation metadata goes in a plain-text `<secret>.meta` sidecar next to the
+/// entry, in the same `key:value` format as the file backend. `pass` ignores
+/// it, and scans can read it without decrypting anything. Encryption and
+/// decryption are done by the `gpg` command-line tool, so the usual agent
+/// and pinentry setup applies.
+pub struct PassBackend {
+    store_dir: PathBuf,
+    value_key: String,
+}
+
+impl PassBackend {
+    /// Create a backend for the password store at `store_dir`
+    pub fn new(store_dir: impl AsRef<Path>) -> Result<Self> {
+        let mut store_dir = store_dir.as_ref().to_path_buf();
+
+        // Expand tilde (~) to home directory
+        if let Some(store_dir_str) = store_dir.to_str() {
+            if store_dir_str.starts_with("~") {
+                let home = std::env::var("HOME").context("HOME environment variable not set")?;
+                store_dir = store_dir_str.replacen("~", &home, 1).into();
+            }
+        }
+
+        if !store_dir.is_dir() {
+            anyhow::bail!(
+                "Password store not found: {:?}. Create it with `pass init <gpg-id>`",
+                store_dir
+            );
+        }
+
+        Ok(Self {
+            store_dir,
+            value_key: DEFAULT_VALUE_KEY.to_string(),
+        })
+    }
+
+    /// Set the key under which the first line of an entry is exposed
+    pub fn with_value_key(mut self, value_key: impl Into<String>) -> Self {
+        self.value_key = value_key.into();
+        self
+    }
+
+    /// Encrypted entry for a secret path
+    /// Example: "email/work" -> store_dir/email/work.gpg
+    fn entry_path(&self, path: &str) -> PathBuf {
+        let mut entry = self.store_dir.join(path).into_os_string();
+        entry.push(".gpg");
+        entry.into()
+    }
+
+    /// Plain-text metadata sidecar for a secret path
+    fn metadata_path(&self, path: &str) -> PathBuf {
+        let mut meta_path = self.store_dir.join(path).into_os_string();
+        meta_path.push(".meta");
+        meta_path.into()
+    }
+
+    /// Keys an entry is encrypted to, from the nearest `.gpg-id` at or above it
+    fn recipients(&self, entry: &Path) -> Result<Vec<String>> {
+        let mut dir = entry.parent();
+        while let Some(current) = dir {
+            let gpg_id = current.join(".gpg-id");
+            if gpg_id.is_file() {
+                let content = fs::read_to_string(&gpg_id)
+                    .with_context(|| format!("Failed to read {:?}", gpg_id))?;
+                let ids: Vec<String> = content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string)
+                    .collect();
+                if ids.is_empty() {
+                    anyhow::bail!("{:?} lists no GPG keys", gpg_id);
+                }
+                return Ok(ids);
+            }
+            if current == self.store_dir {
+                break;
+            }
+            dir = current.parent();
+        }
+
+        anyhow::bail!(
+            "No .gpg-id found for {:?}. Initialize the store with `pass init <gpg-id>`",
+            entry
+        )
+    }
+
+    /// Split a decrypted entry into the password and its `key: value` lines
+    fn parse_entry(&self, content: &str) -> HashMap<String, String> {
+        let mut lines = content.lines();
+        let mut data = HashMap::new();
+        data.insert(
+            self.value_key.clone(),
+            lines.next().unwrap_or_default().to_string(),
+        );
+
+        for (key, value) in lines.filter_map(parse_field) {
+            if key != self.value_key {
+                data.insert(key.to_string(), value.to_string());
+            }
+        }
+        data
+    }
+
+    /// Format data as an entry: the password first, then the lines of the
+    /// `existing` entry with their fields updated (or dropped if no longer in
+    /// `data`), then any new keys sorted
+    fn format_entry(&self, data: &HashMap<String, String>, existing: Option<&str>) -> String {
+        let mut content = data.get(&self.value_key).cloned().unwrap_or_default();
+        content.push('\n');
+
+        let mut written = HashSet::new();
+        for line in existing.into_iter().flat_map(|e| e.lines().skip(1)) {
+            match parse_field(line) {
+                Some((key, _)) if key == self.value_key => {}
+                Some((key, _)) => {
+                    if let Some(value) = data.get(key).filter(|_| written.insert(key)) {
+                        content.push_str(&format!("{}: {}\n", key, value));
+                    }
+                }
+                None => {
+                    content.push_str(line);
+                    content.push('\n');
+                }
+            }
+        }
+
+        let mut keys: Vec<&String> = data
+            .keys()
+            .filter(|k| **k != self.value_key && !written.contains(k.as_str()))
+            .collect();
+        keys.sort();
+        for key in keys {
+            content.push_str(&format!("{}: {}\n", key, data[key]));
+        }
+        content
+    }
+
+    /// Decrypt the entry at `entry`, if it exists
+    async fn decrypt_entry(&self, entry: &Path) -> Result<Option<String>> {
+        let ciphertext = match fs::read(entry) {
+            Ok(ciphertext) => ciphertext,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", entry)),
+        };
+
+        let plaintext = run_gpg(&["--decrypt"], &ciphertext)
+            .await
+            .with_context(|| format!("Failed to decrypt {:?}", entry))?;
+        let content = String::from_utf8(plaintext)
+            .with_context(|| format!("Entry {:?} is not valid UTF-8", entry))?;
+        Ok(Some(content))
+    }
+}
+
+/// A `key: value` line whose key is a plain identifier
+///
+/// Lines such as `https://example.com` or `otpauth://totp/...` contain a colon
+/// but are not fields.
+fn parse_field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim();
+    let is_identifier = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !is_identifier || !(value.is_empty() || value.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+/// Run `gpg` with `input` on stdin, returning its stdout
+async fn run_gpg(args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    let mut child = match tokio::process::Command::new("gpg")
+        .args(["--quiet", "--batch", "--yes"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => anyhow::bail!("gpg is not installed"),
+        Err(e) => return Err(e).context("Failed to run gpg"),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input)
+            .await
+            .context("Failed to write to gpg")?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .await
+        .context("Failed to wait for gpg")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "gpg exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[async_trait::async_trait]
+impl SecretBackend for PassBackend {
+    async fn read_secret(&self, path: &str) -> Result<SecretData> {
+        debug!("Reading secret from password store: {}", path);
+
+        let entry = self.entry_path(path);
+        let content = self
+            .decrypt_entry(&entry)
+            .await?
+            .with_context(|| format!("Password store entry not found: {:?}", entry))?;
+
+        let metadata = FileBackend::read_entries(&self.metadata_path(path))
+            .ok()
+            .map(Option::unwrap_or_default);
+
+        Ok(SecretData {
+            data: self.parse_entry(&content),
+            metadata,
+            version: None,
+        })
+    }
+
+    async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
+        info!("Writing secret to password store: {}", path);
+
+        let entry = self.entry_path(path);
+        if let Some(parent) = entry.parent() {
+            create_private_dir(parent)
+                .with_context(|| format!("Failed to create parent directory: {:?}", parent))?;
+        }
+
+        let mut args = vec!["--encrypt", "--trust-model", "always"];
+        let recipients = self.recipients(&entry)?;
+        for recipient in &recipients {
+            args.push("--recipient");
+            args.push(recipient);
+        }
+
+        // Lines that are not fields are carried over from the current entry
+        let existing = self.decrypt_entry(&entry).await?;
+        let content = self.format_entry(&data, existing.as_deref());
+        let ciphertext = run_gpg(&args, content.as_bytes())
+            .await
+            .with_context(|| format!("Failed to encrypt {:?}", entry))?;
+        write_private_file(&entry, ciphertext)
+            .with_context(|| format!("Failed to write password store entry: {:?}", entry))?;
+
+        debug!("Successfully wrote secret to: {:?}", entry);
+        Ok(())
+    }
+
+    async fn update_metadata(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        debug!("Updating metadata for: {}", path);
+
+        let meta_path = self.metadata_path(path);
+        if let Some(parent) = meta_path.parent() {
+            create_private_dir(parent)
+                .with_context(|| format!("Failed to create parent directory: {:?}", parent))?;
+        }
+
+        let mut content = String::new();
+        content.push_str("# Rotation metadata - Auto-generated by asr\n");
+        content.push_str("# Format: key:value (one per line)\n\n");
+
+        for (key, value) in &metadata {
+            content.push_str(&FileBackend::format_line(key, value));
+        }
+
+        write_private_file(&meta_path, content)
+            .with_context(|| format!("Failed to write metadata file: {:?}", meta_path))
+    }
+
+    async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>> {
+        let meta_path = self.metadata_path(path);
+        let metadata = FileBackend::read_entries(&meta_path)
+            .with_context(|| format!("Failed to read metadata file: {:?}", meta_path))?;
+
+        Ok(metadata.unwrap_or_default())
+    }
+
+    async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
+        debug!("Listing password store entries at path: {}", path);
+
+        let dir_path = self.store_dir.join(path);
+        if !path.is_empty() && self.entry_path(path).is_file() {
+            return Ok(vec![path.to_string()]);
+        }
+        if !dir_path.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut secrets = Vec::new();
+        let entries = fs::read_dir(&dir_path)
+            .with_context(|| format!("Failed to read directory: {:?}", dir_path))?;
+
+        for entry in entries {
+            let entry = entry.context("Failed to read directory entry")?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            // Skip .gpg-id, .git and temporary files
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let sub_path = if path.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", path.trim_end_matches('/'), name)
+            };
+
+            let file_type = entry
+                .file_type()
+                .context("Failed to read directory entry")?;
+            if file_type.is_dir() {
+                secrets.extend(self.list_secrets(&sub_path).await?);
+            } else if let Some(secret) = sub_path.strip_suffix(".gpg") {
+                secrets.push(secret.to_string());
+            }
+        }
+
+        Ok(secrets)
+    }
+
+    fn backend_type(&self) -> &'static str {
+        "pass"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_and_format_entry() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = PassBackend::new(temp_dir.path())?;
+
+        let entry = "hunter2\nusername: admin\nurl: https://example.com\nnotes\n\
+                     otpauth://totp/app?secret=ABC\nhttps://example.com/login\n";
+        let mut data = backend.parse_entry(entry);
+        assert_eq!(data["password"], "hunter2");
+        assert_eq!(data["username"], "admin");
+        assert_eq!(data["url"], "https://example.com");
+        assert_eq!(data.len(), 3);
+
+        assert_eq!(
+            backend.format_entry(&data, None),
+            "hunter2\nurl: https://example.com\nusername: admin\n"
+        );
+
+        // Rewriting keeps other lines verbatim and in place
+        data.insert("password".to_string(), "new-pass".to_string());
+        data.remove("url");
+        data.insert("email".to_string(), "admin@example.com".to_string());
+        assert_eq!(
+            backend.format_entry(&data, Some(entry)),
+            "new-pass\nusername: admin\nnotes\notpauth://totp/app?secret=ABC\n\
+             https://example.com/login\nemail: admin@example.com\n"
+        );
+
+        let backend = backend.with_value_key("token");
+        assert_eq!(backend.parse_entry("abc\n")["token"], "abc");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recipients_from_nearest_gpg_id() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = PassBackend::new(temp_dir.path())?;
+        fs::write(temp_dir.path().join(".gpg-id"), "me@example.com\n")?;
+        fs::create_dir_all(temp_dir.path().join("work/db"))?;
+        fs::write(
+            temp_dir.path().join("work/.gpg-id"),
+            "me@example.com\nteam@example.com\n",
+        )?;
+
+        assert_eq!(
+            backend.recipients(&backend.entry_path("personal/email"))?,
+            vec!["me@example.com"]
+        );
+        assert_eq!(
+            backend.recipients(&backend.entry_path("work/db/prod"))?,
+            vec!["me@example.com", "team@example.com"]
+        );
+
+        fs::remove_file(temp_dir.path().join(".gpg-id"))?;
+        assert!(backend.recipients(&backend.entry_path("email")).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_and_metadata() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = PassBackend::new(temp_dir.path())?;
+        fs::write(temp_dir.path().join(".gpg-id"), "me@example.com\n")?;
+        fs::create_dir_all(temp_dir.path().join("web/.git"))?;
+        fs::write(temp_dir.path().join("email.gpg"), b"")?;
+        fs::write(temp_dir.path().join("web/github.gpg"), b"")?;
+
+        let metadata = HashMap::from([("rotation_enabled".to_string(), "true".to_string())]);
+        backend
+            .update_metadata("web/github", metadata.clone())
+            .await?;
+        assert!(temp_dir.path().join("web/github.meta").exists());
+        assert_eq!(backend.read_metadata("web/github").await?, metadata);
+        assert!(backend.read_metadata("email").await?.is_empty());
+
+        let mut secrets = backend.list_secrets("").await?;
+        secrets.sort();
+        assert_eq!(secrets, vec!["email", "web/github"]);
+        assert_eq!(backend.list_secrets("web").await?, vec!["web/github"]);
+
+        Ok(())
+    }
+}
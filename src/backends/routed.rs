@@ -0,0 +1,455 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use tracing::debug;
+
+use super::secret_backend::{SecretBackend, SecretData};
+use super::{Backend, SecretPage, SecretVersion};
+use crate::transfer;
+
+/// A routing rule sending secret paths that match `pattern` to a named backend
+///
+/// Written as `<backend>:<pattern>` in config, e.g. `vault:prod/*` or
+/// `aws:lambda/*`. A trailing `*` matches everything under the prefix; any
+/// other pattern matches that exact path and everything below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendRoute {
+    pub backend: String,
+    pub pattern: String,
+}
+
+impl BackendRoute {
+    /// Prefix covered by this route, without a trailing `*` or `/`
+    fn prefix(&self) -> &str {
+        self.pattern.trim_end_matches('*').trim_end_matches('/')
+    }
+
+    /// Check whether a secret path is routed by this rule
+    pub fn matches(&self, path: &str) -> bool {
+        path_matches(&self.pattern, path)
+    }
+
+    /// Check whether listing `path` could return secrets routed by this rule
+    fn overlaps(&self, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        let prefix = self.prefix();
+        prefix.starts_with(path) || self.matches(path)
+    }
+}
+
+/// Check whether a secret path matches a route-style pattern
+///
+/// A trailing `*` matches everything under the prefix (`prod/*`) or every path
+/// starting with it (`app-*`); any other pattern matches that exact path and
+/// everything below it.
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let path = path.trim_matches('/');
+    let prefix = pattern.trim_end_matches('*').trim_end_matches('/');
+
+    if pattern.ends_with('*') && !pattern.ends_with("/*") {
+        // Plain prefix glob such as "app-*"
+        return path.starts_with(prefix);
+    }
+
+    prefix.is_empty()
+        || path == prefix
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+impl std::str::FromStr for BackendRoute {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (backend, pattern) = s.split_once(':').ok_or_else(|| {
+            format!(
+                "Invalid route '{}'. Expected <backend>:<pattern>, e.g. vault:prod/*",
+                s
+            )
+        })?;
+
+        let backend = backend.trim();
+        let pattern = pattern.trim().trim_start_matches('/');
+        if backend.is_empty() || pattern.is_empty() {
+            return Err(format!(
+                "Invalid route '{}': backend and pattern are required",
+                s
+            ));
+        }
+
+        Ok(Self {
+            backend: backend.to_lowercase(),
+            pattern: pattern.to_string(),
+        })
+    }
+}
+
+/// Backend that dispatches each secret path to one of several backends
+///
+/// Routes are checked in order and the first match wins; paths without a
+/// matching route go to the default backend. Listing merges results from
+/// every backend that can hold secrets under the listed path.
+pub struct RoutedBackend {
+    backends: HashMap<String, Backend>,
+    routes: Vec<BackendRoute>,
+    default: String,
+}
+
+impl RoutedBackend {
+    /// Create a routed backend from named backends and routing rules
+    pub fn new(
+        backends: HashMap<String, Backend>,
+        routes: Vec<BackendRoute>,
+        default: impl Into<String>,
+    ) -> Result<Self> {
+        let default = default.into();
+        if !backends.contains_key(&default) {
+            anyhow::bail!("Default backend '{}' is not configured", default);
+        }
+        for route in &routes {
+            if !backends.contains_key(&route.backend) {
+                anyhow::bail!(
+                    "Route '{}:{}' refers to backend '{}', which is not configured",
+                    route.backend,
+                    route.pattern,
+                    route.backend
+                );
+            }
+        }
+
+        Ok(Self {
+            backends,
+            routes,
+            default,
+        })
+    }
+
+    /// Name of the backend responsible for a secret path
+    pub fn backend_name_for(&self, path: &str) -> &str {
+        self.routes
+            .iter()
+            .find(|route| route.matches(path))
+            .map(|route| route.backend.as_str())
+            .unwrap_or(&self.default)
+    }
+
+    fn backend_for(&self, path: &str) -> &dyn SecretBackend {
+        let name = self.backend_name_for(path);
+        debug!("Routing {} to {} backend", path, name);
+        self.backends[name].as_ref()
+    }
+
+    /// Names of the backends that may hold secrets under `path`, in listing order
+    fn backends_listing(&self, path: &str) -> Vec<&String> {
+        let mut names: Vec<&String> = self
+            .routes
+            .iter()
+            .filter(|route| route.overlaps(path))
+            .map(|route| &route.backend)
+            .chain(std::iter::once(&self.default))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Keep only the entries listed by backend `name` that it is actually
+    /// responsible for
+    fn owned_by(&self, name: &str, path: &str, listed: Vec<String>) -> Vec<String> {
+        listed
+            .into_iter()
+            .filter(|secret| self.backend_name_for(&transfer::child_path(path, secret)) == name)
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretBackend for RoutedBackend {
+    async fn read_secret(&self, path: &str) -> Result<SecretData> {
+        self.backend_for(path).read_secret(path).await
+    }
+
+    async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
+        self.backend_for(path).write_secret(path, data).await
+    }
+
+    async fn write_secret_cas(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.backend_for(path)
+            .write_secret_cas(path, data, version)
+            .await
+    }
+
+    fn supports_cas(&self) -> bool {
+        self.backends.values().all(|backend| backend.supports_cas())
+    }
+
+    fn stages_secrets(&self) -> bool {
+        self.backends
+            .values()
+            .any(|backend| backend.stages_secrets())
+    }
+
+    async fn stage_secret(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.backend_for(path)
+            .stage_secret(path, data, version)
+            .await
+    }
+
+    async fn promote_staged_secret(&self, path: &str) -> Result<()> {
+        self.backend_for(path).promote_staged_secret(path).await
+    }
+
+    async fn discard_staged_secret(&self, path: &str) -> Result<()> {
+        self.backend_for(path).discard_staged_secret(path).await
+    }
+
+    async fn update_metadata(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        self.backend_for(path).update_metadata(path, metadata).await
+    }
+
+    async fn remove_metadata(&self, path: &str, keys: &[String]) -> Result<()> {
+        self.backend_for(path).remove_metadata(path, keys).await
+    }
+
+    async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>> {
+        self.backend_for(path).read_metadata(path).await
+    }
+
+    /// One batch per backend, with the results put back in the order asked for
+    async fn read_metadata_batch(&self, paths: &[String]) -> Vec<Result<HashMap<String, String>>> {
+        let mut batches: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, path) in paths.iter().enumerate() {
+            batches
+                .entry(self.backend_name_for(path))
+                .or_default()
+                .push(index);
+        }
+
+        let mut results: Vec<Option<Result<HashMap<String, String>>>> =
+            paths.iter().map(|_| None).collect();
+        for (name, indexes) in batches {
+            let batch: Vec<String> = indexes.iter().map(|&i| paths[i].clone()).collect();
+            let read = self.backends[name].read_metadata_batch(&batch).await;
+            for (index, result) in indexes.into_iter().zip(read) {
+                results[index] = Some(result);
+            }
+        }
+        results
+            .into_iter()
+            .zip(paths)
+            .map(|(result, path)| {
+                result.unwrap_or_else(|| {
+                    Err(anyhow::anyhow!("Backend returned no metadata for {}", path))
+                })
+            })
+            .collect()
+    }
+
+    async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
+        let mut secrets = Vec::new();
+        for name in self.backends_listing(path) {
+            let listed = self.backends[name]
+                .list_secrets(path)
+                .await
+                .with_context(|| format!("Failed to list secrets from {} backend", name))?;
+            for secret in self.owned_by(name, path, listed) {
+                if !secrets.contains(&secret) {
+                    secrets.push(secret);
+                }
+            }
+        }
+
+        Ok(secrets)
+    }
+
+    /// Pages of each backend in turn
+    ///
+    /// The cursor is the index of the backend being listed, followed by
+    /// `:` and that backend's own cursor once it is past its first page.
+    async fn list_secrets_page(&self, path: &str, cursor: Option<&str>) -> Result<SecretPage> {
+        let names = self.backends_listing(path);
+        let (index, inner) = match cursor {
+            None => (0, None),
+            Some(cursor) => {
+                let (index, inner) = match cursor.split_once(':') {
+                    Some((index, inner)) => (index, Some(inner)),
+                    None => (cursor, None),
+                };
+                let index = index
+                    .parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Invalid list cursor '{}'", cursor))?;
+                (index, inner)
+            }
+        };
+        let Some(&name) = names.get(index) else {
+            return Ok(SecretPage::default());
+        };
+
+        let page = self.backends[name]
+            .list_secrets_page(path, inner)
+            .await
+            .with_context(|| format!("Failed to list secrets from {} backend", name))?;
+        let next = match page.next {
+            Some(inner) => Some(format!("{}:{}", index, inner)),
+            None if index + 1 < names.len() => Some((index + 1).to_string()),
+            None => None,
+        };
+        Ok(SecretPage {
+            names: self.owned_by(name, path, page.names),
+            next,
+        })
+    }
+
+    async fn list_versions(&self, path: &str) -> Result<Vec<SecretVersion>> {
+        self.backend_for(path).list_versions(path).await
+    }
+
+    async fn read_version(&self, path: &str, version: u64) -> Result<SecretData> {
+        self.backend_for(path).read_version(path, version).await
+    }
+
+    fn backend_type(&self) -> &'static str {
+        "routed"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::FileBackend;
+    use tempfile::TempDir;
+
+    fn route(spec: &str) -> BackendRoute {
+        spec.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_route() {
+        assert_eq!(
+            route("vault:prod/*"),
+            BackendRoute {
+                backend: "vault".to_string(),
+                pattern: "prod/*".to_string()
+            }
+        );
+        assert!("prod/*".parse::<BackendRoute>().is_err());
+        assert!("aws:".parse::<BackendRoute>().is_err());
+    }
+
+    #[test]
+    fn test_route_matches() {
+        let prod = route("vault:prod/*");
+        assert!(prod.matches("prod/db"));
+        assert!(prod.matches("prod"));
+        assert!(!prod.matches("production/db"));
+
+        let exact = route("aws:shared/token");
+        assert!(exact.matches("shared/token"));
+        assert!(!exact.matches("shared/token2"));
+
+        let glob = route("file:app-*");
+        assert!(glob.matches("app-web/key"));
+        assert!(!glob.matches("web/app-key"));
+    }
+
+    #[tokio::test]
+    async fn test_routed_read_write_and_list() -> Result<()> {
+        let vault_dir = TempDir::new()?;
+        let aws_dir = TempDir::new()?;
+
+        let mut backends: HashMap<String, Backend> = HashMap::new();
+        backends.insert(
+            "vault".to_string(),
+            Box::new(FileBackend::new(vault_dir.path())?),
+        );
+        backends.insert(
+            "aws".to_string(),
+            Box::new(FileBackend::new(aws_dir.path())?),
+        );
+        let routed = RoutedBackend::new(backends, vec![route("aws:lambda/*")], "vault")?;
+
+        let data = HashMap::from([("password".to_string(), "p".to_string())]);
+        routed.write_secret("lambda/fn", data.clone()).await?;
+        routed.write_secret("prod/db", data.clone()).await?;
+
+        assert!(aws_dir.path().join("lambda/fn").exists());
+        assert!(vault_dir.path().join("prod/db").exists());
+        assert_eq!(routed.read_secret("lambda/fn").await?.data, data);
+
+        let mut listed = routed.list_secrets("").await?;
+        listed.sort();
+        assert_eq!(listed, vec!["lambda/fn", "prod/db"]);
+
+        assert!(RoutedBackend::new(HashMap::new(), Vec::new(), "vault").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_routed_list_pages() -> Result<()> {
+        use crate::backends::MemoryBackend;
+
+        let vault = MemoryBackend::new().with_page_size(2);
+        let aws = MemoryBackend::new().with_page_size(2);
+        for path in ["prod/a", "prod/b", "prod/c"] {
+            vault.insert_secret(path, [("password", "p")]);
+        }
+        aws.insert_secret("lambda/fn", [("password", "p")]);
+        // Listed by the vault backend, but routed to aws
+        vault.insert_secret("lambda/stale", [("password", "p")]);
+        vault
+            .update_metadata(
+                "lambda/stale",
+                HashMap::from([("owner".to_string(), "vault".to_string())]),
+            )
+            .await?;
+
+        let mut backends: HashMap<String, Backend> = HashMap::new();
+        backends.insert("vault".to_string(), Box::new(vault));
+        backends.insert("aws".to_string(), Box::new(aws));
+        let routed = RoutedBackend::new(backends, vec![route("aws:lambda/*")], "vault")?;
+
+        let mut pages = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = routed.list_secrets_page("", cursor.as_deref()).await?;
+            pages.push(page.names);
+            cursor = page.next;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(
+            pages,
+            vec![vec!["lambda/fn"], vec!["prod/a"], vec!["prod/b", "prod/c"],]
+        );
+
+        let paths: Vec<String> = ["prod/a", "lambda/fn", "lambda/stale", "prod/b"]
+            .iter()
+            .map(|path| path.to_string())
+            .collect();
+        routed
+            .update_metadata(
+                "lambda/fn",
+                HashMap::from([("owner".to_string(), "lambda".to_string())]),
+            )
+            .await?;
+        let metadata = routed.read_metadata_batch(&paths).await;
+        assert_eq!(metadata.len(), 4);
+        assert!(metadata[0].as_ref().unwrap().is_empty());
+        assert_eq!(metadata[1].as_ref().unwrap()["owner"], "lambda");
+        // Read from aws, not from the stale copy in vault
+        assert!(metadata[2].as_ref().unwrap().is_empty());
+        assert!(metadata[3].is_ok());
+        Ok(())
+    }
+}
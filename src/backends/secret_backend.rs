@@ -1,4 +1,5 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 
 /// Common data structure for secrets across backends
@@ -6,6 +7,33 @@ use std::collections::HashMap;
 pub struct SecretData {
     pub data: HashMap<String, String>,
     pub metadata: Option<HashMap<String, String>>,
+    /// Version of the secret that was read, for backends that track versions
+    pub version: Option<u64>,
+}
+
+/// One page of a secret listing
+#[derive(Debug, Clone, Default)]
+pub struct SecretPage {
+    /// Names relative to the listed path, as from [`SecretBackend::list_secrets`]
+    pub names: Vec<String>,
+    /// Cursor for the next page; `None` on the last page
+    pub next: Option<String>,
+}
+
+/// A kept version of a secret, as listed by [`SecretBackend::list_versions`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretVersion {
+    pub version: u64,
+    /// When this version was written
+    pub created: DateTime<Utc>,
+}
+
+/// A check-and-set write failed because the secret changed since it was read
+#[derive(Debug, thiserror::Error)]
+#[error("Secret at {path} was modified concurrently (expected version {expected})")]
+pub struct VersionConflict {
+    pub path: String,
+    pub expected: u64,
 }
 
 /// Trait for secret management backends (Vault, AWS Secrets Manager, etc.)
@@ -17,15 +45,139 @@ pub trait SecretBackend: Send + Sync {
     /// Write a secret to the backend
     async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()>;
 
+    /// Write a secret only if it is still at `version` (as returned by `read_secret`)
+    ///
+    /// Fails with [`VersionConflict`] if another writer got there first. Backends
+    /// without versioning write unconditionally.
+    async fn write_secret_cas(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        let _ = version;
+        self.write_secret(path, data).await
+    }
+
+    /// Whether [`SecretBackend::write_secret_cas`] really checks the version,
+    /// including `Some(0)` for "only if the secret does not exist yet"
+    fn supports_cas(&self) -> bool {
+        false
+    }
+
+    /// Whether [`SecretBackend::stage_secret`] keeps the old value current
+    /// until the new one is promoted
+    fn stages_secrets(&self) -> bool {
+        false
+    }
+
+    /// Write a rotated secret without making it the current value yet
+    ///
+    /// Backends with a staging model keep serving the old value until
+    /// [`SecretBackend::promote_staged_secret`] is called, so the new value can
+    /// be verified against its target first. Other backends write it directly.
+    async fn stage_secret(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.write_secret_cas(path, data, version).await
+    }
+
+    /// Make a value written by [`SecretBackend::stage_secret`] the current one
+    async fn promote_staged_secret(&self, path: &str) -> Result<()> {
+        let _ = path;
+        Ok(())
+    }
+
+    /// Drop a staged value after a failed rotation, leaving the current one in place
+    async fn discard_staged_secret(&self, path: &str) -> Result<()> {
+        let _ = path;
+        Ok(())
+    }
+
     /// Update metadata for a secret
     async fn update_metadata(&self, path: &str, metadata: HashMap<String, String>) -> Result<()>;
 
     /// Read metadata for a secret
     async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>>;
 
+    /// Read metadata for several secrets
+    ///
+    /// Returns one result per path, in the same order, so a secret that cannot
+    /// be read does not fail the others. Backends that can fetch metadata for
+    /// many secrets in one request override this; the default calls
+    /// [`SecretBackend::read_metadata`] for each path.
+    async fn read_metadata_batch(&self, paths: &[String]) -> Vec<Result<HashMap<String, String>>> {
+        let mut results = Vec::with_capacity(paths.len());
+        for path in paths {
+            results.push(self.read_metadata(path).await);
+        }
+        results
+    }
+
+    /// Remove metadata keys from a secret
+    ///
+    /// The default rewrites the metadata without `keys`, which suits backends
+    /// whose `update_metadata` replaces everything. Backends that merge
+    /// metadata on update must override this.
+    async fn remove_metadata(&self, path: &str, keys: &[String]) -> Result<()> {
+        let mut metadata = self.read_metadata(path).await?;
+        metadata.retain(|key, _| !keys.contains(key));
+        self.update_metadata(path, metadata).await
+    }
+
     /// List secrets at a path
     async fn list_secrets(&self, path: &str) -> Result<Vec<String>>;
 
+    /// List one page of the secrets at a path
+    ///
+    /// Pass `None` for the first page, then each page's `next` cursor until it
+    /// is `None`. Callers can process a large listing as it arrives instead of
+    /// waiting for all of it. Backends whose API pages natively override this;
+    /// the default returns all of [`SecretBackend::list_secrets`] as one page.
+    async fn list_secrets_page(&self, path: &str, cursor: Option<&str>) -> Result<SecretPage> {
+        let _ = cursor;
+        Ok(SecretPage {
+            names: self.list_secrets(path).await?,
+            next: None,
+        })
+    }
+
+    /// Versions of a secret the backend keeps, oldest first; the last one is
+    /// the current value
+    ///
+    /// Backends without version history fail.
+    async fn list_versions(&self, path: &str) -> Result<Vec<SecretVersion>> {
+        let _ = path;
+        anyhow::bail!(
+            "The {} backend does not keep previous versions of secrets",
+            self.backend_type()
+        )
+    }
+
+    /// Read a version listed by [`SecretBackend::list_versions`]
+    async fn read_version(&self, path: &str, version: u64) -> Result<SecretData> {
+        let _ = (path, version);
+        anyhow::bail!(
+            "The {} backend does not keep previous versions of secrets",
+            self.backend_type()
+        )
+    }
+
     /// Get the backend type name for display purposes
     fn backend_type(&self) -> &'static str;
 }
+
+/// Whether `path` is listed under its parent
+///
+/// Lets a failed read be told apart from a secret that does not exist, since
+/// backends word "not found" differently.
+pub async fn is_listed(backend: &dyn SecretBackend, path: &str) -> Result<bool> {
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let names = backend.list_secrets(parent).await?;
+    Ok(names
+        .iter()
+        .any(|listed| listed.trim_end_matches('/') == name))
+}
@@ -1,22 +1,55 @@
 use anyhow::{Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 use tracing::{debug, info};
 
-use super::secret_backend::{SecretBackend, SecretData};
+use super::secret_backend::{SecretBackend, SecretData, VersionConflict};
+use crate::redact::{self, SecretString};
 
-/// HashiCorp Vault client
+/// Server the Vault client talks to
+///
+/// OpenBao serves the same API as Vault. In `openbao` mode the token is sent
+/// in the standard `Authorization: Bearer` header rather than Vault's
+/// `X-Vault-Token`, and the backend reports itself as OpenBao.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultFlavor {
+    #[default]
+    Vault,
+    OpenBao,
+}
+
+impl VaultFlavor {
+    /// Product name, for messages and `backend_type()`
+    pub fn name(self) -> &'static str {
+        match self {
+            VaultFlavor::Vault => "HashiCorp Vault",
+            VaultFlavor::OpenBao => "OpenBao",
+        }
+    }
+}
+
+/// HashiCorp Vault (or OpenBao) client
 #[derive(Clone)]
 pub struct VaultClient {
     client: Client,
     address: String,
-    token: String,
+    token: SecretString,
+    flavor: VaultFlavor,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SecretMetadata {
     pub custom_metadata: Option<HashMap<String, String>>,
+    /// Version of the secret data (returned when reading secret data)
+    #[serde(default)]
+    pub version: Option<u64>,
+    /// Latest version of the secret (returned by the metadata endpoint)
+    #[serde(default)]
+    pub current_version: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,6 +58,74 @@ pub struct VaultSecretData {
     pub metadata: Option<SecretMetadata>,
 }
 
+/// Certificate issued by the PKI secrets engine
+#[derive(Debug, Deserialize)]
+pub struct PkiCertificate {
+    pub certificate: String,
+    pub private_key: String,
+    /// Issuing CA followed by any intermediates
+    #[serde(default)]
+    pub ca_chain: Vec<String>,
+    pub serial_number: String,
+    /// `notAfter` of the certificate, as a Unix timestamp
+    pub expiration: i64,
+}
+
+/// A lease as reported by `sys/leases/lookup`
+#[derive(Debug, Clone, Deserialize)]
+pub struct LeaseInfo {
+    pub id: String,
+    #[serde(default)]
+    pub expire_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Seconds until the lease expires
+    pub ttl: i64,
+    #[serde(default)]
+    pub renewable: bool,
+}
+
+/// Server state as reported by `sys/health`
+#[derive(Debug, Clone, Deserialize)]
+pub struct VaultHealth {
+    pub initialized: bool,
+    pub sealed: bool,
+    #[serde(default)]
+    pub standby: bool,
+    #[serde(default)]
+    pub version: String,
+}
+
+/// The client token as reported by `auth/token/lookup-self`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenInfo {
+    #[serde(default)]
+    pub policies: Vec<String>,
+    /// Seconds until the token expires; 0 if it never does
+    pub ttl: i64,
+    #[serde(default)]
+    pub renewable: bool,
+}
+
+/// A response-wrapping token from `sys/wrapping/wrap`
+#[derive(Debug, Clone, Deserialize)]
+pub struct WrapInfo {
+    /// Single-use token that `vault unwrap` exchanges for the wrapped data
+    pub token: String,
+    /// Accessor for looking up or revoking the token without using it
+    pub accessor: String,
+    /// Seconds until the token expires
+    pub ttl: u64,
+    pub creation_time: chrono::DateTime<chrono::Utc>,
+}
+
+/// A data key from the transit engine's `datakey` endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransitDataKey {
+    /// The data key, base64-encoded
+    pub plaintext: String,
+    /// The data key encrypted with the transit key
+    pub ciphertext: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct VaultResponse<T> {
     data: T,
@@ -34,13 +135,47 @@ struct VaultResponse<T> {
 struct VaultWriteRequest {
     data: HashMap<String, String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    options: Option<HashMap<String, String>>,
+    options: Option<VaultWriteOptions>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultWriteOptions {
+    /// Check-and-set: only write if the current version matches (0 = must not exist)
+    cas: u64,
+}
+
+/// Default time allowed to connect to Vault
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default time allowed for a whole Vault request
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 impl VaultClient {
-    /// Create a new Vault client
-    pub fn new(address: String, token: String) -> Result<Self> {
-        let client = Client::builder()
+    /// Create a new Vault client with the default timeouts
+    #[cfg_attr(not(test), allow(dead_code))] // Used in tests
+    pub fn new(address: String, token: SecretString) -> Result<Self> {
+        Self::with_timeouts(
+            address,
+            token,
+            DEFAULT_CONNECT_TIMEOUT,
+            DEFAULT_REQUEST_TIMEOUT,
+        )
+    }
+
+    /// Create a new Vault client that gives up on an unresponsive server
+    ///
+    /// `connect` bounds establishing the connection and `request` the whole
+    /// request including the response body, so a hung Vault node fails the
+    /// affected secret instead of stalling the run.
+    pub fn with_timeouts(
+        address: String,
+        token: SecretString,
+        connect: Duration,
+        request: Duration,
+    ) -> Result<Self> {
+        let client = crate::http::client_builder()
+            .connect_timeout(connect)
+            .timeout(request)
             .build()
             .context("Failed to create HTTP client")?;
 
@@ -48,9 +183,42 @@ impl VaultClient {
             client,
             address,
             token,
+            flavor: VaultFlavor::default(),
         })
     }
 
+    /// Talk to `flavor` (Vault unless set)
+    pub fn with_flavor(mut self, flavor: VaultFlavor) -> Self {
+        self.flavor = flavor;
+        self
+    }
+
+    pub fn flavor(&self) -> VaultFlavor {
+        self.flavor
+    }
+
+    /// Header carrying the token, marked sensitive so it is never logged
+    fn auth_headers(&self) -> HeaderMap {
+        let (name, value) = match self.flavor {
+            VaultFlavor::Vault => (
+                HeaderName::from_static("x-vault-token"),
+                self.token.expose_secret().to_string(),
+            ),
+            VaultFlavor::OpenBao => (
+                AUTHORIZATION,
+                format!("Bearer {}", self.token.expose_secret()),
+            ),
+        };
+        let mut headers = HeaderMap::new();
+        // A token that is not a valid header value is sent without it and
+        // rejected by the server as missing
+        if let Ok(mut value) = HeaderValue::from_str(&value) {
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+        headers
+    }
+
     /// Read a secret from Vault KV v2
     pub async fn read_secret(&self, mount: &str, path: &str) -> Result<VaultSecretData> {
         let url = format!("{}/v1/{}/data/{}", self.address, mount, path);
@@ -59,14 +227,14 @@ impl VaultClient {
         let response = self
             .client
             .get(&url)
-            .header("X-Vault-Token", &self.token)
+            .headers(self.auth_headers())
             .send()
             .await
             .context("Failed to read secret from Vault")?;
 
         if !response.status().is_success() {
             let status = response.status();
-            let body = response.text().await.unwrap_or_default();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
             anyhow::bail!("Vault request failed with status {}: {}", status, body);
         }
 
@@ -79,24 +247,28 @@ impl VaultClient {
     }
 
     /// Write a secret to Vault KV v2
+    ///
+    /// With `cas` set, Vault only accepts the write if the secret's current
+    /// version matches; a mismatch is reported as [`VersionConflict`].
     pub async fn write_secret(
         &self,
         mount: &str,
         path: &str,
         data: HashMap<String, String>,
+        cas: Option<u64>,
     ) -> Result<()> {
         let url = format!("{}/v1/{}/data/{}", self.address, mount, path);
         debug!("Writing secret to: {}", url);
 
         let request_body = VaultWriteRequest {
             data,
-            options: None,
+            options: cas.map(|cas| VaultWriteOptions { cas }),
         };
 
         let response = self
             .client
             .post(&url)
-            .header("X-Vault-Token", &self.token)
+            .headers(self.auth_headers())
             .json(&request_body)
             .send()
             .await
@@ -104,7 +276,14 @@ impl VaultClient {
 
         if !response.status().is_success() {
             let status = response.status();
-            let body = response.text().await.unwrap_or_default();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            if let Some(expected) = cas.filter(|_| is_cas_mismatch(status, &body)) {
+                return Err(VersionConflict {
+                    path: path.to_string(),
+                    expected,
+                }
+                .into());
+            }
             anyhow::bail!("Vault write failed with status {}: {}", status, body);
         }
 
@@ -128,7 +307,7 @@ impl VaultClient {
         let response = self
             .client
             .post(&url)
-            .header("X-Vault-Token", &self.token)
+            .headers(self.auth_headers())
             .json(&body)
             .send()
             .await
@@ -136,7 +315,7 @@ impl VaultClient {
 
         if !response.status().is_success() {
             let status = response.status();
-            let body = response.text().await.unwrap_or_default();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
             anyhow::bail!("Metadata update failed with status {}: {}", status, body);
         }
 
@@ -152,14 +331,14 @@ impl VaultClient {
         let response = self
             .client
             .get(&url)
-            .header("X-Vault-Token", &self.token)
+            .headers(self.auth_headers())
             .send()
             .await
             .context("Failed to read metadata from Vault")?;
 
         if !response.status().is_success() {
             let status = response.status();
-            let body = response.text().await.unwrap_or_default();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
             anyhow::bail!(
                 "Vault metadata request failed with status {}: {}",
                 status,
@@ -183,7 +362,7 @@ impl VaultClient {
         let response = self
             .client
             .request(reqwest::Method::from_bytes(b"LIST").unwrap(), &url)
-            .header("X-Vault-Token", &self.token)
+            .headers(self.auth_headers())
             .send()
             .await
             .context("Failed to list secrets from Vault")?;
@@ -196,7 +375,7 @@ impl VaultClient {
 
         if !response.status().is_success() {
             let status = response.status();
-            let body = response.text().await.unwrap_or_default();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
             anyhow::bail!("Vault list request failed with status {}: {}", status, body);
         }
 
@@ -212,6 +391,412 @@ impl VaultClient {
 
         Ok(vault_response.data.keys)
     }
+
+    /// Issue a certificate with a new private key from a PKI secrets engine role
+    pub async fn issue_certificate(
+        &self,
+        mount: &str,
+        role: &str,
+        common_name: &str,
+        alt_names: &[String],
+        ttl: Option<&str>,
+    ) -> Result<PkiCertificate> {
+        let url = format!("{}/v1/{}/issue/{}", self.address, mount, role);
+        debug!("Issuing certificate for {} from: {}", common_name, url);
+
+        let mut body = serde_json::json!({ "common_name": common_name });
+        if !alt_names.is_empty() {
+            body["alt_names"] = alt_names.join(",").into();
+        }
+        if let Some(ttl) = ttl {
+            body["ttl"] = ttl.into();
+        }
+
+        let response = self
+            .client
+            .post(&url)
+            .headers(self.auth_headers())
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to request certificate from Vault")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            anyhow::bail!(
+                "Vault PKI issue request failed with status {}: {}",
+                status,
+                body
+            );
+        }
+
+        let vault_response: VaultResponse<PkiCertificate> = response
+            .json()
+            .await
+            .context("Failed to parse Vault PKI response")?;
+
+        redact::register_secret(&vault_response.data.private_key);
+        Ok(vault_response.data)
+    }
+
+    /// Wrap `data` in a single-use token that expires after `ttl` (e.g. "5m")
+    ///
+    /// The recipient gets the data with `vault unwrap <token>`; once unwrapped,
+    /// or after the TTL, the token is useless.
+    pub async fn wrap(&self, data: &HashMap<String, String>, ttl: &str) -> Result<WrapInfo> {
+        #[derive(Deserialize)]
+        struct WrapResponse {
+            wrap_info: WrapInfo,
+        }
+
+        let url = format!("{}/v1/sys/wrapping/wrap", self.address);
+        debug!("Wrapping secret with TTL {}", ttl);
+
+        let response = self
+            .client
+            .post(&url)
+            .headers(self.auth_headers())
+            .header("X-Vault-Wrap-TTL", ttl)
+            .json(data)
+            .send()
+            .await
+            .context("Failed to wrap secret with Vault")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            anyhow::bail!("Vault wrap request failed with status {}: {}", status, body);
+        }
+
+        let wrapped: WrapResponse = response
+            .json()
+            .await
+            .context("Failed to parse Vault wrap response")?;
+        redact::register_secret(&wrapped.wrap_info.token);
+        Ok(wrapped.wrap_info)
+    }
+
+    /// Generate a data key with a transit engine key
+    ///
+    /// Returns the key itself, base64-encoded, and the key encrypted with
+    /// the transit key (`vault:v<n>:...`). Only the latter may be stored.
+    pub async fn transit_data_key(&self, mount: &str, key: &str) -> Result<TransitDataKey> {
+        let url = format!("{}/v1/{}/datakey/plaintext/{}", self.address, mount, key);
+        debug!("Generating a data key with transit key {}/{}", mount, key);
+        let data_key: TransitDataKey = self
+            .post_transit(&url, serde_json::json!({}))
+            .await
+            .context("Failed to generate data key")?;
+        redact::register_secret(&data_key.plaintext);
+        Ok(data_key)
+    }
+
+    /// Decrypt `ciphertext` (`vault:v<n>:...`) with a transit engine key,
+    /// returning the base64-encoded plaintext
+    pub async fn transit_decrypt(
+        &self,
+        mount: &str,
+        key: &str,
+        ciphertext: &str,
+    ) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Decrypted {
+            plaintext: String,
+        }
+
+        let url = format!("{}/v1/{}/decrypt/{}", self.address, mount, key);
+        debug!("Decrypting with transit key {}/{}", mount, key);
+        let decrypted: Decrypted = self
+            .post_transit(&url, serde_json::json!({ "ciphertext": ciphertext }))
+            .await
+            .context("Failed to decrypt data key")?;
+        redact::register_secret(&decrypted.plaintext);
+        Ok(decrypted.plaintext)
+    }
+
+    async fn post_transit<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &str,
+        body: serde_json::Value,
+    ) -> Result<T> {
+        let response = self
+            .client
+            .post(url)
+            .headers(self.auth_headers())
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to send request to Vault")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            anyhow::bail!(
+                "Vault transit request failed with status {}: {}",
+                status,
+                body
+            );
+        }
+
+        let vault_response: VaultResponse<T> = response
+            .json()
+            .await
+            .context("Failed to parse Vault transit response")?;
+        Ok(vault_response.data)
+    }
+
+    /// Rotate the root credential of a database secrets engine connection
+    ///
+    /// Vault generates the new password itself and never returns it.
+    pub async fn rotate_database_root(&self, mount: &str, connection: &str) -> Result<()> {
+        let url = format!("{}/v1/{}/rotate-root/{}", self.address, mount, connection);
+        self.post_empty(&url)
+            .await
+            .with_context(|| format!("Failed to rotate root credential of {}", connection))
+    }
+
+    /// Rotate the password of a database secrets engine static role now
+    pub async fn rotate_database_role(&self, mount: &str, role: &str) -> Result<()> {
+        let url = format!("{}/v1/{}/rotate-role/{}", self.address, mount, role);
+        self.post_empty(&url)
+            .await
+            .with_context(|| format!("Failed to rotate static role {}", role))
+    }
+
+    async fn post_empty(&self, url: &str) -> Result<()> {
+        debug!("POST {}", url);
+        let response = self
+            .client
+            .post(url)
+            .headers(self.auth_headers())
+            .send()
+            .await
+            .context("Failed to send request to Vault")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            anyhow::bail!("Vault request failed with status {}: {}", status, body);
+        }
+        Ok(())
+    }
+
+    /// List the IDs of the leases under `prefix` (e.g. "database/creds/app/")
+    ///
+    /// Vault lists one level at a time; nested prefixes are followed. This
+    /// needs `sudo` capability on `sys/leases/lookup`.
+    pub async fn list_leases(&self, prefix: &str) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct ListData {
+            keys: Vec<String>,
+        }
+
+        let mut leases = Vec::new();
+        let mut pending = vec![format!("{}/", prefix.trim_end_matches('/'))];
+        while let Some(prefix) = pending.pop() {
+            let url = format!("{}/v1/sys/leases/lookup/{}", self.address, prefix);
+            debug!("Listing leases at: {}", url);
+
+            let response = self
+                .client
+                .request(reqwest::Method::from_bytes(b"LIST").unwrap(), &url)
+                .headers(self.auth_headers())
+                .send()
+                .await
+                .context("Failed to list leases from Vault")?;
+
+            if response.status() == 404 {
+                continue;
+            }
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = redact::redact(&response.text().await.unwrap_or_default());
+                anyhow::bail!(
+                    "Vault lease list request failed with status {}: {}",
+                    status,
+                    body
+                );
+            }
+
+            let vault_response: VaultResponse<ListData> = response
+                .json()
+                .await
+                .context("Failed to parse Vault lease list response")?;
+            for key in vault_response.data.keys {
+                if key.ends_with('/') {
+                    pending.push(format!("{}{}", prefix, key));
+                } else {
+                    leases.push(format!("{}{}", prefix, key));
+                }
+            }
+        }
+        leases.sort();
+        Ok(leases)
+    }
+
+    /// Look up the expiry of a lease
+    pub async fn lookup_lease(&self, lease_id: &str) -> Result<LeaseInfo> {
+        let url = format!("{}/v1/sys/leases/lookup", self.address);
+        let response = self
+            .client
+            .put(&url)
+            .headers(self.auth_headers())
+            .json(&serde_json::json!({ "lease_id": lease_id }))
+            .send()
+            .await
+            .context("Failed to look up lease in Vault")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            anyhow::bail!("Vault lease lookup failed with status {}: {}", status, body);
+        }
+
+        let vault_response: VaultResponse<LeaseInfo> = response
+            .json()
+            .await
+            .context("Failed to parse Vault lease lookup response")?;
+        Ok(vault_response.data)
+    }
+
+    /// Renew a lease, returning its new duration in seconds
+    ///
+    /// `increment` is a Vault duration such as "24h"; Vault caps it at the
+    /// role's max TTL.
+    pub async fn renew_lease(&self, lease_id: &str, increment: Option<&str>) -> Result<i64> {
+        #[derive(Deserialize)]
+        struct RenewResponse {
+            lease_duration: i64,
+        }
+
+        let url = format!("{}/v1/sys/leases/renew", self.address);
+        let mut body = serde_json::json!({ "lease_id": lease_id });
+        if let Some(increment) = increment {
+            body["increment"] = increment.into();
+        }
+
+        let response = self
+            .client
+            .put(&url)
+            .headers(self.auth_headers())
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to renew lease in Vault")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            anyhow::bail!(
+                "Vault lease renewal failed with status {}: {}",
+                status,
+                body
+            );
+        }
+
+        let renewed: RenewResponse = response
+            .json()
+            .await
+            .context("Failed to parse Vault lease renewal response")?;
+        Ok(renewed.lease_duration)
+    }
+
+    /// Check whether the server is reachable, initialized and unsealed
+    ///
+    /// This needs no token. Sealed, standby and uninitialized servers are
+    /// reported in the result rather than as errors.
+    pub async fn health(&self) -> Result<VaultHealth> {
+        let url = format!(
+            "{}/v1/sys/health?standbyok=true&perfstandbyok=true&sealedcode=200&uninitcode=200",
+            self.address
+        );
+        let response = self.client.get(&url).send().await.with_context(|| {
+            format!("Failed to reach {} at {}", self.flavor.name(), self.address)
+        })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            anyhow::bail!("Vault health check failed with status {}: {}", status, body);
+        }
+
+        response
+            .json()
+            .await
+            .context("Failed to parse Vault health response")
+    }
+
+    /// Look up the client token, which fails if it is invalid or expired
+    pub async fn lookup_self(&self) -> Result<TokenInfo> {
+        let url = format!("{}/v1/auth/token/lookup-self", self.address);
+        let response = self
+            .client
+            .get(&url)
+            .headers(self.auth_headers())
+            .send()
+            .await
+            .context("Failed to look up Vault token")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            anyhow::bail!("Vault token lookup failed with status {}: {}", status, body);
+        }
+
+        let vault_response: VaultResponse<TokenInfo> = response
+            .json()
+            .await
+            .context("Failed to parse Vault token lookup response")?;
+        Ok(vault_response.data)
+    }
+
+    /// The client token's capabilities on each of `paths`
+    /// (e.g. "secret/data/app/db" to `["read", "update"]`)
+    pub async fn capabilities(&self, paths: &[String]) -> Result<HashMap<String, Vec<String>>> {
+        let url = format!("{}/v1/sys/capabilities-self", self.address);
+        let response = self
+            .client
+            .post(&url)
+            .headers(self.auth_headers())
+            .json(&serde_json::json!({ "paths": paths }))
+            .send()
+            .await
+            .context("Failed to check Vault capabilities")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = redact::redact(&response.text().await.unwrap_or_default());
+            anyhow::bail!(
+                "Vault capabilities check failed with status {}: {}",
+                status,
+                body
+            );
+        }
+
+        // Capabilities are keyed by path, next to a "capabilities" key that
+        // merges them all
+        let vault_response: VaultResponse<HashMap<String, serde_json::Value>> = response
+            .json()
+            .await
+            .context("Failed to parse Vault capabilities response")?;
+        Ok(paths
+            .iter()
+            .map(|path| {
+                let capabilities = vault_response
+                    .data
+                    .get(path)
+                    .and_then(|value| serde_json::from_value(value.clone()).ok())
+                    .unwrap_or_default();
+                (path.clone(), capabilities)
+            })
+            .collect())
+    }
+}
+
+/// Whether a failed write was rejected because the `cas` version did not match
+fn is_cas_mismatch(status: reqwest::StatusCode, body: &str) -> bool {
+    status == reqwest::StatusCode::BAD_REQUEST && body.contains("check-and-set")
 }
 
 /// Wrapper for VaultClient that implements SecretBackend trait
@@ -231,16 +816,37 @@ impl SecretBackend for VaultBackend {
     async fn read_secret(&self, path: &str) -> Result<SecretData> {
         let vault_data = self.client.read_secret(&self.mount, path).await?;
 
+        let version = vault_data.metadata.as_ref().and_then(|m| m.version);
         let metadata = vault_data.metadata.and_then(|m| m.custom_metadata);
 
         Ok(SecretData {
             data: vault_data.data,
             metadata: metadata.clone(),
+            version,
         })
     }
 
+    /// Unconditional write; callers that read the secret first pass the
+    /// version they saw to `write_secret_cas` instead
     async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
-        self.client.write_secret(&self.mount, path, data).await
+        self.client
+            .write_secret(&self.mount, path, data, None)
+            .await
+    }
+
+    async fn write_secret_cas(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.client
+            .write_secret(&self.mount, path, data, version)
+            .await
+    }
+
+    fn supports_cas(&self) -> bool {
+        true
     }
 
     async fn update_metadata(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
@@ -259,7 +865,7 @@ impl SecretBackend for VaultBackend {
     }
 
     fn backend_type(&self) -> &'static str {
-        "HashiCorp Vault"
+        self.client.flavor.name()
     }
 }
 
@@ -269,20 +875,14 @@ mod tests {
 
     #[test]
     fn test_vault_client_new() {
-        let client = VaultClient::new(
-            "http://localhost:8200".to_string(),
-            "test-token".to_string(),
-        );
+        let client = VaultClient::new("http://localhost:8200".to_string(), "test-token".into());
         assert!(client.is_ok());
     }
 
     #[test]
     fn test_vault_url_construction() {
-        let client = VaultClient::new(
-            "http://localhost:8200".to_string(),
-            "test-token".to_string(),
-        )
-        .unwrap();
+        let client =
+            VaultClient::new("http://localhost:8200".to_string(), "test-token".into()).unwrap();
 
         // Test read URL
         let read_url = format!("{}/v1/{}/data/{}", client.address, "secret", "myapp/db");
@@ -311,6 +911,8 @@ mod tests {
 
         let metadata = SecretMetadata {
             custom_metadata: Some(custom_meta.clone()),
+            version: None,
+            current_version: None,
         };
 
         assert_eq!(
@@ -336,6 +938,8 @@ mod tests {
             data: data.clone(),
             metadata: Some(SecretMetadata {
                 custom_metadata: Some(custom_meta),
+                version: Some(3),
+                current_version: None,
             }),
         };
 
@@ -360,5 +964,150 @@ mod tests {
         // Verify structure
         assert_eq!(request.data.get("password"), Some(&"newpass".to_string()));
         assert!(request.options.is_none());
+
+        let request = VaultWriteRequest {
+            data,
+            options: Some(VaultWriteOptions { cas: 4 }),
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["options"]["cas"], 4);
+    }
+
+    #[tokio::test]
+    async fn test_vault_cas_conflict() {
+        let mut server = mockito::Server::new_async().await;
+        let _write = server
+            .mock("POST", "/v1/secret/data/myapp/db")
+            .with_status(400)
+            .with_body(
+                r#"{"errors":["check-and-set parameter did not match the current version"]}"#,
+            )
+            .create_async()
+            .await;
+
+        let client = VaultClient::new(server.url(), SecretString::new("token")).unwrap();
+        let data = HashMap::from([("password".to_string(), "newpass".to_string())]);
+        let err = client
+            .write_secret("secret", "myapp/db", data, Some(2))
+            .await
+            .unwrap_err();
+
+        let conflict = err.downcast_ref::<VersionConflict>().unwrap();
+        assert_eq!(conflict.expected, 2);
+    }
+
+    #[tokio::test]
+    async fn test_vault_wrap() {
+        let mut server = mockito::Server::new_async().await;
+        let wrap = server
+            .mock("POST", "/v1/sys/wrapping/wrap")
+            .match_header("X-Vault-Wrap-TTL", "5m")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "password": "newpass"
+            })))
+            .with_body(
+                r#"{"data":null,"wrap_info":{"token":"hvs.wrapped","accessor":"acc","ttl":300,
+                    "creation_time":"2026-10-16T10:00:00.123456-04:00","creation_path":"sys/wrapping/wrap"}}"#,
+            )
+            .create_async()
+            .await;
+
+        let client = VaultClient::new(server.url(), SecretString::new("token")).unwrap();
+        let data = HashMap::from([("password".to_string(), "newpass".to_string())]);
+        let info = client.wrap(&data, "5m").await.unwrap();
+
+        wrap.assert_async().await;
+        assert_eq!(info.token, "hvs.wrapped");
+        assert_eq!(info.ttl, 300);
+        assert!(!redact::redact("token hvs.wrapped").contains("hvs.wrapped"));
+    }
+
+    #[tokio::test]
+    async fn test_vault_request_timeout() {
+        // Accepts connections but never answers, like a hung Vault node
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let _server = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let client = VaultClient::with_timeouts(
+            address,
+            SecretString::new("token"),
+            Duration::from_secs(1),
+            Duration::from_millis(200),
+        )
+        .unwrap();
+        let started = std::time::Instant::now();
+        assert!(client.read_secret("secret", "myapp/db").await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_openbao_flavor() {
+        let mut server = mockito::Server::new_async().await;
+        let read = server
+            .mock("GET", "/v1/secret/data/myapp/db")
+            .match_header("Authorization", "Bearer s.token")
+            .match_header("X-Vault-Token", mockito::Matcher::Missing)
+            .with_body(r#"{"data":{"data":{"password":"pass"},"metadata":{"version":3}}}"#)
+            .create_async()
+            .await;
+
+        let client = VaultClient::new(server.url(), SecretString::new("s.token"))
+            .unwrap()
+            .with_flavor(VaultFlavor::OpenBao);
+        let backend = VaultBackend::new(client, "secret".to_string());
+        let secret = backend.read_secret("myapp/db").await.unwrap();
+
+        read.assert_async().await;
+        assert_eq!(secret.data["password"], "pass");
+        assert_eq!(backend.backend_type(), "OpenBao");
+    }
+
+    /// Round trip against a real OpenBao dev server (run in CI with
+    /// `BAO_ADDR` and `BAO_TOKEN` set)
+    #[tokio::test]
+    #[ignore]
+    async fn test_openbao_server() -> Result<()> {
+        let client = VaultClient::new(
+            std::env::var("BAO_ADDR")?,
+            std::env::var("BAO_TOKEN")?.into(),
+        )?
+        .with_flavor(VaultFlavor::OpenBao);
+        let health = client.health().await?;
+        assert!(health.initialized && !health.sealed);
+
+        let backend = VaultBackend::new(client, "secret".to_string());
+        let path = "asr-ci/openbao";
+        for value in ["first", "second"] {
+            backend
+                .write_secret(
+                    path,
+                    HashMap::from([("password".to_string(), value.to_string())]),
+                )
+                .await?;
+        }
+        backend
+            .update_metadata(
+                path,
+                HashMap::from([("rotation_enabled".to_string(), "true".to_string())]),
+            )
+            .await?;
+
+        let secret = backend.read_secret(path).await?;
+        assert_eq!(secret.data["password"], "second");
+        assert_eq!(
+            backend.read_metadata(path).await?["rotation_enabled"],
+            "true"
+        );
+        assert!(backend
+            .list_secrets("asr-ci")
+            .await?
+            .contains(&"openbao".to_string()));
+        Ok(())
     }
 }
@@ -0,0 +1,232 @@
+//! Encrypted backups of secrets and their metadata
+//!
+//! `asr backup` reads every secret under a path into one JSON document and
+//! encrypts it to an age recipient or GnuPG key (see [`crate::encrypt`]), or
+//! seals it with a Vault transit key (see [`crate::transit`]).
+//! `asr restore` decrypts it and writes the secrets back, all of them or only
+//! those matching a filter, e.g. into an empty backend for a DR rehearsal.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::debug;
+
+use crate::backends::SecretBackend;
+use crate::backends::VaultClient;
+use crate::encrypt::{self, Recipient};
+use crate::path_filter::PathFilter;
+use crate::redact;
+use crate::transfer;
+use crate::transit::{self, Transit};
+
+/// Version of the backup document layout
+const BACKUP_FORMAT: u32 = 1;
+
+/// Secrets and metadata read from a backend at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Backup {
+    pub format: u32,
+    pub created: DateTime<Utc>,
+    /// Backend type and path the secrets were read from
+    pub backend: String,
+    pub path: String,
+    pub secrets: BTreeMap<String, BackedUpSecret>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackedUpSecret {
+    pub data: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// What [`Backup::restore`] did, or would do on a dry run
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub restored: Vec<String>,
+    /// Secrets left alone because they already exist
+    pub skipped: Vec<String>,
+    pub failed: Vec<(String, anyhow::Error)>,
+}
+
+impl Backup {
+    /// Read every secret under `path`, with its metadata
+    pub async fn collect(backend: &dyn SecretBackend, path: &str) -> Result<Self> {
+        let mut secrets = BTreeMap::new();
+        for (secret_path, data) in transfer::collect_secrets(backend, path, false).await? {
+            debug!("Backing up {}", secret_path);
+            let metadata = backend
+                .read_metadata(&secret_path)
+                .await
+                .with_context(|| format!("Failed to read metadata of {}", secret_path))?;
+            secrets.insert(
+                secret_path,
+                BackedUpSecret {
+                    data,
+                    metadata: metadata.into_iter().collect(),
+                },
+            );
+        }
+        Ok(Self {
+            format: BACKUP_FORMAT,
+            created: Utc::now(),
+            backend: backend.backend_type().to_string(),
+            path: path.to_string(),
+            secrets,
+        })
+    }
+
+    /// Serialize and encrypt the backup to `recipient`
+    pub fn seal(&self, recipient: &Recipient) -> Result<String> {
+        let document = serde_json::to_string(self).context("Failed to serialize backup")?;
+        encrypt::encrypt(&document, recipient)
+    }
+
+    /// Serialize the backup and seal it with a Vault transit key
+    pub async fn seal_with_transit(&self, transit: &Transit) -> Result<String> {
+        let document = serde_json::to_string(self).context("Failed to serialize backup")?;
+        transit.seal(document.as_bytes()).await
+    }
+
+    /// Decrypt and parse a backup written by [`Backup::seal`]
+    pub fn open(ciphertext: &str, identity: Option<&Path>) -> Result<Self> {
+        let document = encrypt::decrypt(ciphertext, identity)?;
+        Self::parse(&document)
+    }
+
+    /// Open and parse a backup written by [`Backup::seal_with_transit`]
+    pub async fn open_with_transit(envelope: &str, client: &VaultClient) -> Result<Self> {
+        let document = transit::open(client, envelope).await?;
+        Self::parse(&String::from_utf8(document).context("Backup is not valid UTF-8")?)
+    }
+
+    fn parse(document: &str) -> Result<Self> {
+        let backup: Self = serde_json::from_str(document).context("Not a valid asr backup")?;
+        if backup.format != BACKUP_FORMAT {
+            anyhow::bail!(
+                "Backup format {} is not supported (expected {})",
+                backup.format,
+                BACKUP_FORMAT
+            );
+        }
+        for secret in backup.secrets.values() {
+            for value in secret.data.values() {
+                redact::register_secret(value);
+            }
+        }
+        Ok(backup)
+    }
+
+    /// Write the backed-up secrets matching `filter` to `backend`
+    ///
+    /// Secrets that already exist are skipped unless `overwrite` is set. A
+    /// failure to restore one secret does not stop the others. With
+    /// `dry_run`, nothing is written and the report says what would be.
+    pub async fn restore(
+        &self,
+        backend: &dyn SecretBackend,
+        filter: &PathFilter,
+        overwrite: bool,
+        dry_run: bool,
+    ) -> RestoreReport {
+        let mut report = RestoreReport::default();
+        for (path, secret) in &self.secrets {
+            if !filter.matches(path) {
+                continue;
+            }
+            if !overwrite && backend.read_secret(path).await.is_ok() {
+                report.skipped.push(path.clone());
+                continue;
+            }
+            if dry_run {
+                report.restored.push(path.clone());
+                continue;
+            }
+            match restore_secret(backend, path, secret).await {
+                Ok(()) => report.restored.push(path.clone()),
+                Err(e) => report.failed.push((path.clone(), e)),
+            }
+        }
+        report
+    }
+}
+
+async fn restore_secret(
+    backend: &dyn SecretBackend,
+    path: &str,
+    secret: &BackedUpSecret,
+) -> Result<()> {
+    let data = secret
+        .data
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    backend
+        .write_secret(path, data)
+        .await
+        .context("Failed to write secret")?;
+    if !secret.metadata.is_empty() {
+        let metadata = secret
+            .metadata
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        backend
+            .update_metadata(path, metadata)
+            .await
+            .context("Failed to write metadata")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::MemoryBackend;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_backup_and_restore() -> Result<()> {
+        let source = MemoryBackend::new()
+            .with_secret("prod/db", [("password", "db-pass")])
+            .with_secret("prod/api", [("token", "api-token")])
+            .with_secret("dev/db", [("password", "dev-pass")]);
+        source
+            .update_metadata(
+                "prod/db",
+                HashMap::from([("owner".to_string(), "payments".to_string())]),
+            )
+            .await?;
+
+        let backup = Backup::collect(&source, "prod").await?;
+        assert_eq!(
+            backup.secrets.keys().collect::<Vec<_>>(),
+            ["prod/api", "prod/db"]
+        );
+        let backup = Backup::parse(&serde_json::to_string(&backup)?)?;
+
+        // Selective restore into an empty backend, then again without overwriting
+        let target = MemoryBackend::new().with_secret("prod/api", [("token", "newer")]);
+        let all = PathFilter::new(&[], &[])?;
+        let dry = backup.restore(&target, &all, false, true).await;
+        assert_eq!(dry.restored, ["prod/db"]);
+        assert!(target.secret("prod/db").is_none());
+
+        let report = backup.restore(&target, &all, false, false).await;
+        assert_eq!(report.restored, ["prod/db"]);
+        assert_eq!(report.skipped, ["prod/api"]);
+        assert_eq!(target.secret("prod/db").unwrap()["password"], "db-pass");
+        assert_eq!(target.read_metadata("prod/db").await?["owner"], "payments");
+        assert_eq!(target.secret("prod/api").unwrap()["token"], "newer");
+
+        let only_api = PathFilter::new(&["prod/api".to_string()], &[])?;
+        let report = backup.restore(&target, &only_api, true, false).await;
+        assert_eq!(report.restored, ["prod/api"]);
+        assert_eq!(target.secret("prod/api").unwrap()["token"], "api-token");
+
+        assert!(Backup::parse(r#"{"not": "a backup"}"#).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,454 @@
+//! X.509 certificate rotation
+//!
+//! A certificate secret holds `certificate`, `private_key`, `ca_chain` and
+//! `serial_number` keys. Its metadata records what to request
+//! (`cert_common_name`, `cert_alt_names`, `cert_ttl`, `cert_role`) and when the
+//! current certificate expires (`not_after`). Unlike passwords, certificates
+//! fall due a number of days before `not_after` rather than a period after
+//! their last rotation, and are renewed by requesting a new certificate from
+//! an issuer: Vault's PKI secrets engine or an ACME CA such as Let's Encrypt.
+//! The issuer that first issued a certificate (`cert_issuer`) also renews it.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+mod acme;
+
+pub use acme::AcmeIssuer;
+
+use crate::backends::{is_listed, SecretBackend, VaultClient};
+use crate::config::PkiConfig;
+use crate::rotation::{self, LAST_ROTATED_KEY, ROTATION_METADATA_KEY};
+
+const COMMON_NAME_KEY: &str = "cert_common_name";
+const ALT_NAMES_KEY: &str = "cert_alt_names";
+const TTL_KEY: &str = "cert_ttl";
+const ROLE_KEY: &str = "cert_role";
+const ISSUER_KEY: &str = "cert_issuer";
+const RENEW_BEFORE_KEY: &str = "cert_renew_before_days";
+const NOT_AFTER_KEY: &str = "not_after";
+
+/// Days before `not_after` that a certificate is renewed unless overridden
+pub const DEFAULT_RENEW_BEFORE_DAYS: i64 = 30;
+
+/// What to put in a certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateRequest {
+    pub common_name: String,
+    pub alt_names: Vec<String>,
+    /// Requested lifetime (e.g., "720h"); the issuer's default if unset
+    pub ttl: Option<String>,
+    /// Issuer role, overriding the configured one
+    pub role: Option<String>,
+    /// Issuer to use (e.g., "acme"), by [`CertificateIssuer::issuer_type`]
+    pub issuer: Option<String>,
+}
+
+impl CertificateRequest {
+    pub fn new(common_name: impl Into<String>) -> Self {
+        Self {
+            common_name: common_name.into(),
+            alt_names: Vec::new(),
+            ttl: None,
+            role: None,
+            issuer: None,
+        }
+    }
+
+    /// Request recorded in a certificate secret's metadata
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Option<Self> {
+        let common_name = metadata.get(COMMON_NAME_KEY)?;
+        Some(Self {
+            common_name: common_name.clone(),
+            alt_names: metadata
+                .get(ALT_NAMES_KEY)
+                .map(|names| {
+                    names
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            ttl: metadata.get(TTL_KEY).cloned(),
+            role: metadata.get(ROLE_KEY).cloned(),
+            issuer: metadata.get(ISSUER_KEY).cloned(),
+        })
+    }
+
+    fn write_metadata(&self, metadata: &mut HashMap<String, String>) {
+        metadata.insert(COMMON_NAME_KEY.to_string(), self.common_name.clone());
+        if !self.alt_names.is_empty() {
+            metadata.insert(ALT_NAMES_KEY.to_string(), self.alt_names.join(","));
+        }
+        if let Some(ref ttl) = self.ttl {
+            metadata.insert(TTL_KEY.to_string(), ttl.clone());
+        }
+        if let Some(ref role) = self.role {
+            metadata.insert(ROLE_KEY.to_string(), role.clone());
+        }
+        if let Some(ref issuer) = self.issuer {
+            metadata.insert(ISSUER_KEY.to_string(), issuer.clone());
+        }
+    }
+}
+
+/// A newly issued certificate and its private key (PEM)
+#[derive(Debug, Clone)]
+pub struct IssuedCertificate {
+    pub certificate: String,
+    pub private_key: String,
+    pub ca_chain: String,
+    pub serial_number: String,
+    pub not_after: DateTime<Utc>,
+}
+
+/// Certificate authority that issues certificates with fresh keys
+#[async_trait::async_trait]
+pub trait CertificateIssuer: Send + Sync {
+    async fn issue(&self, request: &CertificateRequest) -> Result<IssuedCertificate>;
+
+    /// Issuer name for display purposes
+    fn issuer_type(&self) -> &'static str;
+}
+
+/// Issues certificates from a Vault PKI secrets engine role
+pub struct VaultPkiIssuer {
+    client: VaultClient,
+    config: PkiConfig,
+}
+
+impl VaultPkiIssuer {
+    pub fn new(client: VaultClient, config: &PkiConfig) -> Self {
+        Self {
+            client,
+            config: config.clone(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CertificateIssuer for VaultPkiIssuer {
+    async fn issue(&self, request: &CertificateRequest) -> Result<IssuedCertificate> {
+        let role = request
+            .role
+            .as_deref()
+            .or(self.config.role.as_deref())
+            .context("No PKI role configured (set [pki] role or cert_role metadata)")?;
+
+        let issued = self
+            .client
+            .issue_certificate(
+                &self.config.mount,
+                role,
+                &request.common_name,
+                &request.alt_names,
+                request.ttl.as_deref(),
+            )
+            .await?;
+
+        Ok(IssuedCertificate {
+            certificate: issued.certificate,
+            private_key: issued.private_key,
+            ca_chain: issued.ca_chain.join("\n"),
+            serial_number: issued.serial_number,
+            not_after: DateTime::from_timestamp(issued.expiration, 0)
+                .context("Vault returned an invalid certificate expiration")?,
+        })
+    }
+
+    fn issuer_type(&self) -> &'static str {
+        "vault-pki"
+    }
+}
+
+/// Pick the issuer named `name`, or the first one if no name is given
+pub fn select_issuer<'a>(
+    issuers: &'a [Box<dyn CertificateIssuer>],
+    name: Option<&str>,
+) -> Result<&'a dyn CertificateIssuer> {
+    let issuer = match name {
+        Some(name) => issuers
+            .iter()
+            .find(|issuer| issuer.issuer_type() == name)
+            .with_context(|| format!("Certificate issuer {} is not configured", name))?,
+        None => issuers
+            .first()
+            .context("No certificate issuer configured (add a [pki] or [acme] section)")?,
+    };
+    Ok(issuer.as_ref())
+}
+
+/// Whether a secret's metadata describes a certificate
+pub fn is_certificate(metadata: &HashMap<String, String>) -> bool {
+    metadata.contains_key(COMMON_NAME_KEY)
+}
+
+/// Expiry of the current certificate, if recorded and valid
+pub fn not_after(metadata: &HashMap<String, String>) -> Option<DateTime<Utc>> {
+    metadata
+        .get(NOT_AFTER_KEY)
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// When a certificate falls due for renewal
+pub fn renewal_due_at(metadata: &HashMap<String, String>) -> Option<DateTime<Utc>> {
+    let renew_before = metadata
+        .get(RENEW_BEFORE_KEY)
+        .and_then(|days| days.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_RENEW_BEFORE_DAYS);
+    not_after(metadata).map(|expiry| expiry - Duration::days(renew_before))
+}
+
+/// Whether a certificate should be renewed at `now`
+///
+/// Certificates without a readable `not_after` are renewed.
+pub fn needs_renewal(metadata: &HashMap<String, String>, now: DateTime<Utc>) -> bool {
+    match renewal_due_at(metadata) {
+        Some(due) => now >= due,
+        None => {
+            if let Some(date) = metadata.get(NOT_AFTER_KEY) {
+                warn!("Failed to parse not_after date: {}", date);
+            }
+            true
+        }
+    }
+}
+
+/// Issue a certificate into a new (or existing) secret and enable renewal
+pub async fn issue_certificate(
+    backend: &dyn SecretBackend,
+    path: &str,
+    issuer: &dyn CertificateIssuer,
+    request: &CertificateRequest,
+) -> Result<IssuedCertificate> {
+    info!(
+        "Issuing certificate for {} into {} ({})",
+        request.common_name,
+        path,
+        issuer.issuer_type()
+    );
+
+    let issued = issuer
+        .issue(request)
+        .await
+        .with_context(|| format!("Failed to issue certificate for {}", request.common_name))?;
+
+    // Keep any other keys already stored in the secret
+    let current = match backend.read_secret(path).await {
+        Ok(current) => current.data,
+        Err(e) => match is_listed(backend, path).await {
+            Ok(false) => HashMap::new(),
+            _ => return Err(e).context("Failed to read the secret to store the certificate in"),
+        },
+    };
+    store(backend, path, current, &issued).await?;
+
+    let mut metadata = backend.read_metadata(path).await.unwrap_or_default();
+    request.write_metadata(&mut metadata);
+    metadata.insert(ISSUER_KEY.to_string(), issuer.issuer_type().to_string());
+    record_issue(backend, path, metadata, &issued).await?;
+
+    Ok(issued)
+}
+
+/// Replace the certificate and key of a certificate secret with new ones
+pub async fn renew_certificate(
+    backend: &dyn SecretBackend,
+    path: &str,
+    issuer: &dyn CertificateIssuer,
+) -> Result<IssuedCertificate> {
+    info!(
+        "Renewing certificate at {} ({})",
+        path,
+        backend.backend_type()
+    );
+
+    let metadata = backend
+        .read_metadata(path)
+        .await
+        .context("Failed to read certificate metadata")?;
+    let request = CertificateRequest::from_metadata(&metadata)
+        .with_context(|| format!("{} is not a certificate secret", path))?;
+
+    let issued = issuer
+        .issue(&request)
+        .await
+        .with_context(|| format!("Failed to issue certificate for {}", request.common_name))?;
+
+    // Keep any other keys stored alongside the certificate
+    let current = backend
+        .read_secret(path)
+        .await
+        .context("Failed to read current certificate")?;
+    store(backend, path, current.data, &issued).await?;
+    record_issue(backend, path, metadata, &issued).await?;
+
+    info!(
+        "Renewed certificate at {} (serial {}, valid until {})",
+        path,
+        issued.serial_number,
+        issued.not_after.to_rfc3339()
+    );
+    Ok(issued)
+}
+
+async fn store(
+    backend: &dyn SecretBackend,
+    path: &str,
+    mut data: HashMap<String, String>,
+    issued: &IssuedCertificate,
+) -> Result<()> {
+    data.insert("certificate".to_string(), issued.certificate.clone());
+    data.insert("private_key".to_string(), issued.private_key.clone());
+    data.insert("ca_chain".to_string(), issued.ca_chain.clone());
+    data.insert("serial_number".to_string(), issued.serial_number.clone());
+    backend
+        .write_secret(path, data)
+        .await
+        .context("Failed to store certificate")
+}
+
+async fn record_issue(
+    backend: &dyn SecretBackend,
+    path: &str,
+    mut metadata: HashMap<String, String>,
+    issued: &IssuedCertificate,
+) -> Result<()> {
+    metadata.insert(ROTATION_METADATA_KEY.to_string(), "true".to_string());
+    metadata.insert(LAST_ROTATED_KEY.to_string(), Utc::now().to_rfc3339());
+    metadata.insert(NOT_AFTER_KEY.to_string(), issued.not_after.to_rfc3339());
+    rotation::write_rotated_metadata(backend, path, metadata)
+        .await
+        .context("Failed to update certificate metadata")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::MemoryBackend;
+
+    struct StaticIssuer;
+
+    #[async_trait::async_trait]
+    impl CertificateIssuer for StaticIssuer {
+        async fn issue(&self, request: &CertificateRequest) -> Result<IssuedCertificate> {
+            Ok(IssuedCertificate {
+                certificate: format!("CERT {}", request.common_name),
+                private_key: "KEY".to_string(),
+                ca_chain: "CA".to_string(),
+                serial_number: "01:02".to_string(),
+                not_after: DateTime::parse_from_rfc3339("2030-01-31T00:00:00Z")
+                    .unwrap()
+                    .with_timezone(&Utc),
+            })
+        }
+
+        fn issuer_type(&self) -> &'static str {
+            "static"
+        }
+    }
+
+    fn at(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(date)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_needs_renewal() {
+        let mut metadata = HashMap::from([
+            (COMMON_NAME_KEY.to_string(), "api.example.com".to_string()),
+            (
+                NOT_AFTER_KEY.to_string(),
+                "2030-01-31T00:00:00Z".to_string(),
+            ),
+        ]);
+        assert!(!needs_renewal(&metadata, at("2029-12-31T00:00:00Z")));
+        assert!(needs_renewal(&metadata, at("2030-01-01T00:00:00Z")));
+
+        metadata.insert(RENEW_BEFORE_KEY.to_string(), "7".to_string());
+        assert!(!needs_renewal(&metadata, at("2030-01-01T00:00:00Z")));
+
+        metadata.remove(NOT_AFTER_KEY);
+        assert!(needs_renewal(&metadata, at("2020-01-01T00:00:00Z")));
+    }
+
+    #[tokio::test]
+    async fn test_issue_and_renew() -> Result<()> {
+        let backend = MemoryBackend::new().with_secret("certs/api", [("dhparam", "DH")]);
+        let mut request = CertificateRequest::new("api.example.com");
+        request.alt_names = vec!["api".to_string(), "api.internal".to_string()];
+        request.ttl = Some("720h".to_string());
+
+        issue_certificate(&backend, "certs/api", &StaticIssuer, &request).await?;
+        let metadata = backend.read_metadata("certs/api").await?;
+        assert!(is_certificate(&metadata));
+        // The issuer is recorded so the same one renews the certificate
+        request.issuer = Some("static".to_string());
+        assert_eq!(CertificateRequest::from_metadata(&metadata), Some(request));
+        assert_eq!(not_after(&metadata), Some(at("2030-01-31T00:00:00Z")));
+        assert_eq!(metadata[ROTATION_METADATA_KEY], "true");
+
+        // Keys stored next to the certificate survive issue and renewal
+        assert_eq!(
+            backend.read_secret("certs/api").await?.data["dhparam"],
+            "DH"
+        );
+        renew_certificate(&backend, "certs/api", &StaticIssuer).await?;
+        let data = backend.read_secret("certs/api").await?.data;
+        assert_eq!(data["certificate"], "CERT api.example.com");
+        assert_eq!(data["private_key"], "KEY");
+        assert_eq!(data["dhparam"], "DH");
+
+        assert!(renew_certificate(&backend, "missing", &StaticIssuer)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_vault_pki_issuer() -> Result<()> {
+        use axum::extract::Path;
+        use axum::Json;
+
+        let app = axum::Router::new().route(
+            "/v1/pki/issue/:role",
+            axum::routing::post(
+                |Path(role): Path<String>, Json(body): Json<serde_json::Value>| async move {
+                    assert_eq!(role, "web");
+                    assert_eq!(body["common_name"], "api.example.com");
+                    assert_eq!(body["alt_names"], "api,api.internal");
+                    assert_eq!(body["ttl"], "720h");
+                    Json(serde_json::json!({ "data": {
+                        "certificate": "CERT",
+                        "private_key": "KEY",
+                        "private_key_type": "rsa",
+                        "issuing_ca": "CA",
+                        "ca_chain": ["CA", "ROOT"],
+                        "serial_number": "01:02",
+                        "expiration": 1896048000,
+                    }}))
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config: PkiConfig = toml::from_str("role = \"web\"")?;
+        let issuer = VaultPkiIssuer::new(VaultClient::new(address, "token".into())?, &config);
+        let mut request = CertificateRequest::new("api.example.com");
+        request.alt_names = vec!["api".to_string(), "api.internal".to_string()];
+        request.ttl = Some("720h".to_string());
+
+        let issued = issuer.issue(&request).await?;
+        assert_eq!(issued.ca_chain, "CA\nROOT");
+        assert_eq!(issued.not_after, at("2030-01-31T00:00:00Z"));
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! Circuit breaker for rotations against a target
+//!
+//! When the target itself is broken (e.g., the admin credential it connects
+//! with has expired), every rotation against it fails the same way. After
+//! `circuit_breaker_threshold` consecutive failures the breaker opens, and
+//! further rotations against the target fail at once without touching the
+//! backend or the target. Once `circuit_breaker_cooldown_minutes` have passed,
+//! one rotation is let through again: if it succeeds the breaker closes, if it
+//! fails the breaker stays open for another cooldown.
+//!
+//! Only failures of the target itself count (see [`TargetFailure`]). A
+//! rotation that fails on the backend or on its own checks says nothing about
+//! the target and leaves the breaker as it is.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+use std::sync::Mutex;
+
+/// Rotation refused because the target's breaker is open
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Circuit breaker open for the {target} target after {failures} consecutive failures \
+     (last: {last_error}); not rotating"
+)]
+pub struct CircuitOpen {
+    pub target: String,
+    pub failures: u32,
+    pub last_error: String,
+}
+
+/// Context marking an error as a failure of the target (updating, verifying
+/// or activating a password), which is what the breaker counts
+#[derive(Debug)]
+pub struct TargetFailure(pub String);
+
+impl fmt::Display for TargetFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// State of an open breaker, for reporting
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenCircuit {
+    pub failures: u32,
+    pub since: DateTime<Utc>,
+    pub last_error: String,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    consecutive_failures: u32,
+    opened_at: Option<DateTime<Utc>>,
+    last_error: String,
+}
+
+/// Counts consecutive rotation failures against one target
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    /// Failures that open the breaker (0 never opens it)
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown_minutes: u64) -> Self {
+        Self {
+            threshold,
+            cooldown: Duration::minutes(cooldown_minutes as i64),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Fail if the breaker is open and its cooldown has not passed yet
+    pub fn check(&self, target: &str) -> Result<(), CircuitOpen> {
+        let state = self.state();
+        match state.opened_at {
+            Some(opened_at) if Utc::now() < opened_at + self.cooldown => Err(CircuitOpen {
+                target: target.to_string(),
+                failures: state.consecutive_failures,
+                last_error: state.last_error.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count the outcome of a rotation that was let through
+    ///
+    /// Returns true when this failure opened the breaker (or opened it again
+    /// after a cooldown). Errors that are not a [`TargetFailure`] are ignored.
+    pub fn record<T>(&self, result: &Result<T>) -> bool {
+        let mut state = self.state();
+        match result {
+            Ok(_) => {
+                *state = State::default();
+                false
+            }
+            Err(e) if e.downcast_ref::<TargetFailure>().is_none() => false,
+            Err(e) => {
+                state.consecutive_failures += 1;
+                state.last_error = format!("{:#}", e);
+                if self.threshold > 0 && state.consecutive_failures >= self.threshold {
+                    state.opened_at = Some(Utc::now());
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// The breaker's state if it is open
+    pub fn open_circuit(&self) -> Option<OpenCircuit> {
+        let state = self.state();
+        state.opened_at.map(|since| OpenCircuit {
+            failures: state.consecutive_failures,
+            since,
+            last_error: state.last_error.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn failure() -> Result<()> {
+        Err(anyhow::anyhow!(
+            "password authentication failed for user \"admin\""
+        ))
+        .context(TargetFailure("Failed to update postgres password".into()))
+    }
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, 30);
+        assert!(!breaker.record(&failure()));
+        assert!(!breaker.record(&Ok(())));
+        assert!(!breaker.record(&failure()));
+        assert!(!breaker.record(&failure()));
+        assert!(breaker.check("postgres").is_ok());
+        assert!(breaker.record(&failure()));
+
+        let err = breaker.check("postgres").unwrap_err();
+        assert_eq!(err.failures, 3);
+        assert!(err.to_string().contains("password authentication failed"));
+        assert!(breaker.open_circuit().is_some());
+
+        // Without a cooldown the next rotation is let through and closes it
+        let breaker = CircuitBreaker::new(1, 0);
+        assert!(breaker.record(&failure()));
+        assert!(breaker.check("api").is_ok());
+        breaker.record(&Ok(()));
+        assert!(breaker.open_circuit().is_none());
+
+        // Failures outside the target neither count nor reset the count
+        let breaker = CircuitBreaker::new(2, 30);
+        assert!(!breaker.record(&failure()));
+        let backend: Result<()> = Err(anyhow::anyhow!("Failed to read current secret"));
+        for _ in 0..5 {
+            assert!(!breaker.record(&backend));
+        }
+        assert!(breaker.record(&failure().context("Rotation of app/db failed")));
+
+        let disabled = CircuitBreaker::new(0, 30);
+        for _ in 0..10 {
+            assert!(!disabled.record(&failure()));
+        }
+        assert!(disabled.check("api").is_ok());
+    }
+}
@@ -3,15 +3,54 @@
 //! This module handles command-line argument parsing and routes commands to the appropriate handlers.
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use std::io::IsTerminal;
 use std::path::PathBuf;
-use tracing::{error, info};
-
-use crate::backends::Backend;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::audit::{AuditEvent, AuditLog, Outcome};
+use crate::backends::{
+    Backend, BackendRoute, BackendType, CachedBackend, MirroredBackend, RoutedBackend,
+    SecretBackend,
+};
+use crate::backup;
+use crate::certs;
+use crate::circuit_breaker::CircuitOpen;
+use crate::clipboard;
+use crate::compliance;
 use crate::config::Config;
+use crate::diff;
+use crate::doctor;
+use crate::encrypt;
 use crate::env_updater;
+use crate::external;
+use crate::hooks;
+use crate::inventory;
+use crate::leader::{BackendLease, KubernetesLease, Leadership, LeaseKind, LeaseStore};
+use crate::listener;
+use crate::logging::LogFormat;
+use crate::metadata_store::{self, MetadataStoreBackend};
+use crate::migrate;
+use crate::notify::Notifier;
+use crate::output;
+use crate::path_filter::{MetadataCondition, MetadataSelector, PathFilter};
+use crate::plan::{self, PlanFormat, PlanOptions, RotationPlan};
+use crate::plugins;
+use crate::progress::Progress;
+use crate::redact::{self, SecretString};
+use crate::report;
+use crate::retry::RetryPolicy;
 use crate::rotation;
-use crate::targets::{Target, TargetInstance};
+use crate::rotator::{RotatedSecret, Rotator, Verification};
+use crate::run_report::{RunReport, SecretStatus};
+use crate::server;
+use crate::structured_file::StructuredFormat;
+use crate::targets::{SmtpTarget, Target, TargetInstance, WireGuardTarget};
+use crate::transfer;
+use crate::transit::{self, Transit};
+use crate::vault_db;
 
 #[derive(Parser)]
 #[command(name = "asr")]
@@ -22,6 +61,14 @@ pub struct Cli {
     #[arg(short, long, env = "ROTATOR_CONFIG")]
     pub config: Option<PathBuf>,
 
+    /// Configuration file format (detected from the extension if not set, TOML otherwise)
+    #[arg(long, value_enum, env = "ROTATOR_CONFIG_FORMAT")]
+    pub config_format: Option<StructuredFormat>,
+
+    /// Named profile from the config file's [profiles] section
+    #[arg(long, env = "ASR_PROFILE")]
+    pub profile: Option<String>,
+
     /// Vault address (overrides config file)
     #[arg(long, env = "VAULT_ADDR")]
     pub vault_addr: Option<String>,
@@ -34,14 +81,144 @@ pub struct Cli {
     #[arg(long, env = "VAULT_MOUNT")]
     pub vault_mount: Option<String>,
 
+    /// AWS region, or comma-separated regions holding copies of the secrets
+    /// (overrides config file; the first is read from, all are written)
+    #[arg(long, value_delimiter = ',')]
+    pub aws_region: Vec<String>,
+
     /// Secret backend to use (vault or aws)
     #[arg(long, env = "SECRET_BACKEND")]
     pub backend: Option<String>,
 
+    /// Rotate without asking for confirmation on a terminal
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+
+    /// Log line format (overrides config file)
+    #[arg(long, global = true, value_enum, env = "ASR_LOG_FORMAT")]
+    pub log_format: Option<LogFormat>,
+
+    /// Print without colors (also off when output is not a terminal or NO_COLOR is set)
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Rewrite the config file's deprecated [database] section as [targets.postgres]
+    Migrate {
+        /// Print the migrated config instead of writing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbAction {
+    /// Rotate the root credential Vault uses to connect to each database
+    RotateRoot {
+        /// Connections to rotate (default: [vault_database] connections)
+        connections: Vec<String>,
+    },
+
+    /// Rotate the passwords of static roles now instead of waiting for their period
+    RotateRole {
+        /// Static roles to rotate (default: [vault_database] static_roles)
+        roles: Vec<String>,
+    },
+
+    /// Show dynamic credential leases that are about to expire
+    Leases {
+        /// Lease prefixes to check (default: [vault_database] lease_prefixes)
+        prefixes: Vec<String>,
+
+        /// Renew the leases that expire within renew_within_hours
+        #[arg(long)]
+        renew: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MetaAction {
+    /// Print a secret's metadata, or only the given keys
+    Get {
+        /// Path to the secret
+        path: String,
+
+        /// Keys to print (a single key prints just its value)
+        keys: Vec<String>,
+    },
+
+    /// Set metadata keys, keeping the others
+    Set {
+        /// Path to the secret
+        path: String,
+
+        /// KEY=VALUE pairs to set
+        #[arg(required = true, value_parser = parse_key_value, value_name = "KEY=VALUE")]
+        pairs: Vec<(String, String)>,
+    },
+
+    /// Remove metadata keys
+    Unset {
+        /// Path to the secret
+        path: String,
+
+        /// Keys to remove
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ReencryptAction {
+    /// List data-encryption keys whose re-encryption is pending or failed
+    Status {
+        /// Base path to check
+        #[arg(default_value = "")]
+        path: String,
+    },
+
+    /// Mark the re-encryption for a key complete once the application is done
+    Done {
+        /// Path to the data-encryption key
+        path: String,
+    },
+}
+
+/// Parse a `KEY=VALUE` argument
+fn parse_key_value(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => anyhow::bail!("expected KEY=VALUE, got '{}'", s),
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ReportKind {
+    /// List flagged secrets with age, policy, due date and days overdue, against an SLA
+    Compliance {
+        /// Base path to report on (leave empty for root)
+        #[arg(default_value = "")]
+        path: String,
+
+        /// Report format (detected from --out if not set, Markdown otherwise)
+        #[arg(short, long, value_enum)]
+        format: Option<compliance::ComplianceFormat>,
+
+        /// Output file (prints to stdout if not set)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+
+        /// Percent of flagged secrets that must be within policy (overrides [rotation] sla_percent)
+        #[arg(long)]
+        sla: Option<f64>,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Initialize a sample configuration file
@@ -56,9 +233,17 @@ pub enum Commands {
         /// Path to the secret
         path: String,
 
-        /// Rotation period in months
+        /// Rotation period in months (6, 1.5) or days (90d)
         #[arg(short, long, default_value = "6")]
-        period: u32,
+        period: rotation::RotationPeriod,
+
+        /// Spread the first due date by up to this many days either way (overrides [rotation] jitter_days)
+        #[arg(long, value_name = "DAYS")]
+        jitter_days: Option<u32>,
+
+        /// Team responsible for the secret, used to route notifications
+        #[arg(long)]
+        owner: Option<String>,
     },
 
     /// Scan for secrets that need rotation
@@ -66,6 +251,22 @@ pub enum Commands {
         /// Base path to scan (leave empty for root)
         #[arg(default_value = "")]
         path: String,
+
+        /// Only consider secrets matching this glob, e.g. 'prod/**' (repeatable)
+        #[arg(long = "include", value_name = "GLOB")]
+        include: Vec<String>,
+
+        /// Only consider secrets matching this regular expression (repeatable)
+        #[arg(long = "match-regex", value_name = "REGEX")]
+        match_regex: Vec<String>,
+
+        /// Only consider secrets whose metadata has KEY=VALUE, e.g. team=payments (repeatable)
+        #[arg(long = "where", value_name = "KEY=VALUE")]
+        conditions: Vec<MetadataCondition>,
+
+        /// Don't report progress on stderr
+        #[arg(short, long)]
+        quiet: bool,
     },
 
     /// Rotate a specific secret
@@ -84,6 +285,64 @@ pub enum Commands {
         /// Target username/identifier to update (required if --update-target is set)
         #[arg(long)]
         target_username: Option<String>,
+
+        /// Do not print the new secret (the default when stdout is not a terminal)
+        #[arg(long)]
+        no_show: bool,
+
+        /// Copy the new secret to the clipboard
+        #[arg(long)]
+        copy: bool,
+
+        /// Seconds before the copied secret is cleared from the clipboard (0 keeps it)
+        #[arg(long, default_value = "45", requires = "copy")]
+        clear_after: u64,
+
+        /// Print the new secret encrypted to an age recipient or GnuPG key
+        #[arg(long, value_name = "RECIPIENT", conflicts_with = "no_show")]
+        encrypt_to: Option<encrypt::Recipient>,
+
+        /// Print a single-use Vault wrapping token valid for this long (e.g., 5m) instead of the secret
+        #[arg(
+            long,
+            value_name = "TTL",
+            conflicts_with_all = ["no_show", "copy", "encrypt_to", "value_from_stdin", "prompt"]
+        )]
+        wrap_ttl: Option<String>,
+
+        /// Store a value read from stdin (e.g., issued by a third party) instead of generating one
+        #[arg(long, conflicts_with = "prompt")]
+        value_from_stdin: bool,
+
+        /// Prompt for the value to store, without echo, instead of generating one
+        #[arg(long)]
+        prompt: bool,
+    },
+
+    /// Issue a certificate into a secret and renew it before it expires
+    IssueCert {
+        /// Path to store the certificate and key
+        path: String,
+
+        /// Common name of the certificate
+        #[arg(long)]
+        common_name: String,
+
+        /// Subject alternative names (comma-separated or repeated)
+        #[arg(long, value_delimiter = ',')]
+        alt_names: Vec<String>,
+
+        /// Requested lifetime (e.g., 720h); the role's default if not set
+        #[arg(long)]
+        ttl: Option<String>,
+
+        /// PKI role to issue from (overrides [pki] role)
+        #[arg(long)]
+        role: Option<String>,
+
+        /// Issuer to use: vault-pki or acme (defaults to [pki] if configured, else [acme])
+        #[arg(long)]
+        issuer: Option<String>,
     },
 
     /// Automatically rotate all secrets that are due for rotation
@@ -96,19 +355,163 @@ pub enum Commands {
         #[arg(long)]
         dry_run: bool,
 
-        /// Also update local environment variables (expects env var name to match secret path)
+        /// Also update local environment variables (names from `env_var` metadata, [env.mappings], or the secret path)
         #[arg(long)]
         update_env: bool,
 
         /// Also update target passwords (requires target config and metadata)
         #[arg(long)]
         update_target: bool,
+
+        /// Shell config file to update with --update-env (repeatable; overrides [env] shell_files)
+        #[arg(long = "shell-file")]
+        shell_files: Vec<String>,
+
+        /// Rotate the due secrets as one unit: if any fails, restore all of them
+        #[arg(long)]
+        atomic: bool,
+
+        /// Only consider secrets matching this glob, e.g. 'prod/**' (repeatable)
+        #[arg(long = "include", value_name = "GLOB")]
+        include: Vec<String>,
+
+        /// Only consider secrets matching this regular expression (repeatable)
+        #[arg(long = "match-regex", value_name = "REGEX")]
+        match_regex: Vec<String>,
+
+        /// Only consider secrets whose metadata has KEY=VALUE, e.g. team=payments (repeatable)
+        #[arg(long = "where", value_name = "KEY=VALUE")]
+        conditions: Vec<MetadataCondition>,
+
+        /// Rotate at most N secrets, leaving the rest for the next run (overrides [rotation] max_per_run)
+        #[arg(long, value_name = "N")]
+        max_rotations: Option<usize>,
+
+        /// Dry-run plan format: table or json
+        #[arg(long, value_enum, default_value_t = PlanFormat::Table, requires = "dry_run")]
+        format: PlanFormat,
+
+        /// Check every flagged secret's stored credential against the target instead of rotating
+        #[arg(long, conflicts_with_all = ["dry_run", "update_env", "update_target", "atomic"])]
+        verify_only: bool,
+
+        /// Don't report progress on stderr
+        #[arg(short, long)]
+        quiet: bool,
     },
 
     /// Read a secret
     Read {
         /// Path to the secret
         path: String,
+
+        /// Print only the value of this key
+        #[arg(long, value_name = "KEY")]
+        key: Option<String>,
+
+        /// With --key, print the value exactly as stored, without a trailing newline
+        #[arg(long, requires = "key")]
+        raw: bool,
+
+        /// Print every key in a machine-readable format
+        #[arg(long, value_enum, conflicts_with = "key")]
+        format: Option<transfer::FieldFormat>,
+
+        /// Print the secret encrypted to an age recipient or GnuPG key instead of plaintext
+        #[arg(long, value_name = "RECIPIENT")]
+        encrypt_to: Option<encrypt::Recipient>,
+
+        /// Print a single-use Vault wrapping token valid for this long (e.g., 5m) instead of the secret
+        #[arg(long, value_name = "TTL", conflicts_with_all = ["raw", "format", "encrypt_to"])]
+        wrap_ttl: Option<String>,
+    },
+
+    /// Create or replace a secret with explicit KEY=VALUE pairs
+    Write {
+        /// Path to the secret
+        path: String,
+
+        /// KEY=VALUE pairs; KEY=- reads the value from stdin, or prompts for it on a terminal
+        #[arg(required = true, value_parser = parse_key_value, value_name = "KEY=VALUE")]
+        pairs: Vec<(String, String)>,
+
+        /// Keep the secret's other keys instead of replacing all of its data
+        #[arg(long)]
+        merge: bool,
+    },
+
+    /// Write the configured composite secrets from their inputs now
+    Compose {
+        /// Only the composite at this path
+        path: Option<String>,
+    },
+
+    /// Apply a secret's stored value to the target again, after the target
+    /// credential was changed out of band
+    RestoreTarget {
+        /// Path to the secret
+        path: String,
+
+        /// Apply this earlier version instead, writing it back as the current value
+        #[arg(long)]
+        version: Option<u64>,
+
+        /// Target user (defaults to target_username from the secret's metadata)
+        #[arg(long)]
+        target_username: Option<String>,
+    },
+
+    /// Put back a previous value of a secret kept by the backend
+    Rollback {
+        /// Path to the secret
+        path: String,
+
+        /// Version to restore (defaults to the one before the current value)
+        #[arg(long, conflicts_with = "list")]
+        version: Option<u64>,
+
+        /// List the kept versions instead
+        #[arg(long)]
+        list: bool,
+    },
+
+    /// Show which keys differ between two versions of a secret, or between
+    /// backends
+    Diff {
+        /// Path to the secret
+        path: String,
+
+        /// Version to compare from (defaults to the one before --to)
+        #[arg(long, conflicts_with = "other_backend")]
+        from: Option<u64>,
+
+        /// Version to compare to (defaults to the current value)
+        #[arg(long, conflicts_with = "other_backend")]
+        to: Option<u64>,
+
+        /// Compare the current value with the same secret in this backend (e.g., aws)
+        #[arg(long, value_name = "BACKEND")]
+        other_backend: Option<String>,
+
+        /// Path of the secret in the other backend (defaults to PATH)
+        #[arg(long, requires = "other_backend")]
+        other_path: Option<String>,
+
+        /// Print the values of the keys too (asks for confirmation)
+        #[arg(long)]
+        show_values: bool,
+    },
+
+    /// Track application-side re-encryption after data-encryption keys rotate
+    Reencrypt {
+        #[command(subcommand)]
+        action: ReencryptAction,
+    },
+
+    /// Read or change a secret's metadata (target_username, owner, rotation settings, ...)
+    Meta {
+        #[command(subcommand)]
+        action: MetaAction,
     },
 
     /// List secrets at a path
@@ -130,6 +533,10 @@ pub enum Commands {
         /// Environment variable name to update
         #[arg(short, long)]
         env_var: String,
+
+        /// Shell config file to update (repeatable; overrides [env] shell_files)
+        #[arg(long = "shell-file")]
+        shell_files: Vec<String>,
     },
 
     /// Generate a new password, store it, and optionally update local environment variable
@@ -148,275 +555,1792 @@ pub enum Commands {
         /// Length of the generated password
         #[arg(short, long)]
         length: Option<usize>,
-    },
-}
 
-/// Execute a CLI command
-pub async fn execute(cli: Cli) -> Result<()> {
-    // Handle init command separately as it doesn't need backend
-    if let Commands::Init { output } = cli.command {
-        Config::create_sample(&output)
-            .with_context(|| format!("Failed to create sample config at {:?}", output))?;
-        info!("Sample configuration created at {:?}", output);
-        return Ok(());
-    }
+        /// Shell config file to update with --env-var (repeatable; overrides [env] shell_files)
+        #[arg(long = "shell-file")]
+        shell_files: Vec<String>,
 
-    // Load configuration
-    let mut config = if let Some(config_path) = cli.config {
-        Config::from_file(&config_path)
-            .with_context(|| format!("Failed to load config from {:?}", config_path))?
-    } else {
-        Config::from_env().context("Failed to load config from environment")?
-    };
+        /// Copy the generated password to the clipboard
+        #[arg(long)]
+        copy: bool,
 
-    // Override backend selection if provided
-    if let Some(backend) = cli.backend {
-        config.backend = backend.to_lowercase();
-    }
+        /// Seconds before the copied password is cleared from the clipboard (0 keeps it)
+        #[arg(long, default_value = "45", requires = "copy")]
+        clear_after: u64,
 
-    // Override with CLI arguments if provided
-    if let Some(addr) = cli.vault_addr {
-        if let Some(ref mut vault_config) = config.vault {
-            vault_config.address = addr;
-        }
-    }
-    if let Some(token) = cli.vault_token {
-        if let Some(ref mut vault_config) = config.vault {
-            vault_config.token = token;
-        }
-    }
-    if let Some(mount) = cli.vault_mount {
-        if let Some(ref mut vault_config) = config.vault {
-            vault_config.mount = mount;
-        }
-    }
+        /// Print the generated password encrypted to an age recipient or GnuPG key
+        #[arg(long, value_name = "RECIPIENT")]
+        encrypt_to: Option<encrypt::Recipient>,
 
-    // Create backend client based on configuration
-    let backend = create_backend(&config).await?;
+        /// Store a password read from stdin instead of generating one
+        #[arg(long, conflicts_with_all = ["prompt", "length"])]
+        value_from_stdin: bool,
 
-    // Create target if target config is present (support both legacy database and new targets)
-    let target = create_target(&config, backend.as_ref()).await?;
+        /// Prompt for the password to store, without echo, instead of generating one
+        #[arg(long, conflicts_with = "length")]
+        prompt: bool,
+    },
 
-    // Execute command
-    match cli.command {
-        Commands::Init { .. } => unreachable!(), // Handled above
+    /// Import secrets from a dotenv (.env) file
+    Import {
+        /// Path to the dotenv file to import
+        #[arg(long)]
+        env_file: PathBuf,
 
-        Commands::Flag { path, period } => {
-            rotation::flag_for_rotation(backend.as_ref(), &path, period)
-                .await
-                .context("Failed to flag secret for rotation")?;
-            println!(
-                "Successfully flagged {} for rotation every {} months",
-                path, period
-            );
-        }
+        /// Path prefix for imported secrets (e.g., "myapp/")
+        #[arg(long, default_value = "")]
+        prefix: String,
 
-        Commands::Scan { path } => {
-            let secrets =
-                rotation::scan_for_rotation(backend.as_ref(), &path, config.rotation.period_months)
-                    .await
-                    .context("Failed to scan for secrets needing rotation")?;
+        /// Store all variables as keys of a single secret at the prefix path
+        #[arg(long)]
+        single: bool,
 
-            if secrets.is_empty() {
-                println!("No secrets need rotation at this time");
-            } else {
-                println!("Secrets needing rotation:");
-                for secret in secrets {
-                    println!("  - {}", secret);
-                }
-            }
-        }
+        /// Rotation period in months for imported secrets
+        #[arg(short, long, default_value = "6")]
+        period: u32,
 
-        Commands::Rotate {
-            path,
-            update_target,
-            target_type: _target_type,
-            target_username,
-        } => {
-            if update_target && target_username.is_none() {
-                anyhow::bail!("--target-username is required when --update-target is set");
-            }
+        /// Do not flag imported secrets for rotation
+        #[arg(long)]
+        no_flag: bool,
+    },
 
-            if update_target && target.is_none() {
-                anyhow::bail!("Target configuration not found. Configure [targets.postgres] or [targets.api] section in config file");
-            }
+    /// Export secrets to a dotenv, JSON, or YAML file
+    Export {
+        /// Base path to export (leave empty for root)
+        #[arg(long, default_value = "")]
+        path: String,
 
-            let new_secret = if update_target {
-                rotation::rotate_secret_with_target(
-                    backend.as_ref(),
-                    &path,
-                    config.rotation.secret_length,
-                    target.as_ref().map(|t| t.as_ref() as &dyn Target),
-                    target_username.as_deref(),
-                )
-                .await
-                .context("Failed to rotate secret")?
-            } else {
-                rotation::rotate_secret(backend.as_ref(), &path, config.rotation.secret_length)
-                    .await
-                    .context("Failed to rotate secret")?
-            };
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "dotenv")]
+        format: transfer::ExportFormat,
 
-            println!("Successfully rotated secret at: {}", path);
-            if update_target {
-                let target_type_name = target
-                    .as_ref()
-                    .map(|t| t.target_type())
-                    .unwrap_or("unknown");
-                println!(
-                    "Updated {} password for user: {}",
-                    target_type_name,
-                    target_username.as_deref().unwrap_or("unknown")
-                );
-            }
-            eprintln!("WARNING: Secret value will be displayed. Ensure this output is secured.");
-            println!("New secret value: {}", new_secret);
-            eprintln!("Please update your application with the new secret and clear your terminal history.");
-        }
+        /// Output file (prints to stdout if not set)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
 
-        Commands::Auto {
-            path,
-            dry_run,
-            update_env,
-            update_target,
-        } => {
-            if update_target && target.is_none() {
-                anyhow::bail!("Target configuration not found. Configure [targets.postgres] or [targets.api] section in config file");
-            }
-            let secrets =
-                rotation::scan_for_rotation(backend.as_ref(), &path, config.rotation.period_months)
-                    .await
-                    .context("Failed to scan for secrets needing rotation")?;
+        /// Replace secret values with a placeholder
+        #[arg(long)]
+        redact: bool,
+    },
 
-            if secrets.is_empty() {
-                println!("No secrets need rotation at this time");
-                return Ok(());
-            }
+    /// Save secrets and their metadata to one encrypted file
+    Backup {
+        /// Base path to back up (leave empty for root)
+        #[arg(long, default_value = "")]
+        path: String,
 
-            println!("Found {} secret(s) needing rotation", secrets.len());
+        /// File to write the encrypted backup to
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// age recipient or GnuPG key to encrypt the backup to
+        #[arg(
+            short,
+            long,
+            value_name = "RECIPIENT",
+            required_unless_present = "transit"
+        )]
+        recipient: Option<encrypt::Recipient>,
+
+        /// Seal the backup with the [transit] Vault key instead
+        #[arg(long, conflicts_with = "recipient")]
+        transit: bool,
+    },
 
-            let env_updater = if update_env {
-                Some(env_updater::EnvUpdater::new().context("Failed to create EnvUpdater")?)
-            } else {
-                None
-            };
+    /// Write secrets back from a file made by `backup`
+    Restore {
+        /// Encrypted backup file
+        file: PathBuf,
 
-            for secret_path in &secrets {
-                if dry_run {
-                    println!("[DRY RUN] Would rotate: {}", secret_path);
-                    if update_env {
-                        println!("  [DRY RUN] Would update env var based on path");
-                    }
-                    if update_target {
-                        println!(
-                            "  [DRY RUN] Would update target password (username from metadata)"
+        /// age identity (private key) file; GnuPG backups use the keyring and
+        /// transit-sealed backups the [vault] connection
+        #[arg(short, long)]
+        identity: Option<PathBuf>,
+
+        /// Only restore secrets matching this glob, e.g. 'prod/db/*' (repeatable)
+        #[arg(long = "include", value_name = "GLOB")]
+        include: Vec<String>,
+
+        /// Only restore secrets matching this regular expression (repeatable)
+        #[arg(long = "match-regex", value_name = "REGEX")]
+        match_regex: Vec<String>,
+
+        /// Replace secrets that already exist instead of skipping them
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Show what would be restored without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Generate a shareable report of rotation posture
+    Report {
+        #[command(subcommand)]
+        kind: Option<ReportKind>,
+
+        /// Base path to report on (leave empty for root)
+        #[arg(default_value = "")]
+        path: String,
+
+        /// Report format (detected from --out if not set, Markdown otherwise)
+        #[arg(short, long, value_enum)]
+        format: Option<report::ReportFormat>,
+
+        /// Output file (prints to stdout if not set)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+
+        /// Rotate due secrets first and include the results in the report
+        #[arg(long)]
+        rotate: bool,
+
+        /// Send each owner their overdue and failed secrets via [notifications] webhooks
+        #[arg(long)]
+        notify: bool,
+
+        /// With --notify, also remind owners of secrets due within this many days
+        /// (overrides [notifications] due_soon_days)
+        #[arg(long, value_name = "DAYS", requires = "notify")]
+        due_soon: Option<u32>,
+    },
+
+    /// Export every secret with its owner, target, policy and due date for a CMDB (no values)
+    Inventory {
+        /// Base path to inventory (leave empty for root)
+        #[arg(default_value = "")]
+        path: String,
+
+        /// Output format (detected from --out if not set, JSON otherwise)
+        #[arg(short, long, value_enum)]
+        format: Option<inventory::InventoryFormat>,
+
+        /// Output file (prints to stdout if not set)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Manage Vault's database secrets engine (root credentials, static roles, leases)
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+
+    /// Maintain the configuration file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Check backend connectivity, token and policy permissions, and target access
+    Doctor {
+        /// Path to check permissions under (leave empty for root)
+        #[arg(default_value = "")]
+        path: String,
+    },
+
+    /// Serve a REST API for triggering scans and rotations
+    Serve {
+        /// Address to listen on (overrides [server] bind)
+        #[arg(long)]
+        bind: Option<String>,
+
+        /// Admin bearer token clients can present (overrides [server] token)
+        #[arg(long, env = "ASR_SERVER_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+
+        /// Secret webhook senders sign request bodies with (overrides [server] webhook_secret)
+        #[arg(long, env = "ASR_WEBHOOK_SECRET", hide_env_values = true)]
+        webhook_secret: Option<String>,
+    },
+
+    /// Rotate secrets named by messages on an SQS queue (e.g., fed by EventBridge)
+    Listen {
+        /// Queue to consume (overrides [listener] queue_url)
+        #[arg(long, env = "ASR_QUEUE_URL")]
+        queue_url: Option<String>,
+    },
+
+    /// Generate shell completion scripts
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Generate a man page
+    #[command(hide = true)]
+    Mangen {
+        /// Output directory for the man page (prints to stdout if not set)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Clear a copied secret from the clipboard after a delay (reads the value from stdin)
+    #[command(hide = true)]
+    ClipboardClear {
+        /// Seconds to wait before clearing
+        #[arg(long)]
+        after: u64,
+    },
+
+    /// Run `asr-<name>` from PATH with the resolved settings in its environment
+    #[command(external_subcommand)]
+    External(Vec<String>),
+}
+
+/// Error returned by `auto` when some due secrets could not be rotated
+///
+/// The CLI exits with [`RotationFailures::exit_code`] instead of the usual 1,
+/// so schedulers can tell partial failures from total ones.
+#[derive(Debug, thiserror::Error)]
+#[error("{failed} secret(s) failed to rotate, {rotated} rotated")]
+pub struct RotationFailures {
+    pub rotated: usize,
+    pub failed: usize,
+}
+
+impl RotationFailures {
+    /// 2 if some secrets were rotated, 3 if none were
+    pub fn exit_code(&self) -> u8 {
+        if self.rotated > 0 {
+            2
+        } else {
+            3
+        }
+    }
+}
+
+/// Execute a CLI command
+pub async fn execute(cli: Cli) -> Result<()> {
+    // Handle init command separately as it doesn't need backend
+    if let Commands::Init { output } = cli.command {
+        Config::create_sample(&output)
+            .with_context(|| format!("Failed to create sample config at {:?}", output))?;
+        info!("Sample configuration created at {:?}", output);
+        return Ok(());
+    }
+
+    // Completions and man pages only need the clap command definition
+    if let Commands::Completions { shell } = cli.command {
+        let mut cmd = Cli::command();
+        let bin_name = cmd.get_name().to_string();
+        clap_complete::generate(shell, &mut cmd, bin_name, &mut std::io::stdout());
+        return Ok(());
+    }
+
+    if let Commands::Mangen { output } = cli.command {
+        let man = clap_mangen::Man::new(Cli::command());
+        let mut buffer = Vec::new();
+        man.render(&mut buffer)
+            .context("Failed to render man page")?;
+
+        match output {
+            Some(dir) => {
+                std::fs::create_dir_all(&dir)
+                    .with_context(|| format!("Failed to create directory {:?}", dir))?;
+                let man_path = dir.join("asr.1");
+                std::fs::write(&man_path, buffer)
+                    .with_context(|| format!("Failed to write man page to {:?}", man_path))?;
+                info!("Man page written to {:?}", man_path);
+            }
+            None => {
+                use std::io::Write;
+                std::io::stdout()
+                    .write_all(&buffer)
+                    .context("Failed to write man page to stdout")?;
+            }
+        }
+        return Ok(());
+    }
+
+    if let Commands::ClipboardClear { after } = cli.command {
+        return clipboard::run_scheduled_clear(Duration::from_secs(after));
+    }
+
+    if let Commands::Config {
+        action: ConfigAction::Migrate { dry_run },
+    } = cli.command
+    {
+        return migrate_config_file(cli.config.as_deref(), cli.config_format, dry_run);
+    }
+
+    // Unknown commands fail before the config is needed
+    let external = match cli.command {
+        Commands::External(ref args) => Some(external::resolve(&args[0])?),
+        _ => None,
+    };
+
+    // Load configuration
+    let from_file = cli.config.is_some();
+    let mut config = if let Some(ref config_path) = cli.config {
+        Config::load(config_path, cli.config_format, cli.profile.as_deref())
+            .with_context(|| format!("Failed to load config from {:?}", config_path))?
+    } else if let Some(ref profile) = cli.profile {
+        anyhow::bail!(
+            "Profile '{}' requested but no config file given (use --config)",
+            profile
+        );
+    } else {
+        Config::from_env().context("Failed to load config from environment")?
+    };
+    if let Some(warning) = migrate::deprecation_warning(&config, from_file) {
+        output::warn(warning);
+    }
+
+    if let Some(format) = cli.log_format {
+        config.logging.format = format;
+    }
+
+    // Override backend selection if provided
+    if let Some(ref backend) = cli.backend {
+        config.backend = backend.to_lowercase();
+    }
+
+    // Override with CLI arguments if provided
+    if let Some(ref addr) = cli.vault_addr {
+        if let Some(ref mut vault_config) = config.vault {
+            vault_config.address = addr.clone();
+        }
+    }
+    if let Some(ref token) = cli.vault_token {
+        if let Some(ref mut vault_config) = config.vault {
+            vault_config.token = token.clone().into();
+        }
+    }
+    if let Some(ref mount) = cli.vault_mount {
+        if let Some(ref mut vault_config) = config.vault {
+            vault_config.mount = mount.clone();
+        }
+    }
+
+    if !cli.aws_region.is_empty() {
+        if let Some(ref mut aws_config) = config.aws {
+            aws_config.region = cli.aws_region[0].clone();
+            aws_config.regions = cli.aws_region.clone();
+        }
+    }
+
+    register_config_secrets(&config);
+    crate::http::configure(&config.http).context("Invalid [http] configuration")?;
+    crate::logging::configure(&config.logging).context("Invalid [logging] configuration")?;
+
+    if let (Some(program), Commands::External(args)) = (external, &cli.command) {
+        return run_external(&program, &args[1..], &cli, &config).await;
+    }
+
+    // Doctor reports backend and target failures instead of stopping at them
+    if let Commands::Doctor { path } = cli.command {
+        return run_doctor(&config, &path).await;
+    }
+
+    // Create backend client based on configuration
+    let backend = create_backend(&config).await?;
+
+    // Create target if target config is present (support both legacy database and new targets)
+    let target = create_target(&config, backend.as_ref()).await?;
+    let canary = create_canary_target(&config, backend.as_ref()).await?;
+
+    // The server scans the same tree repeatedly; one-shot commands read each
+    // secret's metadata about once, so they go to the backend directly
+    let backend: Backend = if matches!(cli.command, Commands::Serve { .. })
+        && config.server.metadata_cache_seconds > 0
+    {
+        Box::new(CachedBackend::new(
+            backend,
+            Duration::from_secs(config.server.metadata_cache_seconds),
+        ))
+    } else {
+        backend
+    };
+
+    let mut builder = Rotator::builder()
+        .backend(backend)
+        .policy(config.rotation.clone())
+        .hooks(config.hooks.clone())
+        .composites(config.composites.clone())
+        .audit(AuditLog::from_config(&config.audit).context("Invalid [audit] configuration")?);
+    if let Some(target) = target {
+        builder = builder.target(target);
+    }
+    if let Some((canary, username)) = canary {
+        builder = builder.canary(canary, username);
+    }
+    for issuer in create_issuers(&config)? {
+        builder = builder.issuer(issuer);
+    }
+    let rotator = builder.build()?;
+
+    if let Commands::Serve {
+        bind,
+        token,
+        webhook_secret,
+    } = cli.command
+    {
+        let bind = bind.unwrap_or_else(|| config.server.bind.clone());
+        let mut tokens = config.server.tokens.clone();
+        if let Some(token) = token
+            .map(SecretString::from)
+            .or_else(|| config.server.token.clone())
+        {
+            tokens.insert(0, server::ApiTokenConfig::admin(token));
+        }
+        if tokens.is_empty() {
+            anyhow::bail!(
+                "An API token is required (use --token, ASR_SERVER_TOKEN, [server] token, or [[server.tokens]])"
+            );
+        }
+        for api_token in &tokens {
+            redact::register_secret(api_token.token.expose_secret());
+        }
+        let webhook_secret = webhook_secret
+            .map(SecretString::from)
+            .or_else(|| config.server.webhook_secret.clone());
+        if let Some(ref secret) = webhook_secret {
+            redact::register_secret(secret.expose_secret());
+        }
+        let leadership = start_leadership(&config).await?;
+        let retry = RetryPolicy {
+            attempts: config.server.retry_attempts,
+            base: Duration::from_secs(config.server.retry_base_seconds),
+            max: Duration::from_secs(config.server.retry_max_seconds),
+        };
+        return server::serve(rotator, &bind, tokens, webhook_secret, leadership, retry).await;
+    }
+
+    if let Commands::Listen { queue_url } = cli.command {
+        let queue_url = queue_url
+            .or_else(|| config.listener.queue_url.clone())
+            .context(
+            "An SQS queue is required (use --queue-url, ASR_QUEUE_URL, or [listener] queue_url)",
+        )?;
+        let region = config
+            .listener
+            .region
+            .clone()
+            .or_else(|| config.aws.as_ref().map(|aws| aws.region.clone()));
+        let leadership = start_leadership(&config).await?;
+        return listener::listen(rotator, &queue_url, region, leadership).await;
+    }
+
+    let backend = rotator.backend();
+    let target = rotator.target();
+    let yes = cli.yes;
+
+    // Execute command
+    match cli.command {
+        Commands::Init { .. }
+        | Commands::Completions { .. }
+        | Commands::Mangen { .. }
+        | Commands::ClipboardClear { .. }
+        | Commands::External(_)
+        | Commands::Doctor { .. }
+        | Commands::Config { .. }
+        | Commands::Serve { .. }
+        | Commands::Listen { .. } => {
+            unreachable!() // Handled above
+        }
+
+        Commands::Flag {
+            path,
+            period,
+            jitter_days,
+            owner,
+        } => {
+            let jitter_days = jitter_days.unwrap_or(config.rotation.jitter_days);
+            let mut result =
+                rotation::flag_for_rotation_every(backend, &path, period, jitter_days).await;
+            if let (Ok(()), Some(owner)) = (&result, &owner) {
+                result = rotation::set_owner(backend, &path, owner).await;
+            }
+            let outcome = if result.is_ok() {
+                Outcome::Success
+            } else {
+                Outcome::Failure
+            };
+            rotator.audit().record(&AuditEvent::new(
+                "flag",
+                &path,
+                backend.backend_type(),
+                outcome,
+            ));
+            result.context("Failed to flag secret for rotation")?;
+            output::success(format!(
+                "Successfully flagged {} for rotation every {}",
+                path, period
+            ));
+            if let Some(owner) = owner {
+                println!("  Owner: {}", owner);
+            }
+        }
+
+        Commands::Import {
+            env_file,
+            prefix,
+            single,
+            period,
+            no_flag,
+        } => {
+            let entries = crate::dotenv::parse_file(&env_file)
+                .with_context(|| format!("Failed to parse env file {:?}", env_file))?;
+
+            if entries.is_empty() {
+                output::note(format!("No variables found in {:?}", env_file));
+                return Ok(());
+            }
+
+            let options = transfer::ImportOptions {
+                prefix,
+                layout: if single {
+                    transfer::ImportLayout::Single
+                } else {
+                    transfer::ImportLayout::PerVariable
+                },
+                flag_period_months: if no_flag { None } else { Some(period) },
+                flag_jitter_days: config.rotation.jitter_days,
+            };
+
+            let written = transfer::import_entries(backend, &entries, &options)
+                .await
+                .context("Failed to import secrets")?;
+
+            output::success(format!(
+                "Imported {} variable(s) from {:?} into {} secret(s) in {}",
+                entries.len(),
+                env_file,
+                written.len(),
+                backend.backend_type()
+            ));
+            for path in &written {
+                println!("  - {}", path);
+            }
+            if !no_flag {
+                output::note(format!("Flagged for rotation every {} months", period));
+            }
+        }
+
+        Commands::Export {
+            path,
+            format,
+            output,
+            redact,
+        } => {
+            let secrets = transfer::collect_secrets(backend, &path, redact)
+                .await
+                .context("Failed to collect secrets for export")?;
+            let rendered = transfer::render_export(&secrets, &path, format)?;
+
+            match output {
+                Some(output_path) => {
+                    write_private_file(&output_path, &rendered)
+                        .with_context(|| format!("Failed to write export to {:?}", output_path))?;
+                    output::success(format!(
+                        "Exported {} secret(s) to {:?}",
+                        secrets.len(),
+                        output_path
+                    ));
+                }
+                None => {
+                    if !redact {
+                        output::warn(
+                            "Secret values will be displayed. Ensure this output is secured.",
                         );
                     }
+                    print!("{}", rendered);
+                }
+            }
+        }
+
+        Commands::Backup {
+            path,
+            out,
+            recipient,
+            transit: _,
+        } => {
+            let backup = backup::Backup::collect(backend, &path)
+                .await
+                .context("Failed to read secrets to back up")?;
+            let (sealed, sealed_for) = match recipient {
+                Some(recipient) => (backup.seal(&recipient)?, recipient.to_string()),
+                None => {
+                    let transit = create_transit(&config)?;
+                    let sealed = backup.seal_with_transit(&transit).await?;
+                    (sealed, format!("Vault transit key {}", transit.key_name()))
+                }
+            };
+            let result = write_private_file(&out, &sealed)
+                .with_context(|| format!("Failed to write backup to {:?}", out));
+            rotator.audit().record(
+                &AuditEvent::new(
+                    "backup",
+                    &path,
+                    backend.backend_type(),
+                    if result.is_ok() {
+                        Outcome::Success
+                    } else {
+                        Outcome::Failure
+                    },
+                )
+                .with_detail(format!("{} secret(s)", backup.secrets.len())),
+            );
+            result?;
+            output::success(format!(
+                "Backed up {} secret(s) to {:?}, encrypted to {}",
+                backup.secrets.len(),
+                out,
+                sealed_for
+            ));
+        }
+
+        Commands::Restore {
+            file,
+            identity,
+            include,
+            match_regex,
+            overwrite,
+            dry_run,
+        } => {
+            let filter = PathFilter::new(&include, &match_regex)?;
+            let ciphertext = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read backup {:?}", file))?;
+            let backup = if transit::is_envelope(&ciphertext) {
+                let vault_config = config.vault.as_ref().context(
+                    "The backup is sealed with Vault transit; set VAULT_ADDR/VAULT_TOKEN or configure [vault]",
+                )?;
+                let client = vault_client(vault_config).context("Failed to create Vault client")?;
+                backup::Backup::open_with_transit(&ciphertext, &client).await?
+            } else {
+                backup::Backup::open(&ciphertext, identity.as_deref())?
+            };
+            println!(
+                "Backup of {} secret(s) from {} ({}), taken {}",
+                backup.secrets.len(),
+                if backup.path.is_empty() {
+                    "/"
                 } else {
-                    // Try to get target username from metadata if update_target is enabled
-                    let target_username = if update_target {
-                        match backend.read_metadata(secret_path).await {
-                            Ok(metadata) => metadata
-                                .get("target_username")
-                                .or_else(|| metadata.get("database_username"))
-                                .cloned(),
-                            Err(_) => None,
+                    &backup.path
+                },
+                backup.backend,
+                backup.created.to_rfc3339()
+            );
+            if !dry_run {
+                confirm(
+                    &format!("About to restore secrets into {}", backend.backend_type()),
+                    yes,
+                )?;
+            }
+
+            let report = backup.restore(backend, &filter, overwrite, dry_run).await;
+            if !dry_run {
+                for path in &report.restored {
+                    rotator.audit().record(&AuditEvent::new(
+                        "restore",
+                        path,
+                        backend.backend_type(),
+                        Outcome::Success,
+                    ));
+                }
+                for (path, e) in &report.failed {
+                    rotator.audit().record(
+                        &AuditEvent::new("restore", path, backend.backend_type(), Outcome::Failure)
+                            .with_detail(redact::redact(&format!("{:#}", e))),
+                    );
+                }
+            }
+
+            let verb = if dry_run { "Would restore" } else { "Restored" };
+            for path in &report.restored {
+                output::success(format!("{}: {}", verb, path));
+            }
+            for path in &report.skipped {
+                output::note(format!(
+                    "Skipped {} (already exists; use --overwrite)",
+                    path
+                ));
+            }
+            output::success(format!(
+                "{} {} secret(s), skipped {}",
+                verb,
+                report.restored.len(),
+                report.skipped.len()
+            ));
+            if !report.failed.is_empty() {
+                let failures: Vec<_> = report
+                    .failed
+                    .iter()
+                    .map(|(path, e)| (path.clone(), format!("{:#}", e)))
+                    .collect();
+                print_failure_summary("Failed restores:", &failures);
+                anyhow::bail!("{} secret(s) could not be restored", failures.len());
+            }
+        }
+
+        Commands::Scan {
+            path,
+            include,
+            match_regex,
+            conditions,
+            quiet,
+        } => {
+            let filter = PathFilter::new(&include, &match_regex)?;
+            let selector = MetadataSelector::new(conditions);
+            let progress = Progress::stderr(quiet);
+            let secrets = rotator
+                .scan_with_progress(&path, &selector, &progress)
+                .await
+                .context("Failed to scan for secrets needing rotation")?;
+            let secrets = filter.apply(secrets);
+
+            if secrets.is_empty() {
+                output::note("No secrets need rotation at this time");
+            } else {
+                let failing = rotator.rotation_failures(&secrets).await;
+                println!("Secrets needing rotation:");
+                for secret in secrets {
+                    match failing.get(&secret) {
+                        Some(failures) => println!("  - {} ({})", secret, failures),
+                        None => println!("  - {}", secret),
+                    }
+                }
+            }
+        }
+
+        Commands::Report {
+            kind:
+                Some(ReportKind::Compliance {
+                    path,
+                    format,
+                    out,
+                    sla,
+                }),
+            ..
+        } => {
+            let sla = sla.unwrap_or(config.rotation.sla_percent);
+            if !(0.0..=100.0).contains(&sla) {
+                anyhow::bail!("SLA must be a percentage between 0 and 100, got {}", sla);
+            }
+            let compliance = compliance::ComplianceReport::collect(
+                backend,
+                &path,
+                config.rotation.period_months,
+                sla,
+            )
+            .await
+            .context("Failed to collect compliance report")?;
+
+            let format = format.unwrap_or_else(|| match out {
+                Some(ref out) => compliance::ComplianceFormat::from_path(out),
+                None => compliance::ComplianceFormat::Markdown,
+            });
+            let rendered = redact::redact(&compliance.render(format)?);
+
+            match out {
+                Some(out) => {
+                    std::fs::write(&out, rendered).with_context(|| {
+                        format!("Failed to write compliance report to {:?}", out)
+                    })?;
+                    output::success(format!(
+                        "Wrote compliance report on {} secret(s) to {:?}",
+                        compliance.secrets.len(),
+                        out
+                    ));
+                }
+                None => print!("{}", rendered),
+            }
+
+            if compliance.summary.sla_met {
+                output::success(compliance.summary_line());
+            } else {
+                output::warn(compliance.summary_line());
+            }
+        }
+
+        Commands::Inventory { path, format, out } => {
+            let inventory =
+                inventory::Inventory::collect(backend, &path, config.rotation.period_months)
+                    .await
+                    .context("Failed to collect inventory")?;
+
+            let format = format.unwrap_or_else(|| match out {
+                Some(ref out) => inventory::InventoryFormat::from_path(out),
+                None => inventory::InventoryFormat::Json,
+            });
+            let rendered = redact::redact(&inventory.render(format)?);
+
+            match out {
+                Some(out) => {
+                    std::fs::write(&out, rendered)
+                        .with_context(|| format!("Failed to write inventory to {:?}", out))?;
+                    output::success(format!(
+                        "Wrote inventory of {} secret(s) to {:?}",
+                        inventory.secrets.len(),
+                        out
+                    ));
+                }
+                None => print!("{}", rendered),
+            }
+        }
+
+        Commands::Report {
+            kind: None,
+            path,
+            format,
+            out,
+            rotate,
+            notify,
+            due_soon,
+        } => {
+            let notifier = if notify {
+                if config.notifications.webhooks.is_empty() {
+                    anyhow::bail!("No notification webhooks configured. Add [[notifications.webhooks]] to the config file");
+                }
+                let notifier = Notifier::new(&config.notifications)?;
+                Some(match due_soon {
+                    Some(days) => notifier.with_due_soon_days(days),
+                    None => notifier,
+                })
+            } else {
+                None
+            };
+            let run = if rotate {
+                let results = rotator
+                    .auto(&path)
+                    .await
+                    .context("Failed to rotate secrets for report")?;
+                Some(report::RunResults::from(&results))
+            } else {
+                None
+            };
+
+            let mut posture =
+                report::Report::collect(backend, &path, config.rotation.period_months)
+                    .await
+                    .context("Failed to collect rotation report")?;
+            if let Some(run) = run {
+                posture = posture.with_run(run);
+            }
+
+            let format = format.unwrap_or_else(|| match out {
+                Some(ref out) => report::ReportFormat::from_path(out),
+                None => report::ReportFormat::Markdown,
+            });
+            let rendered = redact::redact(&posture.render(format));
+
+            match out {
+                Some(out) => {
+                    std::fs::write(&out, rendered)
+                        .with_context(|| format!("Failed to write report to {:?}", out))?;
+                    output::success(format!(
+                        "Wrote report on {} secret(s) to {:?}",
+                        posture.secrets.len(),
+                        out
+                    ));
+                }
+                None => print!("{}", rendered),
+            }
+
+            if let Some(notifier) = notifier {
+                let sent = notifier.notify(&posture).await;
+                output::hint(format!("Sent {} owner notification(s)", sent));
+            }
+        }
+
+        Commands::Rotate {
+            path,
+            update_target,
+            target_type: _target_type,
+            target_username,
+            no_show,
+            copy,
+            clear_after,
+            encrypt_to,
+            wrap_ttl,
+            value_from_stdin,
+            prompt,
+        } => {
+            if update_target && target_username.is_none() {
+                anyhow::bail!("--target-username is required when --update-target is set");
+            }
+
+            if update_target && target.is_none() {
+                anyhow::bail!("Target configuration not found. Configure [targets.postgres] or [targets.api] section in config file");
+            }
+
+            let mut confirmation = format!("About to rotate {}", path);
+            if let Some(username) = target_username.as_deref().filter(|_| update_target) {
+                let target_type_name = target.map(|t| t.target_type()).unwrap_or("unknown");
+                confirmation.push_str(&format!(
+                    " and the {} password of user {}",
+                    target_type_name, username
+                ));
+            }
+            confirm(&confirmation, yes)?;
+
+            let supplied = supplied_value(value_from_stdin, prompt)?;
+            let username = target_username.as_deref().filter(|_| update_target);
+            let rotated = match (supplied.as_deref(), username) {
+                (Some(value), username) => rotator.set_value(&path, value, username).await,
+                (None, Some(username)) => rotator.rotate_with_target(&path, username).await,
+                (None, None) => rotator.rotate(&path).await,
+            }
+            .context("Failed to rotate secret")?;
+            let new_secret = rotated.value.expose_secret();
+
+            output::success(format!("Successfully rotated secret at: {}", path));
+            if let Some(username) = &rotated.target_username {
+                let target_type_name = target.map(|t| t.target_type()).unwrap_or("unknown");
+                output::success(format!(
+                    "Updated {} password for user: {}",
+                    target_type_name, username
+                ));
+            }
+            for failure in &rotated.hook_failures {
+                output::warn(format!(
+                    "Post-rotation hook failed: {}",
+                    redact::redact(failure)
+                ));
+            }
+            if let Some(not_after) = rotated.not_after {
+                output::note(format!(
+                    "New certificate valid until: {}",
+                    not_after.to_rfc3339()
+                ));
+                return Ok(());
+            }
+            if supplied.is_some() {
+                // The caller already has the value
+                return Ok(());
+            }
+            if let Some(ttl) = wrap_ttl {
+                let secret = backend
+                    .read_secret(&path)
+                    .await
+                    .context("Secret was rotated, but could not be read back to wrap it")?;
+                return print_wrapped(&config, &secret.data, &ttl).await;
+            }
+            // The secret is already rotated, so a clipboard failure must not abort
+            let copied = copy && copy_to_clipboard(new_secret, clear_after);
+
+            if let Some(recipient) = encrypt_to {
+                print_encrypted("New secret value", new_secret, &recipient)?;
+            } else if no_show || !std::io::stdout().is_terminal() {
+                if !copied {
+                    output::note(format!(
+                        "New secret value hidden (use 'asr read {}' to view it)",
+                        path
+                    ));
+                }
+            } else {
+                output::warn("Secret value will be displayed. Ensure this output is secured.");
+                println!("New secret value: {}", new_secret);
+                output::hint("Please update your application with the new secret and clear your terminal history.");
+            }
+        }
+
+        Commands::IssueCert {
+            path,
+            common_name,
+            alt_names,
+            ttl,
+            role,
+            issuer,
+        } => {
+            let issuer = rotator.issuer(issuer.as_deref())?;
+            let request = certs::CertificateRequest {
+                common_name,
+                alt_names,
+                ttl,
+                role,
+                issuer: None,
+            };
+            let result = certs::issue_certificate(backend, &path, issuer, &request).await;
+            rotator.audit().record(&AuditEvent::new(
+                "issue-cert",
+                &path,
+                backend.backend_type(),
+                if result.is_ok() {
+                    Outcome::Success
+                } else {
+                    Outcome::Failure
+                },
+            ));
+            let issued = result.context("Failed to issue certificate")?;
+            output::success(format!(
+                "Issued certificate for {} into {} (serial {})",
+                request.common_name, path, issued.serial_number
+            ));
+            output::note(format!(
+                "Valid until {}; it will be renewed {} days before expiry",
+                issued.not_after.to_rfc3339(),
+                certs::DEFAULT_RENEW_BEFORE_DAYS
+            ));
+        }
+
+        Commands::Auto {
+            path,
+            dry_run,
+            update_env,
+            update_target,
+            shell_files,
+            atomic,
+            include,
+            match_regex,
+            conditions,
+            max_rotations,
+            format,
+            verify_only,
+            quiet,
+        } => {
+            let filter = PathFilter::new(&include, &match_regex)?;
+            let selector = MetadataSelector::new(conditions);
+            if verify_only {
+                return verify_flagged(&rotator, &path, &filter, &selector).await;
+            }
+            if update_target && target.is_none() {
+                anyhow::bail!("Target configuration not found. Configure [targets.postgres] or [targets.api] section in config file");
+            }
+            if !dry_run {
+                for retired in rotator.retire_previous_values(&path, &filter).await? {
+                    output::success(format!(
+                        "Retired previous value of {} (overlap window over)",
+                        retired
+                    ));
+                }
+            }
+            let mut run_report =
+                RunReport::start(backend.backend_type(), &path, atomic, update_target);
+            let progress = Progress::stderr(quiet);
+            let secrets = rotator
+                .scan_with_progress(&path, &selector, &progress)
+                .await
+                .context("Failed to scan for secrets needing rotation")?;
+            let mut secrets = filter.apply(secrets);
+
+            // A JSON plan is printed even when it is empty
+            if secrets.is_empty() && !(dry_run && format == PlanFormat::Json) {
+                output::note("No secrets need rotation at this time");
+                if !dry_run {
+                    write_run_report(&config, backend, &mut run_report).await;
+                }
+                return Ok(());
+            }
+
+            let max_rotations = max_rotations.or(config.rotation.max_per_run);
+            let deferred = crate::rotator::defer_excess(&mut secrets, max_rotations);
+
+            let env_updater = if update_env {
+                Some(create_env_updater(&config, &shell_files)?)
+            } else {
+                None
+            };
+
+            if dry_run {
+                let options = PlanOptions {
+                    default_period_months: config.rotation.period_months,
+                    target: target.filter(|_| update_target),
+                    env: env_updater
+                        .as_ref()
+                        .map(|updater| (updater, &config.env.mappings)),
+                };
+                let mut planned = Vec::new();
+                for secret_path in &secrets {
+                    planned.push(plan::plan_secret(backend, secret_path, &options).await);
+                }
+                print!("{}", RotationPlan::new(planned, deferred).render(format)?);
+                return Ok(());
+            }
+
+            println!(
+                "Found {} secret(s) needing rotation",
+                secrets.len() + deferred.len()
+            );
+            if !deferred.is_empty() {
+                output::note(format!(
+                    "Rotating {} now; {} deferred to the next run (limit {})",
+                    secrets.len(),
+                    deferred.len(),
+                    secrets.len()
+                ));
+            }
+            let mut confirmation = format!("About to rotate {} secret(s)", secrets.len());
+            if update_target {
+                confirmation.push_str(" and their target passwords");
+            }
+            for secret_path in &secrets {
+                confirmation.push_str(&format!("\n  - {}", secret_path));
+            }
+            confirm(&confirmation, yes)?;
+
+            // Replicas scheduled together leave the run to the one holding the lease
+            let leadership = start_leadership(&config).await?;
+            if leadership.as_ref().is_some_and(|l| !l.is_leader()) {
+                if let Some(standby) = leadership {
+                    standby.stop().await;
+                }
+                output::note("Another replica holds the leader lease; skipping this run");
+                return Ok(());
+            }
+
+            for secret_path in &deferred {
+                run_report.record(secret_path, SecretStatus::Deferred, None, None);
+            }
+            let mut hook_failures = Vec::new();
+            let mut rotated_count = 0;
+            let mut failures = Vec::new();
+            let mut atomic_error = None;
+            if atomic {
+                let rotated = match rotator.rotate_atomic(&secrets, update_target).await {
+                    Ok(rotated) => rotated,
+                    Err(e) => {
+                        // Nothing in the batch stays rotated, so all of it failed
+                        let error = format!("{:#}", e);
+                        for secret_path in &secrets {
+                            failures.push((secret_path.clone(), "rolled back".to_string()));
+                            run_report.record(
+                                secret_path,
+                                SecretStatus::RolledBack,
+                                None,
+                                Some(&error),
+                            );
                         }
+                        atomic_error = Some(e);
+                        Vec::new()
+                    }
+                };
+                rotated_count = rotated.len();
+                for rotated in &rotated {
+                    run_report
+                        .record(&rotated.path, SecretStatus::Rotated, None, None)
+                        .hook_failures = rotated.hook_failures.clone();
+                    let metadata = if update_env {
+                        backend
+                            .read_metadata(&rotated.path)
+                            .await
+                            .unwrap_or_default()
                     } else {
-                        None
+                        Default::default()
+                    };
+                    let env_var_names =
+                        env_updater::env_var_names(&rotated.path, &metadata, &config.env.mappings);
+                    report_rotated(
+                        rotated,
+                        target,
+                        env_updater.as_ref(),
+                        &env_var_names,
+                        &mut hook_failures,
+                    );
+                }
+            } else {
+                progress.start("rotating", secrets.len());
+                for (index, secret_path) in secrets.iter().enumerate() {
+                    if leadership.as_ref().is_some_and(|l| !l.is_leader()) {
+                        progress.clear();
+                        output::warn("Lost the leader lease; leaving the remaining secrets to the new leader");
+                        for skipped in &secrets[index..] {
+                            run_report.record(skipped, SecretStatus::Skipped, None, None);
+                        }
+                        break;
+                    }
+                    progress.step(secret_path);
+                    let started = std::time::Instant::now();
+                    // Metadata supplies the target username and env var names
+                    let metadata = if update_target || update_env {
+                        backend.read_metadata(secret_path).await.unwrap_or_default()
+                    } else {
+                        Default::default()
                     };
+                    let env_var_names =
+                        env_updater::env_var_names(secret_path, &metadata, &config.env.mappings);
 
-                    let new_value = if update_target && target_username.is_some() {
-                        rotation::rotate_secret_with_target(
-                            backend.as_ref(),
-                            secret_path,
-                            config.rotation.secret_length,
-                            target.as_ref().map(|t| t.as_ref() as &dyn Target),
-                            target_username.as_deref(),
-                        )
-                        .await
-                        .with_context(|| format!("Failed to rotate secret: {}", secret_path))
+                    // Try to get target username from metadata if update_target is enabled
+                    let target_username = if update_target {
+                        rotation::target_username(&metadata)
                     } else {
-                        rotation::rotate_secret(
-                            backend.as_ref(),
-                            secret_path,
-                            config.rotation.secret_length,
-                        )
-                        .await
-                        .with_context(|| format!("Failed to rotate secret: {}", secret_path))
+                        None
                     };
 
-                    match new_value {
-                        Ok(new_value) => {
-                            println!("Rotated: {}", secret_path);
-
-                            // Update target password if requested
-                            if update_target && target_username.is_some() {
-                                let target_type_name = target
-                                    .as_ref()
-                                    .map(|t| t.target_type())
-                                    .unwrap_or("unknown");
-                                println!(
-                                    "  Updated {} password for user: {}",
-                                    target_type_name,
-                                    target_username.as_deref().unwrap_or("unknown")
-                                );
-                            }
+                    let rotated = match target_username {
+                        Some(ref username) => {
+                            rotator.rotate_with_target(secret_path, username).await
+                        }
+                        None => rotator.rotate(secret_path).await,
+                    };
 
-                            // Update environment variable if requested
-                            if let Some(ref updater) = env_updater {
-                                // Convert path to env var name: myapp/database -> MYAPP_DATABASE
-                                let env_var_name = secret_path.replace('/', "_").to_uppercase();
-
-                                match updater.update_env_var(&env_var_name, &new_value) {
-                                    Ok(_) => println!("  Updated env var: {}", env_var_name),
-                                    Err(e) => {
-                                        eprintln!(
-                                            "  Failed to update env var {}: {}",
-                                            env_var_name, e
-                                        )
-                                    }
-                                }
-                            }
+                    progress.clear();
+                    let duration = Some(started.elapsed());
+                    match rotated {
+                        Ok(rotated) => {
+                            rotated_count += 1;
+                            run_report
+                                .record(secret_path, SecretStatus::Rotated, duration, None)
+                                .hook_failures = rotated.hook_failures.clone();
+                            report_rotated(
+                                &rotated,
+                                target,
+                                env_updater.as_ref(),
+                                &env_var_names,
+                                &mut hook_failures,
+                            );
+                        }
+                        // Refused by the circuit breaker without being attempted
+                        Err(e) if e.downcast_ref::<CircuitOpen>().is_some() => {
+                            warn!("Skipped {}: {:#}", secret_path, e);
+                            let error = format!("{:#}", e);
+                            run_report.record(
+                                secret_path,
+                                SecretStatus::Skipped,
+                                duration,
+                                Some(&error),
+                            );
                         }
                         Err(e) => {
-                            error!("Failed to rotate {}: {}", secret_path, e);
+                            error!("Failed to rotate {}: {:#}", secret_path, e);
+                            let error = format!("{:#}", e);
+                            run_report.record(
+                                secret_path,
+                                SecretStatus::Failed,
+                                duration,
+                                Some(&error),
+                            );
+                            failures.push((secret_path.clone(), error));
                         }
                     }
                 }
             }
 
-            if !dry_run {
-                println!("\nRotation complete!");
-                if update_env {
-                    println!("  Note: Reload your shell or run 'source ~/.bashrc' for env var changes to take effect");
+            if let Some(leadership) = leadership {
+                leadership.stop().await;
+            }
+            write_run_report(&config, backend, &mut run_report).await;
+
+            println!();
+            if failures.is_empty() {
+                output::success("Rotation complete!");
+            } else {
+                output::failure_heading("Rotation finished with errors");
+            }
+            if update_env {
+                output::note(env_updater::EnvUpdater::reload_hint());
+            }
+            if let Some(open) = rotator.open_circuit() {
+                println!();
+                output::failure_heading(format!(
+                    "Circuit breaker open: the {} target failed {} rotations in a row",
+                    target.map(|t| t.target_type()).unwrap_or("configured"),
+                    open.failures
+                ));
+                println!("  Last error: {}", redact::redact(&open.last_error));
+                println!("  Later rotations against it were skipped; fix the target (e.g., its admin credential) and run again");
+            }
+            if !hook_failures.is_empty() {
+                print_failure_summary("Post-rotation hook failures:", &hook_failures);
+            }
+            if !failures.is_empty() {
+                print_failure_summary("Failed rotations:", &failures);
+                if let Some(e) = atomic_error {
+                    println!("\n{}", redact::redact(&format!("{:#}", e)));
+                }
+                return Err(RotationFailures {
+                    rotated: rotated_count,
+                    failed: failures.len(),
+                }
+                .into());
+            }
+        }
+
+        Commands::Read {
+            path,
+            key,
+            raw,
+            format,
+            encrypt_to,
+            wrap_ttl,
+        } => {
+            let mut secret = backend
+                .read_secret(&path)
+                .await
+                .context("Failed to read secret")?;
+            if let Some(ttl) = wrap_ttl {
+                if let Some(key) = &key {
+                    let value = secret
+                        .data
+                        .remove(key)
+                        .with_context(|| format!("Secret {} has no key '{}'", path, key))?;
+                    secret.data = std::collections::HashMap::from([(key.clone(), value)]);
+                }
+                return print_wrapped(&config, &secret.data, &ttl).await;
+            }
+            let data: std::collections::BTreeMap<_, _> = secret.data.into_iter().collect();
+
+            let output = match (key, format) {
+                (Some(key), _) => {
+                    let value = data
+                        .get(&key)
+                        .with_context(|| format!("Secret {} has no key '{}'", path, key))?;
+                    if raw {
+                        value.clone()
+                    } else {
+                        format!("{}\n", value)
+                    }
+                }
+                (None, Some(format)) => transfer::render_fields(&data, format)?,
+                (None, None) if encrypt_to.is_some() => data
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}\n", key, value))
+                    .collect(),
+                (None, None) => {
+                    output::warn("Secret values will be displayed. Ensure this output is secured.");
+                    println!("Secret data:");
+                    for (key, value) in &data {
+                        println!("  {}: {}", key, value);
+                    }
+                    output::hint("Please clear your terminal history after viewing.");
+                    return Ok(());
+                }
+            };
+
+            if let Some(recipient) = encrypt_to {
+                print_encrypted("Secret data", &output, &recipient)?;
+            } else {
+                let mut stdout = std::io::stdout().lock();
+                std::io::Write::write_all(&mut stdout, output.as_bytes())
+                    .and_then(|()| std::io::Write::flush(&mut stdout))
+                    .context("Failed to write secret to stdout")?;
+            }
+        }
+
+        Commands::Write {
+            path,
+            mut pairs,
+            merge,
+        } => {
+            read_stdin_values(&mut pairs)?;
+            for (_, value) in &pairs {
+                redact::register_secret(value);
+            }
+            let keys: Vec<_> = pairs.iter().map(|(key, _)| key.clone()).collect();
+
+            if merge {
+                let current = backend
+                    .read_secret(&path)
+                    .await
+                    .context("Failed to read secret to merge into")?;
+                let mut data = current.data;
+                data.extend(pairs);
+                backend
+                    .write_secret_cas(&path, data, current.version)
+                    .await
+                    .context("Failed to write secret")?;
+            } else {
+                backend
+                    .write_secret(&path, pairs.into_iter().collect())
+                    .await
+                    .context("Failed to write secret")?;
+            }
+            output::success(format!(
+                "Wrote {} to {} ({})",
+                keys.join(", "),
+                path,
+                backend.backend_type()
+            ));
+            for failure in rotator.rewrite_composites(&path).await {
+                output::warn(format!("Failed to rewrite {}", failure));
+            }
+        }
+
+        Commands::RestoreTarget {
+            path,
+            version,
+            target_username,
+        } => {
+            let target = rotator
+                .target()
+                .context("No target configured; see [targets] in the config file")?;
+            let source = match version {
+                Some(version) => format!("version {} of {}", version, path),
+                None => format!("the value stored in {}", path),
+            };
+            confirm(
+                &format!(
+                    "About to set the {} password to {}",
+                    target.target_type(),
+                    source
+                ),
+                yes,
+            )?;
+            let login = rotator
+                .restore_target(&path, version, target_username.as_deref())
+                .await?;
+            output::success(format!(
+                "Applied {} to {} user {}",
+                source,
+                target.target_type(),
+                login
+            ));
+        }
+
+        Commands::Diff {
+            path,
+            from,
+            to,
+            other_backend,
+            other_path,
+            show_values,
+        } => {
+            let (old, new) = match other_backend {
+                Some(other_type) => {
+                    let other = create_single_backend(&config, &other_type)
+                        .await
+                        .with_context(|| format!("Failed to create {} backend", other_type))?;
+                    let other_path = other_path.unwrap_or_else(|| path.clone());
+                    let old = backend
+                        .read_secret(&path)
+                        .await
+                        .with_context(|| format!("Failed to read {}", path))?;
+                    let new = other.read_secret(&other_path).await.with_context(|| {
+                        format!("Failed to read {} from {}", other_path, other_type)
+                    })?;
+                    let last_rotated =
+                        |metadata: Option<&std::collections::HashMap<String, String>>| {
+                            metadata
+                                .and_then(rotation::last_rotated)
+                                .map(|at| format!(", last rotated {}", at.to_rfc3339()))
+                                .unwrap_or_default()
+                        };
+                    println!(
+                        "{} ({}{}) -> {} ({}{})",
+                        path,
+                        backend.backend_type(),
+                        last_rotated(old.metadata.as_ref()),
+                        other_path,
+                        other.backend_type(),
+                        last_rotated(new.metadata.as_ref())
+                    );
+                    (old.data, new.data)
+                }
+                None => {
+                    let versions = backend
+                        .list_versions(&path)
+                        .await
+                        .with_context(|| format!("Failed to list versions of {}", path))?;
+                    let Some(current) = versions.last() else {
+                        anyhow::bail!("No versions of {} are kept", path);
+                    };
+                    let created = |version: u64| {
+                        versions
+                            .iter()
+                            .find(|kept| kept.version == version)
+                            .map(|kept| kept.created)
+                            .with_context(|| format!("Version {} of {} is not kept", version, path))
+                    };
+                    let to = to.unwrap_or(current.version);
+                    let from = match from {
+                        Some(from) => from,
+                        None => versions
+                            .iter()
+                            .rev()
+                            .map(|kept| kept.version)
+                            .find(|&version| version < to)
+                            .with_context(|| {
+                                format!("{} has no version before version {}", path, to)
+                            })?,
+                    };
+                    println!(
+                        "{} version {} ({}) -> version {} ({})",
+                        path,
+                        from,
+                        created(from)?.to_rfc3339(),
+                        to,
+                        created(to)?.to_rfc3339()
+                    );
+                    let old = backend.read_version(&path, from).await?;
+                    let new = backend.read_version(&path, to).await?;
+                    (old.data, new.data)
+                }
+            };
+            for value in old.values().chain(new.values()) {
+                redact::register_secret(value);
+            }
+
+            if show_values {
+                confirm(&format!("About to display the values of {}", path), yes)?;
+                output::warn("Secret values will be displayed. Ensure this output is secured.");
+            }
+            let changes = diff::diff(&old, &new);
+            print!("{}", diff::render(&changes, show_values));
+            if !diff::has_changes(&changes) {
+                output::note("No keys differ");
+            }
+        }
+
+        Commands::Rollback {
+            path,
+            version,
+            list,
+        } => {
+            let versions = backend
+                .list_versions(&path)
+                .await
+                .with_context(|| format!("Failed to list versions of {}", path))?;
+            let Some(current) = versions.last() else {
+                anyhow::bail!("No versions of {} are kept", path);
+            };
+
+            if list {
+                println!("{:<8}  CREATED", "VERSION");
+                for kept in &versions {
+                    let marker = if kept.version == current.version {
+                        "  (current)"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "{:<8}  {}{}",
+                        kept.version,
+                        kept.created.to_rfc3339(),
+                        marker
+                    );
+                }
+                return Ok(());
+            }
+
+            let version = match version {
+                Some(version) => version,
+                None => match versions.len() {
+                    1 => anyhow::bail!("{} has no version before the current one", path),
+                    n => versions[n - 2].version,
+                },
+            };
+            if version == current.version {
+                anyhow::bail!(
+                    "Version {} is already the current value of {}",
+                    version,
+                    path
+                );
+            }
+            confirm(
+                &format!("About to roll {} back to version {}", path, version),
+                yes,
+            )?;
+
+            let kept = backend.read_version(&path, version).await?;
+            for value in kept.data.values() {
+                redact::register_secret(value);
+            }
+            let current_secret = backend
+                .read_secret(&path)
+                .await
+                .context("Failed to read current secret")?;
+            let result = backend
+                .write_secret_cas(&path, kept.data, current_secret.version)
+                .await;
+            rotator.audit().record(
+                &AuditEvent::new(
+                    "rollback",
+                    &path,
+                    backend.backend_type(),
+                    if result.is_ok() {
+                        Outcome::Success
+                    } else {
+                        Outcome::Failure
+                    },
+                )
+                .with_detail(format!("version {}", version)),
+            );
+            result.context("Failed to write previous version")?;
+
+            output::success(format!("Rolled {} back to version {}", path, version));
+            output::note("Targets were not changed; push the value with `rotate --value-from-stdin` if needed");
+            for failure in rotator.rewrite_composites(&path).await {
+                output::warn(format!("Failed to rewrite {}", failure));
+            }
+        }
+
+        Commands::Compose { path } => {
+            let composites: Vec<_> = rotator
+                .composites()
+                .iter()
+                .filter(|composite| path.as_ref().is_none_or(|path| *path == composite.path))
+                .collect();
+            if composites.is_empty() {
+                match path {
+                    Some(path) => anyhow::bail!("No composite is configured at {}", path),
+                    None => anyhow::bail!("No composites are configured ([[composites]])"),
+                }
+            }
+
+            let mut failures = Vec::new();
+            for composite in composites {
+                match rotator.write_composite(composite).await {
+                    Ok(()) => output::success(format!(
+                        "Wrote {} ({} key(s))",
+                        composite.path,
+                        composite.keys.len()
+                    )),
+                    Err(e) => failures.push((composite.path.clone(), format!("{:#}", e))),
+                }
+            }
+            if !failures.is_empty() {
+                print_failure_summary("Failed composites:", &failures);
+                anyhow::bail!("{} composite(s) could not be written", failures.len());
+            }
+        }
+
+        Commands::Reencrypt { action } => match action {
+            ReencryptAction::Status { path } => {
+                let unfinished = hooks::unfinished_reencryptions(backend, &path).await?;
+                if unfinished.is_empty() {
+                    output::success("No re-encryption is pending");
+                    return Ok(());
+                }
+                println!("{:<10}  PATH", "STATE");
+                for (secret_path, state) in &unfinished {
+                    println!("{:<10}  {}", state, secret_path);
+                }
+            }
+            ReencryptAction::Done { path } => {
+                rotator.complete_reencryption(&path).await?;
+                output::success(format!("Re-encryption for {} is complete", path));
+            }
+        },
+        Commands::Meta { action } => match action {
+            MetaAction::Get { path, keys } => {
+                let metadata = backend
+                    .read_metadata(&path)
+                    .await
+                    .context("Failed to read metadata")?;
+                let missing: Vec<_> = keys
+                    .iter()
+                    .filter(|key| !metadata.contains_key(*key))
+                    .map(String::as_str)
+                    .collect();
+                if !missing.is_empty() {
+                    anyhow::bail!("{} has no metadata key(s): {}", path, missing.join(", "));
+                }
+
+                if let [key] = keys.as_slice() {
+                    println!("{}", metadata[key]);
+                } else {
+                    let mut entries: Vec<_> = metadata
+                        .iter()
+                        .filter(|(key, _)| keys.is_empty() || keys.contains(key))
+                        .collect();
+                    entries.sort();
+                    for (key, value) in entries {
+                        println!("{}: {}", key, value);
+                    }
+                }
+            }
+            MetaAction::Set { path, pairs } => {
+                let keys: Vec<_> = pairs.iter().map(|(key, _)| key.clone()).collect();
+                let result = async {
+                    let mut metadata = backend
+                        .read_metadata(&path)
+                        .await
+                        .context("Failed to read metadata")?;
+                    metadata.extend(pairs);
+                    backend
+                        .update_metadata(&path, metadata)
+                        .await
+                        .context("Failed to update metadata")
+                }
+                .await;
+                record_metadata_change(&rotator, backend, &path, "set", &keys, &result);
+                result?;
+                output::success(format!("Set {} on {}", keys.join(", "), path));
+            }
+            MetaAction::Unset { path, keys } => {
+                let result = backend
+                    .remove_metadata(&path, &keys)
+                    .await
+                    .context("Failed to remove metadata");
+                record_metadata_change(&rotator, backend, &path, "unset", &keys, &result);
+                result?;
+                output::success(format!("Removed {} from {}", keys.join(", "), path));
+            }
+        },
+
+        Commands::Db { action } => {
+            let engine = create_database_engine(&config)?;
+            match action {
+                DbAction::RotateRoot { connections } => {
+                    rotate_database_credentials(&rotator, &engine, false, connections, yes).await?;
+                }
+                DbAction::RotateRole { roles } => {
+                    rotate_database_credentials(&rotator, &engine, true, roles, yes).await?;
+                }
+                DbAction::Leases { prefixes, renew } => {
+                    let prefixes = if prefixes.is_empty() {
+                        engine.lease_prefixes()
+                    } else {
+                        prefixes
+                    };
+                    let leases = engine
+                        .check_leases(&prefixes, renew)
+                        .await
+                        .context("Failed to check leases")?;
+                    if leases.is_empty() {
+                        output::note(format!("No leases found under {}", prefixes.join(", ")));
+                        return Ok(());
+                    }
+
+                    let (mut renewed, mut failed) = (0, 0);
+                    for lease in &leases {
+                        let expires = lease
+                            .expire_time
+                            .map(|t| t.to_rfc3339())
+                            .unwrap_or_else(|| "-".to_string());
+                        let state = match &lease.action {
+                            vault_db::LeaseAction::Current => "ok".to_string(),
+                            vault_db::LeaseAction::Expiring => "expiring".to_string(),
+                            vault_db::LeaseAction::NotRenewable => {
+                                "expiring (not renewable)".to_string()
+                            }
+                            vault_db::LeaseAction::Renewed { ttl } => {
+                                renewed += 1;
+                                format!("renewed for {}s", ttl)
+                            }
+                            vault_db::LeaseAction::Failed(error) => {
+                                failed += 1;
+                                format!("failed: {}", redact::redact(error))
+                            }
+                        };
+                        let outcome = match lease.action {
+                            vault_db::LeaseAction::Renewed { .. } => Some(Outcome::Success),
+                            vault_db::LeaseAction::Failed(_) => Some(Outcome::Failure),
+                            _ => None,
+                        };
+                        if let Some(outcome) = outcome {
+                            rotator.audit().record(&AuditEvent::new(
+                                "renew-lease",
+                                &lease.id,
+                                "vault",
+                                outcome,
+                            ));
+                        }
+                        println!("  {}  expires {}  {}", lease.id, expires, state);
+                    }
+                    if failed > 0 {
+                        return Err(RotationFailures {
+                            rotated: renewed,
+                            failed,
+                        }
+                        .into());
+                    }
                 }
             }
         }
 
-        Commands::Read { path } => {
-            let secret = backend
-                .read_secret(&path)
-                .await
-                .context("Failed to read secret")?;
-            eprintln!("WARNING: Secret values will be displayed. Ensure this output is secured.");
-            println!("Secret data:");
-            for (key, value) in secret.data {
-                println!("  {}: {}", key, value);
-            }
-            eprintln!("  Please clear your terminal history after viewing.");
-        }
-
         Commands::List { path } => {
             let secrets = backend
                 .list_secrets(&path)
                 .await
                 .context("Failed to list secrets")?;
             if secrets.is_empty() {
-                println!(
+                output::note(format!(
                     "No secrets found at path: {}",
                     if path.is_empty() { "/" } else { &path }
-                );
+                ));
             } else {
                 println!("Secrets at {}:", if path.is_empty() { "/" } else { &path });
                 for secret in secrets {
@@ -429,6 +2353,7 @@ pub async fn execute(cli: Cli) -> Result<()> {
             vault_path,
             key,
             env_var,
+            shell_files,
         } => {
             // Read the secret from backend
             let secret = backend
@@ -443,24 +2368,21 @@ pub async fn execute(cli: Cli) -> Result<()> {
                 .with_context(|| format!("Key '{}' not found in secret", key))?;
 
             // Update the environment variable
-            let env_updater =
-                env_updater::EnvUpdater::new().context("Failed to create EnvUpdater")?;
+            let env_updater = create_env_updater(&config, &shell_files)?;
 
             env_updater
                 .update_env_var(&env_var, value)
                 .with_context(|| format!("Failed to update environment variable {}", env_var))?;
 
-            println!(
-                "Updated environment variable '{}' in shell config files",
-                env_var
-            );
+            output::success(format!("Updated environment variable '{}'", env_var));
             println!(
                 "  Value synced from {}: {} (key: {})",
                 backend.backend_type(),
                 vault_path,
                 key
             );
-            println!("\nNote: You need to reload your shell or run 'source ~/.bashrc' (or ~/.zshrc) for changes to take effect");
+            println!();
+            output::note(env_updater::EnvUpdater::reload_hint());
         }
 
         Commands::GenPassword {
@@ -468,10 +2390,22 @@ pub async fn execute(cli: Cli) -> Result<()> {
             key,
             env_var,
             length,
+            shell_files,
+            copy,
+            clear_after,
+            encrypt_to,
+            value_from_stdin,
+            prompt,
         } => {
-            // Generate a new password
-            let password_length = length.unwrap_or(config.rotation.secret_length);
-            let new_password = rotation::generate_secret(password_length);
+            // Generate a new password unless one was supplied
+            let supplied = supplied_value(value_from_stdin, prompt)?;
+            let generated = supplied.is_none();
+            let new_password = match supplied {
+                Some(value) => value,
+                None => rotation::generate_secret(length.unwrap_or(config.rotation.secret_length)),
+            };
+            let password_length = new_password.chars().count();
+            redact::register_secret(&new_password);
 
             // Prepare secret data
             let mut secret_data = std::collections::HashMap::new();
@@ -483,18 +2417,29 @@ pub async fn execute(cli: Cli) -> Result<()> {
                 .await
                 .context("Failed to write secret")?;
 
-            println!(
-                "Generated new password and stored in {}",
+            output::success(format!(
+                "{} password and stored in {}",
+                if generated {
+                    "Generated new"
+                } else {
+                    "Received supplied"
+                },
                 backend.backend_type()
-            );
+            ));
             println!("  Location: {}", vault_path);
             println!("  Key: {}", key);
             println!("  Length: {} characters", password_length);
 
+            if copy {
+                copy_to_clipboard(&new_password, clear_after);
+            }
+            if let Some(recipient) = encrypt_to {
+                print_encrypted("Password", &new_password, &recipient)?;
+            }
+
             // Update local environment variable if specified
             if let Some(env_var_name) = env_var {
-                let env_updater =
-                    env_updater::EnvUpdater::new().context("Failed to create EnvUpdater")?;
+                let env_updater = create_env_updater(&config, &shell_files)?;
 
                 env_updater
                     .update_env_var(&env_var_name, &new_password)
@@ -502,19 +2447,299 @@ pub async fn execute(cli: Cli) -> Result<()> {
                         format!("Failed to update environment variable {}", env_var_name)
                     })?;
 
-                println!(
-                    "Updated environment variable '{}' in shell config files",
-                    env_var_name
-                );
-                println!("\nNote: You need to reload your shell or run 'source ~/.bashrc' (or ~/.zshrc) for changes to take effect");
+                output::success(format!("Updated environment variable '{}'", env_var_name));
+                println!();
+                output::note(env_updater::EnvUpdater::reload_hint());
             } else {
-                println!(
-                    "\nTip: Use --env-var to automatically update a local environment variable"
-                );
+                output::note("Use --env-var to automatically update a local environment variable");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print a secret as ciphertext only `recipient` can decrypt
+fn print_encrypted(label: &str, plaintext: &str, recipient: &encrypt::Recipient) -> Result<()> {
+    let ciphertext = encrypt::encrypt(plaintext, recipient)
+        .with_context(|| format!("Failed to encrypt secret to {}", recipient))?;
+    println!("{} (encrypted to {}):", label, recipient);
+    print!("{}", ciphertext);
+    Ok(())
+}
+
+/// Wrap secret data in a single-use Vault token and print the token instead
+async fn print_wrapped(
+    config: &Config,
+    data: &std::collections::HashMap<String, String>,
+    ttl: &str,
+) -> Result<()> {
+    let vault_config = config.vault.as_ref().context(
+        "--wrap-ttl wraps secrets with Vault; set VAULT_ADDR/VAULT_TOKEN or configure [vault]",
+    )?;
+    let client = vault_client(vault_config).context("Failed to create Vault client")?;
+    let wrapped = client
+        .wrap(data, ttl)
+        .await
+        .context("Failed to wrap secret")?;
+
+    let expires = wrapped.creation_time
+        + chrono::Duration::seconds(i64::try_from(wrapped.ttl).unwrap_or(i64::MAX));
+    println!("Wrapping token: {}", wrapped.token);
+    println!("Wrapping accessor: {}", wrapped.accessor);
+    println!("Expires: {} (single use)", expires.to_rfc3339());
+    output::hint("The recipient unwraps it with: vault unwrap <token>");
+    Ok(())
+}
+
+/// Copy a secret to the clipboard, scheduling it to be cleared after `clear_after` seconds
+///
+/// Failures are reported as warnings since the secret has already been stored.
+/// Returns whether the value was copied.
+fn copy_to_clipboard(value: &str, clear_after: u64) -> bool {
+    if let Err(e) = clipboard::copy(value) {
+        output::warn(format!("Failed to copy secret to clipboard: {:#}", e));
+        return false;
+    }
+
+    if clear_after == 0 {
+        output::success("Copied to clipboard");
+    } else if let Err(e) = clipboard::schedule_clear(value, Duration::from_secs(clear_after)) {
+        output::warn(format!(
+            "Copied to clipboard, but it will not be cleared: {:#}",
+            e
+        ));
+    } else {
+        output::success(format!(
+            "Copied to clipboard (clears in {} seconds)",
+            clear_after
+        ));
+    }
+    true
+}
+
+/// Ask before a rotation, unless `--yes` was given or stdin is not a terminal
+///
+/// Scripts and CI jobs never see the prompt, so they are unaffected.
+fn confirm(action: &str, yes: bool) -> Result<()> {
+    let stdin = std::io::stdin();
+    if yes || !stdin.is_terminal() {
+        return Ok(());
+    }
+
+    eprint!("{}\nContinue? [y/N] ", action);
+    std::io::Write::flush(&mut std::io::stderr()).context("Failed to show prompt")?;
+    let mut answer = String::new();
+    stdin
+        .read_line(&mut answer)
+        .context("Failed to read confirmation")?;
+    if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        anyhow::bail!("Aborted; nothing was rotated");
+    }
+    Ok(())
+}
+
+/// Value given with `--value-from-stdin` or `--prompt`, if either was used
+fn supplied_value(from_stdin: bool, prompt: bool) -> Result<Option<String>> {
+    let value = if from_stdin {
+        let mut input = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin().lock(), &mut input)
+            .context("Failed to read value from stdin")?;
+        strip_final_newline(&input).to_string()
+    } else if prompt {
+        let value = rpassword::prompt_password("New value: ").context("Failed to read value")?;
+        let confirm =
+            rpassword::prompt_password("Confirm value: ").context("Failed to read value")?;
+        if value != confirm {
+            anyhow::bail!("Values do not match");
+        }
+        value
+    } else {
+        return Ok(None);
+    };
+
+    if value.is_empty() {
+        anyhow::bail!("No value supplied");
+    }
+    Ok(Some(value))
+}
+
+/// Drop the newline that ends piped input, keeping any inside the value
+fn strip_final_newline(input: &str) -> &str {
+    let input = input.strip_suffix('\n').unwrap_or(input);
+    input.strip_suffix('\r').unwrap_or(input)
+}
+
+/// Replace `-` values with input from stdin
+///
+/// On a terminal each one is prompted for without echo. Otherwise a single
+/// `-` takes all of stdin (so multi-line values such as PEM keys work), and
+/// several take one line each, in order.
+fn read_stdin_values(pairs: &mut [(String, String)]) -> Result<()> {
+    let wanted = pairs.iter().filter(|(_, value)| value == "-").count();
+    if wanted == 0 {
+        return Ok(());
+    }
+
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        for (key, value) in pairs.iter_mut().filter(|(_, value)| value == "-") {
+            *value = rpassword::prompt_password(format!("Value for {}: ", key))
+                .with_context(|| format!("Failed to read value for {}", key))?;
+        }
+        return Ok(());
+    }
+
+    let mut input = String::new();
+    std::io::Read::read_to_string(&mut stdin.lock(), &mut input)
+        .context("Failed to read values from stdin")?;
+    if wanted == 1 {
+        if let Some((_, slot)) = pairs.iter_mut().find(|(_, value)| value == "-") {
+            *slot = strip_final_newline(&input).to_string();
+        }
+        return Ok(());
+    }
+
+    let mut lines = input.lines();
+    for (key, value) in pairs.iter_mut().filter(|(_, value)| value == "-") {
+        *value = lines
+            .next()
+            .with_context(|| format!("stdin ran out of lines before the value for {}", key))?
+            .to_string();
+    }
+    Ok(())
+}
+
+/// Audit a metadata change; only key names are recorded, not values
+fn record_metadata_change(
+    rotator: &Rotator,
+    backend: &dyn SecretBackend,
+    path: &str,
+    change: &str,
+    keys: &[String],
+    result: &Result<()>,
+) {
+    let outcome = if result.is_ok() {
+        Outcome::Success
+    } else {
+        Outcome::Failure
+    };
+    rotator.audit().record(
+        &AuditEvent::new("meta", path, backend.backend_type(), outcome).with_detail(format!(
+            "{} {}",
+            change,
+            keys.join(",")
+        )),
+    );
+}
+
+/// Print the paths that failed to rotate, with the reason for each
+fn print_failure_summary(heading: &str, failures: &[(String, String)]) {
+    let width = failures
+        .iter()
+        .map(|(path, _)| path.len())
+        .chain(["PATH".len()])
+        .max()
+        .unwrap_or_default();
+    println!();
+    output::failure_heading(heading);
+    println!("  {:<width$}  REASON", "PATH");
+    for (path, reason) in failures {
+        println!("  {:<width$}  {}", path, redact::redact(reason));
+    }
+}
+
+/// Print a rotated secret and apply the follow-up environment updates for it
+fn report_rotated(
+    rotated: &RotatedSecret,
+    target: Option<&dyn Target>,
+    env_updater: Option<&env_updater::EnvUpdater>,
+    env_var_names: &[String],
+    hook_failures: &mut Vec<(String, String)>,
+) {
+    output::success(format!("Rotated: {}", rotated.path));
+    if let Some(not_after) = rotated.not_after {
+        println!("  Certificate valid until: {}", not_after.to_rfc3339());
+    }
+
+    // Update target password if requested
+    if let Some(username) = &rotated.target_username {
+        let target_type_name = target.map(|t| t.target_type()).unwrap_or("unknown");
+        println!(
+            "  Updated {} password for user: {}",
+            target_type_name, username
+        );
+    }
+    for failure in &rotated.hook_failures {
+        hook_failures.push((rotated.path.clone(), failure.clone()));
+    }
+
+    // Update environment variable if requested
+    if let Some(updater) = env_updater.filter(|_| rotated.not_after.is_none()) {
+        let new_value = rotated.value.expose_secret();
+        for env_var_name in env_var_names {
+            match updater.update_env_var(env_var_name, new_value) {
+                Ok(_) => println!("  Updated env var: {}", env_var_name),
+                Err(e) => output::warn(format!("Failed to update env var {}: {}", env_var_name, e)),
+            }
+        }
+    }
+}
+
+/// Finish the run report and write it where `[run_reports]` says
+///
+/// A report that cannot be written is only warned about; the run itself is over.
+async fn write_run_report(
+    config: &Config,
+    backend: &dyn crate::backends::SecretBackend,
+    run_report: &mut RunReport,
+) {
+    run_report.finish();
+    if config.run_reports.is_empty() {
+        return;
+    }
+    match run_report.write(&config.run_reports, backend).await {
+        Ok(written) => {
+            for location in written {
+                output::note(format!(
+                    "Run {} report written to {}",
+                    run_report.run_id, location
+                ));
             }
         }
+        Err(e) => output::warn(format!("Failed to write run report: {:#}", e)),
+    }
+}
+
+/// Create an EnvUpdater from config, with CLI-specified shell files taking precedence
+fn create_env_updater(config: &Config, shell_files: &[String]) -> Result<env_updater::EnvUpdater> {
+    let updater =
+        env_updater::EnvUpdater::from_config(&config.env).context("Failed to create EnvUpdater")?;
+
+    if shell_files.is_empty() {
+        return Ok(updater);
+    }
+
+    let paths = shell_files
+        .iter()
+        .map(|f| updater.resolve_path(f))
+        .collect();
+    Ok(updater.with_shell_files(paths))
+}
+
+/// Write a file readable only by the current user
+fn write_private_file(path: &std::path::Path, contents: &str) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
     }
 
+    use std::io::Write;
+    let mut file = options.open(path)?;
+    file.write_all(contents.as_bytes())?;
     Ok(())
 }
 
@@ -526,6 +2751,17 @@ async fn create_target(
 ) -> Result<Option<TargetInstance>> {
     // Check for new targets config first
     if let Some(ref targets_config) = config.targets {
+        // A registered plugin takes precedence over the built-in targets
+        if let Some(ref name) = targets_config.plugin {
+            let factory = plugins::target_factory(name)
+                .with_context(|| format!("Target plugin '{}' is not registered", name))?;
+            let target = factory
+                .create(config, backend)
+                .await
+                .with_context(|| format!("Failed to create {} target", name))?;
+            return Ok(Some(target));
+        }
+
         // Try PostgreSQL target
         if let Some(ref postgres_config) = targets_config.postgres {
             return Ok(Some(
@@ -537,6 +2773,19 @@ async fn create_target(
         if let Some(ref api_config) = targets_config.api {
             return Ok(Some(create_api_target(api_config).await?));
         }
+
+        // Try SMTP target
+        if let Some(ref smtp_config) = targets_config.smtp {
+            let target = SmtpTarget::new(smtp_config)
+                .await
+                .context("Failed to create SMTP target")?;
+            return Ok(Some(Box::new(target)));
+        }
+
+        // Try WireGuard target
+        if let Some(ref wireguard_config) = targets_config.wireguard {
+            return Ok(Some(Box::new(WireGuardTarget::new(wireguard_config))));
+        }
     }
 
     // Fall back to legacy database config for backward compatibility
@@ -547,37 +2796,453 @@ async fn create_target(
     Ok(None)
 }
 
+/// Create the canary target from `[targets.canary]`, with its canary username
+async fn create_canary_target(
+    config: &Config,
+    backend: &dyn crate::backends::SecretBackend,
+) -> Result<Option<(TargetInstance, Option<String>)>> {
+    let Some(canary) = config.targets.as_ref().and_then(|t| t.canary.as_ref()) else {
+        return Ok(None);
+    };
+
+    let target = if let Some(ref postgres_config) = canary.postgres {
+        create_postgres_target(postgres_config, backend)
+            .await
+            .context("Failed to create canary target")?
+    } else if let Some(ref api_config) = canary.api {
+        create_api_target(api_config)
+            .await
+            .context("Failed to create canary target")?
+    } else {
+        anyhow::bail!("[targets.canary] needs a postgres or api section");
+    };
+
+    Ok(Some((target, canary.username.clone())))
+}
+
+/// Mask credentials from the configuration in all log output
+fn register_config_secrets(config: &Config) {
+    if let Some(ref vault) = config.vault {
+        redact::register_secret(vault.token.expose_secret());
+    }
+    if let Some(password) = config.keepass.as_ref().and_then(|k| k.password.as_ref()) {
+        redact::register_secret(password.expose_secret());
+    }
+
+    let targets = config.targets.as_ref();
+    let canary = targets.and_then(|t| t.canary.as_ref());
+    let postgres_configs = targets
+        .and_then(|t| t.postgres.as_ref())
+        .into_iter()
+        .chain(canary.and_then(|c| c.postgres.as_ref()))
+        .chain(config.database.as_ref());
+    for postgres in postgres_configs {
+        if let Some(ref password) = postgres.password {
+            redact::register_secret(password.expose_secret());
+        }
+    }
+
+    let api_configs = targets
+        .and_then(|t| t.api.as_ref())
+        .into_iter()
+        .chain(canary.and_then(|c| c.api.as_ref()));
+    for api in api_configs {
+        if let Some(ref auth_header) = api.auth_header {
+            redact::register_secret(auth_header.expose_secret());
+        }
+        if let Some(ref oauth2) = api.oauth2 {
+            redact::register_secret(oauth2.client_secret.expose_secret());
+        }
+    }
+
+    for webhook in &config.notifications.webhooks {
+        redact::register_secret(webhook.url.expose_secret());
+    }
+}
+
+/// Check the stored credential of every flagged secret against the target
+/// (`auto --verify-only`), failing if any no longer works
+async fn verify_flagged(
+    rotator: &Rotator,
+    path: &str,
+    filter: &PathFilter,
+    selector: &MetadataSelector,
+) -> Result<()> {
+    if rotator.target().is_none() {
+        anyhow::bail!("Target configuration not found. Configure [targets.postgres] or [targets.api] section in config file");
+    }
+    let flagged = rotation::flagged_secrets(rotator.backend(), path, selector)
+        .await
+        .context("Failed to list flagged secrets")?;
+    let flagged = filter.apply(flagged);
+    if flagged.is_empty() {
+        output::note("No flagged secrets to verify");
+        return Ok(());
+    }
+
+    let mut verified = 0;
+    let mut skipped = 0;
+    let mut drifted = Vec::new();
+    for secret_path in &flagged {
+        match rotator.verify(secret_path).await {
+            Ok(Verification::Verified { login }) => {
+                verified += 1;
+                println!("  ok       {} ({})", secret_path, login);
+            }
+            Ok(Verification::Skipped(reason)) => {
+                skipped += 1;
+                println!("  skipped  {} ({})", secret_path, reason);
+            }
+            Err(e) => {
+                println!("  FAILED   {}", secret_path);
+                drifted.push((secret_path.clone(), format!("{:#}", e)));
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "Verified {}, skipped {}, failed {} of {} flagged secret(s)",
+        verified,
+        skipped,
+        drifted.len(),
+        flagged.len()
+    );
+    if drifted.is_empty() {
+        output::success("Every stored credential works");
+        return Ok(());
+    }
+    print_failure_summary("Failed verifications:", &drifted);
+    anyhow::bail!(
+        "{} stored credential(s) no longer work on the target",
+        drifted.len()
+    )
+}
+
+/// Start leader election if `[leader]` is configured
+async fn start_leadership(config: &Config) -> Result<Option<Leadership>> {
+    let Some(ref leader) = config.leader else {
+        return Ok(None);
+    };
+    let store: Box<dyn LeaseStore> = match leader.lease {
+        LeaseKind::Backend => Box::new(
+            BackendLease::new(Arc::from(create_backend(config).await?), &leader.path)
+                .context("Invalid [leader] configuration")?,
+        ),
+        LeaseKind::Kubernetes => Box::new(KubernetesLease::in_cluster(
+            leader.namespace.as_deref(),
+            &leader.name,
+        )?),
+    };
+    let leadership = Leadership::start(
+        store,
+        leader.identity(),
+        Duration::from_secs(leader.ttl_seconds),
+    )
+    .await?;
+    Ok(Some(leadership))
+}
+
 /// Create a PostgreSQL target instance
 async fn create_postgres_target(
     config: &crate::config::PostgresTargetConfig,
     backend: &dyn crate::backends::SecretBackend,
 ) -> Result<TargetInstance> {
     // Get admin password from secret backend or direct config
-    let admin_password =
-        if let Some(ref password_path) = config.password_path {
-            // Read from secret backend
-            let secret = backend
-                .read_secret(password_path)
-                .await
-                .context("Failed to read admin password from secret backend")?;
-
-            // Try to find password key
-            secret.data.values().next().cloned().ok_or_else(|| {
-                anyhow::anyhow!("No password found in secret at {}", password_path)
-            })?
-        } else if let Some(ref password) = config.password {
-            password.clone()
+    let admin_password: SecretString = if let Some(ref password_path) = config.password_path {
+        // Read from secret backend
+        let secret = backend
+            .read_secret(password_path)
+            .await
+            .context("Failed to read admin password from secret backend")?;
+
+        // The key rotation writes to, so a rotated admin password is picked up
+        secret
+            .data
+            .get(&crate::rotation::key_to_rotate(
+                &secret.data,
+                &secret.metadata.clone().unwrap_or_default(),
+            ))
+            .or_else(|| secret.data.values().next())
+            .map(|password| SecretString::new(password.clone()))
+            .ok_or_else(|| anyhow::anyhow!("No password found in secret at {}", password_path))?
+    } else if let Some(ref password) = config.password {
+        password.clone()
+    } else {
+        anyhow::bail!(
+            "PostgreSQL password not configured. Set password_path or password in config"
+        );
+    };
+
+    redact::register_secret(admin_password.expose_secret());
+
+    Ok(Box::new(crate::targets::PostgresTarget::new(
+        config,
+        &admin_password,
+    )))
+}
+
+/// Run an external command, passing the resolved settings in its environment
+///
+/// The Vault token is only passed when it was given as `--vault-token`;
+/// otherwise the command gets it the same way `asr` did.
+async fn run_external(
+    program: &std::path::Path,
+    args: &[String],
+    cli: &Cli,
+    config: &Config,
+) -> Result<()> {
+    let mut env = vec![
+        ("SECRET_BACKEND", config.backend.clone()),
+        ("ASR_RUN_ID", crate::logging::run_id().to_string()),
+    ];
+    if let Ok(bin) = std::env::current_exe() {
+        env.push(("ASR_BIN", bin.display().to_string()));
+    }
+    if let Some(ref path) = cli.config {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        env.push(("ROTATOR_CONFIG", path.display().to_string()));
+    }
+    if let Some(format) = cli.config_format.and_then(|f| f.to_possible_value()) {
+        env.push(("ROTATOR_CONFIG_FORMAT", format.get_name().to_string()));
+    }
+    if let Some(ref profile) = cli.profile {
+        env.push(("ASR_PROFILE", profile.clone()));
+    }
+    if let Some(ref vault) = config.vault {
+        env.push(("VAULT_ADDR", vault.address.clone()));
+        env.push(("VAULT_MOUNT", vault.mount.clone()));
+    }
+    if let Some(ref token) = cli.vault_token {
+        env.push(("VAULT_TOKEN", token.clone()));
+    }
+    if let Some(ref aws) = config.aws {
+        env.push(("AWS_REGION", aws.region.clone()));
+    }
+    external::run(program, args, &env).await
+}
+
+/// Move a config file's `[database]` section to `[targets.postgres]`
+///
+/// The original is kept next to it with a `.bak` suffix. Without a config
+/// file, the replacement for the `DB_*` variables is printed instead.
+fn migrate_config_file(
+    path: Option<&std::path::Path>,
+    format: Option<StructuredFormat>,
+    dry_run: bool,
+) -> Result<()> {
+    let Some(path) = path else {
+        let config = Config::from_env().context("Failed to load config from environment")?;
+        match config.database {
+            Some(database) => print!(
+                "No config file given. Add this to one to replace the DB_* variables:\n\n{}",
+                migrate::env_replacement(&database)
+            ),
+            None => {
+                output::note("No config file given and no DB_* variables set; nothing to migrate")
+            }
+        }
+        return Ok(());
+    };
+
+    let format = format.unwrap_or_else(|| Config::detect_format(path));
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {:?}", path))?;
+    let Some(migrated) = migrate::migrate_config(&contents, format)
+        .with_context(|| format!("Failed to migrate {:?}", path))?
+    else {
+        output::note(format!(
+            "{} has no [database] section; nothing to migrate",
+            path.display()
+        ));
+        return Ok(());
+    };
+    Config::parse(&migrated, format, None).context("Migrated config does not load")?;
+
+    if dry_run {
+        print!("{}", migrated);
+        return Ok(());
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    std::fs::copy(path, &backup)
+        .with_context(|| format!("Failed to back up {:?} to {:?}", path, backup))?;
+    std::fs::write(path, migrated)
+        .with_context(|| format!("Failed to write config file: {:?}", path))?;
+    output::success(format!(
+        "Moved [database] to [targets.postgres] in {} (original saved as {})",
+        path.display(),
+        backup.display()
+    ));
+    if format != StructuredFormat::Toml {
+        output::note("comments are only kept in TOML files");
+    }
+    Ok(())
+}
+
+/// Run the `doctor` checks and print them as a table
+async fn run_doctor(config: &Config, path: &str) -> Result<()> {
+    let mut checks = vec![doctor::Check::new(
+        "config",
+        doctor::Status::Pass,
+        format!("loaded; backend {}", config.backend),
+    )];
+
+    match create_backend(config).await {
+        Ok(backend) => {
+            let uses_vault = config.backend == "vault"
+                || config.routes.iter().any(|route| {
+                    route
+                        .parse::<BackendRoute>()
+                        .is_ok_and(|route| route.backend == "vault")
+                });
+            if let Some(vault_config) = config.vault.as_ref().filter(|_| uses_vault) {
+                let client = vault_client(vault_config).context("Failed to create Vault client")?;
+                checks.extend(doctor::check_vault(&client, &vault_config.mount, path).await);
+            }
+            checks.extend(doctor::check_backend(backend.as_ref(), path).await);
+
+            checks.push(match create_target(config, backend.as_ref()).await {
+                Ok(target) => doctor::check_target(target.as_deref()).await,
+                Err(e) => doctor::Check::failed("target", &e),
+            });
+        }
+        Err(e) => {
+            checks.push(doctor::Check::failed("backend", &e));
+            checks.push(doctor::Check::new(
+                "target",
+                doctor::Status::Skip,
+                "needs a working backend",
+            ));
+        }
+    }
+
+    print!("{}", doctor::render(&checks));
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == doctor::Status::Fail)
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{} check(s) failed", failed);
+    }
+    Ok(())
+}
+
+/// Create the client for the database secrets engine configured in [vault_database]
+fn create_database_engine(config: &Config) -> Result<vault_db::DatabaseEngine> {
+    let database = config.vault_database.as_ref().context(
+        "No database secrets engine configured. Add a [vault_database] section to the config file",
+    )?;
+    let vault_config = config.vault.as_ref().context(
+        "[vault_database] is managed through Vault; set VAULT_ADDR/VAULT_TOKEN or configure [vault]",
+    )?;
+    let client = vault_client(vault_config).context("Failed to create Vault client")?;
+    Ok(vault_db::DatabaseEngine::new(client, database))
+}
+
+/// Rotate database engine root credentials (or static roles), continuing past
+/// failures and recording an audit event for each
+async fn rotate_database_credentials(
+    rotator: &Rotator,
+    engine: &vault_db::DatabaseEngine,
+    static_roles: bool,
+    names: Vec<String>,
+    yes: bool,
+) -> Result<()> {
+    let (action, configured, setting, kind) = if static_roles {
+        (
+            "rotate-role",
+            engine.static_roles(),
+            "static_roles",
+            "static role password(s)",
+        )
+    } else {
+        (
+            "rotate-root",
+            engine.connections(),
+            "connections",
+            "root credential(s)",
+        )
+    };
+    let names = if names.is_empty() {
+        configured.to_vec()
+    } else {
+        names
+    };
+    if names.is_empty() {
+        anyhow::bail!(
+            "Nothing to rotate. Name them on the command line or set [vault_database] {}",
+            setting
+        );
+    }
+    confirm(
+        &format!(
+            "About to rotate {} {} in {}: {}",
+            names.len(),
+            kind,
+            engine.mount(),
+            names.join(", ")
+        ),
+        yes,
+    )?;
+
+    let mut failed = 0;
+    for name in &names {
+        let result = if static_roles {
+            engine.rotate_role(name).await
         } else {
-            anyhow::bail!(
-                "PostgreSQL password not configured. Set password_path or password in config"
-            );
+            engine.rotate_root(name).await
+        };
+        let path = format!("{}/{}", engine.mount(), name);
+        let outcome = if result.is_ok() {
+            Outcome::Success
+        } else {
+            Outcome::Failure
         };
+        rotator
+            .audit()
+            .record(&AuditEvent::new(action, &path, "vault", outcome));
+        match result {
+            Ok(()) if static_roles => output::success(format!("Rotated static role {}", path)),
+            Ok(()) => output::success(format!("Rotated root credential of {}", path)),
+            Err(e) => {
+                failed += 1;
+                error!("{}", redact::redact(&format!("{:#}", e)));
+            }
+        }
+    }
 
-    let target = crate::targets::PostgresTarget::new(config, &admin_password)
-        .await
-        .context("Failed to create PostgreSQL target")?;
+    if failed > 0 {
+        return Err(RotationFailures {
+            rotated: names.len() - failed,
+            failed,
+        }
+        .into());
+    }
+    Ok(())
+}
 
-    Ok(Box::new(target))
+/// Create the certificate issuers configured in [pki] and [acme]
+fn create_issuers(config: &Config) -> Result<Vec<Box<dyn certs::CertificateIssuer>>> {
+    let mut issuers: Vec<Box<dyn certs::CertificateIssuer>> = Vec::new();
+
+    if let Some(ref pki) = config.pki {
+        let vault_config = config.vault.as_ref().context(
+            "[pki] issues certificates from Vault; set VAULT_ADDR/VAULT_TOKEN or configure [vault]",
+        )?;
+        let client = vault_client(vault_config).context("Failed to create Vault client")?;
+        issuers.push(Box::new(certs::VaultPkiIssuer::new(client, pki)));
+    }
+
+    if let Some(ref acme) = config.acme {
+        issuers.push(Box::new(
+            certs::AcmeIssuer::new(acme).context("Invalid [acme] configuration")?,
+        ));
+    }
+
+    Ok(issuers)
 }
 
 /// Create an API target instance
@@ -590,19 +3255,129 @@ async fn create_api_target(config: &crate::config::ApiTargetConfig) -> Result<Ta
 }
 
 /// Create a backend instance based on configuration
+///
+/// With a `[metadata_store]`, rotation metadata is kept there instead of in
+/// the backend.
 async fn create_backend(config: &Config) -> Result<Backend> {
-    match config.backend.as_str() {
+    let backend = create_routed_backend(config).await?;
+    let Some(ref store_config) = config.metadata_store else {
+        return Ok(backend);
+    };
+    let backend: Arc<dyn SecretBackend> = Arc::from(backend);
+    let store = metadata_store::open(store_config, backend.clone())
+        .await
+        .context("Failed to open the metadata store")?;
+    tracing::debug!(
+        "Keeping rotation metadata in the {} store",
+        store.store_type()
+    );
+    Ok(Box::new(MetadataStoreBackend::new(backend, store)))
+}
+
+/// Create the backend, or several of them when routes are configured
+///
+/// Every backend the routes name is created and paths are dispatched between
+/// them, with `backend` as the fallback.
+async fn create_routed_backend(config: &Config) -> Result<Backend> {
+    if config.routes.is_empty() {
+        return create_single_backend(config, &config.backend).await;
+    }
+
+    let routes = config
+        .routes
+        .iter()
+        .map(|route| {
+            route
+                .parse::<BackendRoute>()
+                .map_err(|e| anyhow::anyhow!(e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut backends = std::collections::HashMap::new();
+    let names = routes
+        .iter()
+        .map(|route| route.backend.as_str())
+        .chain(std::iter::once(config.backend.as_str()));
+    for name in names {
+        if backends.contains_key(name) {
+            continue;
+        }
+        if plugins::backend_factory(name).is_none() {
+            name.parse::<BackendType>()
+                .map_err(|e| anyhow::anyhow!(e))?;
+        }
+        let backend = create_single_backend(config, name)
+            .await
+            .with_context(|| format!("Failed to create {} backend for routing", name))?;
+        backends.insert(name.to_string(), backend);
+    }
+
+    Ok(Box::new(RoutedBackend::new(
+        backends,
+        routes,
+        config.backend.clone(),
+    )?))
+}
+
+/// Envelope encryption with the `[transit]` key
+fn create_transit(config: &Config) -> Result<Transit> {
+    let transit_config = config
+        .transit
+        .as_ref()
+        .context("No transit key configured. Add a [transit] section with the key name")?;
+    let vault_config = config
+        .vault
+        .as_ref()
+        .context("[transit] keys live in Vault; set VAULT_ADDR/VAULT_TOKEN or configure [vault]")?;
+    let client = vault_client(vault_config).context("Failed to create Vault client")?;
+    Ok(Transit::new(client, transit_config))
+}
+
+/// Create a Vault client with the configured timeouts
+fn vault_client(config: &crate::config::VaultConfig) -> Result<crate::backends::VaultClient> {
+    Ok(crate::backends::VaultClient::with_timeouts(
+        config.address.clone(),
+        config.token.clone(),
+        Duration::from_secs(config.connect_timeout_seconds),
+        Duration::from_secs(config.request_timeout_seconds),
+    )?
+    .with_flavor(config.flavor))
+}
+
+/// Create the backend of the given type, preferring registered plugins
+async fn create_single_backend(config: &Config, backend_type: &str) -> Result<Backend> {
+    if let Some(factory) = plugins::backend_factory(backend_type) {
+        return factory
+            .create(config)
+            .await
+            .with_context(|| format!("Failed to create {} backend", backend_type));
+    }
+
+    match backend_type {
         "aws" => {
             let aws_config = config.aws.as_ref().ok_or_else(|| {
                 anyhow::anyhow!(
                     "AWS configuration not found. Set AWS_REGION or configure [aws] section"
                 )
             })?;
-            let aws_client =
-                crate::backends::AwsSecretsClient::new(Some(aws_config.region.clone()))
+            let mut clients: Vec<(String, Backend)> = Vec::new();
+            for region in aws_config.all_regions() {
+                let aws_client = crate::backends::AwsSecretsClient::new(Some(region.to_string()))
                     .await
-                    .context("Failed to create AWS Secrets Manager client")?;
-            Ok(Box::new(aws_client))
+                    .with_context(|| {
+                        format!("Failed to create AWS Secrets Manager client for {}", region)
+                    })?
+                    .with_value_key(aws_config.value_key.clone())
+                    .with_replication_wait(Duration::from_secs(
+                        aws_config.replication_wait_seconds,
+                    ));
+                clients.push((region.to_string(), Box::new(aws_client)));
+            }
+            if clients.len() == 1 {
+                Ok(clients.remove(0).1)
+            } else {
+                Ok(Box::new(MirroredBackend::new(clients)?))
+            }
         }
         "file" => {
             let file_config = config.file.as_ref().ok_or_else(|| {
@@ -610,21 +3385,53 @@ async fn create_backend(config: &Config) -> Result<Backend> {
                     "File configuration not found. Set ASR_FILE_DIR or configure [file] section"
                 )
             })?;
-            let file_backend = crate::backends::FileBackend::new(&file_config.directory)
-                .context("Failed to create file backend")?;
+            let mut file_backend = crate::backends::FileBackend::new(&file_config.directory)
+                .context("Failed to create file backend")?
+                .with_versions(file_config.versions);
+            if file_config.transit {
+                file_backend = file_backend.with_transit(create_transit(config)?);
+            }
             Ok(Box::new(file_backend))
         }
+        "pass" => {
+            let pass_config = config.pass.as_ref().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Pass configuration not found. Set PASSWORD_STORE_DIR or configure [pass] section"
+                )
+            })?;
+            let pass_backend = crate::backends::PassBackend::new(&pass_config.directory)
+                .context("Failed to create pass backend")?
+                .with_value_key(pass_config.value_key.clone());
+            Ok(Box::new(pass_backend))
+        }
+        #[cfg(feature = "keepass")]
+        "keepass" => {
+            let keepass_config = config.keepass.as_ref().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "KeePass configuration not found. Set ASR_KEEPASS_PATH or configure [keepass] section"
+                )
+            })?;
+            let keepass_backend = crate::backends::KeePassBackend::new(
+                &keepass_config.path,
+                keepass_config.password.clone(),
+                keepass_config.keyfile.as_ref().map(Into::into),
+            )
+            .context("Failed to create KeePass backend")?;
+            Ok(Box::new(keepass_backend))
+        }
+        #[cfg(not(feature = "keepass"))]
+        "keepass" => {
+            anyhow::bail!(
+                "This build of asr has no KeePass support. Rebuild with --features keepass"
+            )
+        }
         "vault" => {
             let vault_config = config.vault.as_ref().ok_or_else(|| {
                 anyhow::anyhow!("Vault configuration not found. Set VAULT_ADDR/VAULT_TOKEN or configure [vault] section")
             })?;
-            let vault_client = crate::backends::VaultClient::new(
-                vault_config.address.clone(),
-                vault_config.token.clone(),
-            )
-            .context("Failed to create Vault client")?;
+            let client = vault_client(vault_config).context("Failed to create Vault client")?;
             Ok(Box::new(crate::backends::VaultBackend::new(
-                vault_client,
+                client,
                 vault_config.mount.clone(),
             )))
         }
@@ -632,13 +3439,9 @@ async fn create_backend(config: &Config) -> Result<Backend> {
             let vault_config = config.vault.as_ref().ok_or_else(|| {
                 anyhow::anyhow!("Vault configuration not found. Set VAULT_ADDR/VAULT_TOKEN or configure [vault] section")
             })?;
-            let vault_client = crate::backends::VaultClient::new(
-                vault_config.address.clone(),
-                vault_config.token.clone(),
-            )
-            .context("Failed to create Vault client")?;
+            let client = vault_client(vault_config).context("Failed to create Vault client")?;
             Ok(Box::new(crate::backends::VaultBackend::new(
-                vault_client,
+                client,
                 vault_config.mount.clone(),
             )))
         }
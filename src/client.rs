@@ -0,0 +1,291 @@
+//! In-process secret cache for applications (library use)
+//!
+//! Services that read the same secrets on every request should not hit the
+//! backend each time. [`CachingSecretClient`] keeps each secret for a TTL and
+//! refreshes it in the background shortly before it expires, so requests are
+//! served from memory and pick up a rotated value within the TTL:
+//!
+//! ```no_run
+//! # async fn example(backend: std::sync::Arc<dyn secret_rotator::backends::SecretBackend>) -> anyhow::Result<()> {
+//! use secret_rotator::client::CachingSecretClient;
+//! use std::time::Duration;
+//!
+//! let client = CachingSecretClient::new(backend, Duration::from_secs(300));
+//! let db = client.get("app/db").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Concurrent reads of a secret that is missing or expired wait for a single
+//! backend read instead of all going to the backend at once. A failed read is
+//! shared the same way and returned for a short while (one second by
+//! default), so an outage does not turn every request into a backend read.
+//! Secrets that have expired and are not being read are dropped from the
+//! cache as new ones are added. Unlike
+//! [`crate::backends::CachedBackend`], which caches metadata for scans, this
+//! caches secret values, so keep it inside the application.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::backends::{SecretBackend, SecretData};
+
+/// How long a failed read is returned to callers before the backend is tried
+/// again, by default
+pub const DEFAULT_ERROR_TTL: Duration = Duration::from_secs(1);
+
+/// Cached secrets kept before expired ones are swept out for the first time
+const MIN_SWEEP_SIZE: usize = 64;
+
+/// Caches secrets read from a backend; clones share the cache
+#[derive(Clone)]
+pub struct CachingSecretClient {
+    inner: Arc<Inner>,
+    refresh_ahead: Duration,
+    error_ttl: Duration,
+}
+
+struct Inner {
+    backend: Arc<dyn SecretBackend>,
+    ttl: Duration,
+    slots: Mutex<HashMap<String, Arc<Slot>>>,
+    /// Number of cached secrets at which expired ones are swept out next
+    sweep_at: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Slot {
+    /// Held while the secret is read, so concurrent misses wait for that read
+    entry: tokio::sync::Mutex<Option<Entry>>,
+    refreshing: AtomicBool,
+}
+
+struct Entry {
+    /// The secret, or the error reading it failed with
+    value: Result<SecretData, String>,
+    expires: Instant,
+}
+
+impl CachingSecretClient {
+    /// Cache secrets read from `backend` for `ttl`, refreshing them in the
+    /// background during the last quarter of it
+    pub fn new(backend: Arc<dyn SecretBackend>, ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                backend,
+                ttl,
+                slots: Mutex::new(HashMap::new()),
+                sweep_at: AtomicUsize::new(MIN_SWEEP_SIZE),
+            }),
+            refresh_ahead: ttl / 4,
+            error_ttl: DEFAULT_ERROR_TTL.min(ttl),
+        }
+    }
+
+    /// Start background refreshes when a read finds less than `refresh_ahead`
+    /// of the TTL left (zero disables them)
+    pub fn with_refresh_ahead(mut self, refresh_ahead: Duration) -> Self {
+        self.refresh_ahead = refresh_ahead.min(self.inner.ttl);
+        self
+    }
+
+    /// Return a failed read for `error_ttl` instead of [`DEFAULT_ERROR_TTL`]
+    /// (zero reads the backend again on every call)
+    pub fn with_error_ttl(mut self, error_ttl: Duration) -> Self {
+        self.error_ttl = error_ttl;
+        self
+    }
+
+    /// The secret at `path`, from the cache while it is fresh
+    pub async fn get(&self, path: &str) -> Result<SecretData> {
+        let slot = self.inner.slot(path);
+        let mut entry = slot.entry.lock().await;
+        let now = Instant::now();
+        if let Some(cached) = entry.as_ref().filter(|cached| now < cached.expires) {
+            return match &cached.value {
+                Ok(secret) => {
+                    let left = cached.expires - now;
+                    if !self.refresh_ahead.is_zero() && left <= self.refresh_ahead {
+                        self.refresh_in_background(path, &slot);
+                    }
+                    Ok(secret.clone())
+                }
+                // Callers that waited for a failed read get its error too
+                Err(e) => Err(anyhow::anyhow!("{}", e)),
+            };
+        }
+
+        let result = self.inner.backend.read_secret(path).await;
+        *entry = Some(match &result {
+            Ok(secret) => Entry {
+                value: Ok(secret.clone()),
+                expires: Instant::now() + self.inner.ttl,
+            },
+            Err(e) => Entry {
+                value: Err(format!("{:#}", e)),
+                expires: Instant::now() + self.error_ttl,
+            },
+        });
+        result
+    }
+
+    /// Drop the cached copy of `path`, e.g. after writing it
+    pub fn invalidate(&self, path: &str) {
+        self.inner.slots().remove(path);
+    }
+
+    /// Drop every cached secret
+    pub fn clear(&self) {
+        self.inner.slots().clear();
+    }
+
+    /// Re-read `path` without blocking readers, who keep getting the cached
+    /// copy until the new one is stored
+    fn refresh_in_background(&self, path: &str, slot: &Arc<Slot>) {
+        if slot.refreshing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let inner = self.inner.clone();
+        let slot = slot.clone();
+        let path = path.to_string();
+        tokio::spawn(async move {
+            match inner.backend.read_secret(&path).await {
+                Ok(secret) => {
+                    *slot.entry.lock().await = Some(Entry {
+                        value: Ok(secret),
+                        expires: Instant::now() + inner.ttl,
+                    });
+                }
+                // The cached copy is served until it expires, then read again
+                Err(e) => warn!("Failed to refresh cached secret {}: {:#}", path, e),
+            }
+            slot.refreshing.store(false, Ordering::Release);
+        });
+    }
+}
+
+impl Inner {
+    fn slots(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Slot>>> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The slot of `path`, added if needed; adding one sweeps out expired
+    /// secrets nobody is reading once the cache has doubled since the last
+    /// sweep
+    fn slot(&self, path: &str) -> Arc<Slot> {
+        let mut slots = self.slots();
+        if let Some(slot) = slots.get(path) {
+            return slot.clone();
+        }
+        if slots.len() >= self.sweep_at.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            slots.retain(|_, slot| !slot.idle(now));
+            self.sweep_at
+                .store((slots.len() * 2).max(MIN_SWEEP_SIZE), Ordering::Relaxed);
+        }
+        slots.entry(path.to_string()).or_default().clone()
+    }
+}
+
+impl Slot {
+    /// Expired (or never filled) and neither read nor refreshed right now
+    fn idle(self: &Arc<Self>, now: Instant) -> bool {
+        // The map holds the only reference while nobody uses the slot
+        Arc::strong_count(self) == 1
+            && self
+                .entry
+                .try_lock()
+                .is_ok_and(|entry| entry.as_ref().is_none_or(|e| now >= e.expires))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::{MemoryBackend, Operation};
+
+    #[tokio::test]
+    async fn test_caching_client() -> Result<()> {
+        let memory = Arc::new(
+            MemoryBackend::new()
+                .with_secret("app/db", [("password", "one")])
+                .with_latency(Duration::from_millis(20)),
+        );
+        let backend: Arc<dyn SecretBackend> = memory.clone();
+
+        // Concurrent misses share one read, and later reads are cached
+        let client = CachingSecretClient::new(backend.clone(), Duration::from_secs(3600));
+        let reads: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move { client.get("app/db").await })
+            })
+            .collect();
+        for read in reads {
+            assert_eq!(read.await??.data["password"], "one");
+        }
+        client.get("app/db").await?;
+        assert_eq!(memory.calls(Operation::Read), 1);
+
+        memory.insert_secret("app/db", [("password", "two")]);
+        client.invalidate("app/db");
+        assert_eq!(client.get("app/db").await?.data["password"], "two");
+
+        // A failed read is shared with the callers waiting for it, and
+        // returned until the error TTL is over
+        let client = CachingSecretClient::new(backend.clone(), Duration::from_secs(3600))
+            .with_error_ttl(Duration::from_millis(50));
+        let reads_before = memory.calls(Operation::Read);
+        let reads: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move { client.get("app/late").await })
+            })
+            .collect();
+        for read in reads {
+            let err = read.await?.unwrap_err();
+            assert!(err.to_string().contains("app/late"));
+        }
+        assert!(client.get("app/late").await.is_err());
+        assert_eq!(memory.calls(Operation::Read), reads_before + 1);
+        memory.insert_secret("app/late", [("password", "p")]);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(client.get("app/late").await?.data["password"], "p");
+
+        // Inside the refresh window the cached value is returned while a
+        // background read replaces it
+        let client = CachingSecretClient::new(backend.clone(), Duration::from_secs(3600))
+            .with_refresh_ahead(Duration::from_secs(3600));
+        client.get("app/db").await?;
+        memory.insert_secret("app/db", [("password", "three")]);
+        assert_eq!(client.get("app/db").await?.data["password"], "two");
+        let refreshed = async {
+            while client.get("app/db").await?.data["password"] != "three" {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            anyhow::Ok(())
+        };
+        tokio::time::timeout(Duration::from_secs(5), refreshed).await??;
+
+        // Expired secrets nobody reads are swept out as others are added
+        let quick = Arc::new(MemoryBackend::new());
+        let client = CachingSecretClient::new(quick.clone(), Duration::ZERO);
+        for i in 0..200 {
+            quick.insert_secret(&format!("app/s{}", i), [("password", "p")]);
+            client.get(&format!("app/s{}", i)).await?;
+        }
+        assert!(client.inner.slots().len() < MIN_SWEEP_SIZE * 2);
+
+        // Expired entries are read again, and read errors are returned
+        let client = CachingSecretClient::new(backend, Duration::ZERO);
+        client.get("app/db").await?;
+        memory.fail_always(Operation::Read, "connection reset");
+        assert!(client.get("app/db").await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! Clipboard access for handing over secrets without printing them
+//!
+//! Uses the platform clipboard tools (`pbcopy`, `clip`, `wl-copy`, `xclip`,
+//! `xsel`) rather than linking a clipboard library, so no display server
+//! libraries are needed at build time.
+
+use anyhow::{Context, Result};
+use std::io::{ErrorKind, Read, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tracing::debug;
+
+/// Candidate commands for writing to the clipboard, in order of preference
+fn copy_commands(wayland: bool) -> Vec<&'static [&'static str]> {
+    if cfg!(target_os = "macos") {
+        vec![&["pbcopy"]]
+    } else if cfg!(windows) {
+        vec![&["clip"]]
+    } else {
+        let mut commands: Vec<&'static [&'static str]> = vec![
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ];
+        if wayland {
+            commands.insert(0, &["wl-copy"]);
+        }
+        commands
+    }
+}
+
+/// Candidate commands for reading the clipboard, in order of preference
+fn paste_commands(wayland: bool) -> Vec<&'static [&'static str]> {
+    if cfg!(target_os = "macos") {
+        vec![&["pbpaste"]]
+    } else if cfg!(windows) {
+        vec![&["powershell", "-NoProfile", "-Command", "Get-Clipboard"]]
+    } else {
+        let mut commands: Vec<&'static [&'static str]> = vec![
+            &["xclip", "-selection", "clipboard", "-o"],
+            &["xsel", "--clipboard", "--output"],
+        ];
+        if wayland {
+            commands.insert(0, &["wl-paste", "--no-newline"]);
+        }
+        commands
+    }
+}
+
+fn is_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// Place a value on the system clipboard
+pub fn copy(value: &str) -> Result<()> {
+    for command in copy_commands(is_wayland()) {
+        let child = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to run {}", command[0])),
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(value.as_bytes())
+                .with_context(|| format!("Failed to write to {}", command[0]))?;
+        }
+
+        let status = child
+            .wait()
+            .with_context(|| format!("Failed to wait for {}", command[0]))?;
+        if !status.success() {
+            anyhow::bail!("{} exited with {}", command[0], status);
+        }
+
+        debug!("Copied value to clipboard with {}", command[0]);
+        return Ok(());
+    }
+
+    anyhow::bail!("No clipboard tool found (install wl-clipboard, xclip, or xsel)")
+}
+
+/// Read the current clipboard contents
+pub fn paste() -> Result<String> {
+    for command in paste_commands(is_wayland()) {
+        let output = match Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to run {}", command[0])),
+        };
+
+        if !output.status.success() {
+            anyhow::bail!("{} exited with {}", command[0], output.status);
+        }
+
+        let contents = String::from_utf8_lossy(&output.stdout);
+        // Get-Clipboard appends a line ending
+        return Ok(contents.trim_end_matches(['\r', '\n']).to_string());
+    }
+
+    anyhow::bail!("No clipboard tool found (install wl-clipboard, xclip, or xsel)")
+}
+
+/// Clear the clipboard if it still holds `value`
+///
+/// If the clipboard cannot be read back, it is cleared anyway.
+pub fn clear_if_unchanged(value: &str) -> Result<()> {
+    match paste() {
+        Ok(current) if current != value => {
+            debug!("Clipboard changed since copy, leaving it alone");
+            Ok(())
+        }
+        _ => copy(""),
+    }
+}
+
+/// Clear the clipboard after a delay without blocking the current process
+///
+/// Spawns `asr clipboard-clear` in the background and hands it the value over
+/// stdin, so the secret never appears in a process listing.
+pub fn schedule_clear(value: &str, after: Duration) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to locate the asr executable")?;
+
+    let mut child = Command::new(exe)
+        .arg("clipboard-clear")
+        .arg("--after")
+        .arg(after.as_secs().to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to start background clipboard clear")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(value.as_bytes())
+            .context("Failed to hand value to background clipboard clear")?;
+    }
+
+    Ok(())
+}
+
+/// Entry point of the background process started by [`schedule_clear`]
+pub fn run_scheduled_clear(after: Duration) -> Result<()> {
+    let mut value = String::new();
+    std::io::stdin()
+        .read_to_string(&mut value)
+        .context("Failed to read value from stdin")?;
+
+    std::thread::sleep(after);
+    clear_if_unchanged(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_wayland_tools_preferred() {
+        assert_eq!(copy_commands(true)[0], &["wl-copy"]);
+        assert_eq!(
+            copy_commands(false)[0],
+            &["xclip", "-selection", "clipboard"]
+        );
+        assert_eq!(paste_commands(true)[0], &["wl-paste", "--no-newline"]);
+        assert_eq!(copy_commands(true).len(), paste_commands(true).len());
+    }
+}
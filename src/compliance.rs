@@ -0,0 +1,392 @@
+//! Compliance reports of secret age (`asr report compliance`)
+//!
+//! Lists every secret flagged for rotation with its age, rotation policy, due
+//! date and how many days it is overdue, and measures the share rotated
+//! within policy against an SLA (e.g., "95% rotated within policy"). Exported
+//! as CSV or JSON for auditors, or Markdown to read. Like the posture report,
+//! it only contains paths and dates, never secret values.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::backends::SecretBackend;
+use crate::certs;
+use crate::report::{format_date, markdown_cell, read_all_metadata};
+use crate::rotation;
+
+/// Output format for compliance reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ComplianceFormat {
+    Csv,
+    Json,
+    #[value(name = "md", alias = "markdown")]
+    Markdown,
+}
+
+impl ComplianceFormat {
+    /// Pick the format from an output file extension (Markdown unless `.csv`/`.json`)
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => ComplianceFormat::Csv,
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ComplianceFormat::Json,
+            _ => ComplianceFormat::Markdown,
+        }
+    }
+}
+
+/// Whether a flagged secret is within its rotation policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceStatus {
+    WithinPolicy,
+    Overdue,
+    /// Metadata could not be read, so compliance is unknown
+    Error,
+}
+
+impl ComplianceStatus {
+    fn label(self) -> &'static str {
+        match self {
+            ComplianceStatus::WithinPolicy => "within_policy",
+            ComplianceStatus::Overdue => "overdue",
+            ComplianceStatus::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComplianceEntry {
+    pub path: String,
+    pub owner: Option<String>,
+    pub last_rotated: Option<DateTime<Utc>>,
+    /// Whole days since the last rotation
+    pub age_days: Option<i64>,
+    /// Rotation period (e.g., "90 days") or certificate renewal
+    pub policy: Option<String>,
+    pub due_at: Option<DateTime<Utc>>,
+    /// Whole days past the due date (0 when within policy)
+    pub days_overdue: Option<i64>,
+    pub status: ComplianceStatus,
+    pub error: Option<String>,
+}
+
+/// Counts and share of flagged secrets within policy
+#[derive(Debug, Clone, Serialize)]
+pub struct ComplianceSummary {
+    pub flagged: usize,
+    pub within_policy: usize,
+    pub overdue: usize,
+    pub errors: usize,
+    /// Share of flagged secrets within policy, in percent (100 with none flagged)
+    pub percent_within_policy: f64,
+    pub sla_percent: f64,
+    pub sla_met: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComplianceReport {
+    pub generated_at: DateTime<Utc>,
+    pub backend: String,
+    pub path: String,
+    pub summary: ComplianceSummary,
+    pub secrets: Vec<ComplianceEntry>,
+}
+
+/// Assess a flagged secret against its policy at `now`
+fn assess(
+    path: String,
+    metadata: &HashMap<String, String>,
+    default_period: u32,
+    now: DateTime<Utc>,
+) -> ComplianceEntry {
+    let last_rotated = rotation::last_rotated(metadata);
+    let (policy, due_at) = if certs::is_certificate(metadata) {
+        (
+            "certificate renewal".to_string(),
+            certs::renewal_due_at(metadata),
+        )
+    } else {
+        (
+            rotation::rotation_period(metadata, default_period).to_string(),
+            rotation::due_at(metadata, default_period),
+        )
+    };
+
+    let overdue = rotation::needs_rotation(&Some(metadata.clone()), default_period);
+    ComplianceEntry {
+        path,
+        owner: rotation::owner(metadata),
+        last_rotated,
+        age_days: last_rotated.map(|last| (now - last).num_days()),
+        policy: Some(policy),
+        due_at,
+        // Never rotated secrets are overdue by an unknown number of days
+        days_overdue: match (overdue, due_at) {
+            (false, _) => Some(0),
+            (true, Some(due)) => Some((now - due).num_days().max(0)),
+            (true, None) => None,
+        },
+        status: if overdue {
+            ComplianceStatus::Overdue
+        } else {
+            ComplianceStatus::WithinPolicy
+        },
+        error: None,
+    }
+}
+
+impl ComplianceSummary {
+    fn new(secrets: &[ComplianceEntry], sla_percent: f64) -> Self {
+        let count = |status| secrets.iter().filter(|s| s.status == status).count();
+        let within_policy = count(ComplianceStatus::WithinPolicy);
+        let percent_within_policy = if secrets.is_empty() {
+            100.0
+        } else {
+            within_policy as f64 * 100.0 / secrets.len() as f64
+        };
+        Self {
+            flagged: secrets.len(),
+            within_policy,
+            overdue: count(ComplianceStatus::Overdue),
+            errors: count(ComplianceStatus::Error),
+            percent_within_policy,
+            sla_percent,
+            sla_met: percent_within_policy >= sla_percent,
+        }
+    }
+}
+
+impl ComplianceReport {
+    /// Assess every secret flagged for rotation under `path`
+    ///
+    /// Secrets whose metadata cannot be read are included as errors and
+    /// count against the SLA, since they cannot be shown to be compliant.
+    pub async fn collect(
+        backend: &dyn SecretBackend,
+        path: &str,
+        default_period: u32,
+        sla_percent: f64,
+    ) -> Result<Self> {
+        let now = Utc::now();
+        let mut secrets = Vec::new();
+        for (secret_path, metadata) in read_all_metadata(backend, path).await? {
+            match metadata {
+                Ok(metadata) if rotation::rotation_enabled(&metadata) => {
+                    secrets.push(assess(secret_path, &metadata, default_period, now))
+                }
+                Ok(_) => {}
+                Err(e) => secrets.push(ComplianceEntry {
+                    path: secret_path,
+                    owner: None,
+                    last_rotated: None,
+                    age_days: None,
+                    policy: None,
+                    due_at: None,
+                    days_overdue: None,
+                    status: ComplianceStatus::Error,
+                    error: Some(format!("{:#}", e)),
+                }),
+            }
+        }
+        secrets.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(Self {
+            generated_at: now,
+            backend: backend.backend_type().to_string(),
+            path: path.to_string(),
+            summary: ComplianceSummary::new(&secrets, sla_percent),
+            secrets,
+        })
+    }
+
+    pub fn render(&self, format: ComplianceFormat) -> Result<String> {
+        Ok(match format {
+            ComplianceFormat::Csv => self.render_csv(),
+            ComplianceFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            ComplianceFormat::Markdown => self.render_markdown(),
+        })
+    }
+
+    /// One-line summary, e.g. "96.0% of 25 flagged secret(s) rotated within policy (SLA 95%: met)"
+    pub fn summary_line(&self) -> String {
+        let summary = &self.summary;
+        format!(
+            "{:.1}% of {} flagged secret(s) rotated within policy (SLA {}%: {})",
+            summary.percent_within_policy,
+            summary.flagged,
+            summary.sla_percent,
+            if summary.sla_met { "met" } else { "missed" }
+        )
+    }
+
+    fn render_csv(&self) -> String {
+        let mut out = String::from(
+            "path,owner,last_rotated,age_days,policy,due_at,days_overdue,status,error\n",
+        );
+        for s in &self.secrets {
+            let date = |d: Option<DateTime<Utc>>| d.map(|d| d.to_rfc3339()).unwrap_or_default();
+            let number = |n: Option<i64>| n.map(|n| n.to_string()).unwrap_or_default();
+            let fields = [
+                s.path.clone(),
+                s.owner.clone().unwrap_or_default(),
+                date(s.last_rotated),
+                number(s.age_days),
+                s.policy.clone().unwrap_or_default(),
+                date(s.due_at),
+                number(s.days_overdue),
+                s.status.label().to_string(),
+                s.error.clone().unwrap_or_default(),
+            ];
+            let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            let _ = writeln!(out, "{}", row.join(","));
+        }
+        out
+    }
+
+    fn render_markdown(&self) -> String {
+        let summary = &self.summary;
+        let mut out = String::new();
+        let _ = writeln!(out, "# Rotation Compliance Report\n");
+        let _ = writeln!(
+            out,
+            "Generated {} for `{}` on the {} backend.\n",
+            format_date(Some(self.generated_at)),
+            if self.path.is_empty() {
+                "/"
+            } else {
+                &self.path
+            },
+            self.backend
+        );
+        let _ = writeln!(out, "**{}**\n", self.summary_line());
+        let _ = writeln!(out, "| Flagged | Within policy | Overdue | Errors |");
+        let _ = writeln!(out, "|---|---|---|---|");
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} |\n",
+            summary.flagged, summary.within_policy, summary.overdue, summary.errors
+        );
+
+        if self.secrets.is_empty() {
+            let _ = writeln!(out, "No secrets are flagged for rotation.");
+            return out;
+        }
+        let _ = writeln!(
+            out,
+            "| Secret | Owner | Age (days) | Policy | Due | Days overdue | Status |"
+        );
+        let _ = writeln!(out, "|---|---|---|---|---|---|---|");
+        for s in &self.secrets {
+            let number = |n: Option<i64>| n.map(|n| n.to_string()).unwrap_or("-".to_string());
+            let _ = writeln!(
+                out,
+                "| `{}` | {} | {} | {} | {} | {} | {} |",
+                markdown_cell(&s.path),
+                markdown_cell(s.owner.as_deref().unwrap_or("-")),
+                number(s.age_days),
+                s.policy.as_deref().unwrap_or("-"),
+                format_date(s.due_at),
+                number(s.days_overdue),
+                match &s.error {
+                    Some(e) => format!("error: {}", markdown_cell(e)),
+                    None => s.status.label().replace('_', " "),
+                }
+            );
+        }
+        out
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::{MemoryBackend, Operation};
+
+    #[tokio::test]
+    async fn test_compliance_report() -> Result<()> {
+        let backend = MemoryBackend::new()
+            .with_secret("app/due", [("password", "a")])
+            .with_secret("app/fresh", [("password", "b")])
+            .with_secret("app/plain", [("password", "c")]);
+        backend
+            .update_metadata(
+                "app/due",
+                HashMap::from([
+                    ("rotation_enabled".to_string(), "true".to_string()),
+                    ("rotation_period_days".to_string(), "90".to_string()),
+                    ("owner".to_string(), "team, \"payments\"".to_string()),
+                    (
+                        "last_rotated".to_string(),
+                        "2000-01-01T00:00:00Z".to_string(),
+                    ),
+                ]),
+            )
+            .await?;
+        rotation::flag_for_rotation(&backend, "app/fresh", 6).await?;
+
+        let report = ComplianceReport::collect(&backend, "app", 6, 95.0).await?;
+        assert_eq!(report.secrets.len(), 2);
+        let due = &report.secrets[0];
+        assert_eq!(due.status, ComplianceStatus::Overdue);
+        assert_eq!(due.policy.as_deref(), Some("90 days"));
+        assert!(due.days_overdue.unwrap() > 0);
+        assert_eq!(report.secrets[1].days_overdue, Some(0));
+        assert_eq!(report.summary.within_policy, 1);
+        assert_eq!(report.summary.percent_within_policy, 50.0);
+        assert!(!report.summary.sla_met);
+
+        let csv = report.render(ComplianceFormat::Csv)?;
+        assert!(csv.contains("app/due,\"team, \"\"payments\"\"\",2000-01-01T00:00:00+00:00"));
+        let json: serde_json::Value =
+            serde_json::from_str(&report.render(ComplianceFormat::Json)?)?;
+        assert_eq!(json["summary"]["flagged"], 2);
+        assert_eq!(json["secrets"][0]["status"], "overdue");
+        let markdown = report.render(ComplianceFormat::Markdown)?;
+        assert!(
+            markdown.contains("50.0% of 2 flagged secret(s)"),
+            "{}",
+            markdown
+        );
+
+        backend.fail_always(Operation::ReadMetadata, "down");
+        let report = ComplianceReport::collect(&backend, "app", 6, 95.0).await?;
+        assert_eq!(report.summary.errors, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_report_meets_sla() {
+        let summary = ComplianceSummary::new(&[], 95.0);
+        assert_eq!(summary.percent_within_policy, 100.0);
+        assert!(summary.sla_met);
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            ComplianceFormat::from_path(Path::new("audit.CSV")),
+            ComplianceFormat::Csv
+        );
+        assert_eq!(
+            ComplianceFormat::from_path(Path::new("audit.json")),
+            ComplianceFormat::Json
+        );
+        assert_eq!(
+            ComplianceFormat::from_path(Path::new("audit.md")),
+            ComplianceFormat::Markdown
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Secrets assembled from keys of other secrets
+//!
+//! An application that wants one secret holding everything it needs (e.g., a
+//! JSON blob with its database password and an API key) can get a composite:
+//!
+//! ```toml
+//! [[composites]]
+//! path = "app/bundle"
+//! json_key = "config"
+//! [composites.keys]
+//! db_password = "app/db#password"
+//! api_key = "vendor/api#key"
+//! ```
+//!
+//! The composite is written again whenever one of its inputs rotates.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::backends::SecretBackend;
+use crate::redact;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompositeConfig {
+    /// Path the assembled secret is written to
+    pub path: String,
+    /// Keys of the assembled secret and where each value comes from, as
+    /// `secret/path#key`
+    pub keys: BTreeMap<String, String>,
+    /// Store every key as one JSON object under this key instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_key: Option<String>,
+}
+
+impl CompositeConfig {
+    /// Secret path and key an input reference points at
+    fn source(reference: &str) -> Result<(&str, &str)> {
+        match reference.rsplit_once('#') {
+            Some((path, key)) if !path.is_empty() && !key.is_empty() => Ok((path, key)),
+            _ => anyhow::bail!("Invalid input '{}' (expected secret/path#key)", reference),
+        }
+    }
+
+    /// Whether the composite is built from the secret at `path`
+    pub fn depends_on(&self, path: &str) -> bool {
+        self.keys
+            .values()
+            .any(|reference| Self::source(reference).is_ok_and(|(source, _)| source == path))
+    }
+
+    /// Read the inputs and assemble the composite's data
+    pub async fn render(&self, backend: &dyn SecretBackend) -> Result<HashMap<String, String>> {
+        let mut inputs: HashMap<&str, HashMap<String, String>> = HashMap::new();
+        let mut values = BTreeMap::new();
+        for (key, reference) in &self.keys {
+            let (path, source_key) = Self::source(reference)?;
+            if path == self.path {
+                anyhow::bail!("Composite {} cannot be built from itself", self.path);
+            }
+            if !inputs.contains_key(path) {
+                let secret = backend
+                    .read_secret(path)
+                    .await
+                    .with_context(|| format!("Failed to read input {}", path))?;
+                inputs.insert(path, secret.data);
+            }
+            let value = inputs[path]
+                .get(source_key)
+                .with_context(|| format!("Input {} has no '{}' key", path, source_key))?;
+            values.insert(key.clone(), value.clone());
+        }
+
+        Ok(match &self.json_key {
+            Some(json_key) => HashMap::from([(json_key.clone(), serde_json::to_string(&values)?)]),
+            None => values.into_iter().collect(),
+        })
+    }
+
+    /// Render the composite and write it to the backend
+    pub async fn write(&self, backend: &dyn SecretBackend) -> Result<()> {
+        let data = self.render(backend).await?;
+        for value in data.values() {
+            redact::register_secret(value);
+        }
+        backend
+            .write_secret(&self.path, data)
+            .await
+            .with_context(|| format!("Failed to write composite {}", self.path))
+    }
+}
+
+/// Composites to write again after the secret at `path` changed, in order
+///
+/// Composites may be inputs of other composites; each one follows every
+/// composite it is built from, whatever order they are configured in. Inputs
+/// that form a cycle are an error, since no order writes them correctly.
+pub fn dependents<'a>(
+    composites: &'a [CompositeConfig],
+    path: &str,
+) -> Result<Vec<&'a CompositeConfig>> {
+    // Every composite the change reaches, directly or through other composites
+    let mut affected: Vec<&CompositeConfig> = Vec::new();
+    let mut changed = vec![path];
+    while let Some(path) = changed.pop() {
+        for composite in composites {
+            if composite.depends_on(path) && !affected.iter().any(|c| c.path == composite.path) {
+                affected.push(composite);
+                changed.push(&composite.path);
+            }
+        }
+    }
+
+    // Write a composite once none of its affected inputs are left to write
+    let mut ordered: Vec<&CompositeConfig> = Vec::new();
+    while !affected.is_empty() {
+        let ready = affected.iter().position(|composite| {
+            !affected
+                .iter()
+                .any(|input| composite.depends_on(&input.path))
+        });
+        match ready {
+            Some(index) => ordered.push(affected.remove(index)),
+            None => {
+                let cycle: Vec<&str> = affected.iter().map(|c| c.path.as_str()).collect();
+                anyhow::bail!("Composites {} depend on each other", cycle.join(", "));
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::MemoryBackend;
+
+    fn composite(path: &str, keys: &[(&str, &str)], json_key: Option<&str>) -> CompositeConfig {
+        CompositeConfig {
+            path: path.to_string(),
+            keys: keys
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            json_key: json_key.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_render_and_write() -> Result<()> {
+        let backend = MemoryBackend::new()
+            .with_secret("app/db", [("password", "db-pass"), ("username", "app")])
+            .with_secret("vendor/api", [("key", "api-key")]);
+
+        let flat = composite(
+            "app/env",
+            &[
+                ("DB_PASSWORD", "app/db#password"),
+                ("API_KEY", "vendor/api#key"),
+            ],
+            None,
+        );
+        flat.write(&backend).await?;
+        let written = backend.secret("app/env").unwrap();
+        assert_eq!(written["DB_PASSWORD"], "db-pass");
+        assert_eq!(written["API_KEY"], "api-key");
+
+        let blob = composite("app/bundle", &[("db", "app/db#password")], Some("config"));
+        let data = blob.render(&backend).await?;
+        assert_eq!(data["config"], r#"{"db":"db-pass"}"#);
+
+        for broken in [
+            composite("app/x", &[("a", "app/db#missing")], None),
+            composite("app/x", &[("a", "app/nothing#key")], None),
+            composite("app/x", &[("a", "no-key-given")], None),
+            composite("app/x", &[("a", "app/x#a")], None),
+        ] {
+            assert!(broken.render(&backend).await.is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dependents_follow_chains() -> Result<()> {
+        let paths = |composites: &[CompositeConfig], path: &str| -> Result<Vec<String>> {
+            Ok(dependents(composites, path)?
+                .iter()
+                .map(|c| c.path.clone())
+                .collect())
+        };
+
+        // A diamond listed out of order: top is written after both sides,
+        // which follow base
+        let composites = vec![
+            composite(
+                "app/top",
+                &[("l", "app/left#v"), ("r", "app/right#v")],
+                None,
+            ),
+            composite("app/left", &[("v", "app/base#v")], None),
+            composite("app/right", &[("v", "app/base#v")], None),
+            composite("app/base", &[("v", "app/db#password")], None),
+            composite("app/other", &[("k", "vendor/api#key")], None),
+        ];
+        assert_eq!(
+            paths(&composites, "app/db")?,
+            ["app/base", "app/left", "app/right", "app/top"]
+        );
+        assert_eq!(paths(&composites, "app/left")?, ["app/top"]);
+        assert!(paths(&composites, "app/unrelated")?.is_empty());
+
+        // Cycles are rejected instead of written in an arbitrary order
+        let cycle = vec![
+            composite("app/a", &[("x", "app/db#password"), ("b", "app/b#b")], None),
+            composite("app/b", &[("b", "app/a#x")], None),
+        ];
+        let err = dependents(&cycle, "app/db").unwrap_err();
+        assert!(err.to_string().contains("app/a, app/b"));
+        let own = vec![composite("app/loop", &[("y", "app/loop#x")], None)];
+        assert!(dependents(&own, "app/loop").is_err());
+        Ok(())
+    }
+}
@@ -1,8 +1,27 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
+use crate::audit::AuditConfig;
+use crate::backends::VaultFlavor;
+use crate::composite::CompositeConfig;
+use crate::hooks::HooksConfig;
+use crate::http::HttpConfig;
+use crate::leader::LeaderConfig;
+use crate::listener::ListenerConfig;
+use crate::logging::LoggingConfig;
+use crate::metadata_store::MetadataStoreConfig;
+use crate::notify::NotificationsConfig;
+use crate::password_policy::PasswordPolicy;
+use crate::redact::SecretString;
+use crate::run_report::RunReportsConfig;
+use crate::server::ApiTokenConfig;
+use crate::structured_file::StructuredFormat;
+use crate::transit::TransitConfig;
+use std::path::PathBuf;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "default_backend")]
@@ -17,6 +36,14 @@ pub struct Config {
     #[serde(default)]
     pub file: Option<FileConfig>,
 
+    /// `pass`/gopass password store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pass: Option<PassConfig>,
+
+    /// KeePass (KDBX) database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepass: Option<KeePassConfig>,
+
     #[serde(default)]
     pub rotation: RotationConfig,
 
@@ -27,20 +54,379 @@ pub struct Config {
     /// Target configurations for password updates
     #[serde(default)]
     pub targets: Option<TargetsConfig>,
+
+    /// Vault PKI issuer for certificate secrets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pki: Option<PkiConfig>,
+
+    /// ACME issuer for certificate secrets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acme: Option<AcmeConfig>,
+
+    /// Vault database secrets engine managed by `asr db`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault_database: Option<VaultDatabaseConfig>,
+
+    /// Vault transit key for envelope encryption of file backend secrets
+    /// and backups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transit: Option<TransitConfig>,
+
+    /// Dedicated store for rotation metadata instead of the backend's own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_store: Option<MetadataStoreConfig>,
+
+    /// Path routing rules for using several backends at once, checked in order
+    /// (e.g., ["vault:prod/*", "aws:lambda/*"]); unmatched paths use `backend`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<String>,
+
+    /// Local environment update settings
+    #[serde(default)]
+    pub env: EnvConfig,
+
+    /// Actions run after a secret rotates (restarting containers, etc.)
+    #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+    pub hooks: HooksConfig,
+
+    /// Audit event sinks (syslog, CEF)
+    #[serde(default, skip_serializing_if = "AuditConfig::is_empty")]
+    pub audit: AuditConfig,
+
+    /// Where to send per-owner reports of overdue and failed rotations
+    #[serde(default, skip_serializing_if = "NotificationsConfig::is_empty")]
+    pub notifications: NotificationsConfig,
+
+    /// Outbound HTTP settings (proxy) for all clients
+    #[serde(default, skip_serializing_if = "HttpConfig::is_empty")]
+    pub http: HttpConfig,
+
+    /// Log file written alongside the console output
+    #[serde(default, skip_serializing_if = "LoggingConfig::is_empty")]
+    pub logging: LoggingConfig,
+
+    /// REST API settings for `asr serve`
+    #[serde(default)]
+    pub server: ServerConfig,
+
+    /// SQS queue consumed by `asr listen`
+    #[serde(default, skip_serializing_if = "ListenerConfig::is_empty")]
+    pub listener: ListenerConfig,
+
+    /// Leader election between replicas, so only one of them rotates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader: Option<LeaderConfig>,
+
+    /// Secrets assembled from keys of other secrets, written again whenever
+    /// one of their inputs rotates
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub composites: Vec<CompositeConfig>,
+
+    /// Where `auto` writes a JSON report of each run
+    #[serde(default, skip_serializing_if = "RunReportsConfig::is_empty")]
+    pub run_reports: RunReportsConfig,
+
+    /// Settings for registered backend and target plugins, keyed by plugin name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub plugins: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvConfig {
+    /// Shell config files to update instead of ~/.bashrc, ~/.zshrc, etc.
+    /// (e.g., ["~/.config/asr/env.sh"] for a single file sourced once)
+    #[serde(default)]
+    pub shell_files: Vec<String>,
+
+    /// Project-level .env files to update instead of shell config files
+    #[serde(default)]
+    pub dotenv_files: Vec<String>,
+
+    /// systemd `EnvironmentFile=` files to update instead of shell config files
+    #[serde(default)]
+    pub systemd_files: Vec<SystemdEnvFileConfig>,
+
+    /// Structured config files (JSON/YAML/TOML) holding values for specific variables
+    #[serde(default)]
+    pub config_files: Vec<StructuredFileConfig>,
+
+    /// Explicit secret path to variable name(s) mapping used by `auto --update-env`
+    /// (e.g., "myapp/database" = "DB_PASSWORD" or ["DB_PASSWORD", "PGPASSWORD"])
+    #[serde(default)]
+    pub mappings: HashMap<String, EnvVarNames>,
+}
+
+/// One or more environment variable names mapped to a secret
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EnvVarNames {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl EnvVarNames {
+    /// The mapped variable names as a list
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            EnvVarNames::One(name) => vec![name.clone()],
+            EnvVarNames::Many(names) => names.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    /// Address to listen on
+    #[serde(default = "default_server_bind")]
+    pub bind: String,
+
+    /// Bearer token clients must send in the `Authorization` header
+    #[serde(default)]
+    pub token: Option<SecretString>,
+
+    /// Tokens with a role and allowed secret paths, for teams sharing the server
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<ApiTokenConfig>,
+
+    /// Shared secret webhook senders sign `/webhook` bodies with, as an
+    /// alternative to the bearer token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_secret: Option<SecretString>,
+
+    /// Seconds to reuse secret metadata between scans (0 disables the cache)
+    ///
+    /// Writes made by the server itself are seen immediately; changes made
+    /// elsewhere, such as `asr flag` from a shell, once the cached entry expires.
+    #[serde(default)]
+    pub metadata_cache_seconds: u64,
+
+    /// Retries of a failed rotation before giving up on it (0 disables retries)
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: u32,
+
+    /// Seconds before the first retry; each further retry waits twice as long
+    #[serde(default = "default_retry_base_seconds")]
+    pub retry_base_seconds: u64,
+
+    /// Longest wait between retries, in seconds
+    #[serde(default = "default_retry_max_seconds")]
+    pub retry_max_seconds: u64,
+}
+
+fn default_server_bind() -> String {
+    "127.0.0.1:8080".to_string()
+}
+
+fn default_retry_attempts() -> u32 {
+    5
+}
+
+fn default_retry_base_seconds() -> u64 {
+    60
+}
+
+fn default_retry_max_seconds() -> u64 {
+    3600
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: default_server_bind(),
+            token: None,
+            tokens: Vec::new(),
+            webhook_secret: None,
+            metadata_cache_seconds: 0,
+            retry_attempts: default_retry_attempts(),
+            retry_base_seconds: default_retry_base_seconds(),
+            retry_max_seconds: default_retry_max_seconds(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructuredFileConfig {
+    /// Path to the config file (e.g., "/srv/app/appsettings.json")
+    pub path: String,
+
+    /// Dotted path or JSON pointer of the value to set (e.g., "database.password")
+    pub key_path: String,
+
+    /// Environment variable whose updates are written to this file
+    pub env_var: String,
+
+    /// File format: json, yaml, or toml (detected from the extension if not set)
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemdEnvFileConfig {
+    /// Path to the environment file (e.g., "/etc/myapp/env")
+    pub path: String,
+
+    /// Unit to restart after the file changes (e.g., "myapp.service")
+    #[serde(default)]
+    pub restart_unit: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VaultConfig {
     pub address: String,
-    pub token: String,
+    pub token: SecretString,
     #[serde(default = "default_mount")]
     pub mount: String,
+    /// Seconds to wait for a connection to Vault (default: 5)
+    #[serde(default = "default_vault_connect_timeout")]
+    pub connect_timeout_seconds: u64,
+    /// Seconds to wait for a whole Vault request (default: 30)
+    #[serde(default = "default_vault_request_timeout")]
+    pub request_timeout_seconds: u64,
+    /// Server the address points at: vault (default) or openbao
+    #[serde(default)]
+    pub flavor: VaultFlavor,
+}
+
+/// Vault PKI secrets engine used to issue certificates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PkiConfig {
+    /// Mount path of the PKI engine (default: "pki")
+    #[serde(default = "default_pki_mount")]
+    pub mount: String,
+    /// Role to issue from, unless a secret's `cert_role` metadata names another
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+fn default_pki_mount() -> String {
+    "pki".to_string()
+}
+
+/// Vault database secrets engine whose root credentials, static roles and
+/// leases `asr db` manages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultDatabaseConfig {
+    /// Mount path of the database engine (default: "database")
+    #[serde(default = "default_database_mount")]
+    pub mount: String,
+
+    /// Connections whose root credential `asr db rotate-root` rotates when
+    /// none are named on the command line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub connections: Vec<String>,
+
+    /// Static roles `asr db rotate-role` rotates when none are named
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub static_roles: Vec<String>,
+
+    /// Lease prefixes `asr db leases` checks (default: "<mount>/creds/")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lease_prefixes: Vec<String>,
+
+    /// Leases expiring within this many hours are renewed (default: 24)
+    #[serde(default = "default_renew_within_hours")]
+    pub renew_within_hours: u64,
+
+    /// How much to extend a lease by (e.g., "24h"); defaults to the role's TTL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renew_increment: Option<String>,
+}
+
+fn default_database_mount() -> String {
+    "database".to_string()
+}
+
+fn default_renew_within_hours() -> u64 {
+    24
+}
+
+/// ACME certificate authority (e.g., Let's Encrypt) used to issue certificates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcmeConfig {
+    /// Directory URL of the CA (default: Let's Encrypt production)
+    #[serde(default = "default_acme_directory")]
+    pub directory_url: String,
+
+    /// Contact addresses for the account (e.g., ["ops@example.com"])
+    #[serde(default)]
+    pub contact: Vec<String>,
+
+    /// Accept the CA's terms of service when creating the account
+    #[serde(default)]
+    pub agree_tos: bool,
+
+    /// PEM file holding the account key, created if missing
+    /// Default: ~/.asr/acme/account.pem
+    #[serde(default = "default_acme_account_key")]
+    pub account_key: PathBuf,
+
+    /// Challenge used to prove control of each domain
+    #[serde(default)]
+    pub challenge: AcmeChallenge,
+
+    /// Directory served at http://<domain>/ (http-01)
+    #[serde(default)]
+    pub webroot: Option<PathBuf>,
+
+    /// Command run as `<hook> present|cleanup <domain> <record name> <value>`
+    /// to create and remove the TXT record (dns-01)
+    #[serde(default)]
+    pub dns_hook: Option<String>,
+
+    /// Seconds to wait for the TXT record to propagate (default: 30)
+    #[serde(default = "default_dns_propagation")]
+    pub dns_propagation_seconds: u64,
+}
+
+/// ACME challenge type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AcmeChallenge {
+    #[default]
+    #[serde(rename = "http-01")]
+    Http01,
+    #[serde(rename = "dns-01")]
+    Dns01,
+}
+
+fn default_acme_directory() -> String {
+    "https://acme-v02.api.letsencrypt.org/directory".to_string()
+}
+
+fn default_acme_account_key() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".to_string()))
+        .join(".asr/acme/account.pem")
+}
+
+fn default_dns_propagation() -> u64 {
+    30
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AwsConfig {
     #[serde(default = "default_aws_region")]
     pub region: String,
+    /// Regions holding copies of the secrets, read from the first and written
+    /// to all (e.g., ["us-east-1", "us-west-2"]); overrides `region`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<String>,
+    /// Key under which plain-string and binary secrets are exposed
+    #[serde(default = "default_aws_value_key")]
+    pub value_key: String,
+    /// Seconds each write waits for replica regions to be in sync before
+    /// failing (0 = only report their state)
+    #[serde(default)]
+    pub replication_wait_seconds: u64,
+}
+
+impl AwsConfig {
+    /// Regions to use, primary first
+    pub fn all_regions(&self) -> Vec<&str> {
+        if self.regions.is_empty() {
+            vec![self.region.as_str()]
+        } else {
+            self.regions.iter().map(String::as_str).collect()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,6 +435,52 @@ pub struct FileConfig {
     /// Default: ~/.asr/secrets
     #[serde(default = "default_file_dir")]
     pub directory: String,
+    /// Previous values kept per secret for rollback (0 keeps none)
+    #[serde(default = "default_file_versions")]
+    pub versions: usize,
+    /// Envelope-encrypt secret files with the `[transit]` key
+    #[serde(default)]
+    pub transit: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PassConfig {
+    /// Password store directory
+    /// Default: $PASSWORD_STORE_DIR or ~/.password-store
+    #[serde(default = "default_pass_dir")]
+    pub directory: String,
+    /// Key under which the first line (the password) of an entry is exposed
+    #[serde(default = "default_pass_value_key")]
+    pub value_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeePassConfig {
+    /// Path to the `.kdbx` database file
+    pub path: String,
+    /// Master password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<SecretString>,
+    /// Key file, used together with or instead of the password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyfile: Option<String>,
+}
+
+fn default_pass_dir() -> String {
+    std::env::var("PASSWORD_STORE_DIR").unwrap_or_else(|_| {
+        format!(
+            "{}/.password-store",
+            std::env::var("HOME").unwrap_or_else(|_| ".".to_string())
+        )
+    })
+}
+
+fn default_pass_value_key() -> String {
+    "password".to_string()
+}
+
+fn default_file_versions() -> usize {
+    crate::backends::FileBackend::DEFAULT_VERSIONS
 }
 
 fn default_file_dir() -> String {
@@ -67,6 +499,38 @@ pub struct TargetsConfig {
     /// API target configuration
     #[serde(default)]
     pub api: Option<ApiTargetConfig>,
+
+    /// WireGuard target whose peer keys are rotated
+    #[serde(default)]
+    pub wireguard: Option<WireGuardTargetConfig>,
+
+    /// SMTP relay account whose password is changed through an API and
+    /// verified by logging in
+    #[serde(default)]
+    pub smtp: Option<SmtpTargetConfig>,
+
+    /// Name of a registered target plugin to use instead of the built-in targets
+    #[serde(default)]
+    pub plugin: Option<String>,
+
+    /// Staging target that receives new credentials before production
+    #[serde(default)]
+    pub canary: Option<CanaryTargetConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryTargetConfig {
+    /// PostgreSQL canary (e.g., a staging database)
+    #[serde(default)]
+    pub postgres: Option<PostgresTargetConfig>,
+
+    /// API canary
+    #[serde(default)]
+    pub api: Option<ApiTargetConfig>,
+
+    /// User whose password is set on the canary (defaults to the production user)
+    #[serde(default)]
+    pub username: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -81,10 +545,25 @@ pub struct PostgresTargetConfig {
     pub password_path: Option<String>,
     /// Direct password (not recommended, use password_path instead)
     #[serde(default)]
-    pub password: Option<String>,
+    pub password: Option<SecretString>,
     /// SSL mode: disable, allow, prefer, require, verify-ca, verify-full
     #[serde(default = "default_ssl_mode")]
     pub ssl_mode: String,
+    /// Rotate paired `<user>_a`/`<user>_b` login roles that inherit from `<user>`
+    /// instead of changing the password of the live role
+    #[serde(default)]
+    pub blue_green: bool,
+    /// Hours the previous blue/green login keeps working after the switch
+    /// before its password expires
+    #[serde(default = "default_previous_login_hours")]
+    pub previous_login_hours: u32,
+    /// Send a SCRAM-SHA-256 verifier computed locally instead of the plaintext
+    /// password, so it never reaches the server or its logs
+    #[serde(default)]
+    pub scram_prehash: bool,
+    /// Rules new passwords must satisfy before they are sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_policy: Option<PasswordPolicy>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,9 +591,27 @@ pub struct ApiTargetConfig {
     #[serde(default)]
     pub additional_fields: Option<std::collections::HashMap<String, String>>,
 
+    /// Encoding of the request body: json (default), form, or raw
+    #[serde(default)]
+    pub body_format: ApiBodyFormat,
+
+    /// Request body with `{{username}}` and `{{password}}` placeholders, used
+    /// instead of the field options above (required for raw bodies)
+    #[serde(default)]
+    pub body_template: Option<String>,
+
+    /// Content-Type sent with raw bodies (default: "text/plain")
+    #[serde(default)]
+    pub content_type: Option<String>,
+
     /// Authorization header value (e.g., "Bearer token123")
     #[serde(default)]
-    pub auth_header: Option<String>,
+    pub auth_header: Option<SecretString>,
+
+    /// Obtain a bearer token with the OAuth2 client-credentials grant instead
+    /// of sending a static `auth_header`
+    #[serde(default)]
+    pub oauth2: Option<OAuth2Config>,
 
     /// Additional HTTP headers
     #[serde(default)]
@@ -123,6 +620,156 @@ pub struct ApiTargetConfig {
     /// Request timeout in seconds (default: 30)
     #[serde(default = "default_api_timeout")]
     pub timeout_seconds: u64,
+
+    /// PEM file with CA certificates to trust in addition to the system roots
+    #[serde(default)]
+    pub ca_cert: Option<PathBuf>,
+
+    /// PEM client certificate for mutual TLS (requires `client_key`)
+    #[serde(default)]
+    pub client_cert: Option<PathBuf>,
+
+    /// PEM PKCS#8 private key for `client_cert`
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
+
+    /// Retries after a timeout, connection error, 429, or 5xx response (default: 3)
+    #[serde(default = "default_api_max_retries")]
+    pub max_retries: u32,
+
+    /// Delay before the first retry, doubled on each attempt (default: 500)
+    /// A `Retry-After` header from the API takes precedence
+    #[serde(default = "default_api_retry_delay")]
+    pub retry_delay_ms: u64,
+
+    /// Rules new passwords must satisfy before they are sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_policy: Option<PasswordPolicy>,
+}
+
+/// How the API target encodes its request body
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiBodyFormat {
+    /// JSON object (placeholders in templates are JSON-escaped)
+    #[default]
+    Json,
+    /// `application/x-www-form-urlencoded` (placeholders are percent-encoded)
+    Form,
+    /// Template sent as-is
+    Raw,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    /// Token endpoint (e.g., "https://auth.example.com/oauth2/token")
+    pub token_url: String,
+
+    /// Client ID, sent with the secret as HTTP basic credentials
+    pub client_id: String,
+
+    pub client_secret: SecretString,
+
+    /// Scopes to request
+    #[serde(default)]
+    pub scopes: Vec<String>,
+
+    /// Audience parameter required by some providers (e.g., Auth0)
+    #[serde(default)]
+    pub audience: Option<String>,
+}
+
+/// WireGuard interface whose peer keys are rotated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireGuardTargetConfig {
+    /// Interface on the VPN server (e.g., "wg0")
+    pub interface: String,
+
+    /// Run `wg` on this host over SSH (e.g., "root@vpn.example.com")
+    /// instead of locally
+    #[serde(default)]
+    pub ssh: Option<String>,
+
+    /// Seconds to wait for a peer to handshake with its new key (default: 300)
+    #[serde(default = "default_wireguard_handshake_timeout")]
+    pub handshake_timeout_seconds: u64,
+
+    /// Run `wg-quick save` after a rotation so it survives a restart (default: true)
+    #[serde(default = "default_true")]
+    pub save_config: bool,
+
+    /// Peers by name (the secret's `target_username`)
+    pub peers: std::collections::BTreeMap<String, WireGuardPeerConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireGuardPeerConfig {
+    /// Tunnel addresses routed to the peer (e.g., ["10.8.0.2/32"])
+    pub allowed_ips: Vec<String>,
+}
+
+/// SMTP relay whose account passwords are rotated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpTargetConfig {
+    /// SMTP server that the account logs in to (e.g., "smtp.example.com")
+    pub host: String,
+
+    /// SMTP port (default: 587)
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+
+    /// Transport security: starttls (default), implicit (e.g., port 465), or none
+    #[serde(default)]
+    pub tls: SmtpTls,
+
+    /// Seconds allowed for the whole verification session (default: 30)
+    #[serde(default = "default_api_timeout")]
+    pub timeout_seconds: u64,
+
+    /// Provider API or admin endpoint that changes the account password
+    pub update: ApiTargetConfig,
+
+    /// Also send a message with the new credentials to check that the relay
+    /// accepts mail, not just the login
+    #[serde(default)]
+    pub test_message: Option<SmtpTestMessage>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection with STARTTLS
+    #[default]
+    StartTls,
+    /// TLS from the first byte
+    Implicit,
+    /// No TLS (only for local relays and testing)
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpTestMessage {
+    pub from: String,
+    pub to: String,
+    /// Subject line (default: "asr credential check")
+    #[serde(default = "default_smtp_subject")]
+    pub subject: String,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_smtp_subject() -> String {
+    "asr credential check".to_string()
+}
+
+fn default_wireguard_handshake_timeout() -> u64 {
+    300
+}
+
+fn default_true() -> bool {
+    true
 }
 
 fn default_api_method() -> String {
@@ -137,6 +784,14 @@ fn default_api_timeout() -> u64 {
     30
 }
 
+fn default_api_max_retries() -> u32 {
+    3
+}
+
+fn default_api_retry_delay() -> u64 {
+    500
+}
+
 fn default_db_port() -> u16 {
     5432
 }
@@ -145,6 +800,10 @@ fn default_ssl_mode() -> String {
     "prefer".to_string()
 }
 
+fn default_previous_login_hours() -> u32 {
+    24
+}
+
 fn default_backend() -> String {
     "vault".to_string()
 }
@@ -153,16 +812,60 @@ fn default_mount() -> String {
     "secret".to_string()
 }
 
+fn default_vault_connect_timeout() -> u64 {
+    5
+}
+
+fn default_vault_request_timeout() -> u64 {
+    30
+}
+
 fn default_aws_region() -> String {
     "us-east-1".to_string()
 }
 
+fn default_aws_value_key() -> String {
+    "value".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RotationConfig {
     #[serde(default = "default_rotation_period")]
     pub period_months: u32,
     #[serde(default = "default_secret_length")]
     pub secret_length: usize,
+    /// Apply new target credentials to `[targets.canary]` before production
+    /// (secrets can override this with `canary` metadata)
+    #[serde(default)]
+    pub canary: bool,
+    /// Secrets that must rotate before a given secret in the same run, keyed
+    /// by path (added to any `rotate_after` metadata)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rotate_after: HashMap<String, Vec<String>>,
+    /// Templates of keys recomputed from the rest of a secret on every
+    /// rotation (e.g., a connection `url`), keyed by path and then by key;
+    /// `derive_<key>` metadata takes precedence
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub derived_keys: HashMap<String, HashMap<String, String>>,
+    /// Most secrets to rotate in one `auto` run; the rest wait for the next
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_per_run: Option<usize>,
+    /// Spread the first due date of newly flagged secrets by up to this many
+    /// days either way, so a batch flagged together does not fall due together
+    #[serde(default)]
+    pub jitter_days: u32,
+    /// Share of flagged secrets that must be within their rotation policy
+    /// for `report compliance` to meet the SLA, in percent
+    #[serde(default = "default_sla_percent")]
+    pub sla_percent: f64,
+    /// Consecutive failed rotations against the target after which the rest
+    /// are refused until the cooldown has passed (0 disables the breaker)
+    #[serde(default = "default_circuit_breaker_threshold")]
+    pub circuit_breaker_threshold: u32,
+    /// Minutes before a rotation is attempted again against a target whose
+    /// breaker opened
+    #[serde(default = "default_circuit_breaker_cooldown")]
+    pub circuit_breaker_cooldown_minutes: u64,
 }
 
 fn default_rotation_period() -> u32 {
@@ -173,22 +876,148 @@ fn default_secret_length() -> usize {
     32
 }
 
+fn default_sla_percent() -> f64 {
+    95.0
+}
+
+fn default_circuit_breaker_threshold() -> u32 {
+    3
+}
+
+fn default_circuit_breaker_cooldown() -> u64 {
+    30
+}
+
 impl Default for RotationConfig {
     fn default() -> Self {
         Self {
             period_months: default_rotation_period(),
             secret_length: default_secret_length(),
+            canary: false,
+            rotate_after: HashMap::new(),
+            derived_keys: HashMap::new(),
+            max_per_run: None,
+            jitter_days: 0,
+            sla_percent: default_sla_percent(),
+            circuit_breaker_threshold: default_circuit_breaker_threshold(),
+            circuit_breaker_cooldown_minutes: default_circuit_breaker_cooldown(),
+        }
+    }
+}
+
+/// Top-level key holding named configuration profiles
+const PROFILES_KEY: &str = "profiles";
+
+/// Merge the named profile over the top-level settings of a config document
+fn apply_profile(document: &mut serde_json::Value, name: &str) -> Result<()> {
+    let root = document
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("Config file must contain a table at the top level"))?;
+
+    let mut profiles = match root.remove(PROFILES_KEY) {
+        Some(serde_json::Value::Object(profiles)) => profiles,
+        Some(_) => anyhow::bail!("'{}' must be a table of named profiles", PROFILES_KEY),
+        None => serde_json::Map::new(),
+    };
+
+    let overlay = profiles.remove(name).ok_or_else(|| {
+        let mut available: Vec<&String> = profiles.keys().collect();
+        available.sort();
+        anyhow::anyhow!(
+            "Profile '{}' not found in config (available: {})",
+            name,
+            if available.is_empty() {
+                "none".to_string()
+            } else {
+                available
+                    .iter()
+                    .map(|s| s.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        )
+    })?;
+
+    merge_values(document, overlay);
+    Ok(())
+}
+
+/// Recursively merge `overlay` into `base`; non-table values replace existing ones
+fn merge_values(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge_values(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
         }
+        (base, overlay) => *base = overlay,
     }
 }
 
 impl Config {
-    /// Load configuration from a TOML file
+    /// Load configuration from a file, detecting TOML, YAML, or JSON from the extension
+    ///
+    /// Files without a recognized extension are parsed as TOML.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::load(path, None, None)
+    }
+
+    /// Load configuration from a file, optionally forcing the format and selecting a profile
+    ///
+    /// A profile is a table under `[profiles.<name>]` whose settings are merged
+    /// over the top-level ones; nested tables are merged key by key.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        format: Option<StructuredFormat>,
+        profile: Option<&str>,
+    ) -> Result<Self> {
+        let format = format.unwrap_or_else(|| Self::detect_format(path.as_ref()));
         let contents = fs::read_to_string(path.as_ref())
             .with_context(|| format!("Failed to read config file: {:?}", path.as_ref()))?;
 
-        toml::from_str(&contents).context("Failed to parse config file")
+        Self::parse(&contents, format, profile).context("Failed to parse config file")
+    }
+
+    /// Parse configuration from a string in the given format
+    pub fn parse(contents: &str, format: StructuredFormat, profile: Option<&str>) -> Result<Self> {
+        let Some(profile) = profile else {
+            // Deserialize directly to keep line numbers in error messages
+            return match format {
+                StructuredFormat::Toml => toml::from_str(contents).map_err(anyhow::Error::from),
+                StructuredFormat::Yaml => {
+                    serde_yaml::from_str(contents).map_err(anyhow::Error::from)
+                }
+                StructuredFormat::Json => {
+                    serde_json::from_str(contents).map_err(anyhow::Error::from)
+                }
+            };
+        };
+
+        let mut document: serde_json::Value = match format {
+            StructuredFormat::Toml => toml::from_str(contents)?,
+            StructuredFormat::Yaml => serde_yaml::from_str(contents)?,
+            StructuredFormat::Json => serde_json::from_str(contents)?,
+        };
+        apply_profile(&mut document, profile)?;
+
+        serde_json::from_value(document)
+            .with_context(|| format!("Invalid configuration for profile '{}'", profile))
+    }
+
+    /// Settings of the `[plugins.<name>]` section, if present
+    pub fn plugin_settings<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        self.plugins
+            .get(name)
+            .map(|value| {
+                serde_json::from_value(value.clone())
+                    .with_context(|| format!("Invalid settings for plugin '{}'", name))
+            })
+            .transpose()
+    }
+
+    /// Config file format implied by a path's extension, defaulting to TOML
+    pub fn detect_format(path: &Path) -> StructuredFormat {
+        StructuredFormat::from_path(path).unwrap_or(StructuredFormat::Toml)
     }
 
     /// Load configuration from environment variables
@@ -198,12 +1027,28 @@ impl Config {
             .to_lowercase();
 
         let vault = if backend == "vault" {
+            // OpenBao's CLI reads BAO_ADDR/BAO_TOKEN instead
+            let openbao =
+                std::env::var_os("VAULT_ADDR").is_none() && std::env::var_os("BAO_ADDR").is_some();
+            let (addr_var, token_var, flavor) = if openbao {
+                ("BAO_ADDR", "BAO_TOKEN", VaultFlavor::OpenBao)
+            } else {
+                ("VAULT_ADDR", "VAULT_TOKEN", VaultFlavor::Vault)
+            };
             Some(VaultConfig {
-                address: std::env::var("VAULT_ADDR")
-                    .context("VAULT_ADDR environment variable not set")?,
-                token: std::env::var("VAULT_TOKEN")
-                    .context("VAULT_TOKEN environment variable not set")?,
+                address: std::env::var(addr_var)
+                    .with_context(|| format!("{} environment variable not set", addr_var))?,
+                token: std::env::var(token_var)
+                    .with_context(|| format!("{} environment variable not set", token_var))?
+                    .into(),
                 mount: std::env::var("VAULT_MOUNT").unwrap_or_else(|_| "secret".to_string()),
+                connect_timeout_seconds: default_vault_connect_timeout(),
+                // Same variable as the vault CLI
+                request_timeout_seconds: std::env::var("VAULT_CLIENT_TIMEOUT")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(default_vault_request_timeout),
+                flavor,
             })
         } else {
             None
@@ -212,6 +1057,22 @@ impl Config {
         let aws = if backend == "aws" {
             Some(AwsConfig {
                 region: std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+                regions: std::env::var("ASR_AWS_REGIONS")
+                    .map(|regions| {
+                        regions
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|r| !r.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                value_key: std::env::var("ASR_AWS_VALUE_KEY")
+                    .unwrap_or_else(|_| default_aws_value_key()),
+                replication_wait_seconds: std::env::var("ASR_AWS_REPLICATION_WAIT")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0),
             })
         } else {
             None
@@ -220,6 +1081,31 @@ impl Config {
         let file = if backend == "file" {
             Some(FileConfig {
                 directory: std::env::var("ASR_FILE_DIR").unwrap_or_else(|_| default_file_dir()),
+                versions: default_file_versions(),
+                transit: false,
+            })
+        } else {
+            None
+        };
+
+        let pass = if backend == "pass" {
+            Some(PassConfig {
+                directory: default_pass_dir(),
+                value_key: std::env::var("ASR_PASS_VALUE_KEY")
+                    .unwrap_or_else(|_| default_pass_value_key()),
+            })
+        } else {
+            None
+        };
+
+        let keepass = if backend == "keepass" {
+            Some(KeePassConfig {
+                path: std::env::var("ASR_KEEPASS_PATH")
+                    .context("ASR_KEEPASS_PATH environment variable not set")?,
+                password: std::env::var("ASR_KEEPASS_PASSWORD")
+                    .ok()
+                    .map(SecretString::from),
+                keyfile: std::env::var("ASR_KEEPASS_KEYFILE").ok(),
             })
         } else {
             None
@@ -234,6 +1120,14 @@ impl Config {
                 .ok()
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(32),
+            canary: false,
+            rotate_after: HashMap::new(),
+            derived_keys: HashMap::new(),
+            max_per_run: None,
+            jitter_days: 0,
+            sla_percent: default_sla_percent(),
+            circuit_breaker_threshold: default_circuit_breaker_threshold(),
+            circuit_breaker_cooldown_minutes: default_circuit_breaker_cooldown(),
         };
 
         let database = if std::env::var("DB_HOST").is_ok() {
@@ -247,8 +1141,12 @@ impl Config {
                 username: std::env::var("DB_USERNAME")
                     .context("DB_USERNAME environment variable not set")?,
                 password_path: std::env::var("DB_PASSWORD_PATH").ok(),
-                password: std::env::var("DB_PASSWORD").ok(),
+                password: std::env::var("DB_PASSWORD").ok().map(SecretString::from),
                 ssl_mode: std::env::var("DB_SSL_MODE").unwrap_or_else(|_| "prefer".to_string()),
+                blue_green: false,
+                previous_login_hours: default_previous_login_hours(),
+                scram_prehash: false,
+                password_policy: None,
             })
         } else {
             None
@@ -259,9 +1157,48 @@ impl Config {
             vault,
             aws,
             file,
+            pass,
+            keepass,
             rotation,
             database,
             targets: None,
+            pki: None,
+            acme: None,
+            vault_database: None,
+            transit: None,
+            routes: Vec::new(),
+            plugins: HashMap::new(),
+            hooks: HooksConfig::default(),
+            http: HttpConfig {
+                proxy: std::env::var("ASR_HTTP_PROXY").ok(),
+                no_proxy: std::env::var("ASR_NO_PROXY")
+                    .map(|hosts| {
+                        hosts
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|h| !h.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            },
+            logging: LoggingConfig {
+                file: std::env::var("ASR_LOG_FILE").ok(),
+                level: std::env::var("ASR_LOG_LEVEL").unwrap_or_else(|_| "info".to_string()),
+                ..LoggingConfig::default()
+            },
+            server: ServerConfig::default(),
+            listener: ListenerConfig {
+                queue_url: std::env::var("ASR_QUEUE_URL").ok(),
+                region: None,
+            },
+            leader: None,
+            metadata_store: None,
+            composites: Vec::new(),
+            run_reports: RunReportsConfig::default(),
+            audit: AuditConfig::default(),
+            notifications: NotificationsConfig::default(),
+            env: EnvConfig::default(),
         })
     }
 
@@ -271,23 +1208,57 @@ impl Config {
             backend: "vault".to_string(),
             vault: Some(VaultConfig {
                 address: "http://127.0.0.1:8200".to_string(),
-                token: "your-vault-token-here".to_string(),
+                token: "your-vault-token-here".into(),
                 mount: "secret".to_string(),
+                connect_timeout_seconds: default_vault_connect_timeout(),
+                request_timeout_seconds: default_vault_request_timeout(),
+                flavor: VaultFlavor::Vault,
             }),
             aws: Some(AwsConfig {
                 region: "us-east-1".to_string(),
+                regions: Vec::new(),
+                value_key: default_aws_value_key(),
+                replication_wait_seconds: 0,
             }),
             file: Some(FileConfig {
                 directory: default_file_dir(),
+                versions: default_file_versions(),
+                transit: false,
             }),
+            pass: None,
+            keepass: None,
             rotation: RotationConfig::default(),
             database: None,
             targets: None,
+            pki: None,
+            acme: None,
+            vault_database: None,
+            transit: None,
+            routes: Vec::new(),
+            plugins: HashMap::new(),
+            hooks: HooksConfig::default(),
+            http: HttpConfig::default(),
+            logging: LoggingConfig::default(),
+            server: ServerConfig::default(),
+            listener: ListenerConfig::default(),
+            leader: None,
+            metadata_store: None,
+            composites: Vec::new(),
+            run_reports: RunReportsConfig::default(),
+            audit: AuditConfig::default(),
+            notifications: NotificationsConfig::default(),
+            env: EnvConfig::default(),
         };
 
-        let toml_string =
-            toml::to_string_pretty(&sample).context("Failed to serialize sample config")?;
-        fs::write(path.as_ref(), toml_string)
+        let contents = match Self::detect_format(path.as_ref()) {
+            StructuredFormat::Toml => toml::to_string_pretty(&sample).map_err(anyhow::Error::from),
+            StructuredFormat::Yaml => serde_yaml::to_string(&sample).map_err(anyhow::Error::from),
+            StructuredFormat::Json => serde_json::to_string_pretty(&sample)
+                .map(|json| json + "\n")
+                .map_err(anyhow::Error::from),
+        }
+        .context("Failed to serialize sample config")?;
+        fs::write(path.as_ref(), contents)
             .with_context(|| format!("Failed to write sample config to {:?}", path.as_ref()))?;
 
         Ok(())
@@ -350,6 +1321,51 @@ region = "us-west-2"
         let config = Config::from_file(&config_path).unwrap();
         assert_eq!(config.backend, "aws");
         assert_eq!(config.aws.as_ref().unwrap().region, "us-west-2");
+        assert_eq!(
+            config.aws.as_ref().unwrap().all_regions(),
+            vec!["us-west-2"]
+        );
+    }
+
+    #[test]
+    fn test_config_aws_regions() {
+        let config = Config::parse(
+            r#"
+backend = "aws"
+[aws]
+regions = ["us-east-1", "us-west-2"]
+"#,
+            StructuredFormat::Toml,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            config.aws.as_ref().unwrap().all_regions(),
+            vec!["us-east-1", "us-west-2"]
+        );
+    }
+
+    #[test]
+    fn test_config_logging() {
+        let config = Config::parse(
+            r#"
+backend = "file"
+[logging]
+format = "json"
+file = "/var/log/asr/asr.log"
+level = "debug"
+max_size_mb = 50
+"#,
+            StructuredFormat::Toml,
+            None,
+        )
+        .unwrap();
+        assert_eq!(config.logging.file.as_deref(), Some("/var/log/asr/asr.log"));
+        assert_eq!(config.logging.level, "debug");
+        assert_eq!(config.logging.max_size_mb, 50);
+        assert_eq!(config.logging.rotation, crate::logging::LogRotation::Daily);
+        assert_eq!(config.logging.max_files, 7);
+        assert_eq!(config.logging.format, crate::logging::LogFormat::Json);
     }
 
     #[test]
@@ -369,6 +1385,48 @@ directory = "/tmp/test-secrets"
         assert_eq!(config.file.as_ref().unwrap().directory, "/tmp/test-secrets");
     }
 
+    #[test]
+    fn test_config_from_file_with_pass_backend() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let config_content = r#"
+backend = "pass"
+[pass]
+directory = "/tmp/password-store"
+"#;
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.backend, "pass");
+        let pass = config.pass.as_ref().unwrap();
+        assert_eq!(pass.directory, "/tmp/password-store");
+        assert_eq!(pass.value_key, "password");
+    }
+
+    #[test]
+    fn test_config_from_file_with_keepass_backend() {
+        let config = Config::parse(
+            r#"
+backend = "keepass"
+[keepass]
+path = "~/team.kdbx"
+password = "hunter2"
+"#,
+            StructuredFormat::Toml,
+            None,
+        )
+        .unwrap();
+        assert_eq!(config.backend, "keepass");
+        let keepass = config.keepass.as_ref().unwrap();
+        assert_eq!(keepass.path, "~/team.kdbx");
+        assert_eq!(
+            keepass.password.as_ref().unwrap().expose_secret(),
+            "hunter2"
+        );
+        assert!(keepass.keyfile.is_none());
+    }
+
     #[test]
     fn test_config_from_file_with_targets() {
         let temp_dir = TempDir::new().unwrap();
@@ -431,7 +1489,10 @@ auth_header = "Bearer token123"
         assert_eq!(api.password_field, "new_password");
         assert_eq!(api.username_field.as_ref().unwrap(), "user");
         assert_eq!(api.timeout_seconds, 60);
-        assert_eq!(api.auth_header.as_ref().unwrap(), "Bearer token123");
+        assert_eq!(
+            api.auth_header.as_ref().unwrap().expose_secret(),
+            "Bearer token123"
+        );
     }
 
     #[test]
@@ -506,4 +1567,200 @@ endpoint = "/password"
         assert_eq!(api.password_field, "password"); // default password_field
         assert_eq!(api.timeout_seconds, 30); // default timeout
     }
+
+    #[test]
+    fn test_env_config_dotenv_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let config_content = r#"
+[env]
+dotenv_files = ["/srv/app/.env", "~/project/.env"]
+"#;
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(
+            config.env.dotenv_files,
+            vec!["/srv/app/.env", "~/project/.env"]
+        );
+        assert!(config.env.systemd_files.is_empty());
+        assert!(config.env.shell_files.is_empty());
+    }
+
+    #[test]
+    fn test_env_config_systemd_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let config_content = r#"
+[[env.systemd_files]]
+path = "/etc/myapp/env"
+restart_unit = "myapp.service"
+
+[[env.systemd_files]]
+path = "/etc/worker/env"
+"#;
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::from_file(&config_path).unwrap();
+        let files = &config.env.systemd_files;
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "/etc/myapp/env");
+        assert_eq!(files[0].restart_unit.as_deref(), Some("myapp.service"));
+        assert!(files[1].restart_unit.is_none());
+    }
+
+    #[test]
+    fn test_env_config_structured_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let config_content = r#"
+[[env.config_files]]
+path = "/srv/app/config.yaml"
+key_path = "database.password"
+env_var = "DB_PASSWORD"
+"#;
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::from_file(&config_path).unwrap();
+        let file = &config.env.config_files[0];
+        assert_eq!(file.key_path, "database.password");
+        assert_eq!(file.env_var, "DB_PASSWORD");
+        assert!(file.format.is_none());
+    }
+
+    #[test]
+    fn test_env_config_mappings() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let config_content = r#"
+[env.mappings]
+"myapp/database" = "DB_PASSWORD"
+"myapp/api" = ["API_KEY", "LEGACY_API_KEY"]
+"#;
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::from_file(&config_path).unwrap();
+        let mappings = &config.env.mappings;
+        assert_eq!(mappings["myapp/database"].names(), vec!["DB_PASSWORD"]);
+        assert_eq!(
+            mappings["myapp/api"].names(),
+            vec!["API_KEY", "LEGACY_API_KEY"]
+        );
+    }
+
+    #[test]
+    fn test_config_from_yaml_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+
+        let config_content = r#"
+backend: vault
+vault:
+  address: http://127.0.0.1:8200
+  token: test-token
+rotation:
+  period_months: 3
+env:
+  mappings:
+    myapp/database: DB_PASSWORD
+"#;
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::from_file(&config_path).unwrap();
+        let vault = config.vault.as_ref().unwrap();
+        assert_eq!(vault.token.expose_secret(), "test-token");
+        assert_eq!(vault.mount, "secret");
+        assert_eq!(config.rotation.period_months, 3);
+        assert_eq!(config.rotation.secret_length, 32);
+        assert_eq!(
+            config.env.mappings["myapp/database"].names(),
+            vec!["DB_PASSWORD"]
+        );
+    }
+
+    #[test]
+    fn test_config_from_json_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let config_content = r#"{"backend": "file", "file": {"directory": "/tmp/secrets"}}"#;
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.backend, "file");
+        assert_eq!(config.file.as_ref().unwrap().directory, "/tmp/secrets");
+
+        // An explicit format wins over the extension
+        let renamed = temp_dir.path().join("config.conf");
+        fs::write(&renamed, config_content).unwrap();
+        assert!(Config::from_file(&renamed).is_err());
+        assert!(Config::load(&renamed, Some(StructuredFormat::Json), None).is_ok());
+    }
+
+    #[test]
+    fn test_config_create_sample_yaml() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("sample.yml");
+
+        Config::create_sample(&config_path).unwrap();
+
+        let contents = fs::read_to_string(&config_path).unwrap();
+        assert!(contents.starts_with("backend: vault"));
+        let config = Config::from_file(&config_path).unwrap();
+        assert!(config.vault.is_some());
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let config_content = r#"
+backend = "vault"
+
+[vault]
+address = "https://vault.staging:8200"
+token = "staging-token"
+mount = "kv"
+
+[rotation]
+period_months = 6
+secret_length = 24
+
+[profiles.prod]
+backend = "aws"
+
+[profiles.prod.aws]
+region = "eu-west-1"
+
+[profiles.prod.rotation]
+period_months = 1
+
+[profiles.staging.vault]
+token = "other-token"
+"#;
+        fs::write(&config_path, config_content).unwrap();
+
+        let prod = Config::load(&config_path, None, Some("prod")).unwrap();
+        assert_eq!(prod.backend, "aws");
+        assert_eq!(prod.aws.as_ref().unwrap().region, "eu-west-1");
+        assert_eq!(prod.rotation.period_months, 1);
+        assert_eq!(prod.rotation.secret_length, 24); // inherited
+
+        let staging = Config::load(&config_path, None, Some("staging")).unwrap();
+        let vault = staging.vault.as_ref().unwrap();
+        assert_eq!(vault.token.expose_secret(), "other-token");
+        assert_eq!(vault.address, "https://vault.staging:8200");
+        assert_eq!(vault.mount, "kv");
+
+        let base = Config::from_file(&config_path).unwrap();
+        assert_eq!(base.backend, "vault");
+
+        let err = Config::load(&config_path, None, Some("dev")).unwrap_err();
+        assert!(format!("{:#}", err).contains("available: prod, staging"));
+    }
 }
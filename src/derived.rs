@@ -0,0 +1,198 @@
+//! Keys whose value is computed from the other keys of a secret
+//!
+//! A secret often carries a connection string next to its password, e.g.
+//! `url = postgres://app:<password>@db/app`. Declaring the `url` key as
+//! derived keeps it in step with the password on every rotation:
+//!
+//! ```text
+//! derive_url = postgres://{username}:{password|url}@{host}:{port}/{database}
+//! ```
+//!
+//! `{key}` inserts another key's value as is; `{key|url}` percent-encodes it
+//! so that it can sit inside a URL. `{{` and `}}` stand for literal braces.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+
+/// Prefix of metadata keys declaring a derived key (`derive_<key>`)
+const DERIVE_PREFIX: &str = "derive_";
+
+/// Templates of a secret's derived keys, by key name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DerivedKeys {
+    templates: BTreeMap<String, String>,
+}
+
+impl DerivedKeys {
+    /// Templates declared in `metadata` over those configured for the secret
+    pub fn resolve(
+        metadata: &HashMap<String, String>,
+        configured: Option<&HashMap<String, String>>,
+    ) -> Self {
+        let mut templates: BTreeMap<String, String> = configured
+            .map(|configured| {
+                configured
+                    .iter()
+                    .map(|(key, template)| (key.clone(), template.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        for (key, template) in metadata {
+            if let Some(name) = key.strip_prefix(DERIVE_PREFIX) {
+                if !name.is_empty() {
+                    templates.insert(name.to_string(), template.clone());
+                }
+            }
+        }
+        Self { templates }
+    }
+
+    /// Whether `key` is computed rather than stored
+    pub fn contains(&self, key: &str) -> bool {
+        self.templates.contains_key(key)
+    }
+
+    /// Recompute every derived key of `data` from its other keys
+    ///
+    /// Templates may only refer to keys that are not derived themselves, so
+    /// the result does not depend on the order they are rendered in.
+    pub fn apply(&self, data: &mut HashMap<String, String>) -> Result<()> {
+        let mut rendered = Vec::with_capacity(self.templates.len());
+        for (key, template) in &self.templates {
+            let value = render(template, |name| {
+                if self.contains(name) {
+                    anyhow::bail!("'{}' is itself a derived key", name);
+                }
+                data.get(name)
+                    .cloned()
+                    .with_context(|| format!("the secret has no '{}' key", name))
+            })
+            .with_context(|| format!("Failed to derive '{}'", key))?;
+            rendered.push((key.clone(), value));
+        }
+        data.extend(rendered);
+        Ok(())
+    }
+}
+
+/// Fill in the `{key}` and `{key|url}` placeholders of `template`
+fn render(template: &str, lookup: impl Fn(&str) -> Result<String>) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(pos) = rest.find(['{', '}']) {
+        out.push_str(&rest[..pos]);
+        let brace = rest.as_bytes()[pos];
+        rest = &rest[pos + 1..];
+        if rest.as_bytes().first() == Some(&brace) {
+            out.push(brace as char);
+            rest = &rest[1..];
+            continue;
+        }
+        if brace == b'}' {
+            anyhow::bail!("Unmatched '}}' in template");
+        }
+        let end = rest.find('}').context("Unclosed '{' in template")?;
+        let placeholder = &rest[..end];
+        rest = &rest[end + 1..];
+
+        let (name, filter) = match placeholder.split_once('|') {
+            Some((name, filter)) => (name.trim(), Some(filter.trim())),
+            None => (placeholder.trim(), None),
+        };
+        if name.is_empty() {
+            anyhow::bail!("Empty placeholder in template");
+        }
+        let value = lookup(name)?;
+        match filter {
+            None => out.push_str(&value),
+            Some("url") => out.push_str(&percent_encode(&value)),
+            Some(other) => anyhow::bail!("Unknown filter '{}' (expected 'url')", other),
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters
+pub(crate) fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_templates() {
+        let metadata = data(&[
+            (
+                "derive_url",
+                "postgres://{username}:{password|url}@{host}/app",
+            ),
+            ("derive_dsn", "user={username} password={password}"),
+            ("rotation_enabled", "true"),
+        ]);
+        let derived = DerivedKeys::resolve(&metadata, None);
+        assert!(derived.contains("url") && derived.contains("dsn"));
+
+        let mut secret = data(&[
+            ("username", "app"),
+            ("password", "p@ss/w:rd"),
+            ("host", "db"),
+            ("url", "postgres://app:stale@db/app"),
+        ]);
+        derived.apply(&mut secret).unwrap();
+        assert_eq!(secret["url"], "postgres://app:p%40ss%2Fw%3Ard@db/app");
+        assert_eq!(secret["dsn"], "user=app password=p@ss/w:rd");
+    }
+
+    #[test]
+    fn test_metadata_overrides_config() {
+        let configured = data(&[("url", "from-config"), ("dsn", "{password}")]);
+        let metadata = data(&[("derive_url", "{{literal}}")]);
+        let derived = DerivedKeys::resolve(&metadata, Some(&configured));
+
+        let mut secret = data(&[("password", "x")]);
+        derived.apply(&mut secret).unwrap();
+        assert_eq!(secret["url"], "{literal}");
+        assert_eq!(secret["dsn"], "x");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let secret = data(&[("password", "x"), ("url", "old")]);
+        for template in [
+            "{missing}",
+            "{password|base64}",
+            "{password",
+            "password}",
+            "{}",
+            "{url}",
+        ] {
+            let derived = DerivedKeys::resolve(&data(&[("derive_url", template)]), None);
+            let mut copy = secret.clone();
+            assert!(
+                derived.apply(&mut copy).is_err(),
+                "{} should fail",
+                template
+            );
+            assert_eq!(copy, secret);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Key-level comparison of two values of a secret (`asr diff`)
+//!
+//! The two sides are versions of one secret or the same secret in two
+//! backends. Values are only shown when asked for; otherwise the diff says
+//! which keys were added, removed or changed.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+/// How a key differs between the two sides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+impl ChangeKind {
+    fn label(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+            ChangeKind::Unchanged => "unchanged",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChange {
+    pub key: String,
+    pub kind: ChangeKind,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Changes from `old` to `new`, one per key of either side, sorted by key
+pub fn diff(old: &HashMap<String, String>, new: &HashMap<String, String>) -> Vec<KeyChange> {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .map(|key| {
+            let (old, new) = (old.get(key), new.get(key));
+            let kind = match (old, new) {
+                (None, _) => ChangeKind::Added,
+                (_, None) => ChangeKind::Removed,
+                (Some(a), Some(b)) if a == b => ChangeKind::Unchanged,
+                _ => ChangeKind::Changed,
+            };
+            KeyChange {
+                key: key.clone(),
+                kind,
+                old: old.cloned(),
+                new: new.cloned(),
+            }
+        })
+        .collect()
+}
+
+/// One line per key, with the values when `show_values` is set
+pub fn render(changes: &[KeyChange], show_values: bool) -> String {
+    let width = changes.iter().map(|c| c.key.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    for change in changes {
+        let mut line = format!(
+            "  {:<9}  {:<width$}",
+            change.kind.label(),
+            change.key,
+            width = width
+        );
+        if show_values {
+            let value = |v: &Option<String>| v.clone().unwrap_or_default();
+            let _ = match change.kind {
+                ChangeKind::Unchanged | ChangeKind::Added => {
+                    write!(line, "  {}", value(&change.new))
+                }
+                ChangeKind::Removed => write!(line, "  {}", value(&change.old)),
+                ChangeKind::Changed => {
+                    write!(line, "  {} -> {}", value(&change.old), value(&change.new))
+                }
+            };
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Whether any key differs
+pub fn has_changes(changes: &[KeyChange]) -> bool {
+    changes.iter().any(|c| c.kind != ChangeKind::Unchanged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_and_render() {
+        let old = data(&[("password", "old"), ("username", "app"), ("legacy", "x")]);
+        let new = data(&[("password", "new"), ("username", "app"), ("url", "db")]);
+        let changes = diff(&old, &new);
+        let kinds: Vec<_> = changes.iter().map(|c| (c.key.as_str(), c.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("legacy", ChangeKind::Removed),
+                ("password", ChangeKind::Changed),
+                ("url", ChangeKind::Added),
+                ("username", ChangeKind::Unchanged),
+            ]
+        );
+        assert!(has_changes(&changes));
+        assert!(!has_changes(&diff(&old, &old)));
+
+        let redacted = render(&changes, false);
+        assert!(!redacted.contains("old") && !redacted.contains("new"));
+        assert!(redacted.contains("  changed    password\n"));
+
+        let shown = render(&changes, true);
+        assert!(shown.contains("  changed    password  old -> new\n"));
+        assert!(shown.contains("  removed    legacy    x\n"));
+    }
+}
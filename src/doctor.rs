@@ -0,0 +1,365 @@
+//! Connectivity and permission checks for `asr doctor`
+//!
+//! Each check is independent and read-only, so doctor can be run against
+//! production. Most failed rotations come down to a missing Vault policy or
+//! an unreachable target, which these checks name directly.
+
+use std::fmt;
+
+use crate::backends::{SecretBackend, VaultClient};
+use crate::redact;
+use crate::targets::Target;
+
+/// Tokens expiring sooner than this are reported as a warning
+pub const MIN_TOKEN_TTL_HOURS: i64 = 24;
+
+/// Name of the secret that capabilities are checked on, below the checked path
+///
+/// It is never read or written; Vault only evaluates policies against it, so
+/// that `prod/*` style policies match.
+const PROBE_NAME: &str = "asr-doctor-probe";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+    Skip,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+            Status::Skip => "SKIP",
+        })
+    }
+}
+
+/// Result of one check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn new(name: &str, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    /// A failed check, with the error chain redacted
+    pub fn failed(name: &str, error: &anyhow::Error) -> Self {
+        Self::new(name, Status::Fail, redact::redact(&format!("{:#}", error)))
+    }
+}
+
+/// Check that Vault is up, the token is valid and long-lived, and the token's
+/// policies allow everything rotation does under `path` of the KV `mount`
+pub async fn check_vault(client: &VaultClient, mount: &str, path: &str) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let server = client.flavor().name();
+    match client.health().await {
+        Ok(health) if !health.initialized => {
+            checks.push(Check::new(
+                "vault server",
+                Status::Fail,
+                format!("{} is not initialized", server),
+            ));
+            return checks;
+        }
+        Ok(health) if health.sealed => {
+            checks.push(Check::new(
+                "vault server",
+                Status::Fail,
+                format!("{} is sealed", server),
+            ));
+            return checks;
+        }
+        Ok(health) => {
+            let mut detail = format!("{} reachable, unsealed, version {}", server, health.version);
+            if health.standby {
+                detail.push_str(" (standby)");
+            }
+            checks.push(Check::new("vault server", Status::Pass, detail));
+        }
+        Err(e) => {
+            checks.push(Check::failed("vault server", &e));
+            return checks;
+        }
+    }
+
+    match client.lookup_self().await {
+        Ok(token) => {
+            let policies = format!("policies: {}", token.policies.join(", "));
+            let check = if token.ttl <= 0 {
+                Check::new(
+                    "vault token",
+                    Status::Pass,
+                    format!("never expires; {}", policies),
+                )
+            } else {
+                let hours = token.ttl / 3600;
+                let status = if hours < MIN_TOKEN_TTL_HOURS {
+                    Status::Warn
+                } else {
+                    Status::Pass
+                };
+                let renewable = if token.renewable {
+                    "renewable"
+                } else {
+                    "not renewable"
+                };
+                Check::new(
+                    "vault token",
+                    status,
+                    format!("expires in {}h, {}; {}", hours, renewable, policies),
+                )
+            };
+            checks.push(check);
+        }
+        Err(e) => {
+            checks.push(Check::failed("vault token", &e));
+            return checks;
+        }
+    }
+
+    let path = path.trim_matches('/');
+    let under = |api: &str, child: Option<&str>| {
+        [mount, api, path, child.unwrap_or("")]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+    let required = [
+        ("list", under("metadata", None), &["list"][..]),
+        ("read", under("data", Some(PROBE_NAME)), &["read"][..]),
+        (
+            "write",
+            under("data", Some(PROBE_NAME)),
+            &["create", "update"][..],
+        ),
+        (
+            "metadata",
+            under("metadata", Some(PROBE_NAME)),
+            &["read", "update"][..],
+        ),
+    ];
+    let paths: Vec<String> = required.iter().map(|(_, path, _)| path.clone()).collect();
+    match client.capabilities(&paths).await {
+        Ok(granted) => {
+            for (name, path, needed) in &required {
+                let granted = granted.get(path).cloned().unwrap_or_default();
+                checks.push(capability_check(name, path, needed, &granted));
+            }
+        }
+        Err(e) => checks.push(Check::failed("capabilities", &e)),
+    }
+    checks
+}
+
+fn capability_check(name: &str, path: &str, needed: &[&str], granted: &[String]) -> Check {
+    let has = |capability: &str| granted.iter().any(|g| g == capability);
+    if has("deny") {
+        return Check::new(name, Status::Fail, format!("denied on {}", path));
+    }
+    if has("root") {
+        return Check::new(name, Status::Pass, format!("root on {}", path));
+    }
+    let missing: Vec<&str> = needed.iter().copied().filter(|c| !has(c)).collect();
+    if missing.is_empty() {
+        Check::new(
+            name,
+            Status::Pass,
+            format!("{} on {}", needed.join(", "), path),
+        )
+    } else {
+        Check::new(
+            name,
+            Status::Fail,
+            format!("policy lacks {} on {}", missing.join(", "), path),
+        )
+    }
+}
+
+/// Check that the backend can list `path` and read the first secret under it
+///
+/// Writes are not attempted; for Vault they are covered by [`check_vault`].
+pub async fn check_backend(backend: &dyn SecretBackend, path: &str) -> Vec<Check> {
+    let location = if path.is_empty() { "/" } else { path };
+    let secrets = match backend.list_secrets(path).await {
+        Ok(secrets) => secrets,
+        Err(e) => return vec![Check::failed("backend list", &e)],
+    };
+    let mut checks = vec![Check::new(
+        "backend list",
+        Status::Pass,
+        format!(
+            "{} entries under {} ({})",
+            secrets.len(),
+            location,
+            backend.backend_type()
+        ),
+    )];
+
+    let Some(secret) = secrets.iter().find(|secret| !secret.ends_with('/')) else {
+        checks.push(Check::new(
+            "backend read",
+            Status::Skip,
+            format!("no secret directly under {} to read", location),
+        ));
+        return checks;
+    };
+    let secret_path = crate::transfer::child_path(path, secret);
+    let read = async {
+        backend.read_secret(&secret_path).await?;
+        backend.read_metadata(&secret_path).await
+    };
+    checks.push(match read.await {
+        Ok(_) => Check::new(
+            "backend read",
+            Status::Pass,
+            format!("read {} and its metadata", secret_path),
+        ),
+        Err(e) => Check::failed("backend read", &e),
+    });
+    checks
+}
+
+/// Check that the target can be reached with its admin credentials
+pub async fn check_target(target: Option<&dyn Target>) -> Check {
+    let Some(target) = target else {
+        return Check::new("target", Status::Skip, "no target configured");
+    };
+    let name = format!("target ({})", target.target_type());
+    match target.check().await {
+        Ok(Some(detail)) => Check::new(&name, Status::Pass, detail),
+        Ok(None) => Check::new(&name, Status::Skip, "this target cannot be checked"),
+        Err(e) => Check::failed(&name, &e),
+    }
+}
+
+/// The checks as a table with one row per check
+pub fn render(checks: &[Check]) -> String {
+    let name_width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .chain(["CHECK".len()])
+        .max()
+        .unwrap_or(0);
+    let mut out = format!("{:<width$}  RESULT  DETAIL\n", "CHECK", width = name_width);
+    for check in checks {
+        out.push_str(&format!(
+            "{:<width$}  {:<6}  {}\n",
+            check.name,
+            check.status.to_string(),
+            check.detail,
+            width = name_width
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::FileBackend;
+    use crate::redact::SecretString;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_check_vault_reports_missing_capabilities() {
+        let mut server = mockito::Server::new_async().await;
+        let _health = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/v1/sys/health".to_string()),
+            )
+            .with_body(r#"{"initialized":true,"sealed":false,"standby":false,"version":"1.15.0"}"#)
+            .create_async()
+            .await;
+        let _token = server
+            .mock("GET", "/v1/auth/token/lookup-self")
+            .with_body(
+                r#"{"data":{"display_name":"token-asr","policies":["asr"],"ttl":7200,"renewable":true}}"#,
+            )
+            .create_async()
+            .await;
+        let _capabilities = server
+            .mock("POST", "/v1/sys/capabilities-self")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "paths": [
+                    "secret/metadata/prod",
+                    "secret/data/prod/asr-doctor-probe",
+                    "secret/data/prod/asr-doctor-probe",
+                    "secret/metadata/prod/asr-doctor-probe",
+                ]
+            })))
+            .with_body(
+                r#"{"data":{
+                    "capabilities":["list","read","update"],
+                    "secret/metadata/prod":["list"],
+                    "secret/data/prod/asr-doctor-probe":["read","update"],
+                    "secret/metadata/prod/asr-doctor-probe":["deny"]
+                }}"#,
+            )
+            .create_async()
+            .await;
+
+        let client = VaultClient::new(server.url(), SecretString::new("token")).unwrap();
+        let checks = check_vault(&client, "secret", "/prod/").await;
+        let results: Vec<(&str, Status)> = checks
+            .iter()
+            .map(|check| (check.name.as_str(), check.status))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                ("vault server", Status::Pass),
+                ("vault token", Status::Warn),
+                ("list", Status::Pass),
+                ("read", Status::Pass),
+                ("write", Status::Fail),
+                ("metadata", Status::Fail),
+            ]
+        );
+        assert_eq!(
+            checks[4].detail,
+            "policy lacks create on secret/data/prod/asr-doctor-probe"
+        );
+        assert!(render(&checks).contains("write         FAIL    policy lacks create"));
+    }
+
+    #[tokio::test]
+    async fn test_check_backend() -> anyhow::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?;
+
+        let checks = check_backend(&backend, "app").await;
+        assert_eq!(checks[1].status, Status::Skip);
+
+        backend
+            .write_secret(
+                "app/db",
+                HashMap::from([("password".to_string(), "x".to_string())]),
+            )
+            .await?;
+        let checks = check_backend(&backend, "app").await;
+        assert_eq!(checks[0].status, Status::Pass);
+        assert_eq!(checks[1].status, Status::Pass);
+        assert_eq!(checks[1].detail, "read app/db and its metadata");
+
+        let target = check_target(None).await;
+        assert_eq!(target.status, Status::Skip);
+        Ok(())
+    }
+}
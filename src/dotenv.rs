@@ -0,0 +1,231 @@
+//! Minimal dotenv (.env) parsing and formatting
+//!
+//! Supports the common subset of the format used by most dotenv loaders:
+//! `KEY=value`, optional `export ` prefixes, single/double quoted values,
+//! blank lines, and `#` comments.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// A single variable parsed from a dotenv file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DotenvEntry {
+    pub key: String,
+    pub value: String,
+}
+
+/// Parse dotenv content into an ordered list of entries
+///
+/// Later duplicates are kept so callers can decide how to resolve them; most
+/// loaders let the last definition win.
+pub fn parse(content: &str) -> Result<Vec<DotenvEntry>> {
+    let mut entries = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        if let Some(entry) = parse_line(line)
+            .with_context(|| format!("Invalid dotenv syntax on line {}", index + 1))?
+        {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Parse a dotenv file from disk
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Vec<DotenvEntry>> {
+    let content = fs::read_to_string(path.as_ref())
+        .with_context(|| format!("Failed to read {}", path.as_ref().display()))?;
+    parse(&content)
+}
+
+/// Parse a single dotenv line, returning `None` for blank lines and comments
+pub fn parse_line(line: &str) -> Result<Option<DotenvEntry>> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return Ok(None);
+    }
+
+    let trimmed = trimmed
+        .strip_prefix("export ")
+        .map(str::trim_start)
+        .unwrap_or(trimmed);
+
+    let (key, raw_value) = trimmed
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("expected KEY=value"))?;
+
+    let key = key.trim();
+    if !is_valid_key(key) {
+        anyhow::bail!("invalid variable name '{}'", key);
+    }
+
+    Ok(Some(DotenvEntry {
+        key: key.to_string(),
+        value: parse_value(raw_value.trim())?,
+    }))
+}
+
+/// Check whether a variable name is a valid environment variable identifier
+pub fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Parse the value part of a dotenv line, handling quotes and inline comments
+fn parse_value(raw: &str) -> Result<String> {
+    if let Some(rest) = raw.strip_prefix('"') {
+        let end = find_closing_quote(rest, '"')
+            .ok_or_else(|| anyhow::anyhow!("unterminated double-quoted value"))?;
+        return Ok(unescape_double_quoted(&rest[..end]));
+    }
+
+    if let Some(rest) = raw.strip_prefix('\'') {
+        let end = rest
+            .find('\'')
+            .ok_or_else(|| anyhow::anyhow!("unterminated single-quoted value"))?;
+        return Ok(rest[..end].to_string());
+    }
+
+    // Unquoted: strip inline comments introduced by " #"
+    let value = match raw.find(" #") {
+        Some(pos) => &raw[..pos],
+        None => raw,
+    };
+    Ok(value.trim().to_string())
+}
+
+/// Find the index of the closing quote, skipping backslash-escaped characters
+fn find_closing_quote(s: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn unescape_double_quoted(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('r') => result.push('\r'),
+                Some('t') => result.push('\t'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Format a value for writing to a dotenv file, quoting when necessary
+pub fn format_value(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '#' | '"' | '\'' | '\\' | '$' | '`'));
+
+    if !needs_quotes {
+        return value.to_string();
+    }
+
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+/// Format a `KEY=value` line (without trailing newline)
+pub fn format_line(key: &str, value: &str) -> String {
+    format!("{}={}", key, format_value(value))
+}
+
+/// Format an `export KEY='value'` line that a POSIX shell can `eval` or source
+///
+/// Single quotes keep `$`, backticks and backslashes literal, unlike the double
+/// quotes of [`format_value`].
+pub fn format_export_line(key: &str, value: &str) -> String {
+    format!("export {}='{}'", key, value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_basic() -> Result<()> {
+        let entries = parse("# comment\n\nFOO=bar\nexport BAZ=qux\n")?;
+        assert_eq!(
+            entries,
+            vec![
+                DotenvEntry {
+                    key: "FOO".to_string(),
+                    value: "bar".to_string()
+                },
+                DotenvEntry {
+                    key: "BAZ".to_string(),
+                    value: "qux".to_string()
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_quoted_values() -> Result<()> {
+        let entries = parse("A=\"hello world\"\nB='single $quoted'\nC=\"line\\nbreak\"\n")?;
+        assert_eq!(entries[0].value, "hello world");
+        assert_eq!(entries[1].value, "single $quoted");
+        assert_eq!(entries[2].value, "line\nbreak");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_inline_comment() -> Result<()> {
+        let entries = parse("TOKEN=abc123 # api token\n")?;
+        assert_eq!(entries[0].value, "abc123");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_line() {
+        assert!(parse("NOT A VALID LINE\n").is_err());
+        assert!(parse("1BAD=value\n").is_err());
+    }
+
+    #[test]
+    fn test_format_roundtrip() -> Result<()> {
+        for value in [
+            "plain",
+            "with space",
+            "quote\"d",
+            "p@$$w0rd#1",
+            "multi\nline",
+            "",
+        ] {
+            let line = format_line("KEY", value);
+            let parsed = parse_line(&line)?.unwrap();
+            assert_eq!(parsed.value, value);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+//! Encrypting displayed secrets to a recipient
+//!
+//! `--encrypt-to` prints ASCII-armored ciphertext instead of the plaintext
+//! value, so a secret can be shown in a shared terminal or CI log and only be
+//! read by the holder of the recipient key. Encryption is done by the `age` or
+//! `gpg` command-line tools, which must be installed.
+//!
+//! Backups are encrypted the same way and read back with [`decrypt`].
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Key that displayed secrets are encrypted to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recipient {
+    /// age recipient (`age1...`) or SSH public key
+    Age(String),
+    /// GnuPG key ID, fingerprint, or email
+    Gpg(String),
+}
+
+impl FromStr for Recipient {
+    type Err = String;
+
+    /// `age1...` and `ssh-...` keys use age; anything else is a GnuPG key.
+    /// An explicit `age:` or `gpg:` prefix overrides the detection.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("Recipient must not be empty".to_string());
+        }
+
+        if let Some(key) = s.strip_prefix("age:") {
+            Ok(Recipient::Age(key.to_string()))
+        } else if let Some(key) = s.strip_prefix("gpg:") {
+            Ok(Recipient::Gpg(key.to_string()))
+        } else if s.starts_with("age1") || s.starts_with("ssh-") {
+            Ok(Recipient::Age(s.to_string()))
+        } else {
+            Ok(Recipient::Gpg(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recipient::Age(key) => write!(f, "age recipient {}", key),
+            Recipient::Gpg(key) => write!(f, "GnuPG key {}", key),
+        }
+    }
+}
+
+impl Recipient {
+    /// Program and arguments that encrypt stdin to this recipient
+    fn command(&self) -> (&'static str, Vec<&str>) {
+        match self {
+            Recipient::Age(key) => ("age", vec!["--encrypt", "--armor", "--recipient", key]),
+            Recipient::Gpg(key) => (
+                "gpg",
+                vec![
+                    "--batch",
+                    "--yes",
+                    "--armor",
+                    "--encrypt",
+                    // The recipient is named explicitly on the command line
+                    "--trust-model",
+                    "always",
+                    "--recipient",
+                    key,
+                ],
+            ),
+        }
+    }
+}
+
+/// Encrypt `plaintext` to `recipient`, returning ASCII-armored ciphertext
+pub fn encrypt(plaintext: &str, recipient: &Recipient) -> Result<String> {
+    let (program, args) = recipient.command();
+    run(program, &args, plaintext)
+        .with_context(|| format!("{} failed to encrypt to {}", program, recipient))
+}
+
+/// Armor header of age-encrypted data; anything else is handed to GnuPG
+const AGE_ARMOR_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// Decrypt ASCII-armored ciphertext written by [`encrypt`]
+///
+/// age needs the `identity` file holding the private key. GnuPG finds the
+/// key in its keyring (and asks for its passphrase through the agent).
+pub fn decrypt(ciphertext: &str, identity: Option<&Path>) -> Result<String> {
+    let (program, args) = if ciphertext.trim_start().starts_with(AGE_ARMOR_HEADER) {
+        let identity = identity
+            .context("Data encrypted with age needs --identity with the private key file")?;
+        let identity = identity
+            .to_str()
+            .context("Identity file path is not valid UTF-8")?;
+        ("age", vec!["--decrypt", "--identity", identity])
+    } else {
+        ("gpg", vec!["--batch", "--quiet", "--decrypt"])
+    };
+    run(program, &args, ciphertext).with_context(|| format!("{} failed to decrypt", program))
+}
+
+/// Run `program`, feeding it `input` and returning its output
+fn run(program: &str, args: &[&str], input: &str) -> Result<String> {
+    let mut child = match Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            anyhow::bail!("{} is not installed", program)
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to run {}", program)),
+    };
+
+    // Write from another thread while the output is read, so neither side
+    // blocks on a full pipe when the input or output is large
+    let stdin = child.stdin.take();
+    let (written, output) = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || match stdin {
+            Some(mut stdin) => stdin.write_all(input.as_bytes()),
+            None => Ok(()),
+        });
+        let output = child.wait_with_output();
+        let written = writer
+            .join()
+            .unwrap_or_else(|_| Err(std::io::Error::other("writer thread panicked")));
+        (written, output)
+    });
+
+    let output = output.with_context(|| format!("Failed to wait for {}", program))?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    written.with_context(|| format!("Failed to write to {}", program))?;
+
+    String::from_utf8(output.stdout).with_context(|| format!("{} returned invalid output", program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_recipient() {
+        assert_eq!(
+            "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p".parse(),
+            Ok(Recipient::Age(
+                "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p".to_string()
+            ))
+        );
+        assert_eq!(
+            "ssh-ed25519 AAAAC3 ops".parse(),
+            Ok(Recipient::Age("ssh-ed25519 AAAAC3 ops".to_string()))
+        );
+        assert_eq!(
+            "ops@example.com".parse(),
+            Ok(Recipient::Gpg("ops@example.com".to_string()))
+        );
+        assert_eq!(
+            "age:ops-key".parse(),
+            Ok(Recipient::Age("ops-key".to_string()))
+        );
+        assert!("  ".parse::<Recipient>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_streams_large_input() -> Result<()> {
+        // Larger than a pipe buffer in both directions
+        let input = "x".repeat(1 << 20);
+        assert_eq!(run("cat", &[], &input)?, input);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recipient_command() {
+        let recipient = Recipient::Gpg("ABCD1234".to_string());
+        let (program, args) = recipient.command();
+        assert_eq!(program, "gpg");
+        assert_eq!(args.last(), Some(&"ABCD1234"));
+        assert!(args.contains(&"--armor"));
+    }
+}
@@ -0,0 +1,154 @@
+//! Loading secrets into the process environment (library use)
+//!
+//! Rust services can read their secrets at startup straight from the backend
+//! instead of sourcing the shell or dotenv files `asr update-env` writes:
+//!
+//! ```no_run
+//! # async fn example(backend: &dyn secret_rotator::backends::SecretBackend) -> anyhow::Result<()> {
+//! secret_rotator::env::load_into_process(backend, &["myapp/database"]).await?;
+//! let password = std::env::var("MYAPP_DATABASE")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each secret's rotated value is set under the same names the environment
+//! updates of `asr auto --update-env` use: the secret's `env_var` metadata,
+//! then the configured mappings, then the path (`myapp/database` ->
+//! `MYAPP_DATABASE`), with an optional prefix.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::backends::SecretBackend;
+use crate::config::EnvVarNames;
+use crate::dotenv;
+use crate::env_updater::env_var_names;
+use crate::rotation::key_to_rotate;
+
+/// How secrets are named in the environment
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Prepended to every variable name (e.g., "APP_")
+    pub prefix: Option<String>,
+    /// Secret path to variable name(s), as in `[env.mappings]`
+    pub mappings: HashMap<String, EnvVarNames>,
+    /// Keep variables that are already set instead of replacing them
+    pub keep_existing: bool,
+}
+
+impl LoadOptions {
+    pub fn with_prefix(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: Some(prefix.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Read the secrets at `paths` and set them in this process's environment
+pub async fn load_into_process(backend: &dyn SecretBackend, paths: &[&str]) -> Result<()> {
+    load_into_process_with(backend, paths, &LoadOptions::default()).await?;
+    Ok(())
+}
+
+/// [`load_into_process`] with a prefix, mappings or existing variables kept
+///
+/// Every secret is read before any variable is set, so a failed read leaves
+/// the environment unchanged. Returns the names of the variables set.
+pub async fn load_into_process_with(
+    backend: &dyn SecretBackend,
+    paths: &[&str],
+    options: &LoadOptions,
+) -> Result<Vec<String>> {
+    let vars = read_vars(backend, paths, options).await?;
+    let mut set = Vec::new();
+    for (name, value) in vars {
+        if options.keep_existing && std::env::var_os(&name).is_some() {
+            continue;
+        }
+        // Setting variables races with other threads reading the environment;
+        // call this at startup, before spawning any
+        std::env::set_var(&name, value);
+        set.push(name);
+    }
+    Ok(set)
+}
+
+/// Variables the secrets at `paths` would set, by name
+pub async fn read_vars(
+    backend: &dyn SecretBackend,
+    paths: &[&str],
+    options: &LoadOptions,
+) -> Result<BTreeMap<String, String>> {
+    let prefix = options.prefix.as_deref().unwrap_or_default();
+    let mut vars = BTreeMap::new();
+    for path in paths {
+        let secret = backend
+            .read_secret(path)
+            .await
+            .with_context(|| format!("Failed to read secret {}", path))?;
+        let metadata = secret.metadata.unwrap_or_default();
+        let key = key_to_rotate(&secret.data, &metadata);
+        let value = secret
+            .data
+            .get(&key)
+            .with_context(|| format!("Secret {} has no value to load", path))?;
+        for name in env_var_names(path, &metadata, &options.mappings) {
+            let name = format!("{}{}", prefix, name);
+            if !dotenv::is_valid_key(&name) {
+                anyhow::bail!(
+                    "Secret {} maps to invalid environment variable name '{}'",
+                    path,
+                    name
+                );
+            }
+            vars.insert(name, value.clone());
+        }
+    }
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::MemoryBackend;
+
+    #[tokio::test]
+    async fn test_load_into_process() -> Result<()> {
+        let backend = MemoryBackend::new()
+            .with_secret("asrtest/database", [("password", "db-pass")])
+            .with_secret("asrtest/api", [("username", "svc"), ("token", "api-token")]);
+        backend
+            .update_metadata(
+                "asrtest/api",
+                HashMap::from([("env_var".to_string(), "API_TOKEN".to_string())]),
+            )
+            .await?;
+
+        let options = LoadOptions::with_prefix("ASRTEST_LOAD_");
+        std::env::set_var("ASRTEST_LOAD_API_TOKEN", "unchanged");
+        let set = load_into_process_with(
+            &backend,
+            &["asrtest/database", "asrtest/api"],
+            &LoadOptions {
+                keep_existing: true,
+                ..options.clone()
+            },
+        )
+        .await?;
+        assert_eq!(set, ["ASRTEST_LOAD_ASRTEST_DATABASE"]);
+        assert_eq!(std::env::var("ASRTEST_LOAD_ASRTEST_DATABASE")?, "db-pass");
+        assert_eq!(std::env::var("ASRTEST_LOAD_API_TOKEN")?, "unchanged");
+
+        load_into_process_with(&backend, &["asrtest/api"], &options).await?;
+        assert_eq!(std::env::var("ASRTEST_LOAD_API_TOKEN")?, "api-token");
+
+        // Nothing is set when any secret is missing
+        let err = load_into_process(&backend, &["asrtest/database", "asrtest/missing"])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("asrtest/missing"));
+        assert!(std::env::var_os("ASRTEST_DATABASE").is_none());
+        Ok(())
+    }
+}
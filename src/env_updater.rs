@@ -1,23 +1,147 @@
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use tracing::{debug, info, warn};
 
+use crate::config::{EnvConfig, EnvVarNames, StructuredFileConfig, SystemdEnvFileConfig};
+use crate::dotenv;
+use crate::structured_file;
+
 /// Updates environment variables in shell configuration files
+///
+/// On Windows there are no shell rc files to edit, so variables are written to
+/// the user environment (HKCU\Environment) instead. When dotenv, systemd, or
+/// structured config files are configured, those are updated instead of either.
 #[allow(dead_code)]
 pub struct EnvUpdater {
     /// Home directory of the user
     home_dir: PathBuf,
+    /// Project-level .env files to update instead of shell config files
+    dotenv_files: Vec<PathBuf>,
+    /// systemd environment files to update instead of shell config files
+    systemd_files: Vec<SystemdEnvFileConfig>,
+    /// Structured config files holding values for specific variables
+    config_files: Vec<StructuredFileConfig>,
+    /// Explicit shell config files to update instead of the default rc files
+    shell_files: Option<Vec<PathBuf>>,
 }
 
+/// Shell config files updated when no explicit list is configured
+const DEFAULT_SHELL_FILES: [&str; 4] = [".bashrc", ".bash_profile", ".zshrc", ".profile"];
+
 impl EnvUpdater {
     /// Create a new EnvUpdater for the current user
     pub fn new() -> Result<Self> {
         let home_dir = std::env::var("HOME")
-            .context("HOME environment variable not set")?
+            .or_else(|_| std::env::var("USERPROFILE"))
+            .context("HOME (or USERPROFILE on Windows) environment variable not set")?
             .into();
 
-        Ok(Self { home_dir })
+        Ok(Self {
+            home_dir,
+            dotenv_files: Vec::new(),
+            systemd_files: Vec::new(),
+            config_files: Vec::new(),
+            shell_files: None,
+        })
+    }
+
+    /// Create an EnvUpdater for the current user using the `[env]` config section
+    pub fn from_config(config: &EnvConfig) -> Result<Self> {
+        let updater = Self::new()?;
+        let dotenv_files = config
+            .dotenv_files
+            .iter()
+            .map(|path| updater.expand_home(path))
+            .collect();
+
+        let updater = if config.shell_files.is_empty() {
+            updater
+        } else {
+            let shell_files = config
+                .shell_files
+                .iter()
+                .map(|path| updater.expand_home(path))
+                .collect();
+            updater.with_shell_files(shell_files)
+        };
+
+        Ok(updater
+            .with_dotenv_files(dotenv_files)
+            .with_systemd_files(config.systemd_files.clone())
+            .with_config_files(config.config_files.clone()))
+    }
+
+    /// Update the given .env files instead of shell config files
+    pub fn with_dotenv_files(mut self, dotenv_files: Vec<PathBuf>) -> Self {
+        self.dotenv_files = dotenv_files;
+        self
+    }
+
+    /// Update the given systemd environment files instead of shell config files
+    pub fn with_systemd_files(mut self, systemd_files: Vec<SystemdEnvFileConfig>) -> Self {
+        self.systemd_files = systemd_files;
+        self
+    }
+
+    /// Update structured config files (JSON/YAML/TOML) for their mapped variables
+    pub fn with_config_files(mut self, config_files: Vec<StructuredFileConfig>) -> Self {
+        self.config_files = config_files;
+        self
+    }
+
+    /// Update only the given shell config files instead of every default rc file
+    ///
+    /// Unlike the defaults, explicitly listed files are created if missing, so a
+    /// single dedicated file (e.g., `~/.config/asr/env.sh`) can be sourced once.
+    pub fn with_shell_files(mut self, shell_files: Vec<PathBuf>) -> Self {
+        self.shell_files = Some(shell_files);
+        self
+    }
+
+    /// Expand a user-supplied path (e.g., from a CLI flag) relative to the home directory
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        self.expand_home(path)
+    }
+
+    /// Shell config files to update, and whether missing files should be created
+    fn shell_file_paths(&self) -> (Vec<PathBuf>, bool) {
+        match self.shell_files {
+            Some(ref files) => (files.clone(), true),
+            None => (
+                DEFAULT_SHELL_FILES
+                    .iter()
+                    .map(|f| self.home_dir.join(f))
+                    .collect(),
+                false,
+            ),
+        }
+    }
+
+    /// Whether any file destinations are configured in place of shell config files
+    fn has_file_destinations(&self) -> bool {
+        !self.dotenv_files.is_empty()
+            || !self.systemd_files.is_empty()
+            || !self.config_files.is_empty()
+    }
+
+    /// Expand a leading `~/` to the home directory
+    fn expand_home(&self, path: &str) -> PathBuf {
+        match path.strip_prefix("~/") {
+            Some(rest) => self.home_dir.join(rest),
+            None => PathBuf::from(path),
+        }
+    }
+
+    /// Hint telling the user how to pick up updated variables on this platform
+    pub fn reload_hint() -> &'static str {
+        if cfg!(windows) {
+            "Open a new terminal for the updated user environment variables to take effect"
+        } else {
+            "You need to reload your shell or run 'source ~/.bashrc' (or ~/.zshrc) for changes to take effect"
+        }
     }
 
     /// Create an EnvUpdater for a specific home directory
@@ -26,34 +150,111 @@ impl EnvUpdater {
     /// in a different user's home directory.
     #[cfg_attr(not(test), allow(dead_code))] // Used in tests
     pub fn with_home_dir(home_dir: PathBuf) -> Self {
-        Self { home_dir }
+        Self {
+            home_dir,
+            dotenv_files: Vec::new(),
+            systemd_files: Vec::new(),
+            config_files: Vec::new(),
+            shell_files: None,
+        }
     }
 
-    /// Update or add an environment variable in shell config files
+    /// Where [`EnvUpdater::update_env_var`] would write `var_name`, without
+    /// changing anything
+    pub fn destinations(&self, var_name: &str) -> Vec<String> {
+        if self.has_file_destinations() {
+            let dotenv = self
+                .dotenv_files
+                .iter()
+                .map(|path| path.display().to_string());
+            let systemd = self
+                .systemd_files
+                .iter()
+                .map(|file| match &file.restart_unit {
+                    Some(unit) => format!("{} (restarts {})", file.path, unit),
+                    None => file.path.clone(),
+                });
+            let config = self
+                .config_files
+                .iter()
+                .filter(|file| file.env_var == var_name)
+                .map(|file| {
+                    format!(
+                        "{} ({})",
+                        self.expand_home(&file.path).display(),
+                        file.key_path
+                    )
+                });
+            return dotenv.chain(systemd).chain(config).collect();
+        }
+
+        if cfg!(windows) {
+            return vec!["Windows user environment".to_string()];
+        }
+
+        let (files, create_missing) = self.shell_file_paths();
+        files
+            .into_iter()
+            .filter(|path| create_missing || path.exists())
+            .map(|path| path.display().to_string())
+            .collect()
+    }
+
+    /// Update or add an environment variable for the current user
+    ///
+    /// Updates shell config files on Unix-like systems and the user environment
+    /// on Windows.
     pub fn update_env_var(&self, var_name: &str, new_value: &str) -> Result<()> {
+        if self.has_file_destinations() {
+            self.update_dotenv_files(var_name, new_value)?;
+            self.update_systemd_files(var_name, new_value)?;
+            return self.update_config_files(var_name, new_value);
+        }
+
+        if cfg!(windows) {
+            return self.update_windows_env_var(var_name, new_value);
+        }
+
+        self.update_shell_config_files(var_name, new_value)
+    }
+
+    /// Update or add an environment variable in shell config files
+    fn update_shell_config_files(&self, var_name: &str, new_value: &str) -> Result<()> {
         info!("Updating environment variable: {}", var_name);
 
-        // Common shell config files
-        let config_files = vec![".bashrc", ".bash_profile", ".zshrc", ".profile"];
+        let (config_files, create_missing) = self.shell_file_paths();
 
         let mut updated_count = 0;
 
-        for config_file in config_files {
-            let config_path = self.home_dir.join(config_file);
+        for config_path in config_files {
+            if create_missing && !config_path.exists() {
+                if let Some(parent) = config_path.parent() {
+                    fs::create_dir_all(parent).with_context(|| {
+                        format!("Failed to create directory {}", parent.display())
+                    })?;
+                }
+                fs::write(&config_path, "")
+                    .with_context(|| format!("Failed to create {}", config_path.display()))?;
+                info!("Created {}", config_path.display());
+            }
 
             if config_path.exists() {
                 match self.update_in_file(&config_path, var_name, new_value) {
                     Ok(true) => {
-                        info!("Updated {} in {}", var_name, config_file);
+                        info!("Updated {} in {}", var_name, config_path.display());
                         updated_count += 1;
                     }
                     Ok(false) => {
-                        debug!("{} not found in {}, appending", var_name, config_file);
+                        debug!(
+                            "{} not found in {}, appending",
+                            var_name,
+                            config_path.display()
+                        );
                         self.append_to_file(&config_path, var_name, new_value)?;
                         updated_count += 1;
                     }
                     Err(e) => {
-                        warn!("Failed to update {}: {}", config_file, e);
+                        warn!("Failed to update {}: {}", config_path.display(), e);
                     }
                 }
             }
@@ -66,6 +267,232 @@ impl EnvUpdater {
         Ok(())
     }
 
+    /// Update or add a variable in every configured .env file
+    fn update_dotenv_files(&self, var_name: &str, new_value: &str) -> Result<()> {
+        if self.dotenv_files.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            "Updating {} in {} .env file(s)",
+            var_name,
+            self.dotenv_files.len()
+        );
+
+        for path in &self.dotenv_files {
+            Self::update_dotenv_file(path, var_name, new_value)
+                .with_context(|| format!("Failed to update {}", path.display()))?;
+            info!("Updated {} in {}", var_name, path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Set a variable in a single .env file, preserving comments and other entries
+    ///
+    /// Missing files are created.
+    fn update_dotenv_file(path: &Path, var_name: &str, new_value: &str) -> Result<()> {
+        let content = if path.exists() {
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+        } else {
+            String::new()
+        };
+
+        fs::write(path, Self::set_assignment(&content, var_name, new_value))
+            .with_context(|| format!("Failed to write to {}", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Update or add a variable in every configured systemd environment file,
+    /// then restart the units that depend on them
+    fn update_systemd_files(&self, var_name: &str, new_value: &str) -> Result<()> {
+        let mut units_to_restart: Vec<&str> = Vec::new();
+
+        for file in &self.systemd_files {
+            let path = Path::new(&file.path);
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+
+            Self::write_atomically(path, &Self::set_assignment(&content, var_name, new_value))
+                .with_context(|| format!("Failed to update {}", path.display()))?;
+            info!("Updated {} in {}", var_name, path.display());
+
+            if let Some(ref unit) = file.restart_unit {
+                if !units_to_restart.contains(&unit.as_str()) {
+                    units_to_restart.push(unit);
+                }
+            }
+        }
+
+        for unit in units_to_restart {
+            Self::restart_unit(unit)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a variable's value into every structured config file mapped to it
+    fn update_config_files(&self, var_name: &str, new_value: &str) -> Result<()> {
+        for file in self.config_files.iter().filter(|f| f.env_var == var_name) {
+            let format = file
+                .format
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(|e: String| anyhow::anyhow!(e))?;
+
+            structured_file::set_value_in_file(
+                &self.expand_home(&file.path),
+                format,
+                &file.key_path,
+                new_value,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Set `var_name` in KEY=value formatted content, preserving comments and other entries
+    ///
+    /// Existing definitions keep their `export ` prefix; missing variables are appended.
+    fn set_assignment(content: &str, var_name: &str, new_value: &str) -> String {
+        let mut found = false;
+        let mut new_content = String::with_capacity(content.len() + var_name.len() + 16);
+
+        for line in content.lines() {
+            let trimmed = line.trim_start();
+            let (export_prefix, assignment) = match trimmed.strip_prefix("export ") {
+                Some(rest) => ("export ", rest.trim_start()),
+                None => ("", trimmed),
+            };
+
+            let is_target = assignment
+                .split_once('=')
+                .map(|(key, _)| key.trim() == var_name)
+                .unwrap_or(false);
+
+            if is_target && !trimmed.starts_with('#') {
+                new_content.push_str(export_prefix);
+                new_content.push_str(&dotenv::format_line(var_name, new_value));
+                found = true;
+            } else {
+                new_content.push_str(line);
+            }
+            new_content.push('\n');
+        }
+
+        if !found {
+            new_content.push_str(&dotenv::format_line(var_name, new_value));
+            new_content.push('\n');
+        }
+
+        new_content
+    }
+
+    /// Replace a file via write-to-temp and rename, keeping its permissions and owner
+    ///
+    /// The temporary file is created with `0600` so the secret is never briefly
+    /// world-readable, then given the original file's mode before the rename.
+    fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid file path: {}", path.display()))?;
+        let tmp_path = path.with_file_name(format!(".{}.asr-tmp", file_name.to_string_lossy()));
+        let original = fs::metadata(path).ok();
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let write_result = (|| -> Result<()> {
+            use std::io::Write;
+            let mut file = options
+                .open(&tmp_path)
+                .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()?;
+
+            if let Some(ref metadata) = original {
+                fs::set_permissions(&tmp_path, metadata.permissions())
+                    .context("Failed to preserve file permissions")?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::MetadataExt;
+                    std::os::unix::fs::chown(&tmp_path, Some(metadata.uid()), Some(metadata.gid()))
+                        .context("Failed to preserve file ownership")?;
+                }
+            }
+
+            fs::rename(&tmp_path, path)
+                .with_context(|| format!("Failed to replace {}", path.display()))
+        })();
+
+        if write_result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        write_result
+    }
+
+    /// Restart a systemd unit so it picks up the updated environment file
+    fn restart_unit(unit: &str) -> Result<()> {
+        info!("Restarting systemd unit: {}", unit);
+
+        let status = Command::new("systemctl")
+            .args(["restart", unit])
+            .status()
+            .context("Failed to run systemctl")?;
+
+        if !status.success() {
+            anyhow::bail!("systemctl restart {} exited with {}", unit, status);
+        }
+
+        Ok(())
+    }
+
+    /// Persist an environment variable in the Windows user environment
+    fn update_windows_env_var(&self, var_name: &str, new_value: &str) -> Result<()> {
+        info!("Updating Windows user environment variable: {}", var_name);
+
+        let status = Self::windows_env_command(var_name, new_value)
+            .status()
+            .context("Failed to run powershell to update the user environment")?;
+
+        if !status.success() {
+            anyhow::bail!(
+                "powershell exited with {} while setting user environment variable {}",
+                status,
+                var_name
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Build the PowerShell invocation that sets a user environment variable
+    ///
+    /// The name and value are passed through the child's environment rather than
+    /// its command line so the secret never shows up in process listings, and
+    /// `SetEnvironmentVariable` avoids the 1024-character truncation of `setx`.
+    fn windows_env_command(var_name: &str, new_value: &str) -> Command {
+        let mut command = Command::new("powershell");
+        command
+            .args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                "[Environment]::SetEnvironmentVariable($env:ASR_ENV_NAME, $env:ASR_ENV_VALUE, 'User')",
+            ])
+            .env("ASR_ENV_NAME", var_name)
+            .env("ASR_ENV_VALUE", new_value);
+        command
+    }
+
     /// Update environment variable in a specific file
     fn update_in_file(&self, path: &Path, var_name: &str, new_value: &str) -> Result<bool> {
         let content = fs::read_to_string(path)
@@ -129,11 +556,9 @@ impl EnvUpdater {
     pub fn remove_env_var(&self, var_name: &str) -> Result<()> {
         info!("Removing environment variable: {}", var_name);
 
-        let config_files = vec![".bashrc", ".bash_profile", ".zshrc", ".profile"];
-
-        for config_file in config_files {
-            let config_path = self.home_dir.join(config_file);
+        let (config_files, _) = self.shell_file_paths();
 
+        for config_path in config_files {
             if config_path.exists() {
                 self.remove_from_file(&config_path, var_name)?;
             }
@@ -194,6 +619,41 @@ impl EnvUpdater {
     }
 }
 
+/// Secret metadata key listing the variable(s) a secret updates (comma-separated)
+pub const ENV_VAR_METADATA_KEY: &str = "env_var";
+
+/// Resolve the environment variable names a secret should update
+///
+/// An `env_var` entry in the secret's metadata wins, then the `[env.mappings]`
+/// table, and finally the naming convention (`myapp/database` -> `MYAPP_DATABASE`).
+pub fn env_var_names(
+    secret_path: &str,
+    metadata: &HashMap<String, String>,
+    mappings: &HashMap<String, EnvVarNames>,
+) -> Vec<String> {
+    if let Some(value) = metadata.get(ENV_VAR_METADATA_KEY) {
+        let names: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        if !names.is_empty() {
+            return names;
+        }
+    }
+
+    let path = secret_path.trim_matches('/');
+    if let Some(names) = mappings.get(path).or_else(|| mappings.get(secret_path)) {
+        let names = names.names();
+        if !names.is_empty() {
+            return names;
+        }
+    }
+
+    vec![path.replace('/', "_").to_uppercase()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -246,4 +706,162 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_windows_env_command_keeps_value_off_command_line() {
+        let command = EnvUpdater::windows_env_command("MY_SECRET", "s3cr3t");
+
+        assert_eq!(command.get_program(), "powershell");
+        assert!(command
+            .get_args()
+            .all(|arg| !arg.to_string_lossy().contains("s3cr3t")));
+
+        let envs: Vec<_> = command.get_envs().collect();
+        assert!(envs.contains(&(
+            std::ffi::OsStr::new("ASR_ENV_VALUE"),
+            Some(std::ffi::OsStr::new("s3cr3t"))
+        )));
+    }
+
+    #[test]
+    fn test_update_dotenv_file_preserves_other_entries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let env_file = temp_dir.path().join(".env");
+        fs::write(
+            &env_file,
+            "# database settings\nDB_HOST=localhost\nexport DB_PASSWORD=old\n# DB_PASSWORD=commented\n",
+        )?;
+
+        let updater = EnvUpdater::with_home_dir(temp_dir.path().to_path_buf())
+            .with_dotenv_files(vec![env_file.clone()]);
+        updater.update_env_var("DB_PASSWORD", "new value")?;
+        updater.update_env_var("API_TOKEN", "abc123")?;
+
+        let content = fs::read_to_string(&env_file)?;
+        assert_eq!(
+            content,
+            "# database settings\nDB_HOST=localhost\nexport DB_PASSWORD=\"new value\"\n# DB_PASSWORD=commented\nAPI_TOKEN=abc123\n"
+        );
+
+        // Shell config files are left alone when dotenv files are configured
+        assert!(!temp_dir.path().join(".bashrc").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_dotenv_file_creates_missing_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let env_file = temp_dir.path().join("new.env");
+
+        let updater = EnvUpdater::with_home_dir(temp_dir.path().to_path_buf())
+            .with_dotenv_files(vec![env_file.clone()]);
+        updater.update_env_var("MY_SECRET", "value")?;
+
+        assert_eq!(fs::read_to_string(&env_file)?, "MY_SECRET=value\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_systemd_file_is_atomic_and_keeps_mode() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let env_file = temp_dir.path().join("env");
+        fs::write(&env_file, "DB_HOST=db.internal\nDB_PASSWORD=old\n")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&env_file, fs::Permissions::from_mode(0o640))?;
+        }
+
+        let updater =
+            EnvUpdater::with_home_dir(temp_dir.path().to_path_buf()).with_systemd_files(vec![
+                SystemdEnvFileConfig {
+                    path: env_file.to_string_lossy().to_string(),
+                    restart_unit: None,
+                },
+            ]);
+        updater.update_env_var("DB_PASSWORD", "n3w$ecret")?;
+
+        let content = fs::read_to_string(&env_file)?;
+        assert_eq!(content, "DB_HOST=db.internal\nDB_PASSWORD=\"n3w$ecret\"\n");
+        assert!(!temp_dir.path().join(".env.asr-tmp").exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&env_file)?.permissions().mode() & 0o777;
+            assert_eq!(mode, 0o640);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_structured_config_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config_file = temp_dir.path().join("config.toml");
+        fs::write(&config_file, "[database]\npassword = \"old\"\n")?;
+
+        let updater =
+            EnvUpdater::with_home_dir(temp_dir.path().to_path_buf()).with_config_files(vec![
+                StructuredFileConfig {
+                    path: config_file.to_string_lossy().to_string(),
+                    key_path: "database.password".to_string(),
+                    env_var: "DB_PASSWORD".to_string(),
+                    format: None,
+                },
+            ]);
+        updater.update_env_var("DB_PASSWORD", "new")?;
+        updater.update_env_var("UNRELATED", "ignored")?;
+
+        assert_eq!(
+            fs::read_to_string(&config_file)?,
+            "[database]\npassword = \"new\"\n"
+        );
+        assert!(!temp_dir.path().join(".bashrc").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_shell_file_mode() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let bashrc = temp_dir.path().join(".bashrc");
+        fs::write(&bashrc, "# existing config\n")?;
+        let env_sh = temp_dir.path().join(".config/asr/env.sh");
+
+        let updater = EnvUpdater::with_home_dir(temp_dir.path().to_path_buf())
+            .with_shell_files(vec![env_sh.clone()]);
+        updater.update_env_var("MY_SECRET", "new_value")?;
+
+        assert!(fs::read_to_string(&env_sh)?.contains("export MY_SECRET=\"new_value\""));
+        assert_eq!(fs::read_to_string(&bashrc)?, "# existing config\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_var_names_resolution() {
+        let mut mappings = HashMap::new();
+        mappings.insert(
+            "myapp/database".to_string(),
+            EnvVarNames::Many(vec!["DB_PASSWORD".to_string(), "PGPASSWORD".to_string()]),
+        );
+        let no_metadata = HashMap::new();
+
+        assert_eq!(
+            env_var_names("myapp/database", &no_metadata, &mappings),
+            vec!["DB_PASSWORD", "PGPASSWORD"]
+        );
+        assert_eq!(
+            env_var_names("myapp/api", &no_metadata, &mappings),
+            vec!["MYAPP_API"]
+        );
+
+        let mut metadata = HashMap::new();
+        metadata.insert("env_var".to_string(), "APP_DB_PASS, DB_PASS".to_string());
+        assert_eq!(
+            env_var_names("myapp/database", &metadata, &mappings),
+            vec!["APP_DB_PASS", "DB_PASS"]
+        );
+    }
 }
@@ -0,0 +1,169 @@
+//! External subcommands (`asr foo` runs `asr-foo` from `PATH`)
+//!
+//! Like git and cargo, commands `asr` does not know are looked up as
+//! executables named `asr-<name>` on `PATH`, so teams can add their own
+//! commands without forking the CLI. The remaining arguments are passed on
+//! unchanged, and the resolved settings are passed in the environment:
+//!
+//! - `ASR_BIN`: path of the running `asr`, to call back into it
+//! - `ROTATOR_CONFIG`, `ROTATOR_CONFIG_FORMAT`, `ASR_PROFILE`: the config file,
+//!   so `$ASR_BIN` picks up the same settings
+//! - `SECRET_BACKEND`: the selected backend
+//! - `VAULT_ADDR`, `VAULT_MOUNT`, `AWS_REGION`: backend location, when configured
+//! - `ASR_RUN_ID`: the run ID of this invocation (see [`crate::logging::run_id`])
+//!
+//! The command's exit code becomes `asr`'s.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Prefix of external command executables
+const PREFIX: &str = "asr-";
+
+/// An external command exited unsuccessfully; `asr` exits with the same code
+#[derive(Debug, thiserror::Error)]
+#[error("{PREFIX}{name} exited with code {code}")]
+pub struct ExternalExit {
+    pub name: String,
+    pub code: u8,
+}
+
+/// Executable for `asr <name>` in the directories of `path` (a `PATH` value)
+pub fn find(name: &str, path: &OsStr) -> Option<PathBuf> {
+    std::env::split_paths(path)
+        .map(|dir| dir.join(format!("{}{}", PREFIX, name)))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// Names of the external commands available in `path`, sorted
+pub fn discover(path: &OsStr) -> Vec<String> {
+    let mut names = BTreeSet::new();
+    for dir in std::env::split_paths(path) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(name) = file_name.to_str().and_then(|n| n.strip_prefix(PREFIX)) else {
+                continue;
+            };
+            if !name.is_empty() && is_executable(&entry.path()) {
+                names.insert(name.to_string());
+            }
+        }
+    }
+    names.into_iter().collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Fail with the available external commands unless `asr <name>` exists
+pub fn resolve(name: &str) -> Result<PathBuf> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    if let Some(program) = find(name, &path) {
+        return Ok(program);
+    }
+    let available = discover(&path);
+    let hint = if available.is_empty() {
+        String::new()
+    } else {
+        format!(" External commands on PATH: {}.", available.join(", "))
+    };
+    anyhow::bail!(
+        "Unknown command '{}': not built in, and no {}{} found on PATH.{} \
+         Run `asr --help` for the built-in commands.",
+        name,
+        PREFIX,
+        name,
+        hint
+    )
+}
+
+/// Run `program` with `args`, adding `env` to the inherited environment
+///
+/// Returns [`ExternalExit`] when it exits with a non-zero code.
+pub async fn run(program: &Path, args: &[String], env: &[(&str, String)]) -> Result<()> {
+    let name = program
+        .file_name()
+        .and_then(OsStr::to_str)
+        .and_then(|n| n.strip_prefix(PREFIX))
+        .unwrap_or_default()
+        .to_string();
+    let status = tokio::process::Command::new(program)
+        .args(args)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .status()
+        .await
+        .with_context(|| format!("Failed to run {:?}", program))?;
+    if status.success() {
+        return Ok(());
+    }
+    // Killed by a signal: report it as a generic failure
+    let code = status.code().map_or(1, |code| code.clamp(1, 255) as u8);
+    Err(ExternalExit { name, code }.into())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn script(dir: &Path, name: &str, body: &str, mode: u32) -> Result<PathBuf> {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body))?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+        Ok(path)
+    }
+
+    #[tokio::test]
+    async fn test_find_and_run() -> Result<()> {
+        let first = TempDir::new()?;
+        let second = TempDir::new()?;
+        let out = first.path().join("out");
+        script(
+            first.path(),
+            "asr-hello",
+            &format!("echo \"$1 $SECRET_BACKEND\" > {}", out.display()),
+            0o755,
+        )?;
+        script(second.path(), "asr-hello", "exit 9", 0o755)?;
+        script(second.path(), "asr-fail", "exit 4", 0o755)?;
+        script(second.path(), "asr-notes", "", 0o644)?;
+        let path = std::env::join_paths([first.path(), second.path()])?;
+
+        assert_eq!(discover(&path), ["fail", "hello"]);
+        assert!(find("notes", &path).is_none());
+
+        // The first match on PATH wins
+        let hello = find("hello", &path).unwrap();
+        assert!(hello.starts_with(first.path()));
+        run(
+            &hello,
+            &["world".to_string()],
+            &[("SECRET_BACKEND", "vault".to_string())],
+        )
+        .await?;
+        assert_eq!(std::fs::read_to_string(&out)?, "world vault\n");
+
+        let err = run(&find("fail", &path).unwrap(), &[], &[])
+            .await
+            .unwrap_err();
+        let exit = err.downcast_ref::<ExternalExit>().unwrap();
+        assert_eq!((exit.name.as_str(), exit.code), ("fail", 4));
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! Secret value generation with per-secret overrides
+//!
+//! The global `[rotation]` settings decide how new secrets look by default.
+//! Individual secrets can deviate through metadata, e.g. a legacy system that
+//! only accepts 16 alphanumeric characters:
+//!
+//! - `secret_length`: number of characters to generate
+//! - `generator_style`: `random` (default), `alphanumeric`, `hex`, `numeric`,
+//!   or `wireguard` (a base64 WireGuard private key; length and charset are
+//!   ignored)
+//! - `charset`: explicit characters to draw from (overrides the style's set)
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Metadata key overriding the generated secret length
+pub const SECRET_LENGTH_KEY: &str = "secret_length";
+/// Metadata key overriding the characters used for generation
+pub const CHARSET_KEY: &str = "charset";
+/// Metadata key selecting the generator style
+pub const GENERATOR_STYLE_KEY: &str = "generator_style";
+
+/// Upper bound for generated secrets, to catch typos like `secret_length = 32000`
+const MAX_SECRET_LENGTH: usize = 4096;
+
+const RANDOM_CHARSET: &str =
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!@#$%^&*";
+const ALPHANUMERIC_CHARSET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const HEX_CHARSET: &str = "0123456789abcdef";
+const NUMERIC_CHARSET: &str = "0123456789";
+
+/// Built-in character sets for generated secrets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GeneratorStyle {
+    /// Letters, digits, and symbols
+    #[default]
+    Random,
+    /// Letters and digits only
+    Alphanumeric,
+    /// Lowercase hexadecimal
+    Hex,
+    /// Digits only (e.g., PINs)
+    Numeric,
+    /// A WireGuard private key: 32 random bytes, base64-encoded
+    WireGuard,
+}
+
+impl GeneratorStyle {
+    fn charset(&self) -> &'static str {
+        match self {
+            GeneratorStyle::Random => RANDOM_CHARSET,
+            GeneratorStyle::Alphanumeric => ALPHANUMERIC_CHARSET,
+            GeneratorStyle::Hex => HEX_CHARSET,
+            GeneratorStyle::Numeric => NUMERIC_CHARSET,
+            // Not drawn from characters; see `GeneratorOptions::generate`
+            GeneratorStyle::WireGuard => "",
+        }
+    }
+}
+
+impl std::str::FromStr for GeneratorStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(GeneratorStyle::Random),
+            "alphanumeric" => Ok(GeneratorStyle::Alphanumeric),
+            "hex" => Ok(GeneratorStyle::Hex),
+            "numeric" => Ok(GeneratorStyle::Numeric),
+            "wireguard" => Ok(GeneratorStyle::WireGuard),
+            _ => Err(format!(
+                "Unknown generator style: {}. Supported: random, alphanumeric, hex, numeric, wireguard",
+                s
+            )),
+        }
+    }
+}
+
+/// How to generate a new secret value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratorOptions {
+    pub length: usize,
+    pub style: GeneratorStyle,
+    /// Explicit characters to use instead of the style's character set
+    pub charset: Option<String>,
+}
+
+impl GeneratorOptions {
+    /// Default options for the given length
+    pub fn new(length: usize) -> Self {
+        Self {
+            length,
+            style: GeneratorStyle::default(),
+            charset: None,
+        }
+    }
+
+    /// Apply overrides from secret metadata on top of the default length
+    ///
+    /// Invalid overrides are errors rather than being ignored, so a secret is
+    /// never rotated to a value its consumer cannot accept.
+    pub fn from_metadata(
+        metadata: &HashMap<String, String>,
+        default_length: usize,
+    ) -> Result<Self> {
+        let mut options = Self::new(default_length);
+
+        if let Some(length) = metadata.get(SECRET_LENGTH_KEY) {
+            options.length = length.trim().parse().with_context(|| {
+                format!("Invalid {} in metadata: '{}'", SECRET_LENGTH_KEY, length)
+            })?;
+        }
+
+        if let Some(style) = metadata.get(GENERATOR_STYLE_KEY) {
+            options.style = style.trim().parse().map_err(|e: String| {
+                anyhow::anyhow!("Invalid {} in metadata: {}", GENERATOR_STYLE_KEY, e)
+            })?;
+        }
+
+        if let Some(charset) = metadata.get(CHARSET_KEY) {
+            options.charset = Some(charset.clone());
+        }
+
+        options.validate()?;
+        Ok(options)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.length == 0 || self.length > MAX_SECRET_LENGTH {
+            anyhow::bail!(
+                "Secret length must be between 1 and {}, got {}",
+                MAX_SECRET_LENGTH,
+                self.length
+            );
+        }
+        if self.charset.as_deref().is_some_and(str::is_empty) {
+            anyhow::bail!("{} must not be empty", CHARSET_KEY);
+        }
+        Ok(())
+    }
+
+    /// Characters values are drawn from
+    pub fn characters(&self) -> &str {
+        self.charset.as_deref().unwrap_or(self.style.charset())
+    }
+
+    /// Generate a random secret with these options
+    pub fn generate(&self) -> String {
+        if self.style == GeneratorStyle::WireGuard {
+            let key: [u8; 32] = rand::thread_rng().gen();
+            return STANDARD.encode(key);
+        }
+
+        let mut charset: Vec<char> = self.characters().chars().collect();
+        // Repeated characters in a custom charset would skew the distribution
+        charset.sort_unstable();
+        charset.dedup();
+
+        let mut rng = rand::thread_rng();
+        (0..self.length)
+            .map(|_| charset[rng.gen_range(0..charset.len())])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults_without_overrides() -> Result<()> {
+        let options = GeneratorOptions::from_metadata(&HashMap::new(), 32)?;
+        assert_eq!(options, GeneratorOptions::new(32));
+        assert_eq!(options.generate().len(), 32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata_overrides() -> Result<()> {
+        let options = GeneratorOptions::from_metadata(
+            &metadata(&[("secret_length", "16"), ("generator_style", "alphanumeric")]),
+            32,
+        )?;
+        let secret = options.generate();
+        assert_eq!(secret.len(), 16);
+        assert!(secret.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        let options = GeneratorOptions::from_metadata(&metadata(&[("charset", "ab")]), 20)?;
+        assert!(options.generate().chars().all(|c| c == 'a' || c == 'b'));
+
+        let options =
+            GeneratorOptions::from_metadata(&metadata(&[("generator_style", "wireguard")]), 20)?;
+        assert_eq!(STANDARD.decode(options.generate())?.len(), 32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_overrides() {
+        for entries in [
+            [("secret_length", "sixteen")],
+            [("secret_length", "0")],
+            [("generator_style", "emoji")],
+            [("charset", "")],
+        ] {
+            assert!(GeneratorOptions::from_metadata(&metadata(&entries), 32).is_err());
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::{debug, info};
+
+/// Default location of the Docker Engine API socket
+pub const DEFAULT_DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// What to do with a container after a secret rotates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DockerAction {
+    /// Restart the container
+    #[default]
+    Restart,
+    /// Send a signal (e.g., SIGHUP) to the container's main process
+    Signal,
+    /// Run a command inside the container
+    Exec,
+}
+
+/// Docker API request target with each path segment and query value
+/// percent-encoded, so container names and signals cannot alter the request
+fn api_path(segments: &[&str], query: &[(&str, &str)]) -> String {
+    let mut url = url::Url::parse("http://docker/").expect("static URL is valid");
+    url.path_segments_mut()
+        .expect("http URLs have path segments")
+        .pop_if_empty()
+        .extend(segments);
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+    url[url::Position::BeforePath..].to_string()
+}
+
+/// Minimal Docker Engine API client talking to the local Unix socket
+pub struct DockerClient {
+    socket: PathBuf,
+}
+
+impl DockerClient {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    /// Client for the socket named by `DOCKER_HOST` (`unix://...`) or the default socket
+    pub fn from_env() -> Self {
+        let socket = std::env::var("DOCKER_HOST")
+            .ok()
+            .and_then(|host| host.strip_prefix("unix://").map(str::to_string))
+            .unwrap_or_else(|| DEFAULT_DOCKER_SOCKET.to_string());
+        Self::new(socket)
+    }
+
+    /// Restart a container
+    pub async fn restart(&self, container: &str) -> Result<()> {
+        info!("Restarting Docker container: {}", container);
+        self.expect_success(
+            "POST",
+            &api_path(&["containers", container, "restart"], &[]),
+            None,
+        )
+        .await
+        .with_context(|| format!("Failed to restart container {}", container))?;
+        Ok(())
+    }
+
+    /// Send a signal to a container's main process
+    pub async fn signal(&self, container: &str, signal: &str) -> Result<()> {
+        info!("Sending {} to Docker container: {}", signal, container);
+        self.expect_success(
+            "POST",
+            &api_path(&["containers", container, "kill"], &[("signal", signal)]),
+            None,
+        )
+        .await
+        .with_context(|| format!("Failed to send {} to container {}", signal, container))?;
+        Ok(())
+    }
+
+    /// Run a command inside a container and wait for it to finish
+    pub async fn exec(&self, container: &str, command: &[String]) -> Result<()> {
+        info!("Running {:?} in Docker container: {}", command, container);
+
+        let created = self
+            .expect_success(
+                "POST",
+                &api_path(&["containers", container, "exec"], &[]),
+                Some(serde_json::json!({
+                    "Cmd": command,
+                    "AttachStdout": true,
+                    "AttachStderr": true,
+                })),
+            )
+            .await
+            .with_context(|| format!("Failed to create exec in container {}", container))?;
+        let exec_id = serde_json::from_slice::<serde_json::Value>(&created)
+            .ok()
+            .and_then(|v| v["Id"].as_str().map(str::to_string))
+            .context("Docker did not return an exec ID")?;
+
+        // Without Detach the request only completes once the command exits
+        self.expect_success(
+            "POST",
+            &api_path(&["exec", &exec_id, "start"], &[]),
+            Some(serde_json::json!({ "Detach": false, "Tty": false })),
+        )
+        .await
+        .with_context(|| format!("Failed to run exec in container {}", container))?;
+
+        let inspected = self
+            .expect_success("GET", &api_path(&["exec", &exec_id, "json"], &[]), None)
+            .await?;
+        let exit_code = serde_json::from_slice::<serde_json::Value>(&inspected)
+            .ok()
+            .and_then(|v| v["ExitCode"].as_i64());
+        match exit_code {
+            Some(0) => Ok(()),
+            Some(code) => anyhow::bail!(
+                "Command {:?} in container {} exited with code {}",
+                command,
+                container,
+                code
+            ),
+            None => anyhow::bail!("Could not determine exit code of exec in {}", container),
+        }
+    }
+
+    /// Send a request and fail on a non-2xx status
+    async fn expect_success(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<Vec<u8>> {
+        let (status, body) = self.request(method, path, body).await?;
+        if !(200..300).contains(&status) {
+            let message = serde_json::from_slice::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|v| v["message"].as_str().map(str::to_string))
+                .unwrap_or_else(|| String::from_utf8_lossy(&body).into_owned());
+            anyhow::bail!("Docker API returned {}: {}", status, message.trim());
+        }
+        Ok(body)
+    }
+
+    #[cfg(unix)]
+    async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<(u16, Vec<u8>)> {
+        use http_body_util::{BodyExt, Full};
+        use hyper::body::Bytes;
+        use hyper_util::rt::TokioIo;
+
+        debug!("Docker API request: {} {}", method, path);
+
+        let stream = tokio::net::UnixStream::connect(&self.socket)
+            .await
+            .with_context(|| format!("Failed to connect to Docker socket {:?}", self.socket))?;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .context("Failed to start HTTP connection to Docker")?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!("Docker connection closed with error: {}", e);
+            }
+        });
+
+        let body = match body {
+            Some(body) => serde_json::to_vec(&body).context("Failed to encode Docker request")?,
+            None => Vec::new(),
+        };
+        let request = hyper::Request::builder()
+            .method(method)
+            .uri(path)
+            .header(hyper::header::HOST, "docker")
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .context("Failed to build Docker request")?;
+
+        let response = sender
+            .send_request(request)
+            .await
+            .context("Docker API request failed")?;
+        let status = response.status().as_u16();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .context("Failed to read Docker API response")?
+            .to_bytes();
+
+        Ok((status, body.to_vec()))
+    }
+
+    #[cfg(not(unix))]
+    async fn request(
+        &self,
+        _method: &str,
+        _path: &str,
+        _body: Option<serde_json::Value>,
+    ) -> Result<(u16, Vec<u8>)> {
+        anyhow::bail!("Docker hooks require a Unix socket and are not supported on this platform")
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixListener;
+
+    /// Serve one canned response per connection and collect request lines
+    async fn fake_docker(
+        socket: &Path,
+        responses: Vec<&'static str>,
+    ) -> tokio::task::JoinHandle<Vec<String>> {
+        let listener = UnixListener::bind(socket).unwrap();
+        tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = vec![0; 4096];
+                let read = stream.read(&mut buffer).await.unwrap();
+                let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+                requests.push(request.lines().next().unwrap_or_default().to_string());
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        })
+    }
+
+    #[tokio::test]
+    async fn test_restart_and_signal() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let socket = dir.path().join("docker.sock");
+        let server = fake_docker(
+            &socket,
+            vec![
+                "HTTP/1.1 204 No Content\r\n\r\n",
+                "HTTP/1.1 204 No Content\r\n\r\n",
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 38\r\n\r\n{\"message\":\"No such container: ghost\"}",
+            ],
+        )
+        .await;
+
+        let client = DockerClient::new(&socket);
+        client.restart("web").await?;
+        client.signal("web/../db", "SIGHUP&all=1").await?;
+        let err = client.restart("ghost").await.unwrap_err();
+        assert!(format!("{:#}", err).contains("No such container: ghost"));
+
+        assert_eq!(
+            server.await?,
+            vec![
+                "POST /containers/web/restart HTTP/1.1",
+                "POST /containers/web%2F..%2Fdb/kill?signal=SIGHUP%26all%3D1 HTTP/1.1",
+                "POST /containers/ghost/restart HTTP/1.1",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+//! Post-rotation hooks
+//!
+//! Rotating a secret does not help much if the services using it keep running
+//! with the old value. Hooks run after a secret rotates so those services pick
+//! up the new credential:
+//! - Docker containers: restart, signal, or exec a reload command
+//! - systemd units: reload or restart through `systemctl`
+//! - Re-encryption: ask an application to re-encrypt its data after a
+//!   data-encryption key rotates, tracking progress in the key's metadata
+//!
+//! Hooks are configured in `[[hooks.<type>]]` sections that apply to secrets
+//! matching their `paths`, or per secret through metadata. A failing hook does
+//! not undo the rotation; its error is reported alongside the rotation result.
+
+mod docker;
+mod reencrypt;
+mod systemd;
+
+pub use docker::{DockerAction, DockerClient};
+pub use reencrypt::{
+    complete_reencryption, unfinished_reencryptions, ReencryptHookConfig, ReencryptionState,
+};
+pub use systemd::UnitAction;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::backends::{path_matches, SecretBackend};
+
+/// Metadata key listing Docker containers to restart after rotation (comma-separated)
+pub const DOCKER_RESTART_KEY: &str = "docker_restart";
+
+/// Metadata key listing systemd units to reload after rotation (comma-separated)
+pub const RELOAD_UNITS_KEY: &str = "reload_units";
+
+/// Post-rotation hook configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HooksConfig {
+    /// Docker container hooks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub docker: Vec<DockerHookConfig>,
+
+    /// systemd unit hooks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub systemd: Vec<SystemdHookConfig>,
+
+    /// Re-encryption hooks for data-encryption keys
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reencrypt: Vec<ReencryptHookConfig>,
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        self.docker.is_empty() && self.systemd.is_empty() && self.reencrypt.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DockerHookConfig {
+    /// Container names or IDs
+    pub containers: Vec<String>,
+
+    /// restart (default), signal, or exec
+    #[serde(default)]
+    pub action: DockerAction,
+
+    /// Signal to send for `action = "signal"` (default: SIGHUP)
+    #[serde(default)]
+    pub signal: Option<String>,
+
+    /// Command to run for `action = "exec"` (e.g., ["nginx", "-s", "reload"])
+    #[serde(default)]
+    pub command: Vec<String>,
+
+    /// Secret path patterns that trigger this hook (e.g., ["myapp/*"]); empty means all
+    #[serde(default)]
+    pub paths: Vec<String>,
+
+    /// Docker socket path (default: $DOCKER_HOST or /var/run/docker.sock)
+    #[serde(default)]
+    pub socket: Option<String>,
+}
+
+impl DockerHookConfig {
+    fn applies_to(&self, path: &str) -> bool {
+        self.paths.is_empty() || self.paths.iter().any(|p| path_matches(p, path))
+    }
+
+    fn client(&self) -> DockerClient {
+        match self.socket {
+            Some(ref socket) => DockerClient::new(socket),
+            None => DockerClient::from_env(),
+        }
+    }
+
+    async fn run(&self, container: &str) -> Result<()> {
+        let client = self.client();
+        match self.action {
+            DockerAction::Restart => client.restart(container).await,
+            DockerAction::Signal => {
+                client
+                    .signal(container, self.signal.as_deref().unwrap_or("SIGHUP"))
+                    .await
+            }
+            DockerAction::Exec => {
+                if self.command.is_empty() {
+                    anyhow::bail!("Docker exec hook for {} has no command", container);
+                }
+                client.exec(container, &self.command).await
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemdHookConfig {
+    /// Unit names (e.g., ["nginx.service"])
+    pub units: Vec<String>,
+
+    /// reload, restart, or reload-or-restart (default)
+    #[serde(default)]
+    pub action: UnitAction,
+
+    /// Secret path patterns that trigger this hook; empty means all
+    #[serde(default)]
+    pub paths: Vec<String>,
+
+    /// Manage units of the user service manager (`systemctl --user`)
+    #[serde(default)]
+    pub user: bool,
+}
+
+impl SystemdHookConfig {
+    fn applies_to(&self, path: &str) -> bool {
+        self.paths.is_empty() || self.paths.iter().any(|p| path_matches(p, path))
+    }
+}
+
+/// Split a comma-separated metadata value into its entries
+fn metadata_list(metadata: &HashMap<String, String>, key: &str) -> Vec<String> {
+    metadata
+        .get(key)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Run every hook that applies to a rotated secret
+///
+/// Returns a description of each hook that failed; an empty list means all
+/// hooks succeeded (or none applied).
+pub async fn run_post_rotation(
+    config: &HooksConfig,
+    path: &str,
+    metadata: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    for hook in config.docker.iter().filter(|hook| hook.applies_to(path)) {
+        for container in &hook.containers {
+            if let Err(e) = hook.run(container).await {
+                failures.push(format!("docker {}: {:#}", container, e));
+            }
+        }
+    }
+
+    let client = DockerClient::from_env();
+    for container in metadata_list(metadata, DOCKER_RESTART_KEY) {
+        if let Err(e) = client.restart(&container).await {
+            failures.push(format!("docker {}: {:#}", container, e));
+        }
+    }
+
+    for hook in config.systemd.iter().filter(|hook| hook.applies_to(path)) {
+        for unit in &hook.units {
+            if let Err(e) = systemd::apply(hook.action, unit, hook.user).await {
+                failures.push(format!("systemd {}: {:#}", unit, e));
+            }
+        }
+    }
+
+    for unit in metadata_list(metadata, RELOAD_UNITS_KEY) {
+        if let Err(e) = systemd::apply(UnitAction::default(), &unit, false).await {
+            failures.push(format!("systemd {}: {:#}", unit, e));
+        }
+    }
+
+    failures
+}
+
+/// Run the re-encryption hooks that apply to a rotated key, recording the
+/// re-encryption state in its metadata
+///
+/// Returns a description of each hook that failed, like
+/// [`run_post_rotation`].
+pub async fn run_reencryption(
+    config: &HooksConfig,
+    backend: &dyn SecretBackend,
+    path: &str,
+) -> Vec<String> {
+    reencrypt::run(&config.reencrypt, backend, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_hook_config() {
+        let config: HooksConfig = toml::from_str(
+            r#"
+            [[docker]]
+            containers = ["web", "worker"]
+            paths = ["myapp/*"]
+
+            [[docker]]
+            containers = ["nginx"]
+            action = "exec"
+            command = ["nginx", "-s", "reload"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.docker[0].action, DockerAction::Restart);
+        assert!(config.docker[0].applies_to("myapp/db"));
+        assert!(!config.docker[0].applies_to("other/db"));
+        assert_eq!(config.docker[1].action, DockerAction::Exec);
+        assert!(config.docker[1].applies_to("anything"));
+    }
+
+    #[test]
+    fn test_systemd_hook_config() {
+        let config: HooksConfig = toml::from_str(
+            r#"
+            [[systemd]]
+            units = ["nginx.service"]
+            action = "reload"
+            paths = ["web/*"]
+            "#,
+        )
+        .unwrap();
+
+        assert!(!config.is_empty());
+        assert_eq!(config.systemd[0].action, UnitAction::Reload);
+        assert!(!config.systemd[0].user);
+        assert!(config.systemd[0].applies_to("web/tls"));
+        assert!(!config.systemd[0].applies_to("db/admin"));
+    }
+
+    #[test]
+    fn test_metadata_list() {
+        let metadata =
+            HashMap::from([(DOCKER_RESTART_KEY.to_string(), "web, worker,".to_string())]);
+        assert_eq!(
+            metadata_list(&metadata, DOCKER_RESTART_KEY),
+            vec!["web", "worker"]
+        );
+        assert!(metadata_list(&HashMap::new(), DOCKER_RESTART_KEY).is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+//! Re-encryption hooks for data-encryption keys
+//!
+//! Data encrypted with a key stays encrypted with the old key after that key
+//! rotates, until the application re-encrypts it. A re-encryption hook asks
+//! the application to start, by running a command or POSTing to a URL, and
+//! the key's metadata records how far it got:
+//!
+//! - `reencryption = pending` while the application re-encrypts in the
+//!   background: the command exited with 75, or the URL answered
+//!   `202 Accepted`. `asr reencrypt done <path>` (or `POST /reencrypt/<path>`
+//!   on the REST API) marks it complete.
+//! - `reencryption = complete` once the command exited with 0, or the URL
+//!   answered with any other 2xx status
+//! - `reencryption = failed` when a hook failed
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::backends::{path_matches, SecretBackend};
+use crate::path_filter::MetadataSelector;
+use crate::redact::{self, SecretString};
+use crate::rotation;
+
+/// Metadata key holding the re-encryption state of a rotated key
+pub const REENCRYPTION_KEY: &str = "reencryption";
+
+/// Metadata key holding when the re-encryption state last changed
+pub const REENCRYPTION_UPDATED_KEY: &str = "reencryption_updated";
+
+/// Exit code of a re-encryption command that started re-encrypting in the
+/// background (`EX_TEMPFAIL`)
+const PENDING_EXIT_CODE: i32 = 75;
+
+/// How long to wait for a re-encryption URL to answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How far the data encrypted with a rotated key has been re-encrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReencryptionState {
+    Pending,
+    Complete,
+    Failed,
+}
+
+impl ReencryptionState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReencryptionState::Pending => "pending",
+            ReencryptionState::Complete => "complete",
+            ReencryptionState::Failed => "failed",
+        }
+    }
+
+    /// State recorded in a secret's metadata, if any
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Option<Self> {
+        match metadata.get(REENCRYPTION_KEY)?.as_str() {
+            "pending" => Some(ReencryptionState::Pending),
+            "complete" => Some(ReencryptionState::Complete),
+            "failed" => Some(ReencryptionState::Failed),
+            other => {
+                warn!("Invalid {} '{}'", REENCRYPTION_KEY, other);
+                None
+            }
+        }
+    }
+
+    /// Whether some data may still be encrypted with the previous key
+    pub fn is_unfinished(self) -> bool {
+        self != ReencryptionState::Complete
+    }
+}
+
+impl fmt::Display for ReencryptionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReencryptHookConfig {
+    /// Paths of the data-encryption keys this hook re-encrypts for; empty means all
+    #[serde(default)]
+    pub paths: Vec<String>,
+
+    /// Command to run, with the key's path in `ASR_SECRET_PATH`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+
+    /// URL to POST `{"path": ..., "rotated_at": ...}` to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<SecretString>,
+}
+
+impl ReencryptHookConfig {
+    pub(super) fn applies_to(&self, path: &str) -> bool {
+        self.paths.is_empty() || self.paths.iter().any(|p| path_matches(p, path))
+    }
+
+    /// Ask the application to re-encrypt the data under the key at `path`
+    async fn run(&self, path: &str) -> Result<ReencryptionState> {
+        if self.command.is_empty() && self.url.is_none() {
+            anyhow::bail!("Re-encryption hook has neither a command nor a url");
+        }
+        let mut state = ReencryptionState::Complete;
+        if !self.command.is_empty()
+            && run_command(&self.command, path).await? != ReencryptionState::Complete
+        {
+            state = ReencryptionState::Pending;
+        }
+        if let Some(ref url) = self.url {
+            if call(url, path).await? != ReencryptionState::Complete {
+                state = ReencryptionState::Pending;
+            }
+        }
+        Ok(state)
+    }
+}
+
+async fn run_command(command: &[String], path: &str) -> Result<ReencryptionState> {
+    info!("Running re-encryption command {} for {}", command[0], path);
+    let output = Command::new(&command[0])
+        .args(&command[1..])
+        .env("ASR_SECRET_PATH", path)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}", command[0]))?;
+
+    match output.status.code() {
+        Some(0) => Ok(ReencryptionState::Complete),
+        Some(PENDING_EXIT_CODE) => Ok(ReencryptionState::Pending),
+        _ => anyhow::bail!(
+            "{} exited with {}: {}",
+            command[0],
+            output.status,
+            redact::redact(String::from_utf8_lossy(&output.stderr).trim())
+        ),
+    }
+}
+
+async fn call(url: &SecretString, path: &str) -> Result<ReencryptionState> {
+    let client = crate::http::client_builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to create HTTP client for re-encryption")?;
+    let response = client
+        .post(url.expose_secret())
+        .json(&serde_json::json!({
+            "path": path,
+            "rotated_at": Utc::now().to_rfc3339(),
+        }))
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!(redact::redact(&e.to_string())))
+        .context("Failed to call re-encryption URL")?;
+
+    match response.status() {
+        reqwest::StatusCode::ACCEPTED => Ok(ReencryptionState::Pending),
+        status if status.is_success() => Ok(ReencryptionState::Complete),
+        status => anyhow::bail!("Re-encryption URL returned status {}", status),
+    }
+}
+
+/// Record the re-encryption state of the key at `path`
+pub async fn set_reencryption_state(
+    backend: &dyn SecretBackend,
+    path: &str,
+    state: ReencryptionState,
+) -> Result<()> {
+    let mut metadata = backend
+        .read_metadata(path)
+        .await
+        .with_context(|| format!("Failed to read metadata of {}", path))?;
+    metadata.insert(REENCRYPTION_KEY.to_string(), state.to_string());
+    metadata.insert(
+        REENCRYPTION_UPDATED_KEY.to_string(),
+        Utc::now().to_rfc3339(),
+    );
+    backend
+        .update_metadata(path, metadata)
+        .await
+        .with_context(|| format!("Failed to record re-encryption state of {}", path))
+}
+
+/// Mark the pending (or failed) re-encryption for the key at `path` complete
+pub async fn complete_reencryption(backend: &dyn SecretBackend, path: &str) -> Result<()> {
+    let metadata = backend
+        .read_metadata(path)
+        .await
+        .with_context(|| format!("Failed to read metadata of {}", path))?;
+    match ReencryptionState::from_metadata(&metadata) {
+        None => anyhow::bail!("{} has no re-encryption in progress", path),
+        Some(ReencryptionState::Complete) => {
+            anyhow::bail!("Re-encryption for {} is already complete", path)
+        }
+        Some(_) => set_reencryption_state(backend, path, ReencryptionState::Complete).await,
+    }
+}
+
+/// Run the re-encryption hooks that apply to a rotated key and record in its
+/// metadata how far re-encryption got, returning the failures
+pub(super) async fn run(
+    hooks: &[ReencryptHookConfig],
+    backend: &dyn SecretBackend,
+    path: &str,
+) -> Vec<String> {
+    let hooks: Vec<_> = hooks.iter().filter(|hook| hook.applies_to(path)).collect();
+    if hooks.is_empty() {
+        return Vec::new();
+    }
+
+    let mut failures = Vec::new();
+    // Recorded first so that a crash mid-hook still leaves the key marked
+    if let Err(e) = set_reencryption_state(backend, path, ReencryptionState::Pending).await {
+        failures.push(format!("reencrypt: {:#}", e));
+    }
+
+    let mut state = ReencryptionState::Complete;
+    for hook in hooks {
+        match hook.run(path).await {
+            Ok(ReencryptionState::Complete) => {}
+            Ok(_) if state == ReencryptionState::Complete => state = ReencryptionState::Pending,
+            Ok(_) => {}
+            Err(e) => {
+                state = ReencryptionState::Failed;
+                failures.push(format!("reencrypt: {:#}", e));
+            }
+        }
+    }
+
+    info!("Re-encryption for {} is {}", path, state);
+    if let Err(e) = set_reencryption_state(backend, path, state).await {
+        failures.push(format!("reencrypt: {:#}", e));
+    }
+    failures
+}
+
+/// Keys under `path` whose re-encryption is pending or failed
+pub async fn unfinished_reencryptions(
+    backend: &dyn SecretBackend,
+    path: &str,
+) -> Result<Vec<(String, ReencryptionState)>> {
+    let mut unfinished = Vec::new();
+    for secret_path in
+        rotation::flagged_secrets(backend, path, &MetadataSelector::default()).await?
+    {
+        let metadata = match backend.read_metadata(&secret_path).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!("Failed to read metadata for {}: {}", secret_path, e);
+                continue;
+            }
+        };
+        if let Some(state) = ReencryptionState::from_metadata(&metadata) {
+            if state.is_unfinished() {
+                unfinished.push((secret_path, state));
+            }
+        }
+    }
+    Ok(unfinished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::MemoryBackend;
+
+    fn command_hook(script: &str) -> ReencryptHookConfig {
+        ReencryptHookConfig {
+            paths: vec!["keys/*".to_string()],
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            url: None,
+        }
+    }
+
+    async fn state(backend: &MemoryBackend, path: &str) -> Option<ReencryptionState> {
+        ReencryptionState::from_metadata(&backend.read_metadata(path).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_command_contract() -> Result<()> {
+        let backend = MemoryBackend::new()
+            .with_secret("keys/dek", [("key", "k1")])
+            .with_secret("app/db", [("password", "p")]);
+        for path in ["keys/dek", "app/db"] {
+            backend
+                .update_metadata(
+                    path,
+                    HashMap::from([("rotation_enabled".to_string(), "true".to_string())]),
+                )
+                .await?;
+        }
+
+        // Only keys matching the hook's paths are tracked
+        assert!(run(&[command_hook("exit 0")], &backend, "app/db")
+            .await
+            .is_empty());
+        assert_eq!(state(&backend, "app/db").await, None);
+
+        let hook = command_hook(r#"test "$ASR_SECRET_PATH" = keys/dek"#);
+        assert!(run(&[hook], &backend, "keys/dek").await.is_empty());
+        assert_eq!(
+            state(&backend, "keys/dek").await,
+            Some(ReencryptionState::Complete)
+        );
+
+        assert!(run(&[command_hook("exit 75")], &backend, "keys/dek")
+            .await
+            .is_empty());
+        assert_eq!(
+            state(&backend, "keys/dek").await,
+            Some(ReencryptionState::Pending)
+        );
+        assert_eq!(
+            unfinished_reencryptions(&backend, "").await?,
+            [("keys/dek".to_string(), ReencryptionState::Pending)]
+        );
+
+        // The previous key is kept past its window until re-encryption is done
+        let mut metadata = backend.read_metadata("keys/dek").await?;
+        metadata.insert(
+            "previous_expires_at".to_string(),
+            "2000-01-01T00:00:00Z".to_string(),
+        );
+        backend.update_metadata("keys/dek", metadata).await?;
+        assert!(rotation::expired_previous_values(&backend, "")
+            .await?
+            .is_empty());
+
+        complete_reencryption(&backend, "keys/dek").await?;
+        assert!(unfinished_reencryptions(&backend, "").await?.is_empty());
+        assert_eq!(
+            rotation::expired_previous_values(&backend, "").await?,
+            ["keys/dek"]
+        );
+        assert!(complete_reencryption(&backend, "keys/dek").await.is_err());
+        assert!(complete_reencryption(&backend, "app/db").await.is_err());
+
+        let failures = run(
+            &[command_hook("echo boom >&2; exit 1")],
+            &backend,
+            "keys/dek",
+        )
+        .await;
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].contains("boom"));
+        assert_eq!(
+            state(&backend, "keys/dek").await,
+            Some(ReencryptionState::Failed)
+        );
+        Ok(())
+    }
+}
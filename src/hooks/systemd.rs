@@ -0,0 +1,78 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tracing::info;
+
+/// What to do with a systemd unit after a secret rotates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnitAction {
+    /// Reload the unit's configuration (e.g., nginx, postgres)
+    Reload,
+    /// Restart the unit
+    Restart,
+    /// Reload if the unit supports it, otherwise restart
+    #[default]
+    ReloadOrRestart,
+}
+
+impl UnitAction {
+    fn verb(self) -> &'static str {
+        match self {
+            UnitAction::Reload => "reload",
+            UnitAction::Restart => "restart",
+            UnitAction::ReloadOrRestart => "reload-or-restart",
+        }
+    }
+}
+
+/// Arguments passed to `systemctl` for a unit action
+fn systemctl_args(action: UnitAction, unit: &str, user: bool) -> Vec<&str> {
+    let mut args = Vec::with_capacity(3);
+    if user {
+        args.push("--user");
+    }
+    args.push(action.verb());
+    args.push(unit);
+    args
+}
+
+/// Apply `action` to a systemd unit through `systemctl`
+pub async fn apply(action: UnitAction, unit: &str, user: bool) -> Result<()> {
+    info!("Running systemctl {} for unit: {}", action.verb(), unit);
+
+    let output = Command::new("systemctl")
+        .args(systemctl_args(action, unit, user))
+        .output()
+        .await
+        .context("Failed to run systemctl")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "systemctl {} {} exited with {}: {}",
+            action.verb(),
+            unit,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_systemctl_args() {
+        assert_eq!(
+            systemctl_args(UnitAction::ReloadOrRestart, "nginx.service", false),
+            vec!["reload-or-restart", "nginx.service"]
+        );
+        assert_eq!(
+            systemctl_args(UnitAction::Restart, "app.service", true),
+            vec!["--user", "restart", "app.service"]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Outbound HTTP settings shared by every client
+//!
+//! Rotation hosts often reach Vault, AWS and target APIs only through an
+//! egress proxy. The `[http]` section is applied once at startup with
+//! [`configure`], and every HTTP client asr builds (Vault, AWS Secrets
+//! Manager, API targets, OAuth2, ACME and notification webhooks) picks it up
+//! through [`client_builder`] or [`aws_http_client`].
+
+use anyhow::{Context, Result};
+use aws_smithy_http_client::proxy::ProxyConfig;
+use aws_smithy_http_client::tls::{rustls_provider::CryptoMode, Provider};
+use aws_smithy_http_client::{Builder as AwsClientBuilder, Connector};
+use aws_smithy_runtime_api::client::http::SharedHttpClient;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+/// `[http]` configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// Proxy for all outbound requests (e.g., "http://proxy.corp:3128")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    /// Hosts reached directly, bypassing the proxy (e.g., ["localhost", ".corp"])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub no_proxy: Vec<String>,
+}
+
+impl HttpConfig {
+    pub fn is_empty(&self) -> bool {
+        self.proxy.is_none() && self.no_proxy.is_empty()
+    }
+
+    /// `no_proxy` as the comma-separated list proxy libraries expect
+    fn no_proxy_list(&self) -> String {
+        self.no_proxy.join(",")
+    }
+}
+
+/// Settings applied to clients built from now on
+static SETTINGS: RwLock<Option<HttpConfig>> = RwLock::new(None);
+
+/// Apply `[http]` settings to all HTTP clients built after this call
+pub fn configure(config: &HttpConfig) -> Result<()> {
+    if let Some(ref proxy) = config.proxy {
+        reqwest::Proxy::all(proxy).with_context(|| format!("Invalid [http] proxy: {}", proxy))?;
+    }
+
+    *SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = Some(config.clone());
+    Ok(())
+}
+
+fn settings() -> HttpConfig {
+    SETTINGS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+/// A `reqwest` client builder with the configured proxy
+///
+/// Without a configured proxy, reqwest's usual `HTTPS_PROXY`/`NO_PROXY`
+/// environment handling applies.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    apply(reqwest::Client::builder(), &settings())
+}
+
+fn apply(builder: reqwest::ClientBuilder, config: &HttpConfig) -> reqwest::ClientBuilder {
+    let Some(ref url) = config.proxy else {
+        return builder;
+    };
+    match reqwest::Proxy::all(url) {
+        Ok(proxy) => {
+            builder.proxy(proxy.no_proxy(reqwest::NoProxy::from_string(&config.no_proxy_list())))
+        }
+        // Rejected by `configure`, so only reachable when it was skipped
+        Err(_) => builder,
+    }
+}
+
+/// HTTP client for the AWS SDK with the configured proxy
+///
+/// Returns `None` when no proxy is configured, leaving the SDK's default
+/// client in place.
+pub fn aws_http_client() -> Result<Option<SharedHttpClient>> {
+    let config = settings();
+    let Some(ref url) = config.proxy else {
+        return Ok(None);
+    };
+
+    let mut proxy = ProxyConfig::all(url.as_str())
+        .map_err(|e| anyhow::anyhow!("Invalid [http] proxy {}: {}", url, e))?;
+    if !config.no_proxy.is_empty() {
+        proxy = proxy.no_proxy(config.no_proxy_list());
+    }
+
+    Ok(Some(AwsClientBuilder::new().build_with_connector_fn(
+        move |settings, components| {
+            let mut builder = Connector::builder();
+            builder.set_connector_settings(settings.cloned());
+            if let Some(components) = components {
+                builder.set_sleep_impl(components.sleep_impl());
+            }
+            builder
+                .proxy_config(proxy.clone())
+                .tls_provider(Provider::Rustls(CryptoMode::AwsLc))
+                .build()
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configure_rejects_invalid_proxy() {
+        let config = HttpConfig {
+            proxy: Some("not a url".to_string()),
+            no_proxy: Vec::new(),
+        };
+        assert!(configure(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_proxy() -> Result<()> {
+        let mut proxy = mockito::Server::new_async().await;
+        let mock = proxy
+            .mock("GET", mockito::Matcher::Any)
+            .match_header("host", "vault.internal:8200")
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let config = HttpConfig {
+            proxy: Some(proxy.url()),
+            no_proxy: vec!["localhost".to_string()],
+        };
+        let client = apply(reqwest::Client::builder(), &config).build()?;
+        let response = client
+            .get("http://vault.internal:8200/v1/sys/health")
+            .send()
+            .await?;
+        assert_eq!(response.status(), 200);
+        mock.assert_async().await;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+//! Secrets inventory for CMDB and asset systems (`asr inventory`)
+//!
+//! Exports every secret under a path, flagged for rotation or not, with its
+//! owner, target user, rotation policy, last rotation and due date, plus its
+//! full metadata, as JSON or CSV for ingestion into an asset inventory.
+//! Secret values are never read.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::backends::SecretBackend;
+use crate::certs;
+use crate::compliance::csv_field;
+use crate::report::read_all_metadata;
+use crate::rotation;
+
+/// Output format for the inventory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum InventoryFormat {
+    Json,
+    Csv,
+}
+
+impl InventoryFormat {
+    /// Pick the format from an output file extension (JSON unless `.csv`)
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => InventoryFormat::Csv,
+            _ => InventoryFormat::Json,
+        }
+    }
+}
+
+/// Rotation state of an inventoried secret
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InventoryStatus {
+    Current,
+    Overdue,
+    /// Not flagged for rotation
+    Unmanaged,
+    /// Metadata could not be read
+    Error,
+}
+
+impl InventoryStatus {
+    fn label(self) -> &'static str {
+        match self {
+            InventoryStatus::Current => "current",
+            InventoryStatus::Overdue => "overdue",
+            InventoryStatus::Unmanaged => "unmanaged",
+            InventoryStatus::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryEntry {
+    pub path: String,
+    pub backend: String,
+    pub rotation_enabled: bool,
+    pub owner: Option<String>,
+    /// Target user whose password follows the secret
+    pub target_username: Option<String>,
+    /// Rotation period (e.g., "90 days") or certificate renewal
+    pub policy: Option<String>,
+    pub last_rotated: Option<DateTime<Utc>>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub status: InventoryStatus,
+    /// Failed rotation attempts since the last successful one
+    pub consecutive_failures: u32,
+    /// All of the secret's metadata
+    pub metadata: BTreeMap<String, String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Inventory {
+    pub generated_at: DateTime<Utc>,
+    pub backend: String,
+    pub path: String,
+    pub secrets: Vec<InventoryEntry>,
+}
+
+impl Inventory {
+    /// List every secret under `path` with its metadata
+    pub async fn collect(
+        backend: &dyn SecretBackend,
+        path: &str,
+        default_period: u32,
+    ) -> Result<Self> {
+        let backend_type = backend.backend_type().to_string();
+        let mut secrets = Vec::new();
+        for (secret_path, metadata) in read_all_metadata(backend, path).await? {
+            let entry = match metadata {
+                Ok(metadata) => {
+                    let enabled = rotation::rotation_enabled(&metadata);
+                    let (policy, due_at) = if certs::is_certificate(&metadata) {
+                        (
+                            "certificate renewal".to_string(),
+                            certs::renewal_due_at(&metadata),
+                        )
+                    } else {
+                        (
+                            rotation::rotation_period(&metadata, default_period).to_string(),
+                            rotation::due_at(&metadata, default_period),
+                        )
+                    };
+                    let status = if !enabled {
+                        InventoryStatus::Unmanaged
+                    } else if rotation::needs_rotation(&Some(metadata.clone()), default_period) {
+                        InventoryStatus::Overdue
+                    } else {
+                        InventoryStatus::Current
+                    };
+                    InventoryEntry {
+                        path: secret_path,
+                        backend: backend_type.clone(),
+                        rotation_enabled: enabled,
+                        owner: rotation::owner(&metadata),
+                        target_username: rotation::target_username(&metadata),
+                        policy: enabled.then_some(policy),
+                        last_rotated: rotation::last_rotated(&metadata),
+                        due_at: due_at.filter(|_| enabled),
+                        status,
+                        consecutive_failures: rotation::rotation_failures(&metadata)
+                            .map_or(0, |failures| failures.consecutive_failures),
+                        metadata: metadata.into_iter().collect(),
+                        error: None,
+                    }
+                }
+                Err(e) => InventoryEntry {
+                    path: secret_path,
+                    backend: backend_type.clone(),
+                    rotation_enabled: false,
+                    owner: None,
+                    target_username: None,
+                    policy: None,
+                    last_rotated: None,
+                    due_at: None,
+                    status: InventoryStatus::Error,
+                    consecutive_failures: 0,
+                    metadata: BTreeMap::new(),
+                    error: Some(format!("{:#}", e)),
+                },
+            };
+            secrets.push(entry);
+        }
+        secrets.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(Self {
+            generated_at: Utc::now(),
+            backend: backend_type,
+            path: path.to_string(),
+            secrets,
+        })
+    }
+
+    pub fn render(&self, format: InventoryFormat) -> Result<String> {
+        Ok(match format {
+            InventoryFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            InventoryFormat::Csv => self.render_csv()?,
+        })
+    }
+
+    /// One row per secret; the full metadata is a JSON object in the last column
+    fn render_csv(&self) -> Result<String> {
+        let mut out = String::from(
+            "path,backend,rotation_enabled,owner,target_username,policy,last_rotated,due_at,\
+             status,consecutive_failures,metadata,error\n",
+        );
+        for s in &self.secrets {
+            let date = |d: Option<DateTime<Utc>>| d.map(|d| d.to_rfc3339()).unwrap_or_default();
+            let fields = [
+                s.path.clone(),
+                s.backend.clone(),
+                s.rotation_enabled.to_string(),
+                s.owner.clone().unwrap_or_default(),
+                s.target_username.clone().unwrap_or_default(),
+                s.policy.clone().unwrap_or_default(),
+                date(s.last_rotated),
+                date(s.due_at),
+                s.status.label().to_string(),
+                s.consecutive_failures.to_string(),
+                serde_json::to_string(&s.metadata)?,
+                s.error.clone().unwrap_or_default(),
+            ];
+            let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            let _ = writeln!(out, "{}", row.join(","));
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::backends::MemoryBackend;
+
+    #[tokio::test]
+    async fn test_inventory() -> Result<()> {
+        let backend = MemoryBackend::new()
+            .with_secret("app/db", [("password", "hunter2")])
+            .with_secret("app/plain", [("token", "t0ken")]);
+        rotation::flag_for_rotation(&backend, "app/db", 3).await?;
+        let mut metadata = backend.read_metadata("app/db").await?;
+        metadata.extend(HashMap::from([
+            ("owner".to_string(), "payments".to_string()),
+            ("target_username".to_string(), "app_user".to_string()),
+        ]));
+        backend.update_metadata("app/db", metadata).await?;
+
+        let inventory = Inventory::collect(&backend, "app", 6).await?;
+        assert_eq!(inventory.secrets.len(), 2);
+        let db = &inventory.secrets[0];
+        assert_eq!(db.status, InventoryStatus::Current);
+        assert_eq!(db.owner.as_deref(), Some("payments"));
+        assert_eq!(db.target_username.as_deref(), Some("app_user"));
+        assert_eq!(db.policy.as_deref(), Some("3 months"));
+        assert!(db.due_at.is_some());
+        let plain = &inventory.secrets[1];
+        assert_eq!(plain.status, InventoryStatus::Unmanaged);
+        assert!(plain.policy.is_none());
+
+        let json = inventory.render(InventoryFormat::Json)?;
+        let csv = inventory.render(InventoryFormat::Csv)?;
+        for rendered in [&json, &csv] {
+            assert!(!rendered.contains("hunter2") && !rendered.contains("t0ken"));
+        }
+        let json: serde_json::Value = serde_json::from_str(&json)?;
+        assert_eq!(json["secrets"][0]["metadata"]["rotation_enabled"], "true");
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.starts_with("app/db,memory,true,payments,app_user,3 months,"));
+        assert!(row.contains(",current,0,\"{"));
+        assert_eq!(
+            InventoryFormat::from_path(Path::new("cmdb.CSV")),
+            InventoryFormat::Csv
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,621 @@
+//! Leader election between replicas (`[leader]`)
+//!
+//! Several instances of `asr serve`, `asr listen` or a scheduled `asr auto`
+//! can run side by side for availability, but only the one holding a lease
+//! rotates secrets; the others stand by and take over once the lease
+//! expires. The lease lives either in the secret backend (a lock secret with
+//! the holder and expiry) or in a Kubernetes `Lease` object.
+//!
+//! The leader renews its lease every third of the TTL and stops rotating as
+//! soon as a renewal fails, so it has stepped down before another replica
+//! can take the lease over.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, SecondsFormat, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::backends::{SecretBackend, VersionConflict};
+
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Where the lease is kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaseKind {
+    /// A lock secret in the configured secret backend
+    #[default]
+    Backend,
+    /// A `coordination.k8s.io/v1` Lease, using the pod's service account
+    Kubernetes,
+}
+
+/// `[leader]` configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderConfig {
+    #[serde(default)]
+    pub lease: LeaseKind,
+
+    /// Path of the lock secret for the backend lease
+    #[serde(default = "default_lock_path")]
+    pub path: String,
+
+    /// Name of the Kubernetes Lease
+    #[serde(default = "default_lease_name")]
+    pub name: String,
+
+    /// Namespace of the Kubernetes Lease (defaults to the pod's namespace)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    /// Seconds a lease stays valid without renewal
+    #[serde(default = "default_ttl_seconds")]
+    pub ttl_seconds: u64,
+
+    /// Name of this replica (defaults to the host name and process id)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+}
+
+fn default_lock_path() -> String {
+    "asr/leader".to_string()
+}
+
+fn default_lease_name() -> String {
+    "asr-leader".to_string()
+}
+
+fn default_ttl_seconds() -> u64 {
+    30
+}
+
+impl LeaderConfig {
+    pub fn identity(&self) -> String {
+        self.identity
+            .clone()
+            .unwrap_or_else(|| format!("{}-{}", crate::audit::hostname(), std::process::id()))
+    }
+}
+
+/// Storage for a lease that at most one holder has at a time
+#[async_trait::async_trait]
+pub trait LeaseStore: Send + Sync {
+    /// Take the lease for `identity`, or renew it if `identity` holds it
+    ///
+    /// Returns false while another holder's lease is still valid.
+    async fn try_acquire(&self, identity: &str, ttl: Duration) -> Result<bool>;
+
+    /// Give the lease up early, if `identity` holds it
+    async fn release(&self, identity: &str) -> Result<()>;
+}
+
+/// Lease kept as a lock secret in the secret backend
+///
+/// The secret holds the `holder` and `expires_at`. Every write is a
+/// check-and-set against the version that was read (creating the lock only
+/// if it does not exist yet), so of two replicas racing for a free lease only
+/// one ends up holding it. Backends without check-and-set are refused.
+pub struct BackendLease {
+    backend: Arc<dyn SecretBackend>,
+    path: String,
+}
+
+impl BackendLease {
+    pub fn new(backend: Arc<dyn SecretBackend>, path: &str) -> Result<Self> {
+        if !backend.supports_cas() {
+            anyhow::bail!(
+                "The {} backend has no check-and-set writes, so it can't hold the leader lease; use lease = \"kubernetes\" or a Vault backend",
+                backend.backend_type()
+            );
+        }
+        Ok(Self {
+            backend,
+            path: path.trim_matches('/').to_string(),
+        })
+    }
+
+    /// Current holder, expiry and version of the lock secret, if it exists
+    async fn read(&self) -> Result<Option<(String, Option<DateTime<Utc>>, Option<u64>)>> {
+        // Listing tells a missing lock apart from a backend that is down
+        let (parent, name) = self.path.rsplit_once('/').unwrap_or(("", &self.path));
+        let exists = self
+            .backend
+            .list_secrets(parent)
+            .await
+            .with_context(|| format!("Failed to look up lock {}", self.path))?
+            .iter()
+            .any(|listed| listed == name);
+        if !exists {
+            return Ok(None);
+        }
+
+        let secret = self
+            .backend
+            .read_secret(&self.path)
+            .await
+            .with_context(|| format!("Failed to read lock {}", self.path))?;
+        let holder = secret.data.get("holder").cloned().unwrap_or_default();
+        let expires_at = secret
+            .data
+            .get("expires_at")
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            .map(|at| at.with_timezone(&Utc));
+        Ok(Some((holder, expires_at, secret.version)))
+    }
+
+    async fn write(
+        &self,
+        holder: &str,
+        expires_at: DateTime<Utc>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        let data = HashMap::from([
+            ("holder".to_string(), holder.to_string()),
+            (
+                "expires_at".to_string(),
+                expires_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ),
+        ]);
+        self.backend
+            .write_secret_cas(&self.path, data, version)
+            .await
+            .with_context(|| format!("Failed to write lock {}", self.path))
+    }
+}
+
+#[async_trait::async_trait]
+impl LeaseStore for BackendLease {
+    async fn try_acquire(&self, identity: &str, ttl: Duration) -> Result<bool> {
+        let now = Utc::now();
+        let version = match self.read().await? {
+            Some((holder, Some(expires_at), _))
+                if holder != identity && !holder.is_empty() && expires_at > now =>
+            {
+                return Ok(false)
+            }
+            Some((_, _, version)) => version,
+            // Version 0: create the lock only if nobody else just did
+            None => Some(0),
+        };
+
+        let expires_at = now + ChronoDuration::from_std(ttl)?;
+        match self.write(identity, expires_at, version).await {
+            Ok(()) => Ok(true),
+            // Another replica wrote the lock since it was read
+            Err(e) if e.chain().any(|cause| cause.is::<VersionConflict>()) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn release(&self, identity: &str) -> Result<()> {
+        match self.read().await? {
+            Some((holder, _, version)) if holder == identity => {
+                self.write("", Utc::now(), version).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Lease kept as a Kubernetes `Lease`, updated with optimistic concurrency
+pub struct KubernetesLease {
+    client: reqwest::Client,
+    /// URL of the Lease collection in the namespace
+    leases_url: String,
+    name: String,
+    /// Service account token, re-read on every request since it is rotated
+    token_file: PathBuf,
+}
+
+impl KubernetesLease {
+    pub fn new(
+        client: reqwest::Client,
+        api_url: &str,
+        namespace: &str,
+        name: &str,
+        token_file: PathBuf,
+    ) -> Self {
+        Self {
+            client,
+            leases_url: format!(
+                "{}/apis/coordination.k8s.io/v1/namespaces/{}/leases",
+                api_url.trim_end_matches('/'),
+                namespace
+            ),
+            name: name.to_string(),
+            token_file,
+        }
+    }
+
+    /// Connect to the API server of the cluster this pod runs in
+    pub fn in_cluster(namespace: Option<&str>, name: &str) -> Result<Self> {
+        let host = std::env::var("KUBERNETES_SERVICE_HOST").context(
+            "KUBERNETES_SERVICE_HOST is not set; the Kubernetes lease only works inside a cluster",
+        )?;
+        let port = std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".to_string());
+        let dir = PathBuf::from(SERVICE_ACCOUNT_DIR);
+
+        let namespace = match namespace {
+            Some(namespace) => namespace.to_string(),
+            None => std::fs::read_to_string(dir.join("namespace"))
+                .context("Failed to read the pod's namespace; set [leader] namespace")?
+                .trim()
+                .to_string(),
+        };
+        let ca = std::fs::read(dir.join("ca.crt")).context("Failed to read the cluster CA")?;
+        // The API server is reached directly, never through the proxy
+        let client = reqwest::Client::builder()
+            .add_root_certificate(
+                reqwest::Certificate::from_pem(&ca).context("Invalid cluster CA certificate")?,
+            )
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create Kubernetes API client")?;
+
+        Ok(Self::new(
+            client,
+            &format!("https://{}:{}", host, port),
+            &namespace,
+            name,
+            dir.join("token"),
+        ))
+    }
+
+    fn token(&self) -> Result<String> {
+        Ok(std::fs::read_to_string(&self.token_file)
+            .with_context(|| format!("Failed to read service account token {:?}", self.token_file))?
+            .trim()
+            .to_string())
+    }
+
+    async fn get(&self) -> Result<Option<Value>> {
+        let response = self
+            .client
+            .get(format!("{}/{}", self.leases_url, self.name))
+            .bearer_auth(self.token()?)
+            .send()
+            .await
+            .context("Failed to read Kubernetes lease")?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.json().await?)),
+            status => anyhow::bail!("Reading Kubernetes lease {} returned {}", self.name, status),
+        }
+    }
+
+    /// Create or replace the lease; false if another replica changed it first
+    async fn put(&self, lease: &Value, create: bool) -> Result<bool> {
+        let request = if create {
+            self.client.post(&self.leases_url)
+        } else {
+            self.client
+                .put(format!("{}/{}", self.leases_url, self.name))
+        };
+        let response = request
+            .bearer_auth(self.token()?)
+            .json(lease)
+            .send()
+            .await
+            .context("Failed to write Kubernetes lease")?;
+        match response.status() {
+            StatusCode::CONFLICT => Ok(false),
+            status if status.is_success() => Ok(true),
+            status => anyhow::bail!("Writing Kubernetes lease {} returned {}", self.name, status),
+        }
+    }
+}
+
+/// Kubernetes `MicroTime`
+fn micro_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+#[async_trait::async_trait]
+impl LeaseStore for KubernetesLease {
+    async fn try_acquire(&self, identity: &str, ttl: Duration) -> Result<bool> {
+        let now = Utc::now();
+        let Some(mut lease) = self.get().await? else {
+            let lease = serde_json::json!({
+                "apiVersion": "coordination.k8s.io/v1",
+                "kind": "Lease",
+                "metadata": { "name": self.name },
+                "spec": {
+                    "holderIdentity": identity,
+                    "leaseDurationSeconds": ttl.as_secs(),
+                    "acquireTime": micro_time(now),
+                    "renewTime": micro_time(now),
+                    "leaseTransitions": 0,
+                },
+            });
+            return self.put(&lease, true).await;
+        };
+
+        let spec = &lease["spec"];
+        let holder = spec["holderIdentity"].as_str().unwrap_or_default();
+        let expires_at = spec["renewTime"]
+            .as_str()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            .map(|at| {
+                at.with_timezone(&Utc)
+                    + ChronoDuration::seconds(spec["leaseDurationSeconds"].as_i64().unwrap_or(0))
+            });
+        if !holder.is_empty() && holder != identity && expires_at.is_some_and(|at| at > now) {
+            return Ok(false);
+        }
+
+        let taking_over = holder != identity;
+        let transitions = spec["leaseTransitions"].as_u64().unwrap_or(0);
+        let spec = &mut lease["spec"];
+        if taking_over {
+            spec["acquireTime"] = micro_time(now).into();
+            spec["leaseTransitions"] = (transitions + 1).into();
+        }
+        spec["holderIdentity"] = identity.into();
+        spec["leaseDurationSeconds"] = ttl.as_secs().into();
+        spec["renewTime"] = micro_time(now).into();
+        // `metadata.resourceVersion` is kept, so a concurrent change is a conflict
+        self.put(&lease, false).await
+    }
+
+    async fn release(&self, identity: &str) -> Result<()> {
+        let Some(mut lease) = self.get().await? else {
+            return Ok(());
+        };
+        if lease["spec"]["holderIdentity"].as_str() != Some(identity) {
+            return Ok(());
+        }
+        lease["spec"]["holderIdentity"] = Value::Null;
+        self.put(&lease, false).await?;
+        Ok(())
+    }
+}
+
+/// Keeps trying to hold the lease in the background and tracks whether this
+/// replica is the leader
+pub struct Leadership {
+    is_leader: watch::Receiver<bool>,
+    stop: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl Leadership {
+    /// Try to take the lease, then keep taking or renewing it in the background
+    ///
+    /// Fails if the first attempt cannot reach the lease store at all.
+    pub async fn start(
+        store: Box<dyn LeaseStore>,
+        identity: String,
+        ttl: Duration,
+    ) -> Result<Self> {
+        if ttl < Duration::from_secs(3) {
+            anyhow::bail!("The leader lease TTL must be at least 3 seconds");
+        }
+        let leader = store
+            .try_acquire(&identity, ttl)
+            .await
+            .context("Failed to take the leader lease")?;
+        if leader {
+            info!("{} is the leader", identity);
+        } else {
+            info!("{} is standing by for the leader lease", identity);
+        }
+
+        let (sender, is_leader) = watch::channel(leader);
+        let stop = Arc::new(Notify::new());
+        let task = tokio::spawn(maintain(store, identity, ttl, sender, stop.clone()));
+        Ok(Self {
+            is_leader,
+            stop,
+            task,
+        })
+    }
+
+    pub fn is_leader(&self) -> bool {
+        *self.is_leader.borrow()
+    }
+
+    /// Wait until this replica holds the lease
+    pub async fn acquired(&mut self) {
+        let _ = self.is_leader.wait_for(|leader| *leader).await;
+    }
+
+    /// A handle that reports whether this replica is the leader
+    pub fn watch(&self) -> watch::Receiver<bool> {
+        self.is_leader.clone()
+    }
+
+    /// Stop renewing and give the lease up
+    pub async fn stop(self) {
+        self.stop.notify_one();
+        let _ = self.task.await;
+    }
+}
+
+/// Take or renew the lease every third of the TTL until stopped
+async fn maintain(
+    store: Box<dyn LeaseStore>,
+    identity: String,
+    ttl: Duration,
+    sender: watch::Sender<bool>,
+    stop: Arc<Notify>,
+) {
+    let interval = ttl / 3;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = stop.notified() => break,
+        }
+
+        // A renewal slower than the interval might land after the lease expired
+        let leader = match tokio::time::timeout(interval, store.try_acquire(&identity, ttl)).await {
+            Ok(Ok(leader)) => leader,
+            Ok(Err(e)) => {
+                warn!("Failed to renew the leader lease: {:#}", e);
+                false
+            }
+            Err(_) => {
+                warn!("Renewing the leader lease timed out");
+                false
+            }
+        };
+        let was_leader = sender.send_replace(leader);
+        match (was_leader, leader) {
+            (false, true) => info!("{} became the leader", identity),
+            (true, false) => warn!("{} is no longer the leader; standing by", identity),
+            _ => {}
+        }
+    }
+
+    if *sender.borrow() {
+        sender.send_replace(false);
+        match store.release(&identity).await {
+            Ok(()) => info!("Released the leader lease"),
+            Err(e) => warn!("Failed to release the leader lease: {:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::MemoryBackend;
+    use axum::extract::State;
+    use axum::http::StatusCode as AxumStatus;
+    use axum::routing::get;
+    use axum::Json;
+    use std::sync::Mutex;
+
+    const TTL: Duration = Duration::from_secs(30);
+
+    #[tokio::test]
+    async fn test_backend_lease() -> Result<()> {
+        let backend: Arc<dyn SecretBackend> = Arc::new(MemoryBackend::new());
+        let a = BackendLease::new(backend.clone(), "asr/leader")?;
+        let b = BackendLease::new(backend.clone(), "asr/leader")?;
+
+        // Creating the lock is check-and-set too: a lock written since the
+        // read wins over the replica that found none
+        let read = a.read().await?;
+        assert!(read.is_none());
+        b.write("b", Utc::now() + ChronoDuration::seconds(30), Some(0))
+            .await?;
+        assert!(a.write("a", Utc::now(), Some(0)).await.is_err());
+        b.release("b").await?;
+
+        assert!(a.try_acquire("a", TTL).await?);
+        assert!(a.try_acquire("a", TTL).await?, "renewal");
+        assert!(!b.try_acquire("b", TTL).await?);
+
+        a.release("a").await?;
+        assert!(b.try_acquire("b", TTL).await?);
+
+        // An expired lease can be taken over
+        b.write("b", Utc::now() - ChronoDuration::seconds(1), None)
+            .await?;
+        assert!(a.try_acquire("a", TTL).await?);
+        assert_eq!(backend.read_secret("asr/leader").await?.data["holder"], "a");
+
+        // Without check-and-set two replicas could both take the lease
+        let dir = tempfile::TempDir::new()?;
+        let file: Arc<dyn SecretBackend> = Arc::new(crate::backends::FileBackend::new(dir.path())?);
+        assert!(BackendLease::new(file, "asr/leader").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_leadership_releases_on_stop() -> Result<()> {
+        let backend: Arc<dyn SecretBackend> = Arc::new(MemoryBackend::new());
+        let store = || Box::new(BackendLease::new(backend.clone(), "leader").unwrap());
+
+        let leadership = Leadership::start(store(), "a".to_string(), TTL).await?;
+        assert!(leadership.is_leader());
+        let standby = Leadership::start(store(), "b".to_string(), TTL).await?;
+        assert!(!standby.is_leader());
+
+        leadership.stop().await;
+        assert!(store().try_acquire("b", TTL).await?);
+        standby.stop().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_kubernetes_lease() -> Result<()> {
+        type Stored = Arc<Mutex<Option<Value>>>;
+
+        // Enough of the Lease API to check resourceVersion conflicts
+        async fn update(
+            State(stored): State<Stored>,
+            Json(mut lease): Json<Value>,
+        ) -> (AxumStatus, Json<Value>) {
+            let mut stored = stored.lock().unwrap();
+            let version = stored.as_ref().map(|s| {
+                s["metadata"]["resourceVersion"]
+                    .as_str()
+                    .unwrap()
+                    .parse::<u64>()
+                    .unwrap()
+            });
+            if let Some(version) = version {
+                let sent = lease["metadata"]["resourceVersion"].as_str();
+                if sent.and_then(|v| v.parse::<u64>().ok()) != Some(version) {
+                    return (AxumStatus::CONFLICT, Json(Value::Null));
+                }
+            } else if !lease["metadata"]["resourceVersion"].is_null() {
+                return (AxumStatus::CONFLICT, Json(Value::Null));
+            }
+            lease["metadata"]["resourceVersion"] = (version.unwrap_or(0) + 1).to_string().into();
+            *stored = Some(lease.clone());
+            (AxumStatus::OK, Json(lease))
+        }
+        let stored: Stored = Arc::default();
+        let app = axum::Router::new()
+            .route(
+                "/apis/coordination.k8s.io/v1/namespaces/ops/leases",
+                axum::routing::post(update),
+            )
+            .route(
+                "/apis/coordination.k8s.io/v1/namespaces/ops/leases/asr-leader",
+                get(|State(stored): State<Stored>| async move {
+                    match stored.lock().unwrap().clone() {
+                        Some(lease) => (AxumStatus::OK, Json(lease)),
+                        None => (AxumStatus::NOT_FOUND, Json(Value::Null)),
+                    }
+                })
+                .put(update),
+            )
+            .with_state(stored.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let dir = tempfile::TempDir::new()?;
+        let token_file = dir.path().join("token");
+        std::fs::write(&token_file, "sa-token\n")?;
+        let lease = KubernetesLease::new(
+            reqwest::Client::new(),
+            &url,
+            "ops",
+            "asr-leader",
+            token_file,
+        );
+
+        assert!(lease.try_acquire("pod-a", TTL).await?);
+        assert!(!lease.try_acquire("pod-b", TTL).await?);
+        assert!(lease.try_acquire("pod-a", TTL).await?);
+        lease.release("pod-a").await?;
+        assert!(lease.try_acquire("pod-b", TTL).await?);
+
+        let stored = stored.lock().unwrap().clone().unwrap();
+        assert_eq!(stored["spec"]["holderIdentity"], "pod-b");
+        assert_eq!(stored["spec"]["leaseTransitions"], 1);
+        Ok(())
+    }
+}
@@ -2,12 +2,57 @@
 //!
 //! A library for automatic secret rotation with support for multiple backends.
 
+pub mod audit;
 pub mod backends;
+pub mod backup;
+pub mod certs;
+pub mod circuit_breaker;
+pub mod cli;
+pub mod client;
+pub mod clipboard;
+pub mod compliance;
+pub mod composite;
 pub mod config;
+pub mod derived;
+pub mod diff;
+pub mod doctor;
+pub mod dotenv;
+pub mod encrypt;
+pub mod env;
 pub mod env_updater;
+pub mod external;
+pub mod generator;
+pub mod hooks;
+pub mod http;
+pub mod inventory;
+pub mod leader;
+pub mod listener;
+pub mod logging;
+pub mod metadata_store;
+pub mod migrate;
+pub mod notify;
+pub mod output;
+pub mod password_policy;
+pub mod path_filter;
+pub mod plan;
+pub mod plugins;
+pub mod progress;
+pub mod redact;
+pub mod report;
+pub mod retry;
 pub mod rotation;
+pub mod rotator;
+pub mod run_report;
+pub mod server;
+pub mod structured_file;
 pub mod targets;
+pub mod transfer;
+pub mod transit;
+pub mod vault_db;
+pub mod watch;
 
 pub use backends::Backend;
 pub use config::Config;
+pub use plugins::{register_backend, register_target};
 pub use rotation::{flag_for_rotation, generate_secret, rotate_secret, scan_for_rotation};
+pub use rotator::{RotationReport, Rotator, RotatorBuilder};
@@ -0,0 +1,367 @@
+//! Event-driven rotation from an SQS queue (`asr listen`)
+//!
+//! EventBridge rules (e.g., "secret read from an unusual IP", a security
+//! scanner finding, or a schedule) send rotation triggers to an SQS queue,
+//! and the listener rotates the secrets they name as they arrive. A message
+//! body may be:
+//!
+//! - a trigger: `{"path": "app/db"}` or `{"paths": [...]}`, optionally with
+//!   `reason`, `source` and `target_username`
+//! - an EventBridge event whose `detail` is such a trigger, or a CloudTrail
+//!   event naming the secret in `detail.requestParameters.secretId`
+//! - either of the above wrapped in an SNS notification
+//!
+//! A message is deleted once every secret it names has been rotated. If a
+//! rotation fails, the message is left on the queue to be received again
+//! after its visibility timeout (and, with a redrive policy, to end up in a
+//! dead-letter queue), so a trigger is never silently dropped. The secrets a
+//! message already rotated are remembered, so a redelivery only retries the
+//! ones that failed. Failed receives are retried with backoff rather than
+//! stopping the listener.
+//!
+//! With `[leader]` configured, only the leader consumes the queue; standby
+//! replicas wait for the lease and leave messages for the leader.
+
+use anyhow::{Context, Result};
+use aws_config::Region;
+use aws_sdk_sqs::Client as SqsClient;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::audit::{AuditEvent, Outcome};
+use crate::leader::Leadership;
+use crate::rotator::Rotator;
+
+/// Longest long-poll SQS allows, in seconds
+const MAX_WAIT_SECONDS: i32 = 20;
+
+/// Wait after a failed receive, doubled for each failure in a row up to
+/// [`MAX_RECEIVE_BACKOFF`]
+const RECEIVE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RECEIVE_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long the secrets rotated for an undeleted message are remembered;
+/// longer than the largest visibility timeout SQS allows (12 hours)
+const ROTATED_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `[listener]` configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListenerConfig {
+    /// URL of the SQS queue to consume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_url: Option<String>,
+
+    /// Region of the queue (defaults to the `[aws]` region or `AWS_REGION`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+impl ListenerConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Secrets a message asks to rotate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trigger {
+    pub paths: Vec<String>,
+    /// Sending system, for the audit log
+    pub source: String,
+    pub reason: Option<String>,
+    pub target_username: Option<String>,
+}
+
+/// Read the rotation trigger out of a message body
+pub fn parse_trigger(body: &str) -> Result<Trigger> {
+    let mut message: Value =
+        serde_json::from_str(body).context("Message body is not a JSON object")?;
+
+    // SNS delivers the original message as a string
+    if message["Type"] == "Notification" {
+        if let Some(inner) = message["Message"].as_str() {
+            message = serde_json::from_str(inner)
+                .context("SNS notification does not carry a JSON message")?;
+        }
+    }
+
+    // EventBridge events carry the trigger in `detail`
+    let (trigger, event_source) = match message.get("detail") {
+        Some(detail) if detail.is_object() => (detail, message["source"].as_str()),
+        _ => (&message, None),
+    };
+
+    let mut paths: Vec<String> = match (&trigger["path"], &trigger["paths"]) {
+        (Value::String(path), _) => vec![path.clone()],
+        (_, Value::Array(paths)) => paths
+            .iter()
+            .filter_map(|p| p.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    };
+    if paths.is_empty() {
+        if let Some(secret_id) = trigger["requestParameters"]["secretId"].as_str() {
+            paths.push(secret_name(secret_id).to_string());
+        }
+    }
+    if paths.is_empty() {
+        anyhow::bail!("Message does not name a secret (expected path, paths or detail.path)");
+    }
+
+    let string = |key: &str| trigger[key].as_str().map(str::to_string);
+    Ok(Trigger {
+        paths,
+        source: string("source")
+            .or_else(|| event_source.map(str::to_string))
+            .unwrap_or_else(|| "sqs".to_string()),
+        reason: string("reason").or_else(|| message["detail-type"].as_str().map(str::to_string)),
+        target_username: string("target_username"),
+    })
+}
+
+/// Secret name from a Secrets Manager ARN, which ends in `:secret:<name>-<6 chars>`
+fn secret_name(secret_id: &str) -> &str {
+    match secret_id.split_once(":secret:") {
+        Some((_, name)) => match name.rsplit_once('-') {
+            Some((name, suffix)) if suffix.len() == 6 => name,
+            _ => name,
+        },
+        None => secret_id,
+    }
+}
+
+/// Rotate every secret a trigger names that is not in `rotated`, recording why
+///
+/// Every secret is attempted, and the ones rotated are added to `rotated`;
+/// the error lists the ones that failed.
+pub async fn handle_trigger(
+    rotator: &Rotator,
+    trigger: &Trigger,
+    rotated: &mut HashSet<String>,
+) -> Result<()> {
+    let reason = trigger.reason.as_deref().unwrap_or("no reason given");
+    let mut failed = Vec::new();
+    for path in &trigger.paths {
+        if rotated.contains(path) {
+            info!("{} was already rotated for this message; skipping", path);
+            continue;
+        }
+        warn!(
+            "Out-of-cycle rotation of {} requested by {}: {}",
+            path, trigger.source, reason
+        );
+        rotator.audit().record(
+            &AuditEvent::new(
+                "event",
+                path,
+                rotator.backend().backend_type(),
+                Outcome::Success,
+            )
+            .with_detail(format!(
+                "rotation requested by {}: {}",
+                trigger.source, reason
+            )),
+        );
+
+        let result = match trigger.target_username.as_deref() {
+            Some(username) => rotator.rotate_with_target(path, username).await,
+            None => rotator.rotate(path).await,
+        };
+        match result {
+            Ok(_) => {
+                info!("Rotated {} for {}", path, trigger.source);
+                rotated.insert(path.clone());
+            }
+            Err(e) => {
+                error!("Event-triggered rotation of {} failed: {:#}", path, e);
+                failed.push(path.as_str());
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!("Failed to rotate {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+/// Consume `queue_url` until interrupted, rotating the secrets each message names
+///
+/// With `leadership`, messages are only received while this replica is the
+/// leader, and the lease is given up when listening stops.
+pub async fn listen(
+    rotator: Rotator,
+    queue_url: &str,
+    region: Option<String>,
+    mut leadership: Option<Leadership>,
+) -> Result<()> {
+    let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+    if let Some(region) = region {
+        loader = loader.region(Region::new(region));
+    }
+    if let Some(http_client) = crate::http::aws_http_client()? {
+        loader = loader.http_client(http_client);
+    }
+    let client = SqsClient::new(&loader.load().await);
+    info!("Listening for rotation triggers on {}", queue_url);
+
+    // Secrets rotated per message ID, for messages that are not deleted yet
+    let mut rotated: HashMap<String, (Instant, HashSet<String>)> = HashMap::new();
+    let mut backoff = RECEIVE_BACKOFF;
+    loop {
+        if let Some(ref mut leadership) = leadership {
+            if !leadership.is_leader() {
+                info!("Standing by until this replica is the leader");
+                tokio::select! {
+                    _ = leadership.acquired() => info!("Resuming on {} as the leader", queue_url),
+                    _ = tokio::signal::ctrl_c() => break,
+                }
+            }
+        }
+
+        let received = tokio::select! {
+            received = client
+                .receive_message()
+                .queue_url(queue_url)
+                .max_number_of_messages(10)
+                .wait_time_seconds(MAX_WAIT_SECONDS)
+                .send() => received,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        let received = match received {
+            Ok(received) => {
+                backoff = RECEIVE_BACKOFF;
+                received
+            }
+            Err(e) => {
+                error!(
+                    "Failed to receive messages from {}: {}; retrying in {:?}",
+                    queue_url,
+                    aws_sdk_sqs::error::DisplayErrorContext(&e),
+                    backoff
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = tokio::signal::ctrl_c() => break,
+                }
+                backoff = (backoff * 2).min(MAX_RECEIVE_BACKOFF);
+                continue;
+            }
+        };
+        rotated.retain(|_, (since, _)| since.elapsed() < ROTATED_TTL);
+
+        for message in received.messages() {
+            // Unhandled messages go back to the queue for the new leader
+            if leadership.as_ref().is_some_and(|l| !l.is_leader()) {
+                warn!("No longer the leader; leaving the rest of the batch on the queue");
+                break;
+            }
+            let id = message.message_id().unwrap_or("unknown");
+            let done = &mut rotated
+                .entry(id.to_string())
+                .or_insert_with(|| (Instant::now(), HashSet::new()))
+                .1;
+            let handled = match parse_trigger(message.body().unwrap_or_default()) {
+                Ok(trigger) => handle_trigger(&rotator, &trigger, done).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = handled {
+                // Left on the queue, to be retried or dead-lettered
+                error!("Message {} not handled: {:#}", id, e);
+                continue;
+            }
+
+            if let Some(receipt_handle) = message.receipt_handle() {
+                let deleted = client
+                    .delete_message()
+                    .queue_url(queue_url)
+                    .receipt_handle(receipt_handle)
+                    .send()
+                    .await;
+                if let Err(e) = deleted {
+                    // Received again later; its secrets are not rotated twice
+                    error!(
+                        "Failed to delete message {}: {}",
+                        id,
+                        aws_sdk_sqs::error::DisplayErrorContext(&e)
+                    );
+                    continue;
+                }
+            }
+            rotated.remove(id);
+        }
+    }
+
+    info!("Stopped listening on {}", queue_url);
+    if let Some(leadership) = leadership {
+        leadership.stop().await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::MemoryBackend;
+
+    #[test]
+    fn test_parse_trigger_formats() -> Result<()> {
+        let direct =
+            parse_trigger(r#"{"path": "app/db", "reason": "leaked", "source": "button"}"#)?;
+        assert_eq!(direct.paths, vec!["app/db"]);
+        assert_eq!(direct.source, "button");
+        assert_eq!(direct.reason.as_deref(), Some("leaked"));
+
+        let event = parse_trigger(
+            r#"{"source": "custom.scanner", "detail-type": "Credential exposed",
+                "detail": {"paths": ["app/db", "app/api"]}}"#,
+        )?;
+        assert_eq!(event.paths, vec!["app/db", "app/api"]);
+        assert_eq!(event.source, "custom.scanner");
+        assert_eq!(event.reason.as_deref(), Some("Credential exposed"));
+
+        let cloudtrail = serde_json::json!({
+            "source": "aws.secretsmanager",
+            "detail-type": "AWS API Call via CloudTrail",
+            "detail": {"requestParameters": {
+                "secretId": "arn:aws:secretsmanager:us-east-1:123456789012:secret:app/db-AbCdEf"
+            }}
+        });
+        let sns = serde_json::json!({"Type": "Notification", "Message": cloudtrail.to_string()});
+        let wrapped = parse_trigger(&sns.to_string())?;
+        assert_eq!(wrapped.paths, vec!["app/db"]);
+        assert_eq!(wrapped.source, "aws.secretsmanager");
+
+        assert!(parse_trigger(r#"{"detail": {}}"#).is_err());
+        assert!(parse_trigger("not json").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_trigger_rotates_and_reports_failures() -> Result<()> {
+        let backend = MemoryBackend::new().with_secret("app/db", [("password", "old")]);
+        let rotator = Rotator::builder().backend(Box::new(backend)).build()?;
+
+        let trigger = parse_trigger(r#"{"paths": ["app/db", "app/missing"]}"#)?;
+        let mut rotated = HashSet::new();
+        let err = handle_trigger(&rotator, &trigger, &mut rotated)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("app/missing"), "{}", err);
+        assert_eq!(rotated, HashSet::from(["app/db".to_string()]));
+
+        let secret = rotator.backend().read_secret("app/db").await?;
+        assert_ne!(secret.data["password"], "old");
+
+        // A redelivered message does not rotate app/db again
+        assert!(handle_trigger(&rotator, &trigger, &mut rotated)
+            .await
+            .is_err());
+        let again = rotator.backend().read_secret("app/db").await?;
+        assert_eq!(again.data["password"], secret.data["password"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+//! Log output: the console and an optional rotating log file
+//!
+//! Console logging is set up by [`init`] at startup and filtered by
+//! `RUST_LOG`. The `[logging]` section adds a log file with its own level,
+//! applied with [`configure`] once the config is loaded, so daemon runs keep
+//! their logs without relying on journald. The file is rotated when it
+//! reaches `max_size_mb` and/or at the start of every hour or day, and only
+//! the newest `max_files` rotated files are kept. Both outputs are redacted.
+//!
+//! With `--log-format json` (or `format = "json"`), both outputs switch to one
+//! JSON object per line. Rotations run inside a `rotation` span carrying
+//! `run_id`, `secret_path`, `backend` and `target`, which JSON lines include
+//! under `span`, so log pipelines can index rotation events by field.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+use tracing::level_filters::LevelFilter;
+use tracing::Metadata;
+use tracing_subscriber::filter::{dynamic_filter_fn, FilterExt};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::redact::RedactingMakeWriter;
+
+/// `[logging]` configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Format of console and file log lines
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Log file written in addition to the console (e.g., "/var/log/asr/asr.log")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+
+    /// Most verbose level written to the file: error, warn, info, debug or trace
+    #[serde(default = "default_level")]
+    pub level: String,
+
+    /// When to start a new file regardless of size
+    #[serde(default)]
+    pub rotation: LogRotation,
+
+    /// Start a new file once the current one reaches this many megabytes (0 = no limit)
+    #[serde(default)]
+    pub max_size_mb: u64,
+
+    /// Rotated files to keep (asr.log.1 is the newest)
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            file: None,
+            level: default_level(),
+            rotation: LogRotation::default(),
+            max_size_mb: 0,
+            max_files: default_max_files(),
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn default_level() -> String {
+    "info".to_string()
+}
+
+fn default_max_files() -> usize {
+    7
+}
+
+/// Format of log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// Time-based rotation of the log file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// Rotate only by size
+    Never,
+    Hourly,
+    #[default]
+    Daily,
+}
+
+impl LogRotation {
+    /// Identifies the period `time` falls in; a file is rotated when it changes
+    fn period(self, time: DateTime<Utc>) -> Option<String> {
+        match self {
+            LogRotation::Never => None,
+            LogRotation::Hourly => Some(time.format("%Y-%m-%d %H").to_string()),
+            LogRotation::Daily => Some(time.format("%Y-%m-%d").to_string()),
+        }
+    }
+}
+
+/// The configured log file and the most verbose level written to it
+struct FileLog {
+    file: Mutex<RotatingFile>,
+    level: LevelFilter,
+}
+
+static FILE_LOG: RwLock<Option<FileLog>> = RwLock::new(None);
+
+/// Whether log lines are written as JSON
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Identifies this process's run in every rotation span
+pub fn run_id() -> &'static str {
+    static RUN_ID: OnceLock<String> = OnceLock::new();
+    RUN_ID.get_or_init(|| format!("{:016x}", rand::random::<u64>()))
+}
+
+fn format() -> LogFormat {
+    if JSON.load(Ordering::Relaxed) {
+        LogFormat::Json
+    } else {
+        LogFormat::Text
+    }
+}
+
+/// Install the global subscriber: the console, plus the log file once [`configure`]d
+///
+/// Both formats are installed for each output and only the configured one
+/// lets lines through, so the format can be chosen after startup.
+pub fn init() {
+    let console_filter =
+        || EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let console_text = tracing_subscriber::fmt::layer()
+        .with_writer(RedactingMakeWriter::new(std::io::stdout))
+        .with_filter(console_filter().and(format_filter(LogFormat::Text)));
+    let console_json = tracing_subscriber::fmt::layer()
+        .json()
+        .flatten_event(true)
+        .with_current_span(true)
+        .with_span_list(false)
+        .with_writer(RedactingMakeWriter::new(std::io::stdout))
+        .with_filter(console_filter().and(format_filter(LogFormat::Json)));
+
+    tracing_subscriber::registry()
+        .with(console_text)
+        .with(console_json)
+        .with(file_layer())
+        .init();
+}
+
+/// Lets lines through only while `format` is the configured format
+fn format_filter<S>(format: LogFormat) -> impl tracing_subscriber::layer::Filter<S> {
+    dynamic_filter_fn(move |_, _| self::format() == format)
+}
+
+/// Layers writing to the log file, if one is configured
+fn file_layer<S>() -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    let text = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_writer(RedactingMakeWriter::new(FileMakeWriter))
+        .with_filter(
+            dynamic_filter_fn(|metadata, _| file_enabled(metadata))
+                .and(format_filter(LogFormat::Text)),
+        );
+    let json = tracing_subscriber::fmt::layer()
+        .json()
+        .flatten_event(true)
+        .with_current_span(true)
+        .with_span_list(false)
+        .with_writer(RedactingMakeWriter::new(FileMakeWriter))
+        .with_filter(
+            dynamic_filter_fn(|metadata, _| file_enabled(metadata))
+                .and(format_filter(LogFormat::Json)),
+        );
+    text.and_then(json)
+}
+
+fn file_enabled(metadata: &Metadata<'_>) -> bool {
+    FILE_LOG
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .is_some_and(|log| *metadata.level() <= log.level)
+}
+
+/// Apply the configured format and start writing logs to the configured file, if any
+pub fn configure(config: &LoggingConfig) -> Result<()> {
+    JSON.store(config.format == LogFormat::Json, Ordering::Relaxed);
+    let Some(ref path) = config.file else {
+        return Ok(());
+    };
+    let level: LevelFilter = config
+        .level
+        .parse()
+        .with_context(|| format!("Invalid log level '{}'", config.level))?;
+    let file = RotatingFile::open(
+        PathBuf::from(path),
+        config.rotation,
+        config.max_size_mb * 1024 * 1024,
+        config.max_files,
+    )?;
+
+    *FILE_LOG.write().unwrap_or_else(|e| e.into_inner()) = Some(FileLog {
+        file: Mutex::new(file),
+        level,
+    });
+    Ok(())
+}
+
+/// `MakeWriter` for the configured log file
+struct FileMakeWriter;
+
+impl<'a> MakeWriter<'a> for FileMakeWriter {
+    type Writer = FileWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        FileWriter
+    }
+}
+
+/// Writes to the configured log file, or nowhere if there is none
+struct FileWriter;
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let log = FILE_LOG.read().unwrap_or_else(|e| e.into_inner());
+        match log.as_ref() {
+            Some(log) => log
+                .file
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .write_line(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A log file that is moved aside (`asr.log` to `asr.log.1`, and so on) when
+/// it gets too big or a new rotation period starts
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    rotation: LogRotation,
+    /// Size limit in bytes; 0 for none
+    max_size: u64,
+    max_files: usize,
+    size: u64,
+    /// Rotation period the current file was started in
+    period: Option<String>,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, rotation: LogRotation, max_size: u64, max_files: usize) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create log directory {:?}", parent))?;
+        }
+        let file = open_append(&path)?;
+        let metadata = file
+            .metadata()
+            .with_context(|| format!("Failed to read log file {:?}", path))?;
+        // An existing file belongs to the period it was last written in
+        let modified = metadata
+            .modified()
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+
+        Ok(Self {
+            file,
+            rotation,
+            max_size,
+            max_files,
+            size: metadata.len(),
+            period: rotation.period(modified),
+            path,
+        })
+    }
+
+    fn write_line(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_line_at(buf, Utc::now())
+    }
+
+    fn write_line_at(&mut self, buf: &[u8], now: DateTime<Utc>) -> io::Result<usize> {
+        let period = self.rotation.period(now);
+        let too_big = self.max_size > 0 && self.size + buf.len() as u64 > self.max_size;
+        if self.size > 0 && (too_big || period != self.period) {
+            self.rotate()?;
+        }
+        self.period = period;
+
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    /// Shift the rotated files up by one, dropping the oldest, and start afresh
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let _ = std::fs::remove_file(self.rotated(self.max_files));
+            for n in (1..self.max_files).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated(n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated(1))?;
+        }
+
+        self.file = open_append(&self.path).map_err(io::Error::other)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open log file {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rotates_by_size_and_keeps_max_files() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("asr.log");
+        let mut file = RotatingFile::open(path.clone(), LogRotation::Never, 10, 2)?;
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_line(line.as_bytes())?;
+        }
+
+        assert_eq!(std::fs::read_to_string(&path)?, "fourth\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("asr.log.1"))?,
+            "third\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("asr.log.2"))?,
+            "second\n"
+        );
+        assert!(!dir.path().join("asr.log.3").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotates_daily() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("logs/asr.log");
+        let mut file = RotatingFile::open(path.clone(), LogRotation::Daily, 0, 7)?;
+
+        let morning = Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap();
+        file.write_line_at(b"monday\n", morning)?;
+        file.write_line_at(b"monday again\n", morning + chrono::Duration::hours(4))?;
+        file.write_line_at(b"tuesday\n", morning + chrono::Duration::days(1))?;
+
+        assert_eq!(std::fs::read_to_string(&path)?, "tuesday\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("logs/asr.log.1"))?,
+            "monday\nmonday again\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_configure_rejects_invalid_level() {
+        let dir = TempDir::new().unwrap();
+        let config = LoggingConfig {
+            file: Some(dir.path().join("asr.log").display().to_string()),
+            level: "loud".to_string(),
+            ..LoggingConfig::default()
+        };
+        assert!(configure(&config).is_err());
+    }
+}
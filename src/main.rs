@@ -2,33 +2,90 @@
 //!
 //! This is the main entry point for the CLI application.
 
+mod audit;
 mod backends;
+mod backup;
+mod certs;
+mod circuit_breaker;
 mod cli;
+mod clipboard;
+mod compliance;
+mod composite;
 mod config;
+mod derived;
+mod diff;
+mod doctor;
+mod dotenv;
+mod encrypt;
 mod env_updater;
+mod external;
+mod generator;
+mod hooks;
+mod http;
+mod inventory;
+mod leader;
+mod listener;
+mod logging;
+mod metadata_store;
+mod migrate;
+mod notify;
+mod output;
+mod password_policy;
+mod path_filter;
+mod plan;
+mod plugins;
+mod progress;
+mod redact;
+mod report;
+mod retry;
 mod rotation;
+mod rotator;
+mod run_report;
+mod server;
+mod structured_file;
 mod targets;
+mod transfer;
+mod transit;
+mod vault_db;
+#[allow(dead_code)] // Library API, not used by the CLI
+mod watch;
 
 // Re-export for library usage
 pub use config::Config;
+pub use plugins::{register_backend, register_target};
 pub use rotation::{flag_for_rotation, generate_secret, rotate_secret, scan_for_rotation};
+pub use rotator::{RotationReport, Rotator, RotatorBuilder};
+#[cfg(feature = "test-util")]
+pub use {
+    backends::{MemoryBackend, Operation},
+    targets::MockTarget,
+};
 
-use anyhow::Result;
-use tracing_subscriber::EnvFilter;
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
+async fn main() -> ExitCode {
+    // Initialize tracing, scrubbing secrets from every log line
+    logging::init();
 
     // Parse CLI arguments
     use clap::Parser;
     let cli = cli::Cli::parse();
+    output::init(cli.no_color);
 
-    // Execute the command
-    cli::execute(cli).await
+    // Execute the command; `auto` reports partial failures with its own codes
+    match cli::execute(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // The external command has already reported its own failure
+            if let Some(exit) = e.downcast_ref::<external::ExternalExit>() {
+                return ExitCode::from(exit.code);
+            }
+            let code = e
+                .downcast_ref::<cli::RotationFailures>()
+                .map_or(1, cli::RotationFailures::exit_code);
+            output::error(format!("{:?}", redact::redact_error(e)));
+            ExitCode::from(code)
+        }
+    }
 }
@@ -0,0 +1,715 @@
+//! Rotation metadata kept outside the secret backend
+//!
+//! Rotation state normally lives in the backend's own metadata: KV v2 custom
+//! metadata in Vault, tags in AWS. That competes with user tags (AWS allows 50
+//! per secret), and some backends have little or no metadata of their own.
+//! With a `[metadata_store]` section, metadata is read from and written to a
+//! dedicated store instead, and secret values stay in the backend:
+//!
+//! ```toml
+//! [metadata_store]
+//! type = "sidecar"              # a companion secret per secret, in the backend
+//! prefix = ".asr-metadata"
+//!
+//! # type = "sqlite"             # a local SQLite file (`sqlite` feature)
+//! # file = "~/.asr/metadata.db"
+//!
+//! # type = "dynamodb"           # a DynamoDB table keyed by `path` (`dynamodb` feature)
+//! # table = "asr-metadata"
+//! # region = "us-east-1"
+//! ```
+//!
+//! The store's entries are laid over the backend's own metadata, so existing
+//! flags and tags set outside asr (`owner`, `target_username`) keep showing.
+//! Writes put what differs from the backend's metadata into the store; a key
+//! that is removed is also removed from the backend if it is there.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::backends::{is_listed, SecretBackend, SecretData, SecretPage, SecretVersion};
+
+/// `[metadata_store]` configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MetadataStoreConfig {
+    /// Metadata of `path` kept as the data of secret `<prefix>/<path>`
+    Sidecar {
+        #[serde(default = "default_sidecar_prefix")]
+        prefix: String,
+    },
+    /// SQLite database file
+    Sqlite { file: PathBuf },
+    /// DynamoDB table with a string partition key named `path`
+    Dynamodb {
+        table: String,
+        /// Defaults to `AWS_REGION`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        region: Option<String>,
+    },
+}
+
+fn default_sidecar_prefix() -> String {
+    ".asr-metadata".to_string()
+}
+
+/// Where rotation metadata is kept, keyed by secret path
+#[async_trait::async_trait]
+pub trait MetadataStore: Send + Sync {
+    /// Metadata of a secret, or `None` if the store has no entry for it
+    async fn read(&self, path: &str) -> Result<Option<HashMap<String, String>>>;
+
+    /// Metadata of several secrets, one result per path in the same order
+    ///
+    /// Stores that can look up many entries at once override this; the
+    /// default calls [`MetadataStore::read`] for each path.
+    async fn read_batch(&self, paths: &[String]) -> Vec<Result<Option<HashMap<String, String>>>> {
+        let mut results = Vec::with_capacity(paths.len());
+        for path in paths {
+            results.push(self.read(path).await);
+        }
+        results
+    }
+
+    /// Replace the metadata of a secret
+    async fn write(&self, path: &str, metadata: HashMap<String, String>) -> Result<()>;
+
+    /// Backend path under which the store keeps its own entries, hidden from listings
+    fn reserved_prefix(&self) -> Option<&str> {
+        None
+    }
+
+    /// Store type name for display purposes
+    fn store_type(&self) -> &'static str;
+}
+
+/// Open the configured store; `backend` holds sidecar entries
+pub async fn open(
+    config: &MetadataStoreConfig,
+    backend: Arc<dyn SecretBackend>,
+) -> Result<Box<dyn MetadataStore>> {
+    match config {
+        MetadataStoreConfig::Sidecar { prefix } => Ok(Box::new(SidecarStore::new(backend, prefix))),
+        #[cfg(feature = "sqlite")]
+        MetadataStoreConfig::Sqlite { file } => Ok(Box::new(SqliteStore::open(file)?)),
+        #[cfg(not(feature = "sqlite"))]
+        MetadataStoreConfig::Sqlite { .. } => {
+            anyhow::bail!("The sqlite metadata store needs asr built with `--features sqlite`")
+        }
+        #[cfg(feature = "dynamodb")]
+        MetadataStoreConfig::Dynamodb { table, region } => Ok(Box::new(
+            DynamoDbStore::new(table.clone(), region.clone()).await?,
+        )),
+        #[cfg(not(feature = "dynamodb"))]
+        MetadataStoreConfig::Dynamodb { .. } => {
+            anyhow::bail!("The dynamodb metadata store needs asr built with `--features dynamodb`")
+        }
+    }
+}
+
+/// Metadata kept as companion secrets in the backend itself
+///
+/// Useful for backends without metadata (or with too little room for it):
+/// the metadata of `app/db` is the data of the secret `.asr-metadata/app/db`.
+pub struct SidecarStore {
+    backend: Arc<dyn SecretBackend>,
+    prefix: String,
+}
+
+impl SidecarStore {
+    pub fn new(backend: Arc<dyn SecretBackend>, prefix: &str) -> Self {
+        Self {
+            backend,
+            prefix: prefix.trim_matches('/').to_string(),
+        }
+    }
+
+    fn sidecar_path(&self, path: &str) -> String {
+        format!("{}/{}", self.prefix, path.trim_start_matches('/'))
+    }
+}
+
+#[async_trait::async_trait]
+impl MetadataStore for SidecarStore {
+    async fn read(&self, path: &str) -> Result<Option<HashMap<String, String>>> {
+        let sidecar = self.sidecar_path(path);
+        // Backends report a missing secret as an error; tell it apart from a
+        // failing backend by checking the listing, which must succeed too
+        match self.backend.read_secret(&sidecar).await {
+            Ok(secret) => Ok(Some(secret.data)),
+            Err(e) => match is_listed(self.backend.as_ref(), &sidecar).await {
+                Ok(false) => Ok(None),
+                _ => Err(e).with_context(|| format!("Failed to read metadata sidecar {}", sidecar)),
+            },
+        }
+    }
+
+    /// List each directory of sidecars once and read only the sidecars that
+    /// exist, instead of failing a read for every secret without one
+    async fn read_batch(&self, paths: &[String]) -> Vec<Result<Option<HashMap<String, String>>>> {
+        let mut listings: HashMap<String, Result<HashSet<String>, String>> = HashMap::new();
+        let mut results = Vec::with_capacity(paths.len());
+        for path in paths {
+            let sidecar = self.sidecar_path(path);
+            let (parent, name) = sidecar.rsplit_once('/').unwrap_or(("", &sidecar));
+            if !listings.contains_key(parent) {
+                let listing = match self.backend.list_secrets(parent).await {
+                    Ok(names) => Ok(names
+                        .iter()
+                        .map(|name| name.trim_end_matches('/').to_string())
+                        .collect()),
+                    Err(e) => Err(format!("{:#}", e)),
+                };
+                listings.insert(parent.to_string(), listing);
+            }
+            results.push(match &listings[parent] {
+                Ok(names) if !names.contains(name) => Ok(None),
+                Ok(_) => self
+                    .backend
+                    .read_secret(&sidecar)
+                    .await
+                    .map(|secret| Some(secret.data))
+                    .with_context(|| format!("Failed to read metadata sidecar {}", sidecar)),
+                Err(e) => Err(anyhow::anyhow!(
+                    "Failed to list metadata sidecars under {}: {}",
+                    parent,
+                    e
+                )),
+            });
+        }
+        results
+    }
+
+    async fn write(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        let sidecar = self.sidecar_path(path);
+        self.backend
+            .write_secret(&sidecar, metadata)
+            .await
+            .with_context(|| format!("Failed to write metadata sidecar {}", sidecar))
+    }
+
+    fn reserved_prefix(&self) -> Option<&str> {
+        Some(&self.prefix)
+    }
+
+    fn store_type(&self) -> &'static str {
+        "sidecar"
+    }
+}
+
+/// Metadata kept in a local SQLite file, one JSON row per secret
+#[cfg(feature = "sqlite")]
+pub struct SqliteStore {
+    connection: std::sync::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    pub fn open(file: &std::path::Path) -> Result<Self> {
+        let file = expand_home(file)?;
+        if let Some(dir) = file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory {:?}", dir))?;
+        }
+        let connection = rusqlite::Connection::open(&file)
+            .with_context(|| format!("Failed to open metadata database {:?}", file))?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS metadata (
+                    path TEXT PRIMARY KEY,
+                    metadata TEXT NOT NULL,
+                    updated_at TEXT NOT NULL
+                )",
+                [],
+            )
+            .context("Failed to create the metadata table")?;
+        Ok(Self {
+            connection: std::sync::Mutex::new(connection),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, rusqlite::Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[async_trait::async_trait]
+impl MetadataStore for SqliteStore {
+    async fn read(&self, path: &str) -> Result<Option<HashMap<String, String>>> {
+        use rusqlite::OptionalExtension;
+        let json: Option<String> = self
+            .lock()
+            .query_row(
+                "SELECT metadata FROM metadata WHERE path = ?1",
+                [path],
+                |row| row.get(0),
+            )
+            .optional()
+            .with_context(|| format!("Failed to read metadata of {}", path))?;
+        json.map(|json| {
+            serde_json::from_str(&json)
+                .with_context(|| format!("Invalid stored metadata for {}", path))
+        })
+        .transpose()
+    }
+
+    async fn write(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        let json = serde_json::to_string(&metadata)?;
+        self.lock()
+            .execute(
+                "INSERT INTO metadata (path, metadata, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(path) DO UPDATE SET metadata = ?2, updated_at = ?3",
+                [path, &json, &chrono::Utc::now().to_rfc3339()],
+            )
+            .with_context(|| format!("Failed to write metadata of {}", path))?;
+        Ok(())
+    }
+
+    fn store_type(&self) -> &'static str {
+        "sqlite"
+    }
+}
+
+/// Expand a leading `~` to the home directory
+#[cfg(feature = "sqlite")]
+fn expand_home(path: &std::path::Path) -> Result<PathBuf> {
+    match path.to_str().and_then(|p| p.strip_prefix('~')) {
+        Some(rest) => {
+            let home = std::env::var("HOME").context("HOME environment variable not set")?;
+            Ok(format!("{}{}", home, rest).into())
+        }
+        None => Ok(path.to_path_buf()),
+    }
+}
+
+/// Metadata kept in a DynamoDB table, one item per secret
+///
+/// Items have the secret path as the `path` partition key and the metadata
+/// as a string map attribute named `metadata`.
+#[cfg(feature = "dynamodb")]
+pub struct DynamoDbStore {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+#[cfg(feature = "dynamodb")]
+impl DynamoDbStore {
+    pub async fn new(table: String, region: Option<String>) -> Result<Self> {
+        let region = region.unwrap_or_else(|| {
+            std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string())
+        });
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(aws_config::Region::new(region));
+        if let Some(http_client) = crate::http::aws_http_client()? {
+            loader = loader.http_client(http_client);
+        }
+        let config = loader.load().await;
+        Ok(Self {
+            client: aws_sdk_dynamodb::Client::new(&config),
+            table,
+        })
+    }
+}
+
+#[cfg(feature = "dynamodb")]
+#[async_trait::async_trait]
+impl MetadataStore for DynamoDbStore {
+    async fn read(&self, path: &str) -> Result<Option<HashMap<String, String>>> {
+        use aws_sdk_dynamodb::types::AttributeValue;
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key("path", AttributeValue::S(path.to_string()))
+            .consistent_read(true)
+            .send()
+            .await
+            .with_context(|| format!("Failed to read metadata of {} from DynamoDB", path))?;
+        let Some(item) = output.item else {
+            return Ok(None);
+        };
+        let metadata = match item.get("metadata") {
+            Some(AttributeValue::M(map)) => map
+                .iter()
+                .filter_map(|(key, value)| match value {
+                    AttributeValue::S(value) => Some((key.clone(), value.clone())),
+                    _ => None,
+                })
+                .collect(),
+            _ => HashMap::new(),
+        };
+        Ok(Some(metadata))
+    }
+
+    async fn write(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        use aws_sdk_dynamodb::types::AttributeValue;
+        let metadata = metadata
+            .into_iter()
+            .map(|(key, value)| (key, AttributeValue::S(value)))
+            .collect();
+        self.client
+            .put_item()
+            .table_name(&self.table)
+            .item("path", AttributeValue::S(path.to_string()))
+            .item("metadata", AttributeValue::M(metadata))
+            .send()
+            .await
+            .with_context(|| format!("Failed to write metadata of {} to DynamoDB", path))?;
+        Ok(())
+    }
+
+    fn store_type(&self) -> &'static str {
+        "dynamodb"
+    }
+}
+
+/// Backend whose metadata is read from and written to a [`MetadataStore`]
+///
+/// Everything else goes to the wrapped backend. Entries the store keeps in
+/// the backend (sidecars) are left out of listings.
+pub struct MetadataStoreBackend {
+    inner: Arc<dyn SecretBackend>,
+    store: Box<dyn MetadataStore>,
+}
+
+impl MetadataStoreBackend {
+    pub fn new(inner: Arc<dyn SecretBackend>, store: Box<dyn MetadataStore>) -> Self {
+        Self { inner, store }
+    }
+
+    /// Whether `name`, listed under `path`, belongs to the store
+    fn is_reserved(&self, path: &str, name: &str) -> bool {
+        let Some(prefix) = self.store.reserved_prefix() else {
+            return false;
+        };
+        let full = format!("{}/{}", path.trim_matches('/'), name);
+        let full = full.trim_matches('/');
+        full == prefix || full.starts_with(&format!("{}/", prefix))
+    }
+
+    /// The backend's metadata with the store's entry laid over it
+    async fn metadata(&self, path: &str) -> Result<HashMap<String, String>> {
+        let mut metadata = self.inner.read_metadata(path).await?;
+        if let Some(stored) = self.store.read(path).await? {
+            metadata.extend(stored);
+        }
+        Ok(metadata)
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretBackend for MetadataStoreBackend {
+    async fn read_secret(&self, path: &str) -> Result<SecretData> {
+        let mut secret = self.inner.read_secret(path).await?;
+        if let Some(stored) = self.store.read(path).await? {
+            secret
+                .metadata
+                .get_or_insert_with(HashMap::new)
+                .extend(stored);
+        }
+        Ok(secret)
+    }
+
+    async fn write_secret(&self, path: &str, data: HashMap<String, String>) -> Result<()> {
+        self.inner.write_secret(path, data).await
+    }
+
+    async fn write_secret_cas(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.inner.write_secret_cas(path, data, version).await
+    }
+
+    async fn stage_secret(
+        &self,
+        path: &str,
+        data: HashMap<String, String>,
+        version: Option<u64>,
+    ) -> Result<()> {
+        self.inner.stage_secret(path, data, version).await
+    }
+
+    async fn promote_staged_secret(&self, path: &str) -> Result<()> {
+        self.inner.promote_staged_secret(path).await
+    }
+
+    async fn discard_staged_secret(&self, path: &str) -> Result<()> {
+        self.inner.discard_staged_secret(path).await
+    }
+
+    /// Store what differs from the backend's metadata, and remove from the
+    /// backend the keys `metadata` no longer has
+    async fn update_metadata(&self, path: &str, metadata: HashMap<String, String>) -> Result<()> {
+        let native = self.inner.read_metadata(path).await?;
+        let removed: Vec<String> = native
+            .keys()
+            .filter(|key| !metadata.contains_key(*key))
+            .cloned()
+            .collect();
+        if !removed.is_empty() {
+            self.inner.remove_metadata(path, &removed).await?;
+        }
+        let stored = metadata
+            .into_iter()
+            .filter(|(key, value)| native.get(key) != Some(value))
+            .collect();
+        self.store.write(path, stored).await
+    }
+
+    async fn read_metadata(&self, path: &str) -> Result<HashMap<String, String>> {
+        self.metadata(path).await
+    }
+
+    /// Read the backend's metadata and the store in one batch each
+    async fn read_metadata_batch(&self, paths: &[String]) -> Vec<Result<HashMap<String, String>>> {
+        let native = self.inner.read_metadata_batch(paths).await;
+        let stored = self.store.read_batch(paths).await;
+        native
+            .into_iter()
+            .zip(stored)
+            .map(|(native, stored)| {
+                let mut metadata = native?;
+                if let Some(stored) = stored? {
+                    metadata.extend(stored);
+                }
+                Ok(metadata)
+            })
+            .collect()
+    }
+
+    async fn list_secrets(&self, path: &str) -> Result<Vec<String>> {
+        let mut names = self.inner.list_secrets(path).await?;
+        names.retain(|name| !self.is_reserved(path, name.trim_end_matches('/')));
+        Ok(names)
+    }
+
+    async fn list_secrets_page(&self, path: &str, cursor: Option<&str>) -> Result<SecretPage> {
+        let mut page = self.inner.list_secrets_page(path, cursor).await?;
+        page.names
+            .retain(|name| !self.is_reserved(path, name.trim_end_matches('/')));
+        Ok(page)
+    }
+
+    async fn list_versions(&self, path: &str) -> Result<Vec<SecretVersion>> {
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(&self, path: &str, version: u64) -> Result<SecretData> {
+        self.inner.read_version(path, version).await
+    }
+
+    fn supports_cas(&self) -> bool {
+        self.inner.supports_cas()
+    }
+
+    fn stages_secrets(&self) -> bool {
+        self.inner.stages_secrets()
+    }
+
+    fn backend_type(&self) -> &'static str {
+        self.inner.backend_type()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::{MemoryBackend, Operation};
+
+    fn metadata(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_store() -> Result<()> {
+        let memory = Arc::new(
+            MemoryBackend::new()
+                .with_secret("app/db", [("password", "old")])
+                .with_secret("app/api", [("token", "t")]),
+        );
+        let inner: Arc<dyn SecretBackend> = memory.clone();
+        inner
+            .update_metadata(
+                "app/db",
+                metadata(&[("rotation_enabled", "true"), ("owner", "team-a")]),
+            )
+            .await?;
+        let store = open(
+            &MetadataStoreConfig::Sidecar {
+                prefix: default_sidecar_prefix(),
+            },
+            inner.clone(),
+        )
+        .await?;
+        let backend = MetadataStoreBackend::new(inner.clone(), store);
+
+        // Without a sidecar, the backend's own metadata is used
+        assert_eq!(backend.read_metadata("app/db").await?["owner"], "team-a");
+
+        let mut updated = backend.read_metadata("app/db").await?;
+        updated.insert(
+            "last_rotated".to_string(),
+            "2026-10-16T00:00:00Z".to_string(),
+        );
+        backend.update_metadata("app/db", updated).await?;
+        backend
+            .remove_metadata("app/db", &["owner".to_string()])
+            .await?;
+
+        // The sidecar keeps what the backend's metadata does not have, and a
+        // removed key is removed from the backend too
+        assert_eq!(
+            memory.secret(".asr-metadata/app/db").unwrap(),
+            metadata(&[("last_rotated", "2026-10-16T00:00:00Z")])
+        );
+        assert_eq!(
+            memory.metadata("app/db").unwrap(),
+            metadata(&[("rotation_enabled", "true")])
+        );
+
+        // Tags set in the backend later still show through the store's entry
+        inner
+            .update_metadata(
+                "app/db",
+                metadata(&[("rotation_enabled", "true"), ("target_username", "app")]),
+            )
+            .await?;
+        let secret = backend.read_secret("app/db").await?;
+        assert_eq!(secret.data["password"], "old");
+        assert_eq!(
+            secret.metadata.unwrap(),
+            metadata(&[
+                ("rotation_enabled", "true"),
+                ("target_username", "app"),
+                ("last_rotated", "2026-10-16T00:00:00Z")
+            ])
+        );
+
+        // Sidecars do not show up as secrets to rotate
+        let mut names = backend.list_secrets("").await?;
+        names.sort();
+        assert_eq!(names, ["app/api", "app/db"]);
+
+        // A batch lists the sidecars once and reads only those that exist
+        let reads = memory.calls(Operation::Read);
+        let paths = ["app/db", "app/api"].map(String::from);
+        let batch = backend.read_metadata_batch(&paths).await;
+        assert_eq!(
+            batch[0].as_ref().unwrap()["last_rotated"],
+            "2026-10-16T00:00:00Z"
+        );
+        assert!(batch[1].as_ref().unwrap().is_empty());
+        assert_eq!(memory.calls(Operation::Read), reads + 1);
+
+        // A failing backend is not mistaken for a missing sidecar
+        memory.fail_always(Operation::List, "connection reset");
+        assert!(backend.read_metadata("app/api").await.is_err());
+        assert!(backend.read_metadata_batch(&paths).await[1].is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "dynamodb")]
+    #[tokio::test]
+    async fn test_dynamodb_store() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mut get = |key: &str| {
+            server
+                .mock("POST", "/")
+                .match_header("x-amz-target", "DynamoDB_20120810.GetItem")
+                .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                    "TableName": "asr-metadata",
+                    "Key": {"path": {"S": key}},
+                    "ConsistentRead": true
+                })))
+                .with_header("content-type", "application/x-amz-json-1.0")
+        };
+        let found = get("app/db")
+            .with_body(
+                serde_json::json!({ "Item": {
+                    "path": {"S": "app/db"},
+                    "metadata": {"M": {
+                        "rotation_enabled": {"S": "true"},
+                        "ignored": {"N": "1"}
+                    }}
+                }})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let missing = get("app/new").with_body("{}").create_async().await;
+        let put = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.PutItem")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "TableName": "asr-metadata",
+                "Item": {
+                    "path": {"S": "app/new"},
+                    "metadata": {"M": {"owner": {"S": "team-a"}}}
+                }
+            })))
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(aws_config::BehaviorVersion::latest())
+            .region(aws_config::Region::new("us-east-1"))
+            .endpoint_url(server.url())
+            .credentials_provider(aws_sdk_dynamodb::config::Credentials::new(
+                "AKID", "secret", None, None, "test",
+            ))
+            .build();
+        let store = DynamoDbStore {
+            client: aws_sdk_dynamodb::Client::from_conf(config),
+            table: "asr-metadata".to_string(),
+        };
+
+        assert_eq!(
+            store.read("app/db").await?,
+            Some(metadata(&[("rotation_enabled", "true")]))
+        );
+        assert_eq!(store.read("app/new").await?, None);
+        store
+            .write("app/new", metadata(&[("owner", "team-a")]))
+            .await?;
+        found.assert_async().await;
+        missing.assert_async().await;
+        put.assert_async().await;
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_store() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let file = dir.path().join("nested/metadata.db");
+        let store = SqliteStore::open(&file)?;
+        assert!(store.read("app/db").await?.is_none());
+        store
+            .write("app/db", metadata(&[("rotation_enabled", "true")]))
+            .await?;
+        store
+            .write("app/db", metadata(&[("last_rotated", "now")]))
+            .await?;
+        drop(store);
+
+        let reopened = SqliteStore::open(&file)?;
+        assert_eq!(
+            reopened.read("app/db").await?,
+            Some(metadata(&[("last_rotated", "now")]))
+        );
+        Ok(())
+    }
+}
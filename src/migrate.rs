@@ -0,0 +1,277 @@
+//! Migration of the deprecated `[database]` config section
+//!
+//! `[database]` predates `[targets]` and takes exactly the same settings as
+//! `[targets.postgres]`, so migrating is a move. TOML files are edited with
+//! `toml_edit`, keeping comments and layout; YAML and JSON files are
+//! re-serialized. Profiles (`[profiles.<name>.database]`) are migrated too.
+
+use anyhow::{Context, Result};
+
+use crate::config::{Config, PostgresTargetConfig};
+use crate::structured_file::StructuredFormat;
+
+/// Move every `database` section in `contents` to `targets.postgres`
+///
+/// Returns `None` if there is nothing to migrate. Fails if a section already
+/// has both, since the `[database]` one is ignored and only a person can tell
+/// which settings are wanted.
+pub fn migrate_config(contents: &str, format: StructuredFormat) -> Result<Option<String>> {
+    match format {
+        StructuredFormat::Toml => {
+            let mut document: toml_edit::DocumentMut =
+                contents.parse().context("Failed to parse TOML")?;
+            let mut migrated = migrate_toml(document.as_table_mut(), "")?;
+            if let Some(profiles) = document
+                .get_mut("profiles")
+                .and_then(|item| item.as_table_like_mut())
+            {
+                for (name, profile) in profiles.iter_mut() {
+                    if let Some(profile) = profile.as_table_mut() {
+                        migrated |= migrate_toml(profile, &format!("profiles.{}.", name))?;
+                    }
+                }
+            }
+            Ok(migrated.then(|| document.to_string()))
+        }
+        StructuredFormat::Yaml => {
+            let mut document: serde_json::Value =
+                serde_yaml::from_str(contents).context("Failed to parse YAML")?;
+            if !migrate_value(&mut document)? {
+                return Ok(None);
+            }
+            serde_yaml::to_string(&document)
+                .map(Some)
+                .context("Failed to serialize YAML")
+        }
+        StructuredFormat::Json => {
+            let mut document: serde_json::Value =
+                serde_json::from_str(contents).context("Failed to parse JSON")?;
+            if !migrate_value(&mut document)? {
+                return Ok(None);
+            }
+            let mut output =
+                serde_json::to_string_pretty(&document).context("Failed to serialize JSON")?;
+            output.push('\n');
+            Ok(Some(output))
+        }
+    }
+}
+
+/// Move `database` to `targets.postgres` within one TOML table
+///
+/// The moved table keeps its position in the file and the comments above and
+/// inside it.
+fn migrate_toml(table: &mut toml_edit::Table, prefix: &str) -> Result<bool> {
+    let Some(database) = table.remove("database") else {
+        return Ok(false);
+    };
+    let database = database
+        .into_table()
+        .map_err(|_| anyhow::anyhow!("[{}database] must be a table", prefix))?;
+
+    let targets = table.entry("targets").or_insert_with(|| {
+        let mut targets = toml_edit::Table::new();
+        targets.set_implicit(true);
+        toml_edit::Item::Table(targets)
+    });
+    let targets = targets
+        .as_table_like_mut()
+        .with_context(|| format!("[{}targets] must be a table", prefix))?;
+    if targets.contains_key("postgres") {
+        anyhow::bail!(
+            "Both [{p}database] and [{p}targets.postgres] are set; [{p}database] is ignored, so remove it by hand",
+            p = prefix
+        );
+    }
+    targets.insert("postgres", toml_edit::Item::Table(database));
+    Ok(true)
+}
+
+/// Like [`migrate_toml`], for YAML and JSON documents
+fn migrate_value(document: &mut serde_json::Value) -> Result<bool> {
+    let mut migrated = migrate_object(document, "")?;
+    if let Some(profiles) = document
+        .get_mut("profiles")
+        .and_then(|profiles| profiles.as_object_mut())
+    {
+        for (name, profile) in profiles.iter_mut() {
+            migrated |= migrate_object(profile, &format!("profiles.{}.", name))?;
+        }
+    }
+    Ok(migrated)
+}
+
+fn migrate_object(node: &mut serde_json::Value, prefix: &str) -> Result<bool> {
+    let Some(object) = node.as_object_mut() else {
+        return Ok(false);
+    };
+    let Some(database) = object.remove("database") else {
+        return Ok(false);
+    };
+    let targets = object
+        .entry("targets")
+        .or_insert_with(|| serde_json::Value::Object(Default::default()))
+        .as_object_mut()
+        .with_context(|| format!("{}targets must be a mapping", prefix))?;
+    if targets.contains_key("postgres") {
+        anyhow::bail!(
+            "Both {p}database and {p}targets.postgres are set; {p}database is ignored, so remove it by hand",
+            p = prefix
+        );
+    }
+    targets.insert("postgres".to_string(), database);
+    Ok(true)
+}
+
+/// `[targets.postgres]` section equivalent to the `DB_*` environment variables
+///
+/// The admin password is left out so that it is not written to a file by
+/// accident.
+pub fn env_replacement(database: &PostgresTargetConfig) -> String {
+    let mut section = toml_edit::Table::new();
+    section.insert("host", toml_edit::value(database.host.as_str()));
+    section.insert("port", toml_edit::value(i64::from(database.port)));
+    section.insert("database", toml_edit::value(database.database.as_str()));
+    section.insert("username", toml_edit::value(database.username.as_str()));
+    if let Some(ref password_path) = database.password_path {
+        section.insert("password_path", toml_edit::value(password_path.as_str()));
+    }
+    section.insert("ssl_mode", toml_edit::value(database.ssl_mode.as_str()));
+
+    let mut targets = toml_edit::Table::new();
+    targets.set_implicit(true);
+    targets.insert("postgres", toml_edit::Item::Table(section));
+    let mut document = toml_edit::DocumentMut::new();
+    document.insert("targets", toml_edit::Item::Table(targets));
+
+    let mut replacement = document.to_string();
+    if database.password_path.is_none() {
+        replacement.push_str(
+            "password_path = \"<secret holding the admin password>\"  # replaces DB_PASSWORD\n",
+        );
+    }
+    replacement
+}
+
+/// Warning for a loaded config that still uses the legacy settings, if it does
+///
+/// `from_file` tells a `[database]` section apart from `DB_*` variables.
+pub fn deprecation_warning(config: &Config, from_file: bool) -> Option<String> {
+    let database = config.database.as_ref()?;
+    if !from_file {
+        return Some(format!(
+            "DB_HOST, DB_USERNAME and the other DB_* variables are deprecated. Use a config file with:\n\n{}",
+            env_replacement(database)
+        ));
+    }
+    let has_target = config
+        .targets
+        .as_ref()
+        .is_some_and(|targets| targets.postgres.is_some() || targets.api.is_some());
+    Some(if has_target {
+        "[database] is ignored because [targets] configures a target; remove it".to_string()
+    } else {
+        "[database] is deprecated; rename it to [targets.postgres], or run `asr config migrate`"
+            .to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_toml_keeps_comments() {
+        let legacy = r#"backend = "vault"
+
+# Production database
+[database]
+host = "db.example.com" # primary
+database = "app"
+username = "admin"
+password_path = "admin/postgres"
+
+[rotation]
+period_months = 3
+
+[profiles.staging.database]
+host = "db.staging"
+database = "app"
+username = "admin"
+"#;
+        let migrated = migrate_config(legacy, StructuredFormat::Toml)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            migrated,
+            r#"backend = "vault"
+
+# Production database
+[targets.postgres]
+host = "db.example.com" # primary
+database = "app"
+username = "admin"
+password_path = "admin/postgres"
+
+[rotation]
+period_months = 3
+
+[profiles.staging.targets.postgres]
+host = "db.staging"
+database = "app"
+username = "admin"
+"#
+        );
+
+        let config = Config::parse(&migrated, StructuredFormat::Toml, None).unwrap();
+        assert!(config.database.is_none());
+        assert_eq!(
+            config.targets.unwrap().postgres.unwrap().host,
+            "db.example.com"
+        );
+
+        // Nothing left to do
+        assert!(migrate_config(&migrated, StructuredFormat::Toml)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_migrate_refuses_both_sections() {
+        let both = r#"
+[database]
+host = "old"
+database = "app"
+username = "admin"
+
+[targets.postgres]
+host = "new"
+database = "app"
+username = "admin"
+"#;
+        let err = migrate_config(both, StructuredFormat::Toml).unwrap_err();
+        assert!(err.to_string().contains("remove it by hand"));
+    }
+
+    #[test]
+    fn test_migrate_yaml() {
+        let legacy = "backend: vault\ndatabase:\n  host: db\n  database: app\n  username: admin\n";
+        let migrated = migrate_config(legacy, StructuredFormat::Yaml)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            migrated,
+            "backend: vault\ntargets:\n  postgres:\n    host: db\n    database: app\n    username: admin\n"
+        );
+    }
+
+    #[test]
+    fn test_env_replacement() {
+        let database: PostgresTargetConfig =
+            toml::from_str("host = \"db\"\ndatabase = \"app\"\nusername = \"admin\"").unwrap();
+        assert_eq!(
+            env_replacement(&database),
+            "[targets.postgres]\nhost = \"db\"\nport = 5432\ndatabase = \"app\"\nusername = \"admin\"\nssl_mode = \"prefer\"\npassword_path = \"<secret holding the admin password>\"  # replaces DB_PASSWORD\n"
+        );
+    }
+}
@@ -0,0 +1,407 @@
+//! Per-owner notifications of overdue and failed rotations
+//!
+//! Secrets name the team responsible for them in their `owner` metadata (set
+//! with `asr flag --owner`). `asr report --notify` splits the report by owner
+//! and posts each part to the webhooks configured under
+//! `[[notifications.webhooks]]` for that owner, so a team only hears about its
+//! own secrets. Secrets without an owner, or whose owner has no webhook of its
+//! own, go to the webhooks that do not list any owners.
+//!
+//! With a lead time (`due_soon_days`), owners are also reminded of secrets
+//! that will come due within that many days, so they can prepare for the
+//! credential change before automatic rotation happens.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::redact::{self, SecretString};
+use crate::report::{Report, SecretState};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notification sink configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    /// Webhooks that receive JSON notifications
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookSinkConfig>,
+
+    /// Remind owners of secrets due within this many days (0 to disable)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub due_soon_days: u32,
+}
+
+fn is_zero(days: &u32) -> bool {
+    *days == 0
+}
+
+impl NotificationsConfig {
+    pub fn is_empty(&self) -> bool {
+        self.webhooks.is_empty() && self.due_soon_days == 0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSinkConfig {
+    /// URL to POST notifications to (often contains a token)
+    pub url: SecretString,
+
+    /// Owners whose secrets this webhook is for; empty for everything that
+    /// no other webhook claims
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<String>,
+}
+
+/// The part of a report that concerns one owner
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OwnerReport {
+    /// `None` for secrets without `owner` metadata
+    pub owner: Option<String>,
+    pub overdue: Vec<OverdueSecret>,
+    pub failed: Vec<FailedSecret>,
+    /// Current secrets that come due within the lead time
+    pub due_soon: Vec<OverdueSecret>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OverdueSecret {
+    pub path: String,
+    pub due_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailedSecret {
+    pub path: String,
+    pub error: String,
+}
+
+impl OwnerReport {
+    fn is_empty(&self) -> bool {
+        self.overdue.is_empty() && self.failed.is_empty() && self.due_soon.is_empty()
+    }
+
+    /// One-line summary, used as the `text` of the payload for chat webhooks
+    fn summary(&self) -> String {
+        let owner = self.owner.as_deref().unwrap_or("unowned secrets");
+        let mut parts = Vec::new();
+        if !self.failed.is_empty() {
+            let paths: Vec<_> = self.failed.iter().map(|s| s.path.as_str()).collect();
+            parts.push(format!(
+                "{} failed to rotate ({})",
+                paths.len(),
+                paths.join(", ")
+            ));
+        }
+        if !self.overdue.is_empty() {
+            let paths: Vec<_> = self.overdue.iter().map(|s| s.path.as_str()).collect();
+            parts.push(format!("{} overdue ({})", paths.len(), paths.join(", ")));
+        }
+        if !self.due_soon.is_empty() {
+            let secrets: Vec<_> = self
+                .due_soon
+                .iter()
+                .map(|s| match s.due_at {
+                    Some(due) => format!("{} on {}", s.path, due.format("%Y-%m-%d")),
+                    None => s.path.clone(),
+                })
+                .collect();
+            parts.push(format!(
+                "{} due soon ({})",
+                secrets.len(),
+                secrets.join(", ")
+            ));
+        }
+        format!("Secret rotation for {}: {}", owner, parts.join("; "))
+    }
+}
+
+/// Split the overdue secrets and rotation failures of a report by owner
+///
+/// Secrets that were rotated by the run are not reported as overdue, and
+/// owners with nothing to report are left out. With `due_soon_days`, current
+/// secrets due within that many days of the report are included as due soon.
+pub fn group_by_owner(report: &Report, due_soon_days: u32) -> Vec<OwnerReport> {
+    let owner_of = |path: &str| {
+        report
+            .secrets
+            .iter()
+            .find(|secret| secret.path == path)
+            .and_then(|secret| secret.owner.clone())
+    };
+    let rotated = |path: &str| {
+        report
+            .run
+            .as_ref()
+            .is_some_and(|run| run.rotated.iter().any(|p| p == path))
+    };
+
+    let due_soon_until = report.generated_at + ChronoDuration::days(due_soon_days.into());
+
+    let mut groups = BTreeMap::new();
+
+    for secret in &report.secrets {
+        if secret.state == SecretState::Overdue && !rotated(&secret.path) {
+            owner_group(&mut groups, secret.owner.clone())
+                .overdue
+                .push(OverdueSecret {
+                    path: secret.path.clone(),
+                    due_at: secret.due_at,
+                });
+        }
+        if due_soon_days > 0
+            && secret.state == SecretState::Current
+            && secret.due_at.is_some_and(|due| due <= due_soon_until)
+        {
+            owner_group(&mut groups, secret.owner.clone())
+                .due_soon
+                .push(OverdueSecret {
+                    path: secret.path.clone(),
+                    due_at: secret.due_at,
+                });
+        }
+    }
+    for (path, error) in report.run.iter().flat_map(|run| &run.failed) {
+        owner_group(&mut groups, owner_of(path))
+            .failed
+            .push(FailedSecret {
+                path: path.clone(),
+                error: redact::redact(error),
+            });
+    }
+
+    groups
+        .into_values()
+        .filter(|group| !group.is_empty())
+        .collect()
+}
+
+fn owner_group(
+    groups: &mut BTreeMap<Option<String>, OwnerReport>,
+    owner: Option<String>,
+) -> &mut OwnerReport {
+    groups.entry(owner.clone()).or_insert_with(|| OwnerReport {
+        owner,
+        ..Default::default()
+    })
+}
+
+/// Sends owner reports to the configured webhooks
+pub struct Notifier {
+    webhooks: Vec<WebhookSinkConfig>,
+    due_soon_days: u32,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(config: &NotificationsConfig) -> Result<Self> {
+        let client = crate::http::client_builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("Failed to create HTTP client for notifications")?;
+        Ok(Self {
+            webhooks: config.webhooks.clone(),
+            due_soon_days: config.due_soon_days,
+            client,
+        })
+    }
+
+    /// Remind owners of secrets due within `days` (0 to disable), instead of
+    /// the configured lead time
+    pub fn with_due_soon_days(mut self, days: u32) -> Self {
+        self.due_soon_days = days;
+        self
+    }
+
+    /// Webhooks that should receive the report for `owner`
+    fn webhooks_for(&self, owner: Option<&str>) -> Vec<&WebhookSinkConfig> {
+        let claimed: Vec<_> = self
+            .webhooks
+            .iter()
+            .filter(|webhook| owner.is_some_and(|owner| webhook.owners.iter().any(|o| o == owner)))
+            .collect();
+        if !claimed.is_empty() {
+            return claimed;
+        }
+        self.webhooks
+            .iter()
+            .filter(|webhook| webhook.owners.is_empty())
+            .collect()
+    }
+
+    /// Send every owner their part of `report`, returning the number of
+    /// notifications sent
+    ///
+    /// A webhook that cannot be reached is logged and does not stop the others.
+    pub async fn notify(&self, report: &Report) -> usize {
+        let mut sent = 0;
+        for group in group_by_owner(report, self.due_soon_days) {
+            let webhooks = self.webhooks_for(group.owner.as_deref());
+            if webhooks.is_empty() {
+                warn!(
+                    "No webhook configured for owner {}",
+                    group.owner.as_deref().unwrap_or("(none)")
+                );
+                continue;
+            }
+            for webhook in webhooks {
+                match self.send(webhook, &group).await {
+                    Ok(()) => sent += 1,
+                    Err(e) => warn!("{}", redact::redact(&format!("{:#}", e))),
+                }
+            }
+        }
+        sent
+    }
+
+    async fn send(&self, webhook: &WebhookSinkConfig, group: &OwnerReport) -> Result<()> {
+        let mut payload = serde_json::to_value(group)?;
+        payload["text"] = group.summary().into();
+
+        let response = self
+            .client
+            .post(webhook.url.expose_secret())
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!(redact::redact(&e.to_string())))
+            .context("Failed to send notification")?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("Notification webhook returned status {}", status);
+        }
+        info!(
+            "Notified {} about {} overdue, {} failed and {} due soon secret(s)",
+            group.owner.as_deref().unwrap_or("unowned secrets"),
+            group.overdue.len(),
+            group.failed.len(),
+            group.due_soon.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{RunResults, SecretPosture};
+    use axum::extract::State;
+    use axum::Json;
+    use std::sync::{Arc, Mutex};
+
+    fn secret(path: &str, state: SecretState, owner: Option<&str>) -> SecretPosture {
+        SecretPosture {
+            path: path.to_string(),
+            state,
+            last_rotated: None,
+            due_at: None,
+            owner: owner.map(str::to_string),
+            error: None,
+        }
+    }
+
+    fn report() -> Report {
+        Report {
+            generated_at: Utc::now(),
+            backend: "memory".to_string(),
+            path: String::new(),
+            secrets: vec![
+                secret("pay/db", SecretState::Overdue, Some("payments")),
+                secret("pay/api", SecretState::Overdue, Some("payments")),
+                secret("search/key", SecretState::Current, Some("search")),
+                secret("legacy/token", SecretState::Overdue, None),
+            ],
+            run: Some(RunResults {
+                rotated: vec!["pay/db".to_string()],
+                failed: vec![("search/key".to_string(), "target refused".to_string())],
+            }),
+        }
+    }
+
+    #[test]
+    fn test_group_by_owner() {
+        let groups = group_by_owner(&report(), 0);
+        let owners: Vec<_> = groups.iter().map(|g| g.owner.as_deref()).collect();
+        assert_eq!(owners, vec![None, Some("payments"), Some("search")]);
+
+        // Rotated secrets are no longer overdue
+        assert_eq!(groups[1].overdue.len(), 1);
+        assert_eq!(groups[1].overdue[0].path, "pay/api");
+        assert_eq!(groups[2].failed[0].path, "search/key");
+        assert!(groups[2]
+            .summary()
+            .contains("1 failed to rotate (search/key)"));
+    }
+
+    #[test]
+    fn test_due_soon_reminders() {
+        let mut report = report();
+        report.run = None;
+        for (path, days) in [("search/key", 10), ("search/later", 30)] {
+            let mut due = secret(path, SecretState::Current, Some("search"));
+            due.due_at = Some(report.generated_at + ChronoDuration::days(days));
+            report.secrets.retain(|s| s.path != path);
+            report.secrets.push(due);
+        }
+
+        // Without a lead time, current secrets are not reported
+        let groups = group_by_owner(&report, 0);
+        assert!(groups.iter().all(|g| g.owner.as_deref() != Some("search")));
+
+        let groups = group_by_owner(&report, 14);
+        let search = groups
+            .iter()
+            .find(|g| g.owner.as_deref() == Some("search"))
+            .unwrap();
+        assert_eq!(search.due_soon.len(), 1);
+        assert_eq!(search.due_soon[0].path, "search/key");
+        assert!(search.summary().contains("1 due soon (search/key on "));
+    }
+
+    #[tokio::test]
+    async fn test_notify_routes_by_owner() {
+        type Received = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
+        let received: Received = Arc::default();
+        let app = axum::Router::new()
+            .route(
+                "/:hook",
+                axum::routing::post(
+                    |State(received): State<Received>,
+                     axum::extract::Path(hook): axum::extract::Path<String>,
+                     Json(body): Json<serde_json::Value>| async move {
+                        received.lock().unwrap().push((hook, body));
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config: NotificationsConfig = toml::from_str(&format!(
+            r#"
+            [[webhooks]]
+            url = "{base}/central"
+
+            [[webhooks]]
+            url = "{base}/payments"
+            owners = ["payments"]
+            "#
+        ))
+        .unwrap();
+        let notifier = Notifier::new(&config).unwrap();
+        assert_eq!(notifier.notify(&report()).await, 3);
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort_by(|a, b| a.0.cmp(&b.0));
+        let hooks: Vec<_> = received.iter().map(|(hook, _)| hook.as_str()).collect();
+        // Unowned secrets and owners without their own webhook go to the catch-all
+        assert_eq!(hooks, vec!["central", "central", "payments"]);
+        assert_eq!(received[2].1["owner"], "payments");
+        assert_eq!(received[2].1["overdue"][0]["path"], "pay/api");
+        assert!(received[2].1["text"].as_str().unwrap().contains("payments"));
+    }
+}
@@ -0,0 +1,116 @@
+//! Styled command output
+//!
+//! Results go to stdout and warnings and errors to stderr, each with a fixed
+//! prefix (`Warning:`, `Error:`, `Note:`) so log collectors can match on them.
+//! Notes that must not mix with piped output (a report or token printed to
+//! stdout) go to stderr as hints. Listings, tables and secret values are
+//! printed as they are.
+//! Color is used only when the stream is a terminal, and never with
+//! `--no-color` or when `NO_COLOR` is set.
+
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by `--no-color`
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Output stream a message is written to
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    fn color(self) -> bool {
+        if DISABLED.load(Ordering::Relaxed) || std::env::var_os("NO_COLOR").is_some() {
+            return false;
+        }
+        match self {
+            Stream::Stdout => std::io::stdout().is_terminal(),
+            Stream::Stderr => std::io::stderr().is_terminal(),
+        }
+    }
+}
+
+/// Turn color off for the rest of the process (`--no-color`)
+pub fn init(no_color: bool) {
+    DISABLED.store(no_color, Ordering::Relaxed);
+}
+
+/// A completed action, on stdout
+pub fn success(message: impl Display) {
+    println!("{}", paint(Stream::Stdout, GREEN, &message.to_string()));
+}
+
+/// Something worth knowing about a completed action, on stdout
+pub fn note(message: impl Display) {
+    println!("{} {}", paint(Stream::Stdout, CYAN, "Note:"), message);
+}
+
+/// Advice next to output that may be piped, on stderr
+pub fn hint(message: impl Display) {
+    eprintln!("{} {}", paint(Stream::Stderr, CYAN, "Note:"), message);
+}
+
+/// A problem that did not stop the command, on stderr
+pub fn warn(message: impl Display) {
+    eprintln!(
+        "{} {}",
+        paint(Stream::Stderr, &format!("{}{}", BOLD, YELLOW), "Warning:"),
+        message
+    );
+}
+
+/// A failure, on stderr
+pub fn error(message: impl Display) {
+    eprintln!(
+        "{} {}",
+        paint(Stream::Stderr, &format!("{}{}", BOLD, RED), "Error:"),
+        message
+    );
+}
+
+/// A heading for a list of failures, on stdout
+pub fn failure_heading(message: impl Display) {
+    println!("{}", paint(Stream::Stdout, RED, &message.to_string()));
+}
+
+fn paint(stream: Stream, style: &str, text: &str) -> String {
+    style_text(stream.color(), style, text)
+}
+
+fn style_text(color: bool, style: &str, text: &str) -> String {
+    if color {
+        format!("{}{}{}", style, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_style_text() {
+        assert_eq!(style_text(false, RED, "Error:"), "Error:");
+        assert_eq!(style_text(true, RED, "Error:"), "\x1b[31mError:\x1b[0m");
+    }
+
+    #[test]
+    fn test_no_color_when_disabled() {
+        init(true);
+        assert!(!Stream::Stdout.color());
+        assert!(!Stream::Stderr.color());
+        init(false);
+    }
+}
@@ -0,0 +1,232 @@
+//! Password rules enforced by a target, checked before a new value is sent
+//!
+//! Targets often reject passwords that are too long, contain characters they
+//! cannot store, or repeat a recent password. With a `password_policy` on the
+//! target, generated values are shaped to fit (length clamped, forbidden
+//! characters left out of the character set) and regenerated until they
+//! satisfy every rule, so mismatches show up before the target is touched
+//! instead of as a failed update:
+//!
+//! ```toml
+//! [targets.postgres.password_policy]
+//! max_length = 24
+//! forbidden_characters = "'\"\\@"
+//! require = ["lowercase", "uppercase", "digit"]
+//! history = 5   # no reuse of the last 5 values
+//! ```
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::generator::{GeneratorOptions, GeneratorStyle};
+
+/// Generated values tried before giving up on satisfying a policy
+const MAX_ATTEMPTS: usize = 50;
+
+/// A class of characters a password must contain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    /// Anything that is not an ASCII letter or digit
+    Symbol,
+}
+
+impl CharClass {
+    fn matches(self, c: char) -> bool {
+        match self {
+            CharClass::Lowercase => c.is_ascii_lowercase(),
+            CharClass::Uppercase => c.is_ascii_uppercase(),
+            CharClass::Digit => c.is_ascii_digit(),
+            CharClass::Symbol => !c.is_ascii_alphanumeric(),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            CharClass::Lowercase => "lowercase letter",
+            CharClass::Uppercase => "uppercase letter",
+            CharClass::Digit => "digit",
+            CharClass::Symbol => "symbol",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PasswordPolicy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+
+    /// Characters the target does not accept
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub forbidden_characters: String,
+
+    /// Character classes every password must contain
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub require: Vec<CharClass>,
+
+    /// Number of previous values that may not be reused
+    #[serde(default)]
+    pub history: usize,
+}
+
+impl PasswordPolicy {
+    /// Rules `value` breaks, given the secret's previous values (newest first)
+    pub fn violations(&self, value: &str, previous: &[String]) -> Vec<String> {
+        let mut violations = Vec::new();
+        let length = value.chars().count();
+        if let Some(min) = self.min_length.filter(|min| length < *min) {
+            violations.push(format!("shorter than {} characters", min));
+        }
+        if let Some(max) = self.max_length.filter(|max| length > *max) {
+            violations.push(format!("longer than {} characters", max));
+        }
+        let mut forbidden: Vec<char> = value
+            .chars()
+            .filter(|c| self.forbidden_characters.contains(*c))
+            .collect();
+        forbidden.sort_unstable();
+        forbidden.dedup();
+        if !forbidden.is_empty() {
+            // Name the characters, never the value
+            violations.push(format!(
+                "contains forbidden characters {}",
+                forbidden.into_iter().collect::<String>()
+            ));
+        }
+        for class in &self.require {
+            if !value.chars().any(|c| class.matches(c)) {
+                violations.push(format!("has no {}", class.label()));
+            }
+        }
+        if previous
+            .iter()
+            .take(self.history)
+            .any(|old| old.as_str() == value)
+        {
+            violations.push(format!("reuses one of the last {} values", self.history));
+        }
+        violations
+    }
+
+    /// Fail with every rule `value` breaks
+    pub fn check(&self, value: &str, previous: &[String]) -> Result<()> {
+        let violations = self.violations(value, previous);
+        if !violations.is_empty() {
+            anyhow::bail!(
+                "Value violates the target's password policy: {}",
+                violations.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Adjust generator options so generated values can satisfy the policy:
+    /// the length is clamped to the allowed range and forbidden characters
+    /// are removed from the character set
+    pub fn shape(&self, options: &mut GeneratorOptions) -> Result<()> {
+        if options.style == GeneratorStyle::WireGuard {
+            // Keys have a fixed format; they can only be checked
+            return Ok(());
+        }
+        let clamped = options
+            .length
+            .max(self.min_length.unwrap_or(0))
+            .min(self.max_length.unwrap_or(usize::MAX));
+        if clamped != options.length {
+            warn!(
+                "Generating {} characters instead of {} to fit the target's password policy",
+                clamped, options.length
+            );
+            options.length = clamped;
+        }
+        if !self.forbidden_characters.is_empty() {
+            let charset: String = options
+                .characters()
+                .chars()
+                .filter(|c| !self.forbidden_characters.contains(*c))
+                .collect();
+            if charset.is_empty() {
+                anyhow::bail!("The password policy forbids every character of the character set");
+            }
+            options.charset = Some(charset);
+        }
+        Ok(())
+    }
+}
+
+/// Generate a value satisfying every policy, regenerating on violations
+pub fn generate(
+    mut options: GeneratorOptions,
+    policies: &[&PasswordPolicy],
+    previous: &[String],
+) -> Result<String> {
+    for policy in policies {
+        policy.shape(&mut options)?;
+    }
+    let mut violations = Vec::new();
+    for _ in 0..MAX_ATTEMPTS {
+        let value = options.generate();
+        violations = policies
+            .iter()
+            .flat_map(|policy| policy.violations(&value, previous))
+            .collect();
+        if violations.is_empty() {
+            return Ok(value);
+        }
+    }
+    anyhow::bail!(
+        "No generated value satisfied the target's password policy after {} attempts (last: {}); \
+         check secret_length, generator_style and charset",
+        MAX_ATTEMPTS,
+        violations.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_within_policy() -> Result<()> {
+        let policy = PasswordPolicy {
+            max_length: Some(12),
+            forbidden_characters: "@'".to_string(),
+            require: vec![CharClass::Digit, CharClass::Uppercase],
+            history: 2,
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            let value = generate(GeneratorOptions::new(32), &[&policy], &[])?;
+            assert_eq!(value.len(), 12);
+            assert!(policy.violations(&value, &[]).is_empty());
+        }
+
+        let previous = vec!["Abc1".to_string(), "Xyz2".to_string(), "Old3".to_string()];
+        let violations = policy.violations("a@b'", &previous);
+        assert_eq!(
+            violations,
+            [
+                "contains forbidden characters '@",
+                "has no digit",
+                "has no uppercase letter"
+            ]
+        );
+        assert!(policy.check("Xyz2", &previous).is_err());
+        // Only the last `history` values count
+        assert!(policy.check("Old3", &previous).is_ok());
+
+        // Digits-only values can never contain an uppercase letter
+        let mut numeric = GeneratorOptions::new(8);
+        numeric.style = GeneratorStyle::Numeric;
+        let err = generate(numeric, &[&policy], &[]).unwrap_err();
+        assert!(err.to_string().contains("has no uppercase letter"));
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+//! Narrowing `scan` and `auto` to a subset of secrets, by path or metadata
+//!
+//! Globs use `/` as the separator: `*` stays within one path segment and `**`
+//! spans any number of them, so `prod/**` matches every secret under `prod/`.
+
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Include globs and regular expressions a secret path has to match
+///
+/// A path passes when it matches at least one glob (if any are given) and at
+/// least one regex (if any are given). An empty filter matches everything.
+#[derive(Debug, Default)]
+pub struct PathFilter {
+    include: Vec<Pattern>,
+    regexes: Vec<Regex>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], regexes: &[String]) -> Result<Self> {
+        let include = include
+            .iter()
+            .map(|glob| {
+                Pattern::new(glob).with_context(|| format!("Invalid include pattern '{}'", glob))
+            })
+            .collect::<Result<_>>()?;
+        let regexes = regexes
+            .iter()
+            .map(|regex| Regex::new(regex).with_context(|| format!("Invalid regex '{}'", regex)))
+            .collect::<Result<_>>()?;
+        Ok(Self { include, regexes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.regexes.is_empty()
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_start_matches('/');
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|glob| glob.matches_with(path, GLOB_OPTIONS)))
+            && (self.regexes.is_empty() || self.regexes.iter().any(|regex| regex.is_match(path)))
+    }
+
+    /// Keep only the paths that match, preserving their order
+    pub fn apply(&self, paths: Vec<String>) -> Vec<String> {
+        if self.is_empty() {
+            return paths;
+        }
+        paths
+            .into_iter()
+            .filter(|path| self.matches(path))
+            .collect()
+    }
+}
+
+/// A `key=value` condition on secret metadata, as given to `--where`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataCondition {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for MetadataCondition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok(Self {
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+            }),
+            _ => anyhow::bail!(
+                "Invalid condition '{}'. Expected KEY=VALUE, e.g. team=payments",
+                s
+            ),
+        }
+    }
+}
+
+/// Metadata conditions a secret has to satisfy to be selected
+///
+/// Conditions on different keys must all hold; several conditions on the same
+/// key match any of their values (`env=prod` or `env=staging`).
+#[derive(Debug, Clone, Default)]
+pub struct MetadataSelector {
+    conditions: Vec<MetadataCondition>,
+}
+
+impl MetadataSelector {
+    pub fn new(conditions: Vec<MetadataCondition>) -> Self {
+        Self { conditions }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    pub fn matches(&self, metadata: &HashMap<String, String>) -> bool {
+        self.conditions.iter().all(|condition| {
+            self.conditions
+                .iter()
+                .filter(|other| other.key == condition.key)
+                .any(|other| metadata.get(&other.key) == Some(&other.value))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], regexes: &[&str]) -> PathFilter {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        PathFilter::new(&strings(include), &strings(regexes)).unwrap()
+    }
+
+    #[test]
+    fn test_path_filter() {
+        assert!(filter(&[], &[]).matches("anything/at/all"));
+
+        let prod = filter(&["prod/**"], &[]);
+        assert!(prod.matches("prod/db"));
+        assert!(prod.matches("prod/api/token"));
+        assert!(!prod.matches("staging/db"));
+
+        let one_level = filter(&["prod/*"], &[]);
+        assert!(one_level.matches("prod/db"));
+        assert!(!one_level.matches("prod/api/token"));
+
+        // Flat names such as AWS secret IDs
+        let flat = filter(&[], &["^prod-(db|cache)-"]);
+        assert!(flat.matches("prod-db-password"));
+        assert!(!flat.matches("prod-web-token"));
+
+        // Both kinds must match when both are given
+        let both = filter(&["prod/**", "shared/**"], &["password$"]);
+        assert!(both.matches("shared/db/password"));
+        assert!(!both.matches("shared/db/token"));
+        assert!(!both.matches("staging/db/password"));
+
+        assert_eq!(
+            prod.apply(vec!["prod/b".into(), "dev/a".into(), "prod/a".into()]),
+            vec!["prod/b", "prod/a"]
+        );
+        assert!(PathFilter::new(&[], &["(".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_metadata_selector() {
+        let selector = MetadataSelector::new(
+            ["team=payments", "env=prod", "env = staging"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        );
+        let metadata = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+
+        assert!(selector.matches(&metadata(&[("team", "payments"), ("env", "prod")])));
+        assert!(selector.matches(&metadata(&[("team", "payments"), ("env", "staging")])));
+        assert!(!selector.matches(&metadata(&[("team", "payments"), ("env", "dev")])));
+        assert!(!selector.matches(&metadata(&[("env", "prod")])));
+        assert!(MetadataSelector::default().matches(&HashMap::new()));
+
+        assert!("team".parse::<MetadataCondition>().is_err());
+        assert!("=payments".parse::<MetadataCondition>().is_err());
+        assert_eq!(
+            "owner=a=b".parse::<MetadataCondition>().unwrap().value,
+            "a=b"
+        );
+    }
+}
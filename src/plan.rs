@@ -0,0 +1,342 @@
+//! Rotation plans for `auto --dry-run`
+//!
+//! A plan says, for each due secret, why it is due and what rotating it would
+//! change (the key, the target login, environment variables and the files
+//! they live in) without reading or printing any secret value, so it can be
+//! attached to a change-review ticket.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::backends::SecretBackend;
+use crate::certs;
+use crate::config::EnvVarNames;
+use crate::env_updater::{self, EnvUpdater};
+use crate::rotation;
+use crate::targets::Target;
+
+/// Output format of a rotation plan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PlanFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+/// What rotating one secret would do
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedRotation {
+    pub path: String,
+    /// Why the secret is due, in words
+    pub reason: String,
+    pub last_rotated: Option<DateTime<Utc>>,
+    /// Rotation period, for secrets rotated on a schedule
+    pub period: Option<String>,
+    pub due_at: Option<DateTime<Utc>>,
+    /// Key whose value would be replaced
+    pub key: Option<String>,
+    /// Type of the target whose password would follow the secret
+    pub target: Option<String>,
+    pub target_username: Option<String>,
+    pub env_vars: Vec<String>,
+    /// Files (or other destinations) the environment variables are written to
+    pub files: Vec<String>,
+}
+
+/// What an `auto` run would do
+#[derive(Debug, Clone, Serialize)]
+pub struct RotationPlan {
+    pub generated_at: DateTime<Utc>,
+    pub secrets: Vec<PlannedRotation>,
+    /// Due secrets a per-run cap would leave for a later run
+    pub deferred: Vec<String>,
+}
+
+/// What the run would update besides the secrets themselves
+pub struct PlanOptions<'a> {
+    pub default_period_months: u32,
+    /// Set when target passwords would be updated
+    pub target: Option<&'a dyn Target>,
+    /// Set when environment variables would be updated
+    pub env: Option<(&'a EnvUpdater, &'a HashMap<String, EnvVarNames>)>,
+}
+
+/// Plan the rotation of one due secret
+///
+/// Backend errors are not fatal: whatever could not be read is left out of
+/// the plan.
+pub async fn plan_secret(
+    backend: &dyn SecretBackend,
+    path: &str,
+    options: &PlanOptions<'_>,
+) -> PlannedRotation {
+    let metadata = backend.read_metadata(path).await.unwrap_or_default();
+    let is_certificate = certs::is_certificate(&metadata);
+
+    let key = if is_certificate {
+        Some("certificate".to_string())
+    } else {
+        backend
+            .read_secret(path)
+            .await
+            .ok()
+            .map(|secret| rotation::key_to_rotate(&secret.data, &metadata))
+    };
+
+    let target_username = options
+        .target
+        .and_then(|_| rotation::target_username(&metadata))
+        .filter(|_| !is_certificate);
+    let target = options
+        .target
+        .filter(|_| target_username.is_some())
+        .map(|target| target.target_type().to_string());
+
+    let (env_vars, files) = match options.env {
+        Some((updater, mappings)) => {
+            let names = env_updater::env_var_names(path, &metadata, mappings);
+            let mut files: Vec<String> = Vec::new();
+            for name in &names {
+                for file in updater.destinations(name) {
+                    if !files.contains(&file) {
+                        files.push(file);
+                    }
+                }
+            }
+            (names, files)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let last_rotated = rotation::last_rotated(&metadata);
+    let (period, due_at) = if is_certificate {
+        (None, certs::renewal_due_at(&metadata))
+    } else {
+        let period = rotation::rotation_period(&metadata, options.default_period_months);
+        (
+            Some(period.to_string()),
+            rotation::due_at(&metadata, options.default_period_months),
+        )
+    };
+
+    PlannedRotation {
+        path: path.to_string(),
+        reason: reason(
+            &metadata,
+            is_certificate,
+            last_rotated,
+            period.as_deref(),
+            due_at,
+        ),
+        last_rotated,
+        period,
+        due_at,
+        key,
+        target,
+        target_username,
+        env_vars,
+        files,
+    }
+}
+
+fn reason(
+    metadata: &HashMap<String, String>,
+    is_certificate: bool,
+    last_rotated: Option<DateTime<Utc>>,
+    period: Option<&str>,
+    due_at: Option<DateTime<Utc>>,
+) -> String {
+    if is_certificate {
+        return match (certs::not_after(metadata), due_at) {
+            (Some(not_after), Some(due_at)) => format!(
+                "certificate expires {}, renewal due {}",
+                format_date(not_after),
+                format_date(due_at)
+            ),
+            _ => "certificate expiry unknown".to_string(),
+        };
+    }
+    match (
+        metadata.get(rotation::LAST_ROTATED_KEY),
+        last_rotated,
+        due_at,
+    ) {
+        (None, _, _) => "never rotated".to_string(),
+        (Some(recorded), None, _) => format!("last_rotated '{}' is not a valid date", recorded),
+        (Some(_), Some(last), Some(due_at)) => format!(
+            "due {} ({} after {})",
+            format_date(due_at),
+            period.unwrap_or("period"),
+            format_date(last)
+        ),
+        (Some(_), Some(last), None) => format!("last rotated {}", format_date(last)),
+    }
+}
+
+fn format_date(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+impl RotationPlan {
+    pub fn new(secrets: Vec<PlannedRotation>, deferred: Vec<String>) -> Self {
+        Self {
+            generated_at: Utc::now(),
+            secrets,
+            deferred,
+        }
+    }
+
+    pub fn render(&self, format: PlanFormat) -> anyhow::Result<String> {
+        match format {
+            PlanFormat::Table => Ok(self.render_table()),
+            PlanFormat::Json => {
+                let mut json = serde_json::to_string_pretty(self)?;
+                json.push('\n');
+                Ok(json)
+            }
+        }
+    }
+
+    /// Columns padded to line up in a terminal, which is also a Markdown table
+    fn render_table(&self) -> String {
+        let header = ["PATH", "REASON", "KEY", "TARGET", "ENV VARS", "FILES"];
+        let rows: Vec<[String; 6]> = self
+            .secrets
+            .iter()
+            .map(|secret| {
+                let target = match (&secret.target, &secret.target_username) {
+                    (Some(target), Some(username)) => format!("{} user {}", target, username),
+                    _ => "-".to_string(),
+                };
+                let list = |items: &[String]| {
+                    if items.is_empty() {
+                        "-".to_string()
+                    } else {
+                        items.join(", ")
+                    }
+                };
+                [
+                    secret.path.clone(),
+                    secret.reason.clone(),
+                    secret.key.clone().unwrap_or_else(|| "-".to_string()),
+                    target,
+                    list(&secret.env_vars),
+                    list(&secret.files),
+                ]
+            })
+            .collect();
+
+        let widths: Vec<usize> = (0..header.len())
+            .map(|i| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([header[i].len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let line = |cells: &[String]| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+
+        let mut out = line(&header.map(String::from));
+        out.push_str(&line(
+            &widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>(),
+        ));
+        for row in &rows {
+            out.push_str(&line(row));
+        }
+        let _ = writeln!(out, "\n{} secret(s) would be rotated", self.secrets.len());
+        if !self.deferred.is_empty() {
+            let _ = writeln!(
+                out,
+                "{} more deferred to a later run: {}",
+                self.deferred.len(),
+                self.deferred.join(", ")
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::FileBackend;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_plan_secret() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?;
+        backend
+            .write_secret(
+                "app/db",
+                HashMap::from([
+                    ("username".to_string(), "app".to_string()),
+                    ("db_password".to_string(), "old".to_string()),
+                ]),
+            )
+            .await?;
+        backend
+            .update_metadata(
+                "app/db",
+                HashMap::from([
+                    ("rotation_enabled".to_string(), "true".to_string()),
+                    (
+                        "last_rotated".to_string(),
+                        "2025-01-31T12:00:00Z".to_string(),
+                    ),
+                    ("rotation_period_months".to_string(), "1".to_string()),
+                    ("env_var".to_string(), "DB_PASSWORD".to_string()),
+                ]),
+            )
+            .await?;
+
+        let home = TempDir::new()?;
+        std::fs::write(home.path().join(".bashrc"), "")?;
+        let updater = EnvUpdater::with_home_dir(home.path().to_path_buf());
+        let mappings = HashMap::new();
+        let options = PlanOptions {
+            default_period_months: 6,
+            target: None,
+            env: Some((&updater, &mappings)),
+        };
+
+        let planned = plan_secret(&backend, "app/db", &options).await;
+        assert_eq!(planned.key.as_deref(), Some("db_password"));
+        assert_eq!(planned.period.as_deref(), Some("1 months"));
+        assert_eq!(
+            planned.reason,
+            "due 2025-02-28 12:00 UTC (1 months after 2025-01-31 12:00 UTC)"
+        );
+        assert_eq!(planned.env_vars, vec!["DB_PASSWORD"]);
+        assert_eq!(
+            planned.files,
+            vec![home.path().join(".bashrc").display().to_string()]
+        );
+        assert!(planned.target.is_none());
+
+        let never = plan_secret(&backend, "app/missing", &options).await;
+        assert_eq!(never.reason, "never rotated");
+        assert!(never.key.is_none());
+
+        let plan = RotationPlan::new(vec![planned], vec!["app/later".to_string()]);
+        let table = plan.render(PlanFormat::Table)?;
+        assert!(table.starts_with("| PATH "));
+        assert!(table.contains("| app/db "));
+        assert!(table.contains("1 more deferred to a later run: app/later"));
+
+        let json: serde_json::Value = serde_json::from_str(&plan.render(PlanFormat::Json)?)?;
+        assert_eq!(json["secrets"][0]["key"], "db_password");
+        assert_eq!(json["deferred"][0], "app/later");
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+//! Registry for backends and targets provided outside this crate
+//!
+//! Downstream crates register a factory under a name before running the CLI;
+//! the name can then be used wherever a built-in one is accepted (`backend`,
+//! `routes`, `[targets] plugin`). Settings for a plugin live in its own
+//! `[plugins.<name>]` config section, read with [`Config::plugin_settings`].
+//!
+//! ```no_run
+//! use secret_rotator::backends::{Backend, FileBackend};
+//! use secret_rotator::config::Config;
+//! use secret_rotator::plugins;
+//!
+//! plugins::register_backend("mykv", |config: &Config| -> anyhow::Result<Backend> {
+//!     let dir: String = config.plugin_settings("mykv")?.unwrap_or_default();
+//!     Ok(Box::new(FileBackend::new(dir)?))
+//! });
+//! ```
+//!
+//! Factories are consulted before the built-in backends and targets, so a
+//! registered name also replaces a built-in one of the same name.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::backends::{Backend, SecretBackend};
+use crate::config::Config;
+use crate::targets::TargetInstance;
+
+/// Creates a backend from the configuration
+#[async_trait::async_trait]
+pub trait BackendFactory: Send + Sync {
+    async fn create(&self, config: &Config) -> Result<Backend>;
+}
+
+#[async_trait::async_trait]
+impl<F> BackendFactory for F
+where
+    F: Fn(&Config) -> Result<Backend> + Send + Sync,
+{
+    async fn create(&self, config: &Config) -> Result<Backend> {
+        self(config)
+    }
+}
+
+/// Creates a target from the configuration
+///
+/// The secret backend is passed in so that target credentials can be read
+/// from it (like `password_path` for the PostgreSQL target).
+#[async_trait::async_trait]
+pub trait TargetFactory: Send + Sync {
+    async fn create(&self, config: &Config, backend: &dyn SecretBackend) -> Result<TargetInstance>;
+}
+
+#[async_trait::async_trait]
+impl<F> TargetFactory for F
+where
+    F: Fn(&Config, &dyn SecretBackend) -> Result<TargetInstance> + Send + Sync,
+{
+    async fn create(&self, config: &Config, backend: &dyn SecretBackend) -> Result<TargetInstance> {
+        self(config, backend)
+    }
+}
+
+static BACKENDS: RwLock<Option<HashMap<String, Arc<dyn BackendFactory>>>> = RwLock::new(None);
+static TARGETS: RwLock<Option<HashMap<String, Arc<dyn TargetFactory>>>> = RwLock::new(None);
+
+fn insert<T: ?Sized>(
+    registry: &RwLock<Option<HashMap<String, Arc<T>>>>,
+    name: &str,
+    value: Arc<T>,
+) {
+    registry
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(name.to_lowercase(), value);
+}
+
+fn lookup<T: ?Sized>(
+    registry: &RwLock<Option<HashMap<String, Arc<T>>>>,
+    name: &str,
+) -> Option<Arc<T>> {
+    registry
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .as_ref()
+        .and_then(|factories| factories.get(&name.to_lowercase()).cloned())
+}
+
+/// Register a backend factory under `name` (case-insensitive)
+pub fn register_backend(name: &str, factory: impl BackendFactory + 'static) {
+    insert(
+        &BACKENDS,
+        name,
+        Arc::new(factory) as Arc<dyn BackendFactory>,
+    );
+}
+
+/// Register a target factory under `name` (case-insensitive)
+pub fn register_target(name: &str, factory: impl TargetFactory + 'static) {
+    insert(&TARGETS, name, Arc::new(factory) as Arc<dyn TargetFactory>);
+}
+
+/// Factory registered for a backend name, if any
+pub fn backend_factory(name: &str) -> Option<Arc<dyn BackendFactory>> {
+    lookup(&BACKENDS, name)
+}
+
+/// Factory registered for a target name, if any
+pub fn target_factory(name: &str) -> Option<Arc<dyn TargetFactory>> {
+    lookup(&TARGETS, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::FileBackend;
+    use crate::structured_file::StructuredFormat;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_register_backend() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().to_path_buf();
+        register_backend("Test-KV", move |_: &Config| -> Result<Backend> {
+            Ok(Box::new(FileBackend::new(&dir)?))
+        });
+
+        let factory = backend_factory("test-kv").expect("factory registered");
+        let config = Config::parse("backend = \"test-kv\"", StructuredFormat::Toml, None)?;
+        let backend = factory.create(&config).await?;
+        assert_eq!(backend.backend_type(), "file");
+
+        assert!(backend_factory("unknown-kv").is_none());
+        assert!(target_factory("test-kv").is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+//! Progress reporting for long `scan` and `auto` runs
+//!
+//! On a terminal the current item is shown on a single line that is redrawn
+//! in place. Otherwise (CI logs, cron mail) a line is printed every
+//! [`LOG_INTERVAL`], so a long run can be told apart from a hung one without
+//! flooding the log. Progress goes to stderr, leaving stdout to the results.
+
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often progress is printed when stderr is not a terminal
+pub const LOG_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Progress of one pass over a number of items
+pub struct Progress {
+    state: Option<Mutex<State>>,
+}
+
+struct State {
+    terminal: bool,
+    verb: String,
+    total: usize,
+    /// Whether more items may still be added to `total`
+    more: bool,
+    done: usize,
+    started: Instant,
+    last_logged: Instant,
+    /// Whether a redrawn line is currently on screen
+    shown: bool,
+}
+
+impl Progress {
+    /// Progress on stderr, or nothing when `quiet` is set
+    pub fn stderr(quiet: bool) -> Self {
+        if quiet {
+            return Self::hidden();
+        }
+        let now = Instant::now();
+        Self {
+            state: Some(Mutex::new(State {
+                terminal: std::io::stderr().is_terminal(),
+                verb: String::new(),
+                total: 0,
+                more: false,
+                done: 0,
+                started: now,
+                last_logged: now,
+                shown: false,
+            })),
+        }
+    }
+
+    /// Progress that is never shown
+    pub fn hidden() -> Self {
+        Self { state: None }
+    }
+
+    /// Start a pass over `total` items, described by `verb` (e.g. "rotating")
+    pub fn start(&self, verb: &str, total: usize) {
+        self.with_state(|state| {
+            let now = Instant::now();
+            state.verb = verb.to_string();
+            state.total = total;
+            state.more = false;
+            state.done = 0;
+            state.started = now;
+            state.last_logged = now;
+        });
+    }
+
+    /// Add `count` items to the pass, for work that arrives in pages; `more`
+    /// says whether further pages may follow
+    pub fn add(&self, count: usize, more: bool) {
+        self.with_state(|state| {
+            state.total += count;
+            state.more = more;
+        });
+    }
+
+    /// Report that work on `item` is starting
+    pub fn step(&self, item: &str) {
+        self.with_state(|state| {
+            state.done += 1;
+            let line = status_line(
+                &state.verb,
+                state.done,
+                state.total,
+                state.more,
+                item,
+                state.started.elapsed(),
+            );
+            let mut stderr = std::io::stderr().lock();
+            if state.terminal {
+                let _ = write!(stderr, "\r\x1b[2K{}", line);
+                let _ = stderr.flush();
+                state.shown = true;
+            } else if state.last_logged.elapsed() >= LOG_INTERVAL {
+                let _ = writeln!(stderr, "{}", line);
+                state.last_logged = Instant::now();
+            }
+        });
+    }
+
+    /// Remove the progress line from the terminal, before printing other
+    /// output or when the pass is over
+    pub fn clear(&self) {
+        self.with_state(|state| {
+            if state.shown {
+                let mut stderr = std::io::stderr().lock();
+                let _ = write!(stderr, "\r\x1b[2K");
+                let _ = stderr.flush();
+                state.shown = false;
+            }
+        });
+    }
+
+    fn with_state(&self, f: impl FnOnce(&mut State)) {
+        if let Some(state) = &self.state {
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut state);
+        }
+    }
+}
+
+/// `[123/500] rotating prod/db/users... ETA 3m12s`
+///
+/// `done` counts the item being started, so the estimate is based on the
+/// items before it. While `more` items may follow, the total is shown as a
+/// lower bound (`[123/500+]`) and there is no estimate.
+fn status_line(
+    verb: &str,
+    done: usize,
+    total: usize,
+    more: bool,
+    item: &str,
+    elapsed: Duration,
+) -> String {
+    let plus = if more { "+" } else { "" };
+    let mut line = format!("[{}/{}{}] {} {}...", done, total, plus, verb, item);
+    let finished = done.saturating_sub(1);
+    if !more && finished > 0 && total > finished {
+        let per_item = elapsed / finished as u32;
+        let remaining = per_item * (total - finished) as u32;
+        line.push_str(&format!(" ETA {}", format_duration(remaining)));
+    }
+    line
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m{:02}s", m, s),
+        (h, m, _) => format!("{}h{:02}m", h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line() {
+        assert_eq!(
+            status_line("scanning", 1, 500, false, "prod/a", Duration::from_secs(0)),
+            "[1/500] scanning prod/a..."
+        );
+        assert_eq!(
+            status_line("scanning", 2, 100, true, "prod/b", Duration::from_secs(1)),
+            "[2/100+] scanning prod/b..."
+        );
+        // 100 items in 40s leaves 400 items, about 160s
+        assert_eq!(
+            status_line(
+                "rotating",
+                101,
+                500,
+                false,
+                "prod/db/users",
+                Duration::from_secs(40)
+            ),
+            "[101/500] rotating prod/db/users... ETA 2m40s"
+        );
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1h02m");
+    }
+}
@@ -0,0 +1,488 @@
+//! Shareable rotation posture reports (`asr report`)
+//!
+//! A report lists every secret under a path with its rotation state (overdue,
+//! current, not flagged) and, when the report is generated together with a
+//! rotation run, which secrets were rotated and which failed and why. Reports
+//! only contain paths and dates, never secret values.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::backends::SecretBackend;
+use crate::certs;
+use crate::rotation;
+use crate::rotator::RotationReport;
+use crate::transfer;
+
+/// Output format for rotation reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Html,
+    #[value(name = "md", alias = "markdown")]
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Pick the format from an output file extension (Markdown unless `.html`/`.htm`)
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                ReportFormat::Html
+            }
+            _ => ReportFormat::Markdown,
+        }
+    }
+}
+
+/// Rotation state of a single secret
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretState {
+    /// Due for rotation
+    Overdue,
+    /// Rotation enabled and not yet due
+    Current,
+    /// Not flagged for rotation
+    Unmanaged,
+    /// Metadata could not be read
+    Error,
+}
+
+impl SecretState {
+    fn label(self) -> &'static str {
+        match self {
+            SecretState::Overdue => "Overdue",
+            SecretState::Current => "Current",
+            SecretState::Unmanaged => "Not flagged",
+            SecretState::Error => "Error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SecretPosture {
+    pub path: String,
+    pub state: SecretState,
+    pub last_rotated: Option<DateTime<Utc>>,
+    pub due_at: Option<DateTime<Utc>>,
+    /// Team responsible for the secret (`owner` metadata)
+    pub owner: Option<String>,
+    /// Error reading the secret's metadata
+    pub error: Option<String>,
+}
+
+/// Results of the rotation run included in a report
+#[derive(Debug, Clone, Default)]
+pub struct RunResults {
+    pub rotated: Vec<String>,
+    /// Secrets that failed to rotate, with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+impl From<&RotationReport> for RunResults {
+    fn from(report: &RotationReport) -> Self {
+        Self {
+            rotated: report.rotated.iter().map(|r| r.path.clone()).collect(),
+            failed: report
+                .failed
+                .iter()
+                .map(|(path, e)| (path.clone(), format!("{:#}", e)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub generated_at: DateTime<Utc>,
+    pub backend: String,
+    pub path: String,
+    pub secrets: Vec<SecretPosture>,
+    pub run: Option<RunResults>,
+}
+
+/// Classify a secret from its metadata
+fn posture(path: String, metadata: &HashMap<String, String>, default_period: u32) -> SecretPosture {
+    let last_rotated = rotation::last_rotated(metadata);
+    let enabled = rotation::rotation_enabled(metadata);
+    let due_at = if certs::is_certificate(metadata) {
+        certs::renewal_due_at(metadata).filter(|_| enabled)
+    } else {
+        rotation::due_at(metadata, default_period).filter(|_| enabled)
+    };
+
+    let state = if !enabled {
+        SecretState::Unmanaged
+    } else if rotation::needs_rotation(&Some(metadata.clone()), default_period) {
+        SecretState::Overdue
+    } else {
+        SecretState::Current
+    };
+
+    SecretPosture {
+        path,
+        state,
+        last_rotated,
+        due_at,
+        owner: rotation::owner(metadata),
+        error: None,
+    }
+}
+
+impl Report {
+    /// Collect the rotation state of every secret under `path`
+    pub async fn collect(
+        backend: &dyn SecretBackend,
+        path: &str,
+        default_period: u32,
+    ) -> Result<Self> {
+        let listed = read_all_metadata(backend, path).await?;
+
+        let mut secrets = Vec::with_capacity(listed.len());
+        for (secret_path, metadata) in listed {
+            match metadata {
+                Ok(metadata) => secrets.push(posture(secret_path, &metadata, default_period)),
+                Err(e) => secrets.push(SecretPosture {
+                    path: secret_path,
+                    state: SecretState::Error,
+                    last_rotated: None,
+                    due_at: None,
+                    owner: None,
+                    error: Some(format!("{:#}", e)),
+                }),
+            }
+        }
+        secrets.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(Self {
+            generated_at: Utc::now(),
+            backend: backend.backend_type().to_string(),
+            path: path.to_string(),
+            secrets,
+            run: None,
+        })
+    }
+
+    /// Include the results of a rotation run
+    pub fn with_run(mut self, run: RunResults) -> Self {
+        self.run = Some(run);
+        self
+    }
+
+    pub fn count(&self, state: SecretState) -> usize {
+        self.secrets.iter().filter(|s| s.state == state).count()
+    }
+
+    fn overdue(&self) -> impl Iterator<Item = &SecretPosture> {
+        self.secrets
+            .iter()
+            .filter(|s| s.state == SecretState::Overdue)
+    }
+
+    fn scope(&self) -> &str {
+        if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.render_markdown(),
+            ReportFormat::Html => self.render_html(),
+        }
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# Secret Rotation Report\n");
+        let _ = writeln!(
+            out,
+            "Generated {} for `{}` ({} backend)\n",
+            format_date(Some(self.generated_at)),
+            self.scope(),
+            self.backend
+        );
+
+        out.push_str("## Summary\n\n| State | Secrets |\n|-------|---------|\n");
+        for (label, count) in self.summary() {
+            let _ = writeln!(out, "| {} | {} |", label, count);
+        }
+
+        if let Some(ref run) = self.run {
+            let _ = writeln!(
+                out,
+                "\n## Rotation Run\n\n{} rotated, {} failed\n",
+                run.rotated.len(),
+                run.failed.len()
+            );
+            for path in &run.rotated {
+                let _ = writeln!(out, "- Rotated `{}`", path);
+            }
+            for (path, reason) in &run.failed {
+                let _ = writeln!(out, "- **Failed** `{}`: {}", path, markdown_cell(reason));
+            }
+        }
+
+        out.push_str("\n## Overdue Secrets\n\n");
+        if self.count(SecretState::Overdue) == 0 {
+            out.push_str("None\n");
+        } else {
+            out.push_str("| Secret | Last rotated | Due |\n|--------|--------------|-----|\n");
+            for secret in self.overdue() {
+                let _ = writeln!(
+                    out,
+                    "| `{}` | {} | {} |",
+                    markdown_cell(&secret.path),
+                    format_date(secret.last_rotated),
+                    format_date(secret.due_at)
+                );
+            }
+        }
+
+        out.push_str("\n## All Secrets\n\n");
+        out.push_str("| Secret | State | Last rotated | Next due |\n");
+        out.push_str("|--------|-------|--------------|----------|\n");
+        for secret in &self.secrets {
+            let state = match secret.error {
+                Some(ref error) => format!("{}: {}", secret.state.label(), error),
+                None => secret.state.label().to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "| `{}` | {} | {} | {} |",
+                markdown_cell(&secret.path),
+                markdown_cell(&state),
+                format_date(secret.last_rotated),
+                format_date(secret.due_at)
+            );
+        }
+
+        out
+    }
+
+    fn render_html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str("<title>Secret Rotation Report</title>\n<style>\n");
+        out.push_str(
+            "body { font-family: sans-serif; margin: 2em; }\n\
+             table { border-collapse: collapse; margin-bottom: 1.5em; }\n\
+             th, td { border: 1px solid #ccc; padding: 4px 10px; text-align: left; }\n\
+             .overdue { color: #b00020; } .current { color: #1b7f3b; } .error { color: #b00020; }\n",
+        );
+        out.push_str("</style>\n</head>\n<body>\n<h1>Secret Rotation Report</h1>\n");
+        let _ = writeln!(
+            out,
+            "<p>Generated {} for <code>{}</code> ({} backend)</p>",
+            format_date(Some(self.generated_at)),
+            html_escape(self.scope()),
+            html_escape(&self.backend)
+        );
+
+        out.push_str("<h2>Summary</h2>\n<table>\n<tr><th>State</th><th>Secrets</th></tr>\n");
+        for (label, count) in self.summary() {
+            let _ = writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", label, count);
+        }
+        out.push_str("</table>\n");
+
+        if let Some(ref run) = self.run {
+            let _ = writeln!(
+                out,
+                "<h2>Rotation Run</h2>\n<p>{} rotated, {} failed</p>\n<ul>",
+                run.rotated.len(),
+                run.failed.len()
+            );
+            for path in &run.rotated {
+                let _ = writeln!(out, "<li>Rotated <code>{}</code></li>", html_escape(path));
+            }
+            for (path, reason) in &run.failed {
+                let _ = writeln!(
+                    out,
+                    "<li class=\"error\">Failed <code>{}</code>: {}</li>",
+                    html_escape(path),
+                    html_escape(reason)
+                );
+            }
+            out.push_str("</ul>\n");
+        }
+
+        out.push_str("<h2>Overdue Secrets</h2>\n");
+        if self.count(SecretState::Overdue) == 0 {
+            out.push_str("<p>None</p>\n");
+        } else {
+            out.push_str("<table>\n<tr><th>Secret</th><th>Last rotated</th><th>Due</th></tr>\n");
+            for secret in self.overdue() {
+                let _ = writeln!(
+                    out,
+                    "<tr class=\"overdue\"><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+                    html_escape(&secret.path),
+                    format_date(secret.last_rotated),
+                    format_date(secret.due_at)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("<h2>All Secrets</h2>\n<table>\n");
+        out.push_str(
+            "<tr><th>Secret</th><th>State</th><th>Last rotated</th><th>Next due</th></tr>\n",
+        );
+        for secret in &self.secrets {
+            let class = match secret.state {
+                SecretState::Overdue => "overdue",
+                SecretState::Current => "current",
+                SecretState::Unmanaged => "unmanaged",
+                SecretState::Error => "error",
+            };
+            let state = match secret.error {
+                Some(ref error) => format!("{}: {}", secret.state.label(), error),
+                None => secret.state.label().to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "<tr class=\"{}\"><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                class,
+                html_escape(&secret.path),
+                html_escape(&state),
+                format_date(secret.last_rotated),
+                format_date(secret.due_at)
+            );
+        }
+        out.push_str("</table>\n</body>\n</html>\n");
+
+        out
+    }
+
+    fn summary(&self) -> Vec<(&'static str, usize)> {
+        let mut rows = vec![
+            ("Total", self.secrets.len()),
+            (
+                SecretState::Overdue.label(),
+                self.count(SecretState::Overdue),
+            ),
+            (
+                SecretState::Current.label(),
+                self.count(SecretState::Current),
+            ),
+            (
+                SecretState::Unmanaged.label(),
+                self.count(SecretState::Unmanaged),
+            ),
+        ];
+        let errors = self.count(SecretState::Error);
+        if errors > 0 {
+            rows.push((SecretState::Error.label(), errors));
+        }
+        rows
+    }
+}
+
+/// Metadata of every secret under `path`, with the secret's full path
+pub(crate) async fn read_all_metadata(
+    backend: &dyn SecretBackend,
+    path: &str,
+) -> Result<Vec<(String, Result<HashMap<String, String>>)>> {
+    let listed = backend
+        .list_secrets(path)
+        .await
+        .context("Failed to list secrets")?;
+
+    let paths: Vec<String> = listed
+        .iter()
+        .map(|secret| transfer::child_path(path, secret))
+        .collect();
+    let metadata = backend.read_metadata_batch(&paths).await;
+    Ok(paths.into_iter().zip(metadata).collect())
+}
+
+pub(crate) fn format_date(date: Option<DateTime<Utc>>) -> String {
+    date.map(|d| d.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Keep a value from breaking out of a Markdown table cell
+pub(crate) fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+fn html_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::{MemoryBackend, Operation};
+
+    #[tokio::test]
+    async fn test_report_collects_posture() -> Result<()> {
+        let backend = MemoryBackend::new()
+            .with_secret("app/due", [("password", "a")])
+            .with_secret("app/fresh", [("password", "b")])
+            .with_secret("app/plain", [("password", "c")]);
+        backend
+            .update_metadata(
+                "app/due",
+                HashMap::from([
+                    ("rotation_enabled".to_string(), "true".to_string()),
+                    (
+                        "last_rotated".to_string(),
+                        "2000-01-01T00:00:00Z".to_string(),
+                    ),
+                ]),
+            )
+            .await?;
+        rotation::flag_for_rotation(&backend, "app/fresh", 6).await?;
+
+        let report = Report::collect(&backend, "app", 6)
+            .await?
+            .with_run(RunResults {
+                rotated: vec![],
+                failed: vec![("app/<x>".to_string(), "denied | no access".to_string())],
+            });
+        assert_eq!(report.count(SecretState::Overdue), 1);
+        assert_eq!(report.count(SecretState::Current), 1);
+        assert_eq!(report.count(SecretState::Unmanaged), 1);
+        assert!(report.secrets[1].due_at.is_some());
+
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.contains("| `app/due` | 2000-01-01 00:00 UTC |"));
+        assert!(markdown.contains("denied \\| no access"));
+
+        let html = report.render(ReportFormat::Html);
+        assert!(html.contains("<code>app/&lt;x&gt;</code>"));
+        assert!(html.contains("<tr class=\"overdue\"><td><code>app/due</code>"));
+
+        backend.fail_always(Operation::ReadMetadata, "down");
+        let report = Report::collect(&backend, "app", 6).await?;
+        assert_eq!(report.count(SecretState::Error), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            ReportFormat::from_path(Path::new("report.HTML")),
+            ReportFormat::Html
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("report.md")),
+            ReportFormat::Markdown
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! Retries of failed rotations with exponential backoff (`asr serve`)
+//!
+//! When a rotation requested through the server fails, it is scheduled again
+//! after `retry_base_seconds`, and each further failure doubles the wait up
+//! to `retry_max_seconds`, so a transient outage (a database restarting, a
+//! throttled API) is ridden out without waiting for the caller or the next
+//! `asr auto` run. After `retry_attempts` retries the secret is given up on;
+//! its failures stay recorded in its metadata. The queue is listed in
+//! `/status` and counted in `/metrics`.
+//!
+//! Only failures that may pass on their own are retried (see
+//! [`is_transient`]): the backend or the target failing, a concurrent write,
+//! a network error. A secret whose settings or policies make the rotation
+//! fail is not retried. A rotation refused by the circuit breaker is not an
+//! attempt; its retry is postponed without counting it.
+
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::backends::VersionConflict;
+use crate::circuit_breaker::TargetFailure;
+use crate::rotation::BackendFailure;
+
+/// Whether a failed rotation may succeed when tried again
+pub fn is_transient(error: &Error) -> bool {
+    error.downcast_ref::<TargetFailure>().is_some()
+        || error.downcast_ref::<BackendFailure>().is_some()
+        || error.downcast_ref::<VersionConflict>().is_some()
+        || error
+            .chain()
+            .any(|cause| cause.is::<std::io::Error>() || cause.is::<reqwest::Error>())
+}
+
+/// How often and how long to wait before retrying a failed rotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first failure (0 disables retries)
+    pub attempts: u32,
+    /// Wait before the first retry
+    pub base: Duration,
+    /// Longest wait between retries
+    pub max: Duration,
+}
+
+impl RetryPolicy {
+    /// Never retry
+    #[cfg_attr(not(test), allow(dead_code))] // Used in tests
+    pub fn disabled() -> Self {
+        Self {
+            attempts: 0,
+            base: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+
+    /// Wait before the next attempt after `failures` failed ones, or `None`
+    /// once the retries are used up
+    pub fn delay(&self, failures: u32) -> Option<Duration> {
+        if failures == 0 || failures > self.attempts {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(failures - 1);
+        Some(self.base.saturating_mul(factor).min(self.max))
+    }
+}
+
+/// A failed rotation waiting for its next attempt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScheduledRetry {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_username: Option<String>,
+    /// Failed attempts so far
+    pub failures: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: String,
+}
+
+/// Counters of the retry queue, for `/metrics`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryCounts {
+    pub queued: usize,
+    /// Retries started
+    pub retried: u64,
+    /// Secrets given up on after their last retry failed
+    pub exhausted: u64,
+}
+
+#[derive(Default)]
+struct State {
+    entries: BTreeMap<String, ScheduledRetry>,
+    retried: u64,
+    exhausted: u64,
+}
+
+/// Failed rotations scheduled for another attempt, one per secret
+pub struct RetryQueue {
+    policy: RetryPolicy,
+    state: Mutex<State>,
+    changed: Notify,
+}
+
+impl RetryQueue {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(State::default()),
+            changed: Notify::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Count a failed attempt at rotating `path` and schedule the next one
+    ///
+    /// Returns `None` when no retry is scheduled because retries are disabled
+    /// or used up.
+    pub fn failed(
+        &self,
+        path: &str,
+        target_username: Option<&str>,
+        error: &str,
+    ) -> Option<ScheduledRetry> {
+        let mut state = self.state();
+        let failures = state.entries.get(path).map_or(0, |retry| retry.failures) + 1;
+        let Some(delay) = self.policy.delay(failures) else {
+            if state.entries.remove(path).is_some() {
+                state.exhausted += 1;
+            }
+            return None;
+        };
+        let retry = ScheduledRetry {
+            path: path.to_string(),
+            target_username: target_username.map(str::to_string),
+            failures,
+            next_attempt_at: Utc::now()
+                + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::MAX),
+            last_error: error.to_string(),
+        };
+        state.entries.insert(path.to_string(), retry.clone());
+        drop(state);
+        self.changed.notify_one();
+        Some(retry)
+    }
+
+    /// Forget `path` after it rotated successfully
+    pub fn succeeded(&self, path: &str) {
+        self.state().entries.remove(path);
+    }
+
+    /// Stop retrying `path` after a failure that retries would not fix,
+    /// returning whether a retry was scheduled
+    pub fn abandon(&self, path: &str) -> bool {
+        self.state().entries.remove(path).is_some()
+    }
+
+    /// Move a scheduled retry of `path` that was not attempted (e.g., refused
+    /// by the circuit breaker) to later, without counting it as a failure
+    pub fn postpone(&self, path: &str) -> Option<ScheduledRetry> {
+        let mut state = self.state();
+        let retry = state.entries.get_mut(path)?;
+        let delay = self.policy.delay(retry.failures).unwrap_or(self.policy.max);
+        retry.next_attempt_at =
+            Utc::now() + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::MAX);
+        let retry = retry.clone();
+        drop(state);
+        self.changed.notify_one();
+        Some(retry)
+    }
+
+    /// Retries whose time has come, counted as started
+    ///
+    /// They stay queued until [`RetryQueue::failed`] or
+    /// [`RetryQueue::succeeded`] records their outcome.
+    pub fn take_due(&self, now: DateTime<Utc>) -> Vec<ScheduledRetry> {
+        let mut state = self.state();
+        let due: Vec<ScheduledRetry> = state
+            .entries
+            .values()
+            .filter(|retry| retry.next_attempt_at <= now)
+            .cloned()
+            .collect();
+        state.retried += due.len() as u64;
+        due
+    }
+
+    /// Drop every scheduled retry, returning how many there were
+    pub fn clear(&self) -> usize {
+        let mut state = self.state();
+        let count = state.entries.len();
+        state.entries.clear();
+        count
+    }
+
+    /// When the earliest scheduled retry is due
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.state()
+            .entries
+            .values()
+            .map(|retry| retry.next_attempt_at)
+            .min()
+    }
+
+    /// Wait until a retry is scheduled
+    pub async fn changed(&self) {
+        self.changed.notified().await
+    }
+
+    /// Scheduled retries, by path
+    pub fn entries(&self) -> Vec<ScheduledRetry> {
+        self.state().entries.values().cloned().collect()
+    }
+
+    pub fn counts(&self) -> RetryCounts {
+        let state = self.state();
+        RetryCounts {
+            queued: state.entries.len(),
+            retried: state.retried,
+            exhausted: state.exhausted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_and_give_up() {
+        let policy = RetryPolicy {
+            attempts: 4,
+            base: Duration::from_secs(60),
+            max: Duration::from_secs(300),
+        };
+        let delays: Vec<_> = (0..=5).map(|failures| policy.delay(failures)).collect();
+        assert_eq!(
+            delays,
+            [
+                None,
+                Some(Duration::from_secs(60)),
+                Some(Duration::from_secs(120)),
+                Some(Duration::from_secs(240)),
+                Some(Duration::from_secs(300)),
+                None
+            ]
+        );
+
+        let queue = RetryQueue::new(RetryPolicy {
+            attempts: 2,
+            base: Duration::ZERO,
+            max: Duration::ZERO,
+        });
+        let first = queue.failed("app/db", Some("app"), "timeout").unwrap();
+        assert_eq!(first.failures, 1);
+        assert!(queue.failed("app/api", None, "timeout").is_some());
+        queue.succeeded("app/api");
+
+        let due = queue.take_due(Utc::now());
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].target_username.as_deref(), Some("app"));
+        assert_eq!(
+            queue
+                .failed("app/db", Some("app"), "refused")
+                .unwrap()
+                .failures,
+            2
+        );
+        assert!(queue.failed("app/db", Some("app"), "refused").is_none());
+        assert_eq!(
+            queue.counts(),
+            RetryCounts {
+                queued: 0,
+                retried: 1,
+                exhausted: 1
+            }
+        );
+
+        // Postponing keeps the count, and abandoned secrets are not retried
+        let queue = RetryQueue::new(RetryPolicy {
+            attempts: 2,
+            base: Duration::from_secs(60),
+            max: Duration::from_secs(60),
+        });
+        assert!(queue.postpone("app/db").is_none());
+        queue.failed("app/db", None, "timeout");
+        let postponed = queue.postpone("app/db").unwrap();
+        assert_eq!(postponed.failures, 1);
+        assert!(postponed.next_attempt_at > Utc::now());
+        assert!(queue.abandon("app/db"));
+        assert_eq!(queue.counts().queued, 0);
+
+        let transient = Error::new(std::io::Error::other("connection reset"))
+            .context("Failed to connect")
+            .context(TargetFailure("Failed to update postgres password".into()));
+        assert!(is_transient(&transient));
+        assert!(is_transient(
+            &anyhow::anyhow!("503").context(BackendFailure("Failed to write rotated secret"))
+        ));
+        assert!(!is_transient(&anyhow::anyhow!(
+            "Unknown rotation_strategy 'swap'"
+        )));
+
+        let disabled = RetryQueue::new(RetryPolicy::disabled());
+        assert!(disabled.failed("app/db", None, "timeout").is_none());
+        assert_eq!(disabled.counts(), RetryCounts::default());
+    }
+}
@@ -1,15 +1,91 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, Months, TimeZone, Utc};
 use rand::Rng;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use tracing::{info, warn};
 
-use crate::backends::SecretBackend;
+use crate::backends::{SecretBackend, VersionConflict};
+use crate::certs;
+use crate::circuit_breaker::TargetFailure;
+use crate::derived::DerivedKeys;
+use crate::generator::GeneratorOptions;
+use crate::hooks::ReencryptionState;
+use crate::password_policy::{self, PasswordPolicy};
+use crate::path_filter::MetadataSelector;
+use crate::progress::Progress;
+use crate::redact;
 use crate::targets::Target;
+use crate::transfer;
 
-const ROTATION_METADATA_KEY: &str = "rotation_enabled";
-const LAST_ROTATED_KEY: &str = "last_rotated";
+pub(crate) const ROTATION_METADATA_KEY: &str = "rotation_enabled";
+pub(crate) const LAST_ROTATED_KEY: &str = "last_rotated";
 const ROTATION_PERIOD_KEY: &str = "rotation_period_months";
+const ROTATION_PERIOD_DAYS_KEY: &str = "rotation_period_days";
+
+/// Secret key recording the login a rotated target password belongs to
+const USERNAME_KEY: &str = "username";
+
+/// Metadata key listing secrets (comma-separated paths) that must rotate first
+const ROTATE_AFTER_KEY: &str = "rotate_after";
+
+/// Metadata key naming the team responsible for a secret
+const OWNER_KEY: &str = "owner";
+
+/// Metadata key overriding whether a secret goes through the canary target first
+const CANARY_KEY: &str = "canary";
+
+/// Metadata keys naming the target user whose password follows the secret
+const TARGET_USERNAME_KEYS: [&str; 2] = ["target_username", "database_username"];
+
+/// Metadata key choosing how a secret's value is replaced; `overlap` keeps
+/// the old value valid next to the new one for a while
+const ROTATION_STRATEGY_KEY: &str = "rotation_strategy";
+
+/// Metadata key with the hours an overlapping secret keeps its previous value
+const OVERLAP_HOURS_KEY: &str = "overlap_hours";
+
+/// Metadata key recording when the previous value of an overlapping secret is retired
+const PREVIOUS_EXPIRES_KEY: &str = "previous_expires_at";
+
+/// Secret keys of an overlapping secret holding its new and old values
+const CURRENT_KEY: &str = "current";
+const PREVIOUS_KEY: &str = "previous";
+
+/// Metadata keys tracking rotation attempts; the failure keys are cleared by
+/// the next successful rotation
+const LAST_ATTEMPT_KEY: &str = "last_rotation_attempt";
+const LAST_ERROR_KEY: &str = "last_rotation_error";
+const CONSECUTIVE_FAILURES_KEY: &str = "consecutive_failures";
+const FAILING_SINCE_KEY: &str = "failing_since";
+const FAILURE_KEYS: [&str; 3] = [LAST_ERROR_KEY, CONSECUTIVE_FAILURES_KEY, FAILING_SINCE_KEY];
+
+/// Metadata key with the seconds (possibly negative) the first due date after
+/// flagging is moved by, so secrets flagged together do not all fall due at
+/// once; cleared by the next rotation
+const SCHEDULE_OFFSET_KEY: &str = "rotation_offset_seconds";
+
+/// Characters of an error kept in metadata (AWS tag values hold 256)
+const MAX_ERROR_LENGTH: usize = 200;
+
+/// Hours an overlapping secret keeps its previous value by default
+const DEFAULT_OVERLAP_HOURS: i64 = 24;
+
+/// Context marking an error as a failure of the backend while a rotation
+/// read or wrote the secret, as opposed to a problem with its settings
+#[derive(Debug)]
+pub struct BackendFailure(pub &'static str);
+
+impl fmt::Display for BackendFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Attempts at writing a rotated secret when concurrent writers keep conflicting
+const MAX_WRITE_ATTEMPTS: u32 = 3;
 
 /// Check if a secret needs rotation based on metadata
 pub fn needs_rotation(
@@ -21,14 +97,20 @@ pub fn needs_rotation(
     };
 
     // Check if rotation is enabled
-    if meta.get(ROTATION_METADATA_KEY) != Some(&"true".to_string()) {
+    if !rotation_enabled(meta) {
         return false;
     }
 
-    // Get last rotation time
+    // Certificates are due ahead of their expiry, not after a period
+    if certs::is_certificate(meta) {
+        return certs::needs_renewal(meta, Utc::now());
+    }
+
+    // Get last rotation time, keeping its offset so months are counted in the
+    // calendar it was recorded in
     let last_rotated = match meta.get(LAST_ROTATED_KEY) {
         Some(date_str) => match DateTime::parse_from_rfc3339(date_str) {
-            Ok(dt) => dt.with_timezone(&Utc),
+            Ok(dt) => dt,
             Err(_) => {
                 warn!("Failed to parse last_rotated date: {}", date_str);
                 return true; // Rotate if we can't parse the date
@@ -40,30 +122,357 @@ pub fn needs_rotation(
         }
     };
 
-    // Get rotation period (use custom or default)
-    let period_months = meta
-        .get(ROTATION_PERIOD_KEY)
-        .and_then(|s| s.parse::<i64>().ok())
-        .unwrap_or(default_period_months as i64);
-
     // Calculate if rotation is due
-    let rotation_due = last_rotated + Duration::days(period_months * 30);
+    let rotation_due = next_rotation(last_rotated, rotation_period(meta, default_period_months))
+        + schedule_offset(meta);
     let now = Utc::now();
 
     now >= rotation_due
 }
 
+/// Whether a secret's metadata has rotation enabled
+pub fn rotation_enabled(metadata: &HashMap<String, String>) -> bool {
+    metadata.get(ROTATION_METADATA_KEY).map(String::as_str) == Some("true")
+}
+
+/// When a secret was last rotated, if recorded and valid
+pub fn last_rotated(metadata: &HashMap<String, String>) -> Option<DateTime<Utc>> {
+    metadata
+        .get(LAST_ROTATED_KEY)
+        .and_then(|date_str| DateTime::parse_from_rfc3339(date_str).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// When a secret that has rotated before is next due, by its rotation period
+pub fn due_at(
+    metadata: &HashMap<String, String>,
+    default_period_months: u32,
+) -> Option<DateTime<Utc>> {
+    metadata
+        .get(LAST_ROTATED_KEY)
+        .and_then(|date_str| DateTime::parse_from_rfc3339(date_str).ok())
+        .map(|last| {
+            next_rotation(last, rotation_period(metadata, default_period_months))
+                + schedule_offset(metadata)
+        })
+}
+
+/// Jitter added to the due date of a secret that has not rotated since it
+/// was flagged
+fn schedule_offset(metadata: &HashMap<String, String>) -> Duration {
+    metadata
+        .get(SCHEDULE_OFFSET_KEY)
+        .and_then(|seconds| seconds.parse().ok())
+        .map_or(Duration::zero(), Duration::seconds)
+}
+
+/// How long a secret stays current after it rotates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RotationPeriod {
+    /// Calendar months; a fraction covers that share of the month after the
+    /// whole ones (1.5 from January 10th is February 10th plus half of the
+    /// days until March 10th)
+    Months(f64),
+    Days(u32),
+}
+
+impl RotationPeriod {
+    /// Metadata recording this period, as written by `flag`
+    fn metadata_entry(&self) -> (&'static str, String) {
+        match self {
+            Self::Months(months) => (ROTATION_PERIOD_KEY, months.to_string()),
+            Self::Days(days) => (ROTATION_PERIOD_DAYS_KEY, days.to_string()),
+        }
+    }
+}
+
+/// Parses "6" or "6m" as months (fractions allowed, e.g. "1.5") and "90d" as days
+impl FromStr for RotationPeriod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid rotation period '{}'. Use months (6, 1.5, 6m) or days (90d)",
+                s
+            )
+        };
+        if let Some(days) = s.strip_suffix('d') {
+            return days.trim().parse().map(Self::Days).map_err(|_| invalid());
+        }
+        let months: f64 = s
+            .strip_suffix('m')
+            .unwrap_or(s)
+            .trim()
+            .parse()
+            .map_err(|_| invalid())?;
+        if !months.is_finite() || months < 0.0 {
+            return Err(invalid());
+        }
+        Ok(Self::Months(months))
+    }
+}
+
+impl fmt::Display for RotationPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Months(months) => write!(f, "{} months", months),
+            Self::Days(days) => write!(f, "{} days", days),
+        }
+    }
+}
+
+/// Rotation period of a secret: `rotation_period_days`, else
+/// `rotation_period_months`, else the default
+pub fn rotation_period(
+    metadata: &HashMap<String, String>,
+    default_period_months: u32,
+) -> RotationPeriod {
+    let days = metadata
+        .get(ROTATION_PERIOD_DAYS_KEY)
+        .and_then(|s| s.trim().parse().ok())
+        .map(RotationPeriod::Days);
+    let months = || {
+        metadata
+            .get(ROTATION_PERIOD_KEY)
+            .and_then(|s| s.parse().ok())
+            .filter(|period| matches!(period, RotationPeriod::Months(_)))
+    };
+    days.or_else(months)
+        .unwrap_or(RotationPeriod::Months(default_period_months.into()))
+}
+
+/// When a secret last rotated at `last_rotated` falls due again
+///
+/// Months are calendar months in `last_rotated`'s time zone, so a monthly
+/// secret rotated on the 31st is due on the last day of shorter months.
+pub fn next_rotation<Tz: TimeZone>(
+    last_rotated: DateTime<Tz>,
+    period: RotationPeriod,
+) -> DateTime<Utc> {
+    let due = match period {
+        RotationPeriod::Days(days) => last_rotated.checked_add_signed(Duration::days(days.into())),
+        RotationPeriod::Months(months) => {
+            // Saturates for absurd periods, which then never fall due
+            let whole = months.trunc() as u32;
+            let add = |n: u32| last_rotated.clone().checked_add_months(Months::new(n));
+            match add(whole) {
+                Some(due) if months.fract() > 0.0 => add(whole.saturating_add(1)).map(|next| {
+                    let span = (next - due.clone()).num_seconds() as f64;
+                    due + Duration::seconds((span * months.fract()).round() as i64)
+                }),
+                due => due,
+            }
+        }
+    };
+    due.map(|due| due.with_timezone(&Utc))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// Team responsible for a secret, from its `owner` metadata
+pub fn owner(metadata: &HashMap<String, String>) -> Option<String> {
+    metadata
+        .get(OWNER_KEY)
+        .map(|owner| owner.trim())
+        .filter(|owner| !owner.is_empty())
+        .map(str::to_string)
+}
+
+/// Record `owner` as the team responsible for a secret, keeping its other metadata
+pub async fn set_owner(backend: &dyn SecretBackend, path: &str, owner: &str) -> Result<()> {
+    let mut metadata = backend
+        .read_metadata(path)
+        .await
+        .context("Failed to read metadata")?;
+    metadata.insert(OWNER_KEY.to_string(), owner.to_string());
+    backend
+        .update_metadata(path, metadata)
+        .await
+        .context("Failed to update metadata")
+}
+
+/// Failed attempts at rotating a secret since it last rotated, from its metadata
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RotationFailures {
+    pub consecutive_failures: u32,
+    /// When the first of the consecutive failures happened
+    pub failing_since: Option<DateTime<Utc>>,
+    pub last_attempt: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+impl fmt::Display for RotationFailures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failing")?;
+        if let Some(since) = self.failing_since {
+            write!(f, " since {}", since.format("%Y-%m-%d"))?;
+        }
+        write!(f, ", {} attempt(s)", self.consecutive_failures)?;
+        if let Some(ref error) = self.last_error {
+            write!(f, ", last error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rotation failures recorded on a secret, if its last attempt failed
+pub fn rotation_failures(metadata: &HashMap<String, String>) -> Option<RotationFailures> {
+    let consecutive_failures = metadata
+        .get(CONSECUTIVE_FAILURES_KEY)
+        .and_then(|count| count.parse::<u32>().ok())
+        .filter(|count| *count > 0)?;
+    let date = |key: &str| {
+        metadata
+            .get(key)
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+            .map(|date| date.with_timezone(&Utc))
+    };
+    Some(RotationFailures {
+        consecutive_failures,
+        failing_since: date(FAILING_SINCE_KEY),
+        last_attempt: date(LAST_ATTEMPT_KEY),
+        last_error: metadata.get(LAST_ERROR_KEY).cloned(),
+    })
+}
+
+/// Record a failed rotation attempt in the secret's metadata
+///
+/// Secrets not flagged for rotation are left alone, so a failed attempt at
+/// a missing or unmanaged secret does not create metadata for it.
+pub async fn record_rotation_failure(
+    backend: &dyn SecretBackend,
+    path: &str,
+    error: &str,
+) -> Result<()> {
+    let mut metadata = backend
+        .read_metadata(path)
+        .await
+        .context("Failed to read metadata")?;
+    if !rotation_enabled(&metadata) {
+        return Ok(());
+    }
+    let now = Utc::now().to_rfc3339();
+    let failures = rotation_failures(&metadata).map_or(0, |f| f.consecutive_failures) + 1;
+    if failures == 1 || !metadata.contains_key(FAILING_SINCE_KEY) {
+        metadata.insert(FAILING_SINCE_KEY.to_string(), now.clone());
+    }
+    metadata.insert(CONSECUTIVE_FAILURES_KEY.to_string(), failures.to_string());
+    metadata.insert(LAST_ERROR_KEY.to_string(), metadata_error(error));
+    metadata.insert(LAST_ATTEMPT_KEY.to_string(), now);
+    backend
+        .update_metadata(path, metadata)
+        .await
+        .context("Failed to update metadata")
+}
+
+/// `error` shortened and limited to characters every backend accepts in
+/// metadata values (AWS tags allow letters, digits, spaces and `_.:/=+-@`)
+fn metadata_error(error: &str) -> String {
+    let cleaned: String = error
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || "_.:/=+-@".contains(c) {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    let mut cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some((index, _)) = cleaned.char_indices().nth(MAX_ERROR_LENGTH) {
+        cleaned.truncate(index);
+        cleaned.push_str("...");
+    }
+    cleaned
+}
+
+/// Write the metadata of a secret that was just rotated, recording the
+/// attempt and clearing the failures of earlier ones
+pub(crate) async fn write_rotated_metadata(
+    backend: &dyn SecretBackend,
+    path: &str,
+    mut metadata: HashMap<String, String>,
+) -> Result<()> {
+    metadata.insert(LAST_ATTEMPT_KEY.to_string(), Utc::now().to_rfc3339());
+    let stale: Vec<String> = FAILURE_KEYS
+        .iter()
+        .chain([&SCHEDULE_OFFSET_KEY])
+        .filter(|key| metadata.remove(**key).is_some())
+        .map(|key| key.to_string())
+        .collect();
+    backend.update_metadata(path, metadata).await?;
+    if !stale.is_empty() {
+        // Backends that merge metadata keep keys left out of an update
+        backend.remove_metadata(path, &stale).await?;
+    }
+    Ok(())
+}
+
+/// Target user recorded in a secret's metadata, if any
+pub fn target_username(metadata: &HashMap<String, String>) -> Option<String> {
+    TARGET_USERNAME_KEYS
+        .iter()
+        .find_map(|key| metadata.get(*key))
+        .cloned()
+}
+
+/// Login and value a secret holds for its target user, if it names one
+///
+/// Targets with paired logins record the login in the secret's `username`.
+pub fn stored_credential(
+    data: &HashMap<String, String>,
+    metadata: &HashMap<String, String>,
+) -> Option<(String, String)> {
+    credential_for(data, metadata, target_username(metadata)?)
+}
+
+/// Login and value a secret holds for target user `username`
+pub fn credential_for(
+    data: &HashMap<String, String>,
+    metadata: &HashMap<String, String>,
+    username: String,
+) -> Option<(String, String)> {
+    let login = data.get(USERNAME_KEY).cloned().unwrap_or(username);
+    let value = data.get(&key_to_rotate(data, metadata))?.clone();
+    Some((login, value))
+}
+
 /// Generate a random secret
 pub fn generate_secret(length: usize) -> String {
-    const CHARSET: &[u8] =
-        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!@#$%^&*";
-    let mut rng = rand::thread_rng();
-    (0..length)
-        .map(|_| {
-            let idx = rng.gen_range(0..CHARSET.len());
-            CHARSET[idx] as char
+    GeneratorOptions::new(length).generate()
+}
+
+/// Determine which key of a secret holds the rotated value - `current` for
+/// secrets using the overlap strategy, otherwise look for common key names
+pub(crate) fn key_to_rotate(
+    data: &HashMap<String, String>,
+    metadata: &HashMap<String, String>,
+) -> String {
+    if uses_overlap(metadata) {
+        return CURRENT_KEY.to_string();
+    }
+    replaced_key(data)
+}
+
+/// Whether a secret keeps its previous value next to the new one
+fn uses_overlap(metadata: &HashMap<String, String>) -> bool {
+    metadata.get(ROTATION_STRATEGY_KEY).map(String::as_str) == Some("overlap")
+}
+
+/// Key holding the value of a secret that replaces it on rotation
+fn replaced_key(data: &HashMap<String, String>) -> String {
+    data.keys()
+        .find(|k| {
+            let lower = k.to_lowercase();
+            lower.contains("password")
+                || lower.contains("secret")
+                || lower.contains("key")
+                || lower.contains("token")
+                || lower == "value"
         })
-        .collect()
+        .cloned()
+        .unwrap_or_else(|| "secret".to_string())
 }
 
 /// Rotate a secret and update metadata
@@ -75,6 +484,25 @@ pub async fn rotate_secret(
     rotate_secret_with_target(backend, path, secret_length, None, None).await
 }
 
+/// Staging target that receives a new credential before production does
+pub struct Canary<'a> {
+    pub target: &'a dyn Target,
+    /// User on the canary target (defaults to the production target user)
+    pub username: Option<&'a str>,
+    /// Whether secrets without a `canary` metadata override use the canary
+    pub enabled_by_default: bool,
+}
+
+impl Canary<'_> {
+    fn enabled_for(&self, metadata: &HashMap<String, String>) -> bool {
+        match metadata.get(CANARY_KEY).map(String::as_str) {
+            Some("true") => true,
+            Some("false") => false,
+            _ => self.enabled_by_default,
+        }
+    }
+}
+
 /// Rotate a secret and optionally update target password (database, API, etc.)
 pub async fn rotate_secret_with_target(
     backend: &dyn SecretBackend,
@@ -82,66 +510,161 @@ pub async fn rotate_secret_with_target(
     secret_length: usize,
     target: Option<&dyn Target>,
     target_username: Option<&str>,
+) -> Result<String> {
+    rotate_secret_with_canary(
+        backend,
+        path,
+        secret_length,
+        target,
+        target_username,
+        None,
+        None,
+    )
+    .await
+}
+
+/// Rotate a secret, first applying and verifying the new value on a canary target
+///
+/// If the canary rejects the new credential, the production secret and target
+/// are left untouched. Derived keys (see [`crate::derived`]) declared in the
+/// secret's metadata or in `derived` are recomputed in the same write.
+pub async fn rotate_secret_with_canary(
+    backend: &dyn SecretBackend,
+    path: &str,
+    secret_length: usize,
+    target: Option<&dyn Target>,
+    target_username: Option<&str>,
+    canary: Option<&Canary<'_>>,
+    derived: Option<&HashMap<String, String>>,
 ) -> Result<String> {
     info!("Rotating secret at {} ({})", path, backend.backend_type());
 
-    // Read current secret
-    let current = backend
-        .read_secret(path)
-        .await
-        .context("Failed to read current secret")?;
+    // Existing metadata may override how the new value is generated
+    let metadata = existing_metadata(backend, path).await;
+    let generator = GeneratorOptions::from_metadata(&metadata, secret_length)
+        .with_context(|| format!("Invalid generator settings for {}", path))?;
 
-    // Generate new secret
-    let new_secret = generate_secret(secret_length);
+    // Generate new secret, within the targets' password policies, and keep
+    // it out of any log output
+    let target = target.zip(target_username);
+    let policies = password_policies(target, canary, &metadata);
+    let new_secret = if policies.is_empty() {
+        generator.generate()
+    } else {
+        let previous = previous_values(backend, path, &metadata, &policies).await;
+        password_policy::generate(generator, &policies, &previous)
+            .with_context(|| format!("Cannot generate a new value for {}", path))?
+    };
+    redact::register_secret(&new_secret);
 
-    // Update secret data
-    let mut new_data = current.data.clone();
-    // Determine which key to update - look for common key names
-    let key_to_update = new_data
-        .keys()
-        .find(|k| {
-            let lower = k.to_lowercase();
-            lower.contains("password")
-                || lower.contains("secret")
-                || lower.contains("key")
-                || lower.contains("token")
-        })
-        .cloned()
-        .unwrap_or_else(|| "secret".to_string());
+    let derived = DerivedKeys::resolve(&metadata, derived);
+    apply_new_secret(
+        backend,
+        path,
+        &new_secret,
+        metadata,
+        target,
+        &derived,
+        canary,
+    )
+    .await?;
+    Ok(new_secret)
+}
 
-    new_data.insert(key_to_update.clone(), new_secret.clone());
+/// Like [`rotate_secret_with_canary`], but stores `value` instead of generating
+/// one, for credentials issued elsewhere (e.g., by a third party)
+pub async fn set_secret_with_canary(
+    backend: &dyn SecretBackend,
+    path: &str,
+    value: &str,
+    target: Option<&dyn Target>,
+    target_username: Option<&str>,
+    canary: Option<&Canary<'_>>,
+    derived: Option<&HashMap<String, String>>,
+) -> Result<()> {
+    info!(
+        "Storing supplied secret at {} ({})",
+        path,
+        backend.backend_type()
+    );
+    if value.is_empty() {
+        anyhow::bail!("Refusing to store an empty value at {}", path);
+    }
+    redact::register_secret(value);
 
-    // Write updated secret
-    backend
-        .write_secret(path, new_data)
-        .await
-        .context("Failed to write rotated secret")?;
+    let metadata = existing_metadata(backend, path).await;
+    let target = target.zip(target_username);
+    let policies = password_policies(target, canary, &metadata);
+    if !policies.is_empty() {
+        let previous = previous_values(backend, path, &metadata, &policies).await;
+        for policy in policies {
+            policy
+                .check(value, &previous)
+                .with_context(|| format!("Refusing to store the supplied value for {}", path))?;
+        }
+    }
 
-    // Update target password if configured
-    if let Some(target) = target {
-        if let Some(username) = target_username {
-            info!(
-                "Updating {} password for user: {}",
-                target.target_type(),
-                username
-            );
-            target
-                .update_password(username, &new_secret)
-                .await
-                .with_context(|| format!("Failed to update {} password", target.target_type()))?;
+    let derived = DerivedKeys::resolve(&metadata, derived);
+    apply_new_secret(backend, path, value, metadata, target, &derived, canary).await
+}
 
-            // Optionally verify the new password works
-            target
-                .verify_connection(username, &new_secret, None)
-                .await
-                .with_context(|| {
-                    format!("Failed to verify new {} password", target.target_type())
-                })?;
+/// Password policies of the target and canary a new value will be sent to
+fn password_policies<'a>(
+    target: Option<(&'a dyn Target, &str)>,
+    canary: Option<&Canary<'a>>,
+    metadata: &HashMap<String, String>,
+) -> Vec<&'a PasswordPolicy> {
+    let canary = canary
+        .filter(|canary| canary.enabled_for(metadata))
+        .map(|canary| canary.target);
+    target
+        .map(|(target, _)| target)
+        .into_iter()
+        .chain(canary)
+        .filter_map(|target| target.password_policy())
+        .collect()
+}
+
+/// Recent values of the rotated key, newest first, as many as the longest
+/// history rule in `policies` covers
+///
+/// Backends without version history only provide the current value.
+async fn previous_values(
+    backend: &dyn SecretBackend,
+    path: &str,
+    metadata: &HashMap<String, String>,
+    policies: &[&PasswordPolicy],
+) -> Vec<String> {
+    let count = policies.iter().map(|p| p.history).max().unwrap_or(0);
+    if count == 0 {
+        return Vec::new();
+    }
+    let rotated_value =
+        |data: &HashMap<String, String>| data.get(&key_to_rotate(data, metadata)).cloned();
+    let versions = match backend.list_versions(path).await {
+        Ok(versions) => versions,
+        Err(_) => {
+            return match backend.read_secret(path).await {
+                Ok(current) => rotated_value(&current.data).into_iter().collect(),
+                Err(_) => Vec::new(),
+            };
+        }
+    };
+    let mut values = Vec::new();
+    for version in versions.iter().rev().take(count) {
+        match backend.read_version(path, version.version).await {
+            Ok(secret) => values.extend(rotated_value(&secret.data)),
+            Err(e) => warn!(
+                "Failed to read version {} of {} for its password history: {}",
+                version.version, path, e
+            ),
         }
     }
+    values
+}
 
-    // Update metadata with rotation timestamp
-    let mut metadata = match backend.read_metadata(path).await {
+async fn existing_metadata(backend: &dyn SecretBackend, path: &str) -> HashMap<String, String> {
+    match backend.read_metadata(path).await {
         Ok(existing) => existing,
         Err(e) => {
             warn!(
@@ -150,18 +673,360 @@ pub async fn rotate_secret_with_target(
             );
             HashMap::new()
         }
+    }
+}
+
+/// How long the previous value of a secret stays valid after a rotation,
+/// for secrets using the `overlap` rotation strategy
+///
+/// Signing keys and webhook secrets are verified by consumers that may still
+/// hold the old key, so both are kept (as `current` and `previous`) until
+/// the window is over.
+pub fn overlap_window(metadata: &HashMap<String, String>) -> Result<Option<Duration>> {
+    match metadata.get(ROTATION_STRATEGY_KEY).map(String::as_str) {
+        None | Some("replace") => Ok(None),
+        Some("overlap") => {
+            let hours = match metadata.get(OVERLAP_HOURS_KEY) {
+                Some(hours) => hours
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|hours| *hours > 0)
+                    .with_context(|| {
+                        format!("Invalid {} '{}' (expected hours)", OVERLAP_HOURS_KEY, hours)
+                    })?,
+                None => DEFAULT_OVERLAP_HOURS,
+            };
+            Ok(Some(Duration::hours(hours)))
+        }
+        Some(other) => anyhow::bail!(
+            "Unknown {} '{}' (expected replace or overlap)",
+            ROTATION_STRATEGY_KEY,
+            other
+        ),
+    }
+}
+
+/// Put a new value in place: canary first, then the backend (staged) and the
+/// target, promoting the staged value once the target accepts it
+async fn apply_new_secret(
+    backend: &dyn SecretBackend,
+    path: &str,
+    new_secret: &str,
+    mut metadata: HashMap<String, String>,
+    target: Option<(&dyn Target, &str)>,
+    derived: &DerivedKeys,
+    canary: Option<&Canary<'_>>,
+) -> Result<()> {
+    let overlap = overlap_window(&metadata)
+        .with_context(|| format!("Invalid rotation strategy for {}", path))?;
+
+    if let Some(canary) = canary.filter(|canary| canary.enabled_for(&metadata)) {
+        let username = canary
+            .username
+            .or(target.map(|(_, username)| username))
+            .context("Canary rotation needs a canary username or a target username")?;
+        info!(
+            "Applying new secret for {} to canary {} user: {}",
+            path,
+            canary.target.target_type(),
+            username
+        );
+        canary
+            .target
+            .update_password(username, new_secret)
+            .await
+            .context("Canary rejected the new secret; production was not changed")?;
+        canary
+            .target
+            .verify_connection(username, new_secret, None)
+            .await
+            .context("Canary verification failed; production was not changed")?;
+    }
+
+    // The target could never verify a value the backend only stages
+    if let Some((target, _)) = target {
+        if target.needs_current_secret() && backend.stages_secrets() {
+            anyhow::bail!(
+                "The {} target verifies values its clients read from the backend, \
+                 so it cannot be used with a backend that stages rotated values ({})",
+                target.target_type(),
+                backend.backend_type()
+            );
+        }
+    }
+
+    // Read, update, and stage the secret, starting over if another writer
+    // changed it in between (on backends that support check-and-set)
+    let mut attempt = 1;
+    let (login, kept_previous, replaced) = loop {
+        let current = backend
+            .read_secret(path)
+            .await
+            .context(BackendFailure("Failed to read current secret"))?;
+
+        // Derived keys never hold the rotated value themselves
+        let mut new_data: HashMap<String, String> = current
+            .data
+            .iter()
+            .filter(|(key, _)| !derived.contains(key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let mut kept_previous = false;
+        let replaced = if overlap.is_some() {
+            // The value being replaced stays valid until the window is over.
+            // A secret just switched to overlap has it under its old key,
+            // which is dropped so consumers only see `current` and `previous`
+            let old = match current.data.get(CURRENT_KEY) {
+                Some(old) => Some(old.clone()),
+                None => new_data.remove(&replaced_key(&new_data)),
+            };
+            match &old {
+                Some(old) => {
+                    new_data.insert(PREVIOUS_KEY.to_string(), old.clone());
+                    kept_previous = true;
+                }
+                None => {
+                    new_data.remove(PREVIOUS_KEY);
+                }
+            }
+            new_data.insert(CURRENT_KEY.to_string(), new_secret.to_string());
+            old
+        } else {
+            new_data.insert(key_to_rotate(&new_data, &metadata), new_secret.to_string())
+        };
+
+        // Targets with paired logins put the new password on the inactive one,
+        // so the secret has to name the login it belongs to
+        let login = match target {
+            Some((target, username)) => {
+                let current_login = current.data.get(USERNAME_KEY).map(String::as_str);
+                let login = target
+                    .next_login(username, current_login)
+                    .await
+                    .with_context(|| format!("Failed to select {} login", target.target_type()))?;
+                if login != username {
+                    new_data.insert(USERNAME_KEY.to_string(), login.clone());
+                }
+                Some(login)
+            }
+            None => None,
+        };
+        derived
+            .apply(&mut new_data)
+            .with_context(|| format!("Failed to recompute derived keys of {}", path))?;
+
+        match backend.stage_secret(path, new_data, current.version).await {
+            Ok(()) => break (login, kept_previous, replaced),
+            Err(e) if attempt < MAX_WRITE_ATTEMPTS && e.is::<VersionConflict>() => {
+                warn!("{}; retrying (attempt {})", e, attempt + 1);
+                attempt += 1;
+            }
+            Err(e) => return Err(e).context(BackendFailure("Failed to write rotated secret")),
+        }
     };
 
+    // Update target password if configured; the staged value only becomes
+    // current once the target accepts it
+    if let (Some((target, username)), Some(login)) = (target, &login) {
+        info!(
+            "Updating {} password for user: {}",
+            target.target_type(),
+            login
+        );
+        if let Err(e) = target.update_password(login, new_secret).await {
+            // The target still uses the old password, so drop the new one
+            discard_staged(backend, path).await;
+            return Err(e).with_context(|| {
+                TargetFailure(format!(
+                    "Failed to update {} password",
+                    target.target_type()
+                ))
+            });
+        }
+
+        // Verify the new password works before promoting it. An unverified
+        // value must not stay staged, where the next rotation or a consumer
+        // reading the pending version (AWSPENDING) would pick it up.
+        let verified = async {
+            target
+                .verify_connection(login, new_secret, None)
+                .await
+                .with_context(|| {
+                    TargetFailure(format!(
+                        "Failed to verify new {} password",
+                        target.target_type()
+                    ))
+                })?;
+            target
+                .activate_login(username, login)
+                .await
+                .with_context(|| {
+                    TargetFailure(format!(
+                        "Failed to activate {} login {}",
+                        target.target_type(),
+                        login
+                    ))
+                })
+        }
+        .await;
+        if let Err(e) = verified {
+            // The target already has the new password. A paired login still
+            // has the old one active, and a backend that does not stage
+            // already holds the new one. Otherwise put the old one back, or
+            // keep the new one if that fails so the backend matches the target
+            let restored = if login == username && backend.stages_secrets() {
+                match &replaced {
+                    Some(old) => target.update_password(login, old).await,
+                    None => Err(anyhow::anyhow!("{} had no previous value", path)),
+                }
+            } else {
+                Ok(())
+            };
+            match restored {
+                Ok(()) => discard_staged(backend, path).await,
+                Err(restore_err) => {
+                    warn!(
+                        "Failed to restore the previous {} password of {}: {:#}; keeping the new one",
+                        target.target_type(),
+                        login,
+                        restore_err
+                    );
+                    if let Err(promote_err) = backend.promote_staged_secret(path).await {
+                        warn!(
+                            "Failed to promote the new secret for {}: {:#}",
+                            path, promote_err
+                        );
+                    }
+                }
+            }
+            return Err(e);
+        }
+    }
+
+    backend
+        .promote_staged_secret(path)
+        .await
+        .context(BackendFailure("Failed to promote rotated secret"))?;
+
+    // Update metadata with rotation timestamp
     metadata.insert(ROTATION_METADATA_KEY.to_string(), "true".to_string());
     metadata.insert(LAST_ROTATED_KEY.to_string(), Utc::now().to_rfc3339());
+    match overlap {
+        Some(window) if kept_previous => {
+            metadata.insert(
+                PREVIOUS_EXPIRES_KEY.to_string(),
+                (Utc::now() + window).to_rfc3339(),
+            );
+        }
+        _ => {
+            metadata.remove(PREVIOUS_EXPIRES_KEY);
+        }
+    }
 
-    backend
-        .update_metadata(path, metadata)
+    write_rotated_metadata(backend, path, metadata)
         .await
-        .context("Failed to update metadata")?;
+        .context(BackendFailure("Failed to update metadata"))?;
 
     info!("Successfully rotated secret at {}", path);
-    Ok(new_secret)
+    Ok(())
+}
+
+/// Drop a staged value that will not be promoted, warning if that fails
+async fn discard_staged(backend: &dyn SecretBackend, path: &str) {
+    if let Err(e) = backend.discard_staged_secret(path).await {
+        warn!("Failed to discard staged secret for {}: {}", path, e);
+    }
+}
+
+/// A secret's data and metadata as they were before a rotation
+pub struct SecretSnapshot {
+    pub path: String,
+    data: HashMap<String, String>,
+    metadata: HashMap<String, String>,
+}
+
+impl SecretSnapshot {
+    /// Metadata at the time of the snapshot
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+}
+
+/// Capture a secret so that its rotation can be undone with [`restore_secret`]
+pub async fn snapshot_secret(backend: &dyn SecretBackend, path: &str) -> Result<SecretSnapshot> {
+    let data = backend
+        .read_secret(path)
+        .await
+        .with_context(|| format!("Failed to read {} before rotating it", path))?
+        .data;
+    let metadata = backend
+        .read_metadata(path)
+        .await
+        .with_context(|| format!("Failed to read metadata of {} before rotating it", path))?;
+    Ok(SecretSnapshot {
+        path: path.to_string(),
+        data,
+        metadata,
+    })
+}
+
+/// Put a secret back the way it was when `snapshot` was taken
+///
+/// With a target, the user's previous password (and, for paired logins, the
+/// previously active login) is restored first so that the secret and the
+/// target agree again.
+pub async fn restore_secret(
+    backend: &dyn SecretBackend,
+    snapshot: &SecretSnapshot,
+    target: Option<&dyn Target>,
+    target_username: Option<&str>,
+) -> Result<()> {
+    let path = snapshot.path.as_str();
+    info!("Restoring secret at {} ({})", path, backend.backend_type());
+
+    discard_staged(backend, path).await;
+
+    if let (Some(target), Some(username)) = (target, target_username) {
+        let login = snapshot
+            .data
+            .get(USERNAME_KEY)
+            .map(String::as_str)
+            .unwrap_or(username);
+        let password = snapshot
+            .data
+            .get(&key_to_rotate(&snapshot.data, &snapshot.metadata))
+            .with_context(|| format!("Snapshot of {} has no value to restore", path))?;
+        target
+            .update_password(login, password)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to restore {} password for {}",
+                    target.target_type(),
+                    login
+                )
+            })?;
+        target
+            .activate_login(username, login)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to reactivate {} login {}",
+                    target.target_type(),
+                    login
+                )
+            })?;
+    }
+
+    backend
+        .write_secret(path, snapshot.data.clone())
+        .await
+        .context("Failed to restore secret")?;
+    backend
+        .update_metadata(path, snapshot.metadata.clone())
+        .await
+        .context("Failed to restore metadata")?;
+    Ok(())
 }
 
 /// Flag a secret for automatic rotation
@@ -169,24 +1034,73 @@ pub async fn flag_for_rotation(
     backend: &dyn SecretBackend,
     path: &str,
     period_months: u32,
+) -> Result<()> {
+    flag_for_rotation_every(
+        backend,
+        path,
+        RotationPeriod::Months(period_months.into()),
+        0,
+    )
+    .await
+}
+
+/// A random number of seconds within `jitter_days` days either way
+fn jitter_seconds(jitter_days: u32) -> i64 {
+    if jitter_days == 0 {
+        return 0;
+    }
+    let max = Duration::days(jitter_days.into()).num_seconds();
+    rand::thread_rng().gen_range(-max..=max)
+}
+
+/// Flag a secret for rotation with a period in months (possibly fractional) or days
+///
+/// With `jitter_days`, the first due date is moved up to that many days either
+/// way (recorded as `rotation_offset_seconds`), which spreads out secrets
+/// flagged together. `last_rotated` is always the actual time.
+pub async fn flag_for_rotation_every(
+    backend: &dyn SecretBackend,
+    path: &str,
+    period: RotationPeriod,
+    jitter_days: u32,
 ) -> Result<()> {
     info!(
-        "Flagging secret at {} ({}) for rotation every {} months",
+        "Flagging secret at {} ({}) for rotation every {}",
         path,
         backend.backend_type(),
-        period_months
+        period
     );
 
+    let (period_key, period_value) = period.metadata_entry();
     let mut metadata = HashMap::new();
     metadata.insert(ROTATION_METADATA_KEY.to_string(), "true".to_string());
     metadata.insert(LAST_ROTATED_KEY.to_string(), Utc::now().to_rfc3339());
-    metadata.insert(ROTATION_PERIOD_KEY.to_string(), period_months.to_string());
+    metadata.insert(period_key.to_string(), period_value);
+    let offset = jitter_seconds(jitter_days);
+    if offset != 0 {
+        metadata.insert(SCHEDULE_OFFSET_KEY.to_string(), offset.to_string());
+    }
 
     backend
         .update_metadata(path, metadata)
         .await
         .context("Failed to update metadata")?;
 
+    // Days take precedence over months, so a period of the other kind left
+    // behind by backends that merge metadata would shadow the new one
+    let mut stale = vec![if period_key == ROTATION_PERIOD_KEY {
+        ROTATION_PERIOD_DAYS_KEY.to_string()
+    } else {
+        ROTATION_PERIOD_KEY.to_string()
+    }];
+    if offset == 0 {
+        stale.push(SCHEDULE_OFFSET_KEY.to_string());
+    }
+    backend
+        .remove_metadata(path, &stale)
+        .await
+        .context("Failed to update metadata")?;
+
     info!("Successfully flagged secret at {} for rotation", path);
     Ok(())
 }
@@ -196,6 +1110,33 @@ pub async fn scan_for_rotation(
     backend: &dyn SecretBackend,
     path: &str,
     default_period: u32,
+) -> Result<Vec<String>> {
+    scan_for_rotation_ordered(
+        backend,
+        path,
+        default_period,
+        &HashMap::new(),
+        &MetadataSelector::default(),
+        &Progress::hidden(),
+    )
+    .await
+}
+
+/// Scan for secrets that need rotation, ordered so that every secret comes
+/// after the due secrets it depends on
+///
+/// Dependencies come from `rotate_after` metadata and from `configured`
+/// (secret path to the paths it rotates after). Dependencies that are not
+/// due are ignored; a cycle among due secrets is an error. Only secrets whose
+/// metadata matches `selector` are considered due. Each metadata read is
+/// reported to `progress`.
+pub async fn scan_for_rotation_ordered(
+    backend: &dyn SecretBackend,
+    path: &str,
+    default_period: u32,
+    configured: &HashMap<String, Vec<String>>,
+    selector: &MetadataSelector,
+    progress: &Progress,
 ) -> Result<Vec<String>> {
     info!(
         "Scanning for secrets needing rotation in {} ({})",
@@ -203,33 +1144,205 @@ pub async fn scan_for_rotation(
         backend.backend_type()
     );
 
-    let secrets = backend
-        .list_secrets(path)
-        .await
-        .context("Failed to list secrets")?;
-
     let mut needs_rotation_list = Vec::new();
+    let mut dependencies: HashMap<String, Vec<String>> = HashMap::new();
 
-    for secret in secrets {
-        let secret_path = if path.is_empty() {
-            secret.clone()
-        } else {
-            format!("{}/{}", path, secret)
-        };
+    // Check each page as it arrives rather than waiting for the whole listing
+    progress.start("scanning", 0);
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = backend
+            .list_secrets_page(path, cursor.as_deref())
+            .await
+            .context("Failed to list secrets")?;
+        progress.add(page.names.len(), page.next.is_some());
+
+        // Backends differ in whether listed names include the listed path
+        let secret_paths: Vec<String> = page
+            .names
+            .iter()
+            .map(|secret| transfer::child_path(path, secret))
+            .collect();
+        let metadata = backend.read_metadata_batch(&secret_paths).await;
 
-        match backend.read_metadata(&secret_path).await {
-            Ok(metadata) => {
-                if needs_rotation(&Some(metadata), default_period) {
-                    needs_rotation_list.push(secret_path);
+        for (secret_path, metadata) in secret_paths.into_iter().zip(metadata) {
+            progress.step(&secret_path);
+
+            match metadata {
+                Ok(metadata) => {
+                    if let Some(after) = metadata.get(ROTATE_AFTER_KEY) {
+                        dependencies.entry(secret_path.clone()).or_default().extend(
+                            after
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|path| !path.is_empty())
+                                .map(str::to_string),
+                        );
+                    }
+                    if selector.matches(&metadata)
+                        && needs_rotation(&Some(metadata), default_period)
+                    {
+                        needs_rotation_list.push(secret_path);
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to read metadata for {}: {}", secret_path, e);
                 }
             }
+        }
+
+        cursor = page.next;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    progress.clear();
+
+    for (secret_path, after) in configured {
+        dependencies
+            .entry(secret_path.clone())
+            .or_default()
+            .extend(after.iter().cloned());
+    }
+
+    order_by_dependencies(needs_rotation_list, &dependencies)
+}
+
+/// Every secret under `path` flagged for rotation, due or not, whose
+/// metadata matches `selector`
+pub async fn flagged_secrets(
+    backend: &dyn SecretBackend,
+    path: &str,
+    selector: &MetadataSelector,
+) -> Result<Vec<String>> {
+    let listed = backend
+        .list_secrets(path)
+        .await
+        .context("Failed to list secrets")?;
+    let secret_paths: Vec<String> = listed
+        .iter()
+        .map(|secret| transfer::child_path(path, secret))
+        .collect();
+    let metadata = backend.read_metadata_batch(&secret_paths).await;
+
+    let mut flagged = Vec::new();
+    for (secret_path, metadata) in secret_paths.into_iter().zip(metadata) {
+        match metadata {
+            Ok(metadata) if rotation_enabled(&metadata) && selector.matches(&metadata) => {
+                flagged.push(secret_path)
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to read metadata for {}: {}", secret_path, e),
+        }
+    }
+    Ok(flagged)
+}
+
+/// Overlapping secrets under `path` whose previous value is past its window
+pub async fn expired_previous_values(
+    backend: &dyn SecretBackend,
+    path: &str,
+) -> Result<Vec<String>> {
+    let now = Utc::now();
+    let mut expired = Vec::new();
+    for secret_path in flagged_secrets(backend, path, &MetadataSelector::default()).await? {
+        let metadata = match backend.read_metadata(&secret_path).await {
+            Ok(metadata) => metadata,
             Err(e) => {
                 warn!("Failed to read metadata for {}: {}", secret_path, e);
+                continue;
+            }
+        };
+        if previous_expired(&metadata, now) {
+            expired.push(secret_path);
+        }
+    }
+    Ok(expired)
+}
+
+fn previous_expired(metadata: &HashMap<String, String>, now: DateTime<Utc>) -> bool {
+    let Some(expires) = metadata.get(PREVIOUS_EXPIRES_KEY) else {
+        return false;
+    };
+    // Data may still be encrypted with the previous key
+    if ReencryptionState::from_metadata(metadata).is_some_and(ReencryptionState::is_unfinished) {
+        return false;
+    }
+    match DateTime::parse_from_rfc3339(expires) {
+        Ok(expires) => expires <= now,
+        Err(e) => {
+            warn!("Invalid {} '{}': {}", PREVIOUS_EXPIRES_KEY, expires, e);
+            false
+        }
+    }
+}
+
+/// Remove the previous value an overlapping secret kept after its last
+/// rotation, if its window is over; returns whether it was removed
+pub async fn retire_previous_value(backend: &dyn SecretBackend, path: &str) -> Result<bool> {
+    // A rotation since the scan may have kept a new previous value
+    let metadata = backend
+        .read_metadata(path)
+        .await
+        .context("Failed to read metadata")?;
+    if !previous_expired(&metadata, Utc::now()) {
+        return Ok(false);
+    }
+
+    let mut attempt = 1;
+    loop {
+        let current = backend
+            .read_secret(path)
+            .await
+            .context("Failed to read secret")?;
+        if !current.data.contains_key(PREVIOUS_KEY) {
+            break;
+        }
+        let mut data = current.data;
+        data.remove(PREVIOUS_KEY);
+        match backend.write_secret_cas(path, data, current.version).await {
+            Ok(()) => break,
+            Err(e) if attempt < MAX_WRITE_ATTEMPTS && e.is::<VersionConflict>() => {
+                warn!("{}; retrying (attempt {})", e, attempt + 1);
+                attempt += 1;
             }
+            Err(e) => return Err(e).context("Failed to write secret"),
+        }
+    }
+    backend
+        .remove_metadata(path, &[PREVIOUS_EXPIRES_KEY.to_string()])
+        .await
+        .context("Failed to update metadata")?;
+    Ok(true)
+}
+
+/// Order `secrets` so each one follows those of its dependencies that are in
+/// the list, keeping the original order otherwise
+pub fn order_by_dependencies(
+    secrets: Vec<String>,
+    dependencies: &HashMap<String, Vec<String>>,
+) -> Result<Vec<String>> {
+    let mut remaining = secrets;
+    let mut ordered: Vec<String> = Vec::with_capacity(remaining.len());
+
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|secret| {
+            dependencies
+                .get(secret)
+                .into_iter()
+                .flatten()
+                .all(|dependency| dependency == secret || !remaining.contains(dependency))
+        });
+        match ready {
+            Some(index) => ordered.push(remaining.remove(index)),
+            None => anyhow::bail!(
+                "Rotation dependencies form a cycle among: {}",
+                remaining.join(", ")
+            ),
         }
     }
 
-    Ok(needs_rotation_list)
+    Ok(ordered)
 }
 
 #[cfg(test)]
@@ -280,4 +1393,600 @@ mod tests {
         meta.insert("last_rotated".to_string(), old_date.to_rfc3339());
         assert!(needs_rotation(&Some(meta), 6));
     }
+
+    fn at(rfc3339: &str) -> DateTime<chrono::FixedOffset> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap()
+    }
+
+    #[test]
+    fn test_next_rotation_calendar_months() {
+        let due = |last: &str, period: &str| {
+            next_rotation(at(last), period.parse().unwrap()).to_rfc3339()
+        };
+
+        // A year is a year, not 360 days
+        assert_eq!(
+            due("2025-03-15T12:00:00+00:00", "12"),
+            "2026-03-15T12:00:00+00:00"
+        );
+        // Month ends clamp to the last day of shorter months
+        assert_eq!(
+            due("2025-01-31T09:00:00+00:00", "1"),
+            "2025-02-28T09:00:00+00:00"
+        );
+        assert_eq!(
+            due("2024-01-31T09:00:00+00:00", "1"),
+            "2024-02-29T09:00:00+00:00"
+        );
+        assert_eq!(
+            due("2024-02-29T09:00:00+00:00", "12"),
+            "2025-02-28T09:00:00+00:00"
+        );
+        // Months are counted in the recorded time zone: January 31st in
+        // New York is already February 1st in UTC
+        assert_eq!(
+            due("2025-01-31T22:00:00-05:00", "1"),
+            "2025-03-01T03:00:00+00:00"
+        );
+        // Half of the 28 days from February 10th to March 10th
+        assert_eq!(
+            due("2025-01-10T00:00:00+00:00", "1.5"),
+            "2025-02-24T00:00:00+00:00"
+        );
+        assert_eq!(
+            due("2024-02-29T00:00:00+00:00", "90d"),
+            "2024-05-29T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_rotation_period() {
+        assert_eq!(
+            "6".parse::<RotationPeriod>().unwrap(),
+            RotationPeriod::Months(6.0)
+        );
+        assert_eq!(
+            "0.5m".parse::<RotationPeriod>().unwrap(),
+            RotationPeriod::Months(0.5)
+        );
+        assert_eq!(
+            "90d".parse::<RotationPeriod>().unwrap(),
+            RotationPeriod::Days(90)
+        );
+        assert!("-1".parse::<RotationPeriod>().is_err());
+        assert!("1.5d".parse::<RotationPeriod>().is_err());
+        assert!("soon".parse::<RotationPeriod>().is_err());
+
+        let meta = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        assert_eq!(rotation_period(&meta(&[]), 6), RotationPeriod::Months(6.0));
+        assert_eq!(
+            rotation_period(&meta(&[("rotation_period_months", "3")]), 6),
+            RotationPeriod::Months(3.0)
+        );
+        assert_eq!(
+            rotation_period(
+                &meta(&[
+                    ("rotation_period_months", "3"),
+                    ("rotation_period_days", "45")
+                ]),
+                6
+            ),
+            RotationPeriod::Days(45)
+        );
+        // Suffixed values belong in the key for their unit
+        assert_eq!(
+            rotation_period(&meta(&[("rotation_period_months", "90d")]), 6),
+            RotationPeriod::Months(6.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flag_for_rotation_every_replaces_other_unit() -> Result<()> {
+        let backend = crate::backends::MemoryBackend::new();
+        backend.insert_secret(
+            "app/db",
+            HashMap::from([("password".to_string(), "x".to_string())]),
+        );
+
+        flag_for_rotation(&backend, "app/db", 6).await?;
+        flag_for_rotation_every(&backend, "app/db", RotationPeriod::Days(30), 0).await?;
+        let metadata = backend.read_metadata("app/db").await?;
+        assert_eq!(rotation_period(&metadata, 6), RotationPeriod::Days(30));
+        assert!(!metadata.contains_key("rotation_period_months"));
+
+        flag_for_rotation(&backend, "app/db", 2).await?;
+        let metadata = backend.read_metadata("app/db").await?;
+        assert_eq!(rotation_period(&metadata, 6), RotationPeriod::Months(2.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_jitter_seconds() {
+        assert_eq!(jitter_seconds(0), 0);
+
+        let spread: Vec<_> = (0..50).map(|_| jitter_seconds(10)).collect();
+        assert!(spread
+            .iter()
+            .all(|seconds| seconds.abs() <= Duration::days(10).num_seconds()));
+        assert!(spread.iter().any(|seconds| *seconds != spread[0]));
+    }
+
+    #[tokio::test]
+    async fn test_jitter_moves_due_date_until_rotation() -> Result<()> {
+        let backend =
+            crate::backends::MemoryBackend::new().with_secret("app/db", [("password", "old")]);
+        flag_for_rotation_every(&backend, "app/db", RotationPeriod::Days(30), 0).await?;
+        let mut metadata = backend.read_metadata("app/db").await?;
+        let last = last_rotated(&metadata).unwrap();
+        assert!(last <= Utc::now());
+        assert_eq!(due_at(&metadata, 6), Some(last + Duration::days(30)));
+
+        metadata.insert(SCHEDULE_OFFSET_KEY.to_string(), "-2592000".to_string());
+        assert_eq!(due_at(&metadata, 6), Some(last));
+        assert!(needs_rotation(&Some(metadata.clone()), 6));
+
+        backend.update_metadata("app/db", metadata).await?;
+        rotate_secret(&backend, "app/db", 32).await?;
+        let metadata = backend.read_metadata("app/db").await?;
+        assert!(!metadata.contains_key(SCHEDULE_OFFSET_KEY));
+        assert!(!needs_rotation(&Some(metadata), 6));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_secret_honors_generator_metadata() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let backend = crate::backends::FileBackend::new(temp_dir.path())?;
+
+        let data = HashMap::from([("password".to_string(), "old".to_string())]);
+        backend.write_secret("legacy/db", data).await?;
+        let metadata = HashMap::from([
+            ("secret_length".to_string(), "16".to_string()),
+            ("generator_style".to_string(), "alphanumeric".to_string()),
+        ]);
+        backend.update_metadata("legacy/db", metadata).await?;
+
+        let new_secret = rotate_secret(&backend, "legacy/db", 32).await?;
+        assert_eq!(new_secret.len(), 16);
+        assert!(new_secret.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        // Overrides survive the metadata update
+        let metadata = backend.read_metadata("legacy/db").await?;
+        assert_eq!(
+            metadata.get("secret_length").map(String::as_str),
+            Some("16")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_secret_with_target() -> Result<()> {
+        use crate::backends::{MemoryBackend, Operation};
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new().with_secret("app/db", [("password", "old")]);
+        let target = MockTarget::new();
+
+        let new_secret =
+            rotate_secret_with_target(&backend, "app/db", 24, Some(&target), Some("app")).await?;
+        assert_eq!(target.password("app"), Some(new_secret.clone()));
+        assert_eq!(backend.secret("app/db").unwrap()["password"], new_secret);
+        assert_eq!(
+            backend.metadata("app/db").unwrap()[ROTATION_METADATA_KEY],
+            "true"
+        );
+
+        // A rejected target update fails the rotation before metadata is touched
+        target.fail_update(Some("permission denied"));
+        let result =
+            rotate_secret_with_target(&backend, "app/db", 24, Some(&target), Some("app")).await;
+        assert!(result.is_err());
+        assert_eq!(target.updates(), 1);
+        assert_eq!(backend.calls(Operation::UpdateMetadata), 1);
+
+        // Failed verification leaves the backend value working on the target
+        target.fail_update(None);
+        target.fail_verify(Some("connection refused"));
+        let result =
+            rotate_secret_with_target(&backend, "app/db", 24, Some(&target), Some("app")).await;
+        assert!(result.is_err());
+        assert_eq!(target.updates(), 2);
+        assert_eq!(
+            backend.secret("app/db").unwrap()["password"],
+            target.password("app").unwrap()
+        );
+
+        // With a staging backend an unverified value is not left pending, and
+        // the target, which already took it, gets the old password back
+        let staging = MemoryBackend::new()
+            .with_staging()
+            .with_secret("app/db", [("password", "old")]);
+        let result =
+            rotate_secret_with_target(&staging, "app/db", 24, Some(&target), Some("app")).await;
+        assert!(result.is_err());
+        assert!(staging.staged("app/db").is_none());
+        assert_eq!(staging.secret("app/db").unwrap()["password"], "old");
+        assert_eq!(target.password("app").as_deref(), Some("old"));
+
+        // Without an old value to put back, the new one is kept instead
+        let raw = MemoryBackend::new()
+            .with_staging()
+            .with_secret("app/raw", [("username", "app")]);
+        let result =
+            rotate_secret_with_target(&raw, "app/raw", 24, Some(&target), Some("app")).await;
+        assert!(result.is_err());
+        assert!(raw.staged("app/raw").is_none());
+        assert_eq!(
+            raw.secret("app/raw").unwrap()["secret"],
+            target.password("app").unwrap()
+        );
+        target.fail_verify(None);
+        rotate_secret_with_target(&staging, "app/db", 24, Some(&target), Some("app")).await?;
+        assert_eq!(
+            staging.secret("app/db").unwrap()["password"],
+            target.password("app").unwrap()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotation_within_password_policy() -> Result<()> {
+        use crate::backends::MemoryBackend;
+        use crate::password_policy::{CharClass, PasswordPolicy};
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new().with_secret("app/db", [("password", "Old-pass1")]);
+        let target = MockTarget::new().with_password_policy(PasswordPolicy {
+            max_length: Some(16),
+            forbidden_characters: "!@#$%^&*".to_string(),
+            require: vec![CharClass::Digit],
+            history: 1,
+            ..Default::default()
+        });
+
+        let new_secret =
+            rotate_secret_with_target(&backend, "app/db", 32, Some(&target), Some("app")).await?;
+        assert_eq!(new_secret.len(), 16);
+        assert!(new_secret.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(target.password("app"), Some(new_secret.clone()));
+
+        // Supplied values are checked, not regenerated, before the target sees them
+        for value in [new_secret.as_str(), "no-digits-here"] {
+            let err = set_secret_with_canary(
+                &backend,
+                "app/db",
+                value,
+                Some(&target),
+                Some("app"),
+                None,
+                None,
+            )
+            .await
+            .unwrap_err();
+            assert!(format!("{:#}", err).contains("password policy"));
+        }
+        assert_eq!(target.updates(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_secret_with_paired_logins() -> Result<()> {
+        use crate::backends::MemoryBackend;
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new().with_secret("app/db", [("password", "old")]);
+        let target = MockTarget::new().with_paired_logins();
+
+        let first =
+            rotate_secret_with_target(&backend, "app/db", 24, Some(&target), Some("app")).await?;
+        let secret = backend.secret("app/db").unwrap();
+        assert_eq!(secret[USERNAME_KEY], "app_a");
+        assert_eq!(target.password("app_a"), Some(first.clone()));
+        assert_eq!(target.active_login("app"), Some("app_a".to_string()));
+
+        // The next rotation changes the other login and leaves the first one working
+        let second =
+            rotate_secret_with_target(&backend, "app/db", 24, Some(&target), Some("app")).await?;
+        assert_eq!(backend.secret("app/db").unwrap()[USERNAME_KEY], "app_b");
+        assert_eq!(target.password("app_b"), Some(second));
+        assert_eq!(target.password("app_a"), Some(first));
+        assert_eq!(target.active_login("app"), Some("app_b".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restore_secret() -> Result<()> {
+        use crate::backends::MemoryBackend;
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new().with_secret("app/db", [("password", "old")]);
+        let target = MockTarget::new().with_paired_logins();
+        let first =
+            rotate_secret_with_target(&backend, "app/db", 24, Some(&target), Some("app")).await?;
+
+        let snapshot = snapshot_secret(&backend, "app/db").await?;
+        rotate_secret_with_target(&backend, "app/db", 24, Some(&target), Some("app")).await?;
+        assert_eq!(target.active_login("app"), Some("app_b".to_string()));
+
+        restore_secret(&backend, &snapshot, Some(&target), Some("app")).await?;
+        let secret = backend.secret("app/db").unwrap();
+        assert_eq!(secret["password"], first);
+        assert_eq!(secret[USERNAME_KEY], "app_a");
+        assert_eq!(target.password("app_a"), Some(first));
+        assert_eq!(target.active_login("app"), Some("app_a".to_string()));
+        assert_eq!(backend.metadata("app/db"), Some(snapshot.metadata));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_secret_with_canary() -> Result<()> {
+        use crate::backends::MemoryBackend;
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new().with_secret("app/db", [("password", "old")]);
+        let production = MockTarget::new();
+        let staging = MockTarget::new();
+        let canary = Canary {
+            target: &staging,
+            username: Some("app_staging"),
+            enabled_by_default: true,
+        };
+
+        let new_secret = rotate_secret_with_canary(
+            &backend,
+            "app/db",
+            24,
+            Some(&production),
+            Some("app"),
+            Some(&canary),
+            None,
+        )
+        .await?;
+        assert_eq!(staging.password("app_staging"), Some(new_secret.clone()));
+        assert_eq!(production.password("app"), Some(new_secret));
+
+        // A failing canary leaves the production secret and target alone
+        staging.fail_verify(Some("password rejected"));
+        let before = backend.secret("app/db").unwrap();
+        let result = rotate_secret_with_canary(
+            &backend,
+            "app/db",
+            24,
+            Some(&production),
+            Some("app"),
+            Some(&canary),
+            None,
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(backend.secret("app/db").unwrap(), before);
+        assert_eq!(production.updates(), 1);
+
+        // Secrets can opt out of the canary through metadata
+        backend
+            .update_metadata(
+                "app/db",
+                HashMap::from([(CANARY_KEY.to_string(), "false".to_string())]),
+            )
+            .await?;
+        rotate_secret_with_canary(
+            &backend,
+            "app/db",
+            24,
+            Some(&production),
+            Some("app"),
+            Some(&canary),
+            None,
+        )
+        .await?;
+        assert_eq!(production.updates(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotation_recomputes_derived_keys() -> Result<()> {
+        use crate::backends::MemoryBackend;
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new().with_secret(
+            "app/db",
+            [
+                ("password", "old"),
+                ("host", "db"),
+                ("url", "postgres://app:old@db/app"),
+            ],
+        );
+        backend
+            .update_metadata(
+                "app/db",
+                HashMap::from([(
+                    "derive_url".to_string(),
+                    "postgres://app:{password|url}@{host}/app".to_string(),
+                )]),
+            )
+            .await?;
+        let configured = HashMap::from([("dsn".to_string(), "host={host}".to_string())]);
+        let target = MockTarget::new();
+
+        let new_secret = rotate_secret_with_canary(
+            &backend,
+            "app/db",
+            24,
+            Some(&target),
+            Some("app"),
+            None,
+            Some(&configured),
+        )
+        .await?;
+        let secret = backend.secret("app/db").unwrap();
+        assert_eq!(secret["password"], new_secret);
+        assert_eq!(
+            secret["url"],
+            format!(
+                "postgres://app:{}@db/app",
+                crate::derived::percent_encode(&new_secret)
+            )
+        );
+        assert_eq!(secret["dsn"], "host=db");
+
+        // A template that cannot be rendered stops the rotation before anything changes
+        backend
+            .update_metadata(
+                "app/db",
+                HashMap::from([("derive_url".to_string(), "{port}".to_string())]),
+            )
+            .await?;
+        let before = backend.secret("app/db").unwrap();
+        let result = rotate_secret_with_canary(
+            &backend,
+            "app/db",
+            24,
+            Some(&target),
+            Some("app"),
+            None,
+            None,
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(backend.secret("app/db").unwrap(), before);
+        assert_eq!(target.updates(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_overlap_strategy_keeps_previous_value() -> Result<()> {
+        use crate::backends::MemoryBackend;
+
+        let backend = MemoryBackend::new().with_secret("hooks/signing", [("current", "k1")]);
+        let metadata = HashMap::from([
+            (ROTATION_METADATA_KEY.to_string(), "true".to_string()),
+            (ROTATION_STRATEGY_KEY.to_string(), "overlap".to_string()),
+            (OVERLAP_HOURS_KEY.to_string(), "2".to_string()),
+        ]);
+        backend.update_metadata("hooks/signing", metadata).await?;
+
+        let new_key = rotate_secret(&backend, "hooks/signing", 24).await?;
+        let secret = backend.secret("hooks/signing").unwrap();
+        assert_eq!(secret[CURRENT_KEY], new_key);
+        assert_eq!(secret[PREVIOUS_KEY], "k1");
+        let metadata = backend.read_metadata("hooks/signing").await?;
+        let expires = DateTime::parse_from_rfc3339(&metadata[PREVIOUS_EXPIRES_KEY])?;
+        assert!(expires > Utc::now() + Duration::minutes(110));
+
+        // Both values stay until the window is over
+        assert!(expired_previous_values(&backend, "hooks").await?.is_empty());
+        assert!(!retire_previous_value(&backend, "hooks/signing").await?);
+
+        let mut metadata = metadata;
+        metadata.insert(
+            PREVIOUS_EXPIRES_KEY.to_string(),
+            (Utc::now() - Duration::minutes(1)).to_rfc3339(),
+        );
+        backend.update_metadata("hooks/signing", metadata).await?;
+        assert_eq!(
+            expired_previous_values(&backend, "hooks").await?,
+            ["hooks/signing"]
+        );
+        assert!(retire_previous_value(&backend, "hooks/signing").await?);
+        let secret = backend.secret("hooks/signing").unwrap();
+        assert_eq!(secret.get(PREVIOUS_KEY), None);
+        assert_eq!(secret[CURRENT_KEY], new_key);
+        assert!(!backend
+            .read_metadata("hooks/signing")
+            .await?
+            .contains_key(PREVIOUS_EXPIRES_KEY));
+
+        // A secret switched to overlap moves its old value to `previous`
+        let backend =
+            backend.with_secret("hooks/legacy", [("password", "old"), ("username", "svc")]);
+        let metadata = HashMap::from([
+            (ROTATION_METADATA_KEY.to_string(), "true".to_string()),
+            (ROTATION_STRATEGY_KEY.to_string(), "overlap".to_string()),
+        ]);
+        backend.update_metadata("hooks/legacy", metadata).await?;
+        let new_key = rotate_secret(&backend, "hooks/legacy", 24).await?;
+        let secret = backend.secret("hooks/legacy").unwrap();
+        assert_eq!(secret.get("password"), None);
+        assert_eq!(secret[CURRENT_KEY], new_key);
+        assert_eq!(secret[PREVIOUS_KEY], "old");
+        assert_eq!(secret["username"], "svc");
+
+        // Only the overlap strategy rotates a key named `current`
+        let data = HashMap::from([
+            (CURRENT_KEY.to_string(), "c".to_string()),
+            ("api_token".to_string(), "t".to_string()),
+        ]);
+        assert_eq!(key_to_rotate(&data, &HashMap::new()), "api_token");
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlap_window() {
+        let metadata = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        assert_eq!(overlap_window(&metadata(&[])).unwrap(), None);
+        assert_eq!(
+            overlap_window(&metadata(&[(ROTATION_STRATEGY_KEY, "overlap")])).unwrap(),
+            Some(Duration::hours(DEFAULT_OVERLAP_HOURS))
+        );
+        assert!(overlap_window(&metadata(&[(ROTATION_STRATEGY_KEY, "swap")])).is_err());
+        assert!(overlap_window(&metadata(&[
+            (ROTATION_STRATEGY_KEY, "overlap"),
+            (OVERLAP_HOURS_KEY, "0")
+        ]))
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scan_reads_listing_in_pages() -> Result<()> {
+        use crate::backends::{MemoryBackend, Operation};
+
+        let backend = MemoryBackend::new().with_page_size(2);
+        for name in ["a", "b", "c", "d", "e"] {
+            let path = format!("app/{}", name);
+            backend.insert_secret(&path, [("password", "x")]);
+            backend
+                .update_metadata(
+                    &path,
+                    HashMap::from([(ROTATION_METADATA_KEY.to_string(), "true".to_string())]),
+                )
+                .await?;
+        }
+
+        let due = scan_for_rotation(&backend, "app", 6).await?;
+        assert_eq!(due.len(), 5);
+        assert_eq!(backend.calls(Operation::List), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_by_dependencies() {
+        let secrets = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let dependencies = HashMap::from([
+            ("db/admin".to_string(), secrets(&["db/app", "db/report"])),
+            ("db/app".to_string(), secrets(&["db/not-due"])),
+        ]);
+
+        assert_eq!(
+            order_by_dependencies(secrets(&["db/admin", "db/app", "db/report"]), &dependencies)
+                .unwrap(),
+            secrets(&["db/app", "db/report", "db/admin"])
+        );
+
+        let cycle = HashMap::from([
+            ("a".to_string(), secrets(&["b"])),
+            ("b".to_string(), secrets(&["a"])),
+        ]);
+        let err = order_by_dependencies(secrets(&["a", "b", "c"]), &cycle).unwrap_err();
+        assert!(err.to_string().contains("a, b"), "{}", err);
+    }
 }
@@ -0,0 +1,1500 @@
+//! Builder-style entry point for library consumers
+//!
+//! [`Rotator`] bundles a backend, an optional target, and a rotation policy so
+//! that scanning and rotating does not have to be wired up by hand:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use secret_rotator::backends::FileBackend;
+//! use secret_rotator::config::RotationConfig;
+//! use secret_rotator::rotator::Rotator;
+//!
+//! let rotator = Rotator::builder()
+//!     .backend(Box::new(FileBackend::new("/var/lib/asr")?))
+//!     .policy(RotationConfig::default())
+//!     .build()?;
+//!
+//! let report = rotator.auto("").await?;
+//! println!("rotated {} secret(s)", report.rotated.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{error, warn, Instrument, Span};
+
+use crate::audit::{AuditEvent, AuditLog, Outcome};
+use crate::backends::{Backend, SecretBackend};
+use crate::certs::{self, CertificateIssuer};
+use crate::circuit_breaker::{CircuitBreaker, CircuitOpen, OpenCircuit};
+use crate::composite::{self, CompositeConfig};
+use crate::config::RotationConfig;
+use crate::hooks::{self, HooksConfig};
+use crate::logging;
+use crate::path_filter::{MetadataSelector, PathFilter};
+use crate::progress::Progress;
+use crate::redact::{self, SecretString};
+use crate::rotation;
+use crate::targets::{Target, TargetInstance};
+use crate::watch::SecretWatch;
+
+/// A secret that was rotated successfully
+#[derive(Debug)]
+pub struct RotatedSecret {
+    pub path: String,
+    pub value: SecretString,
+    /// Target user whose password was updated, if any
+    pub target_username: Option<String>,
+    /// Composites that could not be rewritten and post-rotation hooks that
+    /// failed; the secret itself was still rotated
+    pub hook_failures: Vec<String>,
+    /// Expiry of the new certificate, when the secret is a certificate
+    pub not_after: Option<DateTime<Utc>>,
+}
+
+/// Outcome of rotating every due secret
+#[derive(Debug, Default)]
+pub struct RotationReport {
+    pub rotated: Vec<RotatedSecret>,
+    /// Secrets that could not be rotated, with the reason
+    pub failed: Vec<(String, anyhow::Error)>,
+    /// Due secrets left for a later run by the policy's `max_per_run`
+    pub deferred: Vec<String>,
+    /// Overlapping secrets whose previous value was removed after its window
+    pub retired: Vec<String>,
+}
+
+/// Rotates secrets in a backend according to a policy
+pub struct Rotator {
+    backend: Arc<dyn SecretBackend>,
+    target: Option<TargetInstance>,
+    policy: RotationConfig,
+    hooks: HooksConfig,
+    audit: AuditLog,
+    canary: Option<CanaryTarget>,
+    issuers: Vec<Box<dyn CertificateIssuer>>,
+    composites: Vec<CompositeConfig>,
+    breaker: CircuitBreaker,
+}
+
+/// Target that receives new credentials before the production target
+struct CanaryTarget {
+    target: TargetInstance,
+    username: Option<String>,
+}
+
+impl Rotator {
+    pub fn builder() -> RotatorBuilder {
+        RotatorBuilder::default()
+    }
+
+    pub fn backend(&self) -> &dyn SecretBackend {
+        self.backend.as_ref()
+    }
+
+    pub fn target(&self) -> Option<&dyn Target> {
+        self.target.as_deref()
+    }
+
+    /// Certificate issuer named `name` (e.g., "acme"), or the first configured
+    pub fn issuer(&self, name: Option<&str>) -> Result<&dyn CertificateIssuer> {
+        certs::select_issuer(&self.issuers, name)
+    }
+
+    pub fn policy(&self) -> &RotationConfig {
+        &self.policy
+    }
+
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Watch the secret at `path` for changes made by anyone, such as a
+    /// rotation by another process
+    ///
+    /// Changes are seen from now on; see [`crate::watch`].
+    #[allow(dead_code)] // Library API, not used by the CLI
+    pub async fn watch(&self, path: &str) -> Result<SecretWatch> {
+        SecretWatch::start(self.backend.clone(), path).await
+    }
+
+    /// State of the target's circuit breaker, if it is open
+    pub fn open_circuit(&self) -> Option<OpenCircuit> {
+        self.breaker.open_circuit()
+    }
+
+    /// Find secrets under `path` that are due for rotation, in the order they
+    /// should be rotated
+    pub async fn scan(&self, path: &str) -> Result<Vec<String>> {
+        self.scan_where(path, &MetadataSelector::default()).await
+    }
+
+    /// Like [`Rotator::scan`], limited to secrets whose metadata matches
+    /// `selector` (e.g., `team=payments`)
+    pub async fn scan_where(&self, path: &str, selector: &MetadataSelector) -> Result<Vec<String>> {
+        self.scan_with_progress(path, selector, &Progress::hidden())
+            .await
+    }
+
+    /// Like [`Rotator::scan_where`], reporting each secret checked to `progress`
+    pub async fn scan_with_progress(
+        &self,
+        path: &str,
+        selector: &MetadataSelector,
+        progress: &Progress,
+    ) -> Result<Vec<String>> {
+        let due = rotation::scan_for_rotation_ordered(
+            self.backend(),
+            path,
+            self.policy.period_months,
+            &self.policy.rotate_after,
+            selector,
+            progress,
+        )
+        .await?;
+        Ok(self.admin_credential_last(due))
+    }
+
+    /// Rotation failures recorded on those of `paths` whose last attempt failed
+    pub async fn rotation_failures(
+        &self,
+        paths: &[String],
+    ) -> BTreeMap<String, rotation::RotationFailures> {
+        let metadata = self.backend.read_metadata_batch(paths).await;
+        paths
+            .iter()
+            .zip(metadata)
+            .filter_map(|(path, metadata)| {
+                let failures = rotation::rotation_failures(&metadata.ok()?)?;
+                Some((path.clone(), failures))
+            })
+            .collect()
+    }
+
+    /// Move the target's own admin credential to the end of `due`, so every
+    /// other secret is rotated over the connection the run started with
+    fn admin_credential_last(&self, mut due: Vec<String>) -> Vec<String> {
+        let admin_path = self
+            .target()
+            .and_then(|target| target.admin_credential())
+            .map(|(path, _)| path);
+        if let Some(index) = due.iter().position(|p| Some(p.as_str()) == admin_path) {
+            let admin = due.remove(index);
+            due.push(admin);
+        }
+        due
+    }
+
+    /// Fail unless rotating `path` as `username` keeps the target able to
+    /// connect, i.e. `path` is not the admin credential or is rotated on the
+    /// target as the admin user
+    fn check_admin_credential(&self, path: &str, username: Option<&str>) -> Result<()> {
+        let Some(target) = self.target() else {
+            return Ok(());
+        };
+        match target.admin_credential() {
+            Some((admin_path, admin)) if admin_path == path && username != Some(admin) => {
+                anyhow::bail!(
+                    "{} holds the admin credential of the {} target; rotate it with \
+                     --update-target and target_username = \"{}\" so the target \
+                     keeps working",
+                    path,
+                    target.target_type(),
+                    admin
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Switch the target to its rotated admin credential, if `path` is it
+    async fn reload_admin_credential(&self, path: &str, value: &str) -> Result<()> {
+        let Some(target) = self.target() else {
+            return Ok(());
+        };
+        if !matches!(target.admin_credential(), Some((admin_path, _)) if admin_path == path) {
+            return Ok(());
+        }
+        let result = target.reload_admin_credential(value).await;
+        let backend = self.backend.backend_type();
+        let event = match &result {
+            Ok(()) => AuditEvent::new("admin-reload", path, backend, Outcome::Success),
+            Err(e) => AuditEvent::new("admin-reload", path, backend, Outcome::Failure)
+                .with_detail(redact::redact(&format!("{:#}", e))),
+        };
+        self.audit.record(&event);
+        result.with_context(|| {
+            format!(
+                "Rotated {}, but the {} target could not reconnect with the new admin credential",
+                path,
+                target.target_type()
+            )
+        })
+    }
+
+    /// Check that the value stored for `path` still works on the target,
+    /// without rotating anything
+    ///
+    /// Fails when the target rejects the stored credential, meaning the
+    /// secret and the target have drifted apart. Secrets without a target
+    /// user in their metadata, and certificates, are skipped.
+    pub async fn verify(&self, path: &str) -> Result<Verification> {
+        let target = self
+            .target()
+            .context("No target configured for this rotator")?;
+        let metadata = self
+            .backend
+            .read_metadata(path)
+            .await
+            .context("Failed to read metadata")?;
+        if certs::is_certificate(&metadata) {
+            return Ok(Verification::Skipped("certificate"));
+        }
+        let secret = self
+            .backend
+            .read_secret(path)
+            .await
+            .context("Failed to read secret")?;
+        let Some((login, value)) = rotation::stored_credential(&secret.data, &metadata) else {
+            return Ok(Verification::Skipped("no target user in metadata"));
+        };
+
+        let result = target
+            .verify_connection(&login, &value, None)
+            .await
+            .with_context(|| {
+                format!(
+                    "Stored credential for {} no longer works on the {} target",
+                    login,
+                    target.target_type()
+                )
+            });
+        let backend = self.backend.backend_type();
+        let event = match &result {
+            Ok(()) => AuditEvent::new("verify", path, backend, Outcome::Success),
+            Err(e) => AuditEvent::new("verify", path, backend, Outcome::Failure)
+                .with_detail(redact::redact(&format!("{:#}", e))),
+        };
+        self.audit.record(&event.with_target_username(Some(&login)));
+        result?;
+        Ok(Verification::Verified { login })
+    }
+
+    /// Apply the value stored for `path` to the target again, for when the
+    /// target credential was changed out of band and locked the app out
+    ///
+    /// With `version`, an earlier value kept by the backend is applied
+    /// instead and, once the target accepts it, written back as the secret's
+    /// current value. `username` overrides the target user in metadata.
+    /// Returns the login whose password was set.
+    pub async fn restore_target(
+        &self,
+        path: &str,
+        version: Option<u64>,
+        username: Option<&str>,
+    ) -> Result<String> {
+        let result = self.restore_target_inner(path, version, username).await;
+        let backend = self.backend.backend_type();
+        let event = match &result {
+            Ok(_) => AuditEvent::new("restore-target", path, backend, Outcome::Success),
+            Err(e) => AuditEvent::new("restore-target", path, backend, Outcome::Failure)
+                .with_detail(redact::redact(&format!("{:#}", e))),
+        };
+        let login = result.as_ref().ok().map(String::as_str).or(username);
+        self.audit.record(&event.with_target_username(login));
+        result
+    }
+
+    async fn restore_target_inner(
+        &self,
+        path: &str,
+        version: Option<u64>,
+        username: Option<&str>,
+    ) -> Result<String> {
+        let target = self
+            .target()
+            .context("No target configured for this rotator")?;
+        let metadata = self
+            .backend
+            .read_metadata(path)
+            .await
+            .context("Failed to read metadata")?;
+        if certs::is_certificate(&metadata) {
+            anyhow::bail!("{} is a certificate and has no target password", path);
+        }
+        let username = username
+            .map(str::to_string)
+            .or_else(|| rotation::target_username(&metadata))
+            .context("No target user in metadata; pass the target username")?;
+
+        let secret = match version {
+            Some(version) => self.backend.read_version(path, version).await,
+            None => self.backend.read_secret(path).await,
+        }
+        .context("Failed to read secret")?;
+        let (login, value) = rotation::credential_for(&secret.data, &metadata, username.clone())
+            .context("The secret holds no value to apply")?;
+        redact::register_secret(&value);
+
+        target
+            .update_password(&login, &value)
+            .await
+            .with_context(|| format!("Failed to update {} password", target.target_type()))?;
+        target
+            .verify_connection(&login, &value, None)
+            .await
+            .with_context(|| format!("Failed to verify {} password", target.target_type()))?;
+        target
+            .activate_login(&username, &login)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to activate {} login {}",
+                    target.target_type(),
+                    login
+                )
+            })?;
+
+        if version.is_some() {
+            let current = self
+                .backend
+                .read_secret(path)
+                .await
+                .context("Failed to read current secret")?;
+            self.backend
+                .write_secret_cas(path, secret.data, current.version)
+                .await
+                .context("The target took the earlier value, but writing it back failed")?;
+            for failure in self.rewrite_composites(path).await {
+                warn!("{}", failure);
+            }
+        }
+        Ok(login)
+    }
+
+    /// Rotate a secret in the backend only
+    ///
+    /// Certificate secrets are renewed through the configured issuer instead
+    /// of getting a generated value.
+    pub async fn rotate(&self, path: &str) -> Result<RotatedSecret> {
+        self.rotate_inner(path)
+            .instrument(self.span(path, None))
+            .await
+    }
+
+    async fn rotate_inner(&self, path: &str) -> Result<RotatedSecret> {
+        let metadata = self.backend.read_metadata(path).await.unwrap_or_default();
+        if certs::is_certificate(&metadata) {
+            let issuer = certs::CertificateRequest::from_metadata(&metadata).and_then(|r| r.issuer);
+            return self.renew_certificate(path, issuer.as_deref()).await;
+        }
+        self.check_admin_credential(path, None)?;
+
+        let result = rotation::rotate_secret_with_canary(
+            self.backend(),
+            path,
+            self.policy.secret_length,
+            None,
+            None,
+            None,
+            self.policy.derived_keys.get(path),
+        )
+        .await;
+        self.record_rotation(path, None, &result).await;
+        let value = result?;
+        Ok(RotatedSecret {
+            path: path.to_string(),
+            value: value.into(),
+            target_username: None,
+            hook_failures: self.run_hooks(path).await,
+            not_after: None,
+        })
+    }
+
+    async fn is_certificate(&self, path: &str) -> bool {
+        self.backend
+            .read_metadata(path)
+            .await
+            .map(|metadata| certs::is_certificate(&metadata))
+            .unwrap_or(false)
+    }
+
+    async fn renew_certificate(&self, path: &str, issuer: Option<&str>) -> Result<RotatedSecret> {
+        let result = match self.issuer(issuer) {
+            Ok(issuer) => certs::renew_certificate(self.backend(), path, issuer).await,
+            Err(e) => Err(e),
+        };
+        self.record_rotation(path, None, &result).await;
+        let issued = result?;
+        Ok(RotatedSecret {
+            path: path.to_string(),
+            value: issued.certificate.into(),
+            target_username: None,
+            hook_failures: self.run_hooks(path).await,
+            not_after: Some(issued.not_after),
+        })
+    }
+
+    /// Rotate a secret and update the password of `username` on the target
+    pub async fn rotate_with_target(&self, path: &str, username: &str) -> Result<RotatedSecret> {
+        self.rotate_with_target_inner(path, username)
+            .instrument(self.span(path, Some(username)))
+            .await
+    }
+
+    async fn rotate_with_target_inner(&self, path: &str, username: &str) -> Result<RotatedSecret> {
+        if self.is_certificate(path).await {
+            anyhow::bail!("{} is a certificate and has no target password", path);
+        }
+        let target = self
+            .target()
+            .context("No target configured for this rotator")?;
+        self.check_admin_credential(path, Some(username))?;
+        let result = match self.breaker.check(target.target_type()) {
+            Ok(()) => {
+                let result = rotation::rotate_secret_with_canary(
+                    self.backend(),
+                    path,
+                    self.policy.secret_length,
+                    Some(target),
+                    Some(username),
+                    self.canary().as_ref(),
+                    self.policy.derived_keys.get(path),
+                )
+                .await;
+                if self.breaker.record(&result) {
+                    self.circuit_opened(path, target.target_type());
+                }
+                result
+            }
+            Err(open) => Err(open.into()),
+        };
+        self.record_rotation(path, Some(username), &result).await;
+        let value = result?;
+        self.reload_admin_credential(path, &value).await?;
+        Ok(RotatedSecret {
+            path: path.to_string(),
+            value: value.into(),
+            target_username: Some(username.to_string()),
+            hook_failures: self.run_hooks(path).await,
+            not_after: None,
+        })
+    }
+
+    /// Store a value supplied from outside (e.g., issued by a third party) as
+    /// the new value of a secret, updating `username` on the target if given
+    ///
+    /// The value is staged, sent to the canary and target, and verified exactly
+    /// like a generated one.
+    pub async fn set_value(
+        &self,
+        path: &str,
+        value: &str,
+        username: Option<&str>,
+    ) -> Result<RotatedSecret> {
+        self.set_value_inner(path, value, username)
+            .instrument(self.span(path, username))
+            .await
+    }
+
+    async fn set_value_inner(
+        &self,
+        path: &str,
+        value: &str,
+        username: Option<&str>,
+    ) -> Result<RotatedSecret> {
+        if self.is_certificate(path).await {
+            anyhow::bail!("{} is a certificate; use issue-cert to replace it", path);
+        }
+        let target = match username {
+            Some(_) => Some(
+                self.target()
+                    .context("No target configured for this rotator")?,
+            ),
+            None => None,
+        };
+        self.check_admin_credential(path, username)?;
+        let canary = target.and(self.canary());
+        let result = rotation::set_secret_with_canary(
+            self.backend(),
+            path,
+            value,
+            target,
+            username,
+            canary.as_ref(),
+            self.policy.derived_keys.get(path),
+        )
+        .await;
+        self.record_rotation(path, username, &result).await;
+        result?;
+        if username.is_some() {
+            self.reload_admin_credential(path, value).await?;
+        }
+        Ok(RotatedSecret {
+            path: path.to_string(),
+            value: value.to_string().into(),
+            target_username: username.map(str::to_string),
+            hook_failures: self.run_hooks(path).await,
+            not_after: None,
+        })
+    }
+
+    /// Span around one rotation, giving its log lines the secret's fields
+    fn span(&self, path: &str, username: Option<&str>) -> Span {
+        let span = tracing::info_span!(
+            "rotation",
+            run_id = logging::run_id(),
+            secret_path = path,
+            backend = self.backend.backend_type(),
+            target = tracing::field::Empty,
+        );
+        if let Some(target) = self.target().filter(|_| username.is_some()) {
+            span.record("target", target.target_type());
+        }
+        span
+    }
+
+    fn canary(&self) -> Option<rotation::Canary<'_>> {
+        self.canary.as_ref().map(|canary| rotation::Canary {
+            target: canary.target.as_ref(),
+            username: canary.username.as_deref(),
+            enabled_by_default: self.policy.canary,
+        })
+    }
+
+    /// Rotate `paths` as one unit, in order
+    ///
+    /// Every secret is snapshotted before it is rotated. If one of them fails,
+    /// the secrets already rotated are restored in reverse order (target
+    /// passwords included, when `update_target` is set) and the error is
+    /// returned. Hooks run again for restored secrets so that consumers pick
+    /// the old values back up.
+    pub async fn rotate_atomic(
+        &self,
+        paths: &[String],
+        update_target: bool,
+    ) -> Result<Vec<RotatedSecret>> {
+        let mut done: Vec<(rotation::SecretSnapshot, Option<String>, RotatedSecret)> = Vec::new();
+
+        for path in paths {
+            let snapshot = match rotation::snapshot_secret(self.backend(), path).await {
+                Ok(snapshot) => snapshot,
+                Err(e) => return Err(self.roll_back(done, None, e).await),
+            };
+            let username = if update_target {
+                rotation::target_username(snapshot.metadata())
+            } else {
+                None
+            };
+
+            let result = match username {
+                Some(ref username) => self.rotate_with_target(path, username).await,
+                None => self.rotate(path).await,
+            };
+            match result {
+                Ok(rotated) => done.push((snapshot, username, rotated)),
+                Err(e) => {
+                    // A target may already have taken the new password
+                    let failed = username.map(|username| (snapshot, username));
+                    let e = e.context(format!("Failed to rotate {}", path));
+                    return Err(self.roll_back(done, failed, e).await);
+                }
+            }
+        }
+
+        Ok(done.into_iter().map(|(_, _, rotated)| rotated).collect())
+    }
+
+    /// Restore the secrets of a failed atomic rotation, returning `cause`
+    /// with the outcome of the rollback attached
+    async fn roll_back(
+        &self,
+        done: Vec<(rotation::SecretSnapshot, Option<String>, RotatedSecret)>,
+        failed: Option<(rotation::SecretSnapshot, String)>,
+        cause: anyhow::Error,
+    ) -> anyhow::Error {
+        error!("Atomic rotation failed, rolling back: {:#}", cause);
+
+        let failed = failed.map(|(snapshot, username)| (snapshot, Some(username)));
+        let to_restore: Vec<_> = failed
+            .into_iter()
+            .chain(
+                done.into_iter()
+                    .rev()
+                    .map(|(snapshot, username, _)| (snapshot, username)),
+            )
+            .collect();
+
+        let mut restored = 0;
+        let mut unrestored = Vec::new();
+        for (snapshot, username) in &to_restore {
+            let target = username.as_ref().and(self.target());
+            let result =
+                rotation::restore_secret(self.backend(), snapshot, target, username.as_deref())
+                    .await;
+            let backend = self.backend.backend_type();
+            let event = match &result {
+                Ok(()) => AuditEvent::new("rollback", &snapshot.path, backend, Outcome::Success),
+                Err(e) => AuditEvent::new("rollback", &snapshot.path, backend, Outcome::Failure)
+                    .with_detail(redact::redact(&format!("{:#}", e))),
+            };
+            self.audit
+                .record(&event.with_target_username(username.as_deref()));
+
+            match result {
+                Ok(()) => {
+                    restored += 1;
+                    self.run_hooks(&snapshot.path).await;
+                }
+                Err(e) => {
+                    error!("Failed to restore {}: {:#}", snapshot.path, e);
+                    unrestored.push(snapshot.path.clone());
+                }
+            }
+        }
+
+        if unrestored.is_empty() {
+            cause.context(format!(
+                "Atomic rotation failed; restored {} secret(s)",
+                restored
+            ))
+        } else {
+            cause.context(format!(
+                "Atomic rotation failed and could not restore: {}",
+                unrestored.join(", ")
+            ))
+        }
+    }
+
+    /// Report that the target's circuit breaker opened on rotating `path`
+    fn circuit_opened(&self, path: &str, target: &str) {
+        let Some(open) = self.breaker.open_circuit() else {
+            return;
+        };
+        let detail = redact::redact(&format!(
+            "{} target failed {} rotations in a row: {}",
+            target, open.failures, open.last_error
+        ));
+        error!(
+            "Circuit breaker opened; refusing rotations against the {} target for {} minutes",
+            target, self.policy.circuit_breaker_cooldown_minutes
+        );
+        self.audit.record(
+            &AuditEvent::new(
+                "circuit-open",
+                path,
+                self.backend.backend_type(),
+                Outcome::Failure,
+            )
+            .with_detail(detail),
+        );
+    }
+
+    /// Send an audit event for a rotation attempt, and record a failed one
+    /// in the secret's metadata (rotations the circuit breaker refused were
+    /// never attempted and are not recorded)
+    async fn record_rotation<T>(&self, path: &str, username: Option<&str>, result: &Result<T>) {
+        let backend = self.backend.backend_type();
+        let event = match result {
+            Ok(_) => AuditEvent::new("rotate", path, backend, Outcome::Success),
+            // Refused by the circuit breaker: nothing was attempted
+            Err(e) if e.downcast_ref::<CircuitOpen>().is_some() => return,
+            Err(e) => {
+                let detail = redact::redact(&format!("{:#}", e));
+                if let Err(e) =
+                    rotation::record_rotation_failure(self.backend(), path, &detail).await
+                {
+                    warn!("Failed to record the failed rotation of {}: {:#}", path, e);
+                }
+                AuditEvent::new("rotate", path, backend, Outcome::Failure).with_detail(detail)
+            }
+        };
+        self.audit.record(&event.with_target_username(username));
+    }
+
+    /// Render a composite secret from its inputs and write it
+    pub async fn write_composite(&self, composite: &CompositeConfig) -> Result<()> {
+        let result = composite.write(self.backend()).await;
+        let backend = self.backend.backend_type();
+        let event = match &result {
+            Ok(()) => AuditEvent::new("composite", &composite.path, backend, Outcome::Success),
+            Err(e) => AuditEvent::new("composite", &composite.path, backend, Outcome::Failure)
+                .with_detail(redact::redact(&format!("{:#}", e))),
+        };
+        self.audit.record(&event);
+        result
+    }
+
+    /// Composite secrets this rotator keeps up to date
+    pub fn composites(&self) -> &[CompositeConfig] {
+        &self.composites
+    }
+
+    /// Post-rotation hooks this rotator runs
+    pub fn hooks(&self) -> &HooksConfig {
+        &self.hooks
+    }
+
+    /// Mark the pending re-encryption for the data-encryption key at `path`
+    /// complete (see [`hooks::ReencryptHookConfig`])
+    pub async fn complete_reencryption(&self, path: &str) -> Result<()> {
+        let result = hooks::complete_reencryption(self.backend(), path).await;
+        let backend = self.backend.backend_type();
+        let event = match &result {
+            Ok(()) => AuditEvent::new("reencrypt-complete", path, backend, Outcome::Success),
+            Err(e) => AuditEvent::new("reencrypt-complete", path, backend, Outcome::Failure)
+                .with_detail(redact::redact(&format!("{:#}", e))),
+        };
+        self.audit.record(&event);
+        result
+    }
+
+    /// Rewrite the composites built from the secret at `path`, returning the
+    /// failures
+    pub async fn rewrite_composites(&self, path: &str) -> Vec<String> {
+        let dependents = match composite::dependents(&self.composites, path) {
+            Ok(dependents) => dependents,
+            Err(e) => {
+                warn!("Failed to order composites built from {}: {:#}", path, e);
+                return vec![format!("composites: {:#}", e)];
+            }
+        };
+        let mut failures = Vec::new();
+        for composite in dependents {
+            if let Err(e) = self.write_composite(composite).await {
+                warn!("Failed to rewrite composite {}: {:#}", composite.path, e);
+                failures.push(format!("composite {}: {:#}", composite.path, e));
+            }
+        }
+        failures
+    }
+
+    /// Rewrite the composites built from a secret and run its post-rotation
+    /// hooks, returning the failures
+    async fn run_hooks(&self, path: &str) -> Vec<String> {
+        let mut failures = self.rewrite_composites(path).await;
+        let metadata = match self.backend.read_metadata(path).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!("Failed to read metadata for hooks of {}: {}", path, e);
+                Default::default()
+            }
+        };
+
+        let mut hook_failures = hooks::run_post_rotation(&self.hooks, path, &metadata).await;
+        hook_failures.extend(hooks::run_reencryption(&self.hooks, self.backend(), path).await);
+        for failure in &hook_failures {
+            warn!("Post-rotation hook failed for {}: {}", path, failure);
+        }
+        failures.extend(hook_failures);
+        failures
+    }
+
+    /// Remove the previous values of overlapping secrets under `path` (see
+    /// [`rotation::overlap_window`]) whose window is over and whose path
+    /// matches `filter`, returning their paths
+    ///
+    /// A secret that cannot be updated is logged and left for the next run.
+    pub async fn retire_previous_values(
+        &self,
+        path: &str,
+        filter: &PathFilter,
+    ) -> Result<Vec<String>> {
+        let expired = rotation::expired_previous_values(self.backend(), path).await?;
+        let mut retired = Vec::new();
+        for secret_path in filter.apply(expired) {
+            let result = rotation::retire_previous_value(self.backend(), &secret_path).await;
+            let backend = self.backend.backend_type();
+            match result {
+                Ok(false) => {}
+                Ok(true) => {
+                    self.audit.record(&AuditEvent::new(
+                        "retire-previous",
+                        &secret_path,
+                        backend,
+                        Outcome::Success,
+                    ));
+                    for failure in self.rewrite_composites(&secret_path).await {
+                        warn!("{}", failure);
+                    }
+                    retired.push(secret_path);
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to retire previous value of {}: {:#}",
+                        secret_path, e
+                    );
+                    self.audit.record(
+                        &AuditEvent::new(
+                            "retire-previous",
+                            &secret_path,
+                            backend,
+                            Outcome::Failure,
+                        )
+                        .with_detail(redact::redact(&format!("{:#}", e))),
+                    );
+                }
+            }
+        }
+        Ok(retired)
+    }
+
+    /// Rotate every due secret under `path`
+    ///
+    /// When a target is configured, secrets whose metadata names a target user
+    /// also get their target password updated. A failure to rotate one secret
+    /// is recorded in the report and does not stop the others. Secrets beyond
+    /// the policy's `max_per_run` are deferred. Previous values of overlapping
+    /// secrets are retired first once their window is over.
+    pub async fn auto(&self, path: &str) -> Result<RotationReport> {
+        let mut report = RotationReport {
+            retired: self
+                .retire_previous_values(path, &PathFilter::default())
+                .await?,
+            ..Default::default()
+        };
+
+        let mut due = self.scan(path).await?;
+        report.deferred = defer_excess(&mut due, self.policy.max_per_run);
+
+        for secret_path in due {
+            let username = match self.target {
+                Some(_) => self
+                    .backend
+                    .read_metadata(&secret_path)
+                    .await
+                    .ok()
+                    .and_then(|metadata| rotation::target_username(&metadata)),
+                None => None,
+            };
+
+            let result = match username {
+                Some(username) => self.rotate_with_target(&secret_path, &username).await,
+                None => self.rotate(&secret_path).await,
+            };
+
+            match result {
+                Ok(rotated) => report.rotated.push(rotated),
+                Err(e) => {
+                    error!("Failed to rotate {}: {:#}", secret_path, e);
+                    report.failed.push((secret_path, e));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Outcome of checking a stored credential against the target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// The stored value works for this login
+    Verified { login: String },
+    /// There was nothing to check, for this reason
+    Skipped(&'static str),
+}
+
+/// Cut `due` down to at most `max` secrets, returning the ones removed
+///
+/// Due secrets are in rotation order, so the deferred ones are those that
+/// would have rotated last.
+pub fn defer_excess(due: &mut Vec<String>, max: Option<usize>) -> Vec<String> {
+    match max {
+        Some(max) if due.len() > max => due.split_off(max),
+        _ => Vec::new(),
+    }
+}
+
+/// Builder for [`Rotator`]
+#[derive(Default)]
+pub struct RotatorBuilder {
+    backend: Option<Backend>,
+    target: Option<TargetInstance>,
+    policy: RotationConfig,
+    hooks: HooksConfig,
+    audit: AuditLog,
+    canary: Option<CanaryTarget>,
+    issuers: Vec<Box<dyn CertificateIssuer>>,
+    composites: Vec<CompositeConfig>,
+}
+
+impl RotatorBuilder {
+    /// Backend holding the secrets (required)
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Target system to update when secrets are rotated
+    pub fn target(mut self, target: TargetInstance) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Rotation period and secret length (defaults to `RotationConfig::default()`)
+    pub fn policy(mut self, policy: RotationConfig) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Hooks to run after each rotation (e.g., restarting containers)
+    pub fn hooks(mut self, hooks: HooksConfig) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Target that new credentials are applied to and verified on before the
+    /// production target, as `username` (or the production user if `None`)
+    pub fn canary(mut self, target: TargetInstance, username: Option<String>) -> Self {
+        self.canary = Some(CanaryTarget { target, username });
+        self
+    }
+
+    /// Secrets to rewrite whenever one of their inputs rotates
+    pub fn composites(mut self, composites: Vec<CompositeConfig>) -> Self {
+        self.composites = composites;
+        self
+    }
+
+    /// Where to send audit events for rotation attempts
+    pub fn audit(mut self, audit: AuditLog) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Certificate authority used to renew certificate secrets
+    ///
+    /// May be called once per issuer type; secrets are renewed by the issuer
+    /// named in their `cert_issuer` metadata, or the first one added.
+    pub fn issuer(mut self, issuer: Box<dyn CertificateIssuer>) -> Self {
+        self.issuers.push(issuer);
+        self
+    }
+
+    pub fn build(self) -> Result<Rotator> {
+        let backend = self
+            .backend
+            .context("A backend is required to build a Rotator")?;
+        Ok(Rotator {
+            backend: Arc::from(backend),
+            target: self.target,
+            breaker: CircuitBreaker::new(
+                self.policy.circuit_breaker_threshold,
+                self.policy.circuit_breaker_cooldown_minutes,
+            ),
+            policy: self.policy,
+            hooks: self.hooks,
+            audit: self.audit,
+            canary: self.canary,
+            issuers: self.issuers,
+            composites: self.composites,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::FileBackend;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_rotator_auto_rotates_due_secrets() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?;
+        for path in ["app/due", "app/fresh"] {
+            backend
+                .write_secret(
+                    path,
+                    HashMap::from([("password".to_string(), "old".to_string())]),
+                )
+                .await?;
+        }
+        rotation::flag_for_rotation(&backend, "app/fresh", 6).await?;
+        backend
+            .update_metadata(
+                "app/due",
+                HashMap::from([
+                    ("rotation_enabled".to_string(), "true".to_string()),
+                    (
+                        "last_rotated".to_string(),
+                        "2000-01-01T00:00:00Z".to_string(),
+                    ),
+                ]),
+            )
+            .await?;
+
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .policy(RotationConfig {
+                period_months: 6,
+                secret_length: 20,
+                ..Default::default()
+            })
+            .build()?;
+
+        assert_eq!(rotator.scan("app").await?, vec!["app/due"]);
+
+        let report = rotator.auto("app").await?;
+        assert!(report.failed.is_empty());
+        assert_eq!(report.rotated.len(), 1);
+        assert_eq!(report.rotated[0].path, "app/due");
+        assert_eq!(report.rotated[0].value.expose_secret().len(), 20);
+        assert!(rotator.scan("app").await?.is_empty());
+
+        // Rotating with a target requires one to be configured
+        assert!(rotator
+            .rotate_with_target("app/due", "admin")
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scan_orders_dependencies() -> Result<()> {
+        use crate::backends::MemoryBackend;
+
+        let backend = MemoryBackend::new();
+        for (path, after) in [
+            ("db/admin", Some("db/app")),
+            ("db/app", None),
+            ("db/report", None),
+        ] {
+            backend.insert_secret(path, [("password", "old")]);
+            let mut metadata =
+                HashMap::from([("rotation_enabled".to_string(), "true".to_string())]);
+            if let Some(after) = after {
+                metadata.insert("rotate_after".to_string(), after.to_string());
+            }
+            backend.update_metadata(path, metadata).await?;
+        }
+
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .policy(RotationConfig {
+                rotate_after: HashMap::from([(
+                    "db/app".to_string(),
+                    vec!["db/report".to_string()],
+                )]),
+                ..Default::default()
+            })
+            .build()?;
+
+        let mut due = rotator.scan("db").await?;
+        assert_eq!(due, vec!["db/report", "db/app", "db/admin"]);
+
+        let selector = MetadataSelector::new(vec!["rotate_after=db/app".parse()?]);
+        assert_eq!(rotator.scan_where("db", &selector).await?, vec!["db/admin"]);
+
+        let report = rotator.auto("db").await?;
+        due = report.rotated.into_iter().map(|r| r.path).collect();
+        assert_eq!(due, vec!["db/report", "db/app", "db/admin"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auto_defers_beyond_max_per_run() -> Result<()> {
+        use crate::backends::MemoryBackend;
+
+        let backend = MemoryBackend::new();
+        for path in ["app/a", "app/b", "app/c"] {
+            backend.insert_secret(path, [("password", "old")]);
+            backend
+                .update_metadata(
+                    path,
+                    HashMap::from([("rotation_enabled".to_string(), "true".to_string())]),
+                )
+                .await?;
+        }
+
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .policy(RotationConfig {
+                max_per_run: Some(2),
+                ..Default::default()
+            })
+            .build()?;
+
+        let report = rotator.auto("app").await?;
+        assert_eq!(report.rotated.len(), 2);
+        assert_eq!(report.deferred.len(), 1);
+        assert_eq!(rotator.scan("app").await?, report.deferred);
+
+        let report = rotator.auto("app").await?;
+        assert_eq!(report.rotated.len(), 1);
+        assert!(report.deferred.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_atomic_rolls_back() -> Result<()> {
+        use crate::backends::MemoryBackend;
+
+        let backend = MemoryBackend::new();
+        for path in ["creds/user", "creds/password", "creds/cert"] {
+            backend.insert_secret(path, [("password", "old")]);
+        }
+        // Certificates cannot be renewed without an issuer, so the last one fails
+        backend
+            .update_metadata(
+                "creds/cert",
+                HashMap::from([("cert_common_name".to_string(), "example.com".to_string())]),
+            )
+            .await?;
+
+        let rotator = Rotator::builder().backend(Box::new(backend)).build()?;
+        let paths: Vec<String> = ["creds/user", "creds/password", "creds/cert"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let err = rotator.rotate_atomic(&paths, false).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("restored 2 secret(s)"));
+        for path in &paths[..2] {
+            let secret = rotator.backend().read_secret(path).await?;
+            assert_eq!(secret.data["password"], "old");
+            assert!(rotator.backend().read_metadata(path).await?.is_empty());
+        }
+
+        let rotated = rotator.rotate_atomic(&paths[..2], false).await?;
+        assert_eq!(rotated.len(), 2);
+        let secret = rotator.backend().read_secret("creds/user").await?;
+        assert_eq!(secret.data["password"], rotated[0].value.expose_secret());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_stops_rotations() -> Result<()> {
+        use crate::backends::MemoryBackend;
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new();
+        for path in ["db/a", "db/b", "db/c"] {
+            backend.insert_secret(path, [("password", "old")]);
+        }
+        backend
+            .update_metadata(
+                "db/c",
+                [("rotation_enabled".to_string(), "true".to_string())].into(),
+            )
+            .await?;
+        let target = MockTarget::new();
+        target.fail_update(Some("admin password expired"));
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .target(Box::new(target))
+            .policy(RotationConfig {
+                circuit_breaker_threshold: 2,
+                ..Default::default()
+            })
+            .build()?;
+
+        // A missing secret fails before the target is involved
+        for _ in 0..3 {
+            rotator
+                .rotate_with_target("db/missing", "app")
+                .await
+                .unwrap_err();
+        }
+        assert!(rotator.open_circuit().is_none());
+
+        for path in ["db/a", "db/b"] {
+            let err = rotator.rotate_with_target(path, "app").await.unwrap_err();
+            assert!(err.downcast_ref::<CircuitOpen>().is_none());
+        }
+        let open = rotator.open_circuit().expect("breaker should be open");
+        assert_eq!(open.failures, 2);
+        assert!(open.last_error.contains("admin password expired"));
+
+        let err = rotator.rotate_with_target("db/c", "app").await.unwrap_err();
+        assert!(err.downcast_ref::<CircuitOpen>().is_some());
+        let secret = rotator.backend().read_secret("db/c").await?;
+        assert_eq!(secret.data["password"], "old");
+        // A refusal is not a failed attempt
+        assert!(rotator
+            .rotation_failures(&["db/c".to_string()])
+            .await
+            .is_empty());
+
+        // Rotations that don't involve the target are unaffected
+        rotator.rotate("db/c").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failures_recorded_in_metadata() -> Result<()> {
+        use crate::backends::MemoryBackend;
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new()
+            .with_secret("db/app", [("password", "old")])
+            .with_secret("db/unflagged", [("password", "old")]);
+        backend
+            .update_metadata(
+                "db/app",
+                [("rotation_enabled".to_string(), "true".to_string())].into(),
+            )
+            .await?;
+        let target = MockTarget::new();
+        target.fail_update(Some("password authentication failed for \"admin\""));
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .target(Box::new(target))
+            .build()?;
+        let paths = vec!["db/app".to_string(), "db/unflagged".to_string()];
+        assert!(rotator.rotation_failures(&paths).await.is_empty());
+
+        for path in &paths {
+            rotator.rotate_with_target(path, "app").await.unwrap_err();
+        }
+        let first = rotator.rotation_failures(&paths).await["db/app"].clone();
+        rotator
+            .rotate_with_target("db/app", "app")
+            .await
+            .unwrap_err();
+
+        let failing = rotator.rotation_failures(&paths).await;
+        assert_eq!(failing.keys().collect::<Vec<_>>(), ["db/app"]);
+        let failures = &failing["db/app"];
+        assert_eq!(failures.consecutive_failures, 2);
+        assert_eq!(failures.failing_since, first.failing_since);
+        assert!(failures
+            .last_error
+            .as_deref()
+            .unwrap()
+            .ends_with("password authentication failed for admin"));
+        // Unflagged secrets get no metadata from failed attempts
+        assert!(rotator
+            .backend()
+            .read_metadata("db/unflagged")
+            .await?
+            .is_empty());
+
+        // A successful rotation clears the failures
+        rotator.rotate("db/app").await?;
+        let metadata = rotator.backend().read_metadata("db/app").await?;
+        assert!(rotation::rotation_failures(&metadata).is_none());
+        assert!(!metadata.contains_key("last_rotation_error"));
+        assert!(metadata.contains_key("last_rotation_attempt"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admin_credential_rotated_last() -> Result<()> {
+        use crate::backends::MemoryBackend;
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new();
+        for (path, username, last_rotated) in [
+            ("db/admin", "postgres", "1999-01-01T00:00:00Z"),
+            ("db/app", "app", "2000-01-01T00:00:00Z"),
+        ] {
+            backend.insert_secret(path, [("password", "old")]);
+            backend
+                .update_metadata(
+                    path,
+                    HashMap::from([
+                        ("rotation_enabled".to_string(), "true".to_string()),
+                        ("last_rotated".to_string(), last_rotated.to_string()),
+                        ("target_username".to_string(), username.to_string()),
+                    ]),
+                )
+                .await?;
+        }
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .target(Box::new(
+                MockTarget::new().with_admin_credential("db/admin", "postgres"),
+            ))
+            .build()?;
+
+        assert_eq!(rotator.scan("db").await?, vec!["db/app", "db/admin"]);
+
+        // Rotating it without updating the admin user would lock the target out
+        let err = rotator.rotate("db/admin").await.unwrap_err();
+        assert!(err.to_string().contains("admin credential"));
+        let err = rotator
+            .rotate_with_target("db/admin", "app")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("target_username = \"postgres\""));
+
+        let report = rotator.auto("db").await?;
+        assert!(report.failed.is_empty());
+        let rotated: Vec<_> = report.rotated.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(rotated, ["db/app", "db/admin"]);
+        let stored = rotator.backend().read_secret("db/admin").await?;
+        rotator
+            .target()
+            .unwrap()
+            .verify_connection("postgres", &stored.data["password"], None)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_value() -> Result<()> {
+        use crate::backends::MemoryBackend;
+
+        let backend = MemoryBackend::new().with_secret("vendor/api", [("token", "old")]);
+        let rotator = Rotator::builder().backend(Box::new(backend)).build()?;
+
+        let stored = rotator
+            .set_value("vendor/api", "issued-by-vendor", None)
+            .await?;
+        assert_eq!(stored.value.expose_secret(), "issued-by-vendor");
+        let secret = rotator.backend().read_secret("vendor/api").await?;
+        assert_eq!(secret.data["token"], "issued-by-vendor");
+        assert!(
+            rotation::last_rotated(&rotator.backend().read_metadata("vendor/api").await?).is_some()
+        );
+
+        assert!(rotator.set_value("vendor/api", "", None).await.is_err());
+        assert!(rotator
+            .set_value("vendor/api", "x", Some("admin"))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_detects_drift() -> Result<()> {
+        use crate::backends::MemoryBackend;
+        use crate::targets::MockTarget;
+
+        let backend = MemoryBackend::new()
+            .with_secret("app/db", [("password", "old")])
+            .with_secret("app/plain", [("password", "x")]);
+        backend
+            .update_metadata(
+                "app/db",
+                HashMap::from([("target_username".to_string(), "app".to_string())]),
+            )
+            .await?;
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .target(Box::new(MockTarget::new()))
+            .build()?;
+
+        rotator.rotate_with_target("app/db", "app").await?;
+        assert_eq!(
+            rotator.verify("app/db").await?,
+            Verification::Verified {
+                login: "app".to_string()
+            }
+        );
+        assert!(matches!(
+            rotator.verify("app/plain").await?,
+            Verification::Skipped(_)
+        ));
+
+        // Someone changed the stored value without updating the target
+        let mut data = rotator.backend().read_secret("app/db").await?.data;
+        data.insert("password".to_string(), "edited".to_string());
+        rotator.backend().write_secret("app/db", data).await?;
+        let err = rotator.verify("app/db").await.unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("no longer works"),
+            "{:#}",
+            err
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotation_rewrites_composites() -> Result<()> {
+        use crate::backends::MemoryBackend;
+
+        let backend = MemoryBackend::new()
+            .with_secret("app/db", [("password", "old")])
+            .with_secret("vendor/api", [("key", "api-key")]);
+        let bundle = CompositeConfig {
+            path: "app/bundle".to_string(),
+            keys: [
+                ("db".to_string(), "app/db#password".to_string()),
+                ("api".to_string(), "vendor/api#key".to_string()),
+            ]
+            .into(),
+            json_key: Some("config".to_string()),
+        };
+        let broken = CompositeConfig {
+            path: "app/broken".to_string(),
+            keys: [("db".to_string(), "app/db#username".to_string())].into(),
+            json_key: None,
+        };
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .composites(vec![bundle, broken])
+            .build()?;
+
+        let rotated = rotator.rotate("app/db").await?;
+        let config = rotator.backend().read_secret("app/bundle").await?.data["config"].clone();
+        let config: HashMap<String, String> = serde_json::from_str(&config)?;
+        assert_eq!(config["db"], rotated.value.expose_secret());
+        assert_eq!(config["api"], "api-key");
+
+        // A composite that cannot be built is reported, not a failed rotation
+        assert_eq!(rotated.hook_failures.len(), 1);
+        assert!(rotated.hook_failures[0].starts_with("composite app/broken"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restore_target() -> Result<()> {
+        use crate::targets::MockTarget;
+
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?;
+        for value in ["first", "second"] {
+            backend
+                .write_secret(
+                    "app/db",
+                    HashMap::from([("password".to_string(), value.to_string())]),
+                )
+                .await?;
+        }
+        backend
+            .update_metadata(
+                "app/db",
+                HashMap::from([("target_username".to_string(), "app".to_string())]),
+            )
+            .await?;
+        let target = MockTarget::new();
+        target.update_password("app", "changed-by-hand").await?;
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .target(Box::new(target))
+            .build()?;
+
+        assert_eq!(rotator.restore_target("app/db", None, None).await?, "app");
+        let target = rotator.target().unwrap();
+        target.verify_connection("app", "second", None).await?;
+
+        // An earlier version goes to the target and back into the secret
+        rotator.restore_target("app/db", Some(1), None).await?;
+        target.verify_connection("app", "first", None).await?;
+        let secret = rotator.backend().read_secret("app/db").await?;
+        assert_eq!(secret.data["password"], "first");
+
+        // Versions and secrets that are not there fail
+        assert!(rotator
+            .restore_target("app/db", Some(9), None)
+            .await
+            .is_err());
+        assert!(rotator
+            .restore_target("app/other", None, Some("app"))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_requires_backend() {
+        assert!(Rotator::builder().build().is_err());
+    }
+}
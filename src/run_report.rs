@@ -0,0 +1,222 @@
+//! Machine-readable reports of `auto` runs
+//!
+//! Every run has an ID, the same one that the `rotation` log spans carry (see
+//! [`crate::logging::run_id`]). With a `[run_reports]` section, `auto` writes
+//! a JSON report of the run when it ends: what happened to each due secret
+//! and how long it took. Tooling can then diff runs and spot secrets that
+//! fail intermittently:
+//!
+//! ```toml
+//! [run_reports]
+//! dir = "/var/lib/asr/runs"   # <dir>/<run_id>.json
+//! path = "asr/runs"           # secret <path>/<run_id>, key "report"
+//! ```
+//!
+//! Reports only contain paths, timings and redacted errors, never secret values.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::backends::SecretBackend;
+use crate::logging;
+use crate::redact;
+
+/// Version of the report layout
+const REPORT_FORMAT: u32 = 1;
+
+/// Key holding the report when it is written to the backend
+pub const REPORT_KEY: &str = "report";
+
+/// Where run reports are written
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunReportsConfig {
+    /// Directory to write `<run_id>.json` to (created if needed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+
+    /// Backend path to write the report under, as secret `<path>/<run_id>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl RunReportsConfig {
+    pub fn is_empty(&self) -> bool {
+        self.dir.is_none() && self.path.is_none()
+    }
+}
+
+/// What happened to a due secret during a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretStatus {
+    Rotated,
+    Failed,
+    /// Rotated as part of an atomic batch that failed, then restored
+    RolledBack,
+    /// Left for a later run by the rotation limit
+    Deferred,
+    /// Not attempted because the run lost the leader lease or the target's
+    /// circuit breaker was open
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SecretResult {
+    pub path: String,
+    pub status: SecretStatus,
+    /// Time spent rotating this secret (absent for atomic batches and
+    /// secrets that were not attempted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Post-rotation hooks and composites that failed for a rotated secret
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hook_failures: Vec<String>,
+}
+
+/// Outcome of one `auto` run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunReport {
+    pub format: u32,
+    pub run_id: String,
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// Backend type and base path of the run
+    pub backend: String,
+    pub path: String,
+    pub atomic: bool,
+    pub update_target: bool,
+    pub secrets: Vec<SecretResult>,
+}
+
+impl RunReport {
+    /// Start the report of this process's run
+    pub fn start(backend: &str, path: &str, atomic: bool, update_target: bool) -> Self {
+        let now = Utc::now();
+        Self {
+            format: REPORT_FORMAT,
+            run_id: logging::run_id().to_string(),
+            started: now,
+            finished: now,
+            duration_ms: 0,
+            backend: backend.to_string(),
+            path: path.to_string(),
+            atomic,
+            update_target,
+            secrets: Vec::new(),
+        }
+    }
+
+    /// Record the outcome of a secret; `error` is redacted
+    pub fn record(
+        &mut self,
+        path: &str,
+        status: SecretStatus,
+        duration: Option<Duration>,
+        error: Option<&str>,
+    ) -> &mut SecretResult {
+        self.secrets.push(SecretResult {
+            path: path.to_string(),
+            status,
+            duration_ms: duration.map(|d| d.as_millis() as u64),
+            error: error.map(redact::redact),
+            hook_failures: Vec::new(),
+        });
+        self.secrets.last_mut().expect("just pushed")
+    }
+
+    /// Stamp the end of the run
+    pub fn finish(&mut self) {
+        self.finished = Utc::now();
+        self.duration_ms = (self.finished - self.started).num_milliseconds().max(0) as u64;
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize run report")
+    }
+
+    /// Write the report wherever `config` says, returning the locations
+    pub async fn write(
+        &self,
+        config: &RunReportsConfig,
+        backend: &dyn SecretBackend,
+    ) -> Result<Vec<String>> {
+        let json = self.to_json()?;
+        let mut written = Vec::new();
+        if let Some(ref dir) = config.dir {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create run report directory {:?}", dir))?;
+            let file = dir.join(format!("{}.json", self.run_id));
+            std::fs::write(&file, &json)
+                .with_context(|| format!("Failed to write run report to {:?}", file))?;
+            written.push(file.display().to_string());
+        }
+        if let Some(ref base) = config.path {
+            let path = format!("{}/{}", base.trim_end_matches('/'), self.run_id);
+            backend
+                .write_secret(&path, HashMap::from([(REPORT_KEY.to_string(), json)]))
+                .await
+                .with_context(|| format!("Failed to write run report to {}", path))?;
+            written.push(format!("{} ({})", path, backend.backend_type()));
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::MemoryBackend;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_write_report() -> Result<()> {
+        let mut report = RunReport::start("memory", "prod", false, true);
+        report
+            .record(
+                "prod/db",
+                SecretStatus::Rotated,
+                Some(Duration::from_millis(42)),
+                None,
+            )
+            .hook_failures
+            .push("systemd nginx.service: exit 1".to_string());
+        report.record(
+            "prod/api",
+            SecretStatus::Failed,
+            Some(Duration::from_millis(7)),
+            Some("target rejected the password"),
+        );
+        report.record("prod/cache", SecretStatus::Deferred, None, None);
+        report.finish();
+
+        let dir = TempDir::new()?;
+        let backend = MemoryBackend::new();
+        let config = RunReportsConfig {
+            dir: Some(dir.path().join("runs")),
+            path: Some("asr/runs/".to_string()),
+        };
+        let written = report.write(&config, &backend).await?;
+        assert_eq!(written.len(), 2);
+
+        let file = dir
+            .path()
+            .join("runs")
+            .join(format!("{}.json", report.run_id));
+        let from_file: RunReport = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+        assert_eq!(from_file, report);
+        let stored = backend
+            .secret(&format!("asr/runs/{}", report.run_id))
+            .unwrap();
+        let from_backend: RunReport = serde_json::from_str(&stored[REPORT_KEY])?;
+        assert_eq!(from_backend.secrets[1].status, SecretStatus::Failed);
+        assert!(!stored[REPORT_KEY].contains("\"duration_ms\": null"));
+        Ok(())
+    }
+}
@@ -0,0 +1,1200 @@
+//! REST API server (`asr serve`)
+//!
+//! Lets other automation (ChatOps bots, internal portals) trigger scans and
+//! rotations over HTTP instead of shelling out to the CLI:
+//!
+//! - `GET /healthz` - liveness check, no authentication
+//! - `GET /status` - backend, target, rotation counters and the retry
+//!   queue, plus the data-encryption keys whose re-encryption is pending or failed when
+//!   `[[hooks.reencrypt]]` is configured
+//! - `GET /scan?path=<base>` - secrets due for rotation, with the recorded
+//!   failures of those whose last rotation attempt failed
+//! - `POST /rotate/<path>` - rotate a secret; an optional JSON body
+//!   `{"target_username": "..."}` also updates the target password
+//! - `POST /webhook` - out-of-cycle rotation requested by another system
+//!   (an audit pipeline, a security scanner, a "credential leaked" button)
+//!   with a JSON body `{"path": "...", "source": "...", "reason": "..."}`
+//! - `POST /reencrypt/<path>` - report that the application finished
+//!   re-encrypting the data under a rotated data-encryption key
+//! - `GET /tokens` - names, roles and paths of the API tokens (admin only)
+//! - `GET /metrics` - rotation and retry counters in the Prometheus text format
+//!
+//! Rotations that fail are retried in the background with exponential
+//! backoff (see [`crate::retry`]); `/status` lists the ones waiting.
+//!
+//! Every endpoint except `/healthz` requires `Authorization: Bearer <token>`.
+//! Each token has a role (`read-only` for status and scans, `rotate` to also
+//! rotate, `admin` for everything) and may be limited to secret paths by
+//! globs, so several teams can share one server without reaching each
+//! other's secrets. `/webhook` alternatively accepts the body signed with the
+//! `[server] webhook_secret`, for senders that can sign payloads but not hold
+//! an API token: `X-Asr-Timestamp` carries the Unix time of the request and
+//! `X-Asr-Signature-256: sha256=<hex>` the HMAC-SHA256 of `<timestamp>.<body>`.
+//! Signatures older than five minutes are rejected, so a captured request
+//! can't be replayed later. Rotated values are never included in responses.
+//!
+//! With `[leader]` configured, every replica answers scans and status, but
+//! only the leader rotates; the others answer rotation requests with `503`.
+
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Extension;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::audit::{AuditEvent, Outcome};
+use crate::backends::is_listed;
+use crate::circuit_breaker::CircuitOpen;
+use crate::hooks;
+use crate::leader::Leadership;
+use crate::path_filter::PathFilter;
+use crate::redact::{self, SecretString};
+use crate::retry::{self, RetryPolicy, RetryQueue, ScheduledRetry};
+use crate::rotator::{RotatedSecret, Rotator};
+
+/// Header carrying the HMAC-SHA256 of a webhook request as `sha256=<hex>`
+const SIGNATURE_HEADER: &str = "x-asr-signature-256";
+
+/// Header carrying the Unix time a webhook request was signed at
+const TIMESTAMP_HEADER: &str = "x-asr-timestamp";
+
+/// Seconds a webhook signature is accepted for, either side of its timestamp
+const MAX_SIGNATURE_AGE_SECS: i64 = 300;
+
+/// What an API token may do; each role can do everything the ones before it can
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// `/status`, `/scan` and `/metrics`
+    ReadOnly,
+    /// Also `/rotate`, `/reencrypt` and `/webhook`
+    Rotate,
+    /// Also `/tokens`
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::ReadOnly => "read-only",
+            Role::Rotate => "rotate",
+            Role::Admin => "admin",
+        })
+    }
+}
+
+/// An API token (`[[server.tokens]]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiTokenConfig {
+    /// Who holds the token (e.g., a team), for logs and `/tokens`
+    pub name: String,
+    pub token: SecretString,
+    pub role: Role,
+    /// Globs of the secret paths the token can scan and rotate; empty for all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
+
+impl ApiTokenConfig {
+    /// The single `[server] token`, which can do everything
+    pub fn admin(token: SecretString) -> Self {
+        Self {
+            name: "default".to_string(),
+            token,
+            role: Role::Admin,
+            paths: Vec::new(),
+        }
+    }
+}
+
+/// The holder of an API token, attached to authenticated requests
+#[derive(Debug)]
+struct Principal {
+    name: String,
+    role: Role,
+    paths: Vec<String>,
+    filter: PathFilter,
+}
+
+impl Principal {
+    fn require(&self, role: Role) -> Result<(), ApiError> {
+        if self.role >= role {
+            return Ok(());
+        }
+        Err(ApiError(
+            StatusCode::FORBIDDEN,
+            format!(
+                "Token '{}' has the {} role; {} is required",
+                self.name, self.role, role
+            ),
+        ))
+    }
+
+    fn require_path(&self, path: &str) -> Result<(), ApiError> {
+        if self.filter.matches(path) {
+            return Ok(());
+        }
+        Err(ApiError(
+            StatusCode::FORBIDDEN,
+            format!("Token '{}' is not allowed to access {}", self.name, path),
+        ))
+    }
+}
+
+/// Refuse paths with empty, `.` or `..` segments
+///
+/// Backends resolve `..` (the file backend as a directory, Vault as a URL), so
+/// such a path could match a token's glob and still reach another secret.
+fn validate_path(path: &str) -> Result<(), ApiError> {
+    if path
+        .split('/')
+        .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid secret path {}", path),
+        ));
+    }
+    Ok(())
+}
+
+struct AppState {
+    rotator: Rotator,
+    tokens: Vec<(SecretString, Arc<Principal>)>,
+    webhook_secret: Option<SecretString>,
+    /// Whether this replica is the leader, with leader election
+    leader: Option<watch::Receiver<bool>>,
+    started_at: DateTime<Utc>,
+    stats: Mutex<Stats>,
+    retries: RetryQueue,
+}
+
+impl AppState {
+    fn require_leader(&self) -> Result<(), ApiError> {
+        if self.leader.as_ref().is_none_or(|leader| *leader.borrow()) {
+            return Ok(());
+        }
+        Err(ApiError(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "This replica is standing by; rotations are handled by the leader".to_string(),
+        ))
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+struct Stats {
+    rotations: u64,
+    failures: u64,
+    /// Rotations the circuit breaker refused without attempting them
+    refused: u64,
+    last_rotation: Option<LastRotation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LastRotation {
+    path: String,
+    at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ScanQuery {
+    #[serde(default)]
+    path: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RotateRequest {
+    target_username: Option<String>,
+}
+
+/// Body of a `/webhook` request
+#[derive(Debug, Deserialize)]
+struct WebhookRequest {
+    path: String,
+    /// Sending system (e.g., "vault-audit", "gitleaks")
+    source: Option<String>,
+    /// Why the secret has to be rotated now
+    reason: Option<String>,
+    target_username: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RotateResponse {
+    path: String,
+    target_username: Option<String>,
+    hook_failures: Vec<String>,
+}
+
+/// An error returned to the client as `{"error": "..."}`
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn internal(error: anyhow::Error) -> Self {
+        Self(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            redact::redact(&format!("{:#}", error)),
+        )
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Build the API router around a rotator
+fn router(
+    rotator: Rotator,
+    tokens: Vec<ApiTokenConfig>,
+    webhook_secret: Option<SecretString>,
+    leader: Option<watch::Receiver<bool>>,
+    retry: RetryPolicy,
+) -> Result<Router> {
+    if tokens.is_empty() {
+        anyhow::bail!("At least one API token is required");
+    }
+    let tokens = tokens
+        .into_iter()
+        .map(|config| {
+            if config.token.expose_secret().is_empty() {
+                anyhow::bail!("API token '{}' is empty", config.name);
+            }
+            let filter = PathFilter::new(&config.paths, &[])
+                .with_context(|| format!("Invalid paths for API token '{}'", config.name))?;
+            let principal = Principal {
+                name: config.name,
+                role: config.role,
+                paths: config.paths,
+                filter,
+            };
+            Ok((config.token, Arc::new(principal)))
+        })
+        .collect::<Result<_>>()?;
+
+    let state = Arc::new(AppState {
+        rotator,
+        tokens,
+        webhook_secret,
+        leader,
+        started_at: Utc::now(),
+        stats: Mutex::new(Stats::default()),
+        retries: RetryQueue::new(retry),
+    });
+    if retry.attempts > 0 {
+        tokio::spawn(retry_failed_rotations(state.clone()));
+    }
+
+    Ok(Router::new()
+        .route("/status", get(status))
+        .route("/scan", get(scan))
+        .route("/rotate/*path", post(rotate))
+        .route("/reencrypt/*path", post(complete_reencryption))
+        .route("/tokens", get(list_tokens))
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .route("/healthz", get(|| async { "ok" }))
+        // Authenticates itself, since it also accepts signed bodies
+        .route("/webhook", post(webhook))
+        .with_state(state))
+}
+
+/// Serve the API on `bind` until interrupted
+///
+/// With `leadership`, the lease is given up when the server shuts down.
+pub async fn serve(
+    rotator: Rotator,
+    bind: &str,
+    tokens: Vec<ApiTokenConfig>,
+    webhook_secret: Option<SecretString>,
+    leadership: Option<Leadership>,
+    retry: RetryPolicy,
+) -> Result<()> {
+    let leader = leadership.as_ref().map(Leadership::watch);
+    let router = router(rotator, tokens, webhook_secret, leader, retry)?;
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("Failed to bind {}", bind))?;
+    info!("Serving REST API on {}", listener.local_addr()?);
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("Shutting down REST API");
+        })
+        .await
+        .context("REST API server failed")?;
+    if let Some(leadership) = leadership {
+        leadership.stop().await;
+    }
+    Ok(())
+}
+
+/// Compare two byte strings without returning early on the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Holder of the API token the request carries, if it is a known one
+fn principal(headers: &HeaderMap, state: &AppState) -> Option<Arc<Principal>> {
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))?;
+    state
+        .tokens
+        .iter()
+        .find(|(token, _)| constant_time_eq(provided.as_bytes(), token.expose_secret().as_bytes()))
+        .map(|(_, principal)| principal.clone())
+}
+
+/// Whether the request carries a valid signature of `body` made with `secret`
+fn has_signature(headers: &HeaderMap, body: &[u8], secret: &SecretString) -> bool {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let Some(provided) = header(SIGNATURE_HEADER).and_then(|value| value.strip_prefix("sha256="))
+    else {
+        return false;
+    };
+    let Some(timestamp) = header(TIMESTAMP_HEADER) else {
+        return false;
+    };
+    let fresh = timestamp
+        .parse::<i64>()
+        .is_ok_and(|at| (Utc::now().timestamp() - at).abs() <= MAX_SIGNATURE_AGE_SECS);
+    if !fresh {
+        warn!("Rejected webhook signature with stale or invalid timestamp");
+        return false;
+    }
+    match sign(&signed_payload(timestamp, body), secret) {
+        Ok(expected) => constant_time_eq(
+            provided.to_ascii_lowercase().as_bytes(),
+            expected.as_bytes(),
+        ),
+        Err(e) => {
+            error!("Failed to verify webhook signature: {:#}", e);
+            false
+        }
+    }
+}
+
+/// What a webhook signature covers: `<timestamp>.<body>`
+fn signed_payload(timestamp: &str, body: &[u8]) -> Vec<u8> {
+    let mut payload = format!("{}.", timestamp).into_bytes();
+    payload.extend_from_slice(body);
+    payload
+}
+
+/// Hex-encoded HMAC-SHA256 of `body`
+fn sign(body: &[u8], secret: &SecretString) -> Result<String> {
+    let key = PKey::hmac(secret.expose_secret().as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(body)?;
+    Ok(signer
+        .sign_to_vec()?
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+fn unauthorized() -> ApiError {
+    ApiError(
+        StatusCode::UNAUTHORIZED,
+        "Missing or invalid bearer token".to_string(),
+    )
+}
+
+async fn authenticate(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let principal = principal(request.headers(), &state).ok_or_else(unauthorized)?;
+    request.extensions_mut().insert(principal);
+    Ok(next.run(request).await)
+}
+
+async fn status(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Arc<Principal>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    principal.require(Role::ReadOnly)?;
+    let stats = state
+        .stats
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    let retry_queue: Vec<ScheduledRetry> = state
+        .retries
+        .entries()
+        .into_iter()
+        .filter(|retry| principal.filter.matches(&retry.path))
+        .collect();
+    // Keys whose data may still be encrypted with their previous value
+    let reencryption = if state.rotator.hooks().reencrypt.is_empty() {
+        None
+    } else {
+        let unfinished = hooks::unfinished_reencryptions(state.rotator.backend(), "")
+            .await
+            .map_err(ApiError::internal)?;
+        let unfinished: BTreeMap<_, _> = unfinished
+            .into_iter()
+            .filter(|(path, _)| principal.filter.matches(path))
+            .collect();
+        Some(unfinished)
+    };
+    Ok(Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "backend": state.rotator.backend().backend_type(),
+        "target": state.rotator.target().map(|t| t.target_type()),
+        "started_at": state.started_at,
+        "uptime_seconds": (Utc::now() - state.started_at).num_seconds(),
+        "rotations": stats.rotations,
+        "failures": stats.failures,
+        "refused": stats.refused,
+        "last_rotation": stats.last_rotation,
+        "leader": state.leader.as_ref().map(|leader| *leader.borrow()),
+        "reencryption": reencryption,
+        "retry_queue": retry_queue,
+    })))
+}
+
+/// Counters in the Prometheus text format
+async fn metrics(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Arc<Principal>>,
+) -> Result<Response, ApiError> {
+    principal.require(Role::ReadOnly)?;
+    let stats = state
+        .stats
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    let retries = state.retries.counts();
+    let mut body = String::new();
+    for (name, kind, help, value) in [
+        (
+            "asr_rotations_total",
+            "counter",
+            "Rotations completed by the server",
+            stats.rotations,
+        ),
+        (
+            "asr_rotation_failures_total",
+            "counter",
+            "Failed rotation attempts, retries included",
+            stats.failures,
+        ),
+        (
+            "asr_rotations_refused_total",
+            "counter",
+            "Rotations refused by an open circuit breaker",
+            stats.refused,
+        ),
+        (
+            "asr_retry_queue_size",
+            "gauge",
+            "Failed rotations waiting for a retry",
+            retries.queued as u64,
+        ),
+        (
+            "asr_retries_total",
+            "counter",
+            "Retries of failed rotations started",
+            retries.retried,
+        ),
+        (
+            "asr_retries_exhausted_total",
+            "counter",
+            "Secrets given up on after their last retry failed",
+            retries.exhausted,
+        ),
+    ] {
+        body.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+        ));
+    }
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response())
+}
+
+/// Secrets due for rotation, limited to the ones the token may access
+async fn scan(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Arc<Principal>>,
+    Query(query): Query<ScanQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    principal.require(Role::ReadOnly)?;
+    let secrets = state
+        .rotator
+        .scan(&query.path)
+        .await
+        .map_err(ApiError::internal)?;
+    let secrets = principal.filter.apply(secrets);
+    let failing = state.rotator.rotation_failures(&secrets).await;
+    Ok(Json(
+        serde_json::json!({ "secrets": secrets, "failing": failing }),
+    ))
+}
+
+async fn rotate(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Arc<Principal>>,
+    Path(path): Path<String>,
+    body: Result<Json<RotateRequest>, JsonRejection>,
+) -> Result<Json<RotateResponse>, ApiError> {
+    principal.require(Role::Rotate)?;
+    validate_path(&path)?;
+    principal.require_path(&path)?;
+    state.require_leader()?;
+    let request = match body {
+        Ok(Json(request)) => request,
+        // The body is optional; requests without one rotate with the defaults
+        Err(JsonRejection::MissingJsonContentType(_)) => RotateRequest::default(),
+        Err(e) => {
+            return Err(ApiError(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid rotation request: {}", e.body_text()),
+            ))
+        }
+    };
+    info!(
+        "Rotation of {} requested over the API by {}",
+        path, principal.name
+    );
+    rotate_now(&state, path, request.target_username).await
+}
+
+/// Report that the application finished re-encrypting the data under a
+/// data-encryption key
+async fn complete_reencryption(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Arc<Principal>>,
+    Path(path): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    principal.require(Role::Rotate)?;
+    validate_path(&path)?;
+    principal.require_path(&path)?;
+    info!(
+        "Re-encryption for {} reported complete over the API by {}",
+        path, principal.name
+    );
+    state
+        .rotator
+        .complete_reencryption(&path)
+        .await
+        .map_err(|e| ApiError(StatusCode::CONFLICT, redact::redact(&format!("{:#}", e))))?;
+    Ok(Json(
+        serde_json::json!({ "path": path, "reencryption": "complete" }),
+    ))
+}
+
+async fn list_tokens(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Arc<Principal>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    principal.require(Role::Admin)?;
+    let tokens: Vec<_> = state
+        .tokens
+        .iter()
+        .map(|(_, p)| serde_json::json!({ "name": p.name, "role": p.role, "paths": p.paths }))
+        .collect();
+    Ok(Json(serde_json::json!({ "tokens": tokens })))
+}
+
+async fn webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<RotateResponse>, ApiError> {
+    let signed = state
+        .webhook_secret
+        .as_ref()
+        .is_some_and(|secret| has_signature(&headers, &body, secret));
+    // Signed requests may rotate any secret; token holders only their own
+    let principal = if signed {
+        None
+    } else {
+        let principal = principal(&headers, &state).ok_or_else(unauthorized)?;
+        principal.require(Role::Rotate)?;
+        Some(principal)
+    };
+
+    let request: WebhookRequest = serde_json::from_slice(&body).map_err(|e| {
+        ApiError(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid webhook payload: {}", e),
+        )
+    })?;
+    validate_path(&request.path)?;
+    if let Some(principal) = principal {
+        principal.require_path(&request.path)?;
+    }
+    state.require_leader()?;
+    let source = request.source.as_deref().unwrap_or("unknown source");
+    let reason = request.reason.as_deref().unwrap_or("no reason given");
+    warn!(
+        "Out-of-cycle rotation of {} requested by {}: {}",
+        request.path, source, reason
+    );
+    let detail = format!("rotation requested by {}: {}", source, reason);
+    let path = request.path.clone();
+
+    let result = rotate_now(&state, request.path, request.target_username).await;
+    let outcome = match result {
+        Ok(_) => Outcome::Success,
+        Err(_) => Outcome::Failure,
+    };
+    state.rotator.audit().record(
+        &AuditEvent::new(
+            "webhook",
+            &path,
+            state.rotator.backend().backend_type(),
+            outcome,
+        )
+        .with_detail(detail),
+    );
+    result
+}
+
+/// Rotate a secret for an API request and count the outcome
+async fn rotate_now(
+    state: &AppState,
+    path: String,
+    target_username: Option<String>,
+) -> Result<Json<RotateResponse>, ApiError> {
+    // A secret that does not exist is not a failed rotation to count or retry
+    // A failed listing is left for the rotation itself to report
+    if !is_listed(state.rotator.backend(), &path)
+        .await
+        .unwrap_or(true)
+    {
+        return Err(ApiError(
+            StatusCode::NOT_FOUND,
+            format!("Secret {} not found", path),
+        ));
+    }
+    match rotate_counted(state, &path, target_username.as_deref()).await {
+        Ok(rotated) => Ok(Json(RotateResponse {
+            path,
+            target_username: rotated.target_username,
+            hook_failures: rotated.hook_failures,
+        })),
+        Err(e) => Err(ApiError::internal(e)),
+    }
+}
+
+/// Rotate a secret, counting the outcome and scheduling a retry on failure
+async fn rotate_counted(
+    state: &AppState,
+    path: &str,
+    target_username: Option<&str>,
+) -> Result<RotatedSecret> {
+    let result = match target_username {
+        Some(username) => state.rotator.rotate_with_target(path, username).await,
+        None => state.rotator.rotate(path).await,
+    };
+
+    let mut stats = state
+        .stats
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match &result {
+        Ok(_) => {
+            stats.rotations += 1;
+            stats.last_rotation = Some(LastRotation {
+                path: path.to_string(),
+                at: Utc::now(),
+            });
+            state.retries.succeeded(path);
+        }
+        Err(e) if e.downcast_ref::<CircuitOpen>().is_some() => {
+            stats.refused += 1;
+            warn!("Rotation of {} refused: {:#}", path, e);
+            // Not an attempt; a scheduled retry waits another interval
+            if let Some(retry) = state.retries.postpone(path) {
+                info!(
+                    "Retrying {} at {}",
+                    path,
+                    retry.next_attempt_at.to_rfc3339()
+                );
+            }
+        }
+        Err(e) if !retry::is_transient(e) => {
+            stats.failures += 1;
+            let detail = redact::redact(&format!("{:#}", e));
+            error!("Rotation of {} failed: {}", path, detail);
+            if state.retries.abandon(path) || state.retries.policy().attempts > 0 {
+                info!("Not retrying {}; the failure is not transient", path);
+            }
+        }
+        Err(e) => {
+            stats.failures += 1;
+            let detail = redact::redact(&format!("{:#}", e));
+            error!("Rotation of {} failed: {}", path, detail);
+            match state.retries.failed(path, target_username, &detail) {
+                Some(retry) => info!(
+                    "Retrying {} at {} (retry {} of {})",
+                    path,
+                    retry.next_attempt_at.to_rfc3339(),
+                    retry.failures,
+                    state.retries.policy().attempts
+                ),
+                None if state.retries.policy().attempts > 0 => error!(
+                    "Giving up on {} after {} retries",
+                    path,
+                    state.retries.policy().attempts
+                ),
+                None => {}
+            }
+        }
+    }
+    result
+}
+
+/// Run scheduled retries of failed rotations as they fall due
+async fn retry_failed_rotations(state: Arc<AppState>) {
+    loop {
+        match state.retries.next_due() {
+            Some(due) => {
+                let wait = (due - Utc::now()).to_std().unwrap_or_default();
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    // An earlier retry may have been scheduled meanwhile
+                    _ = state.retries.changed() => continue,
+                }
+            }
+            None => {
+                state.retries.changed().await;
+                continue;
+            }
+        }
+
+        if state.require_leader().is_err() {
+            let dropped = state.retries.clear();
+            warn!(
+                "No longer the leader; dropping {} scheduled retries",
+                dropped
+            );
+            continue;
+        }
+        for retry in state.retries.take_due(Utc::now()) {
+            info!(
+                "Retrying rotation of {} after {} failure(s)",
+                retry.path, retry.failures
+            );
+            // The outcome is counted and rescheduled by rotate_counted
+            let _ = rotate_counted(&state, &retry.path, retry.target_username.as_deref()).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::{MemoryBackend, Operation, SecretBackend};
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    fn test_router() -> Router {
+        let backend = MemoryBackend::new()
+            .with_secret("app/db", [("password", "old")])
+            .with_secret("payments/db", [("password", "old")]);
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+        let tokens = toml::from_str::<ServerTokens>(
+            r#"
+            [[tokens]]
+            name = "viewer"
+            token = "view-token"
+            role = "read-only"
+
+            [[tokens]]
+            name = "payments"
+            token = "pay-token"
+            role = "rotate"
+            paths = ["payments/**"]
+            "#,
+        )
+        .unwrap()
+        .tokens;
+        let tokens = [vec![ApiTokenConfig::admin("s3cret".into())], tokens].concat();
+        router(
+            rotator,
+            tokens,
+            Some("hook-key".into()),
+            None,
+            RetryPolicy::disabled(),
+        )
+        .unwrap()
+    }
+
+    #[derive(Deserialize)]
+    struct ServerTokens {
+        tokens: Vec<ApiTokenConfig>,
+    }
+
+    async fn send(router: &Router, method: &str, uri: &str, token: Option<&str>) -> (u16, String) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let response = router
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status().as_u16();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_auth_and_rotate() {
+        let router = test_router();
+
+        assert_eq!(send(&router, "GET", "/healthz", None).await.0, 200);
+        assert_eq!(send(&router, "GET", "/status", None).await.0, 401);
+        assert_eq!(send(&router, "GET", "/scan", Some("wrong")).await.0, 401);
+
+        let (status, body) = send(&router, "POST", "/rotate/app/db", Some("s3cret")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert!(body.contains("\"path\":\"app/db\""));
+        assert!(!body.contains("old"));
+
+        let (status, body) = send(&router, "POST", "/rotate/missing", Some("s3cret")).await;
+        assert_eq!(status, 404);
+        assert!(body.contains("error"));
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/rotate/app/db")
+            .header(header::AUTHORIZATION, "Bearer s3cret")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{\"target_username\": "))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let (status, body) = send(&router, "GET", "/status", Some("s3cret")).await;
+        assert_eq!(status, 200);
+        let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(status["backend"], "memory");
+        assert_eq!(status["rotations"], 1);
+        assert_eq!(status["failures"], 0);
+        assert_eq!(status["last_rotation"]["path"], "app/db");
+    }
+
+    #[tokio::test]
+    async fn test_failed_rotation_retried() {
+        let backend = MemoryBackend::new()
+            .with_secret("app/db", [("password", "old")])
+            .with_secret("app/bad", [("password", "old")]);
+        backend
+            .update_metadata(
+                "app/bad",
+                [("rotation_strategy".to_string(), "swap".to_string())].into(),
+            )
+            .await
+            .unwrap();
+        backend.fail_next(Operation::Write, "backend unavailable");
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+        let retry = RetryPolicy {
+            attempts: 3,
+            base: std::time::Duration::from_millis(200),
+            max: std::time::Duration::from_secs(1),
+        };
+        let tokens = vec![ApiTokenConfig::admin("s3cret".into())];
+        let router = router(rotator, tokens, None, None, retry).unwrap();
+
+        // Invalid settings fail the same way every time and are not retried
+        let (status, _) = send(&router, "POST", "/rotate/app/bad", Some("s3cret")).await;
+        assert_eq!(status, 500);
+        let (status, _) = send(&router, "POST", "/rotate/app/db", Some("s3cret")).await;
+        assert_eq!(status, 500);
+        let (_, body) = send(&router, "GET", "/status", Some("s3cret")).await;
+        let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(status["retry_queue"].as_array().unwrap().len(), 1);
+        assert_eq!(status["retry_queue"][0]["path"], "app/db");
+        assert_eq!(status["retry_queue"][0]["failures"], 1);
+        assert!(status["retry_queue"][0]["last_error"]
+            .as_str()
+            .unwrap()
+            .contains("backend unavailable"));
+
+        // The backend has recovered by the time the retry runs
+        let mut metrics = String::new();
+        for _ in 0..50 {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            metrics = send(&router, "GET", "/metrics", Some("s3cret")).await.1;
+            if metrics.contains("asr_rotations_total 1") {
+                break;
+            }
+        }
+        assert!(metrics.contains("asr_rotations_total 1"), "{}", metrics);
+        assert!(metrics.contains("asr_rotation_failures_total 2"));
+        assert!(metrics.contains("asr_retries_total 1"));
+        assert!(metrics.contains("asr_retry_queue_size 0"));
+        assert!(metrics.contains("# TYPE asr_retry_queue_size gauge"));
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_refusals_counted_apart() {
+        use crate::config::RotationConfig;
+        use crate::targets::MockTarget;
+
+        let target = MockTarget::new();
+        target.fail_update(Some("admin password expired"));
+        let rotator = Rotator::builder()
+            .backend(Box::new(
+                MemoryBackend::new().with_secret("app/db", [("password", "old")]),
+            ))
+            .target(Box::new(target))
+            .policy(RotationConfig {
+                circuit_breaker_threshold: 1,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let tokens = vec![ApiTokenConfig::admin("s3cret".into())];
+        let router = router(rotator, tokens, None, None, RetryPolicy::disabled()).unwrap();
+
+        for _ in 0..2 {
+            let request = Request::builder()
+                .method("POST")
+                .uri("/rotate/app/db")
+                .header(header::AUTHORIZATION, "Bearer s3cret")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"target_username": "app"}"#))
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert!(!response.status().is_success());
+        }
+        let (_, body) = send(&router, "GET", "/status", Some("s3cret")).await;
+        let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(status["failures"], 1);
+        assert_eq!(status["refused"], 1);
+        let metrics = send(&router, "GET", "/metrics", Some("s3cret")).await.1;
+        assert!(metrics.contains("asr_rotation_failures_total 1"));
+        assert!(metrics.contains("asr_rotations_refused_total 1"));
+    }
+
+    #[tokio::test]
+    async fn test_roles_and_paths() {
+        let router = test_router();
+
+        assert_eq!(
+            send(&router, "GET", "/status", Some("view-token")).await.0,
+            200
+        );
+        assert_eq!(
+            send(&router, "GET", "/scan", Some("view-token")).await.0,
+            200
+        );
+        let (status, body) = send(&router, "POST", "/rotate/app/db", Some("view-token")).await;
+        assert_eq!(status, 403);
+        assert!(body.contains("rotate is required"), "{}", body);
+
+        assert_eq!(
+            send(&router, "POST", "/rotate/app/db", Some("pay-token"))
+                .await
+                .0,
+            403
+        );
+        let (status, body) = send(&router, "POST", "/rotate/payments/db", Some("pay-token")).await;
+        assert_eq!(status, 200, "{}", body);
+        // Traversal out of the token's paths is refused before the glob check
+        for path in [
+            "payments/../app/db",
+            "payments/%2E%2E/app/db",
+            "payments//db",
+        ] {
+            let uri = format!("/rotate/{}", path);
+            let (status, body) = send(&router, "POST", &uri, Some("pay-token")).await;
+            assert_eq!(status, 400, "{}: {}", path, body);
+        }
+
+        assert_eq!(
+            send(&router, "GET", "/tokens", Some("pay-token")).await.0,
+            403
+        );
+        let (status, body) = send(&router, "GET", "/tokens", Some("s3cret")).await;
+        assert_eq!(status, 200);
+        assert!(body.contains("\"payments/**\""));
+        assert!(!body.contains("pay-token"));
+
+        let body = r#"{"path": "app/db"}"#;
+        let request = Request::builder()
+            .method("POST")
+            .uri("/webhook")
+            .header(header::AUTHORIZATION, "Bearer pay-token")
+            .body(Body::from(body))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status().as_u16(), 403);
+
+        assert!(router_with_tokens(Vec::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_standby_replica_does_not_rotate() {
+        let backend = MemoryBackend::new().with_secret("app/db", [("password", "old")]);
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+        let (leader, is_leader) = watch::channel(false);
+        let tokens = vec![ApiTokenConfig::admin("s3cret".into())];
+        let router = router(
+            rotator,
+            tokens,
+            None,
+            Some(is_leader),
+            RetryPolicy::disabled(),
+        )
+        .unwrap();
+
+        let (status, body) = send(&router, "POST", "/rotate/app/db", Some("s3cret")).await;
+        assert_eq!(status, 503, "{}", body);
+        let (_, body) = send(&router, "GET", "/status", Some("s3cret")).await;
+        assert!(body.contains("\"leader\":false"), "{}", body);
+
+        leader.send_replace(true);
+        assert_eq!(
+            send(&router, "POST", "/rotate/app/db", Some("s3cret"))
+                .await
+                .0,
+            200
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reencryption_status_and_completion() {
+        let backend = MemoryBackend::new().with_secret("keys/dek", [("key", "k1")]);
+        backend
+            .update_metadata(
+                "keys/dek",
+                HashMap::from([
+                    ("rotation_enabled".to_string(), "true".to_string()),
+                    ("reencryption".to_string(), "pending".to_string()),
+                ]),
+            )
+            .await
+            .unwrap();
+        let hooks: hooks::HooksConfig = toml::from_str(
+            r#"
+            [[reencrypt]]
+            paths = ["keys/*"]
+            command = ["true"]
+            "#,
+        )
+        .unwrap();
+        let rotator = Rotator::builder()
+            .backend(Box::new(backend))
+            .hooks(hooks)
+            .build()
+            .unwrap();
+        let tokens = vec![ApiTokenConfig::admin("s3cret".into())];
+        let router = router(rotator, tokens, None, None, RetryPolicy::disabled()).unwrap();
+
+        let (_, body) = send(&router, "GET", "/status", Some("s3cret")).await;
+        assert!(
+            body.contains(r#""reencryption":{"keys/dek":"pending"}"#),
+            "{}",
+            body
+        );
+        let (status, body) = send(&router, "POST", "/reencrypt/keys/dek", Some("s3cret")).await;
+        assert_eq!(status, 200, "{}", body);
+        let (_, body) = send(&router, "GET", "/status", Some("s3cret")).await;
+        assert!(body.contains(r#""reencryption":{}"#), "{}", body);
+        let (status, _) = send(&router, "POST", "/reencrypt/keys/dek", Some("s3cret")).await;
+        assert_eq!(status, 409);
+    }
+
+    fn router_with_tokens(tokens: Vec<ApiTokenConfig>) -> Result<Router> {
+        let rotator = Rotator::builder()
+            .backend(Box::new(MemoryBackend::new()))
+            .build()?;
+        router(rotator, tokens, None, None, RetryPolicy::disabled())
+    }
+
+    /// Send `body` to `/webhook`, signed with `key` at `timestamp` if given
+    async fn send_webhook(
+        router: &Router,
+        body: &str,
+        signed: Option<(&str, i64)>,
+    ) -> (u16, String) {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/webhook")
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some((key, timestamp)) = signed {
+            let timestamp = timestamp.to_string();
+            let signature =
+                sign(&signed_payload(&timestamp, body.as_bytes()), &key.into()).unwrap();
+            request = request
+                .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+                .header(TIMESTAMP_HEADER, timestamp);
+        }
+        let response = router
+            .clone()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status().as_u16();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_webhook_rotation() {
+        let router = test_router();
+        let body = r#"{"path": "app/db", "source": "gitleaks", "reason": "found in a commit"}"#;
+
+        let now = Utc::now().timestamp();
+        assert_eq!(send_webhook(&router, body, None).await.0, 401);
+        let forged = Some(("other", now));
+        assert_eq!(send_webhook(&router, body, forged).await.0, 401);
+        // A captured request can't be replayed once its timestamp is stale
+        let stale = Some(("hook-key", now - MAX_SIGNATURE_AGE_SECS - 60));
+        assert_eq!(send_webhook(&router, body, stale).await.0, 401);
+
+        let (status, response) = send_webhook(&router, body, Some(("hook-key", now))).await;
+        assert_eq!(status, 200, "{}", response);
+        assert!(response.contains("\"path\":\"app/db\""));
+
+        assert_eq!(
+            send_webhook(&router, "{}", Some(("hook-key", now))).await.0,
+            400
+        );
+        // Signed payloads get the same path check as token holders
+        let traversal = r#"{"path": "app/../app/db"}"#;
+        let (status, response) = send_webhook(&router, traversal, Some(("hook-key", now))).await;
+        assert_eq!(status, 400, "{}", response);
+        assert!(response.contains("Invalid secret path"));
+
+        let (_, body) = send(&router, "GET", "/status", Some("s3cret")).await;
+        let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(status["rotations"], 1);
+    }
+}
@@ -0,0 +1,345 @@
+//! In-place updates of values inside JSON, YAML, and TOML config files
+//!
+//! Many applications read credentials from files like `config.yaml` or
+//! `appsettings.json` rather than environment variables. Values are addressed
+//! either with a dotted path (`database.password`) or a JSON pointer
+//! (`/database/password`); numeric segments index into arrays.
+//!
+//! TOML files are edited with `toml_edit`, so comments and formatting are kept.
+//! JSON keeps key order but is re-indented. YAML keeps key order but loses comments.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+/// Supported structured file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum StructuredFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl StructuredFormat {
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+
+        extension.parse().map_err(|e: String| {
+            anyhow::anyhow!(
+                "Cannot detect config format of {} from its extension: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+}
+
+impl std::str::FromStr for StructuredFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(StructuredFormat::Json),
+            "yaml" | "yml" => Ok(StructuredFormat::Yaml),
+            "toml" => Ok(StructuredFormat::Toml),
+            _ => Err(format!(
+                "Unknown config file format: {}. Supported: json, yaml, toml",
+                s
+            )),
+        }
+    }
+}
+
+/// Split a dotted path or JSON pointer into its segments
+pub fn parse_key_path(key_path: &str) -> Result<Vec<String>> {
+    let segments: Vec<String> = if let Some(pointer) = key_path.strip_prefix('/') {
+        pointer
+            .split('/')
+            .map(|s| s.replace("~1", "/").replace("~0", "~"))
+            .collect()
+    } else {
+        key_path.split('.').map(str::to_string).collect()
+    };
+
+    if segments.is_empty() || segments.iter().any(|s| s.is_empty()) {
+        anyhow::bail!("Invalid key path: '{}'", key_path);
+    }
+
+    Ok(segments)
+}
+
+/// Set a string value at `key_path` inside a structured file, creating missing tables
+pub fn set_value_in_file(
+    path: &Path,
+    format: Option<StructuredFormat>,
+    key_path: &str,
+    value: &str,
+) -> Result<()> {
+    let format = match format {
+        Some(format) => format,
+        None => StructuredFormat::from_path(path)?,
+    };
+    let segments = parse_key_path(key_path)?;
+
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let updated = set_value(&content, format, &segments, value)
+        .with_context(|| format!("Failed to set '{}' in {}", key_path, path.display()))?;
+
+    fs::write(path, updated).with_context(|| format!("Failed to write to {}", path.display()))?;
+
+    info!("Updated '{}' in {}", key_path, path.display());
+    Ok(())
+}
+
+/// Set a string value at the given path segments inside structured content
+pub fn set_value(
+    content: &str,
+    format: StructuredFormat,
+    segments: &[String],
+    value: &str,
+) -> Result<String> {
+    match format {
+        StructuredFormat::Json => {
+            let mut document: serde_json::Value = if content.trim().is_empty() {
+                serde_json::Value::Object(Default::default())
+            } else {
+                serde_json::from_str(content).context("Failed to parse JSON")?
+            };
+            set_json(&mut document, segments, value)?;
+
+            let mut output =
+                serde_json::to_string_pretty(&document).context("Failed to serialize JSON")?;
+            output.push('\n');
+            Ok(output)
+        }
+        StructuredFormat::Yaml => {
+            let mut document: serde_yaml::Value = if content.trim().is_empty() {
+                serde_yaml::Value::Mapping(Default::default())
+            } else {
+                serde_yaml::from_str(content).context("Failed to parse YAML")?
+            };
+            set_yaml(&mut document, segments, value)?;
+            serde_yaml::to_string(&document).context("Failed to serialize YAML")
+        }
+        StructuredFormat::Toml => {
+            let mut document: toml_edit::DocumentMut =
+                content.parse().context("Failed to parse TOML")?;
+            set_toml(document.as_item_mut(), segments, value)?;
+            Ok(document.to_string())
+        }
+    }
+}
+
+fn set_json(node: &mut serde_json::Value, segments: &[String], value: &str) -> Result<()> {
+    let (segment, rest) = segments
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("Empty key path"))?;
+
+    let child = match node {
+        serde_json::Value::Object(map) => map
+            .entry(segment.clone())
+            .or_insert_with(|| serde_json::Value::Object(Default::default())),
+        serde_json::Value::Array(items) => {
+            let index = parse_index(segment, items.len())?;
+            &mut items[index]
+        }
+        _ => anyhow::bail!("Cannot descend into non-container value at '{}'", segment),
+    };
+
+    if rest.is_empty() {
+        *child = serde_json::Value::String(value.to_string());
+        Ok(())
+    } else {
+        set_json(child, rest, value)
+    }
+}
+
+fn set_yaml(node: &mut serde_yaml::Value, segments: &[String], value: &str) -> Result<()> {
+    let (segment, rest) = segments
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("Empty key path"))?;
+
+    let child = match node {
+        serde_yaml::Value::Mapping(map) => {
+            let key = serde_yaml::Value::String(segment.clone());
+            if !map.contains_key(&key) {
+                map.insert(key.clone(), serde_yaml::Value::Mapping(Default::default()));
+            }
+            map.get_mut(&key).expect("key inserted above")
+        }
+        serde_yaml::Value::Sequence(items) => {
+            let index = parse_index(segment, items.len())?;
+            &mut items[index]
+        }
+        _ => anyhow::bail!("Cannot descend into non-container value at '{}'", segment),
+    };
+
+    if rest.is_empty() {
+        *child = serde_yaml::Value::String(value.to_string());
+        Ok(())
+    } else {
+        set_yaml(child, rest, value)
+    }
+}
+
+fn set_toml(node: &mut toml_edit::Item, segments: &[String], value: &str) -> Result<()> {
+    let (segment, rest) = segments
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("Empty key path"))?;
+
+    let child = if let Some(table) = node.as_table_like_mut() {
+        if table.get(segment).is_none() {
+            table.insert(segment, toml_edit::Item::Table(toml_edit::Table::new()));
+        }
+        table.get_mut(segment).expect("key inserted above")
+    } else if let Some(array) = node.as_array_of_tables_mut() {
+        let index = parse_index(segment, array.len())?;
+        let table = array.get_mut(index).expect("index checked above");
+        return set_toml_in_table(table, rest, value);
+    } else {
+        anyhow::bail!("Cannot descend into non-table value at '{}'", segment);
+    };
+
+    if rest.is_empty() {
+        // Keep the existing decoration (comments, spacing) of replaced values
+        let decor = child.as_value().map(|v| v.decor().clone());
+        let mut new_value = toml_edit::Value::from(value);
+        if let Some(decor) = decor {
+            *new_value.decor_mut() = decor;
+        }
+        *child = toml_edit::Item::Value(new_value);
+        Ok(())
+    } else {
+        set_toml(child, rest, value)
+    }
+}
+
+fn set_toml_in_table(table: &mut toml_edit::Table, segments: &[String], value: &str) -> Result<()> {
+    let mut item = toml_edit::Item::Table(std::mem::take(table));
+    let result = set_toml(&mut item, segments, value);
+    if let toml_edit::Item::Table(updated) = item {
+        *table = updated;
+    }
+    result
+}
+
+fn parse_index(segment: &str, len: usize) -> Result<usize> {
+    let index: usize = segment
+        .parse()
+        .with_context(|| format!("Expected array index, found '{}'", segment))?;
+    if index >= len {
+        anyhow::bail!("Array index {} out of bounds (length {})", index, len);
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn segments(path: &str) -> Vec<String> {
+        parse_key_path(path).unwrap()
+    }
+
+    #[test]
+    fn test_parse_key_path() {
+        assert_eq!(segments("database.password"), vec!["database", "password"]);
+        assert_eq!(
+            segments("/database/pass~1word"),
+            vec!["database", "pass/word"]
+        );
+        assert!(parse_key_path("database..password").is_err());
+    }
+
+    #[test]
+    fn test_set_json_preserves_order() -> Result<()> {
+        let content =
+            r#"{"name": "app", "database": {"host": "db", "password": "old"}, "port": 80}"#;
+        let updated = set_value(
+            content,
+            StructuredFormat::Json,
+            &segments("database.password"),
+            "new",
+        )?;
+
+        let parsed: serde_json::Value = serde_json::from_str(&updated)?;
+        assert_eq!(parsed["database"]["password"], "new");
+        assert!(updated.find("\"name\"").unwrap() < updated.find("\"port\"").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_yaml_creates_missing_keys() -> Result<()> {
+        let content = "app:\n  name: demo\n";
+        let updated = set_value(
+            content,
+            StructuredFormat::Yaml,
+            &segments("/app/credentials/token"),
+            "abc",
+        )?;
+
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&updated)?;
+        assert_eq!(parsed["app"]["credentials"]["token"], "abc");
+        assert_eq!(parsed["app"]["name"], "demo");
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_toml_preserves_comments() -> Result<()> {
+        let content = "# App config\n[database]\nhost = \"db\" # primary\npassword = \"old\"\n";
+        let updated = set_value(
+            content,
+            StructuredFormat::Toml,
+            &segments("database.password"),
+            "new",
+        )?;
+
+        assert_eq!(
+            updated,
+            "# App config\n[database]\nhost = \"db\" # primary\npassword = \"new\"\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_json_array_index() -> Result<()> {
+        let content = r#"{"servers": [{"password": "a"}, {"password": "b"}]}"#;
+        let updated = set_value(
+            content,
+            StructuredFormat::Json,
+            &segments("servers.1.password"),
+            "c",
+        )?;
+
+        let parsed: serde_json::Value = serde_json::from_str(&updated)?;
+        assert_eq!(parsed["servers"][1]["password"], "c");
+        assert_eq!(parsed["servers"][0]["password"], "a");
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_value_in_file_detects_format() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("appsettings.json");
+        fs::write(&path, r#"{"ConnectionStrings": {"Password": "old"}}"#)?;
+
+        set_value_in_file(&path, None, "ConnectionStrings.Password", "new")?;
+
+        let parsed: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        assert_eq!(parsed["ConnectionStrings"]["Password"], "new");
+
+        let unknown = temp_dir.path().join("settings.ini");
+        fs::write(&unknown, "")?;
+        assert!(set_value_in_file(&unknown, None, "a.b", "c").is_err());
+        Ok(())
+    }
+}
@@ -1,16 +1,24 @@
 use anyhow::{Context, Result};
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Certificate, Client, Identity, StatusCode};
 use serde_json::json;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::{debug, info};
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
-use crate::config::ApiTargetConfig;
+use super::oauth2::TokenSource;
+use crate::config::{ApiBodyFormat, ApiTargetConfig};
+use crate::password_policy::PasswordPolicy;
+use crate::redact;
 use crate::targets::target::Target;
 
 /// API-based target for password updates via REST API
 pub struct ApiTarget {
     config: Arc<ApiTargetConfig>,
     client: Client,
+    oauth2: Option<TokenSource>,
 }
 
 impl ApiTarget {
@@ -18,15 +26,94 @@ impl ApiTarget {
     pub async fn new(config: &ApiTargetConfig) -> Result<Self> {
         info!("Creating API target for: {}", config.base_url);
 
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(config.timeout_seconds))
-            .build()
-            .context("Failed to create HTTP client")?;
+        let client = build_client(config)?;
 
-        Ok(Self {
+        let oauth2 = config
+            .oauth2
+            .as_ref()
+            .map(|oauth2| TokenSource::new(oauth2, client.clone()));
+
+        let target = Self {
             config: Arc::new(config.clone()),
             client,
-        })
+            oauth2,
+        };
+
+        // Catch template mistakes now rather than in the middle of a rotation
+        target
+            .render_body("username", "password")
+            .context("Invalid API request body configuration")?;
+
+        Ok(target)
+    }
+
+    /// Build the request body for a password update
+    pub(crate) fn render_body(&self, username: &str, password: &str) -> Result<RequestBody> {
+        let format = self.config.body_format;
+
+        if let Some(ref template) = self.config.body_template {
+            let rendered = match format {
+                ApiBodyFormat::Json => render_template(template, username, password, |value| {
+                    // Placeholders sit inside JSON strings, so drop the quotes
+                    let quoted = serde_json::Value::from(value).to_string();
+                    quoted[1..quoted.len() - 1].to_string()
+                }),
+                ApiBodyFormat::Form => render_template(template, username, password, |value| {
+                    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+                }),
+                ApiBodyFormat::Raw => {
+                    render_template(template, username, password, |value| value.to_string())
+                }
+            };
+
+            return Ok(match format {
+                ApiBodyFormat::Json => RequestBody::Json(
+                    serde_json::from_str(&rendered).context("body_template is not valid JSON")?,
+                ),
+                ApiBodyFormat::Form => RequestBody::form(rendered),
+                ApiBodyFormat::Raw => RequestBody::Raw {
+                    body: rendered,
+                    content_type: self
+                        .config
+                        .content_type
+                        .clone()
+                        .unwrap_or_else(|| "text/plain".to_string()),
+                },
+            });
+        }
+
+        let mut fields = Vec::new();
+
+        // Set username field
+        if let Some(ref username_field) = self.config.username_field {
+            fields.push((username_field.clone(), username.to_string()));
+        }
+
+        // Set password field
+        fields.push((self.config.password_field.clone(), password.to_string()));
+
+        // Add any additional fields from config
+        if let Some(ref additional_fields) = self.config.additional_fields {
+            for (key, value) in additional_fields {
+                fields.push((key.clone(), value.clone()));
+            }
+        }
+
+        match format {
+            ApiBodyFormat::Json => {
+                let mut body = json!({});
+                for (key, value) in fields {
+                    body[key] = json!(value);
+                }
+                Ok(RequestBody::Json(body))
+            }
+            ApiBodyFormat::Form => Ok(RequestBody::form(
+                url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(fields)
+                    .finish(),
+            )),
+            ApiBodyFormat::Raw => anyhow::bail!("body_format = \"raw\" requires a body_template"),
+        }
     }
 
     /// Build the full URL for password update endpoint
@@ -44,6 +131,136 @@ impl ApiTarget {
             )
         }
     }
+
+    /// Delay before retry number `attempt` (starting at 1), preferring the
+    /// API's `Retry-After` header over exponential backoff
+    fn retry_delay(&self, attempt: u32, headers: Option<&HeaderMap>) -> Duration {
+        headers
+            .and_then(retry_after)
+            .unwrap_or_else(|| {
+                Duration::from_millis(self.config.retry_delay_ms)
+                    .saturating_mul(2u32.saturating_pow(attempt - 1))
+            })
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+/// HTTP client with the target's timeout and TLS settings
+fn build_client(config: &ApiTargetConfig) -> Result<Client> {
+    let read = |path: &PathBuf| {
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+    };
+
+    let mut builder =
+        crate::http::client_builder().timeout(Duration::from_secs(config.timeout_seconds));
+
+    if let Some(ref ca_cert) = config.ca_cert {
+        let certificates = Certificate::from_pem_bundle(&read(ca_cert)?)
+            .with_context(|| format!("Invalid CA certificate in {}", ca_cert.display()))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            let identity =
+                Identity::from_pkcs8_pem(&read(cert)?, &read(key)?).with_context(|| {
+                    format!(
+                        "Invalid client certificate {} or key {} (the key must be PKCS#8 PEM)",
+                        cert.display(),
+                        key.display()
+                    )
+                })?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => anyhow::bail!("client_cert and client_key must be set together"),
+    }
+
+    builder.build().context("Failed to create HTTP client")
+}
+
+/// Encoded body of a password update request
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RequestBody {
+    Json(serde_json::Value),
+    Raw { body: String, content_type: String },
+}
+
+impl RequestBody {
+    fn form(body: String) -> Self {
+        RequestBody::Raw {
+            body,
+            content_type: "application/x-www-form-urlencoded".to_string(),
+        }
+    }
+
+    fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            RequestBody::Json(body) => request.json(body),
+            RequestBody::Raw { body, content_type } => request
+                .header(reqwest::header::CONTENT_TYPE, content_type)
+                .body(body.clone()),
+        }
+    }
+}
+
+/// Substitute `{{username}}` and `{{password}}`, encoding each value with `encode`
+///
+/// Substituted values are never scanned again, so a password containing
+/// `{{username}}` is sent literally.
+fn render_template(
+    template: &str,
+    username: &str,
+    password: &str,
+    encode: impl Fn(&str) -> String,
+) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("{{username}}") {
+            rendered.push_str(&encode(username));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{{password}}") {
+            rendered.push_str(&encode(password));
+            rest = after;
+        } else {
+            rendered.push_str("{{");
+            rest = &rest[2..];
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Upper bound on any single wait between attempts
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Rate limiting and server-side errors that are worth retrying
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Parse a `Retry-After` header given either as seconds or as an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value)
+        .ok()?
+        .with_timezone(&Utc);
+    Some((at - Utc::now()).to_std().unwrap_or(Duration::ZERO))
 }
 
 #[async_trait::async_trait]
@@ -54,23 +271,7 @@ impl Target for ApiTarget {
         let url = self.build_url(username);
         debug!("Calling API endpoint: {}", url);
 
-        // Build request body based on config
-        let mut body = json!({});
-
-        // Set username field
-        if let Some(ref username_field) = self.config.username_field {
-            body[username_field] = json!(username);
-        }
-
-        // Set password field
-        body[&self.config.password_field] = json!(new_password);
-
-        // Add any additional fields from config
-        if let Some(ref additional_fields) = self.config.additional_fields {
-            for (key, value) in additional_fields {
-                body[key] = json!(value);
-            }
-        }
+        let body = self.render_body(username, new_password)?;
 
         // Parse HTTP method
         let method = match self.config.method.to_uppercase().as_str() {
@@ -82,30 +283,80 @@ impl Target for ApiTarget {
             _ => reqwest::Method::POST,
         };
 
-        // Build request
-        let mut request = self.client.request(method, &url).json(&body);
-
-        // Add authentication headers if configured
-        if let Some(ref auth_header) = self.config.auth_header {
-            request = request.header("Authorization", auth_header);
-        }
+        let mut attempt = 0;
+        let mut token_refreshed = false;
+        let response = loop {
+            // Build request
+            let mut request = body.apply(self.client.request(method.clone(), &url));
+
+            // Add authentication headers if configured
+            if let Some(ref oauth2) = self.oauth2 {
+                let token = oauth2.token().await?;
+                request = request.bearer_auth(token.expose_secret());
+            } else if let Some(ref auth_header) = self.config.auth_header {
+                request = request.header("Authorization", auth_header.expose_secret());
+            }
 
-        // Add custom headers if configured
-        if let Some(ref headers) = self.config.headers {
-            for (key, value) in headers {
-                request = request.header(key, value);
+            // Add custom headers if configured
+            if let Some(ref headers) = self.config.headers {
+                for (key, value) in headers {
+                    request = request.header(key, value);
+                }
             }
-        }
 
-        // Send request
-        let response = request.send().await.context("Failed to send API request")?;
+            let retries_left = attempt < self.config.max_retries;
+            attempt += 1;
+            match request.send().await {
+                // The token may have been revoked before its advertised expiry
+                Ok(response)
+                    if response.status() == StatusCode::UNAUTHORIZED
+                        && !token_refreshed
+                        && self.oauth2.is_some() =>
+                {
+                    warn!("API rejected the OAuth2 token; requesting a new one");
+                    if let Some(ref oauth2) = self.oauth2 {
+                        oauth2.invalidate().await;
+                    }
+                    token_refreshed = true;
+                    attempt -= 1;
+                }
+                Ok(response) if retries_left && is_transient(response.status()) => {
+                    let delay = self.retry_delay(attempt, Some(response.headers()));
+                    warn!(
+                        "API returned {}; retrying in {:?} (attempt {})",
+                        response.status(),
+                        delay,
+                        attempt + 1
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) if retries_left && (e.is_timeout() || e.is_connect()) => {
+                    let delay = self.retry_delay(attempt, None);
+                    warn!(
+                        "{}; retrying in {:?} (attempt {})",
+                        redact::redact(&e.to_string()),
+                        delay,
+                        attempt + 1
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                // reqwest errors include the URL, which may carry credentials
+                result => {
+                    break result
+                        .map_err(|e| anyhow::anyhow!(redact::redact(&e.to_string())))
+                        .context("Failed to send API request")?
+                }
+            }
+        };
 
         // Check response status
         let status = response.status();
         if !status.is_success() {
+            // Error bodies sometimes echo the request, including the new password
             let error_text = response
                 .text()
                 .await
+                .map(|text| redact::redact(&text.replace(new_password, redact::REDACTED)))
                 .unwrap_or_else(|_| "Unknown error".to_string());
             anyhow::bail!("API request failed with status {}: {}", status, error_text);
         }
@@ -129,6 +380,37 @@ impl Target for ApiTarget {
         Ok(())
     }
 
+    /// Fetch an OAuth2 token if configured, then make sure the base URL answers
+    ///
+    /// Any HTTP response counts, since the base URL itself need not be an
+    /// endpoint.
+    async fn check(&self) -> Result<Option<String>> {
+        let mut detail = String::new();
+        if let Some(ref oauth2) = self.oauth2 {
+            oauth2
+                .token()
+                .await
+                .context("Failed to obtain OAuth2 token")?;
+            detail.push_str("OAuth2 token obtained; ");
+        }
+        let response = self
+            .client
+            .head(&self.config.base_url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", self.config.base_url))?;
+        detail.push_str(&format!(
+            "{} answered with HTTP {}",
+            self.config.base_url,
+            response.status().as_u16()
+        ));
+        Ok(Some(detail))
+    }
+
+    fn password_policy(&self) -> Option<&PasswordPolicy> {
+        self.config.password_policy.as_ref()
+    }
+
     fn target_type(&self) -> &'static str {
         "api"
     }
@@ -137,7 +419,8 @@ impl Target for ApiTarget {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::ApiTargetConfig;
+    use crate::config::{ApiBodyFormat, ApiTargetConfig};
+    use axum::response::IntoResponse;
 
     #[test]
     fn test_build_url_with_placeholder() {
@@ -148,9 +431,19 @@ mod tests {
             password_field: "password".to_string(),
             username_field: Some("username".to_string()),
             additional_fields: None,
+            body_format: ApiBodyFormat::Json,
+            body_template: None,
+            content_type: None,
             auth_header: None,
+            oauth2: None,
             headers: None,
             timeout_seconds: 30,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            max_retries: 3,
+            retry_delay_ms: 500,
+            password_policy: None,
         };
 
         let rt = tokio::runtime::Runtime::new().unwrap();
@@ -169,9 +462,19 @@ mod tests {
             password_field: "password".to_string(),
             username_field: None,
             additional_fields: None,
+            body_format: ApiBodyFormat::Json,
+            body_template: None,
+            content_type: None,
             auth_header: None,
+            oauth2: None,
             headers: None,
             timeout_seconds: 30,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            max_retries: 3,
+            retry_delay_ms: 500,
+            password_policy: None,
         };
 
         let rt = tokio::runtime::Runtime::new().unwrap();
@@ -190,9 +493,19 @@ mod tests {
             password_field: "password".to_string(),
             username_field: None,
             additional_fields: None,
+            body_format: ApiBodyFormat::Json,
+            body_template: None,
+            content_type: None,
             auth_header: None,
+            oauth2: None,
             headers: None,
             timeout_seconds: 30,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            max_retries: 3,
+            retry_delay_ms: 500,
+            password_policy: None,
         };
 
         let rt = tokio::runtime::Runtime::new().unwrap();
@@ -211,9 +524,19 @@ mod tests {
             password_field: "password".to_string(),
             username_field: None,
             additional_fields: None,
+            body_format: ApiBodyFormat::Json,
+            body_template: None,
+            content_type: None,
             auth_header: None,
+            oauth2: None,
             headers: None,
             timeout_seconds: 30,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            max_retries: 3,
+            retry_delay_ms: 500,
+            password_policy: None,
         };
 
         let rt = tokio::runtime::Runtime::new().unwrap();
@@ -222,4 +545,151 @@ mod tests {
         let url = target.build_url("testuser");
         assert_eq!(url, "https://api.example.com/password");
     }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        // Dates in the past mean "retry now"
+        headers.insert(
+            RETRY_AFTER,
+            "Sun, 06 Nov 1994 08:49:37 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, "soon".parse().unwrap());
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        use axum::http::StatusCode;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = axum::Router::new().route(
+            "/password",
+            axum::routing::post(move || {
+                let counter = counter.clone();
+                async move {
+                    match counter.fetch_add(1, Ordering::SeqCst) {
+                        0 => {
+                            (StatusCode::TOO_MANY_REQUESTS, [("retry-after", "0")]).into_response()
+                        }
+                        1 => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                        _ => StatusCode::NO_CONTENT.into_response(),
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut config: ApiTargetConfig = toml::from_str(&format!(
+            r#"
+            base_url = "{}"
+            endpoint = "/password"
+            retry_delay_ms = 1
+            "#,
+            base_url
+        ))
+        .unwrap();
+        let target = ApiTarget::new(&config).await.unwrap();
+        target.update_password("app", "new").await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Out of retries, the last transient error is returned
+        calls.store(0, Ordering::SeqCst);
+        config.max_retries = 1;
+        let target = ApiTarget::new(&config).await.unwrap();
+        let err = target.update_password("app", "new").await.unwrap_err();
+        assert!(err.to_string().contains("503"), "{}", err);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    fn template_target(format: ApiBodyFormat, template: Option<&str>) -> Result<ApiTarget> {
+        let mut config: ApiTargetConfig = toml::from_str(
+            r#"
+            base_url = "https://api.example.com"
+            endpoint = "/password"
+            username_field = "user"
+            "#,
+        )
+        .unwrap();
+        config.body_format = format;
+        config.body_template = template.map(str::to_string);
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(ApiTarget::new(&config))
+    }
+
+    #[test]
+    fn test_render_body_template() {
+        let target = template_target(
+            ApiBodyFormat::Json,
+            Some(
+                r#"{"user": {"name": "{{username}}", "credentials": {"secret": "{{password}}"}}}"#,
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            target.render_body("app", r#"p"w\{{username}}"#).unwrap(),
+            RequestBody::Json(json!({
+                "user": {"name": "app", "credentials": {"secret": r#"p"w\{{username}}"#}}
+            }))
+        );
+
+        let target = template_target(
+            ApiBodyFormat::Form,
+            Some("login={{username}}&pw={{password}}"),
+        )
+        .unwrap();
+        assert_eq!(
+            target.render_body("app", "a&b =c").unwrap(),
+            RequestBody::form("login=app&pw=a%26b+%3Dc".to_string())
+        );
+
+        let target = template_target(ApiBodyFormat::Form, None).unwrap();
+        assert_eq!(
+            target.render_body("app", "a&b").unwrap(),
+            RequestBody::form("user=app&password=a%26b".to_string())
+        );
+
+        let target =
+            template_target(ApiBodyFormat::Raw, Some("{{username}}:{{password}}")).unwrap();
+        assert_eq!(
+            target.render_body("app", "pw").unwrap(),
+            RequestBody::Raw {
+                body: "app:pw".to_string(),
+                content_type: "text/plain".to_string(),
+            }
+        );
+
+        assert!(template_target(ApiBodyFormat::Raw, None).is_err());
+        assert!(template_target(ApiBodyFormat::Json, Some("{\"a\": {{password}}}")).is_err());
+    }
+
+    #[test]
+    fn test_tls_options() {
+        let mut config: ApiTargetConfig = toml::from_str(
+            r#"
+            base_url = "https://api.example.com"
+            endpoint = "/password"
+            client_cert = "/etc/asr/client.pem"
+            "#,
+        )
+        .unwrap();
+        let err = build_client(&config).unwrap_err();
+        assert!(err.to_string().contains("set together"), "{}", err);
+
+        config.client_cert = None;
+        config.ca_cert = Some("/nonexistent/ca.pem".into());
+        let err = build_client(&config).unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/ca.pem"), "{}", err);
+    }
 }
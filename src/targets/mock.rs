@@ -0,0 +1,158 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::target::Target;
+use crate::password_policy::PasswordPolicy;
+
+#[derive(Default)]
+struct State {
+    passwords: HashMap<String, String>,
+    updates: usize,
+    fail_update: Option<String>,
+    fail_verify: Option<String>,
+    paired_logins: bool,
+    active_logins: HashMap<String, String>,
+}
+
+/// Target that records password updates in memory, for testing rotation flows
+///
+/// `verify_connection` succeeds only for the password last set for a user, so
+/// a rotation that verifies against the wrong value is caught.
+#[derive(Default)]
+pub struct MockTarget {
+    state: Mutex<State>,
+    /// Secret path and username of the admin credential
+    admin: Option<(String, String)>,
+    policy: Option<PasswordPolicy>,
+}
+
+impl MockTarget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Alternate between `<user>_a` and `<user>_b` logins like a blue/green target
+    pub fn with_paired_logins(self) -> Self {
+        self.lock().paired_logins = true;
+        self
+    }
+
+    /// Connect as `username` with the password stored at `path`, like a
+    /// target configured with `password_path`
+    pub fn with_admin_credential(mut self, path: &str, username: &str) -> Self {
+        self.admin = Some((path.to_string(), username.to_string()));
+        self
+    }
+
+    /// Reject passwords that break `policy`, like a target configured with one
+    pub fn with_password_policy(mut self, policy: PasswordPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Login last activated for `username`
+    pub fn active_login(&self, username: &str) -> Option<String> {
+        self.lock().active_logins.get(username).cloned()
+    }
+
+    /// Password currently set for `username`
+    pub fn password(&self, username: &str) -> Option<String> {
+        self.lock().passwords.get(username).cloned()
+    }
+
+    /// Number of successful password updates
+    pub fn updates(&self) -> usize {
+        self.lock().updates
+    }
+
+    /// Make password updates fail with `message` (or succeed again with `None`)
+    pub fn fail_update(&self, message: Option<&str>) {
+        self.lock().fail_update = message.map(str::to_string);
+    }
+
+    /// Make verification fail with `message` (or succeed again with `None`)
+    pub fn fail_verify(&self, message: Option<&str>) {
+        self.lock().fail_verify = message.map(str::to_string);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait::async_trait]
+impl Target for MockTarget {
+    async fn update_password(&self, username: &str, new_password: &str) -> Result<()> {
+        let mut state = self.lock();
+        if let Some(ref message) = state.fail_update {
+            anyhow::bail!("{}", message);
+        }
+        if let Some(ref policy) = self.policy {
+            policy.check(new_password, &[])?;
+        }
+        state
+            .passwords
+            .insert(username.to_string(), new_password.to_string());
+        state.updates += 1;
+        Ok(())
+    }
+
+    async fn verify_connection(
+        &self,
+        username: &str,
+        password: &str,
+        _database: Option<&str>,
+    ) -> Result<()> {
+        let state = self.lock();
+        if let Some(ref message) = state.fail_verify {
+            anyhow::bail!("{}", message);
+        }
+        match state.passwords.get(username) {
+            Some(current) if current == password => Ok(()),
+            _ => anyhow::bail!("Authentication failed for user {}", username),
+        }
+    }
+
+    async fn next_login(&self, username: &str, current: Option<&str>) -> Result<String> {
+        if !self.lock().paired_logins {
+            return Ok(username.to_string());
+        }
+        let blue = format!("{}_a", username);
+        if current == Some(blue.as_str()) {
+            Ok(format!("{}_b", username))
+        } else {
+            Ok(blue)
+        }
+    }
+
+    async fn activate_login(&self, username: &str, login: &str) -> Result<()> {
+        self.lock()
+            .active_logins
+            .insert(username.to_string(), login.to_string());
+        Ok(())
+    }
+
+    fn password_policy(&self) -> Option<&PasswordPolicy> {
+        self.policy.as_ref()
+    }
+
+    fn admin_credential(&self) -> Option<(&str, &str)> {
+        let (path, username) = self.admin.as_ref()?;
+        Some((path.as_str(), username.as_str()))
+    }
+
+    /// Succeeds only with the password last set for the admin user
+    async fn reload_admin_credential(&self, password: &str) -> Result<()> {
+        let Some((_, ref username)) = self.admin else {
+            anyhow::bail!("No admin credential configured");
+        };
+        self.verify_connection(username, password, None).await
+    }
+
+    fn target_type(&self) -> &'static str {
+        "mock"
+    }
+}
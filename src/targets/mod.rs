@@ -5,14 +5,25 @@
 //! - Databases (PostgreSQL, MySQL, etc.)
 //! - APIs (REST APIs that manage user passwords)
 //! - Applications (LDAP, Active Directory, etc.)
+//! - Mail relays (SMTP accounts)
+//! - VPNs (WireGuard peer keys)
 
 mod api;
+#[cfg(any(test, feature = "test-util"))]
+mod mock;
+mod oauth2;
 mod postgres;
+mod smtp;
 mod target;
+mod wireguard;
 
 pub use api::ApiTarget;
+#[cfg(any(test, feature = "test-util"))]
+pub use mock::MockTarget;
 pub use postgres::PostgresTarget;
+pub use smtp::SmtpTarget;
 pub use target::Target;
+pub use wireguard::WireGuardTarget;
 
 /// Target type enumeration
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -20,6 +31,8 @@ pub use target::Target;
 pub enum TargetType {
     Postgres,
     Api,
+    Smtp,
+    WireGuard,
 }
 
 impl std::str::FromStr for TargetType {
@@ -29,8 +42,10 @@ impl std::str::FromStr for TargetType {
         match s.to_lowercase().as_str() {
             "postgres" | "postgresql" => Ok(TargetType::Postgres),
             "api" => Ok(TargetType::Api),
+            "smtp" => Ok(TargetType::Smtp),
+            "wireguard" | "wg" => Ok(TargetType::WireGuard),
             _ => Err(format!(
-                "Unknown target type: {}. Supported: postgres, api",
+                "Unknown target type: {}. Supported: postgres, api, smtp, wireguard",
                 s
             )),
         }
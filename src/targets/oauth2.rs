@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::config::OAuth2Config;
+use crate::redact::{self, SecretString};
+
+/// Tokens are refreshed this long before the issuer says they expire
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// Lifetime assumed when the token endpoint omits `expires_in`
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+struct CachedToken {
+    token: SecretString,
+    refresh_at: Instant,
+}
+
+/// Bearer tokens from an OAuth2 client-credentials grant, cached until
+/// shortly before they expire
+pub(crate) struct TokenSource {
+    config: OAuth2Config,
+    client: Client,
+    cached: Mutex<Option<CachedToken>>,
+}
+
+impl TokenSource {
+    pub(crate) fn new(config: &OAuth2Config, client: Client) -> Self {
+        Self {
+            config: config.clone(),
+            client,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Current access token, requesting a new one if none is cached or the
+    /// cached one is about to expire
+    pub(crate) async fn token(&self) -> Result<SecretString> {
+        let mut cached = self.cached.lock().await;
+        if let Some(ref token) = *cached {
+            if Instant::now() < token.refresh_at {
+                return Ok(token.token.clone());
+            }
+        }
+
+        let fresh = self.fetch().await?;
+        let token = fresh.token.clone();
+        *cached = Some(fresh);
+        Ok(token)
+    }
+
+    /// Drop the cached token, e.g. after the API rejected it
+    pub(crate) async fn invalidate(&self) {
+        *self.cached.lock().await = None;
+    }
+
+    async fn fetch(&self) -> Result<CachedToken> {
+        debug!("Requesting OAuth2 token from {}", self.config.token_url);
+
+        let mut form = vec![("grant_type", "client_credentials".to_string())];
+        if !self.config.scopes.is_empty() {
+            form.push(("scope", self.config.scopes.join(" ")));
+        }
+        if let Some(ref audience) = self.config.audience {
+            form.push(("audience", audience.clone()));
+        }
+
+        let response = self
+            .client
+            .post(&self.config.token_url)
+            .basic_auth(
+                &self.config.client_id,
+                Some(self.config.client_secret.expose_secret()),
+            )
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!(redact::redact(&e.to_string())))
+            .context("Failed to request OAuth2 token")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map(|text| redact::redact(&text))
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            anyhow::bail!(
+                "OAuth2 token request failed with status {}: {}",
+                status,
+                error_text
+            );
+        }
+
+        let token: TokenResponse = response
+            .json()
+            .await
+            .context("Invalid OAuth2 token response")?;
+        redact::register_secret(&token.access_token);
+
+        let lifetime = token
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_LIFETIME);
+        Ok(CachedToken {
+            token: SecretString::new(token.access_token),
+            refresh_at: Instant::now() + lifetime.saturating_sub(EXPIRY_MARGIN),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::{Form, Json};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_token_is_cached_until_invalidated() {
+        let issued = Arc::new(AtomicUsize::new(0));
+        let app = axum::Router::new()
+            .route(
+                "/token",
+                axum::routing::post(
+                    |State(issued): State<Arc<AtomicUsize>>,
+                     headers: HeaderMap,
+                     Form(form): Form<HashMap<String, String>>| async move {
+                        assert!(headers.contains_key("authorization"));
+                        assert_eq!(form["grant_type"], "client_credentials");
+                        assert_eq!(form["scope"], "users:write users:read");
+                        let n = issued.fetch_add(1, Ordering::SeqCst);
+                        Json(serde_json::json!({
+                            "access_token": format!("token-{}", n),
+                            "token_type": "Bearer",
+                            "expires_in": 3600,
+                        }))
+                    },
+                ),
+            )
+            .with_state(issued.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let token_url = format!("http://{}/token", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config: OAuth2Config = toml::from_str(&format!(
+            r#"
+            token_url = "{}"
+            client_id = "asr"
+            client_secret = "shh"
+            scopes = ["users:write", "users:read"]
+            "#,
+            token_url
+        ))
+        .unwrap();
+        let source = TokenSource::new(&config, Client::new());
+
+        assert_eq!(source.token().await.unwrap().expose_secret(), "token-0");
+        assert_eq!(source.token().await.unwrap().expose_secret(), "token-0");
+        source.invalidate().await;
+        assert_eq!(source.token().await.unwrap().expose_secret(), "token-1");
+        assert_eq!(issued.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,57 +1,105 @@
 use anyhow::{Context, Result};
-use std::sync::Arc;
+use chrono::{Duration, SecondsFormat, Utc};
+use std::sync::{Arc, RwLock};
+use tokio::sync::Mutex;
 use tokio_postgres::{Client, NoTls};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::config::PostgresTargetConfig;
+use crate::password_policy::PasswordPolicy;
+use crate::redact::{self, SecretString};
 use crate::targets::target::Target;
 
 /// PostgreSQL database target for password updates
+///
+/// The admin connection is opened on first use rather than when the target is
+/// created, so commands that never touch the database do not depend on it. A
+/// connection that has been closed (server restart, idle timeout) is replaced
+/// on the next use.
 pub struct PostgresTarget {
     config: Arc<PostgresTargetConfig>,
-    admin_client: Client,
+    /// Replaced when the admin credential itself is rotated
+    admin_password: RwLock<SecretString>,
+    admin_client: Mutex<Option<Arc<Client>>>,
 }
 
 impl PostgresTarget {
-    /// Create a new PostgresTarget with admin credentials
-    pub async fn new(config: &PostgresTargetConfig, admin_password: &str) -> Result<Self> {
+    /// Create a new PostgresTarget with admin credentials (does not connect yet)
+    pub fn new(config: &PostgresTargetConfig, admin_password: &SecretString) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            admin_password: RwLock::new(admin_password.clone()),
+            admin_client: Mutex::new(None),
+        }
+    }
+
+    /// Open a new admin connection
+    async fn connect(&self) -> Result<Client> {
         info!(
             "Connecting to PostgreSQL at {}:{}",
-            config.host, config.port
+            self.config.host, self.config.port
         );
 
+        let admin_password = self
+            .admin_password
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
         let connection_string = Self::build_connection_string(
-            &config.host,
-            config.port,
-            &config.username,
-            admin_password,
-            &config.database,
-            &config.ssl_mode,
+            &self.config.host,
+            self.config.port,
+            &self.config.username,
+            admin_password.expose_secret(),
+            &self.config.database,
+            &self.config.ssl_mode,
         );
 
-        let (client, connection) = tokio_postgres::connect(&connection_string, NoTls)
-            .await
-            .context("Failed to connect to PostgreSQL")?;
+        let (client, connection) =
+            tokio_postgres::connect(connection_string.expose_secret(), NoTls)
+                .await
+                .context("Failed to connect to PostgreSQL")?;
 
         // Spawn connection handler
         let _connection_handle = tokio::spawn(async move {
             if let Err(e) = connection.await {
-                eprintln!("PostgreSQL connection error: {}", e);
+                eprintln!(
+                    "PostgreSQL connection error: {}",
+                    redact::redact(&e.to_string())
+                );
             }
         });
 
-        // Test the connection
-        client
-            .query_one("SELECT version()", &[])
-            .await
-            .context("Failed to verify PostgreSQL connection")?;
-
         info!("Successfully connected to PostgreSQL");
+        Ok(client)
+    }
 
-        Ok(Self {
-            config: Arc::new(config.clone()),
-            admin_client: client,
-        })
+    /// The admin connection, connecting or reconnecting as needed
+    async fn admin(&self) -> Result<Arc<Client>> {
+        let mut client = self.admin_client.lock().await;
+        match *client {
+            Some(ref existing) if !existing.is_closed() => Ok(existing.clone()),
+            _ => {
+                if client.is_some() {
+                    warn!("PostgreSQL admin connection was closed; reconnecting");
+                }
+                let connected = Arc::new(self.connect().await?);
+                *client = Some(connected.clone());
+                Ok(connected)
+            }
+        }
+    }
+
+    /// Run statements on the admin connection, retrying once on a fresh
+    /// connection if the current one turns out to be closed
+    async fn admin_execute(&self, statement: &str) -> Result<()> {
+        let client = self.admin().await?;
+        match client.batch_execute(statement).await {
+            Err(_) if client.is_closed() => {
+                self.admin().await?.batch_execute(statement).await?;
+                Ok(())
+            }
+            result => Ok(result?),
+        }
     }
 
     /// Build PostgreSQL connection string
@@ -62,11 +110,46 @@ impl PostgresTarget {
         password: &str,
         database: &str,
         ssl_mode: &str,
-    ) -> String {
-        format!(
+    ) -> SecretString {
+        SecretString::new(format!(
             "host={} port={} user={} password={} dbname={} sslmode={}",
             host, port, username, password, database, ssl_mode
-        )
+        ))
+    }
+
+    /// Create a blue/green login role inheriting from `group` if it does not exist yet
+    async fn ensure_login_role(&self, group: &str, login: &str) -> Result<()> {
+        let exists = self
+            .admin()
+            .await?
+            .query_opt("SELECT 1 FROM pg_roles WHERE rolname = $1", &[&login])
+            .await
+            .context("Failed to look up PostgreSQL role")?
+            .is_some();
+        if exists {
+            return Ok(());
+        }
+
+        info!("Creating PostgreSQL login role {} in role {}", login, group);
+        self.admin_execute(&format!(
+            "CREATE ROLE {} WITH LOGIN INHERIT IN ROLE {}",
+            Self::quote_identifier(login),
+            Self::quote_identifier(group)
+        ))
+        .await
+        .with_context(|| format!("Failed to create PostgreSQL role {}", login))?;
+        Ok(())
+    }
+
+    /// Password as sent in `ALTER USER`: a SCRAM-SHA-256 verifier computed
+    /// locally when `scram_prehash` is set, otherwise the escaped plaintext
+    fn password_literal(&self, password: &str) -> String {
+        if self.config.scram_prehash {
+            // Verifiers contain no characters that need escaping
+            postgres_protocol::password::scram_sha_256(password.as_bytes())
+        } else {
+            password.replace("'", "''")
+        }
     }
 
     /// Quote PostgreSQL identifier to prevent SQL injection
@@ -77,25 +160,31 @@ impl PostgresTarget {
     }
 }
 
+/// The blue/green login paired with `current`, starting with `<username>_a`
+fn next_blue_green_login(username: &str, current: Option<&str>) -> String {
+    let blue = format!("{}_a", username);
+    if current == Some(blue.as_str()) {
+        format!("{}_b", username)
+    } else {
+        blue
+    }
+}
+
 #[async_trait::async_trait]
 impl Target for PostgresTarget {
     async fn update_password(&self, username: &str, new_password: &str) -> Result<()> {
         info!("Updating password for PostgreSQL user: {}", username);
 
-        // Escape single quotes in password
-        let escaped_password = new_password.replace("'", "''");
-
         // Use ALTER USER to change password
         let query = format!(
             "ALTER USER {} WITH PASSWORD '{}'",
             Self::quote_identifier(username),
-            escaped_password
+            self.password_literal(new_password)
         );
 
         debug!("Executing: ALTER USER {} WITH PASSWORD '***'", username);
 
-        self.admin_client
-            .execute(&query, &[])
+        self.admin_execute(&query)
             .await
             .context("Failed to update PostgreSQL password")?;
 
@@ -122,9 +211,10 @@ impl Target for PostgresTarget {
         );
 
         // Try to connect with new credentials
-        let (test_client, test_connection) = tokio_postgres::connect(&connection_string, NoTls)
-            .await
-            .context("Failed to verify new password - connection failed")?;
+        let (test_client, test_connection) =
+            tokio_postgres::connect(connection_string.expose_secret(), NoTls)
+                .await
+                .context("Failed to verify new password - connection failed")?;
 
         // Test with a simple query
         test_client
@@ -139,6 +229,111 @@ impl Target for PostgresTarget {
         Ok(())
     }
 
+    async fn next_login(&self, username: &str, current: Option<&str>) -> Result<String> {
+        if !self.config.blue_green {
+            return Ok(username.to_string());
+        }
+
+        let login = next_blue_green_login(username, current);
+        self.ensure_login_role(username, &login).await?;
+        Ok(login)
+    }
+
+    async fn activate_login(&self, username: &str, login: &str) -> Result<()> {
+        if login == username {
+            return Ok(());
+        }
+
+        // The shared role holds the grants; membership gives them to the login.
+        // A login retired by an earlier switch may still carry an expiry.
+        info!("Granting PostgreSQL role {} to {}", username, login);
+        self.admin_execute(&format!(
+            "GRANT {} TO {}; ALTER ROLE {} VALID UNTIL 'infinity'",
+            Self::quote_identifier(username),
+            Self::quote_identifier(login),
+            Self::quote_identifier(login)
+        ))
+        .await
+        .with_context(|| format!("Failed to grant role {} to {}", username, login))?;
+
+        // The previous login keeps working while applications switch over,
+        // then its password expires
+        let previous = next_blue_green_login(username, Some(login));
+        let exists = self
+            .admin()
+            .await?
+            .query_opt("SELECT 1 FROM pg_roles WHERE rolname = $1", &[&previous])
+            .await
+            .context("Failed to look up PostgreSQL role")?
+            .is_some();
+        if exists {
+            let until = Utc::now() + Duration::hours(i64::from(self.config.previous_login_hours));
+            info!(
+                "Password of PostgreSQL login {} expires at {}",
+                previous,
+                until.to_rfc3339()
+            );
+            self.admin_execute(&format!(
+                "ALTER ROLE {} VALID UNTIL '{}'",
+                Self::quote_identifier(&previous),
+                until.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ))
+            .await
+            .with_context(|| format!("Failed to expire previous login {}", previous))?;
+        }
+        Ok(())
+    }
+
+    /// Connect as the admin user and make sure it may change passwords
+    async fn check(&self) -> Result<Option<String>> {
+        let admin = self.admin().await?;
+        let row = admin
+            .query_one(
+                "SELECT rolsuper OR rolcreaterole FROM pg_roles WHERE rolname = current_user",
+                &[],
+            )
+            .await
+            .context("Failed to look up the admin role")?;
+        let can_alter_roles: bool = row.get(0);
+        if !can_alter_roles {
+            anyhow::bail!(
+                "{} can connect but lacks CREATEROLE, so it cannot change passwords",
+                self.config.username
+            );
+        }
+        Ok(Some(format!(
+            "connected to {}:{}/{} as {}",
+            self.config.host, self.config.port, self.config.database, self.config.username
+        )))
+    }
+
+    fn password_policy(&self) -> Option<&PasswordPolicy> {
+        self.config.password_policy.as_ref()
+    }
+
+    fn admin_credential(&self) -> Option<(&str, &str)> {
+        let path = self.config.password_path.as_deref()?;
+        Some((path, self.config.username.as_str()))
+    }
+
+    /// Replace the admin password and open a new admin connection with it
+    ///
+    /// The old connection stays authenticated after `ALTER USER`, but any
+    /// reconnect would fail with the old password.
+    async fn reload_admin_credential(&self, password: &str) -> Result<()> {
+        redact::register_secret(password);
+        *self
+            .admin_password
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = SecretString::new(password);
+        *self.admin_client.lock().await = None;
+        self.admin()
+            .await
+            .context("Failed to reconnect to PostgreSQL with the rotated admin password")?;
+        info!("Reconnected to PostgreSQL with the rotated admin password");
+        Ok(())
+    }
+
     fn target_type(&self) -> &'static str {
         "postgres"
     }
@@ -160,6 +355,53 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_connects_lazily() {
+        let config: PostgresTargetConfig = toml::from_str(
+            r#"
+            host = "127.0.0.1"
+            port = 1
+            database = "postgres"
+            username = "admin"
+            "#,
+        )
+        .unwrap();
+
+        // Creating the target must not require a reachable server
+        let target = PostgresTarget::new(&config, &SecretString::from("pw"));
+        let err = target.update_password("app", "new").await.unwrap_err();
+        assert!(format!("{:#}", err).contains("Failed to connect to PostgreSQL"));
+    }
+
+    #[test]
+    fn test_password_literal() {
+        let mut config: PostgresTargetConfig = toml::from_str(
+            r#"
+            host = "localhost"
+            database = "postgres"
+            username = "admin"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.previous_login_hours, 24);
+        let target = PostgresTarget::new(&config, &SecretString::from("pw"));
+        assert_eq!(target.password_literal("it's"), "it''s");
+
+        config.scram_prehash = true;
+        let target = PostgresTarget::new(&config, &SecretString::from("pw"));
+        let verifier = target.password_literal("it's");
+        assert!(verifier.starts_with("SCRAM-SHA-256$4096:"));
+        assert!(!verifier.contains("it's"));
+    }
+
+    #[test]
+    fn test_next_blue_green_login() {
+        assert_eq!(next_blue_green_login("app", None), "app_a");
+        assert_eq!(next_blue_green_login("app", Some("app_a")), "app_b");
+        assert_eq!(next_blue_green_login("app", Some("app_b")), "app_a");
+        assert_eq!(next_blue_green_login("app", Some("app")), "app_a");
+    }
+
     #[test]
     fn test_build_connection_string() {
         let conn_str = PostgresTarget::build_connection_string(
@@ -170,6 +412,8 @@ mod tests {
             "postgres",
             "prefer",
         );
+        assert_eq!(format!("{:?}", conn_str), "SecretString([REDACTED])");
+        let conn_str = conn_str.expose_secret();
         assert!(conn_str.contains("host=localhost"));
         assert!(conn_str.contains("port=5432"));
         assert!(conn_str.contains("user=postgres"));
@@ -0,0 +1,384 @@
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_native_tls::{native_tls, TlsConnector};
+use tracing::{debug, info};
+
+use crate::config::{SmtpTargetConfig, SmtpTls};
+use crate::password_policy::PasswordPolicy;
+use crate::targets::target::Target;
+use crate::targets::ApiTarget;
+
+/// Name the client introduces itself with in EHLO
+const EHLO_NAME: &str = "localhost";
+
+/// SMTP relay account target
+///
+/// The password is changed through the provider's API (`[targets.smtp.update]`,
+/// configured like the API target). Verification then logs in to the relay
+/// with the new credentials (EHLO, STARTTLS, AUTH) and, when configured,
+/// sends a test message, so a rotation that the relay did not pick up fails
+/// instead of silently breaking outgoing mail.
+pub struct SmtpTarget {
+    config: SmtpTargetConfig,
+    api: ApiTarget,
+}
+
+impl SmtpTarget {
+    pub async fn new(config: &SmtpTargetConfig) -> Result<Self> {
+        info!("Creating SMTP target for: {}:{}", config.host, config.port);
+        let api = ApiTarget::new(&config.update)
+            .await
+            .context("Invalid [targets.smtp.update] configuration")?;
+        Ok(Self {
+            config: config.clone(),
+            api,
+        })
+    }
+
+    fn address(&self) -> String {
+        format!("{}:{}", self.config.host, self.config.port)
+    }
+
+    /// Connect and greet the server, upgrading to TLS as configured
+    ///
+    /// Returns the session and the extensions listed in the EHLO reply.
+    async fn connect(&self) -> Result<(Session, Vec<String>)> {
+        let tcp = TcpStream::connect((self.config.host.as_str(), self.config.port))
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.address()))?;
+        let mut session = match self.config.tls {
+            SmtpTls::Implicit => Session::new(Box::new(self.tls(tcp).await?)),
+            SmtpTls::StartTls | SmtpTls::None => Session::new(Box::new(tcp)),
+        };
+        session.reply(220).await.context("Unexpected greeting")?;
+        let mut extensions = session.command(&format!("EHLO {}", EHLO_NAME), 250).await?;
+
+        if self.config.tls == SmtpTls::StartTls {
+            if !has_extension(&extensions, "STARTTLS") {
+                anyhow::bail!(
+                    "{} does not offer STARTTLS; set tls = \"implicit\" or \"none\"",
+                    self.address()
+                );
+            }
+            session.command("STARTTLS", 220).await?;
+            session = Session::new(Box::new(self.tls(session.into_inner()).await?));
+            extensions = session.command(&format!("EHLO {}", EHLO_NAME), 250).await?;
+        }
+        Ok((session, extensions))
+    }
+
+    async fn tls<S>(&self, stream: S) -> Result<tokio_native_tls::TlsStream<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let connector = TlsConnector::from(
+            native_tls::TlsConnector::new().context("Failed to create TLS connector")?,
+        );
+        connector
+            .connect(&self.config.host, stream)
+            .await
+            .with_context(|| format!("TLS handshake with {} failed", self.address()))
+    }
+
+    /// Log in as `username` and optionally send the test message
+    async fn login(&self, username: &str, password: &str) -> Result<()> {
+        let (mut session, extensions) = self.connect().await?;
+        let mechanisms = auth_mechanisms(&extensions);
+
+        if mechanisms.iter().any(|m| m == "PLAIN") {
+            let credentials = STANDARD.encode(format!("\0{}\0{}", username, password));
+            session
+                .command(&format!("AUTH PLAIN {}", credentials), 235)
+                .await
+        } else if mechanisms.iter().any(|m| m == "LOGIN") {
+            session.command("AUTH LOGIN", 334).await?;
+            session.command(&STANDARD.encode(username), 334).await?;
+            session.command(&STANDARD.encode(password), 235).await
+        } else {
+            anyhow::bail!(
+                "{} offers no supported AUTH mechanism (PLAIN or LOGIN)",
+                self.address()
+            );
+        }
+        .with_context(|| {
+            format!(
+                "{} rejected the credentials of {}",
+                self.address(),
+                username
+            )
+        })?;
+        info!("Logged in to {} as {}", self.address(), username);
+
+        if let Some(ref message) = self.config.test_message {
+            session
+                .command(&format!("MAIL FROM:<{}>", message.from), 250)
+                .await?;
+            session
+                .command(&format!("RCPT TO:<{}>", message.to), 250)
+                .await?;
+            session.command("DATA", 354).await?;
+            let body = format!(
+                "From: <{}>\r\nTo: <{}>\r\nSubject: {}\r\nDate: {}\r\n\r\n\
+                 Sent by asr to verify the rotated credentials of {}.\r\n.",
+                message.from,
+                message.to,
+                message.subject,
+                Utc::now().to_rfc2822(),
+                username
+            );
+            session
+                .command(&body, 250)
+                .await
+                .context("Relay did not accept the test message")?;
+            info!("Sent test message to {} as {}", message.to, username);
+        }
+
+        // The check is done; a failed QUIT does not matter
+        let _ = session.command("QUIT", 221).await;
+        Ok(())
+    }
+}
+
+/// An SMTP conversation over a plain or TLS stream
+struct Session {
+    stream: BufReader<Box<dyn Stream>>,
+}
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Stream for S {}
+
+impl Session {
+    fn new(stream: Box<dyn Stream>) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    fn into_inner(self) -> Box<dyn Stream> {
+        self.stream.into_inner()
+    }
+
+    /// Send a command line and expect a reply with `code`, returning its lines
+    ///
+    /// Commands are not logged since they may carry credentials.
+    async fn command(&mut self, line: &str, code: u16) -> Result<Vec<String>> {
+        self.stream
+            .get_mut()
+            .write_all(format!("{}\r\n", line).as_bytes())
+            .await
+            .context("Failed to send SMTP command")?;
+        self.reply(code).await
+    }
+
+    /// Read a (possibly multiline) reply and check its code
+    async fn reply(&mut self, code: u16) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                anyhow::bail!("Server closed the connection");
+            }
+            let line = line.trim_end();
+            debug!("SMTP < {}", line);
+            let (received, rest) = line.split_at_checked(3).unwrap_or((line, ""));
+            let received: u16 = received
+                .parse()
+                .with_context(|| format!("Malformed SMTP reply: {}", line))?;
+            lines.push(rest.get(1..).unwrap_or("").to_string());
+            if !rest.starts_with('-') {
+                if received != code {
+                    anyhow::bail!("Server replied {} {}", received, lines.join(" "));
+                }
+                return Ok(lines);
+            }
+        }
+    }
+}
+
+fn has_extension(extensions: &[String], name: &str) -> bool {
+    extensions
+        .iter()
+        .any(|line| line.split_whitespace().next() == Some(name))
+}
+
+/// Mechanisms listed in the `AUTH` line of an EHLO reply
+fn auth_mechanisms(extensions: &[String]) -> Vec<String> {
+    extensions
+        .iter()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            (words.next()? == "AUTH").then(|| words.map(str::to_uppercase).collect::<Vec<_>>())
+        })
+        .flatten()
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl Target for SmtpTarget {
+    async fn update_password(&self, username: &str, new_password: &str) -> Result<()> {
+        self.api.update_password(username, new_password).await
+    }
+
+    /// Log in to the relay with the credentials (and send the test message)
+    async fn verify_connection(
+        &self,
+        username: &str,
+        password: &str,
+        _database: Option<&str>,
+    ) -> Result<()> {
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        tokio::time::timeout(timeout, self.login(username, password))
+            .await
+            .with_context(|| {
+                format!(
+                    "SMTP verification with {} timed out after {}s",
+                    self.address(),
+                    timeout.as_secs()
+                )
+            })?
+    }
+
+    /// Check the update API, then greet the relay without logging in
+    async fn check(&self) -> Result<Option<String>> {
+        let api = self.api.check().await?;
+        let (mut session, extensions) = self.connect().await?;
+        let _ = session.command("QUIT", 221).await;
+        let mut detail = format!(
+            "{} offers AUTH {}",
+            self.address(),
+            auth_mechanisms(&extensions).join(" ")
+        );
+        if let Some(api) = api {
+            detail = format!("{}; {}", api, detail);
+        }
+        Ok(Some(detail))
+    }
+
+    fn password_policy(&self) -> Option<&PasswordPolicy> {
+        self.api.password_policy()
+    }
+
+    fn target_type(&self) -> &'static str {
+        "smtp"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    /// Minimal relay accepting `user`/`secret` with AUTH PLAIN, recording messages
+    async fn fake_relay(messages: Arc<Mutex<Vec<String>>>) -> Result<u16> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let messages = messages.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut lines = BufReader::new(read).lines();
+                    write.write_all(b"220 fake ESMTP\r\n").await.unwrap();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let reply = if line.starts_with("EHLO") {
+                            "250-fake\r\n250 AUTH LOGIN PLAIN".to_string()
+                        } else if let Some(credentials) = line.strip_prefix("AUTH PLAIN ") {
+                            if STANDARD.decode(credentials).unwrap() == b"\0user\0secret" {
+                                "235 2.7.0 Authentication successful".to_string()
+                            } else {
+                                "535 5.7.8 Authentication failed".to_string()
+                            }
+                        } else if line == "DATA" {
+                            write.write_all(b"354 go ahead\r\n").await.unwrap();
+                            let mut message = String::new();
+                            while let Ok(Some(line)) = lines.next_line().await {
+                                if line == "." {
+                                    break;
+                                }
+                                message.push_str(&line);
+                                message.push('\n');
+                            }
+                            messages.lock().unwrap().push(message);
+                            "250 queued".to_string()
+                        } else if line == "QUIT" {
+                            "221 bye".to_string()
+                        } else {
+                            "250 ok".to_string()
+                        };
+                        write
+                            .write_all(format!("{}\r\n", reply).as_bytes())
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        Ok(port)
+    }
+
+    #[tokio::test]
+    async fn test_update_and_verify() -> Result<()> {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let port = fake_relay(messages.clone()).await?;
+        let mut api = mockito::Server::new_async().await;
+        let update = api
+            .mock("POST", "/accounts/user/password")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({ "password": "secret" }),
+            ))
+            .create_async()
+            .await;
+
+        let config: SmtpTargetConfig = toml::from_str(&format!(
+            r#"
+            host = "127.0.0.1"
+            port = {}
+            tls = "none"
+            [update]
+            base_url = "{}"
+            endpoint = "/accounts/{{username}}/password"
+            [test_message]
+            from = "asr@example.com"
+            to = "postmaster@example.com"
+            "#,
+            port,
+            api.url()
+        ))?;
+        let target = SmtpTarget::new(&config).await?;
+
+        target.update_password("user", "secret").await?;
+        update.assert_async().await;
+
+        target.verify_connection("user", "secret", None).await?;
+        let messages = messages.lock().unwrap().clone();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("Subject: asr credential check"));
+        assert!(!messages[0].contains("secret"));
+
+        let err = target
+            .verify_connection("user", "stale", None)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("rejected the credentials"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_auth_mechanisms() {
+        let extensions = vec![
+            "smtp.example.com".to_string(),
+            "STARTTLS".to_string(),
+            "AUTH login Plain XOAUTH2".to_string(),
+        ];
+        assert_eq!(auth_mechanisms(&extensions), ["LOGIN", "PLAIN", "XOAUTH2"]);
+        assert!(has_extension(&extensions, "STARTTLS"));
+        assert!(!has_extension(&extensions, "SMTPUTF8"));
+    }
+}
@@ -1,5 +1,7 @@
 use anyhow::Result;
 
+use crate::password_policy::PasswordPolicy;
+
 /// Trait for password update targets (databases, APIs, applications, etc.)
 #[async_trait::async_trait]
 pub trait Target: Send + Sync {
@@ -14,6 +16,64 @@ pub trait Target: Send + Sync {
         database: Option<&str>,
     ) -> Result<()>;
 
+    /// Whether verifying a new password needs it to be the backend's current
+    /// value, e.g. because clients fetch it from the backend before they can
+    /// connect with it
+    ///
+    /// Rotations refuse such targets on backends that stage new values.
+    fn needs_current_secret(&self) -> bool {
+        false
+    }
+
+    /// Login that the next password should be set on
+    ///
+    /// Targets that alternate between paired logins (blue/green roles) return
+    /// the one not in use; `current` is the login the secret currently holds.
+    /// By default the password of `username` itself is changed.
+    async fn next_login(&self, username: &str, current: Option<&str>) -> Result<String> {
+        let _ = current;
+        Ok(username.to_string())
+    }
+
+    /// Make a verified login the active one for `username` (e.g., grant it the shared role)
+    async fn activate_login(&self, username: &str, login: &str) -> Result<()> {
+        let _ = (username, login);
+        Ok(())
+    }
+
+    /// Check that the target is reachable with its configured credentials,
+    /// without changing anything (used by `asr doctor`)
+    ///
+    /// Returns a short description of what was checked, or `None` if the
+    /// target cannot be checked this way.
+    async fn check(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Rules new passwords must satisfy, checked before they are sent
+    fn password_policy(&self) -> Option<&PasswordPolicy> {
+        None
+    }
+
+    /// Backend path and username of the admin credential the target connects
+    /// with, when it is read from the backend (e.g., `password_path`)
+    ///
+    /// Rotating that secret changes the target's own credential, so it is
+    /// rotated after every other due secret and followed by
+    /// [`Target::reload_admin_credential`].
+    fn admin_credential(&self) -> Option<(&str, &str)> {
+        None
+    }
+
+    /// Switch to a newly rotated admin credential, reconnecting with it
+    async fn reload_admin_credential(&self, password: &str) -> Result<()> {
+        let _ = password;
+        anyhow::bail!(
+            "The {} target cannot switch admin credentials",
+            self.target_type()
+        )
+    }
+
     /// Get the target type name for display purposes
     fn target_type(&self) -> &'static str;
 }
@@ -0,0 +1,424 @@
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use openssl::pkey::{Id, PKey};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::config::{WireGuardPeerConfig, WireGuardTargetConfig};
+use crate::targets::target::Target;
+
+/// How recent a handshake must be to count; WireGuard re-handshakes every
+/// two minutes while a tunnel is in use
+const HANDSHAKE_FRESHNESS_SECS: i64 = 180;
+
+/// How often to check for a handshake while waiting
+const HANDSHAKE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// WireGuard VPN target rotating peer keys
+///
+/// The "password" is the peer's private key (use `generator_style =
+/// wireguard`) and the username names a peer in `[targets.wireguard.peers]`.
+/// A rotation adds the new public key to the server interface as a second
+/// peer without allowed IPs, which is enough for it to handshake. Once the
+/// peer has picked up its new key from the backend and handshaked, its
+/// allowed IPs move to the new key and the old peer entry is removed. Until
+/// then the old key keeps working. A peer that does not handshake within the
+/// timeout is switched to the new key anyway, since the backend already
+/// holds it; backends that stage rotated values are refused for that reason.
+///
+/// `wg` runs locally or, with `ssh`, on the VPN server.
+pub struct WireGuardTarget {
+    config: WireGuardTargetConfig,
+    /// Program and arguments that commands are prefixed with (e.g., `ssh host --`)
+    prefix: Vec<String>,
+    /// New public key of each peer between `update_password` and `activate_login`
+    pending: Mutex<HashMap<String, String>>,
+}
+
+impl WireGuardTarget {
+    pub fn new(config: &WireGuardTargetConfig) -> Self {
+        let prefix = match config.ssh {
+            Some(ref host) => vec![
+                "ssh".to_string(),
+                "-o".to_string(),
+                "BatchMode=yes".to_string(),
+                host.clone(),
+                "--".to_string(),
+            ],
+            None => Vec::new(),
+        };
+        Self {
+            config: config.clone(),
+            prefix,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[cfg(test)]
+    fn with_prefix(mut self, prefix: &[&str]) -> Self {
+        self.prefix = prefix.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    fn peer(&self, name: &str) -> Result<&WireGuardPeerConfig> {
+        self.config.peers.get(name).with_context(|| {
+            format!(
+                "Unknown WireGuard peer '{}'; add it to [targets.wireguard.peers]",
+                name
+            )
+        })
+    }
+
+    /// Run `program` with `args` (on the server with `ssh`), returning stdout
+    async fn run(&self, program: &str, args: &[&str]) -> Result<String> {
+        let mut command: Vec<&str> = self.prefix.iter().map(String::as_str).collect();
+        command.push(program);
+        command.extend_from_slice(args);
+        debug!("Running {}", command.join(" "));
+
+        let output = Command::new(command[0])
+            .args(&command[1..])
+            .output()
+            .await
+            .with_context(|| format!("Failed to run {}", command[0]))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "{} {} exited with {}: {}",
+                program,
+                args.first().unwrap_or(&""),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    async fn wg(&self, args: &[&str]) -> Result<String> {
+        self.run("wg", args).await
+    }
+
+    /// Unix time of the latest handshake of `public_key`, if any
+    async fn latest_handshake(&self, public_key: &str) -> Result<Option<i64>> {
+        let output = self
+            .wg(&["show", &self.config.interface, "latest-handshakes"])
+            .await?;
+        Ok(parse_handshakes(&output).get(public_key).copied())
+    }
+}
+
+/// Public key of a base64 WireGuard private key
+pub fn public_key(private_key: &str) -> Result<String> {
+    let bytes = STANDARD
+        .decode(private_key.trim())
+        .ok()
+        .filter(|bytes| bytes.len() == 32)
+        .context(
+            "Not a WireGuard private key (32 bytes, base64); set generator_style = wireguard",
+        )?;
+    let key = PKey::private_key_from_raw_bytes(&bytes, Id::X25519)
+        .context("Invalid WireGuard private key")?;
+    Ok(STANDARD.encode(key.raw_public_key()?))
+}
+
+/// Parse `wg show <interface> latest-handshakes` (public key, Unix time; 0
+/// when there was none)
+fn parse_handshakes(output: &str) -> HashMap<String, i64> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, time) = line.split_once(char::is_whitespace)?;
+            let time: i64 = time.trim().parse().ok()?;
+            (time > 0).then(|| (key.to_string(), time))
+        })
+        .collect()
+}
+
+/// Public keys in `wg show <interface> allowed-ips` that own any of `ips`
+fn owners_of(output: &str, ips: &[String]) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let key = fields.next()?;
+            fields
+                .any(|ip| ips.iter().any(|wanted| wanted == ip))
+                .then(|| key.to_string())
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl Target for WireGuardTarget {
+    /// Add the peer's new public key to the interface, without allowed IPs yet
+    async fn update_password(&self, username: &str, new_password: &str) -> Result<()> {
+        self.peer(username)?;
+        let public = public_key(new_password)?;
+        info!(
+            "Adding new key {} for WireGuard peer {} on {}",
+            public, username, self.config.interface
+        );
+        self.wg(&["set", &self.config.interface, "peer", &public])
+            .await
+            .with_context(|| format!("Failed to add new key of peer {}", username))?;
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(username.to_string(), public);
+        Ok(())
+    }
+
+    /// Peers fetch their new key from the backend before they can handshake
+    fn needs_current_secret(&self) -> bool {
+        true
+    }
+
+    /// Wait for the peer to handshake with the key
+    ///
+    /// Timing out is not an error: the new key is already current in the
+    /// backend, so keeping the old one active would leave the server and the
+    /// backend out of step until the next rotation.
+    async fn verify_connection(
+        &self,
+        username: &str,
+        password: &str,
+        _database: Option<&str>,
+    ) -> Result<()> {
+        let public = public_key(password)?;
+        let timeout = Duration::from_secs(self.config.handshake_timeout_seconds);
+        let deadline = tokio::time::Instant::now() + timeout;
+        info!(
+            "Waiting up to {}s for WireGuard peer {} to handshake with key {}",
+            timeout.as_secs(),
+            username,
+            public
+        );
+        loop {
+            if let Some(time) = self.latest_handshake(&public).await? {
+                if Utc::now().timestamp() - time <= HANDSHAKE_FRESHNESS_SECS {
+                    info!("WireGuard peer {} handshaked with its key", username);
+                    return Ok(());
+                }
+            }
+            if tokio::time::Instant::now() >= deadline {
+                warn!(
+                    "WireGuard peer {} did not handshake with key {} within {}s; \
+                     switching it to the new key, which it gets from the backend",
+                    username,
+                    public,
+                    timeout.as_secs()
+                );
+                return Ok(());
+            }
+            tokio::time::sleep(HANDSHAKE_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Move the peer's allowed IPs to its new key and remove the old entries
+    async fn activate_login(&self, username: &str, login: &str) -> Result<()> {
+        let Some(public) = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(login)
+        else {
+            return Ok(());
+        };
+        let peer = self.peer(username)?;
+        let interface = &self.config.interface;
+
+        let allowed = self.wg(&["show", interface, "allowed-ips"]).await?;
+        let old_keys: Vec<String> = owners_of(&allowed, &peer.allowed_ips)
+            .into_iter()
+            .filter(|key| *key != public)
+            .collect();
+
+        let ips = peer.allowed_ips.join(",");
+        self.wg(&["set", interface, "peer", &public, "allowed-ips", &ips])
+            .await
+            .with_context(|| {
+                format!("Failed to move allowed IPs of {} to its new key", username)
+            })?;
+        for old in &old_keys {
+            info!("Removing old key {} of WireGuard peer {}", old, username);
+            self.wg(&["set", interface, "peer", old, "remove"])
+                .await
+                .with_context(|| format!("Failed to remove old key of peer {}", username))?;
+        }
+
+        if self.config.save_config {
+            if let Err(e) = self.run("wg-quick", &["save", interface]).await {
+                warn!(
+                    "Failed to save {} after rotating {}; the change is lost on restart: {:#}",
+                    interface, username, e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn check(&self) -> Result<Option<String>> {
+        self.wg(&["show", &self.config.interface, "public-key"])
+            .await
+            .with_context(|| format!("Failed to read interface {}", self.config.interface))?;
+        Ok(Some(match self.config.ssh {
+            Some(ref host) => format!("read {} on {} over SSH", self.config.interface, host),
+            None => format!("read {}", self.config.interface),
+        }))
+    }
+
+    fn target_type(&self) -> &'static str {
+        "wireguard"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_public_key() -> Result<()> {
+        // RFC 7748, section 6.1
+        let private = hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let public = hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+        assert_eq!(
+            public_key(&STANDARD.encode(private))?,
+            STANDARD.encode(public)
+        );
+        assert!(public_key("hunter2").is_err());
+        Ok(())
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_wg_output() {
+        let handshakes = "AAA=\t1700000000\nBBB=\t0\n";
+        assert_eq!(
+            parse_handshakes(handshakes),
+            HashMap::from([("AAA=".to_string(), 1700000000)])
+        );
+
+        let allowed = "AAA=\t10.8.0.2/32\nBBB=\t10.8.0.3/32 fd00::3/128\nCCC=\t(none)\n";
+        assert_eq!(owners_of(allowed, &["fd00::3/128".to_string()]), ["BBB="]);
+        assert!(owners_of(allowed, &["10.8.0.9/32".to_string()]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rotation_flow() -> Result<()> {
+        let private = STANDARD.encode([7u8; 32]);
+        let public = public_key(&private)?;
+
+        let dir = TempDir::new()?;
+        let log = dir.path().join("calls");
+        let script = dir.path().join("fake-wg.sh");
+        // Stands in for `wg`/`wg-quick` on the server: logs every call and
+        // reports a fresh handshake of the new key
+        std::fs::write(
+            &script,
+            format!(
+                r#"echo "$@" >> {log}
+case "$4" in
+  latest-handshakes) printf '{public}\t%s\n' "$(date +%s)" ;;
+  allowed-ips) printf 'OLDKEY=\t10.8.0.2/32\nOTHER=\t10.8.0.3/32\n' ;;
+esac
+"#,
+                log = log.display(),
+                public = public
+            ),
+        )?;
+
+        let config: WireGuardTargetConfig = toml::from_str(
+            r#"
+            interface = "wg0"
+            handshake_timeout_seconds = 1
+            [peers.laptop]
+            allowed_ips = ["10.8.0.2/32"]
+            "#,
+        )?;
+        assert!(config.save_config);
+        let target = WireGuardTarget::new(&config).with_prefix(&["sh", script.to_str().unwrap()]);
+
+        assert!(target.update_password("desktop", &private).await.is_err());
+        target.update_password("laptop", &private).await?;
+        target.verify_connection("laptop", &private, None).await?;
+        target.activate_login("laptop", "laptop").await?;
+
+        let calls = std::fs::read_to_string(&log)?;
+        let calls: Vec<&str> = calls.lines().collect();
+        assert_eq!(
+            calls,
+            [
+                format!("wg set wg0 peer {}", public),
+                "wg show wg0 latest-handshakes".to_string(),
+                "wg show wg0 allowed-ips".to_string(),
+                format!("wg set wg0 peer {} allowed-ips 10.8.0.2/32", public),
+                "wg set wg0 peer OLDKEY= remove".to_string(),
+                "wg-quick save wg0".to_string(),
+            ]
+        );
+
+        // A peer that never handshakes still moves to its new key
+        std::fs::remove_file(&log)?;
+        let silent = STANDARD.encode([8u8; 32]);
+        let silent_public = public_key(&silent)?;
+        let target = WireGuardTarget::new(&WireGuardTargetConfig {
+            handshake_timeout_seconds: 0,
+            save_config: false,
+            ..config
+        })
+        .with_prefix(&["sh", script.to_str().unwrap()]);
+        target.update_password("laptop", &silent).await?;
+        target.verify_connection("laptop", &silent, None).await?;
+        target.activate_login("laptop", "laptop").await?;
+        let calls = std::fs::read_to_string(&log)?;
+        assert!(calls.contains(&format!(
+            "wg set wg0 peer {} allowed-ips 10.8.0.2/32",
+            silent_public
+        )));
+
+        // Staged values never reach the peer, so they are refused up front
+        std::fs::remove_file(&log)?;
+        let backend = crate::backends::MemoryBackend::new()
+            .with_staging()
+            .with_secret("vpn/laptop", [("private_key", private.as_str())]);
+        let err = crate::rotation::rotate_secret_with_target(
+            &backend,
+            "vpn/laptop",
+            32,
+            Some(&target),
+            Some("laptop"),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("stages rotated values"));
+
+        // Including behind a metadata store
+        let inner: std::sync::Arc<dyn crate::backends::SecretBackend> =
+            std::sync::Arc::new(backend);
+        let store = crate::metadata_store::SidecarStore::new(inner.clone(), ".asr-metadata");
+        let backend = crate::metadata_store::MetadataStoreBackend::new(inner, Box::new(store));
+        let err = crate::rotation::rotate_secret_with_target(
+            &backend,
+            "vpn/laptop",
+            32,
+            Some(&target),
+            Some("laptop"),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("stages rotated values"));
+        assert!(!log.exists());
+        Ok(())
+    }
+}
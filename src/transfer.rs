@@ -0,0 +1,452 @@
+//! Bulk import and export of secrets
+//!
+//! Import onboards existing projects whose credentials live in dotenv files;
+//! export snapshots a subtree for local development or backups.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use tracing::{debug, info};
+
+use crate::backends::SecretBackend;
+use crate::dotenv::DotenvEntry;
+use crate::rotation;
+
+/// Key under which single-value imported secrets are stored
+pub const IMPORTED_VALUE_KEY: &str = "value";
+
+/// Placeholder written instead of secret values when redacting
+pub const REDACTED_VALUE: &str = "REDACTED";
+
+/// Secrets collected for export, keyed by full path then by data key
+pub type ExportedSecrets = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Output format for exported secrets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    Dotenv,
+    Json,
+    Yaml,
+}
+
+/// How imported variables are laid out in the backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportLayout {
+    /// One secret per variable at `<prefix><VAR>`, stored under the `value` key
+    PerVariable,
+    /// A single secret at `<prefix>` holding every variable as a key
+    Single,
+}
+
+/// Options controlling how dotenv entries are imported
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub prefix: String,
+    pub layout: ImportLayout,
+    /// Rotation period to flag imported secrets with (`None` skips flagging)
+    pub flag_period_months: Option<u32>,
+    /// Days either way to spread the first due date of flagged secrets by
+    pub flag_jitter_days: u32,
+}
+
+/// Join a path prefix and a name with exactly one separator
+pub fn join_path(prefix: &str, name: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// Group dotenv entries into secret paths and their data, without writing anything
+///
+/// Later definitions of the same variable win, matching dotenv loader behavior.
+pub fn plan_import(
+    entries: &[DotenvEntry],
+    options: &ImportOptions,
+) -> Result<Vec<(String, HashMap<String, String>)>> {
+    match options.layout {
+        ImportLayout::Single => {
+            let path = options.prefix.trim_end_matches('/');
+            if path.is_empty() {
+                anyhow::bail!(
+                    "A non-empty --prefix is required when importing into a single secret"
+                );
+            }
+
+            let data: HashMap<String, String> = entries
+                .iter()
+                .map(|e| (e.key.clone(), e.value.clone()))
+                .collect();
+            Ok(vec![(path.to_string(), data)])
+        }
+        ImportLayout::PerVariable => {
+            let mut by_path: Vec<(String, HashMap<String, String>)> = Vec::new();
+            for entry in entries {
+                let path = join_path(&options.prefix, &entry.key);
+                let mut data = HashMap::new();
+                data.insert(IMPORTED_VALUE_KEY.to_string(), entry.value.clone());
+
+                match by_path.iter_mut().find(|(p, _)| *p == path) {
+                    Some(existing) => existing.1 = data,
+                    None => by_path.push((path, data)),
+                }
+            }
+            Ok(by_path)
+        }
+    }
+}
+
+/// Write dotenv entries into the backend, flagging each secret for rotation if requested
+///
+/// Returns the list of secret paths that were written.
+pub async fn import_entries(
+    backend: &dyn SecretBackend,
+    entries: &[DotenvEntry],
+    options: &ImportOptions,
+) -> Result<Vec<String>> {
+    let planned = plan_import(entries, options)?;
+    let mut written = Vec::with_capacity(planned.len());
+
+    for (path, data) in planned {
+        info!(
+            "Importing {} key(s) into {} ({})",
+            data.len(),
+            path,
+            backend.backend_type()
+        );
+
+        backend
+            .write_secret(&path, data)
+            .await
+            .with_context(|| format!("Failed to write imported secret: {}", path))?;
+
+        if let Some(period) = options.flag_period_months {
+            let period = rotation::RotationPeriod::Months(period.into());
+            rotation::flag_for_rotation_every(backend, &path, period, options.flag_jitter_days)
+                .await
+                .with_context(|| format!("Failed to flag imported secret: {}", path))?;
+        }
+
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// Resolve a name returned by `list_secrets` into a full secret path
+///
+/// Backends differ in whether listed names are relative to the listed path
+/// (Vault, AWS) or to the backend root (file backend).
+pub(crate) fn child_path(base: &str, name: &str) -> String {
+    let base = base.trim_end_matches('/');
+    if base.is_empty() || name.starts_with(&format!("{}/", base)) {
+        name.to_string()
+    } else {
+        join_path(base, name)
+    }
+}
+
+/// Read every secret under `path` (recursively), optionally redacting values
+pub async fn collect_secrets(
+    backend: &dyn SecretBackend,
+    path: &str,
+    redact: bool,
+) -> Result<ExportedSecrets> {
+    let mut secrets = ExportedSecrets::new();
+    let mut pending = vec![path.trim_end_matches('/').to_string()];
+
+    while let Some(dir) = pending.pop() {
+        let names = backend
+            .list_secrets(&dir)
+            .await
+            .with_context(|| format!("Failed to list secrets at {}", dir))?;
+
+        for name in names {
+            let full_path = child_path(&dir, &name);
+
+            // Vault-style listings mark sub-directories with a trailing slash
+            if full_path.ends_with('/') {
+                pending.push(full_path.trim_end_matches('/').to_string());
+                continue;
+            }
+
+            debug!("Exporting secret: {}", full_path);
+            let secret = backend
+                .read_secret(&full_path)
+                .await
+                .with_context(|| format!("Failed to read secret: {}", full_path))?;
+
+            let data = secret
+                .data
+                .into_iter()
+                .map(|(k, v)| {
+                    let value = if redact {
+                        REDACTED_VALUE.to_string()
+                    } else {
+                        v
+                    };
+                    (k, value)
+                })
+                .collect();
+            secrets.insert(full_path, data);
+        }
+    }
+
+    Ok(secrets)
+}
+
+/// Output format for the fields of a single secret, as printed by `asr read`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FieldFormat {
+    /// `export KEY='value'` lines, ready to `eval` in a shell
+    Env,
+    Json,
+    Yaml,
+}
+
+/// Convert a secret path and key into an environment variable name
+///
+/// The path is taken relative to `base`; secrets holding a single imported
+/// `value` key map to the path alone so imports and exports round-trip.
+pub fn env_var_name(base: &str, path: &str, key: &str, single_value: bool) -> String {
+    let base = base.trim_end_matches('/');
+    // Only strip whole segments: base `myapp` must not strip `myapp2/...`
+    let relative = path
+        .strip_prefix(base)
+        .filter(|p| base.is_empty() || p.is_empty() || p.starts_with('/'))
+        .map(|p| p.trim_start_matches('/'))
+        .filter(|p| !p.is_empty())
+        .unwrap_or(path);
+
+    let raw = if single_value && key == IMPORTED_VALUE_KEY {
+        relative.to_string()
+    } else {
+        format!("{}_{}", relative, key)
+    };
+
+    var_name(&raw)
+}
+
+/// Turn an arbitrary string into an upper-case environment variable name
+pub fn var_name(raw: &str) -> String {
+    raw.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Render collected secrets in the requested format
+pub fn render_export(
+    secrets: &ExportedSecrets,
+    base: &str,
+    format: ExportFormat,
+) -> Result<String> {
+    match format {
+        ExportFormat::Dotenv => {
+            let mut output = String::from("# Exported by asr\n");
+            for (path, data) in secrets {
+                let single_value = data.len() == 1;
+                for (key, value) in data {
+                    let var = env_var_name(base, path, key, single_value);
+                    output.push_str(&crate::dotenv::format_line(&var, value));
+                    output.push('\n');
+                }
+            }
+            Ok(output)
+        }
+        ExportFormat::Json => {
+            let mut json = serde_json::to_string_pretty(secrets)
+                .context("Failed to serialize secrets as JSON")?;
+            json.push('\n');
+            Ok(json)
+        }
+        ExportFormat::Yaml => {
+            serde_yaml::to_string(secrets).context("Failed to serialize secrets as YAML")
+        }
+    }
+}
+
+/// Render the fields of one secret in the requested format
+pub fn render_fields(data: &BTreeMap<String, String>, format: FieldFormat) -> Result<String> {
+    match format {
+        FieldFormat::Env => Ok(data
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "{}\n",
+                    crate::dotenv::format_export_line(&var_name(key), value)
+                )
+            })
+            .collect()),
+        FieldFormat::Json => {
+            let mut json =
+                serde_json::to_string_pretty(data).context("Failed to serialize secret as JSON")?;
+            json.push('\n');
+            Ok(json)
+        }
+        FieldFormat::Yaml => {
+            serde_yaml::to_string(data).context("Failed to serialize secret as YAML")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::FileBackend;
+    use crate::dotenv;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_join_path() {
+        assert_eq!(join_path("myapp/", "DB_PASSWORD"), "myapp/DB_PASSWORD");
+        assert_eq!(join_path("myapp", "DB_PASSWORD"), "myapp/DB_PASSWORD");
+        assert_eq!(join_path("", "DB_PASSWORD"), "DB_PASSWORD");
+    }
+
+    #[test]
+    fn test_plan_single_requires_prefix() -> Result<()> {
+        let entries = dotenv::parse("A=1\n")?;
+        let options = ImportOptions {
+            prefix: String::new(),
+            layout: ImportLayout::Single,
+            flag_period_months: None,
+            flag_jitter_days: 0,
+        };
+        assert!(plan_import(&entries, &options).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_per_variable() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?;
+        let entries = dotenv::parse("DB_PASSWORD=hunter2\nAPI_TOKEN=abc\nAPI_TOKEN=def\n")?;
+
+        let options = ImportOptions {
+            prefix: "myapp/".to_string(),
+            layout: ImportLayout::PerVariable,
+            flag_period_months: Some(3),
+            flag_jitter_days: 0,
+        };
+        let written = import_entries(&backend, &entries, &options).await?;
+        assert_eq!(written, vec!["myapp/DB_PASSWORD", "myapp/API_TOKEN"]);
+
+        let secret = backend.read_secret("myapp/API_TOKEN").await?;
+        assert_eq!(secret.data.get("value"), Some(&"def".to_string()));
+
+        let metadata = backend.read_metadata("myapp/DB_PASSWORD").await?;
+        assert_eq!(
+            metadata.get("rotation_period_months"),
+            Some(&"3".to_string())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_single_secret() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?;
+        let entries = dotenv::parse("DB_PASSWORD=hunter2\nAPI_TOKEN=abc\n")?;
+
+        let options = ImportOptions {
+            prefix: "myapp/config".to_string(),
+            layout: ImportLayout::Single,
+            flag_period_months: None,
+            flag_jitter_days: 0,
+        };
+        import_entries(&backend, &entries, &options).await?;
+
+        let secret = backend.read_secret("myapp/config").await?;
+        assert_eq!(secret.data.len(), 2);
+        assert!(backend.read_metadata("myapp/config").await?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_var_name() {
+        assert_eq!(
+            env_var_name("myapp/", "myapp/DB_PASSWORD", "value", true),
+            "DB_PASSWORD"
+        );
+        assert_eq!(
+            env_var_name("myapp", "myapp/api-keys", "token", false),
+            "API_KEYS_TOKEN"
+        );
+        assert_eq!(env_var_name("", "db", "password", false), "DB_PASSWORD");
+        assert_eq!(
+            env_var_name("myapp", "myapp2/db", "password", false),
+            "MYAPP2_DB_PASSWORD"
+        );
+    }
+
+    #[test]
+    fn test_render_fields() -> Result<()> {
+        let data = BTreeMap::from([
+            ("username".to_string(), "app".to_string()),
+            ("db-password".to_string(), "it's $HOME".to_string()),
+        ]);
+
+        let env = render_fields(&data, FieldFormat::Env)?;
+        assert_eq!(
+            env,
+            "export DB_PASSWORD='it'\\''s $HOME'\nexport USERNAME='app'\n"
+        );
+
+        let json: BTreeMap<String, String> =
+            serde_json::from_str(&render_fields(&data, FieldFormat::Json)?)?;
+        assert_eq!(json, data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_roundtrip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?;
+        let entries = dotenv::parse("DB_PASSWORD=hunter2\nAPI_TOKEN=\"a b\"\n")?;
+        let options = ImportOptions {
+            prefix: "myapp/".to_string(),
+            layout: ImportLayout::PerVariable,
+            flag_period_months: None,
+            flag_jitter_days: 0,
+        };
+        import_entries(&backend, &entries, &options).await?;
+
+        let secrets = collect_secrets(&backend, "myapp/", false).await?;
+        assert_eq!(secrets.len(), 2);
+
+        let rendered = render_export(&secrets, "myapp/", ExportFormat::Dotenv)?;
+        let mut reparsed = dotenv::parse(&rendered)?;
+        reparsed.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(reparsed, {
+            let mut expected = entries.clone();
+            expected.sort_by(|a, b| a.key.cmp(&b.key));
+            expected
+        });
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_redacted_json() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backend = FileBackend::new(temp_dir.path())?;
+        let mut data = HashMap::new();
+        data.insert("password".to_string(), "hunter2".to_string());
+        backend.write_secret("app/db", data).await?;
+
+        let secrets = collect_secrets(&backend, "", true).await?;
+        let rendered = render_export(&secrets, "", ExportFormat::Json)?;
+        assert!(!rendered.contains("hunter2"));
+        assert!(rendered.contains(REDACTED_VALUE));
+
+        let yaml = render_export(&secrets, "", ExportFormat::Yaml)?;
+        assert!(yaml.contains("app/db"));
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+//! Envelope encryption with Vault's transit engine
+//!
+//! Each payload is encrypted locally with a fresh AES-256-GCM data key that
+//! Vault generates from a transit key. Only the data key encrypted by Vault
+//! is stored next to the ciphertext, so the plaintext never rests on disk and
+//! the envelope can live offline (a file backend directory, a backup on tape)
+//! until someone with access to the transit key opens it:
+//!
+//! ```toml
+//! [transit]
+//! mount = "transit"   # default
+//! key = "asr"
+//! ```
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use openssl::symm::{self, Cipher};
+use serde::{Deserialize, Serialize};
+
+use crate::backends::VaultClient;
+
+/// Version of the envelope layout
+const ENVELOPE_FORMAT: u32 = 1;
+
+/// Length of the AES-GCM nonce and authentication tag
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Transit key that envelopes are sealed with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransitConfig {
+    /// Mount path of the transit engine (default: "transit")
+    #[serde(default = "default_transit_mount")]
+    pub mount: String,
+    /// Name of the transit key
+    pub key: String,
+}
+
+fn default_transit_mount() -> String {
+    "transit".to_string()
+}
+
+/// A payload encrypted with a data key, and that key encrypted by Vault
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Envelope {
+    asr_transit: u32,
+    /// Transit engine and key that encrypted the data key
+    mount: String,
+    key: String,
+    data_key: String,
+    nonce: String,
+    /// Ciphertext followed by the GCM tag
+    ciphertext: String,
+}
+
+/// Seals payloads with a transit key
+#[derive(Clone)]
+pub struct Transit {
+    client: VaultClient,
+    config: TransitConfig,
+}
+
+impl Transit {
+    pub fn new(client: VaultClient, config: &TransitConfig) -> Self {
+        Self {
+            client,
+            config: config.clone(),
+        }
+    }
+
+    /// Transit key as `<mount>/<key>`
+    pub fn key_name(&self) -> String {
+        format!("{}/{}", self.config.mount, self.config.key)
+    }
+
+    /// Encrypt `plaintext` into an envelope with a new data key
+    pub async fn seal(&self, plaintext: &[u8]) -> Result<String> {
+        let data_key = self
+            .client
+            .transit_data_key(&self.config.mount, &self.config.key)
+            .await?;
+        let key = STANDARD
+            .decode(&data_key.plaintext)
+            .context("Vault returned an invalid data key")?;
+
+        let mut nonce = [0u8; NONCE_LEN];
+        openssl::rand::rand_bytes(&mut nonce).context("Failed to generate nonce")?;
+        let mut tag = [0u8; TAG_LEN];
+        let mut ciphertext =
+            symm::encrypt_aead(cipher(&key)?, &key, Some(&nonce), &[], plaintext, &mut tag)
+                .context("Failed to encrypt payload")?;
+        ciphertext.extend_from_slice(&tag);
+
+        let envelope = Envelope {
+            asr_transit: ENVELOPE_FORMAT,
+            mount: self.config.mount.clone(),
+            key: self.config.key.clone(),
+            data_key: data_key.ciphertext,
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+        Ok(serde_json::to_string(&envelope)? + "\n")
+    }
+
+    /// Decrypt an envelope written by [`Transit::seal`]
+    pub async fn open(&self, envelope: &str) -> Result<Vec<u8>> {
+        open(&self.client, envelope).await
+    }
+}
+
+/// Whether `content` is an envelope written by [`Transit::seal`]
+pub fn is_envelope(content: &str) -> bool {
+    parse(content).is_ok()
+}
+
+fn parse(content: &str) -> Result<Envelope> {
+    let envelope: Envelope =
+        serde_json::from_str(content.trim()).context("Not a transit envelope")?;
+    if envelope.asr_transit != ENVELOPE_FORMAT {
+        anyhow::bail!(
+            "Transit envelope format {} is not supported (expected {})",
+            envelope.asr_transit,
+            ENVELOPE_FORMAT
+        );
+    }
+    Ok(envelope)
+}
+
+/// Decrypt an envelope with the transit key named in it
+///
+/// Only the Vault connection is needed, so an envelope can be opened on a
+/// host that was never configured with `[transit]`.
+pub async fn open(client: &VaultClient, content: &str) -> Result<Vec<u8>> {
+    let envelope = parse(content)?;
+    let key = client
+        .transit_decrypt(&envelope.mount, &envelope.key, &envelope.data_key)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to unwrap the data key with transit key {}/{}",
+                envelope.mount, envelope.key
+            )
+        })?;
+    let key = STANDARD
+        .decode(key)
+        .context("Vault returned an invalid data key")?;
+    let nonce = STANDARD
+        .decode(&envelope.nonce)
+        .context("Invalid nonce in transit envelope")?;
+    let sealed = STANDARD
+        .decode(&envelope.ciphertext)
+        .context("Invalid ciphertext in transit envelope")?;
+    if sealed.len() < TAG_LEN {
+        anyhow::bail!("Transit envelope is truncated");
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    symm::decrypt_aead(cipher(&key)?, &key, Some(&nonce), &[], ciphertext, tag)
+        .context("Failed to decrypt transit envelope (corrupted or tampered with)")
+}
+
+fn cipher(key: &[u8]) -> Result<Cipher> {
+    match key.len() {
+        32 => Ok(Cipher::aes_256_gcm()),
+        16 => Ok(Cipher::aes_128_gcm()),
+        n => anyhow::bail!("Unsupported data key length {} bytes", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA_KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+
+    #[tokio::test]
+    async fn test_seal_and_open() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let data_key = server
+            .mock("POST", "/v1/transit/datakey/plaintext/asr")
+            .with_body(format!(
+                r#"{{"data": {{"plaintext": "{}", "ciphertext": "vault:v1:wrapped"}}}}"#,
+                DATA_KEY
+            ))
+            .create_async()
+            .await;
+        let decrypt = server
+            .mock("POST", "/v1/transit/decrypt/asr")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({ "ciphertext": "vault:v1:wrapped" }),
+            ))
+            .with_body(format!(r#"{{"data": {{"plaintext": "{}"}}}}"#, DATA_KEY))
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = VaultClient::new(server.url(), "token".into())?;
+        let config: TransitConfig = toml::from_str(r#"key = "asr""#)?;
+        let transit = Transit::new(client.clone(), &config);
+
+        let sealed = transit.seal(b"password:hunter2\n").await?;
+        assert!(is_envelope(&sealed));
+        assert!(!sealed.contains("hunter2"));
+        assert!(!is_envelope("password:hunter2\n"));
+        assert_eq!(transit.open(&sealed).await?, b"password:hunter2\n");
+
+        // Tampering is detected by the GCM tag
+        let mut envelope = parse(&sealed)?;
+        let mut ciphertext = STANDARD.decode(&envelope.ciphertext)?;
+        ciphertext[0] ^= 1;
+        envelope.ciphertext = STANDARD.encode(ciphertext);
+        let tampered = serde_json::to_string(&envelope)?;
+        assert!(open(&client, &tampered).await.is_err());
+
+        data_key.assert_async().await;
+        decrypt.assert_async().await;
+        Ok(())
+    }
+}
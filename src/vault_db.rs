@@ -0,0 +1,263 @@
+//! Vault's database secrets engine
+//!
+//! Static KV secrets are rotated by `asr` itself; credentials issued by the
+//! database engine are rotated by Vault. `asr db` drives the engine so both
+//! kinds are managed with one tool: it rotates the root credential Vault uses
+//! to connect to each database, rotates static roles on demand, and renews
+//! dynamic credential leases before they expire.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::backends::{LeaseInfo, VaultClient};
+use crate::config::VaultDatabaseConfig;
+
+/// What `check_leases` found or did for one lease
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeaseAction {
+    /// Not due for renewal yet
+    Current,
+    /// Due for renewal, but renewal was not requested
+    Expiring,
+    /// Due for renewal, but Vault does not allow renewing it
+    NotRenewable,
+    /// Renewed; the lease now lasts this many seconds
+    Renewed {
+        ttl: i64,
+    },
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct LeaseStatus {
+    pub id: String,
+    /// `None` if the lease could not be looked up or never expires
+    pub expire_time: Option<DateTime<Utc>>,
+    pub action: LeaseAction,
+}
+
+/// Client for one database secrets engine mount
+pub struct DatabaseEngine {
+    client: VaultClient,
+    config: VaultDatabaseConfig,
+}
+
+impl DatabaseEngine {
+    pub fn new(client: VaultClient, config: &VaultDatabaseConfig) -> Self {
+        Self {
+            client,
+            config: config.clone(),
+        }
+    }
+
+    pub fn mount(&self) -> &str {
+        &self.config.mount
+    }
+
+    /// Connections to rotate when none are given
+    pub fn connections(&self) -> &[String] {
+        &self.config.connections
+    }
+
+    /// Static roles to rotate when none are given
+    pub fn static_roles(&self) -> &[String] {
+        &self.config.static_roles
+    }
+
+    /// Lease prefixes to check when none are given
+    pub fn lease_prefixes(&self) -> Vec<String> {
+        if self.config.lease_prefixes.is_empty() {
+            vec![format!("{}/creds/", self.config.mount)]
+        } else {
+            self.config.lease_prefixes.clone()
+        }
+    }
+
+    pub async fn rotate_root(&self, connection: &str) -> Result<()> {
+        self.client
+            .rotate_database_root(&self.config.mount, connection)
+            .await
+    }
+
+    pub async fn rotate_role(&self, role: &str) -> Result<()> {
+        self.client
+            .rotate_database_role(&self.config.mount, role)
+            .await
+    }
+
+    /// Look up every lease under `prefixes`, renewing the ones that expire
+    /// within `renew_within_hours` when `renew` is set
+    ///
+    /// A lease that cannot be looked up or renewed is reported as failed
+    /// without stopping the others.
+    pub async fn check_leases(&self, prefixes: &[String], renew: bool) -> Result<Vec<LeaseStatus>> {
+        let threshold = i64::try_from(self.config.renew_within_hours)
+            .unwrap_or(i64::MAX / 3600)
+            .saturating_mul(3600);
+
+        let mut statuses = Vec::new();
+        for prefix in prefixes {
+            for id in self.client.list_leases(prefix).await? {
+                let status = match self.client.lookup_lease(&id).await {
+                    Ok(lease) => self.check_lease(lease, threshold, renew).await,
+                    Err(e) => {
+                        warn!("Failed to look up lease {}: {:#}", id, e);
+                        LeaseStatus {
+                            id,
+                            expire_time: None,
+                            action: LeaseAction::Failed(format!("{:#}", e)),
+                        }
+                    }
+                };
+                statuses.push(status);
+            }
+        }
+        Ok(statuses)
+    }
+
+    async fn check_lease(&self, lease: LeaseInfo, threshold: i64, renew: bool) -> LeaseStatus {
+        let action = if lease.ttl > threshold {
+            LeaseAction::Current
+        } else if !renew {
+            LeaseAction::Expiring
+        } else if !lease.renewable {
+            LeaseAction::NotRenewable
+        } else {
+            match self
+                .client
+                .renew_lease(&lease.id, self.config.renew_increment.as_deref())
+                .await
+            {
+                Ok(ttl) => LeaseAction::Renewed { ttl },
+                Err(e) => {
+                    warn!("Failed to renew lease {}: {:#}", lease.id, e);
+                    LeaseAction::Failed(format!("{:#}", e))
+                }
+            }
+        };
+        LeaseStatus {
+            id: lease.id,
+            expire_time: lease.expire_time,
+            action,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact::SecretString;
+
+    async fn engine(server: &mockito::ServerGuard, config: &str) -> DatabaseEngine {
+        let config: VaultDatabaseConfig = toml::from_str(config).unwrap();
+        let client = VaultClient::new(server.url(), SecretString::new("token")).unwrap();
+        DatabaseEngine::new(client, &config)
+    }
+
+    #[tokio::test]
+    async fn test_rotate_root() {
+        let mut server = mockito::Server::new_async().await;
+        let rotate = server
+            .mock("POST", "/v1/db/rotate-root/postgres-prod")
+            .match_header("X-Vault-Token", "token")
+            .with_status(204)
+            .create_async()
+            .await;
+        let _missing = server
+            .mock("POST", "/v1/db/rotate-root/missing")
+            .with_status(400)
+            .with_body(r#"{"errors":["unable to find connection"]}"#)
+            .create_async()
+            .await;
+
+        let engine = engine(&server, "mount = \"db\"").await;
+        engine.rotate_root("postgres-prod").await.unwrap();
+        rotate.assert_async().await;
+
+        let err = engine.rotate_root("missing").await.unwrap_err();
+        assert!(format!("{:#}", err).contains("unable to find connection"));
+    }
+
+    #[tokio::test]
+    async fn test_check_leases_renews_expiring() {
+        let mut server = mockito::Server::new_async().await;
+        let _list = server
+            .mock("LIST", "/v1/sys/leases/lookup/database/creds/app/")
+            .with_body(r#"{"data":{"keys":["fresh","stale","pinned"]}}"#)
+            .create_async()
+            .await;
+        for (id, ttl, renewable) in [
+            ("fresh", 172800, true),
+            ("stale", 600, true),
+            ("pinned", 600, false),
+        ] {
+            server
+                .mock("PUT", "/v1/sys/leases/lookup")
+                .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                    "lease_id": format!("database/creds/app/{}", id)
+                })))
+                .with_body(
+                    serde_json::json!({
+                        "data": {
+                            "id": format!("database/creds/app/{}", id),
+                            "expire_time": "2026-01-01T00:00:00.000000-07:00",
+                            "ttl": ttl,
+                            "renewable": renewable,
+                        }
+                    })
+                    .to_string(),
+                )
+                .create_async()
+                .await;
+        }
+        let renew = server
+            .mock("PUT", "/v1/sys/leases/renew")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "lease_id": "database/creds/app/stale",
+                "increment": "24h",
+            })))
+            .with_body(r#"{"lease_id":"database/creds/app/stale","renewable":true,"lease_duration":86400}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let engine = engine(&server, "renew_increment = \"24h\"").await;
+        let prefixes = engine.lease_prefixes();
+        assert_eq!(prefixes, vec!["database/creds/"]);
+
+        let prefixes = vec!["database/creds/app".to_string()];
+        let actions = |statuses: Vec<LeaseStatus>| {
+            statuses
+                .into_iter()
+                .map(|s| (s.id.rsplit('/').next().unwrap().to_string(), s.action))
+                .collect::<Vec<_>>()
+        };
+
+        // Without --renew, expiring leases are only reported
+        let checked = engine.check_leases(&prefixes, false).await.unwrap();
+        assert_eq!(
+            actions(checked),
+            vec![
+                ("fresh".to_string(), LeaseAction::Current),
+                ("pinned".to_string(), LeaseAction::Expiring),
+                ("stale".to_string(), LeaseAction::Expiring),
+            ]
+        );
+
+        let renewed = engine.check_leases(&prefixes, true).await.unwrap();
+        assert_eq!(
+            renewed[0].expire_time.unwrap().to_rfc3339(),
+            "2026-01-01T07:00:00+00:00"
+        );
+        assert_eq!(
+            actions(renewed),
+            vec![
+                ("fresh".to_string(), LeaseAction::Current),
+                ("pinned".to_string(), LeaseAction::NotRenewable),
+                ("stale".to_string(), LeaseAction::Renewed { ttl: 86400 }),
+            ]
+        );
+        renew.assert_async().await;
+    }
+}
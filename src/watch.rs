@@ -0,0 +1,264 @@
+//! Watching a secret for changes (library use)
+//!
+//! A long-running service can pick up a credential rotated by another process
+//! (`asr auto` on a schedule, another replica) without restarting:
+//!
+//! ```no_run
+//! # async fn example(rotator: secret_rotator::Rotator) -> anyhow::Result<()> {
+//! let mut watch = rotator.watch("app/db").await?;
+//! tokio::spawn(async move {
+//!     loop {
+//!         match watch.next().await {
+//!             Ok(event) => println!("{:?}, reloading", event),
+//!             Err(e) => eprintln!("Watch failed: {:#}", e),
+//!         }
+//!     }
+//! });
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`SecretWatch`] owns its backend handle, so it can be moved into a task,
+//! and it is also a [`Stream`] of events for use with stream combinators.
+//!
+//! The secret is read every poll interval and an event is returned when its
+//! version (or, for backends without versions, its value) differs from the
+//! last read. Events never carry the value; read the secret again to get it.
+
+use anyhow::{Context, Result};
+use futures_core::Stream;
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Poll};
+use std::time::Duration;
+
+use crate::backends::{is_listed, SecretBackend};
+
+/// How often a watched secret is read by default
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A change to a watched secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// The secret was written (rotated, restored or created)
+    Changed {
+        path: String,
+        /// New version, for backends that track versions
+        version: Option<u64>,
+    },
+    /// The secret no longer exists
+    Deleted { path: String },
+}
+
+/// What the last read of the secret found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Seen {
+    Present {
+        version: Option<u64>,
+        /// Hash of the value, so no copy of it is kept
+        digest: u64,
+    },
+    Absent,
+}
+
+/// Wait for the next change, handing the poller back with the result
+type PendingPoll = Pin<Box<dyn Future<Output = (Poller, Result<WatchEvent>)> + Send>>;
+
+/// Changes to one secret, returned by [`SecretWatch::next`] (or polled as a
+/// [`Stream`]) as they are seen
+pub struct SecretWatch {
+    path: String,
+    /// Between polls; moved into `pending` while one is in progress
+    poller: Option<Poller>,
+    /// A poll that has not finished yet, resumed by the next call
+    pending: Option<PendingPoll>,
+}
+
+impl SecretWatch {
+    /// Start watching `path` from its current state
+    pub async fn start(backend: Arc<dyn SecretBackend>, path: &str) -> Result<Self> {
+        let mut poller = Poller {
+            backend,
+            path: path.to_string(),
+            interval: DEFAULT_POLL_INTERVAL,
+            seen: Seen::Absent,
+        };
+        poller.seen = poller.observe().await?;
+        Ok(Self {
+            path: path.to_string(),
+            poller: Some(poller),
+            pending: None,
+        })
+    }
+
+    /// Poll every `interval` instead of [`DEFAULT_POLL_INTERVAL`]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        if let Some(poller) = self.poller.as_mut() {
+            poller.interval = interval;
+        }
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Wait for the next change to the secret
+    ///
+    /// A failed read is returned as an error; calling `next` again carries on
+    /// watching. Dropping the returned future does not lose a change: the
+    /// next call picks up where it stopped.
+    pub async fn next(&mut self) -> Result<WatchEvent> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx))
+            .await
+            .expect("a secret watch never ends")
+    }
+}
+
+impl Stream for SecretWatch {
+    type Item = Result<WatchEvent>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let pending = match this.pending.as_mut() {
+            Some(pending) => pending,
+            None => {
+                let poller = this
+                    .poller
+                    .take()
+                    .expect("the poller is back once a poll has finished");
+                this.pending.insert(Box::pin(poller.next()))
+            }
+        };
+        let (poller, event) = ready!(pending.as_mut().poll(cx));
+        this.pending = None;
+        this.poller = Some(poller);
+        Poll::Ready(Some(event))
+    }
+}
+
+/// Reads the secret and compares it with the last read
+struct Poller {
+    backend: Arc<dyn SecretBackend>,
+    path: String,
+    interval: Duration,
+    seen: Seen,
+}
+
+impl Poller {
+    async fn next(mut self) -> (Self, Result<WatchEvent>) {
+        loop {
+            tokio::time::sleep(self.interval).await;
+            let seen = match self.observe().await {
+                Ok(seen) => seen,
+                Err(e) => return (self, Err(e)),
+            };
+            if seen == self.seen {
+                continue;
+            }
+            self.seen = seen;
+            let path = self.path.clone();
+            let event = match seen {
+                Seen::Present { version, .. } => WatchEvent::Changed { path, version },
+                Seen::Absent => WatchEvent::Deleted { path },
+            };
+            return (self, Ok(event));
+        }
+    }
+
+    async fn observe(&self) -> Result<Seen> {
+        match self.backend.read_secret(&self.path).await {
+            Ok(secret) => {
+                let mut entries: Vec<_> = secret.data.iter().collect();
+                entries.sort();
+                let mut hasher = DefaultHasher::new();
+                entries.hash(&mut hasher);
+                Ok(Seen::Present {
+                    version: secret.version,
+                    digest: hasher.finish(),
+                })
+            }
+            // A failed read is only a deletion if the secret is no longer listed
+            Err(e) => match is_listed(self.backend.as_ref(), &self.path).await {
+                Ok(false) => Ok(Seen::Absent),
+                _ => Err(e).with_context(|| format!("Failed to read watched secret {}", self.path)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::MemoryBackend;
+    use crate::rotator::Rotator;
+
+    #[tokio::test]
+    async fn test_watch_sees_rotation() -> Result<()> {
+        let backend = MemoryBackend::new()
+            .with_secret("app/db", [("password", "old")])
+            .with_secret("app/api", [("token", "t")]);
+        let rotator = Rotator::builder().backend(Box::new(backend)).build()?;
+
+        // The watch owns its backend, so it can run in its own task
+        let mut watch = rotator
+            .watch("app/db")
+            .await?
+            .with_interval(Duration::from_millis(10));
+        let watching = tokio::spawn(async move { watch.next().await });
+        rotator
+            .backend()
+            .write_secret("app/api", [("token".into(), "u".into())].into())
+            .await?;
+        rotator.rotate("app/db").await?;
+        assert_eq!(
+            tokio::time::timeout(Duration::from_secs(5), watching).await???,
+            WatchEvent::Changed {
+                path: "app/db".to_string(),
+                version: Some(2)
+            }
+        );
+
+        // Without a change the watch keeps waiting, and a dropped wait is
+        // resumed by the next one
+        let mut watch = rotator
+            .watch("app/api")
+            .await?
+            .with_interval(Duration::from_millis(10));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), watch.next())
+                .await
+                .is_err()
+        );
+        rotator
+            .backend()
+            .write_secret("app/api", [("token".into(), "v".into())].into())
+            .await?;
+        assert!(matches!(
+            watch.next().await?,
+            WatchEvent::Changed {
+                version: Some(3),
+                ..
+            }
+        ));
+
+        // Polled as a stream; a watch can start before the secret exists
+        let mut watch = rotator
+            .watch("app/new")
+            .await?
+            .with_interval(Duration::from_millis(10));
+        rotator
+            .backend()
+            .write_secret("app/new", [("password".into(), "p".into())].into())
+            .await?;
+        let event = std::future::poll_fn(|cx| Pin::new(&mut watch).poll_next(cx)).await;
+        assert!(matches!(event, Some(Ok(WatchEvent::Changed { .. }))));
+        Ok(())
+    }
+}